@@ -20,6 +20,7 @@
 
 extern crate dirs;
 extern crate tempdir;
+extern crate time;
 #[macro_use]
 extern crate failure;
 
@@ -28,6 +29,8 @@ use std::io;
 use std::path::{Path, PathBuf};
 use tempdir::TempDir;
 
+pub mod trace;
+
 /// A `Context` for Sequoia.
 ///
 /// # Example
@@ -64,6 +67,12 @@ pub struct Context {
     network_policy: NetworkPolicy,
     ipc_policy: IPCPolicy,
     ephemeral: bool,
+    overlay: bool,
+    lower_home: Option<PathBuf>,
+    gnupg_home: Option<PathBuf>,
+    system_socket: Option<PathBuf>,
+    sandbox: bool,
+    time_format: TimeFormat,
     cleanup: bool,
 }
 
@@ -75,6 +84,12 @@ impl Clone for Context {
             network_policy: self.network_policy,
             ipc_policy: self.ipc_policy,
             ephemeral: self.ephemeral,
+            overlay: self.overlay,
+            lower_home: self.lower_home.clone(),
+            gnupg_home: self.gnupg_home.clone(),
+            system_socket: self.system_socket.clone(),
+            sandbox: self.sandbox,
+            time_format: self.time_format.clone(),
             cleanup: false, // Prevent cleanup.
         }
     }
@@ -114,6 +129,12 @@ impl Context {
             network_policy: NetworkPolicy::Encrypted,
             ipc_policy: IPCPolicy::Robust,
             ephemeral: false,
+            overlay: false,
+            lower_home: None,
+            gnupg_home: None,
+            system_socket: None,
+            sandbox: false,
+            time_format: Default::default(),
             cleanup: false,
         })
     }
@@ -142,6 +163,54 @@ impl Context {
     pub fn ephemeral(&self) -> bool {
         self.ephemeral
     }
+
+    /// Returns the read-only lower layer of an overlay context.
+    ///
+    /// Overlay contexts write to `home()`, but consult the state in
+    /// this directory for anything not found there.  Returns `None`
+    /// if this is not an overlay context.
+    pub fn lower_home(&self) -> Option<&Path> {
+        self.lower_home.as_ref().map(|p| p.as_path())
+    }
+
+    /// Returns GnuPG's home directory.
+    ///
+    /// If set, Sequoia honors some of GnuPG's configuration found
+    /// there, e.g., the keyserver configured in `gpg.conf` or
+    /// `dirmngr.conf`.  Returns `None` if this is disabled, which is
+    /// the default.
+    pub fn gnupg_home(&self) -> Option<&Path> {
+        self.gnupg_home.as_ref().map(|p| p.as_path())
+    }
+
+    /// Returns the socket of the system-wide services.
+    ///
+    /// If set, services like the public key store are not started on
+    /// demand, but provided by a system daemon listening on this
+    /// socket.  The daemon serves many users, and isolates them by
+    /// their user id.  Returns `None` if this is disabled, which is
+    /// the default.
+    pub fn system_socket(&self) -> Option<&Path> {
+        self.system_socket.as_ref().map(|p| p.as_path())
+    }
+
+    /// Returns whether backend servers sandbox themselves.
+    ///
+    /// If set, external backend servers and system daemons restrict
+    /// themselves using the platform's sandboxing mechanism once they
+    /// have opened their databases and sockets.  Currently, this is
+    /// seccomp-bpf on Linux, and pledge(2) and unveil(2) on OpenBSD.
+    /// Servers running in a thread of the application are never
+    /// sandboxed.  Returns `false` if this is disabled, which is the
+    /// default.
+    pub fn sandbox(&self) -> bool {
+        self.sandbox
+    }
+
+    /// Returns how timestamps are presented to the user.
+    pub fn time_format(&self) -> &TimeFormat {
+        &self.time_format
+    }
 }
 
 /// Represents a `Context` configuration.
@@ -177,6 +246,26 @@ impl Context {
 /// # Ok(())
 /// # }
 /// ```
+///
+/// Dropping the context that created an ephemeral home also shuts
+/// down any background servers using it.
+///
+/// Overlay contexts are ephemeral contexts that see the state of an
+/// existing home, but never modify it.  This is useful to preview
+/// the effect of an operation:
+///
+/// ```
+/// # use sequoia_core::{Context, Result};
+/// # f().unwrap();
+/// # fn f() -> Result<()> {
+/// # let real_home = std::env::temp_dir();
+/// let c = Context::configure().home(&real_home).overlay().build()?;
+/// assert!(c.ephemeral());
+/// assert_eq!(c.lower_home(), Some(real_home.as_path()));
+/// assert!(c.home() != real_home);
+/// # Ok(())
+/// # }
+/// ```
 pub struct Config(Context);
 
 impl Config {
@@ -186,7 +275,22 @@ impl Config {
 
         // As a special case, we defer the computation of the default
         // home, because env::home_dir() may fail.
-        let home_not_set = c.home == PathBuf::from("");
+        let mut home_not_set = c.home == PathBuf::from("");
+
+        // An overlay context uses the configured home as lower
+        // layer, and writes to a fresh ephemeral home.  If the lower
+        // layer is given explicitly, the home is used as is.  This
+        // is the case in the servers of overlay contexts.
+        if c.overlay && c.lower_home.is_none() {
+            c.lower_home = Some(if home_not_set {
+                default_home()?
+            } else {
+                c.home.clone()
+            });
+            c.home = PathBuf::from("");
+            c.ephemeral = true;
+            home_not_set = true;
+        }
 
         // If we have an ephemeral home, and home is not explicitly
         // set, create a temporary directory.  Ephemeral contexts can
@@ -198,12 +302,15 @@ impl Config {
             c.cleanup = true;
         } else {
             if home_not_set {
-                c.home =
-                    dirs::home_dir().ok_or(
-                        format_err!("Failed to get users home directory"))?
-                .join(".sequoia");
+                c.home = default_home()?;
             }
         }
+
+        // Catch malformed time formats early, so that formatting
+        // timestamps cannot fail later on.
+        time::strftime(&c.time_format.format, &time::empty_tm())
+            .map_err(|e| format_err!("Malformed time format {:?}: {}",
+                                     c.time_format.format, e))?;
         Ok(c)
     }
 
@@ -262,6 +369,117 @@ impl Config {
     pub fn set_ephemeral(&mut self) -> bool {
         ::std::mem::replace(&mut self.0.ephemeral, true)
     }
+
+    /// Makes this an overlay context.
+    ///
+    /// The configured home, or the default one, becomes a read-only
+    /// lower layer, and all changes go to an ephemeral home.
+    pub fn overlay(mut self) -> Self {
+        self.set_overlay();
+        self
+    }
+
+    /// Makes this an overlay context.
+    pub fn set_overlay(&mut self) -> bool {
+        ::std::mem::replace(&mut self.0.overlay, true)
+    }
+
+    /// Sets the lower layer of an overlay context.
+    ///
+    /// Unlike `overlay`, this does not create an ephemeral home, but
+    /// uses the configured one as upper layer.
+    pub fn lower_home<P: AsRef<Path>>(mut self, lower_home: P) -> Self {
+        self.set_lower_home(lower_home);
+        self
+    }
+
+    /// Sets the lower layer of an overlay context.
+    pub fn set_lower_home<P: AsRef<Path>>(&mut self, lower_home: P)
+                                          -> Option<PathBuf> {
+        self.0.overlay = true;
+        ::std::mem::replace(&mut self.0.lower_home,
+                            Some(PathBuf::new().join(lower_home)))
+    }
+
+    /// Sets GnuPG's home directory, and honors its configuration.
+    ///
+    /// This smooths coexistence with GnuPG.  For example, the store
+    /// updates keys using the keyserver GnuPG is configured to use.
+    pub fn gnupg_home<P: AsRef<Path>>(mut self, gnupg_home: P) -> Self {
+        self.set_gnupg_home(gnupg_home);
+        self
+    }
+
+    /// Sets GnuPG's home directory, and honors its configuration.
+    pub fn set_gnupg_home<P: AsRef<Path>>(&mut self, gnupg_home: P)
+                                          -> Option<PathBuf> {
+        ::std::mem::replace(&mut self.0.gnupg_home,
+                            Some(PathBuf::new().join(gnupg_home)))
+    }
+
+    /// Uses the system-wide services listening on the given socket.
+    ///
+    /// This is useful for thin clients and containers, which then
+    /// share a key store maintained by a system daemon instead of
+    /// keeping their own.
+    pub fn system_socket<P: AsRef<Path>>(mut self, socket: P) -> Self {
+        self.set_system_socket(socket);
+        self
+    }
+
+    /// Uses the system-wide services listening on the given socket.
+    pub fn set_system_socket<P: AsRef<Path>>(&mut self, socket: P)
+                                             -> Option<PathBuf> {
+        ::std::mem::replace(&mut self.0.system_socket,
+                            Some(PathBuf::new().join(socket)))
+    }
+
+    /// Makes backend servers sandbox themselves.
+    ///
+    /// This limits the damage a bug in a long-running server, e.g. in
+    /// the parser, can do.  Servers fail to start if the platform
+    /// does not support sandboxing.
+    pub fn sandbox(mut self) -> Self {
+        self.set_sandbox();
+        self
+    }
+
+    /// Makes backend servers sandbox themselves.
+    pub fn set_sandbox(&mut self) -> bool {
+        ::std::mem::replace(&mut self.0.sandbox, true)
+    }
+
+    /// Sets the time zone timestamps are presented in.
+    pub fn time_zone(mut self, time_zone: TimeZone) -> Self {
+        self.set_time_zone(time_zone);
+        self
+    }
+
+    /// Sets the time zone timestamps are presented in.
+    pub fn set_time_zone(&mut self, time_zone: TimeZone) -> TimeZone {
+        ::std::mem::replace(&mut self.0.time_format.time_zone, time_zone)
+    }
+
+    /// Sets the format timestamps are presented in.
+    ///
+    /// The format is a `strftime(3)` format string.  Malformed
+    /// formats are rejected by `build()`.
+    pub fn time_format<S: Into<String>>(mut self, format: S) -> Self {
+        self.set_time_format(format);
+        self
+    }
+
+    /// Sets the format timestamps are presented in.
+    pub fn set_time_format<S: Into<String>>(&mut self, format: S) -> String {
+        ::std::mem::replace(&mut self.0.time_format.format, format.into())
+    }
+}
+
+/// Returns the default home directory.
+fn default_home() -> Result<PathBuf> {
+    Ok(dirs::home_dir().ok_or(
+        format_err!("Failed to get users home directory"))?
+       .join(".sequoia"))
 }
 
 /* Error handling.  */
@@ -427,6 +645,104 @@ impl From<u8> for IPCPolicy {
     }
 }
 
+/* Time formatting.  */
+
+/// The time zone timestamps are presented in.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum TimeZone {
+    /// Coordinated Universal Time.
+    UTC,
+
+    /// The local time zone.
+    Local,
+}
+
+impl<'a> From<&'a TimeZone> for u8 {
+    fn from(time_zone: &TimeZone) -> Self {
+        match time_zone {
+            &TimeZone::UTC => 0,
+            &TimeZone::Local => 1,
+        }
+    }
+}
+
+// XXX: TryFrom would be nice.
+impl From<u8> for TimeZone {
+    fn from(time_zone: u8) -> Self {
+        match time_zone {
+            0 => TimeZone::UTC,
+            1 => TimeZone::Local,
+            n => panic!("Bad time zone: {}", n),
+        }
+    }
+}
+
+/// How timestamps are presented to the user.
+///
+/// Applications embedding Sequoia can configure this using
+/// `Config::time_zone` and `Config::time_format`, so that timestamps
+/// formatted by Sequoia match their own.
+///
+/// # Example
+///
+/// ```
+/// # extern crate time;
+/// # extern crate sequoia_core;
+/// # use sequoia_core::{Context, TimeZone, Result};
+/// # f().unwrap();
+/// # fn f() -> Result<()> {
+/// let c = Context::configure()
+/// #           .ephemeral()
+///             .time_zone(TimeZone::UTC)
+///             .time_format("%d.%m.%Y %H:%M")
+///             .build()?;
+/// let t = time::at_utc(time::Timespec::new(1554542220, 0));
+/// assert_eq!(c.time_format().format(&t), "06.04.2019 09:17");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(PartialEq, Debug, Clone)]
+pub struct TimeFormat {
+    time_zone: TimeZone,
+    format: String,
+}
+
+impl Default for TimeFormat {
+    fn default() -> Self {
+        TimeFormat {
+            time_zone: TimeZone::UTC,
+            format: "%Y-%m-%dT%H:%M".into(),
+        }
+    }
+}
+
+impl TimeFormat {
+    /// Returns the time zone.
+    pub fn time_zone(&self) -> TimeZone {
+        self.time_zone
+    }
+
+    /// Returns the `strftime(3)` format string.
+    pub fn format_string(&self) -> &str {
+        &self.format
+    }
+
+    /// Formats the given timestamp.
+    pub fn format(&self, t: &time::Tm) -> String {
+        let t = match self.time_zone {
+            TimeZone::UTC => t.to_utc(),
+            TimeZone::Local => t.to_local(),
+        };
+        time::strftime(&self.format, &t)
+            .expect("format is checked by Config::build")
+    }
+
+    /// Formats the given timestamp.
+    pub fn format_timespec(&self, t: time::Timespec) -> String {
+        self.format(&time::at_utc(t))
+    }
+}
+
 #[macro_export]
 macro_rules! assert_match {
     ( $error: pat = $expr:expr ) => {
@@ -441,7 +757,7 @@ macro_rules! assert_match {
 
 #[cfg(test)]
 mod test {
-    use super::{Error, NetworkPolicy};
+    use super::{Context, Error, NetworkPolicy};
 
     fn ok(policy: NetworkPolicy, required: NetworkPolicy) {
         assert!(policy.assert(required).is_ok());
@@ -488,4 +804,34 @@ mod test {
         ok(p, NetworkPolicy::Encrypted);
         ok(p, NetworkPolicy::Insecure);
     }
+
+    #[test]
+    fn overlay() {
+        let lower = ::tempdir::TempDir::new("sequoia").unwrap();
+        let c = Context::configure().home(lower.path()).overlay()
+            .build().unwrap();
+        assert!(c.ephemeral());
+        assert_eq!(c.lower_home(), Some(lower.path()));
+        let upper = c.home().to_path_buf();
+        assert!(upper.exists());
+        assert!(upper != lower.path());
+
+        // Clones do not remove the home.
+        drop(c.clone());
+        assert!(upper.exists());
+
+        drop(c);
+        assert!(! upper.exists());
+        assert!(lower.path().exists());
+    }
+
+    #[test]
+    fn time_format() {
+        let c = Context::configure().ephemeral().build().unwrap();
+        let t = ::time::Timespec::new(1554542220, 0);
+        assert_eq!(c.time_format().format_timespec(t), "2019-04-06T09:17");
+
+        assert!(Context::configure().ephemeral().time_format("%Q")
+                .build().is_err());
+    }
 }
@@ -0,0 +1,247 @@
+//! Leveled tracing for command-line frontends.
+//!
+//! Messages are written to stderr, prefixed with the time of day and
+//! their level, if their level is enabled.  By default, only errors
+//! and warnings are shown.  Frontends call [`init`] with the number
+//! of `-v` flags given on the command line.  If none are given, the
+//! level is taken from the `SEQUOIA_LOG` environment variable, e.g.
+//! `SEQUOIA_LOG=debug`.
+//!
+//! Spans group the messages of a phase of the program.  Messages
+//! emitted while a span is active are indented, and the duration of
+//! the span is reported when it ends.
+//!
+//! # Example
+//!
+//! ```
+//! #[macro_use] extern crate sequoia_core;
+//! use sequoia_core::trace::{self, Level};
+//! # fn main() {
+//!
+//! trace::set_level(Level::Debug);
+//! {
+//!     let _span = trace_span!(Level::Info, "Scanning {}", "keyring.pgp");
+//!     trace!(Level::Debug, "Found key {}", "0123456789ABCDEF");
+//! }
+//! # }
+//! ```
+//!
+//!   [`init`]: fn.init.html
+
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use failure;
+
+use Result;
+
+/// The environment variable selecting the level.
+pub const ENV: &str = "SEQUOIA_LOG";
+
+/// Selects which messages are shown.
+///
+/// Levels are ordered by verbosity.  Enabling a level also enables
+/// all less verbose levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Errors.
+    Error = 0,
+    /// Problems that do not prevent the operation from succeeding.
+    Warning = 1,
+    /// Progress of the operation.
+    Info = 2,
+    /// Details about the operation.
+    Debug = 3,
+    /// Everything.
+    Trace = 4,
+}
+
+impl Level {
+    fn from_usize(level: usize) -> Self {
+        match level {
+            0 => Level::Error,
+            1 => Level::Warning,
+            2 => Level::Info,
+            3 => Level::Debug,
+            _ => Level::Trace,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        })
+    }
+}
+
+impl FromStr for Level {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warning" | "warn" => Ok(Level::Warning),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(format_err!("Unknown trace level: {:?}", s)),
+        }
+    }
+}
+
+/// The most verbose enabled level.
+static LEVEL: AtomicUsize = AtomicUsize::new(Level::Warning as usize);
+
+/// The number of active spans.
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the most verbose level to show.
+pub fn set_level(level: Level) {
+    LEVEL.store(level as usize, Ordering::Relaxed);
+}
+
+/// Returns the most verbose level shown.
+pub fn level() -> Level {
+    Level::from_usize(LEVEL.load(Ordering::Relaxed))
+}
+
+/// Returns whether messages of the given level are shown.
+pub fn enabled(level: Level) -> bool {
+    level <= self::level()
+}
+
+/// Selects the level from the command line and the environment.
+///
+/// `verbosity` is the number of `-v` flags given.  Every flag makes
+/// the output more verbose by one level, starting with warnings.  If
+/// no flag is given, the level is taken from `SEQUOIA_LOG`, if it is
+/// set.
+pub fn init(verbosity: u64) -> Result<()> {
+    let level = if verbosity > 0 {
+        Level::from_usize(Level::Warning as usize + verbosity as usize)
+    } else if let Some(l) = env::var_os(ENV) {
+        l.to_string_lossy().parse()?
+    } else {
+        Level::Warning
+    };
+    set_level(level);
+    Ok(())
+}
+
+/// Writes a message.
+///
+/// Use the [`trace!`] macro instead.
+///
+///   [`trace!`]: ../macro.trace.html
+#[doc(hidden)]
+pub fn write(level: Level, message: fmt::Arguments) {
+    if ! enabled(level) {
+        return;
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::new(0, 0));
+    let secs = now.as_secs() % (24 * 60 * 60);
+    eprintln!("{:02}:{:02}:{:02}.{:03} {:7} {:indent$}{}",
+              secs / 3600, secs / 60 % 60, secs % 60, now.subsec_millis(),
+              level, "", message,
+              indent = 2 * DEPTH.load(Ordering::Relaxed));
+}
+
+/// A phase of the program.
+///
+/// The beginning of the span is reported when it is created, the
+/// end when it is dropped.  Create spans using the [`trace_span!`]
+/// macro.
+///
+///   [`trace_span!`]: ../macro.trace_span.html
+pub struct Span {
+    level: Level,
+    name: String,
+    start: Instant,
+    entered: bool,
+}
+
+impl Span {
+    /// Enters a span.
+    pub fn new(level: Level, name: String) -> Self {
+        write(level, format_args!("{}...", name));
+        let entered = enabled(level);
+        if entered {
+            DEPTH.fetch_add(1, Ordering::Relaxed);
+        }
+
+        Span {
+            level: level,
+            name: name,
+            start: Instant::now(),
+            entered: entered,
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if self.entered {
+            DEPTH.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        let elapsed = self.start.elapsed();
+        write(self.level,
+              format_args!("{} done ({} ms).", self.name,
+                           elapsed.as_secs() * 1000
+                           + elapsed.subsec_millis() as u64));
+    }
+}
+
+/// Writes a message at the given level.
+///
+/// The message is formatted like `format!` does, but only if the
+/// level is enabled.
+#[macro_export]
+macro_rules! trace {
+    ( $level: expr, $($arg: tt)* ) => {
+        $crate::trace::write($level, format_args!($($arg)*))
+    };
+}
+
+/// Enters a span at the given level.
+///
+/// The span ends when the returned value is dropped.  Note that
+/// binding the value to `_` drops it immediately.
+#[macro_export]
+macro_rules! trace_span {
+    ( $level: expr, $($arg: tt)* ) => {
+        $crate::trace::Span::new($level, format!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels() {
+        assert!(Level::Error < Level::Warning);
+        assert!(Level::Debug < Level::Trace);
+        assert_eq!("debug".parse::<Level>().unwrap(), Level::Debug);
+        assert_eq!("WARN".parse::<Level>().unwrap(), Level::Warning);
+        assert!("loud".parse::<Level>().is_err());
+
+        init(2).unwrap();
+        assert_eq!(level(), Level::Debug);
+        assert!(enabled(Level::Info));
+        assert!(! enabled(Level::Trace));
+        init(10).unwrap();
+        assert_eq!(level(), Level::Trace);
+    }
+}
@@ -146,15 +146,20 @@ pub fn ffi_catch_abort(_attr: TokenStream, item: TokenStream) -> TokenStream {
     decl.paren_token.surround(&mut fn_params, |ts| decl.inputs.to_tokens(ts));
 
     let block = &fun.block;
+    let name = ident.to_string();
 
     // We wrap the functions body into an catch_unwind, asserting that
     // all variables captured by the closure are unwind safe.  This is
     // safe because we terminate the process on panics, therefore no
     // inconsistencies can be observed.
+    //
+    // Objects created during the call are attributed to this
+    // function if the ownership is tracked.
     let expanded = quote! {
         #attrs #vis #constness #unsafety #asyncness #abi
         #fn_token #ident #fn_generics #fn_params #fn_out
         {
+            let _site = ::tracking::enter(#name);
             match ::std::panic::catch_unwind(
                 ::std::panic::AssertUnwindSafe(|| #fn_out #block))
             {
@@ -472,6 +477,10 @@ fn derive_conversion_functions(mut st: syn::ItemStruct,
                 if self.is_null() {
                     panic!("FFI contract violation: Parameter is NULL");
                 }
+                if ! ::tracking::release(self, Some(#c_type_name)) {
+                    panic!("FFI contract violation: \
+                            Use after move or use after free detected");
+                }
                 let mut wrapper = unsafe {
                     Box::from_raw(self)
                 };
@@ -516,6 +525,7 @@ fn derive_conversion_functions(mut st: syn::ItemStruct,
                 if self.is_null() {
                     panic!("FFI contract violation: Parameter is NULL");
                 }
+                ::tracking::check(self, Some(#c_type_name));
                 let wrapper = unsafe {
                     &(*self)
                 };
@@ -539,6 +549,7 @@ fn derive_conversion_functions(mut st: syn::ItemStruct,
                 if self.is_null() {
                     panic!("FFI contract violation: Parameter is NULL");
                 }
+                ::tracking::check(self, Some(#c_type_name));
                 let wrapper = unsafe {
                     &mut (*self)
                 };
@@ -563,8 +574,10 @@ fn derive_conversion_functions(mut st: syn::ItemStruct,
                 if self.is_none() {
                     return None;
                 }
+                let ptr = self.unwrap().as_ptr();
+                ::tracking::check(ptr, Some(#c_type_name));
                 let wrapper = unsafe {
-                    &mut (*self.unwrap().as_ptr())
+                    &mut (*ptr)
                 };
                 wrapper.assert_tag();
                 match wrapper.0 {
@@ -582,8 +595,11 @@ fn derive_conversion_functions(mut st: syn::ItemStruct,
 
         impl #generics #wrapper #generics {
             fn wrap(obj: #ownership) -> *mut #wrapper {
-                Box::into_raw(Box::new(#wrapper(obj, #magic_value,
-                                                #c_type_name_padded_literal)))
+                let ptr =
+                    Box::into_raw(Box::new(#wrapper(obj, #magic_value,
+                                                    #c_type_name_padded_literal)));
+                ::tracking::register(ptr, Some(#c_type_name));
+                ptr
             }
         }
 
@@ -721,6 +737,7 @@ fn derive_free(span: proc_macro2::Span, prefix: &str, name: &str,
     }
     let ident = syn::Ident::new(&format!("{}{}_free", prefix, name),
                                 span);
+    let c_type_name = format!("{}{}_t", prefix, name);
     quote! {
         /// Frees this object.
         #[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
@@ -728,6 +745,10 @@ fn derive_free(span: proc_macro2::Span, prefix: &str, name: &str,
             if this.is_null() {
                 return;
             }
+            if ! ::tracking::release(this, Some(#c_type_name)) {
+                // Freed before, don't touch it.
+                return;
+            }
 
             #[allow(unused_mut)]
             let mut wrapper = unsafe {
@@ -943,7 +964,7 @@ fn derive_parse(span: proc_macro2::Span, prefix: &str, name: &str,
     }
 }
 
-/// Derives prefix_name_serialize.
+/// Derives prefix_name_serialize and prefix_name_serialize_to_buffer.
 fn derive_serialize(span: proc_macro2::Span, prefix: &str, name: &str,
                     wrapper_st: &syn::ItemStruct, _wrapped: &syn::Type,
                     arg: &Option<String>)
@@ -957,6 +978,9 @@ fn derive_serialize(span: proc_macro2::Span, prefix: &str, name: &str,
     }
     let ident = syn::Ident::new(&format!("{}{}_serialize", prefix, name),
                                 span);
+    let to_buffer =
+        syn::Ident::new(&format!("{}{}_serialize_to_buffer", prefix, name),
+                        span);
     quote! {
         /// Serializes this object.
         #[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
@@ -970,6 +994,18 @@ fn derive_serialize(span: proc_macro2::Span, prefix: &str, name: &str,
             use ::MoveResultIntoRaw;
             this.ref_raw().serialize(writer.ref_mut_raw()).move_into_raw(errp)
         }
+
+        /// Serializes this object into a newly allocated buffer.
+        #[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+        fn #to_buffer #generics (errp: Option<&mut *mut ::error::Error>,
+                                 this: *const #wrapper #generics)
+                                 -> ::Maybe<::io::Buffer> {
+            use ::sequoia_openpgp::serialize::Serialize;
+            use ::RefRaw;
+            use ::MoveResultIntoRaw;
+            let mut buf = Vec::new();
+            this.ref_raw().serialize(&mut buf).map(|_| buf).move_into_raw(errp)
+        }
     }
 }
 
@@ -17,7 +17,7 @@ fn ident2c(ident: &syn::Ident) -> (String, bool) {
         "TPKBuilder" => return ("pgp_tpk_builder_t".into(), true),
         "UserID" => return ("pgp_userid_t".into(), true),
         "UserIDBinding" => return ("pgp_user_id_binding_t".into(), true),
-        "UserIDBindingIter" =>
+        "UserIDBindingIterWrapper" =>
             return ("pgp_user_id_binding_iter_t".into(), true),
 
         // Types from the libc crate.
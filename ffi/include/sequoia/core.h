@@ -5,6 +5,7 @@
 #include <stdint.h>
 #include <limits.h>
 #include <unistd.h>
+#include <time.h>
 
 /* sequoia::Context.  */
 
@@ -121,6 +122,25 @@ typedef enum sq_ipc_policy {
   SQ_IPC_POLICY_FORCE_WIDTH = INT_MAX,
 } sq_ipc_policy_t;
 
+/*/
+/// The time zone timestamps are presented in.
+/*/
+typedef enum sq_time_zone {
+  /*/
+  /// Coordinated Universal Time.
+  /*/
+  SQ_TIME_ZONE_UTC = 0,
+
+  /*/
+  /// The local time zone.
+  /*/
+  SQ_TIME_ZONE_LOCAL = 1,
+
+  /* Dummy value to make sure the enumeration has a defined size.  Do
+     not use this value.  */
+  SQ_TIME_ZONE_FORCE_WIDTH = INT_MAX,
+} sq_time_zone_t;
+
 
 /*/
 /// Creates a Context with reasonable defaults.
@@ -169,6 +189,25 @@ sq_ipc_policy_t sq_context_ipc_policy(const sq_context_t ctx);
 /*/
 uint8_t sq_context_ephemeral(const sq_context_t ctx);
 
+/*/
+/// Returns whether backend servers sandbox themselves.
+/*/
+uint8_t sq_context_sandbox(const sq_context_t ctx);
+
+/*/
+/// Returns the time zone timestamps are presented in.
+/*/
+sq_time_zone_t sq_context_time_zone(const sq_context_t ctx);
+
+/*/
+/// Formats the given timestamp for presentation to the user.
+///
+/// The timestamp is formatted according to the time zone and format
+/// configured using `sq_config_time_zone` and
+/// `sq_config_time_format`.  The returned string must be freed.
+/*/
+char *sq_context_format_time(const sq_context_t ctx, time_t t);
+
 
 /* sequoia::Config.  */
 
@@ -205,4 +244,35 @@ void sq_config_ipc_policy(sq_config_t cfg, sq_ipc_policy_t policy);
 /*/
 void sq_config_ephemeral(sq_config_t cfg);
 
+/*/
+/// Makes this an overlay context.
+///
+/// The configured home becomes a read-only lower layer, and all
+/// changes go to an ephemeral home.
+/*/
+void sq_config_overlay(sq_config_t cfg);
+
+/*/
+/// Sets GnuPG's home directory, and honors its configuration.
+/*/
+void sq_config_gnupg_home(sq_config_t cfg, const char *gnupg_home);
+
+/*/
+/// Makes backend servers sandbox themselves.
+/*/
+void sq_config_sandbox(sq_config_t cfg);
+
+/*/
+/// Sets the time zone timestamps are presented in.
+/*/
+void sq_config_time_zone(sq_config_t cfg, sq_time_zone_t time_zone);
+
+/*/
+/// Sets the format timestamps are presented in.
+///
+/// The format is a `strftime(3)` format string.  Malformed formats
+/// are rejected by `sq_config_build`.
+/*/
+void sq_config_time_format(sq_config_t cfg, const char *format);
+
 #endif
@@ -58,6 +58,53 @@ pgp_tpk_t sq_keyserver_get (sq_context_t ctx,
 			   sq_keyserver_t ks,
 			   const pgp_keyid_t id);
 
+/*/
+/// Reports download progress.
+///
+/// Called with the cookie, the number of bytes received so far, and
+/// the total size of the download, or 0 if unknown.
+/*/
+typedef void (*sq_progress_cb_t) (void *cookie, uint64_t received,
+				  uint64_t total);
+
+/*/
+/// Allows cancelling network operations.
+/*/
+typedef struct sq_cancellation_token *sq_cancellation_token_t;
+
+/*/
+/// Retrieves the key with the given `keyid`, reporting progress.
+///
+/// If `cb` is not `NULL`, it is called with `cookie` to report the
+/// progress of the download.  If `token` is not `NULL`, the download
+/// can be aborted by cancelling it, possibly from another thread.
+///
+/// Returns `NULL` on errors.
+/*/
+pgp_tpk_t sq_keyserver_get_with (sq_context_t ctx,
+				sq_keyserver_t ks,
+				const pgp_keyid_t id,
+				sq_progress_cb_t cb,
+				void *cookie,
+				sq_cancellation_token_t token);
+
+/*/
+/// Returns a new cancellation token.
+/*/
+sq_cancellation_token_t sq_cancellation_token_new (void);
+
+/*/
+/// Cancels all operations using this token.
+///
+/// This function may be called from any thread.
+/*/
+void sq_cancellation_token_cancel (sq_cancellation_token_t token);
+
+/*/
+/// Frees a cancellation token.
+/*/
+void sq_cancellation_token_free (sq_cancellation_token_t token);
+
 /*/
 /// Sends the given key to the server.
 ///
@@ -163,6 +163,12 @@ void sq_stats_free (sq_stats_t stats);
 /*/
 typedef struct sq_store_iter *sq_store_iter_t;
 
+/*/
+/// Lists all stores with the given prefix.
+/*/
+sq_store_iter_t sq_store_list_stores (sq_context_t ctx,
+				      const char *realm_prefix);
+
 /*/
 /// Returns the next store.
 ///
@@ -198,6 +204,14 @@ sq_binding_t sq_binding_iter_next (sq_binding_iter_t iter,
 				   char **labelp,
 				   pgp_fingerprint_t *fpp);
 
+/*/
+/// Sets the number of bindings fetched from the backend at once.
+///
+/// By default, the iterator fetches 32 bindings at once.  A batch
+/// size of zero is treated like one.
+/*/
+void sq_binding_iter_set_batch_size (sq_binding_iter_t iter, size_t n);
+
 /*/
 /// Frees a sq_binding_iter_t.
 /*/
@@ -217,6 +231,14 @@ typedef struct sq_key_iter *sq_key_iter_t;
 sq_key_t sq_key_iter_next (sq_key_iter_t iter,
 			   pgp_fingerprint_t *fpp);
 
+/*/
+/// Sets the number of keys fetched from the backend at once.
+///
+/// By default, the iterator fetches 32 keys at once.  A batch size
+/// of zero is treated like one.
+/*/
+void sq_key_iter_set_batch_size (sq_key_iter_t iter, size_t n);
+
 /*/
 /// Frees a sq_key_iter_t.
 /*/
@@ -270,6 +292,26 @@ sq_store_t sq_store_open (sq_context_t ctx, const char *realm, const char *name)
 sq_binding_t sq_store_add (sq_context_t ctx, sq_store_t store,
 			   const char *label, pgp_fingerprint_t fp);
 
+/*/
+/// Adds several keys identified by fingerprint to the store.
+///
+/// `labels` and `fingerprints` must not be `NULL`, and must both
+/// contain `len` elements.  All requests are sent at once, which is
+/// considerably faster than calling `sq_store_add` for every key.
+///
+/// If `bindingsp` is not `NULL`, it must point to an array of `len`
+/// elements.  The new bindings are stored there in the same order as
+/// the keys, and must be freed using `sq_binding_free`.
+///
+/// Returns != 0 on error.  Note that in this case, some of the keys
+/// may have been added nevertheless.
+/*/
+pgp_status_t sq_store_add_bindings (sq_context_t ctx, sq_store_t store,
+				    const char **labels,
+				    pgp_fingerprint_t *fingerprints,
+				    size_t len,
+				    sq_binding_t *bindingsp);
+
 /*/
 /// Imports a key into the store.
 /*/
@@ -299,6 +341,19 @@ sq_key_t sq_store_lookup_by_subkeyid (sq_context_t ctx, pgp_keyid_t keyid);
 /*/
 pgp_status_t sq_store_delete (sq_store_t store);
 
+/*/
+/// Deletes the bindings with the given labels.
+///
+/// `labels` must not be `NULL`, and must contain `len` elements.
+/// All requests are sent at once, which is considerably faster than
+/// deleting the bindings one by one.
+///
+/// Returns != 0 on error.  Note that in this case, some of the
+/// bindings may have been deleted nevertheless.
+/*/
+pgp_status_t sq_store_delete_bindings (sq_context_t ctx, sq_store_t store,
+				       const char **labels, size_t len);
+
 /*/
 /// Lists all bindings.
 /*/
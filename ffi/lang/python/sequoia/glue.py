@@ -113,6 +113,14 @@ def sq_static_str(s):
     return ffi.string(s).decode()
 _static_str = sq_static_str
 
+def sq_buffer(b):
+    try:
+        return bytes(ffi.buffer(lib.pgp_buffer_data(b, ffi.NULL),
+                                lib.pgp_buffer_len(b)))
+    finally:
+        lib.pgp_buffer_free(b)
+_buffer = sq_buffer
+
 def sq_iterator(iterator, next_fn, map=lambda x: x):
     while True:
         entry = next_fn(iterator)
@@ -2,7 +2,7 @@ from enum import Enum
 
 from _sequoia import ffi, lib
 from .error import Error
-from .glue import _str, SQObject, invoke
+from .glue import _str, _buffer, SQObject, invoke
 from .core import AbstractReader, AbstractWriter
 
 class KeyID(SQObject):
@@ -79,6 +79,9 @@ class PacketPile(SQObject):
         if status:
             raise Error._last(self.context())
 
+    def as_bytes(self):
+        return _buffer(invoke(lib.pgp_packet_pile_serialize_to_buffer, self.ref()))
+
 class TPK(SQObject):
     _del = lib.pgp_tpk_free
     _clone = lib.pgp_tpk_clone
@@ -115,6 +118,9 @@ class TPK(SQObject):
         if status:
             raise Error._last(self.context())
 
+    def as_bytes(self):
+        return _buffer(invoke(lib.pgp_tpk_serialize_to_buffer, self.ref()))
+
     def fingerprint(self):
         return Fingerprint(lib.pgp_tpk_fingerprint(self.ref()),
                            context=self.context())
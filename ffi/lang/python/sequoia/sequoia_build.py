@@ -5,8 +5,20 @@ from itertools import chain
 
 sq_inc = join(dirname(__file__), '../../../include/sequoia')
 pgp_inc = join(dirname(__file__), '../../../../openpgp-ffi/include/sequoia')
+
+def stable(lines):
+    """Drops the declarations of experimental symbols."""
+    experimental = False
+    for l in lines:
+        if l.startswith('#ifdef PGP_EXPERIMENTAL'):
+            experimental = True
+        elif l.startswith('#endif /* PGP_EXPERIMENTAL */'):
+            experimental = False
+        elif not experimental:
+            yield l
+
 defs = "".join(l
-               for l in chain(open(join(pgp_inc, "openpgp/error.h")).readlines(),
+               for l in stable(chain(open(join(pgp_inc, "openpgp/error.h")).readlines(),
                               open(join(pgp_inc, "io.h")).readlines(),
                               open(join(pgp_inc, "openpgp/types.h")).readlines(),
                               open(join(pgp_inc, "openpgp/crypto.h")).readlines(),
@@ -14,7 +26,7 @@ defs = "".join(l
                               open(join(pgp_inc, "openpgp.h")).readlines(),
                               open(join(sq_inc, "core.h")).readlines(),
                               open(join(sq_inc, "net.h")).readlines(),
-                              open(join(sq_inc, "store.h")).readlines())
+                              open(join(sq_inc, "store.h")).readlines()))
                if not l.startswith('#'))
 
 defs = defs.replace("INT_MAX", "{}".format(1<<31))
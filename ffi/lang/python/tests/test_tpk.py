@@ -56,6 +56,13 @@ def test_from_serialize():
         t = TPK.open(ctx, sink)
         assert t.fingerprint() == fp
 
+def test_as_bytes():
+    ctx = Context(network_policy=NetworkPolicy.Offline,
+                  ephemeral=True)
+    t = TPK.open(ctx, pgp)
+    u = TPK.from_bytes(ctx, t.as_bytes())
+    assert t == u
+
 def test_equals():
     ctx = Context(network_policy=NetworkPolicy.Offline,
                   ephemeral=True)
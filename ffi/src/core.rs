@@ -39,7 +39,8 @@
 //! ```
 
 use std::ptr;
-use libc::{uint8_t, c_char, c_int};
+use libc::{uint8_t, c_char, c_int, time_t};
+extern crate time;
 
 use sequoia_core as core;
 use sequoia_core::Config;
@@ -94,7 +95,7 @@ fn sq_context_free(context: Option<&mut Context>) {
 /// `sq_config_build()` in order to turn it into a Context.
 #[::ffi_catch_abort] #[no_mangle] pub extern "C"
 fn sq_context_configure() -> *mut Config {
-    Box::into_raw(Box::new(core::Context::configure()))
+    box_raw!(core::Context::configure())
 }
 
 /// Returns the directory containing shared state.
@@ -132,6 +133,32 @@ fn sq_context_ephemeral(ctx: *const Context) -> uint8_t {
     if ctx.c.ephemeral() { 1 } else { 0 }
 }
 
+/// Returns whether backend servers sandbox themselves.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_context_sandbox(ctx: *const Context) -> uint8_t {
+    let ctx = ffi_param_ref!(ctx);
+    if ctx.c.sandbox() { 1 } else { 0 }
+}
+
+/// Returns the time zone timestamps are presented in.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_context_time_zone(ctx: *const Context) -> c_int {
+    let ctx = ffi_param_ref!(ctx);
+    u8::from(&ctx.c.time_format().time_zone()) as c_int
+}
+
+/// Formats the given timestamp for presentation to the user.
+///
+/// The timestamp is formatted according to the time zone and format
+/// configured using `sq_config_time_zone` and
+/// `sq_config_time_format`.  The returned string must be freed.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_context_format_time(ctx: *const Context, t: time_t) -> *mut c_char {
+    let ctx = ffi_param_ref!(ctx);
+    ffi_return_string!(ctx.c.time_format()
+                       .format_timespec(time::Timespec::new(t as i64, 0)))
+}
+
 
 /*  sequoia::Config.  */
 
@@ -190,3 +217,49 @@ fn sq_config_ephemeral(cfg: *mut Config) {
     let cfg = ffi_param_ref_mut!(cfg);
     cfg.set_ephemeral();
 }
+
+/// Makes this an overlay context.
+///
+/// The configured home becomes a read-only lower layer, and all
+/// changes go to an ephemeral home.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_config_overlay(cfg: *mut Config) {
+    let cfg = ffi_param_ref_mut!(cfg);
+    cfg.set_overlay();
+}
+
+/// Sets GnuPG's home directory, and honors its configuration.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_config_gnupg_home(cfg: *mut Config, gnupg_home: *const c_char) {
+    let cfg = ffi_param_ref_mut!(cfg);
+    let gnupg_home = ffi_param_cstr!(gnupg_home).to_string_lossy();
+    cfg.set_gnupg_home(gnupg_home.as_ref());
+}
+
+/// Makes backend servers sandbox themselves.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_config_sandbox(cfg: *mut Config) {
+    let cfg = ffi_param_ref_mut!(cfg);
+    cfg.set_sandbox();
+}
+
+/// Sets the time zone timestamps are presented in.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_config_time_zone(cfg: *mut Config, time_zone: c_int) {
+    let cfg = ffi_param_ref_mut!(cfg);
+    if time_zone < 0 || time_zone > 1 {
+        panic!("Bad time zone: {}", time_zone);
+    }
+    cfg.set_time_zone((time_zone as u8).into());
+}
+
+/// Sets the format timestamps are presented in.
+///
+/// The format is a `strftime(3)` format string.  Malformed formats
+/// are rejected by `sq_config_build`.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_config_time_format(cfg: *mut Config, format: *const c_char) {
+    let cfg = ffi_param_ref_mut!(cfg);
+    let format = ffi_param_cstr!(format).to_string_lossy();
+    cfg.set_time_format(format.as_ref());
+}
@@ -68,6 +68,8 @@ impl<'a> FromSequoiaError<'a> for Status {
                     Status::IndexOutOfRange,
                 &openpgp::Error::UnsupportedTPK(_) =>
                     Status::UnsupportedTPK,
+                &openpgp::Error::PolicyViolation(_) =>
+                    Status::PolicyViolation,
             }
         }
 
@@ -13,6 +13,16 @@
 //!  - will not `abort(2)`,
 //!  - XXX
 //!
+//! # ABI Stability
+//!
+//! This library makes the same ABI guarantees as
+//! [sequoia-openpgp-ffi].  Its stable symbols are listed in
+//! `symbols.txt`, and in `symbols.txt` of sequoia-openpgp-ffi.
+//! Adding stable symbols to either library increments
+//! `PGP_API_LEVEL`.
+//!
+//! [sequoia-openpgp-ffi]: ../sequoia_openpgp_ffi/index.html#abi-stability
+//!
 //! # Types
 //!
 //! Sequoia objects are opaque objects.  They are created in
@@ -141,6 +151,7 @@ pub(crate) use openpgp::{
     MoveIntoRaw,
     MoveResultIntoRaw,
     Maybe,
+    tracking,
 };
 
 /* Error handling with implicit context.  */
@@ -27,14 +27,14 @@
 //! tpk = sq_keyserver_get (ctx, ks, id);
 //! ```
 
-use libc::{uint8_t, c_char, size_t};
+use libc::{uint8_t, uint64_t, c_char, c_void, size_t};
 use native_tls::Certificate;
 use std::ptr;
 use std::slice;
 
 extern crate sequoia_openpgp as openpgp;
 
-use sequoia_net::KeyServer;
+use sequoia_net::{CancellationToken, KeyServer, Progress};
 
 use super::error::Status;
 use super::core::Context;
@@ -124,6 +124,73 @@ fn sq_keyserver_get(ctx: *mut Context,
     ks.get(&id).move_into_raw(Some(ctx.errp()))
 }
 
+/// Reports download progress.
+///
+/// Called with the cookie, the number of bytes received so far, and
+/// the total size of the download, or 0 if unknown.
+type ProgressCallback = extern "C" fn(*mut c_void, uint64_t, uint64_t);
+
+/// Makes a C callback usable as `Progress`.
+fn progress(cb: Option<ProgressCallback>, cookie: *mut c_void)
+            -> Option<Box<Progress>> {
+    // The callback is invoked on the calling thread, hence it is
+    // safe to send the cookie along.
+    struct Cookie(*mut c_void);
+    unsafe impl Send for Cookie {}
+
+    let cookie = Cookie(cookie);
+    cb.map(|cb| -> Box<Progress> {
+        Box::new(move |received, total: Option<u64>| {
+            cb(cookie.0, received, total.unwrap_or(0))
+        })
+    })
+}
+
+/// Retrieves the key with the given `keyid`, reporting progress.
+///
+/// If `cb` is not `NULL`, it is called with `cookie` to report the
+/// progress of the download.  If `token` is not `NULL`, the download
+/// can be aborted by cancelling it, possibly from another thread.
+///
+/// Returns `NULL` on errors.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_keyserver_get_with(ctx: *mut Context,
+                         ks: *mut KeyServer,
+                         id: *const KeyID,
+                         cb: Option<ProgressCallback>,
+                         cookie: *mut c_void,
+                         token: Option<&CancellationToken>)
+                         -> Maybe<TPK> {
+    let ctx = ffi_param_ref_mut!(ctx);
+    ffi_make_fry_from_ctx!(ctx);
+    let ks = ffi_param_ref_mut!(ks);
+    let id = id.ref_raw();
+
+    ks.get_with(&id, progress(cb, cookie), token.cloned())
+        .move_into_raw(Some(ctx.errp()))
+}
+
+/// Returns a new cancellation token.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_cancellation_token_new() -> *mut CancellationToken {
+    box_raw!(CancellationToken::new())
+}
+
+/// Cancels all operations using this token.
+///
+/// This function may be called from any thread.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_cancellation_token_cancel(token: *const CancellationToken) {
+    let token = ffi_param_ref!(token);
+    token.cancel();
+}
+
+/// Frees a cancellation token.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_cancellation_token_free(token: Option<&mut CancellationToken>) {
+    ffi_free!(token)
+}
+
 /// Sends the given key to the server.
 ///
 /// Returns != 0 on errors.
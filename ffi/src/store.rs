@@ -23,8 +23,9 @@
 //! ```
 
 
-use libc::{uint8_t, uint64_t, c_char};
+use libc::{uint8_t, uint64_t, c_char, size_t};
 use std::ptr;
+use std::slice;
 
 extern crate sequoia_openpgp as openpgp;
 
@@ -138,6 +139,16 @@ fn sq_key_iter_next(iter: *mut KeyIter,
     }
 }
 
+/// Sets the number of keys fetched from the backend at once.
+///
+/// By default, the iterator fetches 32 keys at once.  A batch size
+/// of zero is treated like one.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_key_iter_set_batch_size(iter: *mut KeyIter, n: size_t) {
+    let iter = ffi_param_ref_mut!(iter);
+    iter.set_batch_size(n);
+}
+
 /// Frees a sq_key_iter_t.
 #[::ffi_catch_abort] #[no_mangle] pub extern "C"
 fn sq_key_iter_free(iter: Option<&mut KeyIter>) {
@@ -224,6 +235,51 @@ fn sq_store_add(ctx: *mut Context,
     ffi_try_box!(store.add(&label, fingerprint))
 }
 
+/// Adds several keys identified by fingerprint to the store.
+///
+/// `labels` and `fingerprints` must not be `NULL`, and must both
+/// contain `len` elements.  All requests are sent at once, which is
+/// considerably faster than calling `sq_store_add` for every key.
+///
+/// If `bindingsp` is not `NULL`, it must point to an array of `len`
+/// elements.  The new bindings are stored there in the same order as
+/// the keys, and must be freed using `sq_binding_free`.
+///
+/// Returns != 0 on error.  Note that in this case, some of the keys
+/// may have been added nevertheless.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_store_add_bindings(ctx: *mut Context,
+                         store: *const Store,
+                         labels: *const *const c_char,
+                         fingerprints: *const *const Fingerprint,
+                         len: size_t,
+                         bindingsp: *mut *mut Binding)
+                         -> Status {
+    let ctx = ffi_param_ref_mut!(ctx);
+    ffi_make_fry_from_ctx!(ctx);
+    let store = ffi_param_ref!(store);
+    assert!(!labels.is_null());
+    assert!(!fingerprints.is_null());
+    let labels = unsafe { slice::from_raw_parts(labels, len) };
+    let fingerprints = unsafe { slice::from_raw_parts(fingerprints, len) };
+
+    let labels: Vec<String> = labels.iter()
+        .map(|&label| ffi_param_cstr!(label).to_string_lossy().into_owned())
+        .collect();
+    let keys: Vec<_> = labels.iter().map(|label| label.as_str())
+        .zip(fingerprints.iter().map(|&fp| fp.ref_raw()))
+        .collect();
+
+    ffi_try_status!(store.add_bindings(&keys).map(|bindings| {
+        if ! bindingsp.is_null() {
+            let bindingsp = unsafe { slice::from_raw_parts_mut(bindingsp, len) };
+            for (p, binding) in bindingsp.iter_mut().zip(bindings.into_iter()) {
+                *p = box_raw!(binding);
+            }
+        }
+    }))
+}
+
 /// Imports a key into the store.
 #[::ffi_catch_abort] #[no_mangle] pub extern "C"
 fn sq_store_import(ctx: *mut Context,
@@ -291,6 +347,34 @@ fn sq_store_delete(ctx: *mut Context, store: *mut Store)
     ffi_try_status!(store.delete())
 }
 
+/// Deletes the bindings with the given labels.
+///
+/// `labels` must not be `NULL`, and must contain `len` elements.
+/// All requests are sent at once, which is considerably faster than
+/// deleting the bindings one by one.
+///
+/// Returns != 0 on error.  Note that in this case, some of the
+/// bindings may have been deleted nevertheless.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_store_delete_bindings(ctx: *mut Context,
+                            store: *const Store,
+                            labels: *const *const c_char,
+                            len: size_t)
+                            -> Status {
+    let ctx = ffi_param_ref_mut!(ctx);
+    ffi_make_fry_from_ctx!(ctx);
+    let store = ffi_param_ref!(store);
+    assert!(!labels.is_null());
+    let labels = unsafe { slice::from_raw_parts(labels, len) };
+
+    let labels: Vec<String> = labels.iter()
+        .map(|&label| ffi_param_cstr!(label).to_string_lossy().into_owned())
+        .collect();
+    let labels: Vec<&str> = labels.iter().map(|label| label.as_str()).collect();
+
+    ffi_try_status!(store.delete_bindings(&labels))
+}
+
 /// Lists all bindings.
 #[::ffi_catch_abort] #[no_mangle] pub extern "C"
 fn sq_store_iter(ctx: *mut Context, store: *const Store)
@@ -334,6 +418,16 @@ fn sq_binding_iter_next(iter: *mut BindingIter,
     }
 }
 
+/// Sets the number of bindings fetched from the backend at once.
+///
+/// By default, the iterator fetches 32 bindings at once.  A batch
+/// size of zero is treated like one.
+#[::ffi_catch_abort] #[no_mangle] pub extern "C"
+fn sq_binding_iter_set_batch_size(iter: *mut BindingIter, n: size_t) {
+    let iter = ffi_param_ref_mut!(iter);
+    iter.set_batch_size(n);
+}
+
 /// Frees a sq_binding_iter_t.
 #[::ffi_catch_abort] #[no_mangle] pub extern "C"
 fn sq_binding_iter_free(iter: Option<&mut BindingIter>) {
@@ -367,7 +461,8 @@ fn sq_key_free(key: Option<&mut Key>) {
 #[::ffi_catch_abort] #[no_mangle] pub extern "C"
 fn sq_log_free(log: Option<&mut Log>) {
     if let Some(log) = log {
-        let log = unsafe { Box::from_raw(log) };
+        let log = log as *mut Log;
+        let log = ffi_param_move!(log);
         if ! log.store.is_null() {
             ffi_param_move!(log.store);
         }
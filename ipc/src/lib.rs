@@ -16,6 +16,39 @@
 //!
 //! This design mimics Unix sockets, but works on Windows too.
 //!
+//! # Authentication
+//!
+//! The rendezvous point must be owned by the current user and must
+//! not be accessible by anyone else.  If its permissions are too
+//! lax, the cookie may have leaked, and we start a new server with a
+//! fresh cookie.
+//!
+//! The cookie itself is never sent over the socket.  Instead, for
+//! every connection, the server sends a random nonce, and the client
+//! proves knowledge of the cookie by responding with an HMAC-SHA256
+//! over the nonce keyed with the cookie.  Hence, a local attacker
+//! capturing this exchange cannot replay it.  The server compares
+//! the response in constant time and acknowledges a successful
+//! authentication with a single byte.  If the authentication fails,
+//! the client assumes that the server has been restarted, and starts
+//! a new one with a fresh cookie.
+//!
+//! Servers learn their cookie from the process starting them, either
+//! directly if they are co-located, or over a pipe if they are
+//! external processes.  Clients have to complete the handshake in a
+//! few seconds, otherwise the server drops the connection.
+//!
+//! # System daemons
+//!
+//! Services may also be provided by a system daemon serving all
+//! users of a machine, see [`Context::system_socket`].  The daemon
+//! listens on a Unix socket that any local user may connect to.
+//! Instead of cookies, the daemon uses the user id of the connecting
+//! process, as reported by the kernel, and hands it to the service,
+//! which must keep the users' state apart.
+//!
+//! [`Context::system_socket`]: ../../sequoia_core/struct.Context.html#method.system_socket
+//!
 //! # External vs internal servers
 //!
 //! These servers can be either in external processes, or co-located
@@ -39,48 +72,70 @@
 use std::fs;
 use std::io::{self, Read, Write};
 use std::net::{SocketAddr, AddrParseError, TcpStream, TcpListener};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
 
 extern crate capnp_rpc;
 #[macro_use] extern crate failure;
 extern crate fs2;
 extern crate futures;
+extern crate libc;
 extern crate memsec;
 extern crate tokio_core;
 extern crate tokio_io;
+extern crate tokio_uds;
 
 use failure::Fallible as Result;
 use fs2::FileExt;
 use futures::{Future, Stream};
 
 use tokio_core::net;
-use tokio_io::io::{ReadHalf, ReadExact};
 use tokio_io::AsyncRead;
 
 use capnp_rpc::{RpcSystem, twoparty};
 use capnp_rpc::rpc_twoparty_capnp::Side;
 
 /* Unix-specific options.  */
-use std::os::unix::io::FromRawFd;
-use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::os::unix::fs::{FileTypeExt, MetadataExt, OpenOptionsExt,
+                        PermissionsExt};
+use std::os::unix::net::{UnixStream, UnixListener};
 
 /* XXX: Implement Windows support.  */
 
 use std::process::{Command, Stdio};
+use std::os::unix::process::CommandExt;
 use std::os::unix::io::AsRawFd;
 
 use std::thread;
 
 extern crate sequoia_core;
+extern crate sequoia_openpgp as openpgp;
 
 use sequoia_core as core;
+use openpgp::constants::HashAlgorithm;
+
+mod sandbox;
+
+/// The network handed to servers.
+pub type Network = twoparty::VatNetwork<Box<Read>>;
+
+/// Identifies the client of a connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Peer {
+    /// The user owning the server.
+    ///
+    /// The client proved knowledge of the cookie.
+    Owner,
+    /// A user of a system daemon, identified by its user id.
+    User(u32),
+}
 
 /// Servers need to implement this trait.
 pub trait Handler {
     /// Called on every connection.
-    fn handle(&self,
-              network: twoparty::VatNetwork<ReadHalf<net::TcpStream>>)
-              -> RpcSystem<Side>;
+    fn handle(&self, network: Network, peer: Peer) -> RpcSystem<Side>;
 }
 
 /// A factory for handlers.
@@ -95,10 +150,33 @@ pub struct Descriptor {
     rendezvous: PathBuf,
     executable: PathBuf,
     factory: HandlerFactory,
+    system_socket: Option<PathBuf>,
 }
 
 const LOCALHOST: &str = "127.0.0.1";
 
+/// The file descriptor external servers read their cookie from.
+///
+/// It is the read end of a pipe, the cookie is written to it before
+/// the server is started.
+const COOKIE_FD: RawFd = 3;
+
+/// How long clients and servers wait for each other during the
+/// handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sent by the server after successfully authenticating a client.
+const HANDSHAKE_OK: u8 = 1;
+
+/// How often servers of ephemeral contexts check whether their home
+/// is still there.
+const HOME_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The permissions of the socket of system daemons.
+///
+/// Every local user may connect, the daemon isolates them.
+const SYSTEM_SOCKET_MODE: u32 = 0o666;
+
 impl Descriptor {
     /// Create a descriptor given its rendezvous point, the path to
     /// the servers executable file, and a handler factory.
@@ -110,9 +188,20 @@ impl Descriptor {
             rendezvous: rendezvous,
             executable: executable,
             factory: factory,
+            system_socket: None,
         }
     }
 
+    /// Makes this the descriptor of a system-wide service.
+    ///
+    /// If `socket` is given, clients connect to the system daemon
+    /// listening there instead of starting a server, and servers
+    /// become the system daemon.
+    pub fn system_socket(mut self, socket: Option<&Path>) -> Self {
+        self.system_socket = socket.map(|p| p.to_path_buf());
+        self
+    }
+
     /// Returns the context.
     pub fn context(&self) -> &core::Context {
         &self.ctx
@@ -131,10 +220,13 @@ impl Descriptor {
     pub fn connect_with_policy(&self, handle: &tokio_core::reactor::Handle,
                                policy: core::IPCPolicy)
                    -> Result<RpcSystem<Side>> {
-        let do_connect =
-            move |cookie: Cookie, mut s: TcpStream| -> Result<RpcSystem<Side>> {
-            cookie.send(&mut s)?;
+        if let Some(ref socket) = self.system_socket {
+            // The system daemon is never started on demand.
+            return self.connect_system(handle, socket);
+        }
 
+        let do_connect =
+            move |s: TcpStream| -> Result<RpcSystem<Side>> {
             /* Tokioize.  */
             let stream = net::TcpStream::from_stream(s, &handle)?;
             stream.set_nodelay(true)?;
@@ -158,6 +250,11 @@ impl Descriptor {
             .open(&self.rendezvous)?;
         file.lock_exclusive()?;
 
+        if ! check_permissions(&file, &self.rendezvous)? {
+            /* The cookie may have leaked.  Invalidate it.  */
+            file.set_len(0)?;
+        }
+
         let mut c = vec![];
         file.read_to_end(&mut c)?;
 
@@ -168,17 +265,24 @@ impl Descriptor {
                 /* Malformed.  Invalidate the cookie and try again.  */
                 file.set_len(0)?;
                 drop(file);
-                return self.connect(handle);
+                return self.connect_with_policy(handle, policy);
             }
 
-            let stream = TcpStream::connect(addr.unwrap());
+            let stream: Result<TcpStream> = TcpStream::connect(addr.unwrap())
+                .map_err(|e| e.into())
+                .and_then(|mut s| {
+                    cookie.authenticate(&mut s)?;
+                    Ok(s)
+                });
             if let Ok(s) = stream {
-                do_connect(cookie, s)
+                do_connect(s)
             } else {
-                /* Failed to connect.  Invalidate the cookie and try again.  */
+                /* Failed to connect or to authenticate, the server
+                 * may have been restarted.  Invalidate the cookie and
+                 * try again.  */
                 file.set_len(0)?;
                 drop(file);
-                self.connect(handle)
+                self.connect_with_policy(handle, policy)
             }
         } else {
             let cookie = Cookie::new()?;
@@ -189,7 +293,7 @@ impl Descriptor {
                     continue;
                 }
 
-                let addr = match self.start(*external) {
+                let addr = match self.start(*external, &cookie) {
                     Ok(a) => a,
                     Err(e) => if *external {
                         if policy == core::IPCPolicy::External {
@@ -206,10 +310,7 @@ impl Descriptor {
                 };
 
                 let mut stream = TcpStream::connect(addr)?;
-                cookie.send(&mut stream)?;
-
-                /* XXX: It'd be nice not to waste this connection.  */
-                drop(stream);
+                cookie.authenticate(&mut stream)?;
 
                 if *external {
                     /* Write connection information to file.  */
@@ -219,12 +320,33 @@ impl Descriptor {
                 }
                 drop(file);
 
-                return do_connect(cookie, TcpStream::connect(addr)?);
+                return do_connect(stream);
             }
             unreachable!();
         }
     }
 
+    /// Connects to the system daemon listening on `socket`.
+    fn connect_system(&self, handle: &tokio_core::reactor::Handle,
+                      socket: &Path)
+                      -> Result<RpcSystem<Side>> {
+        let stream = UnixStream::connect(socket)
+            .map_err(|e| format_err!(
+                "Failed to connect to the system daemon at {}: {}",
+                socket.display(), e))?;
+
+        /* Tokioize.  */
+        let stream = tokio_uds::UnixStream::from_std(
+            stream, handle.new_tokio_handle())?;
+        let (reader, writer) = stream.split();
+
+        let network =
+            Box::new(twoparty::VatNetwork::new(reader, writer,
+                                               Side::Client,
+                                               Default::default()));
+        Ok(RpcSystem::new(network, None))
+    }
+
     /// Try to create a TCP socket, bind it to a random port on
     /// localhost.
     fn listen(&self) -> Result<TcpListener> {
@@ -234,7 +356,7 @@ impl Descriptor {
 
     /// Start the service, either as an external process or as a
     /// thread.
-    fn start(&self, external: bool) -> Result<SocketAddr> {
+    fn start(&self, external: bool, cookie: &Cookie) -> Result<SocketAddr> {
         /* Listen on a random port on localhost.  */
         let mut listener = self.listen();
         while listener.is_err() {
@@ -243,41 +365,79 @@ impl Descriptor {
         let listener = listener.unwrap();
         let addr = listener.local_addr()?;
 
-        /* Start the server, connect to it, and send the cookie.  */
+        /* Start the server and hand it the cookie.  */
         if external {
-            self.fork(listener)?;
+            self.fork(listener, cookie)?;
         } else {
-            self.spawn(listener)?;
+            self.spawn(listener, cookie)?;
         }
 
         Ok(addr)
     }
 
-    fn fork(&self, l: TcpListener) -> Result<()> {
+    fn fork(&self, l: TcpListener, cookie: &Cookie) -> Result<()> {
         // Convert to raw fd, then forget l so that it will not be
         // closed when it is dropped.
         let fd = l.as_raw_fd();
         ::std::mem::forget(l);
 
-        Command::new(&self.executable.clone().into_os_string())
-            .arg("--home")
+        let mut cmd = Command::new(&self.executable.clone().into_os_string());
+        cmd.arg("--home")
             .arg(self.ctx.home().to_string_lossy().into_owned())
             .arg("--lib")
             .arg(self.ctx.home().to_string_lossy().into_owned())
             .arg("--ephemeral")
-            .arg(format!("{}", self.ctx.ephemeral()))
-            // l will be closed here if the exec fails.
-            .stdin(unsafe { Stdio::from_raw_fd(fd) })
+            .arg(format!("{}", self.ctx.ephemeral()));
+        if let Some(lower_home) = self.ctx.lower_home() {
+            cmd.arg("--lower")
+                .arg(lower_home.to_string_lossy().into_owned());
+        }
+        if let Some(gnupg_home) = self.ctx.gnupg_home() {
+            cmd.arg("--gnupg-home")
+                .arg(gnupg_home.to_string_lossy().into_owned());
+        }
+        if self.ctx.sandbox() {
+            cmd.arg("--sandbox").arg("true");
+        }
+
+        // Hand the cookie to the server over a pipe.  It fits into
+        // the pipe's buffer, hence we can write it before starting
+        // the server.
+        let (r, w) = pipe()?;
+        let r = unsafe { fs::File::from_raw_fd(r) };
+        let mut w = unsafe { fs::File::from_raw_fd(w) };
+        cookie.send(&mut w)?;
+        drop(w);
+
+        let cookie_fd = r.as_raw_fd();
+        cmd.before_exec(move || {
+            // dup2 clears the close-on-exec flag, unless the file
+            // descriptors are the same.
+            let rc = if cookie_fd == COOKIE_FD {
+                unsafe { libc::fcntl(COOKIE_FD, libc::F_SETFD, 0) }
+            } else {
+                unsafe { libc::dup2(cookie_fd, COOKIE_FD) }
+            };
+            if rc < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        });
+
+        // l will be closed here if the exec fails.
+        cmd.stdin(unsafe { Stdio::from_raw_fd(fd) })
             .spawn()?;
         Ok(())
     }
 
-    fn spawn(&self, l: TcpListener) -> Result<()> {
+    fn spawn(&self, l: TcpListener, cookie: &Cookie) -> Result<()> {
         let descriptor = self.clone();
+        let cookie = cookie.clone();
         thread::spawn(move || -> Result<()> {
             Ok(Server::new(descriptor)
                .expect("Failed to spawn server") // XXX
-               .serve_listener(l)
+               .serve_listener(l, cookie, false)
                .expect("Failed to spawn server")) // XXX
         });
         Ok(())
@@ -302,18 +462,41 @@ impl Server {
     /// Creates a Context from `env::args()`.
     pub fn context() -> Result<core::Context> {
         use std::env::args;
-        let args: Vec<String> = args().collect();
+        Self::context_from_args(&args().collect::<Vec<_>>())
+    }
+
+    /// Creates a Context from the given command line arguments.
+    fn context_from_args(args: &[String]) -> Result<core::Context> {
+        let usage = || format_err!(
+            "Usage: {} --home <HOMEDIR> --lib <LIBDIR> \
+             --ephemeral true|false [--lower <LOWERDIR>] \
+             [--gnupg-home <GNUPGHOME>] [--system <SOCKET>] \
+             [--sandbox true|false]", args[0]);
 
-        if args.len() != 7 || args[1] != "--home"
+        if args.len() < 7 || args.len() % 2 != 1 || args[1] != "--home"
             || args[3] != "--lib" || args[5] != "--ephemeral" {
-                return Err(format_err!(
-                    "Usage: {} --home <HOMEDIR> --lib <LIBDIR> \
-                     --ephemeral true|false", args[0]));
+                return Err(usage());
             }
 
         let mut cfg = core::Context::configure()
             .home(&args[2]).lib(&args[4]);
 
+        for option in args[7..].chunks(2) {
+            match option[0].as_str() {
+                "--lower" => { cfg.set_lower_home(&option[1]); },
+                "--gnupg-home" => { cfg.set_gnupg_home(&option[1]); },
+                "--system" => { cfg.set_system_socket(&option[1]); },
+                "--sandbox" => match option[1].parse() {
+                    Ok(true) => { cfg.set_sandbox(); },
+                    Ok(false) => (),
+                    Err(_) => return Err(format_err!(
+                        "Expected 'true' or 'false' for --sandbox, got: {}",
+                        option[1])),
+                },
+                _ => return Err(usage()),
+            }
+        }
+
         if let Ok(ephemeral) = args[6].parse() {
             if ephemeral {
                 cfg.set_ephemeral();
@@ -330,7 +513,15 @@ impl Server {
     /// Turns this process into a server.
     ///
     /// External servers must call this early on.  Expects 'stdin' to
-    /// be a listening TCP socket.
+    /// be a listening TCP socket, unless the descriptor names a
+    /// system socket, in which case the server becomes the system
+    /// daemon listening on it.
+    ///
+    /// If the context asks for it, the server sandboxes itself once
+    /// it has opened its database and sockets, see
+    /// [`Context::sandbox`].
+    ///
+    /// [`Context::sandbox`]: ../../sequoia_core/struct.Context.html#method.sandbox
     ///
     /// # Example
     ///
@@ -352,40 +543,172 @@ impl Server {
     /// }
     /// ```
     pub fn serve(&mut self) -> Result<()> {
-        self.serve_listener(unsafe { TcpListener::from_raw_fd(0) })
+        if let Some(socket) = self.descriptor.system_socket.clone() {
+            return self.serve_system(&socket);
+        }
+
+        let cookie = {
+            // Closed when dropped, so that our children do not
+            // inherit it.
+            let mut f = unsafe { fs::File::from_raw_fd(COOKIE_FD) };
+            let mut c = vec![];
+            f.read_to_end(&mut c)?;
+            Cookie::from(&c)
+                .ok_or_else(|| format_err!("Malformed cookie"))?
+        };
+
+        self.serve_listener(unsafe { TcpListener::from_raw_fd(0) }, cookie,
+                            true)
     }
 
-    fn serve_listener(&mut self, l: TcpListener) -> Result<()> {
-        /* The first client tells us our cookie.  */
-        let mut i = l.accept()?;
-        let cookie = Cookie::receive(&mut i.0)?;
-        /* XXX: It'd be nice to recycle this connection.  */
-        drop(i);
+    /// Restricts this process, if the context asks for it.
+    ///
+    /// Must only be called from external servers, never from a
+    /// server running in a thread of the application.
+    fn sandbox(&self) -> Result<()> {
+        let ctx = self.descriptor.context();
+        if ctx.sandbox() {
+            sandbox::enter(ctx)?;
+        }
+        Ok(())
+    }
 
-        let handler = (self.descriptor.factory)(self.descriptor.clone(), self.core.handle())?;
+    /// Serves clients proving knowledge of `cookie` on `l`.
+    ///
+    /// If `external` is true, this is an external server, and it may
+    /// sandbox itself.
+    fn serve_listener(&mut self, l: TcpListener, cookie: Cookie,
+                      external: bool) -> Result<()> {
+        let handler: Rc<Box<Handler>> = Rc::new(
+            (self.descriptor.factory)(self.descriptor.clone(), self.core.handle())?);
+        let mut rng = OsRng::new()?;
 
         /* Tokioize.  */
         let handle = self.core.handle();
         let a = l.local_addr()?;
         let socket = tokio_core::net::TcpListener::from_listener(l, &a, &handle).unwrap();
 
-        let done = socket.incoming().and_then(|(socket, _addr)| {
+        if external {
+            self.sandbox()?;
+        }
+
+        let done = socket.incoming().for_each(|(socket, _addr)| {
             let _ = socket.set_nodelay(true);
-            Cookie::receive_async(socket)
-        }).and_then(|(socket, buf)| {
-            if Cookie::from(&buf).map(|c| c == cookie).unwrap_or(false) {
-                Ok(socket)
-            } else {
-                Err(io::Error::new(io::ErrorKind::BrokenPipe, "Bad cookie."))
-            }
-        }).for_each(|socket| {
-            let (reader, writer) = socket.split();
 
+            /* Challenge the client with a fresh nonce.  */
+            let mut nonce = vec![0; NONCE_SIZE];
+            rng.fill_bytes(&mut nonce);
+            let expected = match cookie.mac(&nonce) {
+                Ok(m) => m,
+                // Drop the connection.
+                Err(_) => return Ok(()),
+            };
+
+            // Do not wait forever for clients that never answer.
+            let timeout =
+                match tokio_core::reactor::Timeout::new(HANDSHAKE_TIMEOUT,
+                                                        &handle) {
+                    Ok(t) => t.and_then(|_| Err(io::Error::new(
+                        io::ErrorKind::TimedOut, "Handshake timed out."))),
+                    // Drop the connection.
+                    Err(_) => return Ok(()),
+                };
+
+            let handler = handler.clone();
+            let spawn_handle = handle.clone();
+            let authenticated = tokio_io::io::write_all(socket, nonce)
+                .and_then(|(socket, _)| {
+                    tokio_io::io::read_exact(socket, vec![0; MAC_SIZE])
+                })
+                .and_then(move |(socket, mac)| {
+                    if constant_time_eq(&mac, &expected) {
+                        Ok(socket)
+                    } else {
+                        Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                                           "Bad cookie."))
+                    }
+                })
+                .and_then(|socket| tokio_io::io::write_all(socket, [HANDSHAKE_OK]))
+                .select(timeout)
+                .map(move |((socket, _), _)| {
+                    let (reader, writer) = socket.split();
+
+                    let network =
+                        twoparty::VatNetwork::new(Box::new(reader) as Box<Read>,
+                                                  writer,
+                                                  Side::Server, Default::default());
+
+                    let rpc_system = handler.handle(network, Peer::Owner);
+                    spawn_handle.spawn(
+                        rpc_system.map_err(|e| println!("error: {:?}", e)));
+                })
+                // A client failing to authenticate must not bring
+                // down the server.
+                .map_err(|(_, _)| ());
+            handle.spawn(authenticated);
+            Ok(())
+        });
+
+        if ! self.descriptor.context().ephemeral() {
+            return Ok(self.core.run(done)?);
+        }
+
+        // The context that created an ephemeral home removes it when
+        // it is dropped.  Then, we are no longer needed.
+        let home = self.descriptor.context().home().to_path_buf();
+        let gone = tokio_core::reactor::Interval::new(HOME_POLL_INTERVAL,
+                                                      &handle)?
+            .take_while(move |_| -> io::Result<bool> { Ok(home.exists()) })
+            .for_each(|_| Ok(()));
+        Ok(self.core.run(done.select(gone))
+           .map(|_| ()).map_err(|(e, _)| e)?)
+    }
+
+    /// Serves all users of this system on `socket`.
+    ///
+    /// There is no cookie.  Instead, the handler learns the user id
+    /// of every client from the kernel, and is responsible for
+    /// keeping the users apart.
+    fn serve_system(&mut self, socket: &Path) -> Result<()> {
+        let handler: Rc<Box<Handler>> = Rc::new(
+            (self.descriptor.factory)(self.descriptor.clone(), self.core.handle())?);
+
+        if let Some(dir) = socket.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // Remove the socket of a previous daemon, but nothing else.
+        match fs::symlink_metadata(socket) {
+            Ok(ref m) if m.file_type().is_socket() =>
+                fs::remove_file(socket)?,
+            Ok(_) => return Err(format_err!(
+                "{} exists and is not a socket", socket.display())),
+            Err(_) => (),
+        }
+        let l = UnixListener::bind(socket)?;
+        fs::set_permissions(socket,
+                            fs::Permissions::from_mode(SYSTEM_SOCKET_MODE))?;
+
+        /* Tokioize.  */
+        let handle = self.core.handle();
+        let l = tokio_uds::UnixListener::from_std(l, handle.new_tokio_handle())?;
+
+        self.sandbox()?;
+
+        let done = l.incoming().for_each(|stream| {
+            let uid = match stream.peer_cred() {
+                Ok(cred) => cred.uid,
+                // Drop the connection.
+                Err(_) => return Ok(()),
+            };
+
+            let (reader, writer) = stream.split();
             let network =
-                twoparty::VatNetwork::new(reader, writer,
+                twoparty::VatNetwork::new(Box::new(reader) as Box<Read>,
+                                          writer,
                                           Side::Server, Default::default());
 
-            let rpc_system = handler.handle(network);
+            let rpc_system = handler.handle(network, Peer::User(uid));
             handle.spawn(rpc_system.map_err(|e| println!("error: {:?}", e)));
             Ok(())
         });
@@ -394,7 +717,50 @@ impl Server {
     }
 }
 
+/// Checks the permissions of the rendezvous point.
+///
+/// Fails if the file is not owned by the current user.  If anyone
+/// else may access it, the permissions are fixed, and `false` is
+/// returned to indicate that the cookie must not be trusted.
+fn check_permissions(file: &fs::File, path: &Path) -> Result<bool> {
+    let metadata = file.metadata()?;
+    if metadata.uid() != unsafe { libc::getuid() } {
+        return Err(format_err!(
+            "Rendezvous point {} is not owned by the current user",
+            path.display()));
+    }
+
+    if metadata.mode() & 0o077 != 0 {
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+/// Creates a pipe, returning the read and the write end.
+///
+/// Both ends are closed on exec.
+fn pipe() -> Result<(RawFd, RawFd)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    for fd in fds.iter() {
+        unsafe { libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+    Ok((fds[0], fds[1]))
+}
+
+/// Compares two byte strings in constant time.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    // The length is not a secret, hence we can use && here.
+    a.len() == b.len()
+        && unsafe { ::memsec::memeq(a.as_ptr(), b.as_ptr(), a.len()) }
+}
+
 /// Cookies are used to authenticate clients.
+#[derive(Clone)]
 struct Cookie(Vec<u8>);
 
 extern crate rand;
@@ -402,6 +768,11 @@ use self::rand::RngCore;
 use self::rand::rngs::OsRng;
 
 const COOKIE_SIZE: usize = 32;
+const NONCE_SIZE: usize = 32;
+const MAC_SIZE: usize = 32;
+
+/// The block size of SHA256, used for the HMAC construction.
+const HMAC_BLOCK_SIZE: usize = 64;
 
 impl Cookie {
     /// Make a new cookie.
@@ -433,20 +804,49 @@ impl Cookie {
         }
     }
 
-    /// Read a cookie from 'from'.
-    fn receive<R: Read>(from: &mut R) -> Result<Self> {
-        let mut buf = vec![0; COOKIE_SIZE];
-        from.read_exact(&mut buf)?;
-        Ok(Cookie(buf))
-    }
+    /// Computes HMAC-SHA256 over `nonce` keyed with the cookie.
+    fn mac(&self, nonce: &[u8]) -> Result<Vec<u8>> {
+        let mut ipad = vec![0x36; HMAC_BLOCK_SIZE];
+        let mut opad = vec![0x5c; HMAC_BLOCK_SIZE];
+        for (i, k) in self.0.iter().enumerate() {
+            ipad[i] ^= k;
+            opad[i] ^= k;
+        }
 
-    /// Asynchronously read a cookie from 'socket'.
-    fn receive_async(socket: net::TcpStream) -> ReadExact<net::TcpStream,
-                                                          Vec<u8>> {
-        let buf = vec![0; COOKIE_SIZE];
-        tokio_io::io::read_exact(socket, buf)
+        let mut inner = vec![0; MAC_SIZE];
+        let mut h = HashAlgorithm::SHA256.context()?;
+        h.update(&ipad);
+        h.update(nonce);
+        h.digest(&mut inner);
+
+        let mut mac = vec![0; MAC_SIZE];
+        let mut h = HashAlgorithm::SHA256.context()?;
+        h.update(&opad);
+        h.update(&inner);
+        h.digest(&mut mac);
+        Ok(mac)
     }
 
+    /// Authenticates to the server listening on `stream`.
+    ///
+    /// Reads the nonce, responds with the MAC, and waits for the
+    /// server's acknowledgment.
+    fn authenticate(&self, stream: &mut TcpStream) -> Result<()> {
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+
+        let mut nonce = vec![0; NONCE_SIZE];
+        stream.read_exact(&mut nonce)?;
+        stream.write_all(&self.mac(&nonce)?)?;
+
+        let mut ack = [0; 1];
+        stream.read_exact(&mut ack)?;
+        if ack[0] != HANDSHAKE_OK {
+            return Err(format_err!("Authentication failed"));
+        }
+
+        stream.set_read_timeout(None)?;
+        Ok(())
+    }
 
     /// Write a cookie to 'to'.
     fn send<W: Write>(&self, to: &mut W) -> io::Result<()> {
@@ -455,15 +855,67 @@ impl Cookie {
     }
 }
 
-impl PartialEq for Cookie {
-    fn eq(&self, other: &Cookie) -> bool {
-        // First, compare the length.
-        self.0.len() == other.0.len()
-            // The length is not a secret, hence we can use && here.
-            && unsafe {
-                ::memsec::memeq(self.0.as_ptr(),
-                                other.0.as_ptr(),
-                                self.0.len())
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openpgp::conversions::hex;
+
+    #[test]
+    fn hmac_sha256() {
+        // RFC 4231, test case 1.  HMAC pads the key with zeros, so
+        // padding it to the size of a cookie does not change the MAC.
+        let mut key = vec![0x0b; 20];
+        key.resize(COOKIE_SIZE, 0);
+        let mac = Cookie(key).mac(b"Hi There").unwrap();
+        assert_eq!(hex::encode(&mac),
+                   "B0344C61D8DB38535CA8AFCEAF0BF12B\
+                    881DC200C9833DA726E9376C2E32CFF7");
+    }
+
+    #[test]
+    fn constant_time_comparison() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(! constant_time_eq(b"abc", b"abd"));
+        assert!(! constant_time_eq(b"abc", b"abcd"));
+    }
+
+    #[test]
+    fn cookie_pipe() {
+        let cookie = Cookie::new().unwrap();
+        let (r, w) = pipe().unwrap();
+        for fd in [r, w].iter() {
+            let flags = unsafe { libc::fcntl(*fd, libc::F_GETFD) };
+            assert!(flags & libc::FD_CLOEXEC != 0);
+        }
+
+        let mut r = unsafe { fs::File::from_raw_fd(r) };
+        let mut w = unsafe { fs::File::from_raw_fd(w) };
+        cookie.send(&mut w).unwrap();
+        drop(w);
+        let mut c = vec![];
+        r.read_to_end(&mut c).unwrap();
+        assert_eq!(Cookie::from(&c).unwrap().0, cookie.0);
+    }
+
+    #[test]
+    fn server_context() {
+        let context = |extra: &[&str]| {
+            let mut args = vec!["server", "--home", "/home", "--lib", "/lib",
+                                "--ephemeral", "false"];
+            args.extend_from_slice(extra);
+            Server::context_from_args(
+                &args.into_iter().map(|a| a.to_string()).collect::<Vec<_>>())
+        };
+
+        assert!(! context(&[]).unwrap().sandbox());
+        assert!(context(&["--sandbox", "true"]).unwrap().sandbox());
+        assert!(! context(&["--sandbox", "false"]).unwrap().sandbox());
+        assert!(context(&["--sandbox", "yes"]).is_err());
+        assert!(context(&["--sandbox"]).is_err());
+
+        let ctx = context(&["--gnupg-home", "/gnupg", "--sandbox", "true"])
+            .unwrap();
+        assert!(ctx.sandbox());
+        assert_eq!(ctx.gnupg_home(), Some(Path::new("/gnupg")));
     }
 }
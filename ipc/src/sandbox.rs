@@ -0,0 +1,309 @@
+//! Sandboxes external servers.
+//!
+//! Servers are long-running and parse data from untrusted sources,
+//! e.g., keys fetched from keyservers.  If the context asks for it,
+//! servers restrict themselves once they have opened their databases
+//! and sockets, limiting what an attacker exploiting a bug in the
+//! server can do.
+//!
+//! The restrictions must not get in the way of the servers' normal
+//! operation.  Servers still access their home, talk to the network
+//! to update keys, and run notification commands.  Hence, we deny
+//! what no server ever needs, like debugging other processes,
+//! changing the file system namespace, or loading kernel modules.
+
+use sequoia_core as core;
+
+use Result;
+
+/// Restricts the current process.
+///
+/// Fails if the platform does not support sandboxing.
+pub(crate) fn enter(ctx: &core::Context) -> Result<()> {
+    imp::enter(ctx)
+}
+
+#[cfg(all(target_os = "linux",
+          any(target_arch = "x86_64", target_arch = "x86",
+              target_arch = "aarch64")))]
+mod imp {
+    use libc;
+    use std::io;
+
+    use sequoia_core as core;
+
+    use Result;
+
+    /// The architecture reported in `seccomp_data`, see
+    /// `linux/audit.h`.
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000003e;
+    #[cfg(target_arch = "x86")]
+    const AUDIT_ARCH: u32 = 0x40000003;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc00000b7;
+
+    /// On x86_64, system calls made using the x32 ABI have this bit
+    /// set.
+    const X32_SYSCALL_BIT: u32 = 0x40000000;
+
+    // See `linux/filter.h` and `linux/seccomp.h`.
+    const BPF_LD_W_ABS: u16 = 0x00 | 0x00 | 0x20;
+    const BPF_JMP_JEQ_K: u16 = 0x05 | 0x10 | 0x00;
+    const BPF_JMP_JGE_K: u16 = 0x05 | 0x30 | 0x00;
+    const BPF_RET_K: u16 = 0x06 | 0x00;
+
+    const SECCOMP_DATA_NR: u32 = 0;
+    const SECCOMP_DATA_ARCH: u32 = 4;
+
+    const SECCOMP_RET_KILL: u32 = 0x00000000;
+    const SECCOMP_RET_ERRNO: u32 = 0x00050000;
+    const SECCOMP_RET_ALLOW: u32 = 0x7fff0000;
+
+    const SECCOMP_SET_MODE_FILTER: libc::c_ulong = 1;
+    const SECCOMP_FILTER_FLAG_TSYNC: libc::c_ulong = 1;
+    const PR_SET_NO_NEW_PRIVS: libc::c_int = 38;
+
+    #[repr(C)]
+    struct SockFilter {
+        code: u16,
+        jt: u8,
+        jf: u8,
+        k: u32,
+    }
+
+    #[repr(C)]
+    struct SockFprog {
+        len: libc::c_ushort,
+        filter: *const SockFilter,
+    }
+
+    fn stmt(code: u16, k: u32) -> SockFilter {
+        SockFilter { code: code, jt: 0, jf: 0, k: k }
+    }
+
+    fn jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
+        SockFilter { code: code, jt: jt, jf: jf, k: k }
+    }
+
+    /// System calls no server needs.
+    ///
+    /// They fail with `EPERM`.
+    const DENIED: &[libc::c_long] = &[
+        // Inspecting and modifying other processes.
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        // Changing the file system namespace.
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_pivot_root,
+        libc::SYS_chroot,
+        libc::SYS_unshare,
+        libc::SYS_setns,
+        // Modifying the kernel.
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_kexec_load,
+        libc::SYS_reboot,
+        libc::SYS_swapon,
+        libc::SYS_swapoff,
+        libc::SYS_bpf,
+        libc::SYS_perf_event_open,
+        // Accessing the kernel's key management.
+        libc::SYS_keyctl,
+        libc::SYS_add_key,
+        libc::SYS_request_key,
+    ];
+
+    fn filter() -> Vec<SockFilter> {
+        let mut f = vec![
+            // Kill the process if it uses a different ABI.  System
+            // call numbers differ between ABIs.
+            stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARCH),
+            jump(BPF_JMP_JEQ_K, AUDIT_ARCH, 1, 0),
+            stmt(BPF_RET_K, SECCOMP_RET_KILL),
+            stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR),
+        ];
+
+        if cfg!(target_arch = "x86_64") {
+            f.push(jump(BPF_JMP_JGE_K, X32_SYSCALL_BIT, 0, 1));
+            f.push(stmt(BPF_RET_K, SECCOMP_RET_KILL));
+        }
+
+        for &nr in DENIED {
+            f.push(jump(BPF_JMP_JEQ_K, nr as u32, 0, 1));
+            f.push(stmt(BPF_RET_K,
+                        SECCOMP_RET_ERRNO | libc::EPERM as u32));
+        }
+        f.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+        f
+    }
+
+    pub(super) fn enter(_: &core::Context) -> Result<()> {
+        let filter = filter();
+        let prog = SockFprog {
+            len: filter.len() as libc::c_ushort,
+            filter: filter.as_ptr(),
+        };
+
+        // Required to install a filter without CAP_SYS_ADMIN.
+        if unsafe { libc::prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        // Filter all threads, not only the current one.
+        let r = unsafe {
+            libc::syscall(libc::SYS_seccomp, SECCOMP_SET_MODE_FILTER,
+                          SECCOMP_FILTER_FLAG_TSYNC,
+                          &prog as *const SockFprog)
+        };
+        match r {
+            0 => Ok(()),
+            -1 => Err(format_err!("Installing the seccomp filter failed: {}",
+                                  io::Error::last_os_error())),
+            tid => Err(format_err!("Thread {} could not be sandboxed", tid)),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Runs the filter for the given system call.
+        fn run(filter: &[SockFilter], arch: u32, nr: u32) -> u32 {
+            let mut acc = 0;
+            let mut pc = 0;
+            loop {
+                let insn = &filter[pc];
+                pc += 1;
+                match insn.code {
+                    BPF_LD_W_ABS => acc = match insn.k {
+                        SECCOMP_DATA_NR => nr,
+                        SECCOMP_DATA_ARCH => arch,
+                        k => panic!("Unexpected offset {}", k),
+                    },
+                    BPF_JMP_JEQ_K => pc += if acc == insn.k {
+                        insn.jt
+                    } else {
+                        insn.jf
+                    } as usize,
+                    BPF_JMP_JGE_K => pc += if acc >= insn.k {
+                        insn.jt
+                    } else {
+                        insn.jf
+                    } as usize,
+                    BPF_RET_K => return insn.k,
+                    c => panic!("Unexpected instruction {:#x}", c),
+                }
+            }
+        }
+
+        #[test]
+        fn filter_decisions() {
+            let f = filter();
+            let denied = SECCOMP_RET_ERRNO | libc::EPERM as u32;
+
+            for &nr in DENIED {
+                assert_eq!(run(&f, AUDIT_ARCH, nr as u32), denied);
+            }
+            for &nr in &[libc::SYS_read, libc::SYS_write, libc::SYS_openat,
+                         libc::SYS_socket, libc::SYS_execve] {
+                assert_eq!(run(&f, AUDIT_ARCH, nr as u32), SECCOMP_RET_ALLOW);
+            }
+
+            // Other ABIs are not allowed.
+            assert_eq!(run(&f, !AUDIT_ARCH, libc::SYS_read as u32),
+                       SECCOMP_RET_KILL);
+            if cfg!(target_arch = "x86_64") {
+                assert_eq!(run(&f, AUDIT_ARCH,
+                               X32_SYSCALL_BIT | libc::SYS_read as u32),
+                           SECCOMP_RET_KILL);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "openbsd")]
+mod imp {
+    use libc::{c_char, c_int};
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::ptr;
+
+    use sequoia_core as core;
+
+    use Result;
+
+    extern "C" {
+        fn pledge(promises: *const c_char, execpromises: *const c_char)
+                  -> c_int;
+        fn unveil(path: *const c_char, permissions: *const c_char) -> c_int;
+    }
+
+    /// What servers may do.
+    ///
+    /// Servers update keys over the network, and run notification
+    /// commands using the shell.
+    const PROMISES: &str =
+        "stdio rpath wpath cpath flock fattr unix inet dns proc exec";
+
+    fn cstring<S: AsRef<[u8]>>(s: S) -> Result<CString> {
+        Ok(CString::new(s.as_ref().to_vec())?)
+    }
+
+    fn unveil_path(path: &Path, permissions: &str) -> Result<()> {
+        let path = cstring(path.as_os_str().as_bytes())?;
+        let permissions = cstring(permissions)?;
+        if unsafe { unveil(path.as_ptr(), permissions.as_ptr()) } != 0 {
+            let e = io::Error::last_os_error();
+            // Paths that do not exist need not be hidden.
+            if e.kind() != io::ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn enter(ctx: &core::Context) -> Result<()> {
+        unveil_path(ctx.home(), "rwc")?;
+        if let Some(lower_home) = ctx.lower_home() {
+            unveil_path(lower_home, "r")?;
+        }
+        if let Some(gnupg_home) = ctx.gnupg_home() {
+            unveil_path(gnupg_home, "r")?;
+        }
+        // Name resolution, TLS, and notification commands.
+        unveil_path(Path::new("/etc/resolv.conf"), "r")?;
+        unveil_path(Path::new("/etc/hosts"), "r")?;
+        unveil_path(Path::new("/etc/ssl"), "r")?;
+        unveil_path(Path::new("/bin/sh"), "x")?;
+        // Forbid further changes.
+        if unsafe { unveil(ptr::null(), ptr::null()) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let promises = cstring(PROMISES)?;
+        if unsafe { pledge(promises.as_ptr(), ptr::null()) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(all(target_os = "linux",
+                  any(target_arch = "x86_64", target_arch = "x86",
+                      target_arch = "aarch64")),
+              target_os = "openbsd")))]
+mod imp {
+    use sequoia_core as core;
+
+    use Result;
+
+    pub(super) fn enter(_: &core::Context) -> Result<()> {
+        Err(format_err!("Sandboxing is not supported on this platform"))
+    }
+}
@@ -4,25 +4,40 @@
 //! asynchronous framework.
 
 use failure;
-use futures::{future, Future, Stream};
+use futures::{future, Future};
+use futures::future::{loop_fn, Loop};
 use hyper::client::{ResponseFuture, HttpConnector};
+use hyper::client::connect::HttpInfo;
+use hyper::client::connect::dns::{GaiResolver, Name, Resolve};
 use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE, HeaderValue};
-use hyper::{self, Client, Body, StatusCode, Request};
+use hyper::{self, Client, Body, StatusCode, Request, Response};
 use hyper_tls::HttpsConnector;
 use native_tls::{Certificate, TlsConnector};
 use percent_encoding::{percent_encode, DEFAULT_ENCODE_SET};
+use std::cell::RefCell;
 use std::convert::From;
-use std::io::Cursor;
+use std::io::{self, Cursor};
+use std::net::IpAddr;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::vec;
 use url::Url;
 
 use openpgp::TPK;
+use openpgp::constants::{HashAlgorithm, SignatureType};
+use openpgp::conversions::hex;
+use openpgp::packet::Signature;
 use openpgp::parse::Parse;
 use openpgp::{KeyID, armor, serialize::Serialize};
 use sequoia_core::{Context, NetworkPolicy};
 
 use wkd as net_wkd;
 
-use super::{Error, Result};
+use super::{CancellationToken, Error, IpPreference, Progress, Result};
+use failover::Failover;
+use health::Health;
+use monitor;
 
 define_encode_set! {
     /// Encoding used for submitting keys.
@@ -36,26 +51,100 @@ define_encode_set! {
 pub struct KeyServer {
     client: Box<AClient>,
     uri: Url,
+    resolver: Resolver,
 }
 
 const DNS_WORKER: usize = 4;
 
+/// How long to wait for a connection using the preferred address
+/// family before racing the other one.
+const HAPPY_EYEBALLS_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Resolves names, ordering the addresses by health and preference.
+#[derive(Clone)]
+struct Resolver {
+    gai: GaiResolver,
+    preference: Arc<Mutex<IpPreference>>,
+    health: Health,
+    /// The addresses returned by the last lookup.
+    resolved: Arc<Mutex<Vec<IpAddr>>>,
+}
+
+impl Resolver {
+    fn new() -> Self {
+        Resolver {
+            gai: GaiResolver::new(DNS_WORKER),
+            preference: Default::default(),
+            health: Health::new(),
+            resolved: Default::default(),
+        }
+    }
+}
+
+impl Resolve for Resolver {
+    type Addrs = vec::IntoIter<IpAddr>;
+    type Future =
+        Box<Future<Item=Self::Addrs, Error=io::Error> + Send + 'static>;
+
+    fn resolve(&self, name: Name) -> Self::Future {
+        let preference = *self.preference.lock()
+            .expect("poisoned lock");
+        let health = self.health.clone();
+        let resolved = self.resolved.clone();
+        Box::new(self.gai.resolve(name).map(move |addrs| {
+            let mut addrs = addrs.collect::<Vec<_>>();
+            order(&mut addrs, preference, &health);
+            *resolved.lock().expect("poisoned lock") = addrs.clone();
+            addrs.into_iter()
+        }))
+    }
+}
+
+/// Orders `addrs` by health and preference.
+///
+/// No address is dropped, the other family is merely tried later.
+fn order(addrs: &mut Vec<IpAddr>, preference: IpPreference, health: &Health) {
+    // The sort is stable, hence within each family the system's
+    // order is retained.
+    match preference {
+        IpPreference::System => (),
+        IpPreference::IPv4 => addrs.sort_by_key(|a| a.is_ipv6()),
+        IpPreference::IPv6 => addrs.sort_by_key(|a| a.is_ipv4()),
+    }
+    // Healthy servers take precedence over the preferred address
+    // family.
+    health.sort(addrs);
+}
+
+/// Returns a connector resolving both A and AAAA records, and
+/// connecting using Happy Eyeballs.
+fn http_connector(resolver: &Resolver) -> HttpConnector<Resolver> {
+    let mut http = HttpConnector::new_with_resolver(resolver.clone());
+    http.set_happy_eyeballs_timeout(Some(HAPPY_EYEBALLS_TIMEOUT));
+    http
+}
+
 impl KeyServer {
     /// Returns a handle for the given URI.
     pub fn new(ctx: &Context, uri: &str) -> Result<Self> {
         let uri: Url = uri.parse()
             .or_else(|_| format!("hkps://{}", uri).parse())?;
 
+        let resolver = Resolver::new();
         let client: Box<AClient> = match uri.scheme() {
-            "hkp" => Box::new(Client::new()),
+            "hkp" => Box::new(Client::builder()
+                              .build(http_connector(&resolver))),
             "hkps" => {
+                let mut http = http_connector(&resolver);
+                http.enforce_http(false);
+                let tls = TlsConnector::new()?;
                 Box::new(Client::builder()
-                         .build(HttpsConnector::new(DNS_WORKER)?))
+                         .build(HttpsConnector::from((http, tls))))
             },
             _ => return Err(Error::MalformedUri.into()),
         };
 
-        Self::make(ctx, client, uri)
+        Self::make(ctx, client, uri, resolver)
     }
 
     /// Returns a handle for the given URI.
@@ -65,18 +154,19 @@ impl KeyServer {
                      -> Result<Self> {
         let uri: Url = uri.parse()?;
 
+        let resolver = Resolver::new();
         let client: Box<AClient> = {
             let mut tls = TlsConnector::builder();
             tls.add_root_certificate(cert);
             let tls = tls.build()?;
 
-            let mut http = HttpConnector::new(DNS_WORKER);
+            let mut http = http_connector(&resolver);
             http.enforce_http(false);
             Box::new(Client::builder()
                      .build(HttpsConnector::from((http, tls))))
         };
 
-        Self::make(ctx, client, uri)
+        Self::make(ctx, client, uri, resolver)
     }
 
     /// Returns a handle for the SKS keyserver pool.
@@ -92,7 +182,9 @@ impl KeyServer {
     }
 
     /// Common code for the above functions.
-    fn make(ctx: &Context, client: Box<AClient>, uri: Url) -> Result<Self> {
+    fn make(ctx: &Context, client: Box<AClient>, uri: Url,
+            resolver: Resolver)
+            -> Result<Self> {
         let s = uri.scheme();
         match s {
             "hkp" => ctx.network_policy().assert(NetworkPolicy::Insecure),
@@ -110,12 +202,76 @@ impl KeyServer {
                         _ => unreachable!(),
                     }.unwrap()).parse()?;
 
-        Ok(KeyServer{client: client, uri: uri})
+        Ok(KeyServer{client: client, uri: uri, resolver: resolver})
+    }
+
+    /// Sets the address family to try first when connecting.
+    ///
+    /// See [`IpPreference`] for details.
+    ///
+    ///   [`IpPreference`]: ../enum.IpPreference.html
+    pub fn set_ip_preference(&mut self, preference: IpPreference) {
+        *self.resolver.preference.lock().expect("poisoned lock") = preference;
+    }
+
+    /// Returns the health statistics of the servers contacted so far.
+    ///
+    /// Servers are identified by their address.  When connecting,
+    /// healthy servers are tried first.  The returned handle can be
+    /// used to retrieve the statistics, and to restore statistics
+    /// collected earlier.  See the [`health`] module for details.
+    ///
+    ///   [`health`]: ../health/index.html
+    pub fn health(&self) -> Health {
+        self.resolver.health.clone()
+    }
+
+    /// Records the outcome of `response` in the health statistics.
+    ///
+    /// If the request fails before a server responds, the failure is
+    /// attributed to all addresses returned by the last lookup.
+    fn monitor_health(&self, response: ResponseFuture)
+                      -> impl Future<Item=Response<Body>, Error=hyper::Error>
+    {
+        let health = self.resolver.health.clone();
+        let resolved = self.resolver.resolved.clone();
+        let start = Instant::now();
+        response.then(move |r| {
+            match r {
+                Ok(ref res) => {
+                    if let Some(info) = res.extensions().get::<HttpInfo>() {
+                        let addr = info.remote_addr().ip();
+                        if res.status().is_server_error() {
+                            health.failure(addr);
+                        } else {
+                            health.success(addr, start.elapsed());
+                        }
+                    }
+                },
+                Err(_) => {
+                    for addr in resolved.lock().expect("poisoned lock").iter() {
+                        health.failure(*addr);
+                    }
+                },
+            }
+            r
+        })
     }
 
     /// Retrieves the key with the given `keyid`.
     pub fn get(&mut self, keyid: &KeyID)
                -> Box<Future<Item=TPK, Error=failure::Error> + 'static> {
+        self.get_with(keyid, None, None)
+    }
+
+    /// Retrieves the key with the given `keyid`, reporting progress.
+    ///
+    /// Progress is reported to `progress`.  The download can be
+    /// aborted using `token`.
+    pub fn get_with(&mut self, keyid: &KeyID,
+                    progress: Option<Box<Progress>>,
+                    token: Option<CancellationToken>)
+                    -> Box<Future<Item=TPK, Error=failure::Error> + 'static> {
         let uri = self.uri.join(
             &format!("pks/lookup?op=get&options=mr&search=0x{}",
                      keyid.to_hex()));
@@ -124,14 +280,16 @@ impl KeyServer {
             return Box::new(future::err(Error::from(e).into()));
         }
 
-        Box::new(self.client.do_get(uri.unwrap())
+        let response = self.client.do_get(uri.unwrap());
+        monitor::cancellable(
+            self.monitor_health(response)
                  .from_err()
-                 .and_then(|res| {
+                 .and_then(move |res| {
                      let status = res.status();
-                     res.into_body().concat2().from_err()
+                     monitor::body(res, progress)
                          .and_then(move |body| match status {
                              StatusCode::OK => {
-                                 let c = Cursor::new(body.as_ref());
+                                 let c = Cursor::new(&body[..]);
                                  let r = armor::Reader::new(
                                      c,
                                      armor::ReaderMode::Tolerant(
@@ -142,7 +300,8 @@ impl KeyServer {
                                  future::err(Error::NotFound.into()),
                              n => future::err(Error::HttpStatus(n).into()),
                          })
-                 }))
+                 }),
+            token)
     }
 
     /// Sends the given key to the server.
@@ -191,7 +350,8 @@ impl KeyServer {
             HeaderValue::from_str(&format!("{}", length))
                 .expect("cannot fail: only ASCII characters"));
 
-        Box::new(self.client.do_request(request)
+        let response = self.client.do_request(request);
+        Box::new(self.monitor_health(response)
                  .from_err()
                  .and_then(|res| {
                      match res.status() {
@@ -201,6 +361,223 @@ impl KeyServer {
                      }
                  }))
     }
+
+    /// Asks the server to timestamp a document.
+    ///
+    /// `digest` is the digest of the document computed using
+    /// `hash_algo`.  Keyservers acting as notaries return a timestamp
+    /// signature over the digest, made using the same hash
+    /// algorithm.  See [`Signature4::verify_timestamp`] for how to
+    /// verify it.
+    ///
+    /// Note: The signature is not verified.  The caller has to
+    /// verify it using the notary's key.
+    ///
+    ///   [`Signature4::verify_timestamp`]: ../../sequoia_openpgp/packet/signature/struct.Signature4.html#method.verify_timestamp
+    pub fn timestamp(&mut self, hash_algo: HashAlgorithm, digest: &[u8])
+                     -> Box<Future<Item=Signature, Error=failure::Error> + 'static> {
+        let uri =
+            match self.uri.join("pks/timestamp") {
+                Err(e) =>
+                // This shouldn't happen, but better safe than sorry.
+                    return Box::new(future::err(Error::from(e).into())),
+                Ok(u) => u,
+            };
+
+        // Prepare to send url-encoded data.
+        let post_data = format!("hash={}&digest={}",
+                                u8::from(hash_algo), hex::encode(digest));
+        let length = post_data.len();
+
+        let mut request = match Request::post(url2uri(uri))
+            .body(Body::from(post_data))
+        {
+            Ok(r) => r,
+            Err(e) => return Box::new(future::err(Error::from(e).into())),
+        };
+        request.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"));
+        request.headers_mut().insert(
+            CONTENT_LENGTH,
+            HeaderValue::from_str(&format!("{}", length))
+                .expect("cannot fail: only ASCII characters"));
+
+        let response = self.client.do_request(request);
+        Box::new(self.monitor_health(response)
+                 .from_err()
+                 .and_then(|res| {
+                     let status = res.status();
+                     monitor::body(res, None)
+                         .and_then(move |body| match status {
+                             StatusCode::OK => {
+                                 let c = Cursor::new(&body[..]);
+                                 let r = armor::Reader::new(
+                                     c,
+                                     armor::ReaderMode::Tolerant(
+                                         Some(armor::Kind::Signature)));
+                                 future::done(Signature::from_reader(r))
+                             },
+                             StatusCode::NOT_FOUND =>
+                                 future::err(Error::ProtocolViolation.into()),
+                             n => future::err(Error::HttpStatus(n).into()),
+                         })
+                 })
+                 .and_then(move |sig| {
+                     if sig.sigtype() != SignatureType::Timestamp
+                         || sig.hash_algo() != hash_algo
+                     {
+                         Err(Error::MalformedResponse.into())
+                     } else {
+                         Ok(sig)
+                     }
+                 }))
+    }
+}
+
+/// For accessing a list of keyservers with sticky failover.
+///
+/// The keyservers are tried in order.  If a keyserver cannot be
+/// reached, or responds with an error, the next one is tried.  If a
+/// fallback works, it is tried first for some time.  See the
+/// [`failover`] module for details.
+///
+///   [`failover`]: ../failover/index.html
+pub struct FailoverKeyServer {
+    servers: Rc<RefCell<Vec<KeyServer>>>,
+    uris: Vec<String>,
+    failover: Failover,
+}
+
+impl FailoverKeyServer {
+    /// Returns a handle for the given keyservers.
+    ///
+    /// The first keyserver is the primary, the others are fallbacks
+    /// in order of preference.
+    pub fn new(servers: Vec<KeyServer>) -> Result<Self> {
+        if servers.is_empty() {
+            return Err(format_err!("No keyservers given"));
+        }
+
+        let uris = servers.iter().map(|s| s.uri.to_string()).collect();
+        Ok(FailoverKeyServer {
+            servers: Rc::new(RefCell::new(servers)),
+            uris: uris,
+            failover: Failover::new(),
+        })
+    }
+
+    /// Returns a handle for the given URIs.
+    ///
+    /// The first URI is the primary, the others are fallbacks in
+    /// order of preference.
+    pub fn from_uris<S: AsRef<str>>(ctx: &Context, uris: &[S]) -> Result<Self> {
+        Self::new(uris.iter()
+                  .map(|u| KeyServer::new(ctx, u.as_ref()))
+                  .collect::<Result<Vec<_>>>()?)
+    }
+
+    /// Returns the URIs of the keyservers, the primary first.
+    pub fn uris(&self) -> &[String] {
+        &self.uris
+    }
+
+    /// Returns the failover decisions.
+    ///
+    /// Decisions are scoped by the URI of the primary keyserver.  The
+    /// returned handle can be used to retrieve the decisions, and to
+    /// restore decisions made earlier.
+    pub fn failover(&self) -> Failover {
+        self.failover.clone()
+    }
+
+    /// Uses the given failover decisions.
+    ///
+    /// This can be used to share decisions between handles.
+    pub fn set_failover(&mut self, failover: Failover) {
+        self.failover = failover;
+    }
+
+    /// Returns the health statistics of the keyservers.
+    ///
+    /// See [`KeyServer::health`].
+    ///
+    ///   [`KeyServer::health`]: struct.KeyServer.html#method.health
+    pub fn health(&self) -> Vec<Health> {
+        self.servers.borrow().iter().map(|s| s.health()).collect()
+    }
+
+    /// Sets the address family to try first when connecting.
+    ///
+    /// See [`IpPreference`] for details.
+    ///
+    ///   [`IpPreference`]: ../enum.IpPreference.html
+    pub fn set_ip_preference(&mut self, preference: IpPreference) {
+        for s in self.servers.borrow_mut().iter_mut() {
+            s.set_ip_preference(preference);
+        }
+    }
+
+    /// Retrieves the key with the given `keyid`.
+    pub fn get(&mut self, keyid: &KeyID)
+               -> Box<Future<Item=TPK, Error=failure::Error> + 'static> {
+        let keyid = keyid.clone();
+        self.try_in_order(move |ks| ks.get(&keyid))
+    }
+
+    /// Sends the given key to the first keyserver that works.
+    pub fn send(&mut self, key: &TPK)
+                -> Box<Future<Item=(), Error=failure::Error> + 'static> {
+        let key = key.clone();
+        self.try_in_order(move |ks| ks.send(&key))
+    }
+
+    /// Tries `op` on the keyservers until one works.
+    ///
+    /// If all keyservers fail, the last error is returned.
+    fn try_in_order<T, F>(&self, op: F)
+                          -> Box<Future<Item=T, Error=failure::Error> + 'static>
+        where T: 'static,
+              F: Fn(&mut KeyServer)
+                    -> Box<Future<Item=T, Error=failure::Error> + 'static>
+                 + 'static
+    {
+        let servers = self.servers.clone();
+        let uris = self.uris.clone();
+        let failover = self.failover.clone();
+        let order = failover.order(&uris[0], &uris);
+
+        Box::new(loop_fn((order.into_iter(), None), move |(mut order, err)| {
+            let i = match order.next() {
+                Some(i) => i,
+                None => return future::Either::A(future::err(
+                    err.expect("there is at least one keyserver"))),
+            };
+
+            let uris = uris.clone();
+            let failover = failover.clone();
+            let response = op(&mut servers.borrow_mut()[i]);
+            future::Either::B(response.then(move |r| match r {
+                Err(e) if ! is_definite(&e) =>
+                    Ok(Loop::Continue((order, Some(e)))),
+                r => {
+                    failover.success(&uris[0], &uris[0], &uris[i]);
+                    r.map(Loop::Break)
+                },
+            }))
+        }))
+    }
+}
+
+/// Returns whether `e` is a definite answer from a keyserver.
+///
+/// If a keyserver does not have a key, asking the next one will not
+/// help.
+fn is_definite(e: &failure::Error) -> bool {
+    match e.downcast_ref::<Error>() {
+        Some(Error::NotFound) => true,
+        _ => false,
+    }
 }
 
 trait AClient {
@@ -208,7 +585,7 @@ trait AClient {
     fn do_request(&mut self, request: Request<Body>) -> ResponseFuture;
 }
 
-impl AClient for Client<HttpConnector> {
+impl AClient for Client<HttpConnector<Resolver>> {
     fn do_get(&mut self, uri: Url) -> ResponseFuture {
         self.get(url2uri(uri))
     }
@@ -217,7 +594,7 @@ impl AClient for Client<HttpConnector> {
     }
 }
 
-impl AClient for Client<HttpsConnector<HttpConnector>> {
+impl AClient for Client<HttpsConnector<HttpConnector<Resolver>>> {
     fn do_get(&mut self, uri: Url) -> ResponseFuture {
         self.get(url2uri(uri))
     }
@@ -266,8 +643,44 @@ pub mod wkd {
     // https://mailarchive.ietf.org/arch/msg/openpgp/6TxZc2dQFLKXtS0Hzmrk963EteE
     pub fn get<S: AsRef<str>>(email_address: S)
         -> impl Future<Item=Vec<TPK>, Error=failure::Error> {
+        get_with(email_address, None, None)
+    }
+
+    /// Retrieves the TPKs that contain userids with a given email
+    /// address from a Web Key Directory, reporting progress.
+    ///
+    /// Like [`get`], but progress is reported to `progress`, and
+    /// the lookup can be aborted using `token`.
+    ///
+    ///   [`get`]: fn.get.html
+    pub fn get_with<S: AsRef<str>>(email_address: S,
+                                   progress: Option<Box<Progress>>,
+                                   token: Option<CancellationToken>)
+        -> impl Future<Item=Vec<TPK>, Error=failure::Error> {
+        get_with_failover(email_address, &Failover::new(), progress, token)
+    }
+
+    /// Retrieves the TPKs that contain userids with a given email
+    /// address from a Web Key Directory, remembering which method
+    /// works.
+    ///
+    /// Like [`get_with`], but if the Advanced Method fails and the
+    /// Direct Method works, the Direct Method is tried first for
+    /// lookups in the same domain for some time.  Decisions are
+    /// recorded in `failover`, scoped by domain.  See the
+    /// [`failover`] module for details.
+    ///
+    ///   [`get_with`]: fn.get_with.html
+    ///   [`failover`]: ../../failover/index.html
+    pub fn get_with_failover<S: AsRef<str>>(email_address: S,
+                                            failover: &Failover,
+                                            progress: Option<Box<Progress>>,
+                                            token: Option<CancellationToken>)
+        -> impl Future<Item=Vec<TPK>, Error=failure::Error> {
+        const METHODS: [&str; 2] = ["advanced", "direct"];
         let email = email_address.as_ref().to_string();
-        future::lazy(move || -> Result<_> {
+        let failover = failover.clone();
+        monitor::cancellable(future::lazy(move || -> Result<_> {
             // First, prepare URIs and client.
             let wkd_url = net_wkd::Url::from(&email)?;
 
@@ -275,23 +688,270 @@ pub mod wkd {
             let https = HttpsConnector::new(4)?;
             let client = Client::builder().build::<_, hyper::Body>(https);
 
-            Ok((email, client, wkd_url.to_uri(false)?, wkd_url.to_uri(true)?))
-        }).and_then(|(email, client, advanced_uri, direct_uri)| {
-            // First, try the Advanced Method.
-            client.get(advanced_uri)
-                // Fall back to the Direct Method.
+            // The Advanced Method is the primary.  Unless we decided
+            // otherwise, try it first.
+            let domain = wkd_url.domain().to_string();
+            let uris = [wkd_url.to_uri(false)?, wkd_url.to_uri(true)?];
+            let order = failover.order(&domain, &METHODS);
+            let (first, second) = (order[0], order[1]);
+
+            Ok((email, client, domain, failover,
+                (METHODS[first], uris[first].clone()),
+                (METHODS[second], uris[second].clone())))
+        }).and_then(|(email, client, domain, failover,
+                      (first_method, first_uri),
+                      (second_method, second_uri))| {
+            let (f, d) = (failover.clone(), domain.clone());
+            client.get(first_uri)
+                .map(move |res| {
+                    f.success(&d, METHODS[0], first_method);
+                    res
+                })
+                // Fall back to the other method.
                 .or_else(move |_| {
-                    client.get(direct_uri)
+                    client.get(second_uri)
+                        .map(move |res| {
+                            failover.success(&domain, METHODS[0],
+                                             second_method);
+                            res
+                        })
                 })
                 .from_err()
                 .map(|res| (email, res))
-        }).and_then(|(email, res)| {
+        }).and_then(move |(email, res)| {
             // Join the response body.
-            res.into_body().concat2().from_err()
+            monitor::body(res, progress)
                 .map(|body| (email, body))
         }).and_then(|(email, body)| {
             // And parse the response.
             net_wkd::parse_body(&body, &email)
-        })
+        }), token)
+    }
+}
+
+#[cfg(feature = "transparency")]
+pub mod transparency {
+    //! Asynchronously access key transparency logs.
+    use super::*;
+    use base64;
+    use serde_json::{self, Value};
+
+    use transparency::{
+        self as net_transparency,
+        InclusionProof,
+        TreeHead,
+        verify_consistency,
+    };
+
+    /// For accessing key transparency logs.
+    ///
+    /// See the [`transparency`] module for details.
+    ///
+    ///   [`transparency`]: ../../transparency/index.html
+    #[derive(Clone)]
+    pub struct Log {
+        client: Client<HttpsConnector<HttpConnector>>,
+        uri: Url,
+        log_key: TPK,
+    }
+
+    impl Log {
+        /// Returns a handle for the log at `uri`.
+        ///
+        /// Logs must be accessed using https.  `log_key` is used to
+        /// authenticate the log's tree heads.
+        pub fn new(ctx: &Context, uri: &str, log_key: TPK) -> Result<Self> {
+            ctx.network_policy().assert(NetworkPolicy::Encrypted)?;
+
+            let mut uri: Url = uri.parse()?;
+            if uri.scheme() != "https" {
+                return Err(Error::MalformedUri.into());
+            }
+            // Resolve the endpoints relative to the log's URI.
+            if ! uri.path().ends_with('/') {
+                let path = format!("{}/", uri.path());
+                uri.set_path(&path);
+            }
+
+            let https = HttpsConnector::new(4)?;
+            let client = Client::builder().build(https);
+            Ok(Log{client: client, uri: uri, log_key: log_key})
+        }
+
+        /// Retrieves a JSON document from the endpoint `path`.
+        fn fetch(&self, path: &str, query: &[(&str, String)])
+                 -> Box<Future<Item=Value, Error=failure::Error> + 'static> {
+            let mut uri = match self.uri.join(path) {
+                Ok(u) => u,
+                Err(e) => return Box::new(future::err(Error::from(e).into())),
+            };
+            if ! query.is_empty() {
+                let mut pairs = uri.query_pairs_mut();
+                for &(k, ref v) in query {
+                    pairs.append_pair(k, v);
+                }
+            }
+
+            Box::new(self.client.get(url2uri(uri))
+                .from_err()
+                .and_then(|res| {
+                    let status = res.status();
+                    monitor::body(res, None)
+                        .and_then(move |body| match status {
+                            StatusCode::OK =>
+                                future::done(
+                                    serde_json::from_slice::<Value>(&body)
+                                        .map_err(|_| failure::Error::from(
+                                            Error::MalformedResponse))),
+                            StatusCode::NOT_FOUND =>
+                                future::err(Error::NotFound.into()),
+                            n => future::err(Error::HttpStatus(n).into()),
+                        })
+                }))
+        }
+
+        /// Retrieves and authenticates the current tree head.
+        pub fn tree_head(&self)
+                         -> Box<Future<Item=TreeHead, Error=failure::Error>
+                                + 'static> {
+            let log_key = self.log_key.clone();
+            Box::new(self.fetch("ct/v1/get-sth", &[])
+                     .and_then(move |v| {
+                         let head = TreeHead::from_json(&v)?;
+                         head.verify(&log_key)?;
+                         Ok(head)
+                     }))
+        }
+
+        /// Retrieves a proof that `tpk` is included in the tree of
+        /// size `tree_size`.
+        pub fn inclusion_proof(&self, tpk: &TPK, tree_size: u64)
+            -> Box<Future<Item=InclusionProof, Error=failure::Error>
+                   + 'static>
+        {
+            match net_transparency::tpk_leaf_hash(tpk) {
+                Ok(leaf) => self.inclusion_proof_by_hash(&leaf, tree_size),
+                Err(e) => Box::new(future::err(e)),
+            }
+        }
+
+        /// Retrieves a proof that the entry with the leaf hash `leaf`
+        /// is included in the tree of size `tree_size`.
+        fn inclusion_proof_by_hash(&self, leaf: &net_transparency::Hash,
+                                   tree_size: u64)
+            -> Box<Future<Item=InclusionProof, Error=failure::Error>
+                   + 'static>
+        {
+            Box::new(self.fetch("ct/v1/get-proof-by-hash",
+                                &[("hash", base64::encode(leaf)),
+                                  ("tree_size", tree_size.to_string())])
+                     .and_then(|v| InclusionProof::from_json(&v)))
+        }
+
+        /// Retrieves a proof that the tree of size `second` is an
+        /// extension of the tree of size `first`.
+        pub fn consistency_proof(&self, first: u64, second: u64)
+            -> Box<Future<Item=Vec<net_transparency::Hash>,
+                          Error=failure::Error> + 'static>
+        {
+            Box::new(self.fetch("ct/v1/get-sth-consistency",
+                                &[("first", first.to_string()),
+                                  ("second", second.to_string())])
+                     .and_then(|v| net_transparency::consistency_from_json(&v)))
+        }
+
+        /// Checks that `tpk` is in the log.
+        ///
+        /// Retrieves and authenticates the current tree head, and
+        /// checks that `tpk` is included in the tree.  If `known` is
+        /// given, e.g. the tree head returned by a previous audit,
+        /// this also checks that the current tree is an extension of
+        /// the known tree, i.e. that the log has not been rewritten.
+        ///
+        /// On success, the current tree head is returned.  It should
+        /// be stored, and used as `known` in the next audit.  If the
+        /// proofs do not verify, `Error::InvalidProof` is returned.
+        pub fn audit(&self, tpk: &TPK, known: Option<&TreeHead>)
+                     -> Box<Future<Item=TreeHead, Error=failure::Error>
+                            + 'static> {
+            let leaf = match net_transparency::tpk_leaf_hash(tpk) {
+                Ok(h) => h,
+                Err(e) => return Box::new(future::err(e)),
+            };
+            let known = known.cloned();
+            let (log0, log1) = (self.clone(), self.clone());
+
+            Box::new(self.tree_head()
+                .and_then(move |head| -> Box<Future<Item=TreeHead,
+                                                    Error=failure::Error>> {
+                    let known = match known {
+                        Some(k) => k,
+                        None => return Box::new(future::ok(head)),
+                    };
+                    if known.tree_size >= head.tree_size {
+                        return Box::new(future::done(
+                            if known.tree_size == head.tree_size
+                                && known.root_hash == head.root_hash
+                            {
+                                Ok(head)
+                            } else {
+                                Err(Error::InvalidProof.into())
+                            }));
+                    }
+
+                    Box::new(log0.consistency_proof(known.tree_size,
+                                                    head.tree_size)
+                             .and_then(move |proof| {
+                                 if verify_consistency(
+                                     known.tree_size, &known.root_hash,
+                                     head.tree_size, &head.root_hash,
+                                     &proof)
+                                 {
+                                     Ok(head)
+                                 } else {
+                                     Err(Error::InvalidProof.into())
+                                 }
+                             }))
+                })
+                .and_then(move |head| {
+                    log1.inclusion_proof_by_hash(&leaf, head.tree_size)
+                        .and_then(move |proof| {
+                            proof.verify(&leaf, &head)?;
+                            Ok(head)
+                        })
+                }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ip_preference() {
+        let a: IpAddr = "2001:db8::1".parse().unwrap();
+        let b: IpAddr = "192.0.2.1".parse().unwrap();
+        let c: IpAddr = "2001:db8::2".parse().unwrap();
+        let d: IpAddr = "192.0.2.2".parse().unwrap();
+        let health = Health::new();
+
+        let ordered = |preference| {
+            let mut addrs = vec![a, b, c, d];
+            order(&mut addrs, preference, &health);
+            addrs
+        };
+
+        // Within each family, the system's order is retained, and
+        // no address is dropped.
+        assert_eq!(ordered(IpPreference::System), vec![a, b, c, d]);
+        assert_eq!(ordered(IpPreference::IPv4), vec![b, d, a, c]);
+        assert_eq!(ordered(IpPreference::IPv6), vec![a, c, b, d]);
+
+        // Failing servers are tried last, regardless of the
+        // preference.
+        health.failure(b);
+        health.failure(b);
+        assert_eq!(ordered(IpPreference::IPv4), vec![d, a, c, b]);
     }
 }
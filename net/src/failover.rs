@@ -0,0 +1,173 @@
+//! Sticky failover between endpoints.
+//!
+//! Keys can often be retrieved from more than one endpoint, e.g. from
+//! a keyserver and its mirrors, or from a Web Key Directory using the
+//! advanced and the direct method.  The endpoints are tried in the
+//! configured order.  If the first endpoint fails, but a fallback
+//! works, we stick to the fallback for some time, so that subsequent
+//! requests do not wait for a dead primary.  Once the decision
+//! expires, the primary is tried first again.
+//!
+//! Decisions are made per scope, e.g. per list of keyservers or per
+//! domain.  They are kept in memory.  They can be retrieved using
+//! [`Failover::decisions`], and restored using [`Failover::restore`],
+//! e.g. to persist them across processes.
+//!
+//!   [`Failover::decisions`]: struct.Failover.html#method.decisions
+//!   [`Failover::restore`]: struct.Failover.html#method.restore
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// How long to stick to a working fallback by default.
+const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Records that a fallback is used instead of the primary endpoint.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Decision {
+    /// The endpoint to try first.
+    pub endpoint: String,
+
+    /// When the decision expires.
+    pub expires: SystemTime,
+}
+
+impl Decision {
+    /// Returns whether the decision has expired.
+    pub fn is_expired(&self) -> bool {
+        self.expires <= SystemTime::now()
+    }
+}
+
+/// Tracks which endpoints to try first.
+///
+/// `Failover` is a handle.  Clones refer to the same decisions.
+#[derive(Clone, Debug)]
+pub struct Failover {
+    ttl: Duration,
+    decisions: Arc<Mutex<HashMap<String, Decision>>>,
+}
+
+impl Default for Failover {
+    fn default() -> Self {
+        Self::with_ttl(DEFAULT_TTL)
+    }
+}
+
+impl Failover {
+    /// Returns an empty tracker.
+    ///
+    /// Working fallbacks are used for an hour.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns an empty tracker using working fallbacks for `ttl`.
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Failover {
+            ttl: ttl,
+            decisions: Default::default(),
+        }
+    }
+
+    /// Returns the decisions that have not expired.
+    pub fn decisions(&self) -> Vec<(String, Decision)> {
+        self.decisions.lock().expect("poisoned lock").iter()
+            .filter(|&(_, d)| ! d.is_expired())
+            .map(|(s, d)| (s.clone(), d.clone()))
+            .collect()
+    }
+
+    /// Returns the decision for the given scope, if any.
+    pub fn get(&self, scope: &str) -> Option<Decision> {
+        self.decisions.lock().expect("poisoned lock").get(scope)
+            .filter(|d| ! d.is_expired())
+            .cloned()
+    }
+
+    /// Restores previously retrieved decisions.
+    ///
+    /// Decisions for scopes that are already known are replaced.
+    pub fn restore<I>(&self, decisions: I)
+        where I: IntoIterator<Item=(String, Decision)>
+    {
+        self.decisions.lock().expect("poisoned lock").extend(decisions)
+    }
+
+    /// Returns the order in which to try `endpoints`.
+    ///
+    /// The endpoints are returned as indices into `endpoints`.  The
+    /// endpoint we decided to stick to comes first, the others
+    /// retain their order.
+    pub(crate) fn order<S: AsRef<str>>(&self, scope: &str, endpoints: &[S])
+                                       -> Vec<usize> {
+        let mut order = (0..endpoints.len()).collect::<Vec<_>>();
+        if let Some(d) = self.get(scope) {
+            // The sort is stable.
+            order.sort_by_key(|&i| endpoints[i].as_ref() != d.endpoint);
+        }
+        order
+    }
+
+    /// Records that `endpoint` worked.
+    ///
+    /// If it is not the primary, we stick to it.  An existing
+    /// decision for the same endpoint is not extended, so that the
+    /// primary is eventually tried again.
+    pub(crate) fn success(&self, scope: &str, primary: &str, endpoint: &str) {
+        let mut decisions = self.decisions.lock().expect("poisoned lock");
+        if endpoint == primary {
+            decisions.remove(scope);
+            return;
+        }
+
+        match decisions.get(scope) {
+            Some(d) if d.endpoint == endpoint && ! d.is_expired() => return,
+            _ => (),
+        }
+        decisions.insert(scope.into(), Decision {
+            endpoint: endpoint.into(),
+            expires: SystemTime::now() + self.ttl,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sticky() {
+        let endpoints = ["primary", "mirror-1", "mirror-2"];
+        let failover = Failover::new();
+        assert_eq!(failover.order("s", &endpoints), vec![0, 1, 2]);
+
+        // The primary failed, the second mirror worked.
+        failover.success("s", endpoints[0], endpoints[2]);
+        assert_eq!(failover.order("s", &endpoints), vec![2, 0, 1]);
+        // Other scopes are not affected.
+        assert_eq!(failover.order("t", &endpoints), vec![0, 1, 2]);
+
+        // The decision is not extended.
+        let expires = failover.get("s").unwrap().expires;
+        failover.success("s", endpoints[0], endpoints[2]);
+        assert_eq!(failover.get("s").unwrap().expires, expires);
+
+        // Restoring decisions.
+        let other = Failover::new();
+        other.restore(failover.decisions());
+        assert_eq!(other.get("s"), failover.get("s"));
+
+        // Once the primary works again, it is tried first.
+        failover.success("s", endpoints[0], endpoints[0]);
+        assert_eq!(failover.get("s"), None);
+        assert_eq!(failover.order("s", &endpoints), vec![0, 1, 2]);
+
+        // Expired decisions are ignored.
+        let failover = Failover::with_ttl(Duration::from_secs(0));
+        failover.success("s", endpoints[0], endpoints[1]);
+        assert_eq!(failover.get("s"), None);
+        assert_eq!(failover.order("s", &endpoints), vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,169 @@
+//! Tracks the health of keyservers.
+//!
+//! Keyserver pools like `hkps.pool.sks-keyservers.net` resolve to
+//! many independently operated servers.  Some of them are often
+//! unreachable or slow.  Therefore, we record the outcome and latency
+//! of every request per server address, and try healthy servers
+//! first when connecting.
+//!
+//! The statistics are kept in memory.  They can be retrieved using
+//! [`Health::stats`], and restored using [`Health::restore`], e.g. to
+//! persist them across processes.
+//!
+//!   [`Health::stats`]: struct.Health.html#method.stats
+//!   [`Health::restore`]: struct.Health.html#method.restore
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// After this many consecutive failures, a server is considered
+/// failing.
+const FAILING_AFTER: u32 = 2;
+
+/// Failing servers are considered again after this time.
+const RETRY_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// Statistics about a keyserver.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServerStats {
+    /// Counts successful requests.
+    pub successes: u64,
+
+    /// Counts failed requests.
+    pub failures: u64,
+
+    /// Counts failed requests since the last successful one.
+    pub consecutive_failures: u32,
+
+    /// Moving average of the latency of successful requests.
+    pub latency: Option<Duration>,
+
+    /// Records the time of the last request.
+    pub last_seen: Option<SystemTime>,
+}
+
+impl ServerStats {
+    /// Returns whether the server recently failed repeatedly.
+    pub fn is_failing(&self) -> bool {
+        self.consecutive_failures >= FAILING_AFTER
+            && self.last_seen
+                .and_then(|t| t.elapsed().ok())
+                .map(|d| d < RETRY_AFTER)
+                .unwrap_or(false)
+    }
+}
+
+/// Tracks the health of keyservers.
+///
+/// `Health` is a handle.  Clones refer to the same statistics.
+#[derive(Clone, Debug, Default)]
+pub struct Health {
+    stats: Arc<Mutex<HashMap<IpAddr, ServerStats>>>,
+}
+
+impl Health {
+    /// Returns an empty tracker.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the statistics of all known servers.
+    pub fn stats(&self) -> Vec<(IpAddr, ServerStats)> {
+        self.stats.lock().expect("poisoned lock").iter()
+            .map(|(a, s)| (*a, s.clone()))
+            .collect()
+    }
+
+    /// Returns the statistics of the given server.
+    pub fn get(&self, addr: &IpAddr) -> Option<ServerStats> {
+        self.stats.lock().expect("poisoned lock").get(addr).cloned()
+    }
+
+    /// Restores previously retrieved statistics.
+    ///
+    /// Statistics of servers that are already known are replaced.
+    pub fn restore<I>(&self, stats: I)
+        where I: IntoIterator<Item=(IpAddr, ServerStats)>
+    {
+        self.stats.lock().expect("poisoned lock").extend(stats)
+    }
+
+    /// Records a successful request.
+    pub(crate) fn success(&self, addr: IpAddr, latency: Duration) {
+        let mut stats = self.stats.lock().expect("poisoned lock");
+        let s = stats.entry(addr).or_insert_with(Default::default);
+        s.successes += 1;
+        s.consecutive_failures = 0;
+        s.latency = Some(match s.latency {
+            // Weigh the new sample with 1/4.
+            Some(l) => (l * 3 + latency) / 4,
+            None => latency,
+        });
+        s.last_seen = Some(SystemTime::now());
+    }
+
+    /// Records a failed request.
+    pub(crate) fn failure(&self, addr: IpAddr) {
+        let mut stats = self.stats.lock().expect("poisoned lock");
+        let s = stats.entry(addr).or_insert_with(Default::default);
+        s.failures += 1;
+        s.consecutive_failures += 1;
+        s.last_seen = Some(SystemTime::now());
+    }
+
+    /// Orders `addrs` so that healthy servers come first.
+    ///
+    /// The sort is stable, hence servers of equal health retain their
+    /// order.
+    pub(crate) fn sort(&self, addrs: &mut Vec<IpAddr>) {
+        let stats = self.stats.lock().expect("poisoned lock");
+        addrs.sort_by_key(|a| match stats.get(a) {
+            Some(s) if s.is_failing() => 2,
+            Some(s) if s.consecutive_failures == 0 => 0,
+            _ => 1,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordering() {
+        let a: IpAddr = "192.0.2.1".parse().unwrap();
+        let b: IpAddr = "192.0.2.2".parse().unwrap();
+        let c: IpAddr = "2001:db8::1".parse().unwrap();
+
+        let health = Health::new();
+        let mut addrs = vec![a, b, c];
+        health.sort(&mut addrs);
+        assert_eq!(addrs, vec![a, b, c]);
+
+        health.failure(a);
+        health.failure(a);
+        health.success(c, Duration::from_millis(100));
+        health.sort(&mut addrs);
+        assert_eq!(addrs, vec![c, b, a]);
+        assert!(health.get(&a).unwrap().is_failing());
+
+        // A single success redeems a server.
+        health.success(a, Duration::from_millis(300));
+        let s = health.get(&a).unwrap();
+        assert!(! s.is_failing());
+        assert_eq!(s.successes, 1);
+        assert_eq!(s.failures, 2);
+
+        health.success(a, Duration::from_millis(100));
+        assert_eq!(health.get(&a).unwrap().latency,
+                   Some(Duration::from_millis(250)));
+
+        // Restoring statistics.
+        let other = Health::new();
+        other.restore(health.stats());
+        assert_eq!(other.get(&a), health.get(&a));
+        assert_eq!(other.get(&b), None);
+    }
+}
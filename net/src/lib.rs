@@ -36,6 +36,8 @@ extern crate sequoia_openpgp as openpgp;
 extern crate sequoia_core;
 extern crate sequoia_rfc2822 as rfc2822;
 
+#[cfg(feature = "transparency")]
+extern crate base64;
 #[macro_use]
 extern crate failure;
 extern crate futures;
@@ -48,6 +50,8 @@ extern crate tokio_core;
 extern crate tokio_io;
 #[macro_use]
 extern crate percent_encoding;
+#[cfg(feature = "transparency")]
+extern crate serde_json;
 extern crate url;
 extern crate zbase32;
 
@@ -61,11 +65,19 @@ use url::Url;
 
 use openpgp::KeyID;
 use openpgp::TPK;
+use openpgp::constants::HashAlgorithm;
+use openpgp::packet::Signature;
 use sequoia_core::Context;
 
 pub mod async;
 use async::url2uri;
+pub mod failover;
+pub mod health;
+#[cfg(feature = "transparency")]
+pub mod transparency;
 pub mod wkd;
+mod monitor;
+pub use monitor::{CancellationToken, Progress};
 
 /// For accessing keyservers using HKP.
 pub struct KeyServer {
@@ -102,6 +114,24 @@ impl KeyServer {
         Self::with_cert(ctx, uri, cert)
     }
 
+    /// Sets the address family to try first when connecting.
+    ///
+    /// See [`IpPreference`] for details.
+    ///
+    ///   [`IpPreference`]: enum.IpPreference.html
+    pub fn set_ip_preference(&mut self, preference: IpPreference) {
+        self.ks.set_ip_preference(preference)
+    }
+
+    /// Returns the health statistics of the servers contacted so far.
+    ///
+    /// See the [`health`] module for details.
+    ///
+    ///   [`health`]: health/index.html
+    pub fn health(&self) -> health::Health {
+        self.ks.health()
+    }
+
     /// Retrieves the key with the given `keyid`.
     pub fn get(&mut self, keyid: &KeyID) -> Result<TPK> {
         self.core.run(
@@ -109,12 +139,82 @@ impl KeyServer {
         )
     }
 
+    /// Retrieves the key with the given `keyid`, reporting progress.
+    ///
+    /// Progress is reported to `progress`.  The download can be
+    /// aborted using `token`.
+    pub fn get_with(&mut self, keyid: &KeyID,
+                    progress: Option<Box<Progress>>,
+                    token: Option<CancellationToken>)
+                    -> Result<TPK> {
+        self.core.run(
+            self.ks.get_with(keyid, progress, token)
+        )
+    }
+
     /// Sends the given key to the server.
     pub fn send(&mut self, key: &TPK) -> Result<()> {
         self.core.run(
             self.ks.send(key)
         )
     }
+
+    /// Asks the server to timestamp a document.
+    ///
+    /// See [`async::KeyServer::timestamp`] for details.
+    ///
+    ///   [`async::KeyServer::timestamp`]: async/struct.KeyServer.html#method.timestamp
+    pub fn timestamp(&mut self, hash_algo: HashAlgorithm, digest: &[u8])
+                     -> Result<Signature> {
+        self.core.run(
+            self.ks.timestamp(hash_algo, digest)
+        )
+    }
+}
+
+/// For accessing a list of keyservers with sticky failover.
+///
+/// See [`async::FailoverKeyServer`] for details.
+///
+///   [`async::FailoverKeyServer`]: async/struct.FailoverKeyServer.html
+pub struct FailoverKeyServer {
+    core: Core,
+    ks: async::FailoverKeyServer,
+}
+
+impl FailoverKeyServer {
+    /// Returns a handle for the given URIs.
+    ///
+    /// The first URI is the primary, the others are fallbacks in
+    /// order of preference.
+    pub fn new<S: AsRef<str>>(ctx: &Context, uris: &[S]) -> Result<Self> {
+        let core = Core::new()?;
+        let ks = async::FailoverKeyServer::from_uris(ctx, uris)?;
+        Ok(FailoverKeyServer{core: core, ks: ks})
+    }
+
+    /// Returns the failover decisions.
+    ///
+    /// See the [`failover`] module for details.
+    ///
+    ///   [`failover`]: failover/index.html
+    pub fn failover(&self) -> failover::Failover {
+        self.ks.failover()
+    }
+
+    /// Retrieves the key with the given `keyid`.
+    pub fn get(&mut self, keyid: &KeyID) -> Result<TPK> {
+        self.core.run(
+            self.ks.get(keyid)
+        )
+    }
+
+    /// Sends the given key to the first keyserver that works.
+    pub fn send(&mut self, key: &TPK) -> Result<()> {
+        self.core.run(
+            self.ks.send(key)
+        )
+    }
 }
 
 trait AClient {
@@ -143,6 +243,32 @@ impl AClient for Client<HttpsConnector<HttpConnector>> {
 /// Results for sequoia-net.
 pub type Result<T> = ::std::result::Result<T, failure::Error>;
 
+/// Which address family to try first when connecting.
+///
+/// Keyservers are frequently reachable over both IPv4 and IPv6, but
+/// some networks can only reach one of them.  Therefore, we connect
+/// to all addresses a name resolves to, starting with the preferred
+/// family.  If that does not succeed within a short time, we race
+/// connections using the other family ("Happy Eyeballs", see [RFC
+/// 6555]), and use whichever is established first.
+///
+///   [RFC 6555]: https://tools.ietf.org/html/rfc6555
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpPreference {
+    /// Use the order returned by the system's resolver.
+    System,
+    /// Try IPv4 addresses first.
+    IPv4,
+    /// Try IPv6 addresses first.
+    IPv6,
+}
+
+impl Default for IpPreference {
+    fn default() -> Self {
+        IpPreference::System
+    }
+}
+
 #[derive(Fail, Debug)]
 /// Errors returned from the network routines.
 pub enum Error {
@@ -174,6 +300,14 @@ pub enum Error {
     #[fail(display = "TLS Error")]
     TlsError(native_tls::Error),
 
+    /// The operation was cancelled.
+    #[fail(display = "Operation cancelled")]
+    Cancelled,
+
+    /// A transparency log's tree head or proof does not verify.
+    #[fail(display = "Transparency log proof does not verify")]
+    InvalidProof,
+
     /// wkd errors:
     /// An email address is malformed
     #[fail(display = "Malformed email address {}", _0)]
@@ -223,5 +357,12 @@ mod tests {
         assert!(KeyServer::new(&ctx, "keys.openpgp.org").is_ok());
         assert!(KeyServer::new(&ctx, "hkp://keys.openpgp.org").is_err());
         assert!(KeyServer::new(&ctx, "hkps://keys.openpgp.org").is_ok());
+
+        let empty: &[&str] = &[];
+        assert!(FailoverKeyServer::new(&ctx, empty).is_err());
+        assert!(FailoverKeyServer::new(
+            &ctx, &["keys.openpgp.org", "hkp://keys.openpgp.org"]).is_err());
+        assert!(FailoverKeyServer::new(
+            &ctx, &["keys.openpgp.org", "pgp.mit.edu"]).is_ok());
     }
 }
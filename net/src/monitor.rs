@@ -0,0 +1,147 @@
+//! Progress reporting and cancellation of network operations.
+
+use failure;
+use futures::future::Either;
+use futures::task::{self, Task};
+use futures::{Async, Future, Poll, Stream};
+use hyper::header::CONTENT_LENGTH;
+use hyper::{Body, Response};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::Error;
+
+/// Receives progress reports of downloads.
+pub trait Progress: Send {
+    /// Reports that `received` bytes have been downloaded so far.
+    ///
+    /// `total` is the size of the download, if the server announced
+    /// it.
+    fn progress(&mut self, received: u64, total: Option<u64>);
+}
+
+impl<F: FnMut(u64, Option<u64>) + Send> Progress for F {
+    fn progress(&mut self, received: u64, total: Option<u64>) {
+        self(received, total)
+    }
+}
+
+/// Allows cancelling network operations.
+///
+/// Clones of a token share their state, so a token can be handed to
+/// an operation, and a clone can be used to cancel it from another
+/// thread.  Cancelled operations fail with `Error::Cancelled`.
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    waiting: Mutex<Vec<Task>>,
+}
+
+impl CancellationToken {
+    /// Returns a new token.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Cancels all operations using this token.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        for task in self.0.waiting.lock().expect("poisoned lock").drain(..) {
+            task.notify();
+        }
+    }
+
+    /// Returns whether this token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns a future that resolves once the token is cancelled.
+    fn cancelled(&self) -> Cancelled {
+        Cancelled(self.clone())
+    }
+}
+
+/// Resolves once a token is cancelled.
+struct Cancelled(CancellationToken);
+
+impl Future for Cancelled {
+    type Item = ();
+    type Error = failure::Error;
+
+    fn poll(&mut self) -> Poll<(), failure::Error> {
+        let mut waiting = (self.0).0.waiting.lock().expect("poisoned lock");
+        // Check under the lock, so that we do not miss a
+        // notification.
+        if self.0.is_cancelled() {
+            return Ok(Async::Ready(()));
+        }
+        if ! waiting.iter().any(|t| t.will_notify_current()) {
+            waiting.push(task::current());
+        }
+        Ok(Async::NotReady)
+    }
+}
+
+/// Makes `f` fail with `Error::Cancelled` once `token` is cancelled.
+pub(crate) fn cancellable<F>(f: F, token: Option<CancellationToken>)
+    -> Box<Future<Item=F::Item, Error=failure::Error> + 'static>
+    where F: Future<Error=failure::Error> + 'static
+{
+    let token = match token {
+        Some(t) => t,
+        None => return Box::new(f),
+    };
+
+    Box::new(f.select2(token.cancelled()).then(|r| match r {
+        Ok(Either::A((v, _))) => Ok(v),
+        Ok(Either::B(_)) => Err(Error::Cancelled.into()),
+        Err(Either::A((e, _))) | Err(Either::B((e, _))) => Err(e),
+    }))
+}
+
+/// Collects the body of `res`, reporting progress.
+pub(crate) fn body(res: Response<Body>, mut progress: Option<Box<Progress>>)
+    -> Box<Future<Item=Vec<u8>, Error=failure::Error> + 'static>
+{
+    let total = res.headers().get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+
+    Box::new(res.into_body().from_err::<failure::Error>()
+             .fold(Vec::new(), move |mut buf, chunk| {
+                 buf.extend_from_slice(&chunk);
+                 if let Some(ref mut p) = progress {
+                     p.progress(buf.len() as u64, total);
+                 }
+                 Ok::<_, failure::Error>(buf)
+             }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use tokio_core::reactor::Core;
+
+    #[test]
+    fn cancel() {
+        let mut core = Core::new().unwrap();
+        let token = CancellationToken::new();
+
+        // Not cancelled.
+        let f = cancellable(future::ok::<_, failure::Error>(1),
+                            Some(token.clone()));
+        assert_eq!(core.run(f).unwrap(), 1);
+
+        // Cancelled before the operation completes.
+        let f = cancellable(future::empty::<(), failure::Error>(),
+                            Some(token.clone()));
+        token.cancel();
+        assert!(token.is_cancelled());
+        assert!(core.run(f).is_err());
+    }
+}
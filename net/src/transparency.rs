@@ -0,0 +1,497 @@
+//! Key transparency logs.
+//!
+//! A key transparency log is an append-only log of OpenPGP keys.
+//! Like the logs used for [Certificate Transparency], it is organized
+//! as a Merkle tree, and the log operator periodically signs the root
+//! of the tree.  Using proofs provided by the log, clients can check
+//! that a key they discovered is included in the log, and that the
+//! log has only been appended to since they last looked at it.
+//! Hence, if a log presents different keys to different users, this
+//! can be detected by comparing tree heads, e.g. by gossiping them.
+//! This allows key discovery to be backed by auditable logs rather
+//! than trust on first use alone.
+//!
+//! Logs are accessed using the protocol described in Section 4 of
+//! [RFC 6962], with two differences.  First, a log entry is a TPK
+//! serialized as transferable public key, and its leaf hash is
+//! computed over the serialized TPK.  Second, the tree head is signed
+//! using an OpenPGP signature made by the log's key, which is
+//! transmitted as base64-encoded signature packet.
+//!
+//! This module is only available if the `transparency` feature is
+//! enabled.
+//!
+//!   [Certificate Transparency]: https://www.certificate-transparency.org/
+//!   [RFC 6962]: https://tools.ietf.org/html/rfc6962
+//!
+//! # Example
+//!
+//! ```no_run
+//! # extern crate sequoia_openpgp as openpgp;
+//! # extern crate sequoia_core;
+//! # extern crate sequoia_net;
+//! # use openpgp::TPK;
+//! # use openpgp::parse::Parse;
+//! # use sequoia_core::Context;
+//! # use sequoia_net::{KeyServer, Result};
+//! # use sequoia_net::transparency::Log;
+//! # fn main() { f().unwrap(); }
+//! # fn f() -> Result<()> {
+//! # let log_key = TPK::from_file("log.pgp")?;
+//! # let keyid = openpgp::KeyID::from_hex("31855247603831FD")?;
+//! let ctx = Context::new()?;
+//! let mut ks = KeyServer::sks_pool(&ctx)?;
+//! let tpk = ks.get(&keyid)?;
+//!
+//! let mut log = Log::new(&ctx, "https://log.example.org/", log_key)?;
+//! // Checks that `tpk` is in the log.  Pass the tree head returned
+//! // by the previous audit to also check that the log is consistent
+//! // with what we have seen before.
+//! let head = log.audit(&tpk, None)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use base64;
+use nettle::Hash as NettleHash;
+use nettle::hash::Sha256;
+use serde_json::Value;
+use tokio_core::reactor::Core;
+
+use openpgp::TPK;
+use openpgp::packet::Signature;
+use openpgp::parse::Parse;
+use openpgp::serialize::SerializeInto;
+use sequoia_core::Context;
+
+use super::{Error, Result, async};
+
+/// A node in the Merkle tree, i.e. a SHA256 digest.
+pub type Hash = [u8; 32];
+
+/// Computes the hash of a leaf containing `entry`.
+pub fn leaf_hash(entry: &[u8]) -> Hash {
+    let mut h = Sha256::default();
+    h.update(&[0]);
+    h.update(entry);
+    let mut digest = [0; 32];
+    h.digest(&mut digest);
+    digest
+}
+
+/// Computes the hash of the leaf containing `tpk`.
+pub fn tpk_leaf_hash(tpk: &TPK) -> Result<Hash> {
+    Ok(leaf_hash(&tpk.to_vec()?))
+}
+
+/// Computes the hash of an interior node.
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut h = Sha256::default();
+    h.update(&[1]);
+    h.update(left);
+    h.update(right);
+    let mut digest = [0; 32];
+    h.digest(&mut digest);
+    digest
+}
+
+/// Verifies that `leaf` is at position `index` in the tree of size
+/// `tree_size` with the given `root`.
+///
+/// `path` is the audit path, see Section 2.1.1 of [RFC 6962].  This
+/// implements the algorithm of Section 2.1.3.2 of [RFC 9162].
+///
+///   [RFC 6962]: https://tools.ietf.org/html/rfc6962#section-2.1.1
+///   [RFC 9162]: https://tools.ietf.org/html/rfc9162#section-2.1.3.2
+pub fn verify_inclusion(leaf: &Hash, index: u64, tree_size: u64,
+                        path: &[Hash], root: &Hash) -> bool {
+    if index >= tree_size {
+        return false;
+    }
+
+    let (mut fn_, mut sn) = (index, tree_size - 1);
+    let mut r = *leaf;
+    for p in path {
+        if sn == 0 {
+            return false;
+        }
+
+        if fn_ & 1 == 1 || fn_ == sn {
+            r = node_hash(p, &r);
+            while fn_ & 1 == 0 && fn_ != 0 {
+                fn_ >>= 1;
+                sn >>= 1;
+            }
+        } else {
+            r = node_hash(&r, p);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+
+    sn == 0 && r == *root
+}
+
+/// Verifies that the tree of size `second_size` with root
+/// `second_root` is an extension of the tree of size `first_size`
+/// with root `first_root`.
+///
+/// `proof` is the consistency proof, see Section 2.1.2 of [RFC 6962].
+/// This implements the algorithm of Section 2.1.4.2 of [RFC 9162].
+///
+///   [RFC 6962]: https://tools.ietf.org/html/rfc6962#section-2.1.2
+///   [RFC 9162]: https://tools.ietf.org/html/rfc9162#section-2.1.4.2
+pub fn verify_consistency(first_size: u64, first_root: &Hash,
+                          second_size: u64, second_root: &Hash,
+                          proof: &[Hash]) -> bool {
+    if first_size > second_size {
+        return false;
+    }
+    if first_size == second_size {
+        return proof.is_empty() && first_root == second_root;
+    }
+    if first_size == 0 {
+        // The empty tree is consistent with every tree.
+        return proof.is_empty();
+    }
+    if proof.is_empty() {
+        return false;
+    }
+
+    let mut path = Vec::with_capacity(proof.len() + 1);
+    if first_size.is_power_of_two() {
+        path.push(*first_root);
+    }
+    path.extend_from_slice(proof);
+
+    let (mut fn_, mut sn) = (first_size - 1, second_size - 1);
+    while fn_ & 1 == 1 {
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+
+    let (mut fr, mut sr) = (path[0], path[0]);
+    for c in &path[1..] {
+        if sn == 0 {
+            return false;
+        }
+
+        if fn_ & 1 == 1 || fn_ == sn {
+            fr = node_hash(c, &fr);
+            sr = node_hash(c, &sr);
+            while fn_ & 1 == 0 && fn_ != 0 {
+                fn_ >>= 1;
+                sn >>= 1;
+            }
+        } else {
+            sr = node_hash(&sr, c);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+
+    sn == 0 && fr == *first_root && sr == *second_root
+}
+
+/// A signed tree head.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeHead {
+    /// The number of entries in the tree.
+    pub tree_size: u64,
+
+    /// When the tree head was signed, in milliseconds since the
+    /// epoch.
+    pub timestamp: u64,
+
+    /// The root of the tree.
+    pub root_hash: Hash,
+
+    /// The log's signature over the tree head.
+    pub signature: Signature,
+}
+
+impl TreeHead {
+    /// Parses the response of `get-sth`.
+    pub(crate) fn from_json(v: &Value) -> Result<Self> {
+        let signature = base64::decode(json_str(v, "tree_head_signature")?)
+            .map_err(|_| Error::MalformedResponse)?;
+        Ok(TreeHead {
+            tree_size: json_u64(v, "tree_size")?,
+            timestamp: json_u64(v, "timestamp")?,
+            root_hash: json_hash(&v["sha256_root_hash"])?,
+            signature: Signature::from_bytes(&signature)
+                .map_err(|_| Error::MalformedResponse)?,
+        })
+    }
+
+    /// Returns the data covered by the signature.
+    ///
+    /// This is the `TreeHeadSignature` structure of Section 3.5 of
+    /// RFC 6962.
+    fn signed_data(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(2 + 8 + 8 + 32);
+        data.push(0); // Version v1.
+        data.push(1); // Signature type tree_hash.
+        data.extend_from_slice(&u64_be(self.timestamp));
+        data.extend_from_slice(&u64_be(self.tree_size));
+        data.extend_from_slice(&self.root_hash);
+        data
+    }
+
+    /// Verifies that the tree head has been signed by `log_key`.
+    pub fn verify(&self, log_key: &TPK) -> Result<()> {
+        let data = self.signed_data();
+        for (_, _, key) in log_key.keys_valid().signing_capable() {
+            if self.signature.verify_message(key, &data).unwrap_or(false) {
+                return Ok(());
+            }
+        }
+        Err(Error::InvalidProof.into())
+    }
+}
+
+/// Proves that an entry is included in the log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InclusionProof {
+    /// The position of the entry in the log.
+    pub leaf_index: u64,
+
+    /// The audit path.
+    pub audit_path: Vec<Hash>,
+}
+
+impl InclusionProof {
+    /// Parses the response of `get-proof-by-hash`.
+    pub(crate) fn from_json(v: &Value) -> Result<Self> {
+        Ok(InclusionProof {
+            leaf_index: json_u64(v, "leaf_index")?,
+            audit_path: json_hashes(&v["audit_path"])?,
+        })
+    }
+
+    /// Verifies that `leaf` is included in the tree described by
+    /// `head`.
+    pub fn verify(&self, leaf: &Hash, head: &TreeHead) -> Result<()> {
+        if verify_inclusion(leaf, self.leaf_index, head.tree_size,
+                            &self.audit_path, &head.root_hash) {
+            Ok(())
+        } else {
+            Err(Error::InvalidProof.into())
+        }
+    }
+}
+
+/// Parses the response of `get-sth-consistency`.
+pub(crate) fn consistency_from_json(v: &Value) -> Result<Vec<Hash>> {
+    json_hashes(&v["consistency"])
+}
+
+/// Returns the string member `name` of `v`.
+fn json_str<'a>(v: &'a Value, name: &str) -> Result<&'a str> {
+    Ok(v[name].as_str().ok_or(Error::MalformedResponse)?)
+}
+
+/// Returns the integer member `name` of `v`.
+fn json_u64(v: &Value, name: &str) -> Result<u64> {
+    Ok(v[name].as_u64().ok_or(Error::MalformedResponse)?)
+}
+
+/// Decodes a base64-encoded hash.
+fn json_hash(v: &Value) -> Result<Hash> {
+    let bytes = v.as_str().and_then(|s| base64::decode(s).ok())
+        .ok_or(Error::MalformedResponse)?;
+    if bytes.len() != 32 {
+        return Err(Error::MalformedResponse.into());
+    }
+    let mut hash = [0; 32];
+    hash.copy_from_slice(&bytes);
+    Ok(hash)
+}
+
+/// Decodes an array of base64-encoded hashes.
+fn json_hashes(v: &Value) -> Result<Vec<Hash>> {
+    v.as_array().ok_or(Error::MalformedResponse)?
+        .iter().map(json_hash).collect()
+}
+
+/// Returns `x` in big endian byte order.
+fn u64_be(x: u64) -> [u8; 8] {
+    let mut b = [0; 8];
+    for i in 0..8 {
+        b[i] = (x >> (56 - 8 * i)) as u8;
+    }
+    b
+}
+
+/// For accessing key transparency logs.
+///
+/// See [`async::transparency::Log`] for details.
+///
+///   [`async::transparency::Log`]: ../async/transparency/struct.Log.html
+pub struct Log {
+    core: Core,
+    log: async::transparency::Log,
+}
+
+impl Log {
+    /// Returns a handle for the log at `uri`.
+    ///
+    /// `log_key` is used to authenticate the log's tree heads.
+    pub fn new(ctx: &Context, uri: &str, log_key: TPK) -> Result<Self> {
+        let core = Core::new()?;
+        let log = async::transparency::Log::new(ctx, uri, log_key)?;
+        Ok(Log{core: core, log: log})
+    }
+
+    /// Retrieves and authenticates the current tree head.
+    pub fn tree_head(&mut self) -> Result<TreeHead> {
+        self.core.run(
+            self.log.tree_head()
+        )
+    }
+
+    /// Checks that `tpk` is in the log.
+    ///
+    /// See [`async::transparency::Log::audit`] for details.
+    ///
+    ///   [`async::transparency::Log::audit`]: ../async/transparency/struct.Log.html#method.audit
+    pub fn audit(&mut self, tpk: &TPK, known: Option<&TreeHead>)
+                 -> Result<TreeHead> {
+        self.core.run(
+            self.log.audit(tpk, known)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the largest power of two smaller than `n`.
+    fn split(n: usize) -> usize {
+        let mut k = 1;
+        while k << 1 < n {
+            k <<= 1;
+        }
+        k
+    }
+
+    /// The Merkle Tree Hash, see Section 2.1 of RFC 6962.
+    fn mth(leaves: &[Hash]) -> Hash {
+        match leaves.len() {
+            0 => unreachable!(),
+            1 => leaves[0],
+            n => {
+                let k = split(n);
+                node_hash(&mth(&leaves[..k]), &mth(&leaves[k..]))
+            },
+        }
+    }
+
+    /// The audit path, see Section 2.1.1 of RFC 6962.
+    fn path(m: usize, leaves: &[Hash]) -> Vec<Hash> {
+        let n = leaves.len();
+        if n <= 1 {
+            return vec![];
+        }
+        let k = split(n);
+        if m < k {
+            let mut p = path(m, &leaves[..k]);
+            p.push(mth(&leaves[k..]));
+            p
+        } else {
+            let mut p = path(m - k, &leaves[k..]);
+            p.push(mth(&leaves[..k]));
+            p
+        }
+    }
+
+    /// The consistency proof, see Section 2.1.2 of RFC 6962.
+    fn subproof(m: usize, leaves: &[Hash], b: bool) -> Vec<Hash> {
+        let n = leaves.len();
+        if m == n {
+            return if b { vec![] } else { vec![mth(leaves)] };
+        }
+        let k = split(n);
+        if m <= k {
+            let mut p = subproof(m, &leaves[..k], b);
+            p.push(mth(&leaves[k..]));
+            p
+        } else {
+            let mut p = subproof(m - k, &leaves[k..], false);
+            p.push(mth(&leaves[..k]));
+            p
+        }
+    }
+
+    #[test]
+    fn inclusion() {
+        let leaves = (0..13u8).map(|i| leaf_hash(&[i])).collect::<Vec<_>>();
+        for n in 1..leaves.len() + 1 {
+            let root = mth(&leaves[..n]);
+            for m in 0..n {
+                let p = path(m, &leaves[..n]);
+                assert!(verify_inclusion(&leaves[m], m as u64, n as u64,
+                                         &p, &root));
+
+                // Wrong leaf, index, root, or path.
+                let other = leaf_hash(b"other");
+                assert!(! verify_inclusion(&other, m as u64, n as u64,
+                                           &p, &root));
+                assert!(! verify_inclusion(&leaves[m], n as u64, n as u64,
+                                           &p, &root));
+                assert!(! verify_inclusion(&leaves[m], m as u64, n as u64,
+                                           &p, &other));
+                if n > 1 {
+                    assert!(! verify_inclusion(&leaves[m], m as u64,
+                                               n as u64, &p[1..], &root));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn consistency() {
+        let leaves = (0..13u8).map(|i| leaf_hash(&[i])).collect::<Vec<_>>();
+        for n in 1..leaves.len() + 1 {
+            let second = mth(&leaves[..n]);
+            for m in 1..n + 1 {
+                let first = mth(&leaves[..m]);
+                let p = subproof(m, &leaves[..n], true);
+                assert!(verify_consistency(m as u64, &first, n as u64,
+                                           &second, &p));
+
+                // Forked or rewritten logs.
+                let other = leaf_hash(b"other");
+                assert!(! verify_consistency(m as u64, &other, n as u64,
+                                             &second, &p));
+                assert!(! verify_consistency(m as u64, &first, n as u64,
+                                             &other, &p));
+                if m < n {
+                    assert!(! verify_consistency(n as u64, &second,
+                                                 m as u64, &first, &p));
+                    assert!(! verify_consistency(m as u64, &first,
+                                                 n as u64, &second,
+                                                 &p[1..]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let v: Value = ::serde_json::from_str(r#"{
+            "leaf_index": 3,
+            "audit_path": ["AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="]
+        }"#).unwrap();
+        let p = InclusionProof::from_json(&v).unwrap();
+        assert_eq!(p.leaf_index, 3);
+        assert_eq!(p.audit_path, vec![[0; 32]]);
+
+        // Hashes must be 32 bytes.
+        let v: Value = ::serde_json::from_str(r#"{
+            "consistency": ["AAAA"]
+        }"#).unwrap();
+        assert!(consistency_from_json(&v).is_err());
+    }
+}
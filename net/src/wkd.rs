@@ -38,7 +38,8 @@ use openpgp::parse::Parse;
 use openpgp::serialize::Serialize;
 use openpgp::tpk::TPKParser;
 
-use super::{Result, Error, async};
+use super::{Result, Error, async, CancellationToken, Progress};
+use failover::Failover;
 
 
 /// Stores the local_part and domain of an email address.
@@ -113,6 +114,11 @@ impl Url {
         Ok(url)
     }
 
+    /// Returns the domain of the email address.
+    pub(crate) fn domain(&self) -> &str {
+        &self.domain
+    }
+
     /// Returns an URL string from a [`Url`].
     pub fn build<T>(&self, direct_method: T) -> String
             where T: Into<Option<bool>> {
@@ -242,6 +248,40 @@ pub fn get<S: AsRef<str>>(email_address: S) -> Result<Vec<TPK>> {
     core.run(async::wkd::get(&email_address))
 }
 
+/// Retrieves the TPKs that contain userids with a given email address
+/// from a Web Key Directory URL, reporting progress.
+///
+/// Like [`get`], but progress is reported to `progress`, and the
+/// lookup can be aborted using `token`.
+///
+///   [`get`]: fn.get.html
+// This function must have the same signature as async::wkd::get_with.
+pub fn get_with<S: AsRef<str>>(email_address: S,
+                               progress: Option<Box<Progress>>,
+                               token: Option<CancellationToken>)
+                               -> Result<Vec<TPK>> {
+    let mut core = Core::new()?;
+    core.run(async::wkd::get_with(&email_address, progress, token))
+}
+
+/// Retrieves the TPKs that contain userids with a given email address
+/// from a Web Key Directory URL, remembering which method works.
+///
+/// See [`async::wkd::get_with_failover`] for details.
+///
+///   [`async::wkd::get_with_failover`]: ../async/wkd/fn.get_with_failover.html
+// This function must have the same signature as
+// async::wkd::get_with_failover.
+pub fn get_with_failover<S: AsRef<str>>(email_address: S,
+                                        failover: &Failover,
+                                        progress: Option<Box<Progress>>,
+                                        token: Option<CancellationToken>)
+                                        -> Result<Vec<TPK>> {
+    let mut core = Core::new()?;
+    core.run(async::wkd::get_with_failover(&email_address, failover,
+                                           progress, token))
+}
+
 /// Generates a Web Key Directory for the given domain and keys.
 ///
 /// The owner of the directory and files will be the user that runs this
@@ -113,6 +113,9 @@ check_cb (void *cookie_opaque, pgp_message_structure_t structure)
       pgp_verification_result_iter_free (results);
       break;
 
+    case PGP_MESSAGE_LAYER_LITERAL:
+      break;
+
     default:
       assert (! "reachable");
     }
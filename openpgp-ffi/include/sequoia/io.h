@@ -13,6 +13,11 @@ typedef struct pgp_reader *pgp_reader_t;
 /*/
 typedef struct pgp_writer *pgp_writer_t;
 
+/*/
+/// An owned chunk of memory.
+/*/
+typedef struct pgp_buffer *pgp_buffer_t;
+
 /*/
 /// Opens a file returning a reader.
 /*/
@@ -28,6 +33,26 @@ pgp_reader_t pgp_reader_from_fd (int fd);
 /*/
 pgp_reader_t pgp_reader_from_bytes (const uint8_t *buf, size_t len);
 
+/*/
+/// The callback type for the callback-based reader interface.
+/*/
+typedef ssize_t (*pgp_reader_cb_t) (void *cookie, uint8_t *buf, size_t len);
+
+/*/
+/// Creates a reader from a callback and cookie.
+///
+/// This reader calls the given callback to read data.  The callback
+/// is invoked with `cookie`, a buffer, and the size of the buffer.
+/// It must store at most that many bytes in the buffer, and return
+/// the number of bytes stored, or zero to signal the end of the
+/// stream.  If it returns a negative value, or more than the size of
+/// the buffer, the read fails.
+///
+/// The cookie is not freed when the reader is freed.  It must
+/// outlive the reader.
+/*/
+pgp_reader_t pgp_reader_from_callback (pgp_reader_cb_t cb, void *cookie);
+
 /*/
 /// Frees a reader.
 /*/
@@ -94,4 +119,35 @@ void pgp_writer_free (pgp_writer_t writer);
 ssize_t pgp_writer_write (pgp_error_t *errp, pgp_writer_t writer,
                          const uint8_t *buf, size_t len);
 
+/*/
+/// Creates an empty buffer.
+/*/
+pgp_buffer_t pgp_buffer_new (void);
+
+/*/
+/// Frees a buffer.
+/*/
+void pgp_buffer_free (pgp_buffer_t buf);
+
+/*/
+/// Returns a pointer to the buffer's data.
+///
+/// If `len` is not `NULL`, the length of the data is stored there.
+/// The returned pointer is valid until the buffer is modified or
+/// freed.
+/*/
+const uint8_t *pgp_buffer_data (const pgp_buffer_t buf, size_t *len);
+
+/*/
+/// Returns the length of the buffer's data.
+/*/
+size_t pgp_buffer_len (const pgp_buffer_t buf);
+
+/*/
+/// Creates a writer appending to the given buffer.
+///
+/// The writer must be destroyed before the buffer is freed.
+/*/
+pgp_writer_t pgp_writer_from_buffer (pgp_buffer_t buf);
+
 #endif
@@ -13,6 +13,40 @@
 #include <sequoia/openpgp/crypto.h>
 #include <sequoia/openpgp/packet.h>
 
+/*/
+/// The API level this header corresponds to.
+///
+/// The API level is incremented whenever stable symbols are added.
+/// Compare it with the result of `pgp_api_level` to make sure that
+/// the library provides all the symbols declared here.
+/*/
+#define PGP_API_LEVEL 7
+
+/* Version information.  */
+
+/*/
+/// Returns the version of the library.
+///
+/// The returned string is statically allocated, and must not be
+/// freed.
+/*/
+const char *pgp_version (void);
+
+/*/
+/// Returns the API level of the library.
+/*/
+int pgp_api_level (void);
+
+/*/
+/// Shuts the library down.
+///
+/// If the library is compiled with the `track-ownership` feature,
+/// this function prints all objects that have not been freed, and
+/// all objects that have been freed more than once, to stderr.  It
+/// returns the number of problems found.  Otherwise, it returns 0.
+/*/
+int pgp_shutdown (void);
+
 /* sequoia::openpgp::KeyID.  */
 
 /*/
@@ -61,6 +95,14 @@ char *pgp_keyid_to_hex (const pgp_keyid_t keyid);
 /*/
 int pgp_keyid_equal (const pgp_keyid_t a, const pgp_keyid_t b);
 
+/*/
+/// Selects how fingerprints and key IDs are displayed.
+///
+/// This affects `pgp_keyid_to_string` and
+/// `pgp_fingerprint_to_string`, and applies to the whole process.
+/*/
+void pgp_identifier_format_set (pgp_identifier_format_t format);
+
 
 /* sequoia::openpgp::Fingerprint.  */
 
@@ -265,6 +307,12 @@ pgp_status_t pgp_packet_pile_serialize (pgp_error_t *errp,
 				      const pgp_packet_pile_t message,
 				      pgp_writer_t writer);
 
+/*/
+/// Serializes the packet pile into a newly allocated buffer.
+/*/
+pgp_buffer_t pgp_packet_pile_serialize_to_buffer (pgp_error_t *errp,
+                                                  const pgp_packet_pile_t message);
+
 /*/
 /// Frees the signature.
 /*/
@@ -312,6 +360,12 @@ pgp_status_t pgp_signature_serialize (pgp_error_t *errp,
 				      const pgp_signature_t signature,
 				      pgp_writer_t writer);
 
+/*/
+/// Serializes this object into a newly allocated buffer.
+/*/
+pgp_buffer_t pgp_signature_serialize_to_buffer (pgp_error_t *errp,
+                                                const pgp_signature_t signature);
+
 /*/
 /// Converts the signature to a packet.
 /*/
@@ -497,6 +551,12 @@ pgp_status_t pgp_literal_serialize (pgp_error_t *errp,
                                     const pgp_literal_t literal,
                                     pgp_writer_t writer);
 
+/*/
+/// Serializes this object into a newly allocated buffer.
+/*/
+pgp_buffer_t pgp_literal_serialize_to_buffer (pgp_error_t *errp,
+                                              const pgp_literal_t literal);
+
 /*/
 /// Converts the literal to a packet.
 /*/
@@ -529,11 +589,20 @@ pgp_signature_t pgp_user_id_binding_selfsig(pgp_user_id_binding_t binding);
 
 /*/
 /// Returns the next element in the iterator.
+///
+/// The returned binding is valid until the iterator is freed.
 /*/
 pgp_user_id_binding_t pgp_user_id_binding_iter_next (pgp_user_id_binding_iter_t iter);
 
+/*/
+/// Returns the number of remaining elements.
+/*/
+size_t pgp_user_id_binding_iter_len (pgp_user_id_binding_iter_t iter);
+
 /*/
 /// Frees an pgp_user_id_binding_iter_t.
+///
+/// This invalidates all bindings returned by the iterator.
 /*/
 void pgp_user_id_binding_iter_free (pgp_user_id_binding_iter_t iter);
 
@@ -565,6 +634,19 @@ void pgp_tpk_key_iter_certification_capable (pgp_tpk_key_iter_t iter);
 /*/
 void pgp_tpk_key_iter_signing_capable (pgp_tpk_key_iter_t iter);
 
+/*/
+/// Changes the iterator to only return keys that are authentication
+/// capable.
+///
+/// If you call this function and, e.g., the `signing_capable`
+/// function, the *union* of the values is used.  That is, the
+/// iterator will return keys that are authentication capable *or*
+/// signing capable.
+///
+/// Note: you may not call this function after starting to iterate.
+/*/
+void pgp_tpk_key_iter_authentication_capable (pgp_tpk_key_iter_t iter);
+
 /*/
 /// Changes the iterator to only return keys that are alive.
 ///
@@ -621,16 +703,55 @@ void pgp_tpk_key_iter_unencrypted_secret (pgp_tpk_key_iter_t iter,
 ///
 /// If rso is not NULL, this stores the key's revocation status in
 /// *rso.
+///
+/// The returned key and signature are copies, and must be freed by
+/// the caller.  The revocation status refers to the iterator's
+/// snapshot of the TPK, and must be freed before the iterator.
 /*/
 pgp_key_t pgp_tpk_key_iter_next (pgp_tpk_key_iter_t iter,
                                  pgp_signature_t *signature,
                                  pgp_revocation_status_t *rev);
 
+/*/
+/// Returns the number of remaining keys.
+///
+/// This starts the iteration, i.e. the iterator's filters may not be
+/// changed afterwards.
+/*/
+size_t pgp_tpk_key_iter_len (pgp_tpk_key_iter_t iter);
+
 /*/
 /// Frees an pgp_tpk_key_iter_t.
+///
+/// This invalidates the revocation statuses returned by the
+/// iterator.
 /*/
 void pgp_tpk_key_iter_free (pgp_tpk_key_iter_t iter);
 
+/*/
+/// Returns an iterator over the keys that can be used to encrypt
+/// data for `tpk`.
+///
+/// A key is returned if its binding signature marks it as
+/// encryption-capable for one of the purposes selected by `flags`,
+/// if it is alive at time `when`, and if it has not been revoked.
+/// Pass 0 for `when` to use the current time.  `flags` is a
+/// combination of `PGP_ENCRYPTION_KEY_AT_REST` and
+/// `PGP_ENCRYPTION_KEY_FOR_TRANSPORT`.
+///
+/// On success, the iterator is stored in `*iterp`.  If `tpk` has no
+/// suitable keys, the iterator is empty.  Further filters may be
+/// added before iterating.  If `flags` is invalid, an error is
+/// returned, and `*iterp` is set to NULL.
+///
+/// The iterator operates on a snapshot of the TPK.  Changes to `tpk`
+/// are not reflected, and `tpk` may be freed while iterating.
+/*/
+pgp_status_t pgp_tpk_encryption_keys (pgp_error_t *errp, pgp_tpk_t tpk,
+                                      time_t when,
+                                      uint8_t flags,
+                                      pgp_tpk_key_iter_t *iterp);
+
 /* openpgp::tpk.  */
 
 /*/
@@ -703,6 +824,12 @@ pgp_status_t pgp_tpk_serialize (pgp_error_t *errp,
                               const pgp_tpk_t tpk,
                               pgp_writer_t writer);
 
+/*/
+/// Serializes the TPK into a newly allocated buffer.
+/*/
+pgp_buffer_t pgp_tpk_serialize_to_buffer (pgp_error_t *errp,
+                                          const pgp_tpk_t tpk);
+
 /*/
 /// Merges `other` into `tpk`.
 ///
@@ -734,6 +861,19 @@ pgp_tpk_t pgp_tpk_merge_packets (pgp_error_t *errp,
 /*/
 pgp_fingerprint_t pgp_tpk_fingerprint (const pgp_tpk_t tpk);
 
+/*/
+/// Returns a JSON description of the TPK.
+///
+/// The description contains the primary key, the subkeys, User IDs,
+/// and User Attributes together with their current binding
+/// signatures, revocation status, and expiration times.  It never
+/// includes secret key material.  The format is documented in
+/// `openpgp-ffi/src/json.rs`.
+///
+/// The caller must free the returned value.
+/*/
+char *pgp_tpk_to_json (const pgp_tpk_t tpk);
+
 
 /*/
 /// Derive a [`TSK`] object from this key.
@@ -833,6 +973,9 @@ int pgp_tpk_is_tsk(pgp_tpk_t tpk);
 
 /*/
 /// Returns an iterator over the `UserIDBinding`s.
+///
+/// The iterator operates on a snapshot of the TPK.  Changes to `tpk`
+/// are not reflected, and `tpk` may be freed while iterating.
 /*/
 pgp_user_id_binding_iter_t pgp_tpk_user_id_binding_iter (pgp_tpk_t tpk);
 
@@ -854,6 +997,9 @@ pgp_user_id_binding_iter_t pgp_tpk_user_id_binding_iter (pgp_tpk_t tpk);
 ///
 /// Compare with `pgp_tpk_key_iter_valid`, which filters out expired and
 /// revoked keys.
+///
+/// The iterator operates on a snapshot of the TPK.  Changes to `tpk`
+/// are not reflected, and `tpk` may be freed while iterating.
 /*/
 pgp_tpk_key_iter_t pgp_tpk_key_iter_all (pgp_tpk_t tpk);
 
@@ -862,6 +1008,9 @@ pgp_tpk_key_iter_t pgp_tpk_key_iter_all (pgp_tpk_t tpk);
 ///
 /// Compare with `pgp_tpk_key_iter_all`, which doesn't filter out
 /// expired and revoked keys by default.
+///
+/// The iterator operates on a snapshot of the TPK.  Changes to `tpk`
+/// are not reflected, and `tpk` may be freed while iterating.
 /*/
 pgp_tpk_key_iter_t pgp_tpk_key_iter_valid (pgp_tpk_t tpk);
 
@@ -962,6 +1111,11 @@ void pgp_tpk_builder_add_encryption_subkey(pgp_tpk_builder_t *tpkb);
 /*/
 void pgp_tpk_builder_add_certification_subkey(pgp_tpk_builder_t *tpkb);
 
+/*/
+/// Adds an authentication capable subkey.
+/*/
+void pgp_tpk_builder_add_authentication_subkey(pgp_tpk_builder_t *tpkb);
+
 /*/
 /// Generates the actual TPK.
 ///
@@ -987,6 +1141,12 @@ pgp_status_t pgp_tsk_serialize (pgp_error_t *errp,
                               const pgp_tsk_t tsk,
                               pgp_writer_t writer);
 
+/*/
+/// Serializes the TSK into a newly allocated buffer.
+/*/
+pgp_buffer_t pgp_tsk_serialize_to_buffer (pgp_error_t *errp,
+                                          const pgp_tsk_t tsk);
+
 /*/
 /// Frees the key.
 /*/
@@ -1044,6 +1204,15 @@ pgp_fingerprint_t pgp_key_fingerprint (pgp_key_t key);
 /*/
 pgp_keyid_t pgp_key_keyid (pgp_key_t key);
 
+/*/
+/// Computes and returns the key's keygrip as hexadecimal number.
+///
+/// Keygrips are used by GnuPG, e.g. to name the files in gpg-agent's
+/// private key store.  Returns `NULL` if no keygrip is defined for
+/// the key's algorithm.
+/*/
+char *pgp_key_keygrip (pgp_error_t *errp, pgp_key_t key);
+
 /*/
 /// Returns the key's public key algorithm.
 /*/
@@ -1259,12 +1428,27 @@ const uint8_t *pgp_user_attribute_value (pgp_packet_t ua,
 /// session key.  If `key` is NULL, or not large enough, then the key
 /// is not written to it.  Either way, `key_len` is set to the size of
 /// the session key.
+///
+/// Prefer `pgp_skesk_decrypt_with_password`, which does not require
+/// the password to be copied into unprotected memory.
 /*/
 pgp_status_t pgp_skesk_decrypt (pgp_error_t *errp, pgp_packet_t skesk,
                               const uint8_t *password, size_t password_len,
                               uint8_t *algo, /* XXX */
                               uint8_t *key, size_t *key_len);
 
+/*/
+/// Returns the session key.
+///
+/// Like `pgp_skesk_decrypt`, but takes the password as
+/// `pgp_password_t`.
+/*/
+pgp_status_t pgp_skesk_decrypt_with_password (pgp_error_t *errp,
+                                              pgp_packet_t skesk,
+                                              const pgp_password_t password,
+                                              uint8_t *algo, /* XXX */
+                                              uint8_t *key, size_t *key_len);
+
 /*/
 /// Returns the key's creation time.
 /*/
@@ -1591,6 +1775,9 @@ pgp_writer_stack_t pgp_literal_writer_new (pgp_error_t *errp,
 /// The stream will be encrypted using a generated session key,
 /// which will be encrypted using the given passwords, and all
 /// encryption-capable subkeys of the given TPKs.
+///
+/// Prefer `pgp_encryptor_new_with_passwords`, which does not require
+/// the passwords to be kept in unprotected memory.
 /*/
 pgp_writer_stack_t pgp_encryptor_new (pgp_error_t *errp,
 				      pgp_writer_stack_t inner,
@@ -1601,6 +1788,40 @@ pgp_writer_stack_t pgp_encryptor_new (pgp_error_t *errp,
 				      pgp_encryption_mode_t mode,
 				      uint8_t cipher_algo);
 
+/*/
+/// Creates a new encryptor.
+///
+/// Like `pgp_encryptor_new`, but takes the passwords as
+/// `pgp_password_t`.
+/*/
+pgp_writer_stack_t pgp_encryptor_new_with_passwords
+    (pgp_error_t *errp,
+     pgp_writer_stack_t inner,
+     pgp_password_t *passwords,
+     size_t passwords_len,
+     pgp_tpk_t *recipients,
+     size_t recipients_len,
+     pgp_encryption_mode_t mode,
+     uint8_t cipher_algo);
+
+/*/
+/// Encrypts data using a password.
+///
+/// Reads the data from `input`, and writes an OpenPGP message
+/// containing the data encrypted using `password` to `output`.  If
+/// `armor` is true, the message is ASCII armored.
+///
+/// The message is encrypted using `cipher_algo`.  Pass 0 for the
+/// default (which is what you usually want).  Use
+/// `pgp_decrypt_with_password` to decrypt the message.
+/*/
+pgp_status_t pgp_encrypt_with_password (pgp_error_t *errp,
+					const pgp_password_t password,
+					pgp_reader_t input,
+					pgp_writer_t output,
+					uint8_t cipher_algo,
+					bool armor);
+
 /*/
 /// Frees this object.
 /*/
@@ -1612,9 +1833,31 @@ void pgp_message_structure_free (pgp_message_structure_t);
 /*/
 char *pgp_message_structure_debug (const pgp_message_structure_t);
 
+/*/
+/// Iterates over the message structure.
+///
+/// The message structure is only valid during the `check` callback
+/// it is passed to.  It cannot be modified, hence, unlike the TPK
+/// iterators, the iterators over it do not take a snapshot.  Instead,
+/// the iterators, and the layers and verification results they
+/// return, are valid until the callback returns.  Freeing an
+/// iterator does not invalidate the objects it returned.
+/*/
 pgp_message_structure_iter_t
 pgp_message_structure_iter (pgp_message_structure_t);
 
+/*/
+/// Returns whether the literal data packet's metadata is
+/// authenticated.
+///
+/// The format, file name, and date stored in the literal data packet
+/// are only authenticated if the packet is inside an AEAD encrypted
+/// container.  If this returns false, the metadata, notably the file
+/// name, must not be trusted.
+/*/
+bool pgp_message_structure_literal_metadata_authenticated
+  (pgp_message_structure_t structure);
+
 /*/
 /// Frees this object.
 /*/
@@ -1623,6 +1866,11 @@ void pgp_message_structure_iter_free (pgp_message_structure_iter_t);
 pgp_message_layer_t
 pgp_message_structure_iter_next (pgp_message_structure_iter_t);
 
+/*/
+/// Returns the number of remaining layers.
+/*/
+size_t pgp_message_structure_iter_len (pgp_message_structure_iter_t);
+
 /*/
 /// Frees this object.
 /*/
@@ -1647,6 +1895,8 @@ bool pgp_message_layer_compression (pgp_message_layer_t, uint8_t *);
 bool pgp_message_layer_encryption (pgp_message_layer_t, uint8_t *, uint8_t *);
 bool pgp_message_layer_signature_group (pgp_message_layer_t,
 					pgp_verification_result_iter_t *);
+bool pgp_message_layer_literal (pgp_message_layer_t, uint8_t *, char **,
+				time_t *);
 
 /*/
 /// Frees this object.
@@ -1656,6 +1906,11 @@ void pgp_verification_result_iter_free (pgp_verification_result_iter_t);
 pgp_verification_result_t
 pgp_verification_result_iter_next (pgp_verification_result_iter_t);
 
+/*/
+/// Returns the number of remaining verification results.
+/*/
+size_t pgp_verification_result_iter_len (pgp_verification_result_iter_t);
+
 /*/
 /// Frees this object.
 /*/
@@ -1708,6 +1963,23 @@ pgp_reader_t pgp_decryptor_new (pgp_error_t *errp, pgp_reader_t input,
     pgp_decryptor_inspect_cb_t inspect,
     void *cookie, time_t time);
 
+/*/
+/// Decrypts a message encrypted using a password.
+///
+/// The message is read from `input`, and the content of the
+/// `LiteralData` packet is written to `output`.  This is the
+/// counterpart of `pgp_encrypt_with_password`.
+///
+/// This function fails if the message is not encrypted, or cannot be
+/// decrypted using `password`.  Signatures are not verified.  Note:
+/// on failure, some data may already have been written to `output`,
+/// which must then be discarded.
+/*/
+pgp_status_t pgp_decrypt_with_password (pgp_error_t *errp,
+					const pgp_password_t password,
+					pgp_reader_t input,
+					pgp_writer_t output);
+
 /*/
 /// Verifies an OpenPGP message.
 ///
@@ -1728,4 +2000,249 @@ pgp_reader_t pgp_detached_verifier_new (pgp_error_t *errp,
     pgp_decryptor_check_cb_t check,
     void *cookie, time_t time);
 
+/* openpgp::autocrypt.  */
+
+/* The Autocrypt bindings are experimental.  They are only built if
+   the library is compiled with the `experimental` feature, and are
+   only declared if `PGP_EXPERIMENTAL` is defined.  */
+#ifdef PGP_EXPERIMENTAL
+
+/*/
+/// Creates a new "Autocrypt" header.
+///
+/// If `prefer_encrypt` is `NULL`, "nopreference" is used.
+/*/
+pgp_autocrypt_header_t pgp_autocrypt_header_new_sender (pgp_error_t *errp,
+							pgp_tpk_t tpk,
+							const char *addr,
+							const char *prefer_encrypt);
+
+/*/
+/// Frees the header.
+/*/
+void pgp_autocrypt_header_free (pgp_autocrypt_header_t header);
+
+/*/
+/// Compares headers.
+/*/
+bool pgp_autocrypt_header_equal (const pgp_autocrypt_header_t a,
+				 const pgp_autocrypt_header_t b);
+
+/*/
+/// Returns a human readable description of this object suitable for
+/// debugging.
+/*/
+char *pgp_autocrypt_header_debug (const pgp_autocrypt_header_t header);
+
+/*/
+/// Returns whether this is an "Autocrypt" or an "Autocrypt-Gossip"
+/// header.
+/*/
+pgp_autocrypt_header_type_t pgp_autocrypt_header_type
+    (pgp_autocrypt_header_t header);
+
+/*/
+/// Returns a reference to the key carried by the header.
+///
+/// If the header has no key, this returns `NULL`.  The returned
+/// object must be deallocated using `pgp_tpk_free` even though it
+/// only references the header.
+/*/
+pgp_tpk_t pgp_autocrypt_header_key (pgp_autocrypt_header_t header);
+
+/*/
+/// Returns the value of the attribute `key`.
+///
+/// If the attribute is not present, this returns `NULL`.  The caller
+/// must free the returned value.
+/*/
+char *pgp_autocrypt_header_get (pgp_autocrypt_header_t header,
+				const char *key);
+
+/*/
+/// Parses the Autocrypt headers of a mail.
+/*/
+pgp_autocrypt_headers_t pgp_autocrypt_headers_from_bytes (pgp_error_t *errp,
+							  const uint8_t *buf,
+							  size_t len);
+
+/*/
+/// Parses the Autocrypt headers of the mail in the file `filename`.
+/*/
+pgp_autocrypt_headers_t pgp_autocrypt_headers_from_file (pgp_error_t *errp,
+							 const char *filename);
+
+/*/
+/// Parses the Autocrypt headers of the mail read from `reader`.
+/*/
+pgp_autocrypt_headers_t pgp_autocrypt_headers_from_reader (pgp_error_t *errp,
+							   pgp_reader_t reader);
+
+/*/
+/// Frees the headers.
+/*/
+void pgp_autocrypt_headers_free (pgp_autocrypt_headers_t headers);
+
+/*/
+/// Compares headers.
+/*/
+bool pgp_autocrypt_headers_equal (const pgp_autocrypt_headers_t a,
+				  const pgp_autocrypt_headers_t b);
+
+/*/
+/// Returns a human readable description of this object suitable for
+/// debugging.
+/*/
+char *pgp_autocrypt_headers_debug (const pgp_autocrypt_headers_t headers);
+
+/*/
+/// Returns the value of the mail's From header.
+///
+/// If there is no From header, this returns `NULL`.  The caller must
+/// free the returned value.
+/*/
+char *pgp_autocrypt_headers_from (pgp_autocrypt_headers_t headers);
+
+/*/
+/// Returns the number of Autocrypt headers.
+/*/
+size_t pgp_autocrypt_headers_count (pgp_autocrypt_headers_t headers);
+
+/*/
+/// Returns a reference to the `idx`th Autocrypt header.
+///
+/// If `idx` is out of range, this returns `NULL`.  The returned
+/// object must be deallocated using `pgp_autocrypt_header_free` even
+/// though it only references `headers`.
+/*/
+pgp_autocrypt_header_t pgp_autocrypt_headers_nth
+    (pgp_autocrypt_headers_t headers, size_t idx);
+
+/*/
+/// Creates a new Autocrypt Setup Message for `tpk`.
+/*/
+pgp_autocrypt_setup_message_t pgp_autocrypt_setup_message_new (pgp_tpk_t tpk);
+
+/*/
+/// Frees the Autocrypt Setup Message.
+/*/
+void pgp_autocrypt_setup_message_free (pgp_autocrypt_setup_message_t asm);
+
+/*/
+/// Compares Autocrypt Setup Messages.
+/*/
+bool pgp_autocrypt_setup_message_equal
+    (const pgp_autocrypt_setup_message_t a,
+     const pgp_autocrypt_setup_message_t b);
+
+/*/
+/// Returns a human readable description of this object suitable for
+/// debugging.
+/*/
+char *pgp_autocrypt_setup_message_debug
+    (const pgp_autocrypt_setup_message_t asm);
+
+/*/
+/// Sets the prefer encrypt header.
+///
+/// Consumes `asm` and returns the modified message.
+/*/
+pgp_autocrypt_setup_message_t pgp_autocrypt_setup_message_set_prefer_encrypt
+    (pgp_autocrypt_setup_message_t asm, const char *value);
+
+/*/
+/// Returns the prefer encrypt header.
+/*/
+char *pgp_autocrypt_setup_message_prefer_encrypt
+    (pgp_autocrypt_setup_message_t asm);
+
+/*/
+/// Sets the passcode.
+///
+/// Consumes `asm` and returns the modified message.
+/*/
+pgp_autocrypt_setup_message_t pgp_autocrypt_setup_message_set_passcode
+    (pgp_autocrypt_setup_message_t asm, pgp_password_t passcode);
+
+/*/
+/// Returns a reference to the passcode.
+///
+/// The returned object must be deallocated using `pgp_password_free`
+/// even though it only references `asm`.
+/*/
+pgp_password_t pgp_autocrypt_setup_message_passcode
+    (pgp_autocrypt_setup_message_t asm);
+
+/*/
+/// Returns the "Passcode-Format" header.
+/*/
+char *pgp_autocrypt_setup_message_passcode_format
+    (pgp_autocrypt_setup_message_t asm);
+
+/*/
+/// Returns the "Passcode-Begin" header.
+/*/
+char *pgp_autocrypt_setup_message_passcode_begin
+    (pgp_autocrypt_setup_message_t asm);
+
+/*/
+/// Writes the Autocrypt Setup Message to `writer`.
+///
+/// If no passcode has been set, a random passcode is generated.
+/*/
+pgp_status_t pgp_autocrypt_setup_message_serialize
+    (pgp_error_t *errp, pgp_autocrypt_setup_message_t asm,
+     pgp_writer_t writer);
+
+/*/
+/// Returns the TPK.
+///
+/// Consumes `asm`.
+/*/
+pgp_tpk_t pgp_autocrypt_setup_message_into_tpk
+    (pgp_autocrypt_setup_message_t asm);
+
+/*/
+/// Starts parsing the Autocrypt Setup Message read from `reader`.
+///
+/// The parser must not outlive `reader`.
+/*/
+pgp_autocrypt_setup_message_parser_t pgp_autocrypt_setup_message_from_reader
+    (pgp_error_t *errp, pgp_reader_t reader);
+
+/*/
+/// Frees the parser.
+/*/
+void pgp_autocrypt_setup_message_parser_free
+    (pgp_autocrypt_setup_message_parser_t parser);
+
+/*/
+/// Returns the "Passcode-Format" header.
+/*/
+char *pgp_autocrypt_setup_message_parser_passcode_format
+    (pgp_autocrypt_setup_message_parser_t parser);
+
+/*/
+/// Returns the "Passcode-Begin" header.
+/*/
+char *pgp_autocrypt_setup_message_parser_passcode_begin
+    (pgp_autocrypt_setup_message_parser_t parser);
+
+/*/
+/// Tries to decrypt the message using `passcode`.
+/*/
+pgp_status_t pgp_autocrypt_setup_message_parser_decrypt
+    (pgp_error_t *errp, pgp_autocrypt_setup_message_parser_t parser,
+     pgp_password_t passcode);
+
+/*/
+/// Finishes parsing the Autocrypt Setup Message.
+///
+/// Consumes `parser`.
+/*/
+pgp_autocrypt_setup_message_t pgp_autocrypt_setup_message_parser_parse
+    (pgp_error_t *errp, pgp_autocrypt_setup_message_parser_t parser);
+
+#endif /* PGP_EXPERIMENTAL */
+
 #endif
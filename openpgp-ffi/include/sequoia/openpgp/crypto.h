@@ -2,6 +2,7 @@
 #define SEQUOIA_OPENPGP_CRYPTO_H
 
 #include <sequoia/openpgp/types.h>
+#include <sequoia/openpgp/error.h>
 
 /*/
 /// Creates a new session key.
@@ -61,6 +62,52 @@ pgp_password_t pgp_password_clone (pgp_password_t password);
 /*/
 int pgp_password_equal (const pgp_password_t a, const pgp_password_t b);
 
+/*/
+/// Returns the default S2K specifier.
+///
+/// This is an iterated and salted S2K using a random salt.
+/*/
+pgp_s2k_t pgp_s2k_default (void);
+
+/*/
+/// Returns an iterated and salted S2K specifier.
+///
+/// The salt is chosen at random.  Pass 0 for `hash_algo` to use the
+/// default hash algorithm.  `hash_bytes` is the number of bytes to
+/// hash, which is rounded up to the nearest value that can be
+/// represented.
+/*/
+pgp_s2k_t pgp_s2k_iterated (uint8_t hash_algo, uint32_t hash_bytes);
+
+/*/
+/// Frees an S2K specifier.
+/*/
+void pgp_s2k_free (pgp_s2k_t);
+
+/*/
+/// Returns a human readable description of this object suitable for
+/// debugging.
+/*/
+char *pgp_s2k_debug (const pgp_s2k_t s2k);
+
+/*/
+/// Clones the S2K specifier.
+/*/
+pgp_s2k_t pgp_s2k_clone (pgp_s2k_t s2k);
+
+/*/
+/// Compares S2K specifiers.
+/*/
+int pgp_s2k_equal (const pgp_s2k_t a, const pgp_s2k_t b);
+
+/*/
+/// Derives a key of size `key_size` from `password`.
+/*/
+pgp_session_key_t pgp_s2k_derive_key (pgp_error_t *errp,
+				      const pgp_s2k_t s2k,
+				      const pgp_password_t password,
+				      size_t key_size);
+
 typedef struct pgp_mpis_secret_key *pgp_mpis_secret_key_t;
 
 /*/
@@ -127,6 +127,11 @@ typedef enum pgp_status {
   /*/
   PGP_STATUS_UNSUPPORTED_TPK = -24,
 
+  /*/
+  /// Policy violation.
+  /*/
+  PGP_STATUS_POLICY_VIOLATION = -29,
+
   /* Dummy value to make sure the enumeration has a defined size.  Do
      not use this value.  */
   PGP_STATUS_FORCE_WIDTH = INT_MAX,
@@ -52,6 +52,18 @@ pgp_tag_t pgp_packet_kind (pgp_packet_t p);
 /*/
 const char *pgp_tag_to_string (pgp_tag_t tag);
 
+/*/
+/// Returns a JSON description of the packet.
+///
+/// The description contains the packet's metadata, like algorithms,
+/// fingerprints, and creation times, but never any secret key
+/// material or message bodies.  The format is documented in
+/// `openpgp-ffi/src/json.rs`.
+///
+/// The caller must free the returned value.
+/*/
+char *pgp_packet_to_json (const pgp_packet_t p);
+
 /*/
 /// Given a packet references the contained signature, if any.
 ///
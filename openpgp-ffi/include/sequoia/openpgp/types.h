@@ -15,6 +15,13 @@ typedef struct pgp_session_key *pgp_session_key_t;
 /*/
 typedef struct pgp_password *pgp_password_t;
 
+/*/
+/// A string-to-key (S2K) specifier.
+///
+/// S2K specifiers are used to derive symmetric keys from passwords.
+/*/
+typedef struct pgp_s2k *pgp_s2k_t;
+
 /*/
 /// Holds a fingerprint.
 /*/
@@ -66,6 +73,35 @@ typedef enum pgp_armor_kind {
   PGP_ARMOR_KIND_FORCE_WIDTH = INT_MAX,
 } pgp_armor_kind_t;
 
+/*/
+/// Selects how fingerprints and key IDs are displayed.
+/*/
+typedef enum pgp_identifier_format {
+  /*/
+  /// Fingerprints are shown in full, key IDs in their long form.
+  ///
+  /// This is the default.
+  /*/
+  PGP_IDENTIFIER_FORMAT_FULL = 0,
+
+  /*/
+  /// Fingerprints are shown as long key IDs.
+  /*/
+  PGP_IDENTIFIER_FORMAT_LONG_KEYID = 1,
+
+  /*/
+  /// Fingerprints and key IDs are shown as short key IDs.
+  ///
+  /// This is a legacy mode, e.g. for matching old logs.  Do not use
+  /// it to identify keys.
+  /*/
+  PGP_IDENTIFIER_FORMAT_LEGACY_SHORT_KEYID = 2,
+
+  /* Dummy value to make sure the enumeration has a defined size.  Do
+     not use this value.  */
+  PGP_IDENTIFIER_FORMAT_FORCE_WIDTH = INT_MAX,
+} pgp_identifier_format_t;
+
 /*/
 /// Represents a (key, value) pair in an armor header.
 /*/
@@ -403,6 +439,44 @@ typedef struct pgp_tpk_parser *pgp_tpk_parser_t;
 /*/
 typedef struct pgp_tsk *pgp_tsk_t;
 
+#ifdef PGP_EXPERIMENTAL
+/*/
+/// A parsed Autocrypt header.
+/*/
+typedef struct pgp_autocrypt_header *pgp_autocrypt_header_t;
+
+typedef enum pgp_autocrypt_header_type {
+  /*/
+  /// An "Autocrypt" header.
+  /*/
+  PGP_AUTOCRYPT_HEADER_TYPE_SENDER = 0,
+
+  /*/
+  /// An "Autocrypt-Gossip" header.
+  /*/
+  PGP_AUTOCRYPT_HEADER_TYPE_GOSSIP = 1,
+
+  /* Dummy value to make sure the enumeration has a defined size.  Do
+     not use this value.  */
+  PGP_AUTOCRYPT_HEADER_TYPE_FORCE_WIDTH = INT_MAX,
+} pgp_autocrypt_header_type_t;
+
+/*/
+/// The Autocrypt headers of a mail.
+/*/
+typedef struct pgp_autocrypt_headers *pgp_autocrypt_headers_t;
+
+/*/
+/// Holds an Autocrypt Setup Message.
+/*/
+typedef struct pgp_autocrypt_setup_message *pgp_autocrypt_setup_message_t;
+
+/*/
+/// A parser for an Autocrypt Setup Message.
+/*/
+typedef struct pgp_autocrypt_setup_message_parser *pgp_autocrypt_setup_message_parser_t;
+#endif /* PGP_EXPERIMENTAL */
+
 
 typedef enum pgp_tpk_cipher_suite {
   /*/
@@ -469,6 +543,23 @@ typedef enum pgp_encryption_mode {
   PGP_ENCRYPTION_MODE_FOR_TRANSPORT = 1,
 } pgp_encryption_mode_t;
 
+/*/
+/// Selects the keys returned by `pgp_tpk_encryption_keys`.
+///
+/// The values can be combined using bitwise or.
+/*/
+typedef enum pgp_encryption_key_flags {
+  /*/
+  /// Keys that may be used to encrypt data for long-term storage.
+  /*/
+  PGP_ENCRYPTION_KEY_AT_REST = 1 << 0,
+
+  /*/
+  /// Keys that may be used to encrypt data in transit.
+  /*/
+  PGP_ENCRYPTION_KEY_FOR_TRANSPORT = 1 << 1,
+} pgp_encryption_key_flags_t;
+
 /// Communicates the message structure to the VerificationHelper.
 typedef struct pgp_message_structure *pgp_message_structure_t;
 
@@ -482,6 +573,7 @@ typedef enum pgp_message_layer_variant {
   PGP_MESSAGE_LAYER_COMPRESSION = 1,
   PGP_MESSAGE_LAYER_ENCRYPTION = 2,
   PGP_MESSAGE_LAYER_SIGNATURE_GROUP = 3,
+  PGP_MESSAGE_LAYER_LITERAL = 4,
 
   /* Dummy value to make sure the enumeration has a defined size.  Do
      not use this value.  */
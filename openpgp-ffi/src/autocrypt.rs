@@ -0,0 +1,451 @@
+//! Autocrypt.
+//!
+//! Wraps [`sequoia-openpgp::autocrypt`].
+//!
+//! [`sequoia-openpgp::autocrypt`]: ../../sequoia_openpgp/autocrypt/index.html
+
+use std::ptr;
+use std::slice;
+use libc::{uint8_t, c_char, c_int, size_t};
+
+extern crate sequoia_openpgp as openpgp;
+use self::openpgp::autocrypt;
+
+use super::crypto::Password;
+use super::io::{Reader, Writer};
+use super::tpk::TPK;
+use ::error::Status;
+use Maybe;
+use MoveFromRaw;
+use MoveIntoRaw;
+use MoveResultIntoRaw;
+use RefRaw;
+use RefMutRaw;
+
+/// A parsed Autocrypt header.
+///
+/// Wraps [`sequoia-openpgp::autocrypt::AutocryptHeader`].
+///
+/// [`sequoia-openpgp::autocrypt::AutocryptHeader`]: ../../sequoia_openpgp/autocrypt/struct.AutocryptHeader.html
+#[::ffi_wrapper_type(prefix = "pgp_", derive = "Debug, PartialEq")]
+pub struct AutocryptHeader(autocrypt::AutocryptHeader);
+
+/// Creates a new "Autocrypt" header.
+///
+/// The key is minimized: only the primary key, the subkeys that have
+/// not been revoked, and the User IDs with the address `addr` are
+/// included.  If `prefer_encrypt` is `NULL`, "nopreference" is used.
+///
+/// # Example
+///
+/// ```c
+/// #include <assert.h>
+/// #include <stdlib.h>
+/// #include <sequoia/openpgp.h>
+///
+/// pgp_tpk_builder_t builder;
+/// pgp_tpk_t tpk;
+/// pgp_signature_t revocation;
+///
+/// builder = pgp_tpk_builder_autocrypt ("alice@example.org");
+/// pgp_tpk_builder_generate (NULL, builder, &tpk, &revocation);
+/// assert (tpk);
+/// pgp_signature_free (revocation);
+///
+/// pgp_autocrypt_header_t header =
+///   pgp_autocrypt_header_new_sender (NULL, tpk, "alice@example.org", "mutual");
+/// assert (header);
+/// assert (pgp_autocrypt_header_type (header)
+///         == PGP_AUTOCRYPT_HEADER_TYPE_SENDER);
+///
+/// char *value = pgp_autocrypt_header_get (header, "prefer-encrypt");
+/// assert (value);
+/// free (value);
+///
+/// pgp_tpk_t key = pgp_autocrypt_header_key (header);
+/// assert (key);
+/// pgp_tpk_free (key);
+///
+/// pgp_autocrypt_header_free (header);
+/// pgp_tpk_free (tpk);
+/// ```
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_header_new_sender(errp: Option<&mut *mut ::error::Error>,
+                                   tpk: *const TPK,
+                                   addr: *const c_char,
+                                   prefer_encrypt: *const c_char)
+                                   -> Maybe<AutocryptHeader> {
+    let tpk = tpk.ref_raw();
+    let addr = ffi_param_cstr!(addr).to_string_lossy();
+    let prefer_encrypt = if prefer_encrypt.is_null() {
+        None
+    } else {
+        Some(ffi_param_cstr!(prefer_encrypt).to_string_lossy())
+    };
+    autocrypt::AutocryptHeader::new_sender(
+        tpk, &addr, prefer_encrypt.as_ref().map(|p| p.as_ref()))
+        .move_into_raw(errp)
+}
+
+/// Returns whether this is an "Autocrypt" or an "Autocrypt-Gossip"
+/// header.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_header_type(header: *const AutocryptHeader) -> c_int {
+    match header.ref_raw().header_type {
+        autocrypt::AutocryptHeaderType::Sender => 0,
+        autocrypt::AutocryptHeaderType::Gossip => 1,
+    }
+}
+
+/// Returns a reference to the key carried by the header.
+///
+/// If the header has no key, this returns `NULL`.  The returned
+/// object must be deallocated using `pgp_tpk_free` even though it
+/// only references the header.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_header_key(header: *const AutocryptHeader) -> Maybe<TPK> {
+    header.ref_raw().key.as_ref().move_into_raw()
+}
+
+/// Returns the value of the attribute `key`.
+///
+/// If the attribute is not present, this returns `NULL`.  The caller
+/// must free the returned value.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_header_get(header: *const AutocryptHeader,
+                            key: *const c_char)
+                            -> *mut c_char {
+    let key = ffi_param_cstr!(key).to_string_lossy();
+    match header.ref_raw().get(&key) {
+        Some(a) => ffi_return_maybe_string!(&a.value),
+        None => ptr::null_mut(),
+    }
+}
+
+/// The Autocrypt headers of a mail.
+///
+/// Wraps [`sequoia-openpgp::autocrypt::AutocryptHeaders`].
+///
+/// [`sequoia-openpgp::autocrypt::AutocryptHeaders`]: ../../sequoia_openpgp/autocrypt/struct.AutocryptHeaders.html
+#[::ffi_wrapper_type(prefix = "pgp_", derive = "Debug, PartialEq")]
+pub struct AutocryptHeaders(autocrypt::AutocryptHeaders);
+
+/// Parses the Autocrypt headers of a mail.
+///
+/// `buf` should contain all of the mail's headers.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_headers_from_bytes(errp: Option<&mut *mut ::error::Error>,
+                                    buf: *const uint8_t, len: size_t)
+                                    -> Maybe<AutocryptHeaders> {
+    assert!(!buf.is_null());
+    let buf = unsafe {
+        slice::from_raw_parts(buf, len as usize)
+    };
+    autocrypt::AutocryptHeaders::from_bytes(buf).move_into_raw(errp)
+}
+
+/// Parses the Autocrypt headers of the mail in the file `filename`.
+///
+/// If the file is in mbox format, then only the first mail is
+/// considered.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_headers_from_file(errp: Option<&mut *mut ::error::Error>,
+                                   filename: *const c_char)
+                                   -> Maybe<AutocryptHeaders> {
+    let filename = ffi_param_cstr!(filename).to_string_lossy().into_owned();
+    autocrypt::AutocryptHeaders::from_file(&filename).move_into_raw(errp)
+}
+
+/// Parses the Autocrypt headers of the mail read from `reader`.
+///
+/// If `reader` contains multiple mails, then only the first mail is
+/// considered.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_headers_from_reader(errp: Option<&mut *mut ::error::Error>,
+                                     reader: *mut Reader)
+                                     -> Maybe<AutocryptHeaders> {
+    autocrypt::AutocryptHeaders::from_reader(reader.ref_mut_raw())
+        .move_into_raw(errp)
+}
+
+/// Returns the value of the mail's From header.
+///
+/// If there is no From header, this returns `NULL`.  The caller must
+/// free the returned value.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_headers_from(headers: *const AutocryptHeaders)
+                              -> *mut c_char {
+    match headers.ref_raw().from {
+        Some(ref from) => ffi_return_maybe_string!(from),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Returns the number of Autocrypt headers.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_headers_count(headers: *const AutocryptHeaders) -> size_t {
+    headers.ref_raw().headers.len()
+}
+
+/// Returns a reference to the `idx`th Autocrypt header.
+///
+/// If `idx` is out of range, this returns `NULL`.  The returned
+/// object must be deallocated using `pgp_autocrypt_header_free` even
+/// though it only references `headers`.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_headers_nth(headers: *const AutocryptHeaders, idx: size_t)
+                             -> Maybe<AutocryptHeader> {
+    headers.ref_raw().headers.get(idx).move_into_raw()
+}
+
+/// Holds an Autocrypt Setup Message.
+///
+/// Wraps [`sequoia-openpgp::autocrypt::AutocryptSetupMessage`].
+///
+/// [`sequoia-openpgp::autocrypt::AutocryptSetupMessage`]: ../../sequoia_openpgp/autocrypt/struct.AutocryptSetupMessage.html
+#[::ffi_wrapper_type(prefix = "pgp_", derive = "Debug, PartialEq")]
+pub struct AutocryptSetupMessage(autocrypt::AutocryptSetupMessage);
+
+/// Creates a new Autocrypt Setup Message for `tpk`.
+///
+/// Unless a passcode is set using
+/// `pgp_autocrypt_setup_message_set_passcode`, a random passcode is
+/// generated when the message is serialized.
+///
+/// # Example
+///
+/// ```c
+/// #include <assert.h>
+/// #include <stdlib.h>
+/// #include <sequoia/openpgp.h>
+///
+/// pgp_tpk_builder_t builder;
+/// pgp_tpk_t tpk;
+/// pgp_signature_t revocation;
+///
+/// builder = pgp_tpk_builder_autocrypt ("alice@example.org");
+/// pgp_tpk_builder_generate (NULL, builder, &tpk, &revocation);
+/// assert (tpk);
+/// pgp_signature_free (revocation);
+///
+/// pgp_autocrypt_setup_message_t asm = pgp_autocrypt_setup_message_new (tpk);
+/// asm = pgp_autocrypt_setup_message_set_prefer_encrypt (asm, "mutual");
+///
+/// void *buf = NULL;
+/// size_t len = 0;
+/// pgp_writer_t writer = pgp_writer_alloc (&buf, &len);
+/// pgp_status_t rc = pgp_autocrypt_setup_message_serialize (NULL, asm, writer);
+/// assert (rc == PGP_STATUS_SUCCESS);
+/// pgp_writer_free (writer);
+///
+/// pgp_password_t passcode = pgp_autocrypt_setup_message_passcode (asm);
+/// assert (passcode);
+///
+/// pgp_reader_t reader = pgp_reader_from_bytes (buf, len);
+/// pgp_autocrypt_setup_message_parser_t parser =
+///   pgp_autocrypt_setup_message_from_reader (NULL, reader);
+/// assert (parser);
+/// rc = pgp_autocrypt_setup_message_parser_decrypt (NULL, parser, passcode);
+/// assert (rc == PGP_STATUS_SUCCESS);
+/// pgp_autocrypt_setup_message_t asm2 =
+///   pgp_autocrypt_setup_message_parser_parse (NULL, parser);
+/// assert (asm2);
+///
+/// pgp_tpk_t tpk2 = pgp_autocrypt_setup_message_into_tpk (asm2);
+/// assert (pgp_tpk_equal (tpk, tpk2));
+///
+/// pgp_tpk_free (tpk2);
+/// pgp_reader_free (reader);
+/// pgp_password_free (passcode);
+/// pgp_autocrypt_setup_message_free (asm);
+/// free (buf);
+/// pgp_tpk_free (tpk);
+/// ```
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_setup_message_new(tpk: *const TPK)
+                                   -> *mut AutocryptSetupMessage {
+    autocrypt::AutocryptSetupMessage::new(tpk.ref_raw().clone())
+        .move_into_raw()
+}
+
+/// Sets the prefer encrypt header.
+///
+/// Consumes `asm` and returns the modified message.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_setup_message_set_prefer_encrypt(
+    asm: *mut AutocryptSetupMessage, value: *const c_char)
+    -> *mut AutocryptSetupMessage
+{
+    let value = ffi_param_cstr!(value).to_string_lossy();
+    asm.move_from_raw().set_prefer_encrypt(&value).move_into_raw()
+}
+
+/// Returns the prefer encrypt header.
+///
+/// If the header is not set, this returns `NULL`.  The caller must
+/// free the returned value.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_setup_message_prefer_encrypt(
+    asm: *const AutocryptSetupMessage)
+    -> *mut c_char
+{
+    match asm.ref_raw().prefer_encrypt() {
+        Some(v) => ffi_return_maybe_string!(v),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Sets the passcode.
+///
+/// Consumes `asm` and returns the modified message.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_setup_message_set_passcode(
+    asm: *mut AutocryptSetupMessage, passcode: *const Password)
+    -> *mut AutocryptSetupMessage
+{
+    let passcode = passcode.ref_raw().clone();
+    asm.move_from_raw().set_passcode(passcode).move_into_raw()
+}
+
+/// Returns a reference to the passcode.
+///
+/// If no passcode has been set or generated yet, this returns
+/// `NULL`.  The returned object must be deallocated using
+/// `pgp_password_free` even though it only references `asm`.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_setup_message_passcode(asm: *const AutocryptSetupMessage)
+                                        -> Maybe<Password> {
+    asm.ref_raw().passcode().move_into_raw()
+}
+
+/// Returns the "Passcode-Format" header.
+///
+/// If the header is not set, this returns `NULL`.  The caller must
+/// free the returned value.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_setup_message_passcode_format(
+    asm: *const AutocryptSetupMessage)
+    -> *mut c_char
+{
+    match asm.ref_raw().passcode_format() {
+        Some(v) => ffi_return_maybe_string!(v),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Returns the "Passcode-Begin" header.
+///
+/// If the header is not set, this returns `NULL`.  The caller must
+/// free the returned value.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_setup_message_passcode_begin(
+    asm: *const AutocryptSetupMessage)
+    -> *mut c_char
+{
+    match asm.ref_raw().passcode_begin() {
+        Some(v) => ffi_return_maybe_string!(v),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Writes the Autocrypt Setup Message to `writer`.
+///
+/// If no passcode has been set, a random passcode is generated.  It
+/// can be retrieved using `pgp_autocrypt_setup_message_passcode`.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_setup_message_serialize(
+    errp: Option<&mut *mut ::error::Error>,
+    asm: *mut AutocryptSetupMessage, writer: *mut Writer)
+    -> Status
+{
+    asm.ref_mut_raw().serialize(writer.ref_mut_raw()).move_into_raw(errp)
+}
+
+/// Returns the TPK.
+///
+/// Consumes `asm`.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_setup_message_into_tpk(asm: *mut AutocryptSetupMessage)
+                                        -> *mut TPK {
+    asm.move_from_raw().into_tpk().move_into_raw()
+}
+
+/// A parser for an Autocrypt Setup Message.
+///
+/// Wraps [`sequoia-openpgp::autocrypt::AutocryptSetupMessageParser`].
+///
+/// [`sequoia-openpgp::autocrypt::AutocryptSetupMessageParser`]: ../../sequoia_openpgp/autocrypt/struct.AutocryptSetupMessageParser.html
+#[::ffi_wrapper_type(prefix = "pgp_")]
+pub struct AutocryptSetupMessageParser<'a>(
+    autocrypt::AutocryptSetupMessageParser<'a>);
+
+/// Starts parsing the Autocrypt Setup Message read from `reader`.
+///
+/// The parser must not outlive `reader`.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_setup_message_from_reader<'a>(
+    errp: Option<&mut *mut ::error::Error>, reader: *mut Reader)
+    -> Maybe<AutocryptSetupMessageParser<'a>>
+{
+    autocrypt::AutocryptSetupMessage::from_reader(reader.ref_mut_raw())
+        .move_into_raw(errp)
+}
+
+/// Returns the "Passcode-Format" header.
+///
+/// If the header is not set, this returns `NULL`.  The caller must
+/// free the returned value.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_setup_message_parser_passcode_format(
+    parser: *const AutocryptSetupMessageParser)
+    -> *mut c_char
+{
+    match parser.ref_raw().passcode_format() {
+        Some(v) => ffi_return_maybe_string!(v),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Returns the "Passcode-Begin" header.
+///
+/// If the header is not set, this returns `NULL`.  The caller must
+/// free the returned value.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_setup_message_parser_passcode_begin(
+    parser: *const AutocryptSetupMessageParser)
+    -> *mut c_char
+{
+    match parser.ref_raw().passcode_begin() {
+        Some(v) => ffi_return_maybe_string!(v),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Tries to decrypt the message using `passcode`.
+///
+/// On success, use `pgp_autocrypt_setup_message_parser_parse` to
+/// extract the Autocrypt Setup Message.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_setup_message_parser_decrypt(
+    errp: Option<&mut *mut ::error::Error>,
+    parser: *mut AutocryptSetupMessageParser,
+    passcode: *const Password)
+    -> Status
+{
+    parser.ref_mut_raw().decrypt(passcode.ref_raw()).move_into_raw(errp)
+}
+
+/// Finishes parsing the Autocrypt Setup Message.
+///
+/// The message must have been decrypted using
+/// `pgp_autocrypt_setup_message_parser_decrypt`.
+///
+/// Consumes `parser`.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_autocrypt_setup_message_parser_parse(
+    errp: Option<&mut *mut ::error::Error>,
+    parser: *mut AutocryptSetupMessageParser)
+    -> Maybe<AutocryptSetupMessage>
+{
+    parser.move_from_raw().parse().move_into_raw(errp)
+}
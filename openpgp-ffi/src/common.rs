@@ -11,8 +11,11 @@ use std::hash::BuildHasher;
 macro_rules! ffi_free {
     ($name:ident) => {{
         if let Some(ptr) = $name {
-            unsafe {
-                drop(Box::from_raw(ptr))
+            let ptr = ptr as *mut _;
+            if ::tracking::release(ptr, None) {
+                unsafe {
+                    drop(Box::from_raw(ptr))
+                }
             }
         }
     }};
@@ -30,6 +33,9 @@ macro_rules! ffi_param_move {
         if $name.is_null() {
             panic!("Parameter {} is NULL", stringify!($name));
         }
+        if ! ::tracking::release($name, None) {
+            panic!("Parameter {} is used after free", stringify!($name));
+        }
         unsafe {
             Box::from_raw($name)
         }
@@ -205,7 +211,7 @@ macro_rules! ffi_make_fry_from_errp {
         #[allow(unused_macros)]
         macro_rules! ffi_try_box {
             ($expr:expr) => {
-                Box::into_raw(Box::new(ffi_try!($expr)))
+                box_raw!(ffi_try!($expr))
             }
         }
     }
@@ -215,9 +221,11 @@ macro_rules! ffi_make_fry_from_errp {
 ///
 /// This is used to transfer ownership from Rust to C.
 macro_rules! box_raw {
-    ($expr:expr) => {
-        Box::into_raw(Box::new($expr))
-    }
+    ($expr:expr) => {{
+        let ptr = Box::into_raw(Box::new($expr));
+        ::tracking::register(ptr, None);
+        ptr
+    }}
 }
 
 /// Box an Option<T>, then turn into raw pointer.
@@ -280,10 +288,13 @@ pub(crate) fn build_hasher() -> DefaultHasher {
 }
 
 pub mod armor;
+#[cfg(feature = "experimental")]
+pub mod autocrypt;
 pub mod crypto;
 pub mod error;
 pub mod fingerprint;
 pub mod io;
+pub mod json;
 pub mod keyid;
 pub mod packet;
 pub mod packet_pile;
@@ -292,3 +303,5 @@ pub mod serialize;
 pub mod tpk;
 pub mod tsk;
 pub mod revocation_status;
+pub mod tracking;
+pub mod version;
@@ -4,17 +4,20 @@
 //!
 //! [`sequoia-openpgp::crypto`]: ../../sequoia_openpgp/crypto/index.html
 
-use libc::{size_t, uint8_t};
-use nettle::Yarrow;
+use libc::{size_t, uint8_t, uint32_t};
 
 extern crate sequoia_openpgp as openpgp;
 use self::openpgp::{
     crypto,
+    constants::HashAlgorithm,
 };
 use super::packet::key::Key;
 
+use Maybe;
 use MoveFromRaw;
 use MoveIntoRaw;
+use MoveResultIntoRaw;
+use RefRaw;
 
 /// Holds a session key.
 ///
@@ -26,7 +29,7 @@ pub struct SessionKey(openpgp::crypto::SessionKey);
 /// Creates a new session key.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
 fn pgp_session_key_new(size: size_t) -> *mut SessionKey {
-    openpgp::crypto::SessionKey::new(&mut Yarrow::default(), size)
+    openpgp::crypto::SessionKey::new(size)
         .move_into_raw()
 }
 
@@ -56,6 +59,52 @@ fn pgp_password_from_bytes(buf: *const uint8_t, size: size_t) -> *mut Password {
     openpgp::crypto::Password::from(buf).move_into_raw()
 }
 
+/// A string-to-key (S2K) specifier.
+///
+/// S2K specifiers are used to derive symmetric keys from passwords.
+#[::ffi_wrapper_type(prefix = "pgp_", name = "s2k",
+                     derive = "Clone, Debug, PartialEq")]
+pub struct S2K(openpgp::crypto::s2k::S2K);
+
+/// Returns the default S2K specifier.
+///
+/// This is an iterated and salted S2K using a random salt.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_s2k_default() -> *mut S2K {
+    openpgp::crypto::s2k::S2K::default().move_into_raw()
+}
+
+/// Returns an iterated and salted S2K specifier.
+///
+/// The salt is chosen at random.  Pass 0 for `hash_algo` to use the
+/// default hash algorithm.  `hash_bytes` is the number of bytes to
+/// hash, which is rounded up to the nearest value that can be
+/// represented.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_s2k_iterated(hash_algo: uint8_t, hash_bytes: uint32_t) -> *mut S2K {
+    use self::openpgp::crypto::s2k::S2K;
+
+    let mut s2k = S2K::default();
+    if let S2K::Iterated { ref mut hash, hash_bytes: ref mut count, .. } = s2k
+    {
+        if hash_algo != 0 {
+            *hash = HashAlgorithm::from(hash_algo);
+        }
+        *count = S2K::nearest_hash_count(hash_bytes as usize);
+    }
+    s2k.move_into_raw()
+}
+
+/// Derives a key of size `key_size` from `password`.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_s2k_derive_key(errp: Option<&mut *mut ::error::Error>,
+                      s2k: *const S2K, password: *const Password,
+                      key_size: size_t)
+                      -> Maybe<SessionKey> {
+    s2k.ref_raw().derive_key(password.ref_raw(), key_size)
+        .move_into_raw(errp)
+}
+
 /// Frees a signer.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
 pub extern "C" fn pgp_signer_free
@@ -143,6 +143,9 @@ pub enum Status {
     // XXX: Skipping UnsupportedAEADAlgorithm = -26
     // XXX: Skipping MissingSessionKey = -27
     // XXX: Skipping UnsupportedCompressionAlgorithm = -28
+
+    /// Policy violation.
+    PolicyViolation = -29,
 }
 
 /// Returns the error message.
@@ -182,6 +185,7 @@ pub extern "C" fn pgp_status_to_string(status: Status) -> *const c_char {
         MalformedMessage => "Malformed message\x00",
         IndexOutOfRange => "Index out of range\x00",
         UnsupportedTPK => "TPK not supported\x00",
+        PolicyViolation => "Policy violation\x00",
     }.as_bytes().as_ptr() as *const c_char
 }
 
@@ -231,6 +235,8 @@ impl<'a> From<&'a failure::Error> for Status {
                     Status::IndexOutOfRange,
                 &openpgp::Error::UnsupportedTPK(_) =>
                     Status::UnsupportedTPK,
+                &openpgp::Error::PolicyViolation(_) =>
+                    Status::PolicyViolation,
             }
         }
 
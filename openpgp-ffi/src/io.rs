@@ -12,6 +12,7 @@ use std::os::unix::io::FromRawFd;
 extern crate sequoia_openpgp as openpgp;
 
 use Maybe;
+use RefRaw;
 use RefMutRaw;
 use MoveIntoRaw;
 use MoveResultIntoRaw;
@@ -73,6 +74,98 @@ pub extern "C" fn pgp_reader_from_bytes(buf: *const uint8_t,
     ReaderKind::Generic(Box::new(Cursor::new(buf))).move_into_raw()
 }
 
+/// The callback type for the callback-based reader interface.
+type ReaderCallbackFn = extern fn(*mut c_void, *mut uint8_t, size_t) -> ssize_t;
+
+/// Creates a reader from a callback and cookie.
+///
+/// This reader calls the given callback to read data.  The callback
+/// is invoked with `cookie`, a buffer, and the size of the buffer.
+/// It must store at most that many bytes in the buffer, and return
+/// the number of bytes stored, or zero to signal the end of the
+/// stream.  If it returns a negative value, or more than the size of
+/// the buffer, the read fails.
+///
+/// The cookie is not freed when the reader is freed.  It must
+/// outlive the reader.
+///
+/// # Example
+///
+/// ```c
+/// #include <assert.h>
+/// #include <error.h>
+/// #include <string.h>
+///
+/// #include <sequoia/openpgp.h>
+///
+/// struct data {
+///   const char *buf;
+///   size_t len;
+/// };
+///
+/// static ssize_t
+/// read_cb (void *cookie, uint8_t *buf, size_t len)
+/// {
+///   struct data *data = cookie;
+///   if (len > data->len)
+///     len = data->len;
+///   memcpy (buf, data->buf, len);
+///   data->buf += len;
+///   data->len -= len;
+///   return len;
+/// }
+///
+/// int
+/// main ()
+/// {
+///   struct data data = { "Hello world!", 12 };
+///   pgp_reader_t reader = pgp_reader_from_callback (read_cb, &data);
+///
+///   pgp_error_t err;
+///   char message[13] = { 0 };
+///   ssize_t n = pgp_reader_read (&err, reader, (uint8_t *) message, 12);
+///   if (n < 0)
+///     error (1, 0, "Reading failed: %s", pgp_error_to_string (err));
+///   assert (n == 12);
+///   assert (strcmp (message, "Hello world!") == 0);
+///   assert (pgp_reader_read (&err, reader, (uint8_t *) message, 12) == 0);
+///
+///   pgp_reader_free (reader);
+///   return 0;
+/// }
+/// ```
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_reader_from_callback(cb: ReaderCallbackFn,
+                            cookie: *mut c_void) -> *mut Reader {
+    let r: Box<io::Read> = Box::new(ReaderCallback {
+        cb: cb,
+        cookie: cookie,
+    });
+    ReaderKind::Generic(r).move_into_raw()
+}
+
+/// A reader reading data using a callback.
+struct ReaderCallback {
+    cb: ReaderCallbackFn,
+    cookie: *mut c_void,
+}
+
+impl Read for ReaderCallback {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = (self.cb)(self.cookie, buf.as_mut_ptr(), buf.len());
+        if n < 0 {
+            Err(io::Error::new(io::ErrorKind::Other, "Read callback failed"))
+        } else if n as usize > buf.len() {
+            Err(io::Error::new(io::ErrorKind::Other,
+                               format!("Read callback claimed to read {} \
+                                        bytes into a buffer of {} bytes",
+                                       n, buf.len())))
+        } else {
+            Ok(n as usize)
+        }
+    }
+}
+
 /// Reads up to `len` bytes into `buf`.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
 pub extern "C" fn pgp_reader_read(errp: Option<&mut *mut ::error::Error>,
@@ -228,6 +321,52 @@ impl Write for WriterAlloc {
     }
 }
 
+/// Owns a chunk of memory.
+///
+/// Buffers are filled using writers created with
+/// `pgp_writer_from_buffer`, or returned by functions like
+/// `pgp_tpk_serialize_to_buffer`.  Unlike `pgp_writer_alloc`, the
+/// memory is owned by the buffer, and released using
+/// `pgp_buffer_free`.
+#[::ffi_wrapper_type(prefix = "pgp_")]
+pub struct Buffer(Vec<u8>);
+
+/// Creates an empty buffer.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_buffer_new() -> *mut Buffer {
+    Vec::new().move_into_raw()
+}
+
+/// Returns a pointer to the buffer's data.
+///
+/// If `len` is not `NULL`, the length of the data is stored there.
+/// The returned pointer is valid until the buffer is modified or
+/// freed.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_buffer_data(buf: *const Buffer, len: Option<&mut size_t>)
+                   -> *const uint8_t {
+    let buf = buf.ref_raw();
+    if let Some(len) = len {
+        *len = buf.len() as size_t;
+    }
+    buf.as_ptr()
+}
+
+/// Returns the length of the buffer's data.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_buffer_len(buf: *const Buffer) -> size_t {
+    buf.ref_raw().len() as size_t
+}
+
+/// Creates a writer appending to the given buffer.
+///
+/// The writer must be destroyed before the buffer is freed.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_writer_from_buffer(buf: *mut Buffer) -> *mut Writer {
+    let w: Box<io::Write> = Box::new(buf.ref_mut_raw());
+    w.move_into_raw()
+}
+
 /// Writes up to `len` bytes of `buf` into `writer`.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
 fn pgp_writer_write(errp: Option<&mut *mut ::error::Error>,
@@ -249,3 +388,23 @@ fn pgp_writer_write(errp: Option<&mut *mut ::error::Error>,
             -1
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern fn overlong_read(_: *mut c_void, _: *mut uint8_t, len: size_t)
+                            -> ssize_t {
+        len as ssize_t + 1
+    }
+
+    #[test]
+    fn callback_overlong_read() {
+        let mut reader = ReaderCallback {
+            cb: overlong_read,
+            cookie: ::std::ptr::null_mut(),
+        };
+        let mut buf = [0; 16];
+        assert!(reader.read(&mut buf).is_err());
+    }
+}
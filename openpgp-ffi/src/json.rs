@@ -0,0 +1,567 @@
+//! JSON descriptions of packets and TPKs.
+//!
+//! These are used by `pgp_packet_to_json` and `pgp_tpk_to_json`.
+//! The output never includes secret key material, session keys, or
+//! message bodies, only metadata.
+//!
+//! The format is stable: fields may be added in later versions, but
+//! existing fields will neither be removed nor change their meaning.
+//! Algorithms and types are given as the numeric identifiers from
+//! RFC 4880, times as seconds since the Unix epoch, and durations as
+//! seconds.  Absent values are `null`.  Binary strings (User IDs,
+//! file names) are converted to UTF-8, replacing invalid sequences.
+//!
+//! # Packets
+//!
+//! Every packet is an object with the following fields:
+//!
+//!   - `tag`: the packet's tag, e.g. `2` for signatures.
+//!   - `tag_name`: a human-readable name of the tag.
+//!
+//! Depending on the tag, more fields are present:
+//!
+//!   - Signature: `version`, `sigtype`, `pk_algo`, `hash_algo`,
+//!     `creation_time`, `expiration_time`, `key_expiration_time`,
+//!     `issuer` (hex Key ID), and `issuer_fingerprint` (hex).
+//!   - One-Pass Signature: `sigtype`, `pk_algo`, `hash_algo`,
+//!     `issuer`, and `last`.
+//!   - Public and secret (sub)keys: `version`, `fingerprint`,
+//!     `keyid`, `pk_algo`, `bits`, `creation_time`, and `has_secret`.
+//!   - User ID: `value`.
+//!   - User Attribute: `length`.
+//!   - Literal Data: `format`, `filename`, and `date`.
+//!   - Compressed Data: `algo`.
+//!   - PKESK: `version`, `recipient` (hex Key ID), and `pk_algo`.
+//!   - SKESK: `version`, `symmetric_algo`, and `aead_algo` (version
+//!     5 only, otherwise `null`).
+//!
+//! # TPKs
+//!
+//! A TPK is an object with the following fields:
+//!
+//!   - `fingerprint`: the primary key's fingerprint (hex).
+//!   - `primary`: the primary key, as described above.
+//!   - `binding_signature`: the primary key's current self
+//!     signature, as described above, or `null`.
+//!   - `revocation_status`: one of `"revoked"`, `"could_be"`, or
+//!     `"not_as_far_as_we_know"`.
+//!   - `expiration_time`: the primary key's expiration time, or
+//!     `null`.
+//!   - `userids`, `user_attributes`, `subkeys`: arrays of components.
+//!
+//! Every component is an object with the fields `binding_signature`,
+//! `revocation_status`, and `certifications` (the number of third
+//! party certifications).  User IDs additionally have `value`, User
+//! Attributes `length`, and subkeys `key` and `expiration_time`.
+//!
+//! # Parsing
+//!
+//! The descriptions are modeled by the types in this module, which
+//! implement serde's traits.  Hence, Rust programs can parse them
+//! using `serde_json`.
+
+extern crate serde;
+extern crate serde_derive;
+extern crate serde_json;
+extern crate sequoia_openpgp as openpgp;
+extern crate time;
+
+use self::serde_derive::{Deserialize, Serialize};
+
+use self::openpgp::{
+    Fingerprint,
+    KeyID,
+    Packet,
+    RevocationStatus,
+    TPK,
+    constants::{
+        AEADAlgorithm,
+        CompressionAlgorithm,
+        DataFormat,
+        HashAlgorithm,
+        PublicKeyAlgorithm,
+        SignatureType,
+        SymmetricAlgorithm,
+    },
+    packet::{Key, Signature, Tag},
+};
+
+/// Describes a packet.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PacketDescription {
+    /// The packet's tag.
+    pub tag: u8,
+    /// A human-readable name of the tag.
+    pub tag_name: String,
+    /// The fields depending on the tag.
+    #[serde(flatten)]
+    pub fields: PacketFields,
+}
+
+/// The fields of a packet description depending on the tag.
+///
+/// The variants are distinguished by their fields, hence their
+/// order matters when parsing.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PacketFields {
+    /// Describes a signature.
+    Signature(SignatureFields),
+    /// Describes a one-pass signature.
+    OnePassSig(OnePassSigFields),
+    /// Describes a public or secret (sub)key.
+    Key(KeyFields),
+    /// Describes a PKESK.
+    PKESK(PKESKFields),
+    /// Describes a SKESK.
+    SKESK(SKESKFields),
+    /// Describes a literal data packet.
+    Literal(LiteralFields),
+    /// Describes a User ID.
+    UserID(UserIDFields),
+    /// Describes a User Attribute.
+    UserAttribute(UserAttributeFields),
+    /// Describes a compressed data packet.
+    CompressedData(CompressedDataFields),
+    /// Packets without further fields.
+    Other {},
+}
+
+/// Describes a signature.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignatureFields {
+    /// The signature's version.
+    pub version: u8,
+    /// The signature's type.
+    pub sigtype: SignatureType,
+    /// The public key algorithm.
+    pub pk_algo: PublicKeyAlgorithm,
+    /// The hash algorithm.
+    pub hash_algo: HashAlgorithm,
+    /// The creation time.
+    pub creation_time: Option<i64>,
+    /// The validity period of the signature.
+    pub expiration_time: Option<i64>,
+    /// The validity period of the key.
+    pub key_expiration_time: Option<i64>,
+    /// The issuer's Key ID.
+    pub issuer: Option<KeyID>,
+    /// The issuer's fingerprint.
+    pub issuer_fingerprint: Option<Fingerprint>,
+}
+
+/// Describes a one-pass signature.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OnePassSigFields {
+    /// The signature's type.
+    pub sigtype: SignatureType,
+    /// The public key algorithm.
+    pub pk_algo: PublicKeyAlgorithm,
+    /// The hash algorithm.
+    pub hash_algo: HashAlgorithm,
+    /// The issuer's Key ID.
+    pub issuer: KeyID,
+    /// Whether this is the last one-pass signature.
+    pub last: bool,
+}
+
+/// Describes a public or secret (sub)key.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyFields {
+    /// The key's version.
+    pub version: u8,
+    /// The key's fingerprint.
+    pub fingerprint: Fingerprint,
+    /// The key's Key ID.
+    pub keyid: KeyID,
+    /// The public key algorithm.
+    pub pk_algo: PublicKeyAlgorithm,
+    /// The key's size in bits, if known.
+    pub bits: Option<usize>,
+    /// The creation time.
+    pub creation_time: i64,
+    /// Whether the packet contains secret key material.
+    pub has_secret: bool,
+}
+
+/// Describes a PKESK.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PKESKFields {
+    /// The packet's version.
+    pub version: u8,
+    /// The recipient's Key ID.
+    pub recipient: KeyID,
+    /// The public key algorithm.
+    pub pk_algo: PublicKeyAlgorithm,
+}
+
+/// Describes a SKESK.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SKESKFields {
+    /// The packet's version.
+    pub version: u8,
+    /// The symmetric algorithm.
+    pub symmetric_algo: SymmetricAlgorithm,
+    /// The AEAD algorithm, version 5 only.
+    pub aead_algo: Option<AEADAlgorithm>,
+}
+
+/// Describes a literal data packet.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LiteralFields {
+    /// The format of the data.
+    pub format: DataFormat,
+    /// The file name.
+    pub filename: Option<String>,
+    /// The date.
+    pub date: Option<i64>,
+}
+
+/// Describes a User ID.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserIDFields {
+    /// The User ID.
+    pub value: String,
+}
+
+/// Describes a User Attribute.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserAttributeFields {
+    /// The size of the User Attribute.
+    pub length: usize,
+}
+
+/// Describes a compressed data packet.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CompressedDataFields {
+    /// The compression algorithm.
+    pub algo: CompressionAlgorithm,
+}
+
+/// Describes a revocation status.
+///
+/// The names must match the ones used by `VerificationResult`'s
+/// serde representation.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RevocationStatusDescription {
+    /// The component is revoked.
+    Revoked,
+    /// There is a revocation certificate from a possible designated
+    /// revoker.
+    CouldBe,
+    /// The component is not revoked.
+    NotAsFarAsWeKnow,
+}
+
+/// Describes a TPK.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TPKDescription {
+    /// The primary key's fingerprint.
+    pub fingerprint: Fingerprint,
+    /// The primary key.
+    pub primary: PacketDescription,
+    /// The primary key's current self signature.
+    pub binding_signature: Option<PacketDescription>,
+    /// The TPK's revocation status.
+    pub revocation_status: RevocationStatusDescription,
+    /// The primary key's expiration time.
+    pub expiration_time: Option<i64>,
+    /// The User IDs.
+    pub userids: Vec<UserIDDescription>,
+    /// The User Attributes.
+    pub user_attributes: Vec<UserAttributeDescription>,
+    /// The subkeys.
+    pub subkeys: Vec<SubkeyDescription>,
+}
+
+/// Describes a User ID and its binding.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserIDDescription {
+    /// The User ID.
+    pub value: String,
+    /// The current binding signature.
+    pub binding_signature: Option<PacketDescription>,
+    /// The revocation status.
+    pub revocation_status: RevocationStatusDescription,
+    /// The number of third party certifications.
+    pub certifications: usize,
+}
+
+/// Describes a User Attribute and its binding.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserAttributeDescription {
+    /// The size of the User Attribute.
+    pub length: usize,
+    /// The current binding signature.
+    pub binding_signature: Option<PacketDescription>,
+    /// The revocation status.
+    pub revocation_status: RevocationStatusDescription,
+    /// The number of third party certifications.
+    pub certifications: usize,
+}
+
+/// Describes a subkey and its binding.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SubkeyDescription {
+    /// The subkey.
+    pub key: PacketDescription,
+    /// The current binding signature.
+    pub binding_signature: Option<PacketDescription>,
+    /// The revocation status.
+    pub revocation_status: RevocationStatusDescription,
+    /// The subkey's expiration time.
+    pub expiration_time: Option<i64>,
+    /// The number of third party certifications.
+    pub certifications: usize,
+}
+
+fn secs(t: &time::Tm) -> i64 {
+    t.to_timespec().sec
+}
+
+fn lossy(s: &[u8]) -> String {
+    String::from_utf8_lossy(s).into_owned()
+}
+
+impl<'a, 'b> From<&'a RevocationStatus<'b>> for RevocationStatusDescription {
+    fn from(rs: &'a RevocationStatus<'b>) -> Self {
+        match rs {
+            RevocationStatus::Revoked(_) =>
+                RevocationStatusDescription::Revoked,
+            RevocationStatus::CouldBe(_) =>
+                RevocationStatusDescription::CouldBe,
+            RevocationStatus::NotAsFarAsWeKnow =>
+                RevocationStatusDescription::NotAsFarAsWeKnow,
+        }
+    }
+}
+
+impl<'a> From<&'a Key> for KeyFields {
+    fn from(key: &'a Key) -> Self {
+        KeyFields {
+            version: key.version(),
+            fingerprint: key.fingerprint(),
+            keyid: key.keyid(),
+            pk_algo: key.pk_algo(),
+            bits: key.mpis().bits(),
+            creation_time: secs(key.creation_time()),
+            has_secret: key.secret().is_some(),
+        }
+    }
+}
+
+impl<'a> From<&'a Signature> for SignatureFields {
+    fn from(sig: &'a Signature) -> Self {
+        SignatureFields {
+            version: sig.version(),
+            sigtype: sig.sigtype(),
+            pk_algo: sig.pk_algo(),
+            hash_algo: sig.hash_algo(),
+            creation_time: sig.signature_creation_time().as_ref().map(secs),
+            expiration_time: sig.signature_expiration_time()
+                .map(|d| d.num_seconds()),
+            key_expiration_time: sig.key_expiration_time()
+                .map(|d| d.num_seconds()),
+            issuer: sig.issuer(),
+            issuer_fingerprint: sig.issuer_fingerprint(),
+        }
+    }
+}
+
+impl PacketDescription {
+    fn new(tag: Tag, fields: PacketFields) -> Self {
+        PacketDescription {
+            tag: tag.into(),
+            tag_name: tag.to_string(),
+            fields: fields,
+        }
+    }
+
+    fn key(key: &Key, tag: Tag) -> Self {
+        Self::new(tag, PacketFields::Key(key.into()))
+    }
+
+    fn signature(sig: Option<&Signature>) -> Option<Self> {
+        sig.map(|sig| Self::new(Tag::Signature,
+                                PacketFields::Signature(sig.into())))
+    }
+}
+
+impl<'a> From<&'a Packet> for PacketDescription {
+    fn from(p: &'a Packet) -> Self {
+        let fields = match p {
+            Packet::Signature(sig) => PacketFields::Signature(sig.into()),
+            Packet::OnePassSig(ops) =>
+                PacketFields::OnePassSig(OnePassSigFields {
+                    sigtype: ops.sigtype(),
+                    pk_algo: ops.pk_algo(),
+                    hash_algo: ops.hash_algo(),
+                    issuer: ops.issuer().clone(),
+                    last: ops.last(),
+                }),
+            Packet::PublicKey(k) | Packet::PublicSubkey(k)
+                | Packet::SecretKey(k) | Packet::SecretSubkey(k) =>
+                PacketFields::Key(k.into()),
+            Packet::UserID(u) =>
+                PacketFields::UserID(UserIDFields { value: lossy(u.value()) }),
+            Packet::UserAttribute(u) =>
+                PacketFields::UserAttribute(UserAttributeFields {
+                    length: u.value().len(),
+                }),
+            Packet::Literal(l) =>
+                PacketFields::Literal(LiteralFields {
+                    format: l.format(),
+                    filename: l.filename().map(lossy),
+                    date: l.date().map(secs),
+                }),
+            Packet::CompressedData(c) =>
+                PacketFields::CompressedData(CompressedDataFields {
+                    algo: c.algorithm(),
+                }),
+            Packet::PKESK(p) =>
+                PacketFields::PKESK(PKESKFields {
+                    version: p.version(),
+                    recipient: p.recipient().clone(),
+                    pk_algo: p.pk_algo(),
+                }),
+            Packet::SKESK(s) => {
+                let (symmetric_algo, aead_algo) = match s {
+                    openpgp::packet::SKESK::V4(s) =>
+                        (s.symmetric_algo(), None),
+                    openpgp::packet::SKESK::V5(s) =>
+                        (s.symmetric_algo(), Some(s.aead_algo())),
+                };
+                PacketFields::SKESK(SKESKFields {
+                    version: s.version(),
+                    symmetric_algo: symmetric_algo,
+                    aead_algo: aead_algo,
+                })
+            },
+            _ => PacketFields::Other {},
+        };
+        Self::new(p.tag(), fields)
+    }
+}
+
+impl<'a> From<&'a TPK> for TPKDescription {
+    fn from(tpk: &'a TPK) -> Self {
+        let primary = tpk.primary();
+        let primary_sig = tpk.primary_key_signature();
+
+        TPKDescription {
+            fingerprint: tpk.fingerprint(),
+            primary: PacketDescription::key(primary, Tag::PublicKey),
+            binding_signature: PacketDescription::signature(primary_sig),
+            revocation_status: (&tpk.revocation_status()).into(),
+            expiration_time: primary_sig
+                .and_then(|s| primary.expiration_time(s))
+                .as_ref().map(secs),
+            userids: tpk.userids().map(|b| UserIDDescription {
+                value: lossy(b.userid().value()),
+                binding_signature:
+                    PacketDescription::signature(b.binding_signature()),
+                revocation_status: (&b.revoked(None)).into(),
+                certifications: b.certifications().len(),
+            }).collect(),
+            user_attributes: tpk.user_attributes().map(|b| {
+                UserAttributeDescription {
+                    length: b.user_attribute().value().len(),
+                    binding_signature:
+                        PacketDescription::signature(b.binding_signature()),
+                    revocation_status: (&b.revoked(None)).into(),
+                    certifications: b.certifications().len(),
+                }
+            }).collect(),
+            subkeys: tpk.subkeys().map(|b| SubkeyDescription {
+                key: PacketDescription::key(b.subkey(), Tag::PublicSubkey),
+                binding_signature:
+                    PacketDescription::signature(b.binding_signature()),
+                revocation_status: (&b.revoked(None)).into(),
+                expiration_time: b.binding_signature()
+                    .and_then(|s| b.subkey().expiration_time(s))
+                    .as_ref().map(secs),
+                certifications: b.certifications().len(),
+            }).collect(),
+        }
+    }
+}
+
+/// Encodes `description` as JSON.
+///
+/// Control characters, including NUL, are escaped, hence the result
+/// can always be returned as C string.
+fn to_string<T: serde::Serialize>(description: &T) -> String {
+    serde_json::to_string(description)
+        .expect("descriptions are always representable in JSON")
+}
+
+/// Describes the given packet.
+pub(crate) fn packet(p: &Packet) -> String {
+    to_string(&PacketDescription::from(p))
+}
+
+/// Describes the given TPK.
+pub(crate) fn tpk(tpk: &TPK) -> String {
+    to_string(&TPKDescription::from(tpk))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::openpgp::packet::{Literal, UserID};
+    use super::openpgp::tpk::{CipherSuite, TPKBuilder};
+
+    #[test]
+    fn roundtrip() {
+        let (tpk, _) = TPKBuilder::general_purpose(CipherSuite::Cv25519,
+                                                   Some("alice@example.org"))
+            .generate().unwrap();
+
+        let description = TPKDescription::from(&tpk);
+        let json = super::tpk(&tpk);
+        assert_eq!(serde_json::from_str::<TPKDescription>(&json).unwrap(),
+                   description);
+        assert_eq!(description.fingerprint, tpk.fingerprint());
+        assert_eq!(description.userids[0].value, "alice@example.org");
+        assert_eq!(description.subkeys.len(), 2);
+
+        let mut literal = Literal::new(DataFormat::Text);
+        literal.set_filename("\"quoted\"\0.txt").unwrap();
+        let packets = tpk.into_packets().into_iter()
+            .chain(vec![
+                UserID::from("Alice \"A\"\n<alice@example.org>").into(),
+                literal.into(),
+            ]);
+        for p in packets {
+            let description = PacketDescription::from(&p);
+            let json = packet(&p);
+            assert!(! json.contains('\0'));
+            assert_eq!(description.tag, u8::from(p.tag()));
+            assert_eq!(serde_json::from_str::<PacketDescription>(&json)
+                       .unwrap(), description);
+        }
+    }
+
+    #[test]
+    fn revocation_status_names() {
+        use super::openpgp::parse::stream::VerificationResult;
+
+        let (tpk, _) = TPKBuilder::general_purpose(CipherSuite::Cv25519,
+                                                   Some("alice@example.org"))
+            .generate().unwrap();
+        let sig = tpk.userids().nth(0).unwrap().selfsigs()[0].clone();
+        for &(ref status, description) in
+            [(RevocationStatus::CouldBe(&[]),
+              RevocationStatusDescription::CouldBe),
+             (RevocationStatus::NotAsFarAsWeKnow,
+              RevocationStatusDescription::NotAsFarAsWeKnow)].iter()
+        {
+            let result = VerificationResult::GoodChecksum(
+                sig.clone(), &tpk, tpk.primary(), None, status.clone());
+            let result = serde_json::to_value(&result).unwrap();
+            assert_eq!(result["revocation_status"],
+                       serde_json::to_value(description).unwrap());
+        }
+    }
+}
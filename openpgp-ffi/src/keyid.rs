@@ -11,7 +11,7 @@
 //! [`sequoia-openpgp::KeyID`]: ../../sequoia_openpgp/enum.KeyID.html
 
 use std::slice;
-use libc::{uint8_t, c_char};
+use libc::{uint8_t, c_char, c_int};
 
 extern crate sequoia_openpgp as openpgp;
 
@@ -88,3 +88,41 @@ fn pgp_keyid_from_hex(id: *const c_char) -> Maybe<KeyID> {
 fn pgp_keyid_to_hex(id: *const KeyID) -> *mut c_char {
     ffi_return_string!(id.ref_raw().to_hex())
 }
+
+fn int_to_identifier_format(format: c_int) -> openpgp::IdentifierFormat {
+    match format {
+        0 => openpgp::IdentifierFormat::Full,
+        1 => openpgp::IdentifierFormat::LongKeyID,
+        2 => openpgp::IdentifierFormat::LegacyShortKeyID,
+        n => panic!("Bad identifier format: {}", n),
+    }
+}
+
+/// Selects how fingerprints and key IDs are displayed.
+///
+/// This affects `pgp_keyid_to_string` and
+/// `pgp_fingerprint_to_string`, and applies to the whole process.
+///
+/// # Example
+///
+/// ```c
+/// #include <assert.h>
+/// #include <stdlib.h>
+/// #include <string.h>
+/// #include <sequoia/openpgp.h>
+///
+/// pgp_fingerprint_t fp =
+///     pgp_fingerprint_from_hex ("D2F2C5D45BE9FDE6A4EE0AAF31855247603831FD");
+///
+/// pgp_identifier_format_set (PGP_IDENTIFIER_FORMAT_LONG_KEYID);
+/// char *s = pgp_fingerprint_to_string (fp);
+/// assert (strcmp (s, "3185 5247 6038 31FD") == 0);
+/// free (s);
+///
+/// pgp_identifier_format_set (PGP_IDENTIFIER_FORMAT_FULL);
+/// pgp_fingerprint_free (fp);
+/// ```
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_identifier_format_set(format: c_int) {
+    openpgp::IdentifierFormat::set(int_to_identifier_format(format))
+}
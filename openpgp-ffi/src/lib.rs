@@ -208,6 +208,12 @@
 //! way to transfer ownership back to Rust is to deallocate the
 //! object.  Failure to deallocate an object leads to a memory leak.
 //!
+//! To check that an application manages the objects correctly,
+//! compile the library with the `track-ownership` feature, and call
+//! `pgp_shutdown` at the end.  See the [tracking] module for details.
+//!
+//! [tracking]: tracking/index.html
+//!
 //! Looking at the Rust functions in this library, when ownership of
 //! an object of type `T` is transferred across the FFI boundary, the
 //! function signature uses the type `*mut T`.
@@ -311,6 +317,47 @@
 //!
 //! pgp_reader_free (armor);
 //! ```
+//!
+//!
+//! # ABI Stability
+//!
+//! The shared library's major version is part of its file name,
+//! e.g. `libsequoia_openpgp_ffi.so.0`.  Within a major version, we
+//! make the following guarantees for all stable symbols:
+//!
+//!  - Stable symbols are not removed.
+//!  - The signatures of stable functions do not change.
+//!  - The values of enumerations do not change.
+//!  - The layout of objects is not exposed, and may change.
+//!
+//! The stable symbols are listed in `symbols.txt`.  The test suite
+//! checks that the library exports exactly these symbols.
+//!
+//! Whenever stable symbols are added, the API level is incremented.
+//! The header defines the API level it corresponds to as
+//! `PGP_API_LEVEL`, and [`pgp_api_level`] returns the API level of
+//! the library.  Applications can use this to detect whether the
+//! library they are running with provides all the symbols they were
+//! compiled against.
+//!
+//! [`pgp_api_level`]: version/fn.pgp_api_level.html
+//!
+//! ## Experimental Symbols
+//!
+//! New interfaces may first be introduced as experimental symbols,
+//! which are not covered by these guarantees.  They are only
+//! exported if the library is compiled with the `experimental`
+//! feature, and only declared if `PGP_EXPERIMENTAL` is defined before
+//! including the header:
+//!
+//! ```c, ignore
+//! #define PGP_EXPERIMENTAL
+//! #include <sequoia/openpgp.h>
+//! ```
+//!
+//! Currently, the [Autocrypt] bindings are experimental.
+//!
+//! [Autocrypt]: autocrypt/index.html
 
 #![warn(missing_docs)]
 
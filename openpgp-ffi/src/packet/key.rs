@@ -4,7 +4,7 @@
 //!
 //!   [Section 5.5 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-5.5
 
-use libc::{c_int, time_t};
+use libc::{c_char, c_int, time_t};
 
 extern crate sequoia_openpgp as openpgp;
 use super::super::fingerprint::Fingerprint;
@@ -41,6 +41,20 @@ fn pgp_key_keyid(key: *const Key) -> *mut KeyID {
     key.ref_raw().keyid().move_into_raw()
 }
 
+/// Computes and returns the key's keygrip as hexadecimal number.
+///
+/// Keygrips are used by GnuPG, e.g. to name the files in gpg-agent's
+/// private key store.  Returns `NULL` if no keygrip is defined for
+/// the key's algorithm.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_key_keygrip(errp: Option<&mut *mut ::error::Error>,
+                   key: *const Key)
+                   -> *mut c_char {
+    ffi_make_fry_from_errp!(errp);
+    let keygrip = ffi_try!(key.ref_raw().keygrip());
+    ffi_return_string!(keygrip.to_hex())
+}
+
 /// Returns the key's creation time.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
 fn pgp_key_creation_time(key: *const Key) -> time_t {
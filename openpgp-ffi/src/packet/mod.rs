@@ -108,6 +108,37 @@ pub extern "C" fn pgp_tag_to_string(tag: uint8_t) -> *const c_char {
     }.as_bytes().as_ptr() as *const c_char
 }
 
+/// Returns a JSON description of the packet.
+///
+/// The description contains the packet's metadata, like algorithms,
+/// fingerprints, and creation times, but never any secret key
+/// material or message bodies.  The format is documented in
+/// `openpgp-ffi/src/json.rs`.
+///
+/// The caller must free the returned value.
+///
+/// # Example
+///
+/// ```c
+/// #include <assert.h>
+/// #include <stdlib.h>
+/// #include <string.h>
+/// #include <sequoia/openpgp.h>
+///
+/// pgp_packet_t p = pgp_user_id_new ("Alice \"A\" <alice@example.org>");
+/// char *json = pgp_packet_to_json (p);
+/// assert (strcmp (json,
+///                 "{\"tag\":13,\"tag_name\":\"User ID Packet\","
+///                 "\"value\":\"Alice \\\"A\\\" <alice@example.org>\"}") == 0);
+///
+/// free (json);
+/// pgp_packet_free (p);
+/// ```
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_packet_to_json(p: *const Packet) -> *mut c_char {
+    ffi_return_string!(super::json::packet(p.ref_raw()))
+}
+
 /// Given a packet references the contained signature, if any.
 ///
 /// If the Packet is not of the `Packet::Signature` variant, this
@@ -6,6 +6,7 @@ use libc::{uint8_t, size_t};
 use failure;
 extern crate sequoia_openpgp as openpgp;
 use super::Packet;
+use super::super::crypto::Password;
 
 use error::Status;
 use RefRaw;
@@ -16,6 +17,9 @@ use RefRaw;
 /// session key.  If `key` is NULL, or not large enough, then the key
 /// is not written to it.  Either way, `key_len` is set to the size of
 /// the session key.
+///
+/// Prefer `pgp_skesk_decrypt_with_password`, which does not require
+/// the password to be copied into unprotected memory.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
 pub extern "C" fn pgp_skesk_decrypt(errp: Option<&mut *mut ::error::Error>,
                                         skesk: *const Packet,
@@ -25,16 +29,43 @@ pub extern "C" fn pgp_skesk_decrypt(errp: Option<&mut *mut ::error::Error>,
                                         key: *mut uint8_t,
                                         key_len: *mut size_t)
                                         -> Status {
-    ffi_make_fry_from_errp!(errp);
     assert!(!password.is_null());
     let password = unsafe {
         slice::from_raw_parts(password, password_len as usize)
     };
+    decrypt(errp, skesk, &password.into(), algo, key, key_len)
+}
+
+/// Returns the session key.
+///
+/// Like `pgp_skesk_decrypt`, but takes the password as
+/// `pgp_password_t`.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle]
+pub extern "C" fn pgp_skesk_decrypt_with_password(
+    errp: Option<&mut *mut ::error::Error>,
+    skesk: *const Packet,
+    password: *const Password,
+    algo: *mut uint8_t, // XXX
+    key: *mut uint8_t,
+    key_len: *mut size_t)
+    -> Status
+{
+    decrypt(errp, skesk, password.ref_raw(), algo, key, key_len)
+}
+
+fn decrypt(errp: Option<&mut *mut ::error::Error>,
+           skesk: *const Packet,
+           password: &openpgp::crypto::Password,
+           algo: *mut uint8_t,
+           key: *mut uint8_t,
+           key_len: *mut size_t)
+           -> Status {
+    ffi_make_fry_from_errp!(errp);
     let algo = ffi_param_ref_mut!(algo);
     let key_len = ffi_param_ref_mut!(key_len);
 
     if let &openpgp::Packet::SKESK(ref skesk) = skesk.ref_raw() {
-        match skesk.decrypt(&password.to_owned().into()) {
+        match skesk.decrypt(password) {
             Ok((a, k)) => {
                 *algo = a.into();
                 if !key.is_null() && *key_len >= k.len() {
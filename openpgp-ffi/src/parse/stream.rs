@@ -11,7 +11,7 @@
 //! [`sequoia-openpgp::parse::stream`]: ../../../sequoia_openpgp/parse/stream/index.html
 
 use std::ptr;
-use libc::{c_int, c_void, uint8_t, time_t};
+use libc::{c_char, c_int, c_void, size_t, uint8_t, time_t};
 
 extern crate sequoia_openpgp as openpgp;
 extern crate time;
@@ -53,20 +53,51 @@ use super::super::{
 };
 
 /// Communicates the message structure to the VerificationHelper.
+///
+/// The message structure is only valid during the `check` callback
+/// it is passed to.  It cannot be modified, hence, unlike the TPK
+/// iterators, the iterators over it do not take a snapshot.  Instead,
+/// the iterators, and the layers and verification results they
+/// return, are valid until the callback returns.  Freeing an
+/// iterator does not invalidate the objects it returned.
 #[::ffi_wrapper_type(prefix = "pgp_", derive = "Debug")]
 pub struct MessageStructure<'a>(stream::MessageStructure<'a>);
 
 /// Iterates over the message structure.
+///
+/// The iterator is valid until the `check` callback returns.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
 fn pgp_message_structure_iter(structure: *const MessageStructure)
                               -> *mut MessageStructureIter {
     structure.ref_raw().iter().move_into_raw()
 }
 
+/// Returns whether the literal data packet's metadata is
+/// authenticated.
+///
+/// The format, file name, and date stored in the literal data packet
+/// are only authenticated if the packet is inside an AEAD encrypted
+/// container.  If this returns false, the metadata, notably the file
+/// name, must not be trusted.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_message_structure_literal_metadata_authenticated(
+    structure: *const MessageStructure)
+    -> bool
+{
+    structure.ref_raw().literal_metadata_authenticated()
+}
+
 /// Iterates over the message structure.
 #[::ffi_wrapper_type(prefix = "pgp_", derive = "Iterator(MessageLayer)")]
 pub struct MessageStructureIter<'a>(stream::MessageStructureIter<'a>);
 
+/// Returns the number of remaining layers.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_message_structure_iter_len(iter: *const MessageStructureIter)
+                                  -> size_t {
+    iter.ref_raw().len()
+}
+
 /// Represents a layer of the message structure.
 #[::ffi_wrapper_type(prefix = "pgp_", derive = "Debug")]
 pub struct MessageLayer<'a>(stream::MessageLayer<'a>);
@@ -81,6 +112,7 @@ fn pgp_message_layer_variant(result: *const MessageLayer)
         Compression { .. } => 1,
         Encryption { .. } => 2,
         SignatureGroup { .. } => 3,
+        Literal { .. } => 4,
     }
 }
 
@@ -153,11 +185,59 @@ fn pgp_message_layer_signature_group<'a>(
     }
 }
 
+/// Decomposes a `MessageLayer::Literal`.
+///
+/// Returns `true` iff the given value is a `MessageLayer::Literal`,
+/// and returns each of the variants members if the corresponding
+/// parameter is not `NULL`.  If the literal data packet has no file
+/// name, `*filename_r` is set to `NULL`, otherwise the caller must
+/// free it.  If it has no date, `*date_r` is set to 0.
+///
+/// Note: the metadata is *not* protected by any signature.  See
+/// `pgp_message_structure_literal_metadata_authenticated`.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_message_layer_literal(v: *const MessageLayer,
+                             format_r: Maybe<uint8_t>,
+                             filename_r: Maybe<*mut c_char>,
+                             date_r: Maybe<time_t>)
+                             -> bool
+{
+    use self::stream::MessageLayer::*;
+    if let Literal { format, filename, date } = v.ref_raw() {
+        if let Some(mut p) = format_r {
+            *unsafe { p.as_mut() } = (*format).into();
+        }
+        if let Some(mut p) = filename_r {
+            *unsafe { p.as_mut() } = match filename {
+                Some(f) => ffi_return_maybe_string!(f),
+                None => ptr::null_mut(),
+            };
+        }
+        if let Some(mut p) = date_r {
+            *unsafe { p.as_mut() } =
+                date.as_ref().map(|d| d.to_timespec().sec as time_t).unwrap_or(0);
+        }
+        true
+    } else {
+        false
+    }
+}
+
 /// A message's verification results.
+///
+/// The iterator is valid until the `check` callback returns, see
+/// `pgp_message_structure_t`.
 #[::ffi_wrapper_type(prefix = "pgp_", derive = "Iterator(VerificationResult)")]
 pub struct VerificationResultIter<'a>(
     ::std::slice::Iter<'a, stream::VerificationResult<'a>>);
 
+/// Returns the number of remaining verification results.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_verification_result_iter_len(iter: *const VerificationResultIter)
+                                    -> size_t {
+    iter.ref_raw().len()
+}
+
 /// A message's verification results.
 #[::ffi_wrapper_type(prefix = "pgp_", derive = "Debug")]
 pub struct VerificationResult<'a>(stream::VerificationResult<'a>);
@@ -440,17 +520,22 @@ impl VerificationHelper for VHelper {
 /// {
 ///   pgp_message_structure_iter_t iter =
 ///     pgp_message_structure_iter (structure);
+///   size_t layers = pgp_message_structure_iter_len (iter);
+///   assert (layers > 1);
 ///   pgp_message_layer_t layer = pgp_message_structure_iter_next (iter);
 ///   assert (layer);
 ///   assert (pgp_message_layer_compression (layer, NULL));
 ///   pgp_message_layer_free (layer);
+///   assert (pgp_message_structure_iter_len (iter) == layers - 1);
 ///   layer = pgp_message_structure_iter_next (iter);
 ///   assert (layer);
 ///   pgp_verification_result_iter_t results;
 ///   if (pgp_message_layer_signature_group (layer, &results)) {
+///     assert (pgp_verification_result_iter_len (results) == 1);
 ///     pgp_verification_result_t result =
 ///       pgp_verification_result_iter_next (results);
 ///     assert (result);
+///     assert (pgp_verification_result_iter_len (results) == 0);
 ///     assert (pgp_verification_result_good_checksum (result, NULL, NULL,
 ///                                                    NULL, NULL, NULL));
 ///     pgp_verification_result_free (result);
@@ -915,3 +1000,76 @@ fn pgp_decryptor_new<'a>(errp: Option<&mut *mut ::error::Error>,
         .map(|r| io::ReaderKind::Generic(Box::new(r)))
         .move_into_raw(errp)
 }
+
+/// Decrypts a message encrypted using a password.
+///
+/// The message is read from `input`, and the content of the
+/// `LiteralData` packet is written to `output`.  This is the
+/// counterpart of `pgp_encrypt_with_password`.
+///
+/// This function fails if the message is not encrypted, or cannot be
+/// decrypted using `password`.  Signatures are not verified.  Note:
+/// on failure, some data may already have been written to `output`,
+/// which must then be discarded.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_decrypt_with_password(errp: Option<&mut *mut ::error::Error>,
+                             password: *const crypto::Password,
+                             input: *mut io::Reader,
+                             output: *mut io::Writer)
+                             -> Status
+{
+    ffi_make_fry_from_errp!(errp);
+    let helper = PasswordHelper {
+        password: password.ref_raw(),
+        decrypted: false,
+    };
+
+    ffi_try_status!(
+        Decryptor::from_reader(input.ref_mut_raw(), helper, None)
+            .and_then(|mut d| -> openpgp::Result<()> {
+                ::std::io::copy(&mut d, output.ref_mut_raw())?;
+                if d.into_helper().decrypted {
+                    Ok(())
+                } else {
+                    Err(openpgp::Error::InvalidOperation(
+                        "Message is not encrypted".into()).into())
+                }
+            }))
+}
+
+/// Decrypts messages using a password.
+struct PasswordHelper<'a> {
+    password: &'a openpgp::crypto::Password,
+    decrypted: bool,
+}
+
+impl<'a> VerificationHelper for PasswordHelper<'a> {
+    fn get_public_keys(&mut self, _: &[openpgp::KeyID])
+                       -> openpgp::Result<Vec<openpgp::TPK>> {
+        Ok(Vec::new())
+    }
+
+    fn check(&mut self, _: &stream::MessageStructure)
+             -> openpgp::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> DecryptionHelper for PasswordHelper<'a> {
+    fn decrypt<D>(&mut self, _: &[PKESK], skesks: &[SKESK],
+                  mut decrypt: D)
+                  -> openpgp::Result<Option<openpgp::Fingerprint>>
+        where D: FnMut(SymmetricAlgorithm, &SessionKey) -> openpgp::Result<()>
+    {
+        for skesk in skesks {
+            if let Ok((algo, sk)) = skesk.decrypt(self.password) {
+                if decrypt(algo, &sk).is_ok() {
+                    self.decrypted = true;
+                    return Ok(None);
+                }
+            }
+        }
+
+        Err(openpgp::Error::InvalidPassword.into())
+    }
+}
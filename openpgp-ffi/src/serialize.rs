@@ -7,13 +7,14 @@
 
 use std::ptr;
 use std::slice;
-use std::io::Write;
+use std::io::{self, Read, Write};
 use libc::{uint8_t, c_char, size_t, ssize_t};
 
 extern crate sequoia_openpgp as openpgp;
 extern crate time;
 
 use self::openpgp::{
+    armor,
     crypto::Password,
 };
 use self::openpgp::constants::{
@@ -24,6 +25,7 @@ use self::openpgp::constants::{
 
 use error::Status;
 use MoveFromRaw;
+use RefMutRaw;
 use RefRaw;
 
 use self::openpgp::serialize::{
@@ -233,6 +235,9 @@ pub extern "C" fn pgp_literal_writer_new
 ///
 /// The stream is encrypted using `cipher_algo`.  Pass 0 for the
 /// default (which is what you usually want).
+///
+/// Prefer `pgp_encryptor_new_with_passwords`, which does not require
+/// the passwords to be kept in unprotected memory.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
 pub extern "C" fn pgp_encryptor_new
     (errp: Option<&mut *mut ::error::Error>,
@@ -243,9 +248,7 @@ pub extern "C" fn pgp_encryptor_new
      cipher_algo: uint8_t)
      -> *mut writer::Stack<'static, Cookie>
 {
-    ffi_make_fry_from_errp!(errp);
-    let inner = ffi_param_move!(inner);
-    let mut passwords_ = Vec::new();
+    let mut passwords_: Vec<Password> = Vec::new();
     if passwords_len > 0 {
         let passwords = passwords.expect("Passwords is NULL");
         let passwords = unsafe {
@@ -256,6 +259,49 @@ pub extern "C" fn pgp_encryptor_new
                             .to_bytes().to_owned().into());
         }
     }
+    encryptor_new(errp, inner, &passwords_.iter().collect::<Vec<_>>(),
+                  recipients, recipients_len, encryption_mode, cipher_algo)
+}
+
+/// Creates a new encryptor.
+///
+/// Like `pgp_encryptor_new`, but takes the passwords as
+/// `pgp_password_t`.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle]
+pub extern "C" fn pgp_encryptor_new_with_passwords
+    (errp: Option<&mut *mut ::error::Error>,
+     inner: *mut writer::Stack<'static, Cookie>,
+     passwords: Option<&*const super::crypto::Password>,
+     passwords_len: size_t,
+     recipients: Option<&*const TPK>, recipients_len: size_t,
+     encryption_mode: uint8_t,
+     cipher_algo: uint8_t)
+     -> *mut writer::Stack<'static, Cookie>
+{
+    let passwords = if passwords_len > 0 {
+        let passwords = passwords.expect("Passwords is NULL");
+        unsafe {
+            slice::from_raw_parts(passwords, passwords_len)
+        }
+    } else {
+        &[]
+    };
+    let passwords: Vec<&Password>
+        = passwords.iter().map(|&p| p.ref_raw()).collect();
+    encryptor_new(errp, inner, &passwords,
+                  recipients, recipients_len, encryption_mode, cipher_algo)
+}
+
+fn encryptor_new(errp: Option<&mut *mut ::error::Error>,
+                 inner: *mut writer::Stack<'static, Cookie>,
+                 passwords: &[&Password],
+                 recipients: Option<&*const TPK>, recipients_len: size_t,
+                 encryption_mode: uint8_t,
+                 cipher_algo: uint8_t)
+                 -> *mut writer::Stack<'static, Cookie>
+{
+    ffi_make_fry_from_errp!(errp);
+    let inner = ffi_param_move!(inner);
     let recipients = if recipients_len > 0 {
         let recipients = recipients.expect("Recipients is NULL");
         unsafe {
@@ -277,8 +323,99 @@ pub extern "C" fn pgp_encryptor_new
         Some(cipher_algo.into())
     };
     ffi_try_box!(Encryptor::new(*inner,
-                                &passwords_.iter().collect::<Vec<&Password>>(),
+                                passwords,
                                 &recipients[..],
                                 encryption_mode,
                                 cipher_algo))
 }
+
+/// Encrypts data using a password.
+///
+/// Reads the data from `input`, and writes an OpenPGP message
+/// containing the data encrypted using `password` to `output`.  If
+/// `armor` is true, the message is ASCII armored.
+///
+/// The message is encrypted using `cipher_algo`.  Pass 0 for the
+/// default (which is what you usually want).  Use
+/// `pgp_decrypt_with_password` to decrypt the message.
+///
+/// # Example
+///
+/// ```c
+/// #include <assert.h>
+/// #include <stdlib.h>
+/// #include <string.h>
+/// #include <sequoia/openpgp.h>
+///
+/// pgp_status_t rc;
+/// const char *text = "Hello world.";
+/// pgp_password_t password =
+///   pgp_password_from_bytes ((uint8_t *) "streng geheim", 13);
+///
+/// void *message = NULL;
+/// size_t message_len = 0;
+/// pgp_reader_t input =
+///   pgp_reader_from_bytes ((uint8_t *) text, strlen (text));
+/// pgp_writer_t output = pgp_writer_alloc (&message, &message_len);
+/// rc = pgp_encrypt_with_password (NULL, password, input, output, 0, true);
+/// assert (rc == PGP_STATUS_SUCCESS);
+/// pgp_writer_free (output);
+/// pgp_reader_free (input);
+///
+/// void *plaintext = NULL;
+/// size_t plaintext_len = 0;
+/// input = pgp_reader_from_bytes (message, message_len);
+/// output = pgp_writer_alloc (&plaintext, &plaintext_len);
+/// rc = pgp_decrypt_with_password (NULL, password, input, output);
+/// assert (rc == PGP_STATUS_SUCCESS);
+/// pgp_writer_free (output);
+/// pgp_reader_free (input);
+///
+/// assert (plaintext_len == strlen (text));
+/// assert (memcmp (plaintext, text, plaintext_len) == 0);
+///
+/// free (plaintext);
+/// free (message);
+/// pgp_password_free (password);
+/// ```
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle]
+pub extern "C" fn pgp_encrypt_with_password
+    (errp: Option<&mut *mut ::error::Error>,
+     password: *const super::crypto::Password,
+     input: *mut super::io::Reader,
+     output: *mut super::io::Writer,
+     cipher_algo: uint8_t,
+     armor: bool)
+     -> Status
+{
+    ffi_make_fry_from_errp!(errp);
+    let cipher_algo : Option<SymmetricAlgorithm> = if cipher_algo == 0 {
+        None
+    } else {
+        Some(cipher_algo.into())
+    };
+    ffi_try_status!(encrypt_with_password(password.ref_raw(),
+                                          input.ref_mut_raw(),
+                                          output.ref_mut_raw(),
+                                          cipher_algo, armor))
+}
+
+fn encrypt_with_password<'a>(password: &Password,
+                             input: &mut Read, output: &'a mut Write,
+                             cipher_algo: Option<SymmetricAlgorithm>,
+                             armor: bool)
+                             -> openpgp::Result<()> {
+    let output: Box<Write + 'a> = if armor {
+        Box::new(armor::Writer::new(output, armor::Kind::Message, &[])?)
+    } else {
+        Box::new(output)
+    };
+
+    let encryptor = Encryptor::new(Message::new(output), &[password], &[],
+                                   EncryptionMode::AtRest, cipher_algo)?;
+    let mut literal = LiteralWriter::new(encryptor, DataFormat::Binary,
+                                         None, None)?;
+    io::copy(input, &mut literal)?;
+    literal.finalize()?;
+    Ok(())
+}
@@ -5,6 +5,7 @@
 //! [`sequoia-openpgp::TPK`]: ../../sequoia_openpgp/struct.TPK.html
 //! [related functionality]: ../../sequoia_openpgp/tpk/index.html
 
+use std::collections::VecDeque;
 use std::ptr;
 use std::slice;
 use libc::{c_char, c_int, size_t, time_t, uint8_t};
@@ -14,6 +15,7 @@ use self::openpgp::{
     autocrypt::Autocrypt,
     crypto,
     constants::ReasonForRevocation,
+    packet::KeyFlags,
     parse::{
         PacketParserResult,
         Parse,
@@ -133,6 +135,46 @@ fn pgp_tpk_fingerprint(tpk: *const TPK)
     tpk.fingerprint().move_into_raw()
 }
 
+/// Returns a JSON description of the TPK.
+///
+/// The description contains the primary key, the subkeys, User IDs,
+/// and User Attributes together with their current binding
+/// signatures, revocation status, and expiration times.  It never
+/// includes secret key material.  The format is documented in
+/// `openpgp-ffi/src/json.rs`.
+///
+/// The caller must free the returned value.
+///
+/// # Example
+///
+/// ```c
+/// #include <assert.h>
+/// #include <stdlib.h>
+/// #include <string.h>
+/// #include <sequoia/openpgp.h>
+///
+/// pgp_tpk_builder_t builder;
+/// pgp_tpk_t tpk;
+/// pgp_signature_t revocation;
+///
+/// builder = pgp_tpk_builder_general_purpose (PGP_TPK_CIPHER_SUITE_CV25519,
+///                                            "alice@example.org");
+/// pgp_tpk_builder_generate (NULL, builder, &tpk, &revocation);
+/// assert (tpk);
+/// pgp_signature_free (revocation);
+///
+/// char *json = pgp_tpk_to_json (tpk);
+/// assert (strstr (json, "\"userids\":[{\"value\":\"alice@example.org\""));
+/// assert (strstr (json, "\"has_secret\":true"));
+///
+/// free (json);
+/// pgp_tpk_free (tpk);
+/// ```
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_tpk_to_json(tpk: *const TPK) -> *mut c_char {
+    ffi_return_string!(super::json::tpk(tpk.ref_raw()))
+}
+
 /// Derives a [`TSK`] object from this key.
 ///
 /// This object writes out secret keys during serialization.
@@ -428,42 +470,133 @@ pub extern "C" fn pgp_user_id_binding_selfsig(
 }
 
 
+/* Iterators.
+ *
+ * The iterators operate on a snapshot of the TPK taken when the
+ * iterator is created.  The TPK may be modified or freed while
+ * iterating, and the references returned by the iterators remain
+ * valid until the iterator is freed. */
+
+/// Returns a snapshot of `tpk` that outlives any borrow.
+///
+/// The snapshot must outlive all references derived from it.  The
+/// iterator wrappers ensure this by declaring the snapshot as their
+/// last field, so that it is dropped last.
+fn snapshot(tpk: *const TPK) -> (Box<openpgp::TPK>, &'static openpgp::TPK) {
+    let snapshot = Box::new(tpk.ref_raw().clone());
+    // The heap allocation does not move when the box does.
+    let tpk = unsafe { &*(snapshot.as_ref() as *const openpgp::TPK) };
+    (snapshot, tpk)
+}
+
 /* UserIDBindingIter */
 
+/// Wraps a UserIDBindingIter for export via the FFI.
+pub struct UserIDBindingIterWrapper {
+    iter: UserIDBindingIter<'static>,
+    // Note: `iter` borrows from this, hence it must be dropped last.
+    _tpk: Box<openpgp::TPK>,
+}
+
 /// Returns an iterator over the TPK's user id bindings.
+///
+/// The iterator operates on a snapshot of the TPK.  Changes to
+/// `tpk` are not reflected, and `tpk` may be freed while iterating.
+/// The returned bindings remain valid until the iterator is freed.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
 pub extern "C" fn pgp_tpk_user_id_binding_iter(tpk: *const TPK)
-    -> *mut UserIDBindingIter<'static>
+    -> *mut UserIDBindingIterWrapper
 {
-    let tpk = tpk.ref_raw();
-    box_raw!(tpk.userids())
+    let (snapshot, tpk) = snapshot(tpk);
+    box_raw!(UserIDBindingIterWrapper {
+        iter: tpk.userids(),
+        _tpk: snapshot,
+    })
 }
 
 /// Frees a pgp_user_id_binding_iter_t.
+///
+/// This invalidates all bindings returned by the iterator.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
 pub extern "C" fn pgp_user_id_binding_iter_free(
-    iter: Option<&mut UserIDBindingIter>)
+    iter: Option<&mut UserIDBindingIterWrapper>)
 {
     ffi_free!(iter)
 }
 
 /// Returns the next `UserIDBinding`.
+///
+/// The returned binding is valid until the iterator is freed.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
 pub extern "C" fn pgp_user_id_binding_iter_next<'a>(
-    iter: *mut UserIDBindingIter<'a>)
+    iter: *mut UserIDBindingIterWrapper)
     -> Option<&'a UserIDBinding>
 {
     let iter = ffi_param_ref_mut!(iter);
-    iter.next()
+    iter.iter.next()
+}
+
+/// Returns the number of remaining `UserIDBinding`s.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle]
+pub extern "C" fn pgp_user_id_binding_iter_len(
+    iter: *const UserIDBindingIterWrapper)
+    -> size_t
+{
+    let iter = ffi_param_ref!(iter);
+    iter.iter.len()
 }
 
 /* tpk::KeyIter. */
 
+/// A key, its self-signature, and its revocation status.
+type KeyIterItem = (Option<&'static openpgp::packet::Signature>,
+                    openpgp::RevocationStatus<'static>,
+                    &'static openpgp::packet::Key);
+
 /// Wrapers a KeyIter for export via the FFI.
-pub struct KeyIterWrapper<'a> {
-    iter: KeyIter<'a>,
-    // Whether next has been called.
-    next_called: bool,
+pub struct KeyIterWrapper {
+    iter: KeyIter<'static>,
+    // The remaining items.  Once iteration starts, the filtered keys
+    // are collected so that the number of keys is known.
+    items: Option<VecDeque<KeyIterItem>>,
+    // Note: `iter` and `items` borrow from this, hence it must be
+    // dropped last.
+    _tpk: Box<openpgp::TPK>,
+}
+
+impl KeyIterWrapper {
+    fn new(tpk: *const TPK, f: fn(&'static openpgp::TPK) -> KeyIter<'static>)
+           -> Self {
+        let (snapshot, tpk) = snapshot(tpk);
+        KeyIterWrapper {
+            iter: f(tpk),
+            items: None,
+            _tpk: snapshot,
+        }
+    }
+
+    /// Changes the filter of the iterator.
+    ///
+    /// Panics if iteration has started.
+    fn filter<F>(&mut self, f: F)
+        where F: FnOnce(KeyIter<'static>) -> KeyIter<'static>
+    {
+        if self.items.is_some() {
+            panic!("Can't change KeyIter filter after iterating.");
+        }
+
+        use std::mem;
+        let tmp = mem::replace(&mut self.iter, KeyIter::empty());
+        self.iter = f(tmp);
+    }
+
+    /// Returns the remaining items, starting iteration if necessary.
+    fn items(&mut self) -> &mut VecDeque<KeyIterItem> {
+        if self.items.is_none() {
+            self.items = Some((&mut self.iter).collect());
+        }
+        self.items.as_mut().unwrap()
+    }
 }
 
 /// Returns an iterator over the TPK's live, non-revoked keys.
@@ -482,33 +615,129 @@ pub struct KeyIterWrapper<'a> {
 /// A valid `Key` has at least one good self-signature.
 ///
 /// To return all keys, use `pgp_tpk_key_iter_all()`.
+///
+/// The iterator operates on a snapshot of the TPK.  Changes to `tpk`
+/// are not reflected, and `tpk` may be freed while iterating.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
 pub extern "C" fn pgp_tpk_key_iter_valid(tpk: *const TPK)
-    -> *mut KeyIterWrapper<'static>
+    -> *mut KeyIterWrapper
 {
-    let tpk = tpk.ref_raw();
-    box_raw!(KeyIterWrapper {
-        iter: tpk.keys_valid(),
-        next_called: false,
-    })
+    box_raw!(KeyIterWrapper::new(tpk, |tpk| tpk.keys_valid()))
 }
 
 /// Returns an iterator over all `Key`s in a TPK.
 ///
 /// Compare with `pgp_tpk_key_iter_valid`, which filters out expired
 /// and revoked keys by default.
+///
+/// The iterator operates on a snapshot of the TPK.  Changes to `tpk`
+/// are not reflected, and `tpk` may be freed while iterating.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
 pub extern "C" fn pgp_tpk_key_iter_all(tpk: *const TPK)
-    -> *mut KeyIterWrapper<'static>
+    -> *mut KeyIterWrapper
 {
-    let tpk = tpk.ref_raw();
-    box_raw!(KeyIterWrapper {
-        iter: tpk.keys_all(),
-        next_called: false,
-    })
+    box_raw!(KeyIterWrapper::new(tpk, |tpk| tpk.keys_all()))
+}
+
+/// Selects keys that may be used to encrypt data for long-term
+/// storage.  See `pgp_tpk_encryption_keys`.
+const ENCRYPTION_KEY_AT_REST: uint8_t = 1 << 0;
+
+/// Selects keys that may be used to encrypt data in transit.  See
+/// `pgp_tpk_encryption_keys`.
+const ENCRYPTION_KEY_FOR_TRANSPORT: uint8_t = 1 << 1;
+
+/// Returns an iterator over the keys that can be used to encrypt
+/// data for `tpk`.
+///
+/// A key is returned if its binding signature marks it as
+/// encryption-capable for one of the purposes selected by `flags`,
+/// if it is alive at time `when`, and if it has not been revoked.
+/// Pass 0 for `when` to use the current time.  `flags` is a
+/// combination of `PGP_ENCRYPTION_KEY_AT_REST` and
+/// `PGP_ENCRYPTION_KEY_FOR_TRANSPORT`.
+///
+/// On success, the iterator is stored in `*iterp`.  If `tpk` has no
+/// suitable keys, the iterator is empty.  Further filters may be
+/// added before iterating.  If `flags` is invalid, an error is
+/// returned, and `*iterp` is set to NULL.
+///
+/// The iterator operates on a snapshot of the TPK.  Changes to `tpk`
+/// are not reflected, and `tpk` may be freed while iterating.
+///
+/// # Example
+///
+/// ```c
+/// #include <assert.h>
+/// #include <sequoia/openpgp.h>
+///
+/// pgp_tpk_builder_t builder;
+/// pgp_tpk_t tpk;
+/// pgp_signature_t revocation;
+/// pgp_tpk_key_iter_t iter;
+/// pgp_status_t rc;
+///
+/// builder = pgp_tpk_builder_general_purpose (PGP_TPK_CIPHER_SUITE_CV25519,
+///                                            "alice@example.org");
+/// pgp_tpk_builder_generate (NULL, builder, &tpk, &revocation);
+/// assert (tpk);
+/// pgp_signature_free (revocation);
+///
+/// /* The primary key is not encryption-capable, the subkey is.  */
+/// rc = pgp_tpk_encryption_keys (NULL, tpk, 0,
+///                               PGP_ENCRYPTION_KEY_AT_REST
+///                               | PGP_ENCRYPTION_KEY_FOR_TRANSPORT,
+///                               &iter);
+/// assert (rc == PGP_STATUS_SUCCESS);
+/// assert (pgp_tpk_key_iter_len (iter) == 1);
+/// pgp_tpk_key_iter_free (iter);
+///
+/// rc = pgp_tpk_encryption_keys (NULL, tpk, 0, 0, &iter);
+/// assert (rc != PGP_STATUS_SUCCESS);
+/// assert (iter == NULL);
+///
+/// pgp_tpk_free (tpk);
+/// ```
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle]
+pub extern "C" fn pgp_tpk_encryption_keys(
+    errp: Option<&mut *mut ::error::Error>,
+    tpk: *const TPK, when: time_t, flags: uint8_t,
+    iterp: *mut *mut KeyIterWrapper)
+    -> Status
+{
+    let iterp = ffi_param_ref_mut!(iterp);
+    *iterp = ptr::null_mut();
+
+    if flags == 0
+        || flags & ! (ENCRYPTION_KEY_AT_REST | ENCRYPTION_KEY_FOR_TRANSPORT)
+            != 0
+    {
+        let r: openpgp::Result<()> = Err(openpgp::Error::InvalidArgument(
+            format!("Bad encryption key flags: {:#x}", flags)).into());
+        return r.move_into_raw(errp);
+    }
+    let key_flags = KeyFlags::default()
+        .set_encrypt_at_rest(flags & ENCRYPTION_KEY_AT_REST != 0)
+        .set_encrypt_for_transport(flags & ENCRYPTION_KEY_FOR_TRANSPORT != 0);
+
+    let when = if when == 0 {
+        time::now()
+    } else {
+        time::at(time::Timespec::new(when as i64, 0))
+    };
+
+    let mut iter = KeyIterWrapper::new(tpk, |tpk| tpk.keys_all());
+    iter.filter(|iter| {
+        iter.key_flags(key_flags).alive_at(when).revoked(false)
+    });
+    *iterp = box_raw!(iter);
+    Status::Success
 }
 
 /// Frees a pgp_tpk_key_iter_t.
+///
+/// This invalidates the revocation statuses returned by the
+/// iterator.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
 pub extern "C" fn pgp_tpk_key_iter_free(
     iter: Option<&mut KeyIterWrapper>)
@@ -516,6 +745,18 @@ pub extern "C" fn pgp_tpk_key_iter_free(
     ffi_free!(iter)
 }
 
+/// Returns the number of remaining keys.
+///
+/// This starts the iteration, i.e. the iterator's filters may not be
+/// changed afterwards.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle]
+pub extern "C" fn pgp_tpk_key_iter_len(iter_wrapper: *mut KeyIterWrapper)
+    -> size_t
+{
+    let iter_wrapper = ffi_param_ref_mut!(iter_wrapper);
+    iter_wrapper.items().len()
+}
+
 /// Changes the iterator to only return keys that are certification
 /// capable.
 ///
@@ -526,17 +767,11 @@ pub extern "C" fn pgp_tpk_key_iter_free(
 ///
 /// Note: you may not call this function after starting to iterate.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
-pub extern "C" fn pgp_tpk_key_iter_certification_capable<'a>(
-    iter_wrapper: *mut KeyIterWrapper<'a>)
+pub extern "C" fn pgp_tpk_key_iter_certification_capable(
+    iter_wrapper: *mut KeyIterWrapper)
 {
     let iter_wrapper = ffi_param_ref_mut!(iter_wrapper);
-    if iter_wrapper.next_called {
-        panic!("Can't change KeyIter filter after iterating.");
-    }
-
-    use std::mem;
-    let tmp = mem::replace(&mut iter_wrapper.iter, KeyIter::empty());
-    iter_wrapper.iter = tmp.certification_capable();
+    iter_wrapper.filter(|iter| iter.certification_capable());
 }
 
 /// Changes the iterator to only return keys that are certification
@@ -549,17 +784,28 @@ pub extern "C" fn pgp_tpk_key_iter_certification_capable<'a>(
 ///
 /// Note: you may not call this function after starting to iterate.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
-pub extern "C" fn pgp_tpk_key_iter_signing_capable<'a>(
-    iter_wrapper: *mut KeyIterWrapper<'a>)
+pub extern "C" fn pgp_tpk_key_iter_signing_capable(
+    iter_wrapper: *mut KeyIterWrapper)
 {
     let iter_wrapper = ffi_param_ref_mut!(iter_wrapper);
-    if iter_wrapper.next_called {
-        panic!("Can't change KeyIter filter after iterating.");
-    }
+    iter_wrapper.filter(|iter| iter.signing_capable());
+}
 
-    use std::mem;
-    let tmp = mem::replace(&mut iter_wrapper.iter, KeyIter::empty());
-    iter_wrapper.iter = tmp.signing_capable();
+/// Changes the iterator to only return keys that are authentication
+/// capable.
+///
+/// If you call this function and, e.g., the `signing_capable`
+/// function, the *union* of the values is used.  That is, the
+/// iterator will return keys that are authentication capable *or*
+/// signing capable.
+///
+/// Note: you may not call this function after starting to iterate.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle]
+pub extern "C" fn pgp_tpk_key_iter_authentication_capable(
+    iter_wrapper: *mut KeyIterWrapper)
+{
+    let iter_wrapper = ffi_param_ref_mut!(iter_wrapper);
+    iter_wrapper.filter(|iter| iter.authentication_capable());
 }
 
 /// Changes the iterator to only return keys that are alive.
@@ -569,17 +815,11 @@ pub extern "C" fn pgp_tpk_key_iter_signing_capable<'a>(
 ///
 /// Note: you may not call this function after starting to iterate.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
-pub extern "C" fn pgp_tpk_key_iter_alive<'a>(
-    iter_wrapper: *mut KeyIterWrapper<'a>)
+pub extern "C" fn pgp_tpk_key_iter_alive(
+    iter_wrapper: *mut KeyIterWrapper)
 {
     let iter_wrapper = ffi_param_ref_mut!(iter_wrapper);
-    if iter_wrapper.next_called {
-        panic!("Can't change KeyIter filter after iterating.");
-    }
-
-    use std::mem;
-    let tmp = mem::replace(&mut iter_wrapper.iter, KeyIter::empty());
-    iter_wrapper.iter = tmp.alive();
+    iter_wrapper.filter(|iter| iter.alive());
 }
 
 /// Changes the iterator to only return keys that are alive at the
@@ -590,18 +830,12 @@ pub extern "C" fn pgp_tpk_key_iter_alive<'a>(
 ///
 /// Note: you may not call this function after starting to iterate.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
-pub extern "C" fn pgp_tpk_key_iter_alive_at<'a>(
-    iter_wrapper: *mut KeyIterWrapper<'a>,
+pub extern "C" fn pgp_tpk_key_iter_alive_at(
+    iter_wrapper: *mut KeyIterWrapper,
     when: time_t)
 {
     let iter_wrapper = ffi_param_ref_mut!(iter_wrapper);
-    if iter_wrapper.next_called {
-        panic!("Can't change KeyIter filter after iterating.");
-    }
-
-    use std::mem;
-    let tmp = mem::replace(&mut iter_wrapper.iter, KeyIter::empty());
-    iter_wrapper.iter = tmp.alive_at(time::at(time::Timespec::new(when as i64, 0)));
+    iter_wrapper.filter(|iter| iter.alive_at(time::at(time::Timespec::new(when as i64, 0))));
 }
 
 /// Changes the iterator to only return keys whose revocation status
@@ -609,18 +843,12 @@ pub extern "C" fn pgp_tpk_key_iter_alive_at<'a>(
 ///
 /// Note: you may not call this function after starting to iterate.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
-pub extern "C" fn pgp_tpk_key_iter_revoked<'a>(
-    iter_wrapper: *mut KeyIterWrapper<'a>,
+pub extern "C" fn pgp_tpk_key_iter_revoked(
+    iter_wrapper: *mut KeyIterWrapper,
     revoked: bool)
 {
     let iter_wrapper = ffi_param_ref_mut!(iter_wrapper);
-    if iter_wrapper.next_called {
-        panic!("Can't change KeyIter filter after iterating.");
-    }
-
-    use std::mem;
-    let tmp = mem::replace(&mut iter_wrapper.iter, KeyIter::empty());
-    iter_wrapper.iter = tmp.revoked(Some(revoked));
+    iter_wrapper.filter(|iter| iter.revoked(Some(revoked)));
 }
 
 /// Changes the iterator to only return keys that have secret keys (or
@@ -628,18 +856,12 @@ pub extern "C" fn pgp_tpk_key_iter_revoked<'a>(
 ///
 /// Note: you may not call this function after starting to iterate.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
-pub extern "C" fn pgp_tpk_key_iter_secret<'a>(
-    iter_wrapper: *mut KeyIterWrapper<'a>,
+pub extern "C" fn pgp_tpk_key_iter_secret(
+    iter_wrapper: *mut KeyIterWrapper,
     secret: bool)
 {
     let iter_wrapper = ffi_param_ref_mut!(iter_wrapper);
-    if iter_wrapper.next_called {
-        panic!("Can't change KeyIter filter after iterating.");
-    }
-
-    use std::mem;
-    let tmp = mem::replace(&mut iter_wrapper.iter, KeyIter::empty());
-    iter_wrapper.iter = tmp.secret(Some(secret));
+    iter_wrapper.filter(|iter| iter.secret(Some(secret)));
 }
 
 /// Changes the iterator to only return keys that have unencrypted
@@ -647,18 +869,12 @@ pub extern "C" fn pgp_tpk_key_iter_secret<'a>(
 ///
 /// Note: you may not call this function after starting to iterate.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
-pub extern "C" fn pgp_tpk_key_iter_unencrypted_secret<'a>(
-    iter_wrapper: *mut KeyIterWrapper<'a>,
+pub extern "C" fn pgp_tpk_key_iter_unencrypted_secret(
+    iter_wrapper: *mut KeyIterWrapper,
     unencrypted_secret: bool)
 {
     let iter_wrapper = ffi_param_ref_mut!(iter_wrapper);
-    if iter_wrapper.next_called {
-        panic!("Can't change KeyIter filter after iterating.");
-    }
-
-    use std::mem;
-    let tmp = mem::replace(&mut iter_wrapper.iter, KeyIter::empty());
-    iter_wrapper.iter = tmp.unencrypted_secret(Some(unencrypted_secret));
+    iter_wrapper.filter(|iter| iter.unencrypted_secret(Some(unencrypted_secret)));
 }
 
 /// Returns the next key.  Returns NULL if there are no more elements.
@@ -670,26 +886,29 @@ pub extern "C" fn pgp_tpk_key_iter_unencrypted_secret<'a>(
 ///
 /// If rso is not NULL, this stores the key's revocation status in
 /// *rso.
+///
+/// The returned key and signature are copies, and must be freed by
+/// the caller.  The revocation status refers to the iterator's
+/// snapshot of the TPK, and must be freed before the iterator.
 #[::sequoia_ffi_macros::extern_fn] #[no_mangle]
-pub extern "C" fn pgp_tpk_key_iter_next<'a>(
-    iter_wrapper: *mut KeyIterWrapper<'a>,
+pub extern "C" fn pgp_tpk_key_iter_next(
+    iter_wrapper: *mut KeyIterWrapper,
     sigo: Option<&mut Maybe<Signature>>,
-    rso: Option<&mut *mut RevocationStatus<'a>>)
+    rso: Option<&mut *mut RevocationStatus<'static>>)
     -> Maybe<Key>
 {
     let iter_wrapper = ffi_param_ref_mut!(iter_wrapper);
-    iter_wrapper.next_called = true;
 
-    if let Some((sig, rs, key)) = iter_wrapper.iter.next() {
+    if let Some((sig, rs, key)) = iter_wrapper.items().pop_front() {
         if let Some(ptr) = sigo {
-            *ptr = sig.move_into_raw();
+            *ptr = sig.cloned().move_into_raw();
         }
 
         if let Some(ptr) = rso {
             *ptr = rs.move_into_raw();
         }
 
-        Some(key).move_into_raw()
+        Some(key.clone()).move_into_raw()
     } else {
         None
     }
@@ -913,6 +1132,17 @@ pub extern "C" fn pgp_tpk_builder_add_certification_subkey
     *tpkb = box_raw!(tpkb_);
 }
 
+/// Adds an authentication capable subkey.
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle]
+pub extern "C" fn pgp_tpk_builder_add_authentication_subkey
+    (tpkb: *mut *mut TPKBuilder)
+{
+    let tpkb = ffi_param_ref_mut!(tpkb);
+    let tpkb_ = ffi_param_move!(*tpkb);
+    let tpkb_ = tpkb_.add_authentication_subkey();
+    *tpkb = box_raw!(tpkb_);
+}
+
 /// Generates the actual TPK.
 ///
 /// Consumes `tpkb`.
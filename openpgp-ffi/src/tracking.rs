@@ -0,0 +1,225 @@
+//! Tracks objects handed across the FFI boundary.
+//!
+//! Getting the ownership right is the hardest part of writing
+//! bindings.  To help binding authors, the library can be compiled
+//! with the `track-ownership` feature.  Then, every object handed to
+//! the application is recorded together with its type and the
+//! function that created it.  Objects that are freed twice, or used
+//! after they have been freed, are detected before the memory is
+//! touched.  Finally, [`pgp_shutdown`] reports all objects that have
+//! not been freed, and all objects freed more than once.
+//!
+//! [`pgp_shutdown`]: fn.pgp_shutdown.html
+//!
+//! The objects are allocated as usual, and the bookkeeping is done
+//! in a separate table.  Therefore, the tracking can be combined
+//! with tools like AddressSanitizer or Valgrind.  Note that if the
+//! memory of a freed object is reused for a new object, freeing the
+//! old object again cannot be detected.
+//!
+//! Without the `track-ownership` feature, the bookkeeping compiles
+//! to nothing, and `pgp_shutdown` does nothing.
+
+use libc::c_int;
+
+#[cfg(feature = "track-ownership")]
+mod imp {
+    use std::cell::Cell;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// The name used for objects without a wrapper type.
+    const UNKNOWN_TYPE: &str = "object";
+
+    /// The name used if an object is created outside of a call
+    /// into the library.
+    const UNKNOWN_SITE: &str = "<unknown>";
+
+    /// A live object.
+    struct Object {
+        type_name: &'static str,
+        site: &'static str,
+    }
+
+    #[derive(Default)]
+    struct Registry {
+        live: HashMap<usize, Object>,
+        bad_frees: Vec<String>,
+    }
+
+    lazy_static! {
+        static ref REGISTRY: Mutex<Registry> = Default::default();
+    }
+
+    thread_local! {
+        // The exported function the application called.
+        static SITE: Cell<Option<&'static str>> = Cell::new(None);
+    }
+
+    /// Forgets the exported function when dropped.
+    pub(crate) struct Site(bool);
+
+    impl Drop for Site {
+        fn drop(&mut self) {
+            if self.0 {
+                SITE.with(|s| s.set(None));
+            }
+        }
+    }
+
+    /// Remembers the exported function that is being executed.
+    ///
+    /// Objects created while the returned guard is alive are
+    /// attributed to `function`.
+    pub(crate) fn enter(function: &'static str) -> Site {
+        SITE.with(|s| {
+            // If the library calls its own exported functions, we
+            // keep the outermost one.
+            if s.get().is_none() {
+                s.set(Some(function));
+                Site(true)
+            } else {
+                Site(false)
+            }
+        })
+    }
+
+    fn site() -> &'static str {
+        SITE.with(|s| s.get()).unwrap_or(UNKNOWN_SITE)
+    }
+
+    /// Records that the object at `ptr` is handed to the application.
+    pub(crate) fn register<T>(ptr: *const T, type_name: Option<&'static str>) {
+        REGISTRY.lock().unwrap().live.insert(ptr as usize, Object {
+            type_name: type_name.unwrap_or(UNKNOWN_TYPE),
+            site: site(),
+        });
+    }
+
+    /// Records that the object at `ptr` is handed back to us.
+    ///
+    /// Returns `false` if the object is not live, i.e. it has already
+    /// been freed.  In this case, the object must not be touched.
+    pub(crate) fn release<T>(ptr: *const T, type_name: Option<&'static str>)
+                             -> bool {
+        let mut registry = REGISTRY.lock().unwrap();
+        if registry.live.remove(&(ptr as usize)).is_some() {
+            true
+        } else {
+            let msg = format!("{} at {:?} freed in {}, but it is not live",
+                              type_name.unwrap_or(UNKNOWN_TYPE), ptr, site());
+            registry.bad_frees.push(msg);
+            false
+        }
+    }
+
+    /// Panics if the object at `ptr` is not live.
+    pub(crate) fn check<T>(ptr: *const T, type_name: Option<&'static str>) {
+        if ! REGISTRY.lock().unwrap().live.contains_key(&(ptr as usize)) {
+            panic!("FFI contract violation: {} at {:?} used in {}, \
+                    but it is not live",
+                   type_name.unwrap_or(UNKNOWN_TYPE), ptr, site());
+        }
+    }
+
+    /// Prints the problems found so far, and returns their number.
+    pub(crate) fn report() -> usize {
+        let mut registry = REGISTRY.lock().unwrap();
+        let mut problems = 0;
+        for msg in registry.bad_frees.drain(..) {
+            eprintln!("pgp_shutdown: {}", msg);
+            problems += 1;
+        }
+        for (ptr, o) in registry.live.iter() {
+            eprintln!("pgp_shutdown: {} at {:#x} created in {} was not freed",
+                      o.type_name, ptr, o.site);
+            problems += 1;
+        }
+        problems
+    }
+}
+
+#[cfg(not(feature = "track-ownership"))]
+mod imp {
+    pub(crate) struct Site;
+
+    #[inline]
+    pub(crate) fn enter(_: &'static str) -> Site {
+        Site
+    }
+
+    #[inline]
+    pub(crate) fn register<T>(_: *const T, _: Option<&'static str>) {
+    }
+
+    #[inline]
+    pub(crate) fn release<T>(_: *const T, _: Option<&'static str>) -> bool {
+        true
+    }
+
+    #[inline]
+    pub(crate) fn check<T>(_: *const T, _: Option<&'static str>) {
+    }
+
+    #[inline]
+    pub(crate) fn report() -> usize {
+        0
+    }
+}
+
+pub(crate) use self::imp::{enter, register, release, check};
+
+/// Shuts the library down.
+///
+/// This function should be called once the application is done
+/// using the library.
+///
+/// If the library is compiled with the `track-ownership` feature,
+/// this function prints all objects that have not been freed, and
+/// all objects that have been freed more than once, to stderr.  It
+/// returns the number of problems found.  Otherwise, it returns 0.
+///
+/// # Example
+///
+/// ```c
+/// #include <assert.h>
+/// #include <sequoia/openpgp.h>
+///
+/// pgp_fingerprint_t fp =
+///     pgp_fingerprint_from_hex ("D2F2C5D45BE9FDE6A4EE0AAF31855247603831FD");
+/// pgp_fingerprint_free (fp);
+///
+/// assert (pgp_shutdown () == 0);
+/// ```
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_shutdown() -> c_int {
+    imp::report() as c_int
+}
+
+#[cfg(all(test, feature = "track-ownership"))]
+mod tests {
+    use super::*;
+    use libc::c_char;
+    use super::super::fingerprint::{
+        pgp_fingerprint_from_hex,
+        pgp_fingerprint_free,
+    };
+
+    #[test]
+    fn leak_and_double_free() {
+        let hex = b"D2F2C5D45BE9FDE6A4EE0AAF31855247603831FD\0";
+        let fp = pgp_fingerprint_from_hex(hex.as_ptr() as *const c_char)
+            .expect("valid fingerprint").as_ptr();
+
+        // The fingerprint is leaked.
+        assert_eq!(pgp_shutdown(), 1);
+
+        pgp_fingerprint_free(fp);
+        assert_eq!(pgp_shutdown(), 0);
+
+        // Freeing it again is detected, and reported once.
+        pgp_fingerprint_free(fp);
+        assert_eq!(pgp_shutdown(), 1);
+        assert_eq!(pgp_shutdown(), 0);
+    }
+}
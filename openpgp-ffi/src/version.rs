@@ -0,0 +1,56 @@
+//! Version information.
+//!
+//! These functions allow applications to check at runtime that the
+//! library they are linked against provides the interfaces they were
+//! compiled against.  See [ABI Stability] for the guarantees we
+//! make.
+//!
+//! [ABI Stability]: ../index.html#abi-stability
+
+use libc::{c_char, c_int};
+
+/// The API level of this library.
+///
+/// The API level is incremented whenever stable symbols are added.
+/// It is never decremented.  The C header exports the API level the
+/// application is compiled against as `PGP_API_LEVEL`.
+pub const PGP_API_LEVEL: c_int = 7;
+
+/// Returns the version of the library.
+///
+/// The returned string is statically allocated, and must not be
+/// freed.
+///
+/// # Example
+///
+/// ```c
+/// #include <assert.h>
+/// #include <sequoia/openpgp.h>
+///
+/// const char *version = pgp_version ();
+/// assert (version);
+/// assert (*version);
+/// ```
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+/// Returns the API level of the library.
+///
+/// Applications should check that this is at least `PGP_API_LEVEL`,
+/// i.e. the API level of the header the application is compiled
+/// against.
+///
+/// # Example
+///
+/// ```c
+/// #include <assert.h>
+/// #include <sequoia/openpgp.h>
+///
+/// assert (pgp_api_level () >= PGP_API_LEVEL);
+/// ```
+#[::sequoia_ffi_macros::extern_fn] #[no_mangle] pub extern "C"
+fn pgp_api_level() -> c_int {
+    PGP_API_LEVEL
+}
@@ -85,6 +85,9 @@ fn build_so(base: &Path) -> Result<()> {
         .arg("--quiet")
         .arg("--package")
         .arg("sequoia-openpgp-ffi")
+        // Test the experimental symbols, too.
+        .arg("--features")
+        .arg("sequoia-openpgp-ffi/experimental")
         .status().unwrap();
     if ! st.success() {
         return Err(io::Error::new(io::ErrorKind::Other, "compilation failed")
@@ -227,7 +230,8 @@ fn build(include_dirs: &[PathBuf], ldpath: &Path, target_dir: &Path,
         include_dirs.iter().map(|dir| format!("-I{:?}", dir))
         .collect::<Vec<String>>().join(" ");
     let st = Command::new("make")
-        .env("CFLAGS", &format!("-O0 -ggdb {}", includes))
+        .env("CFLAGS", &format!("-O0 -ggdb -DPGP_EXPERIMENTAL {}",
+                                includes))
         .env("LDFLAGS", &format!("-L{:?} -lsequoia_openpgp_ffi", ldpath))
         .arg("-C").arg(&target_dir)
         .arg("--quiet")
@@ -0,0 +1,87 @@
+use std::collections::BTreeSet;
+use std::env::var_os;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Checks that the library exports exactly the stable symbols.
+///
+/// Removing a stable symbol breaks the ABI.  Adding a symbol without
+/// listing it in `symbols.txt` extends the ABI by accident.
+#[test]
+fn stable_symbols() {
+    // The location of this crate's (i.e., the ffi crate's) source.
+    let manifest_dir = PathBuf::from(
+        var_os("CARGO_MANIFEST_DIR")
+        .as_ref()
+        .expect("CARGO_MANIFEST_DIR not set"));
+
+    // The top-level directory.
+    let toplevel = manifest_dir.parent().unwrap();
+
+    // The location of the binaries.  We build the library without
+    // the experimental symbols, so we use a separate directory to
+    // avoid clobbering the library used by the C tests.
+    let target_dir = if let Some(dir) = var_os("CARGO_TARGET_DIR") {
+        PathBuf::from(dir)
+    } else {
+        toplevel.join("target")
+    }.join("abi-check");
+
+    let lib = build_so(toplevel, &target_dir, "sequoia-openpgp-ffi",
+                       "sequoia_openpgp_ffi");
+    let exported = exported_symbols(&lib, "pgp_");
+    let stable = listed_symbols(&manifest_dir.join("symbols.txt"));
+
+    let removed = stable.difference(&exported).collect::<Vec<_>>();
+    let unlisted = exported.difference(&stable).collect::<Vec<_>>();
+    if ! removed.is_empty() || ! unlisted.is_empty() {
+        panic!("ABI mismatch:\n  stable symbols not exported: {:?}\n  \
+                exported symbols not listed in symbols.txt: {:?}",
+               removed, unlisted);
+    }
+}
+
+/// Builds the shared object, and returns its path.
+fn build_so(base: &Path, target_dir: &Path, package: &str, name: &str)
+            -> PathBuf {
+    let st = Command::new("cargo")
+        .current_dir(base)
+        .env("CARGO_TARGET_DIR", target_dir)
+        .arg("build")
+        .arg("--quiet")
+        .arg("--package")
+        .arg(package)
+        .status().unwrap();
+    assert!(st.success(), "compilation failed");
+
+    let ext = if cfg!(target_os = "macos") { "dylib" } else { "so" };
+    target_dir.join("debug").join(format!("lib{}.{}", name, ext))
+}
+
+/// Returns the symbols starting with `prefix` exported by `lib`.
+fn exported_symbols(lib: &Path, prefix: &str) -> BTreeSet<String> {
+    let output = Command::new("nm")
+        .arg("-g")
+        .arg("--defined-only")
+        .arg(lib)
+        .output()
+        .expect("Checking the symbols requires nm");
+    assert!(output.status.success(), "nm failed");
+
+    String::from_utf8_lossy(&output.stdout).lines()
+        .filter_map(|l| l.split_whitespace().nth(2))
+        .map(|s| s.trim_start_matches('_'))
+        .filter(|s| s.starts_with(prefix))
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Reads a list of symbols, ignoring empty lines and comments.
+fn listed_symbols(path: &Path) -> BTreeSet<String> {
+    fs::read_to_string(path).unwrap().lines()
+        .map(|l| l.trim())
+        .filter(|l| ! l.is_empty() && ! l.starts_with('#'))
+        .map(|l| l.to_string())
+        .collect()
+}
@@ -0,0 +1,103 @@
+//! Benchmarks hashing and keyring validation.
+//!
+//! Validating a keyring means checking every self-signature, and
+//! hashing the signed data is a significant part of that.  The hash
+//! functions are provided by Nettle, which selects an optimized
+//! implementation (e.g. using the SHA extensions on x86-64, or the
+//! cryptographic extensions on ARMv8) at runtime if it was built
+//! with `--enable-fat`.  Compare the throughput with Nettle's own
+//! benchmarks to see whether the accelerated code is used.
+//!
+//! Every self-signature starts by hashing the primary key.  When
+//! validating a TPK, the primary key is hashed only once per hash
+//! algorithm, see `KeyHashScope`.  The self-signatures benchmark
+//! shows the gain.
+
+#[macro_use]
+extern crate criterion;
+extern crate sequoia_openpgp as openpgp;
+
+use criterion::{Benchmark, Criterion, ParameterizedBenchmark, Throughput};
+
+use openpgp::TPK;
+use openpgp::constants::HashAlgorithm;
+use openpgp::crypto::KeyHashScope;
+use openpgp::packet::Signature;
+use openpgp::parse::Parse;
+use openpgp::tpk::TPKParser;
+
+/// A large key with many signatures.
+const DKG: &[u8] = include_bytes!("../tests/data/keys/dkg.gpg");
+
+fn hash(c: &mut Criterion) {
+    for &algo in &[HashAlgorithm::SHA1, HashAlgorithm::SHA256,
+                   HashAlgorithm::SHA512] {
+        c.bench(
+            &format!("hash {}", algo),
+            // Small inputs are typical for signatures over keys, large
+            // ones for signatures over messages.
+            ParameterizedBenchmark::new(
+                "update",
+                move |b, &size| {
+                    let data = vec![0x42; size];
+                    let mut digest =
+                        vec![0; algo.context().unwrap().digest_size()];
+                    b.iter(|| {
+                        let mut h = algo.context().unwrap();
+                        h.update(&data);
+                        h.digest(&mut digest);
+                    })
+                },
+                vec![64, 1024, 1 << 20])
+            .throughput(|&size| Throughput::Bytes(size as u32)));
+    }
+}
+
+/// Computes the digests of all user ID and subkey self-signatures.
+fn hash_selfsigs(tpk: &TPK) {
+    for binding in tpk.userids() {
+        for selfsig in binding.selfsigs() {
+            Signature::userid_binding_hash(selfsig, tpk.primary(),
+                                           binding.userid()).unwrap();
+        }
+    }
+    for binding in tpk.subkeys() {
+        for selfsig in binding.selfsigs() {
+            Signature::subkey_binding_hash(selfsig, tpk.primary(),
+                                           binding.subkey()).unwrap();
+        }
+    }
+}
+
+fn hash_self_signatures(c: &mut Criterion) {
+    let tpk = TPK::from_bytes(DKG).unwrap();
+    let shared = tpk.clone();
+    c.bench(
+        "hash self-signatures",
+        Benchmark::new("separately", move |b| {
+            b.iter(|| hash_selfsigs(&tpk))
+        })
+        .with_function("sharing the primary key", move |b| {
+            b.iter(|| {
+                let _scope = KeyHashScope::new(shared.primary());
+                hash_selfsigs(&shared)
+            })
+        }));
+}
+
+fn validate_keyring(c: &mut Criterion) {
+    c.bench(
+        "validate keyring",
+        Benchmark::new("TPKParser", |b| {
+            b.iter(|| {
+                for tpk in TPKParser::from_bytes(DKG).unwrap() {
+                    tpk.unwrap();
+                }
+            })
+        })
+        .throughput(Throughput::Bytes(DKG.len() as u32))
+        .sample_size(10));
+}
+
+criterion_group!(benches, hash, hash_self_signatures, validate_keyring);
+criterion_main!(benches);
@@ -0,0 +1,38 @@
+//! Benchmarks key derivation using the iterated and salted S2K.
+//!
+//! Deriving a key is on the critical path of unlocking keys and
+//! decrypting password-protected messages, so its latency is
+//! directly noticeable by users.
+
+#[macro_use]
+extern crate criterion;
+extern crate sequoia_openpgp as openpgp;
+
+use criterion::{Criterion, ParameterizedBenchmark};
+
+use openpgp::constants::HashAlgorithm;
+use openpgp::crypto::Password;
+use openpgp::crypto::s2k::S2K;
+
+fn s2k_iterated(c: &mut Criterion) {
+    let password: Password = "correct horse battery staple".into();
+
+    c.bench(
+        "S2K::Iterated",
+        ParameterizedBenchmark::new(
+            "SHA256",
+            move |b, &hash_bytes| {
+                let s2k = S2K::Iterated {
+                    hash: HashAlgorithm::SHA256,
+                    salt: [0x23; 8],
+                    hash_bytes: hash_bytes,
+                };
+                b.iter(|| s2k.derive_key(&password, 32).unwrap())
+            },
+            // The minimum, a moderate, and the maximum count.
+            vec![1024, 1 << 20, 65_011_712])
+        .sample_size(10));
+}
+
+criterion_group!(benches, s2k_iterated);
+criterion_main!(benches);
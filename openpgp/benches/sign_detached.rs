@@ -0,0 +1,60 @@
+//! Benchmarks detached signing of large amounts of data.
+//!
+//! The data is generated on the fly and streamed through the signer,
+//! so the memory use of the benchmark does not depend on the size of
+//! the input.
+
+#[macro_use]
+extern crate criterion;
+extern crate sequoia_openpgp as openpgp;
+
+use std::io::{self, Read};
+
+use criterion::{Benchmark, Criterion, Throughput};
+
+use openpgp::constants::{Curve, HashAlgorithm, SignatureType};
+use openpgp::crypto::{self, KeyPair};
+use openpgp::packet::key::Key4;
+use openpgp::packet::signature;
+use openpgp::serialize::stream::{Message, Signer};
+
+/// The amount of data signed in each iteration.
+const SIZE: u64 = 16 << 20;
+
+fn keypair() -> KeyPair {
+    Key4::generate_ecc(true, Curve::Ed25519).unwrap()
+        .into_keypair().unwrap()
+}
+
+fn data() -> io::Take<io::Repeat> {
+    io::repeat(0x42).take(SIZE)
+}
+
+fn sign_detached(c: &mut Criterion) {
+    c.bench(
+        "sign detached",
+        Benchmark::new("stream::Signer", |b| {
+            let mut pair = keypair();
+            b.iter(|| {
+                let mut signer = Signer::detached(
+                    Message::new(io::sink()),
+                    vec![&mut pair as &mut dyn crypto::Signer],
+                    HashAlgorithm::SHA256).unwrap();
+                io::copy(&mut data(), &mut signer).unwrap();
+                signer.finalize().unwrap();
+            })
+        })
+        .with_function("signature::Builder::sign_reader", |b| {
+            let mut pair = keypair();
+            b.iter(|| {
+                signature::Builder::new(SignatureType::Binary)
+                    .sign_reader(&mut pair, HashAlgorithm::SHA256,
+                                 &mut data()).unwrap()
+            })
+        })
+        .throughput(Throughput::Bytes(SIZE as u32))
+        .sample_size(10));
+}
+
+criterion_group!(benches, sign_detached);
+criterion_main!(benches);
@@ -120,6 +120,7 @@ impl VerificationHelper for Helper {
                     } else {
                         eprintln!("Encrypted using {}", sym_algo);
                     },
+                MessageLayer::Literal { .. } => (),
                 MessageLayer::SignatureGroup { ref results } =>
                     for result in results {
                         match result {
@@ -118,6 +118,8 @@ impl<'a> VerificationHelper for Helper<'a> {
                             return Err(failure::err_msg("No signature")),
                     }
                 },
+                // The literal data packet is always the last layer.
+                (_, MessageLayer::Literal { .. }) => (),
                 _ => return Err(failure::err_msg(
                     "Unexpected message structure")),
             }
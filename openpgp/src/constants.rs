@@ -1042,6 +1042,67 @@ impl Arbitrary for DataFormat {
     }
 }
 
+/// Implements serde's traits using the numeric identifiers.
+///
+/// Unlike the names, the identifiers are stable, and unknown values
+/// survive a roundtrip.
+#[cfg(feature = "serde")]
+macro_rules! impl_serde_using_u8 {
+    ($typ:ident) => {
+        impl ::serde::Serialize for $typ {
+            fn serialize<S>(&self, s: S) -> result::Result<S::Ok, S::Error>
+                where S: ::serde::Serializer
+            {
+                s.serialize_u8((*self).into())
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for $typ {
+            fn deserialize<D>(d: D) -> result::Result<Self, D::Error>
+                where D: ::serde::Deserializer<'de>
+            {
+                <u8 as ::serde::Deserialize>::deserialize(d).map(Into::into)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl_serde_using_u8!(PublicKeyAlgorithm);
+#[cfg(feature = "serde")]
+impl_serde_using_u8!(SymmetricAlgorithm);
+#[cfg(feature = "serde")]
+impl_serde_using_u8!(AEADAlgorithm);
+#[cfg(feature = "serde")]
+impl_serde_using_u8!(CompressionAlgorithm);
+#[cfg(feature = "serde")]
+impl_serde_using_u8!(HashAlgorithm);
+#[cfg(feature = "serde")]
+impl_serde_using_u8!(SignatureType);
+#[cfg(feature = "serde")]
+impl_serde_using_u8!(ReasonForRevocation);
+#[cfg(feature = "serde")]
+impl_serde_using_u8!(DataFormat);
+
+/// Curves are identified by their OID.
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for Curve {
+    fn serialize<S>(&self, s: S) -> result::Result<S::Ok, S::Error>
+        where S: ::serde::Serializer
+    {
+        ::conversions::serde_raw::serialize(self.oid(), s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for Curve {
+    fn deserialize<D>(d: D) -> result::Result<Self, D::Error>
+        where D: ::serde::Deserializer<'de>
+    {
+        ::conversions::serde_raw::deserialize(d).map(|oid| Curve::from_oid(&oid))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -285,6 +285,60 @@ pub(crate) fn write_be_u64(b: &mut [u8], n: u64) {
     b[7] = (n >>  0) as u8;
 }
 
+/// (De)serializes binary identifiers using serde.
+///
+/// Human-readable formats like JSON get a hexadecimal string, binary
+/// formats like CBOR get the raw bytes.
+#[cfg(feature = "serde")]
+pub(crate) mod serde_raw {
+    use std::fmt;
+    use std::result::Result;
+    use serde::{Deserializer, Serializer};
+    use serde::de::{self, Visitor};
+
+    pub(crate) fn serialize<S>(raw: &[u8], s: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        if s.is_human_readable() {
+            s.serialize_str(&super::to_hex(raw, false))
+        } else {
+            s.serialize_bytes(raw)
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D>(d: D) -> Result<Vec<u8>, D::Error>
+        where D: Deserializer<'de>
+    {
+        struct RawVisitor;
+
+        impl<'de> Visitor<'de> for RawVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a hexadecimal string or a byte string")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Vec<u8>, E>
+                where E: de::Error
+            {
+                super::from_hex(v, true).map_err(E::custom)
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Vec<u8>, E>
+                where E: de::Error
+            {
+                Ok(v.to_vec())
+            }
+        }
+
+        if d.is_human_readable() {
+            d.deserialize_str(RawVisitor)
+        } else {
+            d.deserialize_bytes(RawVisitor)
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
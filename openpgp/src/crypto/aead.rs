@@ -754,8 +754,7 @@ mod tests {
     #[test]
     fn roundtrip() {
         use std::io::Cursor;
-        use nettle::{Random, Yarrow};
-        let mut rng = Yarrow::default();
+        use crypto::random;
 
         for sym_algo in [SymmetricAlgorithm::AES128,
                          SymmetricAlgorithm::AES192,
@@ -768,10 +767,10 @@ mod tests {
                 let version = 1;
                 let chunk_size = 64;
                 let mut key = vec![0; sym_algo.key_size().unwrap()];
-                rng.random(&mut key);
+                random(&mut key);
                 let key: SessionKey = key.into();
                 let mut iv = vec![0; aead.iv_size().unwrap()];
-                rng.random(&mut iv);
+                random(&mut iv);
 
                 let mut ciphertext = Vec::new();
                 {
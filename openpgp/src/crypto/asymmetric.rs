@@ -1,9 +1,9 @@
 //! Asymmetric crypt operations.
 
-use nettle::{dsa, ecc, ecdsa, ed25519, rsa, Yarrow};
+use nettle::{dsa, ecc, ecdsa, ed25519, rsa};
 
 use packet::{self, Key};
-use crypto::SessionKey;
+use crypto::{self, SessionKey};
 use crypto::mpis::{self, MPI};
 use constants::{Curve, HashAlgorithm};
 
@@ -85,7 +85,7 @@ impl Signer for KeyPair {
         use crypto::mpis::PublicKey;
         use memsec;
 
-        let mut rng = Yarrow::default();
+        let mut rng = crypto::random::rng();
 
         #[allow(deprecated)]
         match (self.public.pk_algo(), self.public.mpis(), &self.secret)
@@ -222,7 +222,7 @@ impl Decryptor for KeyPair {
                 let public = rsa::PublicKey::new(&n.value, &e.value)?;
                 let secret = rsa::PrivateKey::new(&d.value, &p.value,
                                                   &q.value, Option::None)?;
-                let mut rand = Yarrow::default();
+                let mut rand = crypto::random::rng();
                 rsa::decrypt_pkcs1(&public, &secret, &mut rand, &c.value)?
             }
 
@@ -14,14 +14,14 @@ use conversions::{
     read_be_u64,
 };
 use crypto::mpis::{MPI, PublicKey, SecretKey, Ciphertext};
-use nettle::{cipher, curve25519, mode, Mode, ecc, ecdh, Yarrow};
+use nettle::{cipher, curve25519, mode, Mode, ecc, ecdh};
 
 /// Wraps a session key using Elliptic Curve Diffie-Hellman.
 #[allow(non_snake_case)]
 pub fn wrap_session_key(recipient: &Key, session_key: &[u8])
     -> Result<Ciphertext>
 {
-    let mut rng = Yarrow::default();
+    let mut rng = ::crypto::random::rng();
 
     if let &PublicKey::ECDH {
         ref curve, ref q,..
@@ -14,6 +14,7 @@ use conversions::Time;
 use nettle;
 use nettle::Hash as NettleHash;
 
+use std::cell::RefCell;
 use std::fs::{File, OpenOptions};
 use std::io::Write;
 
@@ -39,6 +40,10 @@ impl HashAlgorithm {
 
     /// Creates a new Nettle hash context for this algorith. Fails if Sequoia
     /// does not support this algorithm. See `is_supported`.
+    ///
+    /// If Nettle was built with `--enable-fat`, it selects the
+    /// fastest implementation supported by the CPU at runtime, e.g.
+    /// one using the SHA extensions on x86-64.
     pub fn context(self) -> Result<Box<nettle::Hash>> {
         use nettle::hash::*;
         use nettle::hash::insecure_do_not_use::Sha1;
@@ -136,6 +141,109 @@ impl nettle::Hash for HashDumper {
     }
 }
 
+/// Hash contexts over a key, one per hash algorithm.
+struct KeyHashes {
+    key: Key,
+    contexts: Vec<(HashAlgorithm, Box<nettle::Hash>)>,
+}
+
+thread_local! {
+    /// The key of the innermost `KeyHashScope`.
+    static KEY_HASHES: RefCell<Option<KeyHashes>> = RefCell::new(None);
+}
+
+/// Hashes a primary key only once while verifying its signatures.
+///
+/// Self-signatures are over the primary key, followed by the
+/// component they bind and the signature's own fields.  When
+/// verifying many signatures over the same key, e.g. when
+/// canonicalizing a TPK, most of the data hashed is the primary key
+/// over and over again.
+///
+/// While a `KeyHashScope` is alive, the functions computing the
+/// digests of primary key, subkey, user ID, and user attribute
+/// bindings over its key, and the functions verifying these
+/// bindings, continue from a copy of a hash context over the key.
+/// The key is hashed once per hash algorithm.  Digests over other
+/// keys are computed as usual.
+///
+/// The scope is specific to the current thread.
+///
+/// # Example
+///
+/// ```
+/// # extern crate sequoia_openpgp as openpgp;
+/// # use openpgp::Result;
+/// # use openpgp::crypto::KeyHashScope;
+/// # use openpgp::tpk::TPKBuilder;
+/// # fn main() { f().unwrap(); }
+/// # fn f() -> Result<()> {
+/// let (tpk, _) = TPKBuilder::general_purpose(None, Some("alice@example.org"))
+///     .generate()?;
+///
+/// let _scope = KeyHashScope::new(tpk.primary());
+/// for binding in tpk.userids() {
+///     for selfsig in binding.selfsigs() {
+///         assert!(selfsig.verify_userid_binding(tpk.primary(),
+///                                               tpk.primary(),
+///                                               binding.userid())?);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct KeyHashScope {
+    previous: Option<KeyHashes>,
+}
+
+impl KeyHashScope {
+    /// Starts hashing `key` only once.
+    pub fn new(key: &Key) -> Self {
+        let hashes = KeyHashes {
+            key: key.clone(),
+            contexts: Vec::new(),
+        };
+        KeyHashScope {
+            previous: KEY_HASHES.with(|h| h.replace(Some(hashes))),
+        }
+    }
+}
+
+impl Drop for KeyHashScope {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        KEY_HASHES.with(|h| *h.borrow_mut() = previous);
+    }
+}
+
+/// Returns a new hash context over `key`.
+///
+/// If `key` is the key of the innermost `KeyHashScope`, the context
+/// is copied from the scope.
+fn key_context(key: &Key, algo: HashAlgorithm) -> Result<Box<nettle::Hash>> {
+    KEY_HASHES.with(|hashes| {
+        let mut hashes = hashes.borrow_mut();
+        let hashes = match *hashes {
+            Some(ref mut hashes) =>
+                if hashes.key == *key { Some(hashes) } else { None },
+            None => None,
+        };
+
+        if let Some(&(_, ref h)) = hashes.as_ref()
+            .and_then(|hashes| hashes.contexts.iter().find(|c| c.0 == algo))
+        {
+            return Ok(h.box_clone());
+        }
+
+        let mut h = algo.context()?;
+        key.hash(&mut h);
+        if let Some(hashes) = hashes {
+            hashes.contexts.push((algo, h.box_clone()));
+        }
+        Ok(h)
+    })
+}
+
 /// Hashes OpenPGP packets and related types.
 pub trait Hash {
     /// Updates the given hash with this object.
@@ -295,9 +403,8 @@ impl Signature {
         where S: Into<&'a signature::Builder> {
 
         let sig = sig.into();
-        let mut h: Box<nettle::Hash> = sig.hash_algo().context()?;
+        let mut h = key_context(key, sig.hash_algo())?;
 
-        key.hash(&mut h);
         sig.hash(&mut h);
 
         let mut digest = vec![0u8; h.digest_size()];
@@ -312,9 +419,8 @@ impl Signature {
         where S: Into<&'a signature::Builder> {
 
         let sig = sig.into();
-        let mut h: Box<nettle::Hash> = sig.hash_algo().context()?;
+        let mut h = key_context(key, sig.hash_algo())?;
 
-        key.hash(&mut h);
         subkey.hash(&mut h);
         sig.hash(&mut h);
 
@@ -330,9 +436,8 @@ impl Signature {
         where S: Into<&'a signature::Builder> {
 
         let sig = sig.into();
-        let mut h: Box<nettle::Hash> = sig.hash_algo().context()?;
+        let mut h = key_context(key, sig.hash_algo())?;
 
-        key.hash(&mut h);
         userid.hash(&mut h);
         sig.hash(&mut h);
 
@@ -349,9 +454,8 @@ impl Signature {
         where S: Into<&'a signature::Builder> {
 
         let sig = sig.into();
-        let mut h: Box<nettle::Hash> = sig.hash_algo().context()?;
+        let mut h = key_context(key, sig.hash_algo())?;
 
-        key.hash(&mut h);
         ua.hash(&mut h);
         sig.hash(&mut h);
 
@@ -427,8 +531,14 @@ mod test {
         check(TPK::from_bytes(::tests::key("hash-algos/SHA384.gpg")).unwrap());
         check(TPK::from_bytes(::tests::key("hash-algos/SHA512.gpg")).unwrap());
         check(TPK::from_bytes(::tests::key("bannon-all-uids-subkeys.gpg")).unwrap());
-        let (_userid_sigs, ua_sigs, _subkey_sigs)
-            = check(TPK::from_bytes(::tests::key("dkg.gpg")).unwrap());
-        assert!(ua_sigs > 0);
+        let dkg = TPK::from_bytes(::tests::key("dkg.gpg")).unwrap();
+        let sigs = check(dkg.clone());
+        assert!(sigs.1 > 0);
+
+        // Hashing the primary key only once yields the same digests.
+        let _scope = KeyHashScope::new(dkg.primary());
+        assert_eq!(check(dkg.clone()), sigs);
+        assert_eq!(check(dkg), sigs);
+        check(TPK::from_bytes(::tests::key("bannon-all-uids-subkeys.gpg")).unwrap());
     }
 }
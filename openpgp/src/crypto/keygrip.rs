@@ -1,4 +1,5 @@
 use std::fmt;
+use std::str::FromStr;
 
 use nettle;
 use nettle::Hash;
@@ -10,8 +11,12 @@ use crypto::mpis::{MPI, PublicKey};
 
 /// A proprietary, protocol agnostic identifier for public keys.
 ///
-/// This is defined and used by GnuPG.
-#[derive(Clone, PartialEq, Eq, Hash)]
+/// This is defined and used by GnuPG.  Among other things, gpg-agent
+/// names the files in its private key store after the keygrips of
+/// the keys, see [`Key::keygrip`].
+///
+///   [`Key::keygrip`]: ../packet/key/struct.Key.html#method.keygrip
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Keygrip([u8; 20]);
 
 impl fmt::Debug for Keygrip {
@@ -32,6 +37,20 @@ impl fmt::Display for Keygrip {
     }
 }
 
+impl From<[u8; 20]> for Keygrip {
+    fn from(digest: [u8; 20]) -> Self {
+        Keygrip(digest)
+    }
+}
+
+impl FromStr for Keygrip {
+    type Err = ::failure::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_hex(s)
+    }
+}
+
 impl Keygrip {
     /// Parses a keygrip.
     pub fn from_hex(hex: &str) -> Result<Self> {
@@ -45,10 +64,25 @@ impl Keygrip {
         &mut digest[..].copy_from_slice(&bytes[..]);
         Ok(Keygrip(digest))
     }
+
+    /// Returns the raw keygrip.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Converts the keygrip to a hexadecimal number.
+    ///
+    /// This is the form used by GnuPG, e.g. in the names of
+    /// gpg-agent's private key files.
+    pub fn to_hex(&self) -> String {
+        format!("{}", self)
+    }
 }
 
 impl PublicKey {
     /// Computes the keygrip.
+    ///
+    /// Fails for unknown algorithms and curves.
     pub fn keygrip(&self) -> Result<Keygrip> {
         use self::PublicKey::*;
         let mut hash = nettle::hash::insecure_do_not_use::Sha1::default();
@@ -63,7 +97,7 @@ impl PublicKey {
             write!(hash, ")").unwrap();
         }
 
-        fn hash_ecc<H>(hash: &mut H, curve: &Curve, q: &MPI)
+        fn hash_ecc<H>(hash: &mut H, curve: &Curve, q: &MPI) -> Result<()>
             where H: Hash + ::std::io::Write
         {
             for (i, name) in "pabgnhq".chars().enumerate() {
@@ -72,7 +106,7 @@ impl PublicKey {
                 }
 
                 let mut m =
-                    if i == 6 { q.clone() } else { ecc_param(curve, i) };
+                    if i == 6 { q.clone() } else { ecc_param(curve, i)? };
 
                 // Opaque encoding?
                 if m.value[0] == 0x40 {
@@ -84,6 +118,7 @@ impl PublicKey {
 
                 hash_sexp_mpi(hash, name, &[], &m);
             }
+            Ok(())
         }
 
         match self {
@@ -117,9 +152,9 @@ impl PublicKey {
                 hash_sexp_mpi(&mut hash, 'y', b"", y);
             },
 
-            &EdDSA { ref curve, ref q } => hash_ecc(&mut hash, curve, q),
-            &ECDSA { ref curve, ref q } => hash_ecc(&mut hash, curve, q),
-            &ECDH { ref curve, ref q, .. } => hash_ecc(&mut hash, curve, q),
+            &EdDSA { ref curve, ref q } => hash_ecc(&mut hash, curve, q)?,
+            &ECDSA { ref curve, ref q } => hash_ecc(&mut hash, curve, q)?,
+            &ECDH { ref curve, ref q, .. } => hash_ecc(&mut hash, curve, q)?,
 
             &Unknown { .. } =>
                 return Err(Error::InvalidOperation(
@@ -136,7 +171,7 @@ impl PublicKey {
 /// Returns curve parameters.
 ///
 /// These parameters are a courtesy of libgcrypt.
-fn ecc_param(curve: &Curve, i: usize) -> MPI {
+fn ecc_param(curve: &Curve, i: usize) -> Result<MPI> {
     use self::Curve::*;
     assert!(i < 6);
     let hex = match (curve, i) {
@@ -203,10 +238,12 @@ fn ecc_param(curve: &Curve, i: usize) -> MPI {
                          20AE19A1B8A086B4E01EDD2C7748D14C923D4D7E6D7C61B229E9C5A27ECED3D9",
         (Cv25519, 5) => "0x08",
 
+        (Unknown(_), _) =>
+            return Err(Error::UnsupportedEllipticCurve(curve.clone()).into()),
         (_, _) => unreachable!(),
     };
 
-    ::conversions::from_hex(hex, true).unwrap().into()
+    Ok(::conversions::from_hex(hex, true).unwrap().into())
 }
 
 #[cfg(test)]
@@ -274,6 +311,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn unknown_curve() {
+        let key = PublicKey::EdDSA {
+            curve: Curve::Unknown(vec![0x2b, 0x06, 0x01].into_boxed_slice()),
+            q: from_hex("40", false).unwrap().into(),
+        };
+        assert!(key.keygrip().is_err());
+    }
+
+    #[test]
+    fn hex_roundtrip() {
+        let kg: Keygrip = "71ADDE3BBC0B7F1BFC2DA414C4F473B197763733"
+            .parse().unwrap();
+        assert_eq!(kg.to_hex(), "71ADDE3BBC0B7F1BFC2DA414C4F473B197763733");
+        assert_eq!(Keygrip::from_hex(&kg.to_hex().to_lowercase()).unwrap(),
+                   kg);
+        assert!(Keygrip::from_hex("71ADDE3B").is_err());
+    }
+
     /// Tests from our test keys, using GnuPG as oracle.
     #[test]
     fn our_keys() {
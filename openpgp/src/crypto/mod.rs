@@ -1,12 +1,17 @@
 //! Cryptographic primitives.
 
-use std::io::Read;
+use std::alloc::{self, Layout};
+use std::io::{self, Read};
 use std::ops::{Deref, DerefMut};
 use std::fmt;
 use std::cmp::{min, Ordering};
+use std::ptr::{self, NonNull};
+use std::slice;
 
+#[cfg(unix)]
+use libc;
 use memsec;
-use nettle::{self, Random, Yarrow};
+use nettle;
 
 use constants::HashAlgorithm;
 use Result;
@@ -18,6 +23,8 @@ mod hash;
 mod keygrip;
 pub use self::keygrip::Keygrip;
 pub mod mpis;
+pub mod random;
+pub use self::random::random;
 pub mod s2k;
 pub mod sexp;
 pub(crate) mod symmetric;
@@ -29,6 +36,7 @@ pub use self::asymmetric::{
 };
 
 pub use self::hash::Hash;
+pub use self::hash::KeyHashScope;
 
 /// Holds a session key.
 ///
@@ -44,9 +52,9 @@ impl PartialEq for SessionKey {
 
 impl SessionKey {
     /// Creates a new session key.
-    pub fn new(rng: &mut Yarrow, size: usize) -> Self {
+    pub fn new(size: usize) -> Self {
         let mut sk = vec![0; size];
-        rng.random(&mut sk);
+        random(&mut sk);
         sk.into()
     }
 }
@@ -107,11 +115,91 @@ impl fmt::Debug for SessionKey {
     }
 }
 
+/// Returns the size of a memory page.
+#[cfg(unix)]
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+/// Returns the size of a memory page.
+#[cfg(not(unix))]
+fn page_size() -> usize {
+    4096
+}
+
+/// Memory that is locked while it is alive.
+///
+/// The memory is allocated in whole pages that are not shared with
+/// any other allocation, so locking and unlocking it does not affect
+/// other objects.  It is cleared when dropped.
+struct Protected {
+    ptr: NonNull<u8>,
+    len: usize,
+    // The layout of the allocation, or None if nothing is allocated.
+    layout: Option<Layout>,
+}
+
+// The memory is owned exclusively, like a Box<[u8]>.
+unsafe impl Send for Protected {}
+unsafe impl Sync for Protected {}
+
+impl Protected {
+    /// Copies `data` into locked memory.
+    fn new(data: &[u8]) -> Self {
+        if data.is_empty() {
+            return Protected { ptr: NonNull::dangling(), len: 0, layout: None };
+        }
+
+        let page = page_size();
+        let size = (data.len() + page - 1) / page * page;
+        let layout = Layout::from_size_align(size, page)
+            .expect("page size is a power of two");
+        unsafe {
+            let mem = match NonNull::new(alloc::alloc_zeroed(layout)) {
+                Some(mem) => mem,
+                None => alloc::handle_alloc_error(layout),
+            };
+            // Locking is best-effort.  It may fail, e.g., if the
+            // process exceeds RLIMIT_MEMLOCK.
+            memsec::mlock(mem.as_ptr(), size);
+            ptr::copy_nonoverlapping(data.as_ptr(), mem.as_ptr(), data.len());
+            Protected { ptr: mem, len: data.len(), layout: Some(layout) }
+        }
+    }
+}
+
+impl Deref for Protected {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for Protected {
+    fn drop(&mut self) {
+        if let Some(layout) = self.layout {
+            unsafe {
+                memsec::memzero(self.ptr.as_ptr(), layout.size());
+                memsec::munlock(self.ptr.as_ptr(), layout.size());
+                alloc::dealloc(self.ptr.as_ptr(), layout);
+            }
+        }
+    }
+}
+
 /// Holds a password.
 ///
-/// The password is cleared when dropped.
-#[derive(Clone, Eq)]
-pub struct Password(Box<[u8]>);
+/// Where supported by the operating system, the memory holding the
+/// password is locked so that it is not swapped out.  The password is
+/// cleared when dropped.
+pub struct Password(Protected);
+
+impl Clone for Password {
+    fn clone(&self) -> Self {
+        Password(Protected::new(&self.0))
+    }
+}
 
 impl PartialEq for Password {
     fn eq(&self, other: &Self) -> bool {
@@ -119,6 +207,8 @@ impl PartialEq for Password {
     }
 }
 
+impl Eq for Password {}
+
 impl AsRef<[u8]> for Password {
     fn as_ref(&self) -> &[u8] {
         &self.0
@@ -135,13 +225,18 @@ impl Deref for Password {
 
 impl From<Vec<u8>> for Password {
     fn from(v: Vec<u8>) -> Self {
-        Password(v.into_boxed_slice())
+        v.into_boxed_slice().into()
     }
 }
 
 impl From<Box<[u8]>> for Password {
-    fn from(v: Box<[u8]>) -> Self {
-        Password(v)
+    fn from(mut v: Box<[u8]>) -> Self {
+        let p = Password(Protected::new(&v));
+        // Do not leave a copy of the password behind.
+        unsafe {
+            memsec::memzero(v.as_mut_ptr(), v.len());
+        }
+        p
     }
 }
 
@@ -153,28 +248,20 @@ impl From<String> for Password {
 
 impl<'a> From<&'a str> for Password {
     fn from(v: &'a str) -> Self {
-        v.to_owned().into()
+        v.as_bytes().into()
     }
 }
 
 impl From<&[u8]> for Password {
     fn from(v: &[u8]) -> Self {
-        Vec::from(v).into()
-    }
-}
-
-impl Drop for Password {
-    fn drop(&mut self) {
-        unsafe {
-            memsec::memzero(self.0.as_mut_ptr(), self.0.len());
-        }
+        Password(Protected::new(v))
     }
 }
 
 impl fmt::Debug for Password {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if cfg!(debug_assertions) {
-            write!(f, "Password ({:?})", self.0)
+            write!(f, "Password ({:?})", &self.0[..])
         } else {
             f.write_str("Password ( <Redacted> )")
         }
@@ -182,14 +269,66 @@ impl fmt::Debug for Password {
 }
 
 
+/// Hashes all data read from `reader` using `hash`.
+///
+/// Like `io::copy`, this reads the data a chunk at a time into a
+/// fixed-size buffer, so it uses a constant amount of memory
+/// regardless of the size of the input.  Returns the number of bytes
+/// hashed.
+///
+/// # Example
+///
+/// ```
+/// # extern crate sequoia_openpgp as openpgp;
+/// # use openpgp::Result;
+/// use std::io::{self, Read};
+/// use openpgp::constants::HashAlgorithm;
+/// use openpgp::crypto::hash_reader;
+///
+/// # fn main() { f().unwrap(); }
+/// # fn f() -> Result<()> {
+/// let mut hash = HashAlgorithm::SHA256.context()?;
+/// let n = hash_reader(&mut io::repeat(0).take(1 << 20), &mut hash)?;
+/// assert_eq!(n, 1 << 20);
+/// # Ok(()) }
+/// ```
+pub fn hash_reader<R: Read + ?Sized>(reader: &mut R, hash: &mut nettle::Hash)
+    -> io::Result<u64>
+{
+    let mut buf = vec![0u8; HASH_READER_BUF_SIZE];
+    let mut total = 0;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(total),
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hash.update(&buf[..n]);
+        total += n as u64;
+    }
+}
+
+/// The size of the buffer used by `hash_reader`.
+const HASH_READER_BUF_SIZE: usize = 64 * 1024;
+
 /// Hash the specified file.
 ///
-/// This is useful when verifying detached signatures.
+/// This is useful when verifying detached signatures.  The data is
+/// processed a chunk at a time, so arbitrarily large files can be
+/// hashed using a constant amount of memory.
+///
+/// If any of the algorithms is not supported, this fails with
+/// `Error::UnsupportedHashAlgorithm`.
 pub fn hash_file<R: Read>(reader: R, algos: &[HashAlgorithm])
     -> Result<Vec<(HashAlgorithm, Box<nettle::Hash>)>>
 {
     use std::mem;
 
+    if let Some(&algo) = algos.iter().find(|a| ! a.is_supported()) {
+        return Err(::Error::UnsupportedHashAlgorithm(algo).into());
+    }
+
     use ::parse::HashedReader;
     use ::parse::HashesFor;
 
@@ -213,6 +352,25 @@ pub fn hash_file<R: Read>(reader: R, algos: &[HashAlgorithm])
 }
 
 
+#[test]
+fn hash_reader_test() {
+    use std::io::Cursor;
+
+    // Make sure we cross the buffer boundary.
+    let data = vec![0x5au8; HASH_READER_BUF_SIZE * 2 + 17];
+    let mut expected = HashAlgorithm::SHA256.context().unwrap();
+    expected.update(&data);
+    let mut expected_digest = vec![0u8; expected.digest_size()];
+    expected.digest(&mut expected_digest);
+
+    let mut hash = HashAlgorithm::SHA256.context().unwrap();
+    assert_eq!(hash_reader(&mut Cursor::new(&data), &mut hash).unwrap(),
+               data.len() as u64);
+    let mut digest = vec![0u8; hash.digest_size()];
+    hash.digest(&mut digest);
+    assert_eq!(digest, expected_digest);
+}
+
 #[test]
 fn hash_file_test() {
     use std::collections::HashMap;
@@ -234,10 +392,15 @@ fn hash_file_test() {
         assert_eq!(*expected.get(&algo).unwrap(),
                    &::conversions::to_hex(&digest[..], false));
     }
+
+    // Unsupported algorithms are rejected.
+    assert!(hash_file(::std::io::Cursor::new(::tests::manifesto()),
+                      &[HashAlgorithm::SHA256, HashAlgorithm::Unknown(100)])
+            .is_err());
 }
 
 /// Time-constant comparison.
-fn secure_cmp(a: &[u8], b: &[u8]) -> Ordering {
+pub(crate) fn secure_cmp(a: &[u8], b: &[u8]) -> Ordering {
     let ord1 = a.len().cmp(&b.len());
     let ord2 = unsafe {
         memsec::memcmp(a.as_ptr(), b.as_ptr(), min(a.len(), b.len()))
@@ -251,3 +414,73 @@ fn secure_cmp(a: &[u8], b: &[u8]) -> Ordering {
 
     if ord1 == Ordering::Equal { ord2 } else { ord1 }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn password_conversions() {
+        let p = Password::from("hunter2");
+        assert_eq!(&p[..], b"hunter2");
+        assert_eq!(p, Password::from(b"hunter2".to_vec()));
+        assert_eq!(p, Password::from(&b"hunter2"[..]));
+        assert_eq!(p, Password::from(String::from("hunter2")));
+        assert_eq!(p, p.clone());
+        assert!(p != Password::from("hunter3"));
+
+        // Empty passwords are not locked, and must not be unlocked.
+        assert_eq!(Password::from("").len(), 0);
+    }
+
+    /// Returns whether the page containing `addr` is locked.
+    #[cfg(target_os = "linux")]
+    fn locked(addr: usize) -> bool {
+        let smaps = ::std::fs::read_to_string("/proc/self/smaps").unwrap();
+        let mut in_mapping = false;
+        for line in smaps.lines() {
+            let field = line.split_whitespace().next().unwrap_or("");
+            if ! field.ends_with(':') {
+                // A new mapping, e.g. "7f52a0c00000-7f52a0c21000 rw-p ...".
+                let mut range = field.split('-')
+                    .map(|a| usize::from_str_radix(a, 16).unwrap());
+                let start = range.next().unwrap();
+                let end = range.next().unwrap();
+                in_mapping = start <= addr && addr < end;
+            } else if in_mapping && field == "VmFlags:" {
+                return line.split_whitespace().any(|f| f == "lo");
+            }
+        }
+        panic!("No mapping contains {:#x}", addr);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn password_locked() {
+        let page = page_size();
+
+        // Locking is best-effort.  If we are not allowed to lock
+        // memory, there is nothing to test.
+        let mut probe = vec![0u8; 2 * page];
+        unsafe {
+            if ! memsec::mlock(probe.as_mut_ptr(), probe.len()) {
+                return;
+            }
+            memsec::munlock(probe.as_mut_ptr(), probe.len());
+        }
+
+        // Passwords do not share pages, hence dropping one does not
+        // unlock the other.
+        let a = Password::from("hunter2");
+        let b = Password::from("hunter3");
+        assert_eq!(a.as_ptr() as usize % page, 0);
+        assert!(locked(a.as_ptr() as usize));
+        assert!(locked(b.as_ptr() as usize));
+        drop(a);
+        assert!(locked(b.as_ptr() as usize));
+
+        let p = Password::from(vec![0x2a; 2 * page]);
+        assert!(locked(p.as_ptr() as usize + page));
+        assert!(! locked(probe.as_ptr() as usize + page));
+    }
+}
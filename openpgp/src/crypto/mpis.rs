@@ -269,6 +269,23 @@ impl PublicKey {
         }
     }
 
+    /// Returns the curve, if any.
+    ///
+    /// The curve's OID can be retrieved using [`Curve::oid`], even if
+    /// the curve is unknown.
+    ///
+    ///   [`Curve::oid`]: ../../constants/enum.Curve.html#method.oid
+    pub fn curve(&self) -> Option<&Curve> {
+        use self::PublicKey::*;
+        match self {
+            &EdDSA { ref curve, .. } => Some(curve),
+            &ECDSA { ref curve, .. } => Some(curve),
+            &ECDH { ref curve, .. } => Some(curve),
+            &RSA { .. } | &DSA { .. } | &Elgamal { .. } | &Unknown { .. } =>
+                None,
+        }
+    }
+
     /// Returns the length of the public key in bits.
     ///
     /// For finite field crypto this returns the size of the field we
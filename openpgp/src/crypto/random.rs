@@ -0,0 +1,213 @@
+//! Random number generation.
+//!
+//! All randomness used by Sequoia, e.g. for session keys, salts,
+//! initialization vectors, and key generation, is drawn using
+//! [`random`], or from generators derived using [`rng`].
+//!
+//! Every thread has its own generator, which is seeded from the
+//! operating system.  If the process forks, the generators are
+//! reseeded before they are used again, so that parent and child do
+//! not produce the same numbers.  Applications can also request
+//! reseeding explicitly using [`reseed`], and be notified of it
+//! using [`add_reseed_hook`].
+//!
+//! For tests, [`deterministic`] temporarily replaces the current
+//! thread's generator with one seeded from a fixed value.  It is
+//! only available if the `deterministic-rng` feature is enabled, so
+//! that it cannot be used by accident in production code.
+//!
+//!   [`random`]: fn.random.html
+//!   [`rng`]: fn.rng.html
+//!   [`reseed`]: fn.reseed.html
+//!   [`add_reseed_hook`]: fn.add_reseed_hook.html
+//!   [`deterministic`]: fn.deterministic.html
+
+use std::cell::RefCell;
+use std::process;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use nettle::{Random, Yarrow};
+
+/// Size of the seeds of derived generators.
+const SEED_SIZE: usize = 32;
+
+/// Incremented whenever the generators must be reseeded.
+static EPOCH: AtomicUsize = AtomicUsize::new(0);
+
+/// The id of the process that last drew random numbers.
+static PID: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    static ref HOOKS: Mutex<Vec<Box<Fn() + Send>>> = Mutex::new(Vec::new());
+}
+
+/// A thread's generator.
+struct Generator {
+    rng: Yarrow,
+    epoch: usize,
+}
+
+thread_local! {
+    static GENERATOR: RefCell<Option<Generator>> = RefCell::new(None);
+}
+
+#[cfg(any(test, feature = "deterministic-rng"))]
+thread_local! {
+    static DETERMINISTIC: RefCell<Option<Yarrow>> = RefCell::new(None);
+}
+
+/// Fills the given buffer with random numbers.
+///
+/// # Example
+///
+/// ```
+/// use sequoia_openpgp::crypto;
+///
+/// let mut salt = [0u8; 8];
+/// crypto::random(&mut salt);
+/// ```
+pub fn random(buf: &mut [u8]) {
+    #[cfg(any(test, feature = "deterministic-rng"))]
+    {
+        let deterministic = DETERMINISTIC.with(|d| {
+            match d.borrow_mut().as_mut() {
+                Some(rng) => {
+                    rng.random(&mut *buf);
+                    true
+                },
+                None => false,
+            }
+        });
+        if deterministic {
+            return;
+        }
+    }
+
+    let pid = process::id() as usize;
+    let last = PID.swap(pid, Ordering::SeqCst);
+    if last != 0 && last != pid {
+        // We are in a forked child.  Make sure that we do not
+        // produce the same numbers as the parent.
+        reseed();
+    }
+
+    let epoch = EPOCH.load(Ordering::SeqCst);
+    GENERATOR.with(|g| {
+        let mut g = g.borrow_mut();
+        let stale = match *g {
+            Some(ref g) => g.epoch != epoch,
+            None => true,
+        };
+        if stale {
+            *g = Some(Generator {
+                rng: Yarrow::default(),
+                epoch: epoch,
+            });
+        }
+        g.as_mut().expect("initialized above").rng.random(buf);
+    });
+}
+
+/// Returns a generator seeded using [`random`].
+///
+/// This is useful for functions taking a generator, e.g. for key
+/// generation.
+///
+///   [`random`]: fn.random.html
+pub fn rng() -> Yarrow {
+    let mut seed = [0u8; SEED_SIZE];
+    random(&mut seed);
+    Yarrow::from_seed(&seed)
+}
+
+/// Reseeds the generators of all threads.
+///
+/// The generators are reseeded from the operating system the next
+/// time they are used.  Afterwards, the registered reseed hooks are
+/// run.  This happens automatically after a fork.
+pub fn reseed() {
+    EPOCH.fetch_add(1, Ordering::SeqCst);
+    for hook in HOOKS.lock().expect("poisoned lock").iter() {
+        hook();
+    }
+}
+
+/// Registers a function to be run whenever the generators are
+/// reseeded.
+///
+/// This can be used to reseed other generators an application uses.
+/// Hooks must not register further hooks.
+pub fn add_reseed_hook<F>(hook: F)
+    where F: Fn() + Send + 'static
+{
+    HOOKS.lock().expect("poisoned lock").push(Box::new(hook));
+}
+
+/// Calls `fun` with a deterministic generator.
+///
+/// While `fun` runs, [`random`] and [`rng`] draw from a generator
+/// seeded from `seed` in the current thread.  Other threads are not
+/// affected.  This must only be used for tests, hence it requires
+/// the `deterministic-rng` feature.
+///
+///   [`random`]: fn.random.html
+///   [`rng`]: fn.rng.html
+#[cfg(any(test, feature = "deterministic-rng"))]
+pub fn deterministic<F, T>(seed: &[u8], fun: F) -> T
+    where F: FnOnce() -> T
+{
+    /// Restores the previous generator, even if `fun` panics.
+    struct Restore(Option<Yarrow>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            DETERMINISTIC.with(|d| *d.borrow_mut() = previous);
+        }
+    }
+
+    let previous = DETERMINISTIC.with(|d| {
+        d.borrow_mut().replace(Yarrow::from_seed(seed))
+    });
+    let _restore = Restore(previous);
+    fun()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn deterministic_rng() {
+        let draw = || {
+            let mut buf = [0u8; 16];
+            random(&mut buf);
+            buf
+        };
+
+        let a = deterministic(b"seed", || (draw(), draw()));
+        let b = deterministic(b"seed", || (draw(), draw()));
+        assert_eq!(a, b);
+        assert!(a.0 != a.1);
+        assert!(deterministic(b"other seed", &draw) != a.0);
+        assert!(draw() != a.0);
+    }
+
+    #[test]
+    fn reseed_hooks() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let c = count.clone();
+        add_reseed_hook(move || { c.fetch_add(1, Ordering::SeqCst); });
+
+        let mut before = [0u8; 16];
+        random(&mut before);
+        reseed();
+        assert!(count.load(Ordering::SeqCst) >= 1);
+
+        let mut after = [0u8; 16];
+        random(&mut after);
+        assert!(before != after);
+    }
+}
@@ -9,15 +9,49 @@
 use Error;
 use Result;
 use HashAlgorithm;
-use crypto::Password;
+use crypto::{self, Password};
 use crypto::SessionKey;
 
+use std::cmp;
 use std::fmt;
+use std::time::{Duration, Instant};
 
-use nettle::{Hash, Yarrow, Random};
+use nettle::Hash;
 use quickcheck::{Arbitrary, Gen};
 use rand::Rng;
 
+/// The size of the buffer the iterated S2K hashes from.
+const ITERATED_BUF_SIZE: usize = 64 * 1024;
+
+/// The largest number of bytes to hash that OpenPGP can represent.
+const MAX_HASH_BYTES: u32 = 0x3e00000;
+
+/// The time the default S2K should take to derive a key.
+///
+/// On moderate machines, like an Intel(R) Core(TM) i5-2400 CPU @
+/// 3.10GHz, this is about the time it takes to hash
+/// `MAX_HASH_BYTES`, hence faster machines are capped at that count.
+const DEFAULT_TARGET_MS: u32 = 350;
+
+/// The minimum number of bytes to hash for the default S2K.
+///
+/// This guards against bogus measurements, e.g. if the process is
+/// preempted while calibrating.
+const DEFAULT_MIN_HASH_BYTES: u32 = 8 * 1024 * 1024;
+
+/// How long to benchmark the hash function when calibrating.
+const CALIBRATION_TIME: Duration = Duration::from_millis(10);
+
+lazy_static! {
+    /// The number of bytes to hash for the default S2K.
+    ///
+    /// Calibrated once per process.
+    static ref DEFAULT_HASH_BYTES: u32 =
+        S2K::calibrate(HashAlgorithm::SHA256, DEFAULT_TARGET_MS)
+            .map(|n| cmp::max(n, DEFAULT_MIN_HASH_BYTES))
+            .unwrap_or(MAX_HASH_BYTES);
+}
+
 /// String-to-Key (S2K) specifiers.
 ///
 /// String-to-key (S2K) specifiers are used to convert password
@@ -57,7 +91,7 @@ pub enum S2K {
 impl Default for S2K {
     fn default() -> Self {
         let mut salt = [0u8; 8];
-        Yarrow::default().random(&mut salt);
+        crypto::random(&mut salt);
         S2K::Iterated {
             // SHA2-256, being optimized for implementations on
             // architectures with a word size of 32 bit, has a more
@@ -66,15 +100,75 @@ impl Default for S2K {
             // for every cipher algorithm currently in use.
             hash: HashAlgorithm::SHA256,
             salt: salt,
-            // This is the largest count that OpenPGP can represent.
-            // On moderate machines, like my Intel(R) Core(TM) i5-2400
-            // CPU @ 3.10GHz, it takes ~354ms to derive a key.
-            hash_bytes: 65_011_712,
+            // Calibrated so that deriving a key takes about
+            // DEFAULT_TARGET_MS on this machine.
+            hash_bytes: *DEFAULT_HASH_BYTES,
         }
     }
 }
 
 impl S2K {
+    /// Returns an iterated and salted S2K that takes about
+    /// `target_ms` milliseconds to derive a key on this machine.
+    ///
+    /// This benchmarks `hash`, and uses the nearest encodable number
+    /// of bytes to hash.  The number is capped at the largest count
+    /// OpenPGP can represent, hence on fast machines deriving a key
+    /// may take less time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// use openpgp::constants::HashAlgorithm;
+    /// use openpgp::crypto::s2k::S2K;
+    ///
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> openpgp::Result<()> {
+    /// let s2k = S2K::calibrated(HashAlgorithm::SHA256, 100)?;
+    /// let key = s2k.derive_key(&"password".into(), 32)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn calibrated(hash: HashAlgorithm, target_ms: u32) -> Result<Self> {
+        let mut salt = [0u8; 8];
+        crypto::random(&mut salt);
+        Ok(S2K::Iterated {
+            hash: hash,
+            salt: salt,
+            hash_bytes: Self::calibrate(hash, target_ms)?,
+        })
+    }
+
+    /// Returns the encodable number of bytes `hash` digests in about
+    /// `target_ms` milliseconds on this machine.
+    fn calibrate(hash: HashAlgorithm, target_ms: u32) -> Result<u32> {
+        let mut ctx = hash.context()?;
+        let data = vec![0u8; ITERATED_BUF_SIZE];
+
+        let start = Instant::now();
+        let mut hashed = 0u64;
+        let elapsed = loop {
+            ctx.update(&data);
+            hashed += data.len() as u64;
+            let elapsed = start.elapsed();
+            if elapsed >= CALIBRATION_TIME {
+                break elapsed;
+            }
+        };
+
+        let seconds = elapsed.as_secs() as f64
+            + elapsed.subsec_nanos() as f64 / 1e9;
+        let hash_bytes = hashed as f64 / seconds * target_ms as f64 / 1e3;
+
+        // Clamp before converting, casting out-of-range floats is
+        // undefined.
+        if hash_bytes >= MAX_HASH_BYTES as f64 {
+            Ok(MAX_HASH_BYTES)
+        } else {
+            Ok(Self::nearest_hash_count(hash_bytes as usize))
+        }
+    }
+
     /// Convert the string to a key using the S2K's paramters.
     pub fn derive_key(&self, string: &Password, key_size: usize)
     -> Result<SessionKey> {
@@ -83,6 +177,15 @@ impl S2K {
             | &S2K::Iterated { hash, .. } => {
                 let mut hash = hash.context()?;
 
+                // The data hashed by the iterated S2K does not depend
+                // on the context, prepare it once.
+                let iterated = match self {
+                    &S2K::Iterated { ref salt, hash_bytes, .. }
+                    if hash_bytes as usize >= salt.len() + string.len() =>
+                        Some(Self::iterated_data(salt, string, hash_bytes)),
+                    _ => None,
+                };
+
                 // If the digest length is shorter than the key length,
                 // then we need to concatenate multiple hashes, each
                 // preloaded with i 0s.
@@ -110,30 +213,18 @@ impl S2K {
                             hash.update(&salt[..]);
                             hash.update(&string);
                         },
-                        &S2K::Iterated { ref salt, hash_bytes, .. } => {
-                            // Unroll the processing loop N times.
-                            const N: usize = 16;
-                            let data_len = salt.len() + string.len();
-                            let octs_per_iter = N * data_len;
-                            let mut data: SessionKey =
-                                vec![0u8; octs_per_iter].into();
-                            let full = hash_bytes as usize / octs_per_iter;
-                            let tail = hash_bytes as usize - (full * octs_per_iter);
-
-                            for i in 0..N {
-                                let o = data_len * i;
-                                data[o..o + salt.len()]
-                                    .clone_from_slice(salt);
-                                data[o + salt.len()..o + data_len]
-                                    .clone_from_slice(string);
-                            }
+                        &S2K::Iterated { hash_bytes, .. } => {
+                            let data = iterated.as_ref()
+                                .expect("prepared above");
+                            let full = hash_bytes as usize / data.len();
+                            let tail = hash_bytes as usize % data.len();
 
                             for _ in 0..full {
-                                hash.update(&data);
+                                hash.update(data);
                             }
 
                             if tail != 0 {
-                                hash.update(&data[0..tail]);
+                                hash.update(&data[..tail]);
                             }
                         }
                         &S2K::Unknown(_) | &S2K::Private(_) => unreachable!(),
@@ -151,6 +242,26 @@ impl S2K {
         }
     }
 
+    /// Returns the salt and password repeated as often as fits into
+    /// `ITERATED_BUF_SIZE` bytes, but at most `hash_bytes` bytes.
+    ///
+    /// The result contains at least one copy.  Hashing from a larger
+    /// buffer amortizes the per-call overhead of `Hash::update`.
+    fn iterated_data(salt: &[u8], string: &[u8], hash_bytes: u32)
+                     -> SessionKey {
+        let data_len = salt.len() + string.len();
+        let n = cmp::max(
+            1, cmp::min(hash_bytes as usize, ITERATED_BUF_SIZE) / data_len);
+        let mut data: SessionKey = vec![0u8; n * data_len].into();
+
+        for chunk in data.chunks_mut(data_len) {
+            chunk[..salt.len()].copy_from_slice(salt);
+            chunk[salt.len()..].copy_from_slice(string);
+        }
+
+        data
+    }
+
     /// This function returns an encodabled iteration count larger or
     /// equal `hash_bytes`.
     ///
@@ -162,7 +273,7 @@ impl S2K {
         match hash_bytes {
             0...1024 => 1024,
             1025...2048 => hash_bytes as u32,
-            0x3e00001...usize::MAX => 0x3e00000,
+            0x3e00001...usize::MAX => MAX_HASH_BYTES,
             hash_bytes => {
                 let hash_bytes = hash_bytes as u32;
                 let msb = 32 - hash_bytes.leading_zeros();
@@ -461,6 +572,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn s2k_iterated() {
+        // Compare against hashing the salted password byte by byte.
+        let salt = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08];
+        let long = vec![0x61; ITERATED_BUF_SIZE + 3];
+        for &password in &[&b"x"[..], b"password", &long[..]] {
+            for &hash_bytes in &[0u32, 9, 1024, 65_536, 100_003] {
+                let s2k = S2K::Iterated {
+                    hash: HashAlgorithm::SHA256,
+                    salt: salt,
+                    hash_bytes: hash_bytes,
+                };
+                let key = s2k.derive_key(&password.to_vec().into(), 32)
+                    .unwrap();
+
+                let mut data = salt.to_vec();
+                data.extend_from_slice(password);
+                let n = cmp::max(hash_bytes as usize, data.len());
+                let mut hash = HashAlgorithm::SHA256.context().unwrap();
+                for b in data.iter().cycle().take(n) {
+                    hash.update(&[*b]);
+                }
+                let mut expected = vec![0u8; 32];
+                hash.digest(&mut expected);
+
+                assert_eq!(&key[..], &expected[..]);
+            }
+        }
+    }
+
     #[test]
     fn s2k_coded_count_roundtrip() {
         for cc in 0..0x100usize {
@@ -470,6 +611,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn s2k_calibrated() {
+        for &target in [0, 1, 100, u32::max_value()].iter() {
+            match S2K::calibrated(HashAlgorithm::SHA256, target).unwrap() {
+                S2K::Iterated { hash, hash_bytes, .. } => {
+                    assert_eq!(hash, HashAlgorithm::SHA256);
+                    assert!(hash_bytes >= 1024 && hash_bytes <= MAX_HASH_BYTES);
+                    assert!(S2K::encode_count(hash_bytes).is_ok());
+                    if target == u32::max_value() {
+                        assert_eq!(hash_bytes, MAX_HASH_BYTES);
+                    }
+                },
+                s2k => panic!("unexpected S2K: {:?}", s2k),
+            }
+        }
+
+        match S2K::default() {
+            S2K::Iterated { hash_bytes, .. } =>
+                assert!(hash_bytes >= DEFAULT_MIN_HASH_BYTES),
+            s2k => panic!("unexpected S2K: {:?}", s2k),
+        }
+    }
+
     quickcheck!{
         fn s2k_coded_count_approx(i: usize) -> bool {
             let approx = S2K::nearest_hash_count(i);
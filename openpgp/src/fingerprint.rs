@@ -1,12 +1,20 @@
 use std::fmt;
 
 use Fingerprint;
+use IdentifierFormat;
 use KeyID;
 use Result;
 
 impl fmt::Display for Fingerprint {
+    /// Formats the fingerprint according to the
+    /// [`IdentifierFormat`].
+    ///
+    ///   [`IdentifierFormat`]: enum.IdentifierFormat.html
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        match IdentifierFormat::get() {
+            IdentifierFormat::Full => write!(f, "{}", self.to_string()),
+            _ => write!(f, "{}", self.to_keyid()),
+        }
     }
 }
 
@@ -168,6 +176,25 @@ impl Fingerprint {
     }
 }
 
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for Fingerprint {
+    fn serialize<S>(&self, s: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: ::serde::Serializer
+    {
+        ::conversions::serde_raw::serialize(self.as_slice(), s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for Fingerprint {
+    fn deserialize<D>(d: D) -> ::std::result::Result<Self, D::Error>
+        where D: ::serde::Deserializer<'de>
+    {
+        ::conversions::serde_raw::deserialize(d)
+            .map(|raw| Fingerprint::from_bytes(&raw))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -183,4 +210,29 @@ Charlie Delta Echo Foxtrot Zero One Two Three Four Five Six Seven";
 
         assert_eq!(fpr.to_icao(), expected);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use serde_json;
+        use constants::HashAlgorithm;
+
+        let fpr = Fingerprint::from_hex(
+            "3E8877C877274692975189F5D03F6F865226FE8B").unwrap();
+        let json = serde_json::to_string(&fpr).unwrap();
+        assert_eq!(json, "\"3E8877C877274692975189F5D03F6F865226FE8B\"");
+        assert_eq!(serde_json::from_str::<Fingerprint>(&json).unwrap(), fpr);
+
+        let keyid = fpr.to_keyid();
+        let json = serde_json::to_string(&keyid).unwrap();
+        assert_eq!(json, "\"D03F6F865226FE8B\"");
+        assert_eq!(serde_json::from_str::<KeyID>(&json).unwrap(), keyid);
+
+        let json = serde_json::to_string(&HashAlgorithm::SHA256).unwrap();
+        assert_eq!(json, "8");
+        assert_eq!(serde_json::from_str::<HashAlgorithm>(&json).unwrap(),
+                   HashAlgorithm::SHA256);
+        assert_eq!(serde_json::from_str::<HashAlgorithm>("99").unwrap(),
+                   HashAlgorithm::Unknown(99));
+    }
 }
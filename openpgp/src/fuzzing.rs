@@ -0,0 +1,156 @@
+//! Entry points for fuzzers.
+//!
+//! This module is only available if the crate is compiled with
+//! `--cfg fuzzing`, which `cargo fuzz` does automatically.  The
+//! functions accept arbitrary input, enable all of the parser's
+//! limits, and turn panics into [`Panic`] errors, so that fuzz
+//! targets do not need to know about the crate's internals:
+//!
+//! ```text
+//! fuzz_target!(|data: &[u8]| {
+//!     if let Err(e) = openpgp::fuzzing::parse_packet_bytes(data) {
+//!         assert!(e.downcast_ref::<openpgp::fuzzing::Panic>().is_none());
+//!     }
+//! });
+//! ```
+//!
+//! Besides the panics in the crate itself, [`Panic`] errors are also
+//! returned if a roundtrip property does not hold.
+//!
+//!   [`Panic`]: struct.Panic.html
+
+use std::io::{Read, Write};
+use std::panic;
+
+use Error;
+use Result;
+use TPK;
+use armor;
+use packet::aed;
+use parse::{Parse, PacketParserBuilder, PacketParserResult};
+use serialize::SerializeInto;
+
+/// The maximum recursion depth.
+///
+/// This is lower than the parser's default to keep the amount of
+/// work per input small.
+const MAX_RECURSION_DEPTH: u8 = 8;
+
+/// The maximum number of packets processed per input.
+const MAX_PACKETS: usize = 4096;
+
+/// A panic caught while processing fuzzer input.
+#[derive(Fail, Debug, Clone)]
+#[fail(display = "Panicked: {}", _0)]
+pub struct Panic(pub String);
+
+/// Runs `f`, converting panics into errors.
+fn catch<F>(f: F) -> Result<()>
+    where F: FnOnce() -> Result<()> + panic::UnwindSafe
+{
+    match panic::catch_unwind(f) {
+        Ok(r) => r,
+        Err(payload) => {
+            let msg = if let Some(s) = payload.downcast_ref::<&str>() {
+                s.to_string()
+            } else if let Some(s) = payload.downcast_ref::<String>() {
+                s.clone()
+            } else {
+                "unknown cause".into()
+            };
+            Err(Panic(msg).into())
+        },
+    }
+}
+
+/// Parses `data` as sequence of packets, recursing into containers.
+pub fn parse_packet_bytes(data: &[u8]) -> Result<()> {
+    catch(|| {
+        let mut ppr = PacketParserBuilder::from_bytes(data)?
+            .max_recursion_depth(MAX_RECURSION_DEPTH)
+            .max_aead_chunk_size(aed::MAX_CHUNK_SIZE)
+            .drop_unread_content()
+            .finalize()?;
+
+        let mut count = 0;
+        while let PacketParserResult::Some(pp) = ppr {
+            count += 1;
+            if count > MAX_PACKETS {
+                return Err(Error::InvalidOperation(
+                    format!("More than {} packets", MAX_PACKETS)).into());
+            }
+            ppr = pp.recurse()?.1;
+        }
+        Ok(())
+    })
+}
+
+/// Parses `data` as TPK.
+///
+/// If the TPK parses, it is serialized and parsed again, and the
+/// result must be equal to the first TPK.
+pub fn parse_tpk_bytes(data: &[u8]) -> Result<()> {
+    catch(|| {
+        let tpk = TPK::from_bytes(data)?;
+        let buf = tpk.as_tsk().to_vec()?;
+        let tpk_ = TPK::from_bytes(&buf)?;
+        assert_eq!(tpk, tpk_, "TPK does not roundtrip");
+        Ok(())
+    })
+}
+
+/// Dearmors `data`.
+///
+/// If `data` can be dearmored, the content is armored and dearmored
+/// again, and the result must be equal to the first content.
+pub fn armor_roundtrip(data: &[u8]) -> Result<()> {
+    catch(|| {
+        let mut reader = armor::Reader::from_bytes(data, None);
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content)?;
+        let kind = reader.kind().unwrap_or(armor::Kind::File);
+
+        let mut armored = Vec::new();
+        {
+            let mut writer = armor::Writer::new(&mut armored, kind, &[])?;
+            writer.write_all(&content)?;
+            writer.finalize()?;
+        }
+
+        let mut reader = armor::Reader::from_bytes(
+            &armored, armor::ReaderMode::Tolerant(Some(kind)));
+        let mut content_ = Vec::new();
+        reader.read_to_end(&mut content_)?;
+        assert_eq!(content, content_, "Armor does not roundtrip");
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_points() {
+        let tpk = ::tests::key("testy.pgp");
+        parse_packet_bytes(tpk).unwrap();
+        parse_tpk_bytes(tpk).unwrap();
+        armor_roundtrip(::tests::key("testy.asc")).unwrap();
+
+        // Garbage is rejected, but must not panic.
+        for data in [&b""[..], &b"\xff\xff\xff\xff"[..], &tpk[..10]].iter() {
+            for r in [parse_packet_bytes(data), parse_tpk_bytes(data),
+                      armor_roundtrip(data)].iter() {
+                if let Err(e) = r {
+                    assert!(e.downcast_ref::<Panic>().is_none(), "{}", e);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn panics_are_caught() {
+        let e = catch(|| panic!("boom")).unwrap_err();
+        assert_eq!(e.downcast_ref::<Panic>().unwrap().0, "boom");
+    }
+}
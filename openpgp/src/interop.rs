@@ -0,0 +1,525 @@
+//! Runs the parser and verifier against third-party artifacts.
+//!
+//! This module is only available if the `interop` feature is
+//! enabled.  It checks that Sequoia can consume OpenPGP data
+//! produced by other implementations, like GnuPG, OpenPGP.js, or
+//! RNP.
+//!
+//! # Corpus layout
+//!
+//! A corpus is a directory with one subdirectory per producing
+//! implementation.  Every file in a producer's directory with one of
+//! the extensions `pgp`, `gpg`, `asc`, or `sig` is an artifact.  All
+//! artifacts that are TPKs form the keyring used to verify
+//! signatures, regardless of which producer created them.  Detached
+//! signatures are verified over the file with the same name minus
+//! the extension, e.g. `hello.txt.sig` over `hello.txt`.
+//!
+//! Artifacts that Sequoia is known not to handle are marked by a
+//! file with the same name and the additional extension `xfail`,
+//! whose content explains why.
+//!
+//! ```text
+//! corpus/
+//!   gnupg/
+//!     key.pgp
+//!     hello.txt
+//!     hello.txt.sig
+//!     hello.txt.asc
+//!     hello.txt.asc.xfail
+//!   openpgpjs/
+//!     ...
+//! ```
+//!
+//! # Checks
+//!
+//! Every artifact is parsed, and its structure is checked using the
+//! TPK and message grammars.  Signed messages and detached
+//! signatures are then verified.  A check is only run if the
+//! previous one succeeded.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! extern crate sequoia_openpgp as openpgp;
+//! use openpgp::interop::Corpus;
+//!
+//! # fn main() { f().unwrap(); }
+//! # fn f() -> openpgp::Result<()> {
+//! let report = Corpus::new("tests/data/interop").run()?;
+//! report.serialize(&mut ::std::io::stdout())?;
+//! assert!(report.unexpected().is_empty());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use Error;
+use KeyID;
+use Message;
+use Packet;
+use PacketPile;
+use Result;
+use TPK;
+use packet::Tag;
+use parse::{Parse, PacketParser, PacketParserResult};
+use parse::stream::{
+    DetachedVerifier,
+    MessageLayer,
+    MessageStructure,
+    VerificationHelper,
+    VerificationResult,
+    Verifier,
+};
+
+/// The extensions of files considered to be artifacts.
+const ARTIFACT_EXTENSIONS: &[&str] = &["pgp", "gpg", "asc", "sig"];
+
+/// The extension marking an artifact as expected to fail.
+const XFAIL_EXTENSION: &str = "xfail";
+
+/// A check run on an artifact.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// Parses all packets, including the content of containers.
+    Parse,
+    /// Checks the artifact against the TPK or message grammar.
+    Structure,
+    /// Verifies the signatures.
+    Verify,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Check::Parse => f.write_str("parse"),
+            Check::Structure => f.write_str("structure"),
+            Check::Verify => f.write_str("verify"),
+        }
+    }
+}
+
+/// What kind of OpenPGP data an artifact contains.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// A TPK or TSK.
+    Key,
+    /// One or more detached signatures.
+    DetachedSignature,
+    /// A message containing signatures.
+    SignedMessage,
+    /// An encrypted message.
+    EncryptedMessage,
+    /// Any other message.
+    Message,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Kind::Key => f.write_str("key"),
+            Kind::DetachedSignature => f.write_str("detached signature"),
+            Kind::SignedMessage => f.write_str("signed message"),
+            Kind::EncryptedMessage => f.write_str("encrypted message"),
+            Kind::Message => f.write_str("message"),
+        }
+    }
+}
+
+/// The results of checking a single artifact.
+#[derive(Clone, Debug)]
+pub struct ArtifactReport {
+    producer: String,
+    path: PathBuf,
+    kind: Option<Kind>,
+    expected_failure: Option<String>,
+    results: Vec<(Check, ::std::result::Result<(), String>)>,
+}
+
+impl ArtifactReport {
+    /// Returns the name of the implementation that produced the
+    /// artifact.
+    pub fn producer(&self) -> &str {
+        &self.producer
+    }
+
+    /// Returns the path to the artifact.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the kind of the artifact, if it could be determined.
+    pub fn kind(&self) -> Option<Kind> {
+        self.kind
+    }
+
+    /// Returns why the artifact is expected to fail, if it is.
+    pub fn expected_failure(&self) -> Option<&str> {
+        self.expected_failure.as_ref().map(|s| s.as_str())
+    }
+
+    /// Returns the checks that have been run and their results.
+    pub fn results(&self) -> &[(Check, ::std::result::Result<(), String>)] {
+        &self.results
+    }
+
+    /// Returns whether all checks passed.
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|&(_, ref r)| r.is_ok())
+    }
+
+    /// Returns whether the outcome differs from the expectation.
+    ///
+    /// This is the case if an artifact that is expected to work
+    /// fails a check, or if an artifact that is expected to fail
+    /// passes all checks.
+    pub fn unexpected(&self) -> bool {
+        self.passed() == self.expected_failure.is_some()
+    }
+
+    fn record<T>(&mut self, check: Check, r: Result<T>) -> Option<T> {
+        match r {
+            Ok(v) => {
+                self.results.push((check, Ok(())));
+                Some(v)
+            },
+            Err(e) => {
+                self.results.push((check, Err(e.to_string())));
+                None
+            },
+        }
+    }
+}
+
+/// The results of running a corpus.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    artifacts: Vec<ArtifactReport>,
+}
+
+impl Report {
+    /// Returns the reports of the individual artifacts.
+    pub fn artifacts(&self) -> &[ArtifactReport] {
+        &self.artifacts
+    }
+
+    /// Returns the artifacts whose outcome differs from the
+    /// expectation.
+    pub fn unexpected(&self) -> Vec<&ArtifactReport> {
+        self.artifacts.iter().filter(|a| a.unexpected()).collect()
+    }
+
+    /// Writes the report as JSON.
+    pub fn serialize<W: Write>(&self, w: &mut W) -> Result<()> {
+        writeln!(w, "{{")?;
+        writeln!(w, "  \"artifacts\": [")?;
+        for (i, a) in self.artifacts.iter().enumerate() {
+            writeln!(w, "    {{")?;
+            writeln!(w, "      \"producer\": {},", json_str(&a.producer))?;
+            writeln!(w, "      \"artifact\": {},",
+                     json_str(&a.path.to_string_lossy()))?;
+            writeln!(w, "      \"kind\": {},",
+                     a.kind.map(|k| json_str(&k.to_string()))
+                     .unwrap_or_else(|| "null".into()))?;
+            writeln!(w, "      \"expected-failure\": {},",
+                     a.expected_failure.as_ref().map(|s| json_str(s))
+                     .unwrap_or_else(|| "null".into()))?;
+            writeln!(w, "      \"unexpected\": {},", a.unexpected())?;
+            writeln!(w, "      \"checks\": [")?;
+            for (j, &(check, ref r)) in a.results.iter().enumerate() {
+                write!(w, "        {{ \"check\": {}, ",
+                       json_str(&check.to_string()))?;
+                match r {
+                    Ok(()) => write!(w, "\"result\": \"pass\" }}")?,
+                    Err(e) => write!(w, "\"result\": \"fail\", \"error\": {} }}",
+                                     json_str(e))?,
+                }
+                writeln!(w, "{}", if j + 1 < a.results.len() { "," } else { "" })?;
+            }
+            writeln!(w, "      ]")?;
+            writeln!(w, "    }}{}",
+                     if i + 1 < self.artifacts.len() { "," } else { "" })?;
+        }
+        writeln!(w, "  ]")?;
+        writeln!(w, "}}")?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Report {
+    /// Summarizes the report in a single line.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let passed = self.artifacts.iter().filter(|a| a.passed()).count();
+        write!(f, "{} artifacts, {} passed, {} unexpected",
+               self.artifacts.len(), passed, self.unexpected().len())
+    }
+}
+
+/// Quotes `s` as JSON string.
+fn json_str(s: &str) -> String {
+    let mut r = String::with_capacity(s.len() + 2);
+    r.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => r.push_str("\\\""),
+            '\\' => r.push_str("\\\\"),
+            '\n' => r.push_str("\\n"),
+            '\r' => r.push_str("\\r"),
+            '\t' => r.push_str("\\t"),
+            c if (c as u32) < 0x20 =>
+                r.push_str(&format!("\\u{:04x}", c as u32)),
+            c => r.push(c),
+        }
+    }
+    r.push('"');
+    r
+}
+
+/// A directory of third-party artifacts.
+///
+/// See the [module-level documentation] for the expected layout.
+///
+///   [module-level documentation]: index.html
+#[derive(Clone, Debug)]
+pub struct Corpus {
+    root: PathBuf,
+}
+
+impl Corpus {
+    /// Returns a corpus rooted at `root`.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Corpus {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Returns the artifacts in the corpus, grouped by producer.
+    fn artifacts(&self) -> Result<Vec<(String, PathBuf)>> {
+        let mut artifacts = Vec::new();
+        for producer in sorted_entries(&self.root)? {
+            if ! producer.is_dir() {
+                continue;
+            }
+            let name = producer.file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            for path in sorted_entries(&producer)? {
+                let is_artifact = path.is_file()
+                    && path.extension().and_then(|e| e.to_str())
+                    .map(|e| ARTIFACT_EXTENSIONS.contains(&e))
+                    .unwrap_or(false);
+                if is_artifact {
+                    artifacts.push((name.clone(), path));
+                }
+            }
+        }
+        Ok(artifacts)
+    }
+
+    /// Runs all checks on all artifacts.
+    ///
+    /// Failing checks are recorded in the report.  This function
+    /// only fails if the corpus cannot be read.
+    pub fn run(&self) -> Result<Report> {
+        let artifacts = self.artifacts()?;
+        let keyring = artifacts.iter()
+            .filter_map(|&(_, ref path)| TPK::from_file(path).ok())
+            .collect::<Vec<_>>();
+
+        let mut report = Report::default();
+        for (producer, path) in artifacts {
+            report.artifacts.push(check(producer, path, &keyring)?);
+        }
+        Ok(report)
+    }
+}
+
+/// Returns the entries of `dir` in a stable order.
+fn sorted_entries(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        entries.push(entry?.path());
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+/// Runs all checks on the given artifact.
+fn check(producer: String, path: PathBuf, keyring: &[TPK])
+         -> Result<ArtifactReport> {
+    let mut xfail = path.clone().into_os_string();
+    xfail.push(".");
+    xfail.push(XFAIL_EXTENSION);
+    let xfail = PathBuf::from(xfail);
+    let expected_failure = if xfail.is_file() {
+        let mut reason = String::new();
+        fs::File::open(&xfail)?.read_to_string(&mut reason)?;
+        Some(reason.trim().to_string())
+    } else {
+        None
+    };
+
+    let mut report = ArtifactReport {
+        producer: producer,
+        path: path,
+        kind: None,
+        expected_failure: expected_failure,
+        results: Vec::new(),
+    };
+
+    let r = parse(&report.path);
+    if report.record(Check::Parse, r).is_none() {
+        return Ok(report);
+    }
+
+    let r = structure(&report.path);
+    let kind = match report.record(Check::Structure, r) {
+        Some(kind) => kind,
+        None => return Ok(report),
+    };
+    report.kind = Some(kind);
+
+    match kind {
+        Kind::SignedMessage => {
+            let r = verify(&report.path, None, keyring);
+            report.record(Check::Verify, r);
+        },
+        Kind::DetachedSignature => {
+            let data = report.path.with_extension("");
+            if data.is_file() {
+                let r = verify(&report.path, Some(&data), keyring);
+                report.record(Check::Verify, r);
+            }
+        },
+        Kind::Key | Kind::EncryptedMessage | Kind::Message => (),
+    }
+
+    Ok(report)
+}
+
+/// Parses all packets, failing on packets that cannot be parsed.
+fn parse(path: &Path) -> Result<()> {
+    let mut ppr = PacketParser::from_file(path)?;
+    while let PacketParserResult::Some(pp) = ppr {
+        if let Packet::Unknown(ref u) = pp.packet {
+            return Err(Error::MalformedPacket(
+                format!("Failed to parse {} packet: {}",
+                        u.tag(), u.error())).into());
+        }
+        ppr = pp.recurse()?.1;
+    }
+    Ok(())
+}
+
+/// Checks the artifact against the TPK or message grammar.
+fn structure(path: &Path) -> Result<Kind> {
+    let pile = PacketPile::from_file(path)?;
+
+    let first = pile.children().next().map(|p| p.tag());
+    if first == Some(Tag::PublicKey) || first == Some(Tag::SecretKey) {
+        TPK::from_packet_pile(pile)?;
+        return Ok(Kind::Key);
+    }
+    if first.is_some() && pile.children().all(|p| p.tag() == Tag::Signature) {
+        return Ok(Kind::DetachedSignature);
+    }
+
+    let encrypted = pile.descendants().any(|p| match p.tag() {
+        Tag::SED | Tag::SEIP | Tag::AED => true,
+        _ => false,
+    });
+    let signed = pile.descendants().any(|p| match p.tag() {
+        Tag::Signature | Tag::OnePassSig => true,
+        _ => false,
+    });
+    Message::from_packet_pile(pile)?;
+
+    Ok(if encrypted {
+        Kind::EncryptedMessage
+    } else if signed {
+        Kind::SignedMessage
+    } else {
+        Kind::Message
+    })
+}
+
+/// Verifies a signed message, or a detached signature over `data`.
+fn verify(path: &Path, data: Option<&Path>, keyring: &[TPK]) -> Result<()> {
+    let helper = Helper { keyring: keyring };
+    let mut v = if let Some(data) = data {
+        DetachedVerifier::from_file(path, data, helper, None)?
+    } else {
+        Verifier::from_file(path, helper, None)?
+    };
+    io::copy(&mut v, &mut io::sink())?;
+    Ok(())
+}
+
+/// Requires all signatures to be good.
+struct Helper<'a> {
+    keyring: &'a [TPK],
+}
+
+impl<'a> VerificationHelper for Helper<'a> {
+    fn get_public_keys(&mut self, _ids: &[KeyID]) -> Result<Vec<TPK>> {
+        Ok(self.keyring.to_vec())
+    }
+
+    fn check(&mut self, structure: &MessageStructure) -> Result<()> {
+        use self::VerificationResult::*;
+        let mut good = 0;
+        for layer in structure.iter() {
+            if let MessageLayer::SignatureGroup { ref results } = layer {
+                for result in results {
+                    match result {
+                        GoodChecksum(..) => good += 1,
+                        MissingKey(sig) =>
+                            return Err(Error::BadSignature(
+                                format!("Missing key {}",
+                                        sig.issuer().map(|i| i.to_string())
+                                        .unwrap_or_else(|| "unknown".into())))
+                                       .into()),
+                        BadChecksum(_) =>
+                            return Err(Error::BadSignature(
+                                "Bad checksum".into()).into()),
+                    }
+                }
+            }
+        }
+
+        if good > 0 {
+            Ok(())
+        } else {
+            Err(Error::BadSignature("No signatures".into()).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corpus() {
+        let corpus = Corpus::new(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/interop"));
+        let report = corpus.run().unwrap();
+        assert!(report.artifacts().len() > 0);
+
+        let mut json = Vec::new();
+        report.serialize(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+
+        assert!(report.unexpected().is_empty(), "{}\n{}", report, json);
+    }
+
+    #[test]
+    fn json_escaping() {
+        assert_eq!(json_str("a\"b\\c\n\u{1}"), "\"a\\\"b\\\\c\\n\\u0001\"");
+    }
+}
@@ -1,14 +1,52 @@
 use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use quickcheck::{Arbitrary, Gen};
 
 use Error;
 use Fingerprint;
+use IdentifierFormat;
 use KeyID;
 use Result;
 
+/// The process-wide identifier format.
+static IDENTIFIER_FORMAT: AtomicUsize =
+    AtomicUsize::new(IdentifierFormat::Full as usize);
+
+impl IdentifierFormat {
+    /// Returns the format currently in use.
+    pub fn get() -> Self {
+        match IDENTIFIER_FORMAT.load(Ordering::Relaxed) {
+            1 => IdentifierFormat::LongKeyID,
+            2 => IdentifierFormat::LegacyShortKeyID,
+            _ => IdentifierFormat::Full,
+        }
+    }
+
+    /// Changes the format used by this process.
+    pub fn set(format: IdentifierFormat) {
+        IDENTIFIER_FORMAT.store(format as usize, Ordering::Relaxed);
+    }
+}
+
+impl Default for IdentifierFormat {
+    fn default() -> Self {
+        IdentifierFormat::Full
+    }
+}
+
 impl fmt::Display for KeyID {
+    /// Formats the key ID according to the [`IdentifierFormat`].
+    ///
+    ///   [`IdentifierFormat`]: enum.IdentifierFormat.html
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        match IdentifierFormat::get() {
+            IdentifierFormat::LegacyShortKeyID => {
+                let raw = self.as_slice();
+                let short = &raw[raw.len().saturating_sub(4)..];
+                write!(f, "{}", to_hex_string(short, true))
+            },
+            _ => write!(f, "{}", self.to_string()),
+        }
     }
 }
 
@@ -128,49 +166,68 @@ impl KeyID {
 
     /// Common code for the above functions.
     fn convert_to_string(&self, pretty: bool) -> String {
-        let raw = match self {
-            &KeyID::V4(ref fp) => &fp[..],
-            &KeyID::Invalid(ref fp) => &fp[..],
-        };
-
-        // We currently only handle V4 key IDs, which look like:
-        //
-        //   AACB 3243 6300 52D9
-        //
-        // Since we have no idea how to format an invalid key ID, just
-        // format it like a V4 fingerprint and hope for the best.
-
-        let mut output = Vec::with_capacity(
-            // Each byte results in to hex characters.
-            raw.len() * 2
-            + if pretty {
-                // Every 2 bytes of output, we insert a space.
-                raw.len() / 2
-            } else { 0 });
-
-        for (i, b) in raw.iter().enumerate() {
-            if pretty && i > 0 && i % 2 == 0 {
-                output.push(' ' as u8);
-            }
-
-            let top = b >> 4;
-            let bottom = b & 0xFu8;
-
-            if top < 10u8 {
-                output.push('0' as u8 + top)
-            } else {
-                output.push('A' as u8 + (top - 10u8))
-            }
-
-            if bottom < 10u8 {
-                output.push('0' as u8 + bottom)
-            } else {
-                output.push('A' as u8 + (bottom - 10u8))
-            }
+        to_hex_string(self.as_slice(), pretty)
+    }
+}
+
+/// Converts a key ID or a fragment of it to a hexadecimal number.
+fn to_hex_string(raw: &[u8], pretty: bool) -> String {
+    // We currently only handle V4 key IDs, which look like:
+    //
+    //   AACB 3243 6300 52D9
+    //
+    // Since we have no idea how to format an invalid key ID, just
+    // format it like a V4 fingerprint and hope for the best.
+
+    let mut output = Vec::with_capacity(
+        // Each byte results in to hex characters.
+        raw.len() * 2
+        + if pretty {
+            // Every 2 bytes of output, we insert a space.
+            raw.len() / 2
+        } else { 0 });
+
+    for (i, b) in raw.iter().enumerate() {
+        if pretty && i > 0 && i % 2 == 0 {
+            output.push(' ' as u8);
+        }
+
+        let top = b >> 4;
+        let bottom = b & 0xFu8;
+
+        if top < 10u8 {
+            output.push('0' as u8 + top)
+        } else {
+            output.push('A' as u8 + (top - 10u8))
         }
 
-        // We know the content is valid UTF-8.
-        String::from_utf8(output).unwrap()
+        if bottom < 10u8 {
+            output.push('0' as u8 + bottom)
+        } else {
+            output.push('A' as u8 + (bottom - 10u8))
+        }
+    }
+
+    // We know the content is valid UTF-8.
+    String::from_utf8(output).unwrap()
+}
+
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for KeyID {
+    fn serialize<S>(&self, s: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: ::serde::Serializer
+    {
+        ::conversions::serde_raw::serialize(self.as_slice(), s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for KeyID {
+    fn deserialize<D>(d: D) -> ::std::result::Result<Self, D::Error>
+        where D: ::serde::Deserializer<'de>
+    {
+        ::conversions::serde_raw::deserialize(d)
+            .map(|raw| KeyID::from_bytes(&raw))
     }
 }
 
@@ -47,6 +47,7 @@ extern crate failure;
 
 extern crate buffered_reader;
 
+extern crate libc;
 extern crate memsec;
 extern crate nettle;
 
@@ -71,6 +72,11 @@ extern crate sequoia_rfc2822 as rfc2822;
 #[macro_use] extern crate lazy_static;
 
 extern crate idna;
+
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
 
 #[macro_use]
 mod macros;
@@ -107,6 +113,7 @@ pub mod armor;
 pub mod autocrypt;
 pub mod conversions;
 pub mod crypto;
+pub mod ownertrust;
 
 pub mod packet;
 use packet::{BodyLength, Header, Container};
@@ -116,6 +123,11 @@ pub mod parse;
 
 pub mod tpk;
 pub mod serialize;
+pub mod policy;
+#[cfg(feature = "interop")]
+pub mod interop;
+#[cfg(fuzzing)]
+pub mod fuzzing;
 
 mod packet_pile;
 pub mod message;
@@ -237,6 +249,14 @@ pub enum Error {
     /// Index out of range.
     #[fail(display = "Index out of range")]
     IndexOutOfRange,
+
+    /// Rejected by the policy.
+    ///
+    /// See the [`policy`] module.
+    ///
+    ///   [`policy`]: policy/index.html
+    #[fail(display = "Policy violation: {}", _0)]
+    PolicyViolation(String),
 }
 
 /// The OpenPGP packets that Sequoia understands.
@@ -449,6 +469,9 @@ pub struct TPK {
     // Signatures that we couldn't find a place for.
     pub(crate) // XXX for TSK::serialize()
     bad: Vec<packet::Signature>,
+
+    // Caches the revocation status and liveness of the components.
+    status_cache: tpk::StatusCache,
 }
 
 /// An OpenPGP message.
@@ -499,6 +522,33 @@ pub enum KeyID {
     Invalid(Box<[u8]>)
 }
 
+/// Selects how fingerprints and key IDs are displayed.
+///
+/// This policy is used by the `Display` implementations of
+/// [`Fingerprint`] and [`KeyID`].  It is process-wide, and can be
+/// changed using [`IdentifierFormat::set`].
+///
+/// Short key IDs, i.e. the lower four bytes of a fingerprint, are
+/// trivial to forge.  They are never emitted by default.
+///
+///   [`Fingerprint`]: enum.Fingerprint.html
+///   [`KeyID`]: enum.KeyID.html
+///   [`IdentifierFormat::set`]: #method.set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentifierFormat {
+    /// Fingerprints are shown in full, key IDs in their long form.
+    ///
+    /// This is the default.
+    Full,
+    /// Fingerprints are shown as long key IDs.
+    LongKeyID,
+    /// Fingerprints and key IDs are shown as short key IDs.
+    ///
+    /// This is a legacy mode, e.g. for matching old logs.  Do not use
+    /// it to identify keys.
+    LegacyShortKeyID,
+}
+
 /// The revocation status.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RevocationStatus<'a> {
@@ -0,0 +1,308 @@
+//! GnuPG's ownertrust format.
+//!
+//! This module deals with the format produced by `gpg
+//! --export-ownertrust` and consumed by `gpg --import-ownertrust`.
+//! It allows users migrating from GnuPG to carry over their
+//! ownertrust assignments, and to hand them back to GnuPG.
+//!
+//! # Format
+//!
+//! The format is line-based.  Lines starting with `#` are comments
+//! and are ignored.  All other lines have the form
+//! `FINGERPRINT:VALUE:`, where `FINGERPRINT` is the key's hex
+//! fingerprint and `VALUE` is the ownertrust encoded as decimal
+//! number.
+//!
+//! # Example
+//!
+//! ```rust
+//! extern crate sequoia_openpgp as openpgp;
+//! use openpgp::ownertrust::{OwnerTrust, OwnerTrustList};
+//!
+//! # fn main() { f().unwrap(); }
+//! # fn f() -> openpgp::Result<()> {
+//! let list = OwnerTrustList::from_bytes(
+//!     b"3E8877C877274692975189F5D03F6F865226FE8B:6:\n")?;
+//! assert_eq!(list.iter().next().unwrap().trust(), OwnerTrust::Ultimate);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::Path;
+
+use Error;
+use Fingerprint;
+use Result;
+
+/// Mask selecting the trust level from an ownertrust value.
+const TRUST_MASK: u8 = 0x0f;
+
+/// Flag indicating that a key has been disabled.
+const TRUST_FLAG_DISABLED: u8 = 0x80;
+
+/// How much a key's owner is trusted to certify other keys.
+///
+/// The values correspond to GnuPG's trust levels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OwnerTrust {
+    /// No ownertrust has been assigned.
+    Unknown,
+    /// The key has expired.
+    Expired,
+    /// The user has explicitly declined to make a statement.
+    Undefined,
+    /// The owner is not trusted.
+    Never,
+    /// The owner is marginally trusted.
+    Marginal,
+    /// The owner is fully trusted.
+    Full,
+    /// The key belongs to the user.
+    Ultimate,
+    /// Unknown trust level.
+    Other(u8),
+}
+
+impl From<u8> for OwnerTrust {
+    fn from(v: u8) -> Self {
+        match v {
+            0 => OwnerTrust::Unknown,
+            1 => OwnerTrust::Expired,
+            2 => OwnerTrust::Undefined,
+            3 => OwnerTrust::Never,
+            4 => OwnerTrust::Marginal,
+            5 => OwnerTrust::Full,
+            6 => OwnerTrust::Ultimate,
+            v => OwnerTrust::Other(v),
+        }
+    }
+}
+
+impl From<OwnerTrust> for u8 {
+    fn from(t: OwnerTrust) -> u8 {
+        match t {
+            OwnerTrust::Unknown => 0,
+            OwnerTrust::Expired => 1,
+            OwnerTrust::Undefined => 2,
+            OwnerTrust::Never => 3,
+            OwnerTrust::Marginal => 4,
+            OwnerTrust::Full => 5,
+            OwnerTrust::Ultimate => 6,
+            OwnerTrust::Other(v) => v,
+        }
+    }
+}
+
+impl fmt::Display for OwnerTrust {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OwnerTrust::Unknown => f.write_str("unknown"),
+            OwnerTrust::Expired => f.write_str("expired"),
+            OwnerTrust::Undefined => f.write_str("undefined"),
+            OwnerTrust::Never => f.write_str("never"),
+            OwnerTrust::Marginal => f.write_str("marginal"),
+            OwnerTrust::Full => f.write_str("full"),
+            OwnerTrust::Ultimate => f.write_str("ultimate"),
+            OwnerTrust::Other(v) => write!(f, "unknown trust level {}", v),
+        }
+    }
+}
+
+/// An ownertrust assignment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnerTrustEntry {
+    fingerprint: Fingerprint,
+    trust: OwnerTrust,
+    disabled: bool,
+}
+
+impl OwnerTrustEntry {
+    /// Returns a new ownertrust assignment.
+    pub fn new(fingerprint: Fingerprint, trust: OwnerTrust, disabled: bool)
+               -> Self {
+        OwnerTrustEntry {
+            fingerprint: fingerprint,
+            trust: trust,
+            disabled: disabled,
+        }
+    }
+
+    /// Returns the fingerprint of the key.
+    pub fn fingerprint(&self) -> &Fingerprint {
+        &self.fingerprint
+    }
+
+    /// Returns the trust assigned to the key's owner.
+    pub fn trust(&self) -> OwnerTrust {
+        self.trust
+    }
+
+    /// Returns whether the key has been disabled.
+    pub fn disabled(&self) -> bool {
+        self.disabled
+    }
+}
+
+/// A list of ownertrust assignments.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OwnerTrustList {
+    entries: Vec<OwnerTrustEntry>,
+}
+
+impl OwnerTrustList {
+    /// Returns an empty list.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns an iterator over the assignments.
+    pub fn iter(&self) -> ::std::slice::Iter<OwnerTrustEntry> {
+        self.entries.iter()
+    }
+
+    /// Returns the assignment for the given key, if any.
+    pub fn get(&self, fingerprint: &Fingerprint) -> Option<&OwnerTrustEntry> {
+        self.entries.iter().find(|e| &e.fingerprint == fingerprint)
+    }
+
+    /// Assigns ownertrust to a key, replacing any prior assignment.
+    pub fn insert(&mut self, entry: OwnerTrustEntry) {
+        match self.entries.iter()
+            .position(|e| e.fingerprint == entry.fingerprint)
+        {
+            Some(i) => self.entries[i] = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    fn from_lines<I: Iterator<Item = io::Result<String>>>(lines: I)
+                                                          -> Result<Self> {
+        let mut list = Self::new();
+
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let malformed = || -> ::failure::Error {
+                Error::MalformedMessage(
+                    format!("Malformed ownertrust in line {}: {:?}",
+                            i + 1, line)).into()
+            };
+
+            let mut fields = line.split(':');
+            let fingerprint = fields.next().ok_or_else(malformed)?;
+            let value = fields.next().ok_or_else(malformed)?;
+            // GnuPG terminates the value with a colon.
+            match (fields.next(), fields.next()) {
+                (None, None) | (Some(""), None) => (),
+                _ => return Err(malformed()),
+            }
+
+            let fingerprint = Fingerprint::from_hex(fingerprint)
+                .map_err(|_| malformed())?;
+            if let Fingerprint::Invalid(_) = fingerprint {
+                return Err(malformed());
+            }
+            let value: u8 = value.parse().map_err(|_| malformed())?;
+
+            list.insert(OwnerTrustEntry::new(
+                fingerprint,
+                OwnerTrust::from(value & TRUST_MASK),
+                value & TRUST_FLAG_DISABLED != 0));
+        }
+
+        Ok(list)
+    }
+
+    /// Parses a list of ownertrust assignments.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        Self::from_reader(io::Cursor::new(data))
+    }
+
+    /// Parses a list of ownertrust assignments.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    /// Parses a list of ownertrust assignments.
+    pub fn from_reader<R: io::Read>(reader: R) -> Result<Self> {
+        Self::from_lines(BufReader::new(reader).lines())
+    }
+
+    /// Writes the list in a form `gpg --import-ownertrust` accepts.
+    pub fn serialize<W: io::Write>(&self, w: &mut W) -> Result<()> {
+        writeln!(w, "# List of assigned trustvalues")?;
+        writeln!(w, "# (Use \"gpg --import-ownertrust\" to restore them)")?;
+        for e in self.entries.iter() {
+            let mut value = u8::from(e.trust);
+            if e.disabled {
+                value |= TRUST_FLAG_DISABLED;
+            }
+            writeln!(w, "{}:{}:", e.fingerprint.to_hex(), value)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> IntoIterator for &'a OwnerTrustList {
+    type Item = &'a OwnerTrustEntry;
+    type IntoIter = ::std::slice::Iter<'a, OwnerTrustEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &[u8] = b"\
+# List of assigned trustvalues, created Mon 01 Apr 2019 12:00:00 PM CEST
+# (Use \"gpg --import-ownertrust\" to restore them)
+3E8877C877274692975189F5D03F6F865226FE8B:6:
+C03FA6411B03AE12576461187223B56678E02528:4:
+1BF7C45C6B5A0B8EB2DB1CA1BBCEA2F1C7EC1EBD:133:
+";
+
+    #[test]
+    fn parse() {
+        let list = OwnerTrustList::from_bytes(EXPORT).unwrap();
+        let entries = list.iter().collect::<Vec<_>>();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].fingerprint().to_hex(),
+                   "3E8877C877274692975189F5D03F6F865226FE8B");
+        assert_eq!(entries[0].trust(), OwnerTrust::Ultimate);
+        assert!(! entries[0].disabled());
+        assert_eq!(entries[1].trust(), OwnerTrust::Marginal);
+        assert_eq!(entries[2].trust(), OwnerTrust::Full);
+        assert!(entries[2].disabled());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let list = OwnerTrustList::from_bytes(EXPORT).unwrap();
+        let mut buf = Vec::new();
+        list.serialize(&mut buf).unwrap();
+        assert_eq!(OwnerTrustList::from_bytes(&buf).unwrap(), list);
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(OwnerTrustList::from_bytes(b"3E8877C8:6:\n").is_err());
+        assert!(OwnerTrustList::from_bytes(
+            b"3E8877C877274692975189F5D03F6F865226FE8B\n").is_err());
+        assert!(OwnerTrustList::from_bytes(
+            b"3E8877C877274692975189F5D03F6F865226FE8B:x:\n").is_err());
+        assert_eq!(OwnerTrustList::from_bytes(b"# Only a comment\n\n")
+                   .unwrap().iter().count(), 0);
+    }
+}
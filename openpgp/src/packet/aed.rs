@@ -11,6 +11,15 @@ use Packet;
 use Error;
 use Result;
 
+/// The largest chunk size we create.
+///
+/// Newer revisions of RFC 4880bis limit the chunk size to 4 MiB.
+/// This is also the default limit for chunks accepted by the
+/// `PacketParser`, see [`PacketParserBuilder::max_aead_chunk_size`].
+///
+///   [`PacketParserBuilder::max_aead_chunk_size`]: ../../parse/struct.PacketParserBuilder.html#method.max_aead_chunk_size
+pub const MAX_CHUNK_SIZE: usize = 1 << 22;
+
 /// Holds an AEAD encrypted data packet.
 ///
 /// An AEAD encrypted data packet is a container.  See [Section 5.16
@@ -84,7 +93,7 @@ impl AED1 {
         self.chunk_size
     }
 
-    /// Gets the chunk size.
+    /// Sets the chunk size.
     pub fn set_chunk_size(&mut self, chunk_size: usize) -> Result<()> {
         if chunk_size.count_ones() != 1 {
             return Err(Error::InvalidArgument(
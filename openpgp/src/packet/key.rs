@@ -9,13 +9,14 @@ use nettle::Hash as NettleHash;
 use nettle::hash::insecure_do_not_use::Sha1;
 
 use Error;
-use crypto::{mpis, Hash, KeyPair, SessionKey};
+use crypto::{mpis, Hash, KeyPair, Keygrip, SessionKey};
 use packet::Tag;
 use packet;
 use Packet;
 use PublicKeyAlgorithm;
 use SymmetricAlgorithm;
 use HashAlgorithm;
+use constants::AEADAlgorithm;
 use constants::Curve;
 use crypto::s2k::S2K;
 use Result;
@@ -143,7 +144,7 @@ impl Key4 {
         use nettle::curve25519::{self, CURVE25519_SIZE};
 
         let mut public_key = [0x40u8; CURVE25519_SIZE + 1];
-        curve25519::mul_g(&mut public_key[1..], private_key).unwrap();
+        curve25519::mul_g(&mut public_key[1..], private_key)?;
 
         let mut private_key = Vec::from(private_key);
         private_key.reverse();
@@ -202,7 +203,7 @@ impl Key4 {
         use nettle::ed25519::{self, ED25519_KEY_SIZE};
 
         let mut public_key = [0x40u8; ED25519_KEY_SIZE + 1];
-        ed25519::public_key(&mut public_key[1..], private_key).unwrap();
+        ed25519::public_key(&mut public_key[1..], private_key)?;
 
         Ok(Key4 {
             common: Default::default(),
@@ -275,10 +276,10 @@ impl Key4 {
 
     /// Generates a new RSA key with a public modulos of size `bits`.
     pub fn generate_rsa(bits: usize) -> Result<Self> {
-        use nettle::{rsa, Yarrow};
+        use nettle::rsa;
         use crypto::mpis::{self, MPI, PublicKey};
 
-        let mut rng = Yarrow::default();
+        let mut rng = ::crypto::random::rng();
         let (public, private) = rsa::generate_keypair(&mut rng, bits as u32)?;
         let (p, q, u) = private.as_rfc4880();
         let public_mpis = PublicKey::RSA {
@@ -313,7 +314,6 @@ impl Key4 {
     /// signing/encryption
     pub fn generate_ecc(for_signing: bool, curve: Curve) -> Result<Self> {
         use nettle::{
-            Yarrow,
             ed25519, ed25519::ED25519_KEY_SIZE,
             curve25519, curve25519::CURVE25519_SIZE,
             ecc, ecdh, ecdsa,
@@ -323,7 +323,7 @@ impl Key4 {
         use PublicKeyAlgorithm::*;
         use Error;
 
-        let mut rng = Yarrow::default();
+        let mut rng = ::crypto::random::rng();
 
         let (mpis, secret, pk_algo) = match (curve.clone(), for_signing) {
             (Curve::Ed25519, true) => {
@@ -472,9 +472,64 @@ impl Key4 {
 
     /// Sets the key packet's creation time field.
     pub fn set_creation_time(&mut self, timestamp: time::Tm) -> time::Tm {
+        self.common.digest = None;
         ::std::mem::replace(&mut self.creation_time, timestamp.canonicalize())
     }
 
+    /// Returns the time at which the key expires according to the
+    /// given binding signature.
+    ///
+    /// The Key Expiration Time subpacket is relative to the key's
+    /// creation time, not to the binding signature's creation time.
+    /// Returns `None` if the key does not expire.
+    ///
+    /// See [Section 5.2.3.6 of RFC 4880].
+    ///
+    ///  [Section 5.2.3.6 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-5.2.3.6
+    pub fn expiration_time(&self, binding: &packet::signature::Signature4)
+                           -> Option<time::Tm> {
+        match binding.key_expiration_time() {
+            Some(e) if e.num_seconds() == 0 =>
+                None, // Zero expiration time, does not expire.
+            Some(e) =>
+                Some(self.creation_time + e),
+            None =>
+                None, // No expiration time, does not expire.
+        }
+    }
+
+    /// Returns whether the key is expired at time `t` according to
+    /// the given binding signature.
+    ///
+    /// If `t` is `None`, the current time is used.
+    ///
+    /// Note: the binding signature has to be the one that is in
+    /// effect at time `t`.  See, for instance,
+    /// [`SubkeyBinding::binding_signature_at`].
+    ///
+    ///   [`SubkeyBinding::binding_signature_at`]: ../tpk/struct.SubkeyBinding.html#method.binding_signature_at
+    pub fn expired_at<T>(&self, binding: &packet::signature::Signature4, t: T)
+                         -> bool
+        where T: Into<Option<time::Tm>>
+    {
+        let t = t.into().unwrap_or_else(time::now_utc);
+        self.expiration_time(binding).map(|e| e <= t).unwrap_or(false)
+    }
+
+    /// Returns whether the key is alive at time `t` according to the
+    /// given binding signature, i.e. the key has been created, but
+    /// has not yet expired.
+    ///
+    /// If `t` is `None`, the current time is used.  This function
+    /// does not check whether the key was revoked.
+    pub fn alive_at<T>(&self, binding: &packet::signature::Signature4, t: T)
+                       -> bool
+        where T: Into<Option<time::Tm>>
+    {
+        let t = t.into().unwrap_or_else(time::now_utc);
+        self.creation_time <= t && ! self.expired_at(binding, t)
+    }
+
     /// Gets the public key algorithm.
     pub fn pk_algo(&self) -> PublicKeyAlgorithm {
         self.pk_algo
@@ -482,6 +537,7 @@ impl Key4 {
 
     /// Sets the public key algorithm.
     pub fn set_pk_algo(&mut self, pk_algo: PublicKeyAlgorithm) -> PublicKeyAlgorithm {
+        self.common.digest = None;
         ::std::mem::replace(&mut self.pk_algo, pk_algo)
     }
 
@@ -492,11 +548,13 @@ impl Key4 {
 
     /// Gets a mutable reference to the key packet's MPIs.
     pub fn mpis_mut(&mut self) -> &mut mpis::PublicKey {
+        self.common.digest = None;
         &mut self.mpis
     }
 
     /// Sets the key packet's MPIs.
     pub fn set_mpis(&mut self, mpis: mpis::PublicKey) -> mpis::PublicKey {
+        self.common.digest = None;
         ::std::mem::replace(&mut self.mpis, mpis)
     }
 
@@ -507,6 +565,7 @@ impl Key4 {
 
     /// Gets a mutable reference to the key packet's SecretKey.
     pub fn secret_mut(&mut self) -> Option<&mut SecretKey> {
+        self.common.digest = None;
         self.secret.as_mut()
     }
 
@@ -516,6 +575,7 @@ impl Key4 {
     pub fn set_secret(&mut self, secret: Option<SecretKey>)
         -> Option<SecretKey>
     {
+        self.common.digest = None;
         mem::replace(&mut self.secret, secret)
     }
 
@@ -537,6 +597,108 @@ impl Key4 {
         self.fingerprint().to_keyid()
     }
 
+    /// Computes and returns the key's keygrip.
+    ///
+    /// Keygrips identify keys independently of the protocol.  GnuPG
+    /// uses them to name the files in gpg-agent's private key store.
+    ///
+    /// Fails for algorithms and curves for which no keygrip is
+    /// defined.
+    pub fn keygrip(&self) -> Result<Keygrip> {
+        self.mpis().keygrip()
+    }
+
+    /// Encrypts the secret key using `password`.
+    ///
+    /// The secret key is protected as described by `protection`.
+    /// When using AEAD, the public key parameters and the packet's
+    /// `tag` are authenticated along with the secret key, hence
+    /// `tag` must match the tag the key is serialized with.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the secret key is missing, or already encrypted.
+    pub fn encrypt_secret(&mut self, tag: Tag, password: &Password,
+                          protection: &Protection)
+                          -> Result<()> {
+        let secret = match self.secret {
+            Some(SecretKey::Unencrypted { ref mpis }) => match protection {
+                &Protection::CFB { ref s2k, algorithm } => {
+                    let esk = SecretKey::encrypt_cfb(mpis, password, s2k,
+                                                     algorithm)?;
+                    SecretKey::Encrypted {
+                        s2k: s2k.clone(),
+                        algorithm: algorithm,
+                        ciphertext: esk,
+                    }
+                },
+                &Protection::AEAD { ref s2k, algorithm, aead } => {
+                    use serialize::Serialize;
+
+                    let ad = self.secret_key_ad(tag)?;
+                    let mut iv = vec![0u8; aead.iv_size()?];
+                    ::crypto::random(&mut iv);
+
+                    let key = s2k.derive_key(password, algorithm.key_size()?)?;
+                    let mut ctx = aead.context(algorithm, &key, &iv)?;
+                    ctx.update(&ad);
+
+                    // No checksum, the secret key is authenticated.
+                    let mut plaintext = Vec::new();
+                    mpis.serialize(&mut plaintext)?;
+                    let mut esk =
+                        vec![0u8; plaintext.len() + aead.digest_size()?];
+                    {
+                        let (ciphertext, digest) =
+                            esk.split_at_mut(plaintext.len());
+                        ctx.encrypt(ciphertext, &plaintext);
+                        ctx.digest(digest);
+                    }
+                    unsafe {
+                        ::memsec::memzero(plaintext.as_mut_ptr(),
+                                          plaintext.len());
+                    }
+
+                    SecretKey::AEADEncrypted {
+                        s2k: s2k.clone(),
+                        algorithm: algorithm,
+                        aead: aead,
+                        iv: iv.into_boxed_slice(),
+                        ciphertext: esk.into_boxed_slice(),
+                        ad: ad.into_boxed_slice(),
+                    }
+                },
+            },
+            Some(_) =>
+                return Err(Error::InvalidOperation(
+                    "Key is already encrypted".into()).into()),
+            None =>
+                return Err(Error::InvalidOperation(
+                    "No secret key".into()).into()),
+        };
+
+        self.set_secret(Some(secret));
+        Ok(())
+    }
+
+    /// Returns the data authenticated along with AEAD-encrypted
+    /// secret keys.
+    ///
+    /// This is the packet tag in new format encoding, followed by
+    /// the public key fields.
+    pub(crate) fn secret_key_ad(&self, tag: Tag) -> Result<Vec<u8>> {
+        let tag = match tag {
+            Tag::PublicKey | Tag::SecretKey => Tag::SecretKey,
+            Tag::PublicSubkey | Tag::SecretSubkey => Tag::SecretSubkey,
+            _ => return Err(Error::InvalidArgument(
+                format!("Expected a key tag, got {:?}", tag)).into()),
+        };
+
+        let mut ad = vec![0xc0 | u8::from(tag)];
+        self.serialize_key(&mut ad, false)?;
+        Ok(ad)
+    }
+
     /// Convert the `Key` struct to a `Packet`.
     pub fn into_packet(self, tag: Tag) -> Result<Packet> {
         match tag {
@@ -562,7 +724,9 @@ impl Key4 {
         use packet::key::SecretKey;
         let secret = match self.set_secret(None) {
             Some(SecretKey::Unencrypted { mpis }) => mpis,
-            Some(SecretKey::Encrypted { .. }) =>
+            Some(SecretKey::Encrypted { .. })
+                | Some(SecretKey::AEADEncrypted { .. })
+                | Some(SecretKey::LegacyEncrypted { .. }) =>
                 return Err(Error::InvalidArgument(
                     "secret key is encrypted".into()).into()),
             None =>
@@ -599,6 +763,87 @@ pub enum SecretKey {
         /// Encrypted MPIs prefixed with the IV.
         ciphertext: Box<[u8]>,
     },
+    /// The secret key is encrypted with a password using AEAD.
+    ///
+    /// This protection mode is defined in RFC4880bis.
+    AEADEncrypted {
+        /// Key derivation mechanism to use.
+        s2k: S2K,
+        /// Symmetric algorithm used for encryption the secret key.
+        algorithm: SymmetricAlgorithm,
+        /// AEAD algorithm used for encryption the secret key.
+        aead: AEADAlgorithm,
+        /// The nonce.
+        iv: Box<[u8]>,
+        /// Encrypted MPIs followed by the authentication tag.
+        ciphertext: Box<[u8]>,
+        /// Data authenticated along with the secret key.
+        ///
+        /// This is the packet tag and the public key fields, see
+        /// `Key4::encrypt_secret`.  It is not serialized.
+        ad: Box<[u8]>,
+    },
+    /// The secret key is encrypted with a password, and protected
+    /// by a 16-bit checksum.
+    ///
+    /// **Warning**: This protection mode is deprecated.  The checksum
+    /// does not protect the secret key against tampering.  Such keys
+    /// are only parsed if the `legacy` feature is enabled, so that
+    /// old keys can be inspected and re-encrypted using
+    /// [`Key4::encrypt_secret`].  Keys using the simple MD5 S2K
+    /// mechanism of PGP 2 can be inspected, but not decrypted.
+    ///
+    ///   [`Key4::encrypt_secret`]: struct.Key4.html#method.encrypt_secret
+    LegacyEncrypted {
+        /// Key derivation mechanism to use.
+        s2k: S2K,
+        /// Symmetric algorithm used for encryption the secret key.
+        algorithm: SymmetricAlgorithm,
+        /// Encrypted MPIs prefixed with the IV.
+        ciphertext: Box<[u8]>,
+    },
+}
+
+/// How secret keys are protected.
+///
+/// See [`Key4::encrypt_secret`].
+///
+///   [`Key4::encrypt_secret`]: struct.Key4.html#method.encrypt_secret
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Protection {
+    /// CFB mode with a SHA-1 checksum.
+    ///
+    /// This is the protection mode recommended by RFC 4880.  It is
+    /// understood by all versions of GnuPG.
+    CFB {
+        /// Key derivation mechanism to use.
+        s2k: S2K,
+        /// Symmetric algorithm to use.
+        algorithm: SymmetricAlgorithm,
+    },
+    /// AEAD.
+    ///
+    /// This protection mode is defined in RFC4880bis.  The public
+    /// key parameters are authenticated along with the secret key.
+    /// Note that GnuPG 2.2 does not understand this mode.
+    AEAD {
+        /// Key derivation mechanism to use.
+        s2k: S2K,
+        /// Symmetric algorithm to use.
+        algorithm: SymmetricAlgorithm,
+        /// AEAD algorithm to use.
+        aead: AEADAlgorithm,
+    },
+}
+
+impl Default for Protection {
+    /// Returns CFB mode with AES256 and an iterated and salted S2K.
+    fn default() -> Self {
+        Protection::CFB {
+            s2k: S2K::default(),
+            algorithm: SymmetricAlgorithm::AES256,
+        }
+    }
 }
 
 impl SecretKey {
@@ -623,7 +868,49 @@ impl SecretKey {
 
                 dec.read_exact(&mut trash)?;
                 mpis::SecretKey::parse_chksumd(pk_algo, &mut dec)
-            }
+            },
+            &SecretKey::LegacyEncrypted { ref s2k, algorithm, ref ciphertext } => {
+                let key = s2k.derive_key(password, algorithm.key_size()?)?;
+                let mut cur = Cursor::new(ciphertext);
+                let mut dec = Decryptor::new(algorithm, &key, cur)?;
+                let mut trash = vec![0u8; algorithm.block_size()?];
+
+                dec.read_exact(&mut trash)?;
+                mpis::SecretKey::parse_legacy_chksumd(pk_algo, &mut dec)
+            },
+            &SecretKey::AEADEncrypted {
+                ref s2k, algorithm, aead, ref iv, ref ciphertext, ref ad,
+            } => {
+                let digest_size = aead.digest_size()?;
+                if ciphertext.len() < digest_size {
+                    return Err(Error::MalformedPacket(
+                        "Encrypted secret key too short".into()).into());
+                }
+                let (ciphertext, their_digest) =
+                    ciphertext.split_at(ciphertext.len() - digest_size);
+
+                let key = s2k.derive_key(password, algorithm.key_size()?)?;
+                let mut ctx = aead.context(algorithm, &key, iv)?;
+                ctx.update(ad);
+
+                let mut plaintext = vec![0u8; ciphertext.len()];
+                ctx.decrypt(&mut plaintext, ciphertext);
+                let mut digest = vec![0u8; digest_size];
+                ctx.digest(&mut digest);
+
+                let result =
+                    if ::crypto::secure_cmp(&digest, their_digest)
+                        != Ordering::Equal
+                    {
+                        Err(Error::ManipulatedMessage.into())
+                    } else {
+                        mpis::SecretKey::parse(pk_algo, &plaintext[..])
+                    };
+                unsafe {
+                    ::memsec::memzero(plaintext.as_mut_ptr(), plaintext.len());
+                }
+                result
+            },
         }
     }
 
@@ -644,35 +931,48 @@ impl SecretKey {
     }
 
     /// Encrypts this secret key using `password`.
+    ///
+    /// This uses the default protection, see [`Protection`].  To
+    /// select the parameters, use [`Key4::encrypt_secret`].
+    ///
+    ///   [`Protection`]: enum.Protection.html
+    ///   [`Key4::encrypt_secret`]: struct.Key4.html#method.encrypt_secret
     pub fn encrypt(&self, password: &Password)
                    -> Result<(S2K, SymmetricAlgorithm, Box<[u8]>)> {
-        use std::io::Write;
-        use crypto::symmetric::Encryptor;
-        use nettle::{Random, Yarrow};
-
         match self {
-            &SecretKey::Encrypted { .. } =>
-                Err(Error::InvalidOperation("Key is already encrypted".into())
-                    .into()),
             &SecretKey::Unencrypted { ref mpis } => {
                 let s2k = S2K::default();
                 let cipher = SymmetricAlgorithm::AES256;
-                let key = s2k.derive_key(password, cipher.key_size()?)?;
+                let esk = Self::encrypt_cfb(mpis, password, &s2k, cipher)?;
+                Ok((s2k, cipher, esk))
+            },
+            _ =>
+                Err(Error::InvalidOperation("Key is already encrypted".into())
+                    .into()),
+        }
+    }
 
-                // Ciphertext is preceded by a random block.
-                let mut trash = vec![0u8; cipher.block_size()?];
-                Yarrow::default().random(&mut trash);
+    /// Encrypts `mpis` in CFB mode with a SHA-1 checksum.
+    fn encrypt_cfb(mpis: &mpis::SecretKey, password: &Password, s2k: &S2K,
+                   cipher: SymmetricAlgorithm)
+                   -> Result<Box<[u8]>> {
+        use std::io::Write;
+        use crypto::symmetric::Encryptor;
 
-                let mut esk = Vec::new();
-                {
-                    let mut encryptor = Encryptor::new(cipher, &key, &mut esk)?;
-                    encryptor.write_all(&trash)?;
-                    mpis.serialize_chksumd(&mut encryptor)?;
-                }
+        let key = s2k.derive_key(password, cipher.key_size()?)?;
 
-                Ok((s2k, cipher, esk.into_boxed_slice()))
-            },
+        // Ciphertext is preceded by a random block.
+        let mut trash = vec![0u8; cipher.block_size()?];
+        ::crypto::random(&mut trash);
+
+        let mut esk = Vec::new();
+        {
+            let mut encryptor = Encryptor::new(cipher, &key, &mut esk)?;
+            encryptor.write_all(&trash)?;
+            mpis.serialize_chksumd(&mut encryptor)?;
         }
+
+        Ok(esk.into_boxed_slice())
     }
 
     /// Encrypts this secret key using `password`.
@@ -691,6 +991,8 @@ impl SecretKey {
     pub fn is_encrypted(&self) -> bool {
         match self {
             &SecretKey::Encrypted { .. } => true,
+            &SecretKey::AEADEncrypted { .. } => true,
+            &SecretKey::LegacyEncrypted { .. } => true,
             &SecretKey::Unencrypted { .. } => false,
         }
     }
@@ -811,8 +1113,7 @@ mod tests {
             let key = Key::from(key);
             let mut keypair = key.clone().into_keypair().unwrap();
             let cipher = SymmetricAlgorithm::AES256;
-            let sk = SessionKey::new(&mut Default::default(),
-                                     cipher.key_size().unwrap());
+            let sk = SessionKey::new(cipher.key_size().unwrap());
 
             let pkesk = PKESK3::for_recipient(cipher, &sk, &key).unwrap();
             let (cipher_, sk_) = pkesk.decrypt(&mut keypair).unwrap();
@@ -850,6 +1151,127 @@ mod tests {
         }
     }
 
+    #[test]
+    fn secret_encryption_protection() {
+        use constants::Curve::*;
+
+        let password = Password::from("foobarbaz");
+        let protections = vec![
+            Protection::CFB {
+                s2k: S2K::default(),
+                algorithm: SymmetricAlgorithm::AES128,
+            },
+            Protection::AEAD {
+                s2k: S2K::default(),
+                algorithm: SymmetricAlgorithm::AES256,
+                aead: AEADAlgorithm::EAX,
+            },
+        ];
+
+        for protection in protections.iter() {
+            let key: Key = Key4::generate_ecc(true, Ed25519).unwrap().into();
+            let mut encrypted_key = key.clone();
+            encrypted_key.encrypt_secret(Tag::SecretKey, &password, protection)
+                .unwrap();
+            assert!(encrypted_key.secret().unwrap().is_encrypted());
+
+            // Roundtrip through the serialization.
+            let mut buf = Vec::new();
+            Packet::SecretKey(encrypted_key.clone()).serialize(&mut buf)
+                .unwrap();
+            let mut parsed = match Packet::from_bytes(&buf).unwrap() {
+                Packet::SecretKey(k) => k,
+                p => panic!("Unexpected packet: {:?}", p),
+            };
+            assert_eq!(parsed, encrypted_key);
+
+            let pk_algo = parsed.pk_algo();
+            parsed.secret_mut().unwrap()
+                .decrypt_in_place(pk_algo, &password).unwrap();
+            assert_eq!(parsed.secret(), key.secret());
+            assert!(encrypted_key.secret().unwrap()
+                    .decrypt(pk_algo, &"wrong".into()).is_err());
+
+            // With AEAD, the packet tag is authenticated.
+            let mut buf = Vec::new();
+            Packet::SecretSubkey(encrypted_key.clone()).serialize(&mut buf)
+                .unwrap();
+            let parsed = match Packet::from_bytes(&buf).unwrap() {
+                Packet::SecretSubkey(k) => k,
+                p => panic!("Unexpected packet: {:?}", p),
+            };
+            let r = parsed.secret().unwrap().decrypt(pk_algo, &password);
+            match protection {
+                Protection::CFB { .. } => assert!(r.is_ok()),
+                Protection::AEAD { .. } => assert!(r.is_err()),
+            }
+        }
+    }
+
+    #[test]
+    fn legacy_checksum() {
+        use std::io::Write;
+        use constants::Curve::*;
+        use crypto::symmetric::Encryptor;
+
+        let password = Password::from("foobarbaz");
+        let mut key: Key = Key4::generate_ecc(true, Ed25519).unwrap().into();
+        let mpis = match key.secret() {
+            Some(SecretKey::Unencrypted { ref mpis }) => mpis.clone(),
+            _ => unreachable!(),
+        };
+
+        // Protect the secret key the way old implementations did.
+        let s2k = S2K::default();
+        let algorithm = SymmetricAlgorithm::AES128;
+        let mut plaintext = Vec::new();
+        mpis.serialize(&mut plaintext).unwrap();
+        let checksum: usize = plaintext.iter().map(|x| *x as usize).sum();
+        let mut ciphertext = Vec::new();
+        {
+            let k = s2k.derive_key(&password, algorithm.key_size().unwrap())
+                .unwrap();
+            let mut encryptor =
+                Encryptor::new(algorithm, &k, &mut ciphertext).unwrap();
+            encryptor.write_all(&vec![0; algorithm.block_size().unwrap()])
+                .unwrap();
+            encryptor.write_all(&plaintext).unwrap();
+            encryptor.write_all(&[(checksum >> 8) as u8, checksum as u8])
+                .unwrap();
+        }
+
+        key.set_secret(Some(SecretKey::LegacyEncrypted {
+            s2k: s2k,
+            algorithm: algorithm,
+            ciphertext: ciphertext.into_boxed_slice(),
+        }));
+        assert!(key.secret().unwrap().is_encrypted());
+
+        // Without the legacy feature, such keys are rejected.
+        let mut buf = Vec::new();
+        Packet::SecretKey(key.clone()).serialize(&mut buf).unwrap();
+        match Packet::from_bytes(&buf).unwrap() {
+            #[cfg(feature = "legacy")]
+            Packet::SecretKey(k) => assert_eq!(k, key),
+            #[cfg(not(feature = "legacy"))]
+            Packet::Unknown(_) => (),
+            p => panic!("Unexpected packet: {:?}", p),
+        }
+
+        // Re-encrypt the key.
+        let pk_algo = key.pk_algo();
+        key.secret_mut().unwrap().decrypt_in_place(pk_algo, &password)
+            .unwrap();
+        assert_eq!(key.secret(),
+                   Some(&SecretKey::Unencrypted { mpis: mpis }));
+        key.encrypt_secret(Tag::SecretKey, &password, &Default::default())
+            .unwrap();
+        match key.secret() {
+            Some(SecretKey::Encrypted { .. }) => (),
+            s => panic!("Unexpected secret: {:?}", s),
+        }
+    }
+
     #[test]
     fn import_cv25519() {
         use crypto::{ecdh, SessionKey};
@@ -5,6 +5,7 @@
 //!   [Section 4 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-4
 
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::ops::{Deref, DerefMut};
 use std::slice;
 use std::vec;
@@ -111,6 +112,86 @@ impl<'a> DerefMut for Packet {
     }
 }
 
+/// Strips secret key material from a packet stream.
+///
+/// Reads OpenPGP packets from `reader`, converts `SecretKey` and
+/// `SecretSubkey` packets to `PublicKey` and `PublicSubkey` packets,
+/// respectively, and writes the packets to `writer`.  All other
+/// packets are copied.  Container packets are not descended into.
+///
+/// The packets are processed one at a time, so this can be used to
+/// filter arbitrarily large keyrings.
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate sequoia_openpgp as openpgp;
+/// # use openpgp::Result;
+/// # use openpgp::tpk::{CipherSuite, TPKBuilder};
+/// # use openpgp::parse::Parse;
+/// # use openpgp::serialize::Serialize;
+/// use openpgp::TPK;
+/// use openpgp::packet;
+///
+/// # fn main() { f().unwrap(); }
+/// # fn f() -> Result<()> {
+/// let (tpk, _) = TPKBuilder::default()
+///     .set_cipher_suite(CipherSuite::Cv25519)
+///     .add_signing_subkey()
+///     .generate()?;
+/// let mut tsk = Vec::new();
+/// tpk.as_tsk().serialize(&mut tsk)?;
+///
+/// let mut public = Vec::new();
+/// packet::strip_secrets(&tsk[..], &mut public)?;
+/// assert!(! TPK::from_bytes(&public)?.is_tsk());
+/// # Ok(())
+/// # }
+/// ```
+pub fn strip_secrets<R>(reader: R, writer: &mut io::Write) -> Result<()>
+    where R: io::Read
+{
+    use parse::{Parse, PacketParser, PacketParserResult};
+    use serialize::Serialize;
+
+    let mut ppr = PacketParser::from_reader(reader)?;
+    while let PacketParserResult::Some(mut pp) = ppr {
+        pp.buffer_unread_content()?;
+        let (packet, next) = pp.next()?;
+        ppr = next;
+
+        match packet {
+            Packet::SecretKey(mut key) => {
+                key.set_secret(None);
+                Packet::PublicKey(key)
+            },
+            Packet::SecretSubkey(mut key) => {
+                key.set_secret(None);
+                Packet::PublicSubkey(key)
+            },
+            packet => packet,
+        }.serialize(writer)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn strip_secrets_test() {
+    use parse::Parse;
+    use serialize::SerializeInto;
+    use TPK;
+
+    let tsk = ::tests::key("testy-private.pgp");
+    let mut public = Vec::new();
+    strip_secrets(tsk, &mut public).unwrap();
+
+    let tpk = TPK::from_bytes(tsk).unwrap();
+    assert!(tpk.is_tsk());
+    let stripped = TPK::from_bytes(&public).unwrap();
+    assert!(! stripped.is_tsk());
+    assert_eq!(stripped, TPK::from_bytes(&tpk.to_vec().unwrap()).unwrap());
+}
+
 /// The size of a packet.
 ///
 /// A packet's size can be expressed in three different ways.  Either
@@ -236,7 +317,7 @@ fn body_length_old_format() {
 }
 
 /// Fields used by multiple packet types.
-#[derive(PartialEq, Eq, Hash, Clone)]
+#[derive(Clone)]
 pub struct Common {
     /// Used by container packets (such as the encryption and
     /// compression packets) to reference their immediate children.
@@ -297,6 +378,28 @@ pub struct Common {
     /// this is not the packet's entire content; it is just the unread
     /// content.
     pub body: Option<Vec<u8>>,
+
+    /// The SHA-256 digest of the packet's body as it was parsed.
+    ///
+    /// This is only computed if the `PacketParser` is configured to
+    /// do so, and it is cleared when the packet is modified.  It is
+    /// not considered when comparing packets.
+    pub(crate) digest: Option<Vec<u8>>,
+}
+
+impl PartialEq for Common {
+    fn eq(&self, other: &Common) -> bool {
+        self.children == other.children && self.body == other.body
+    }
+}
+
+impl Eq for Common {}
+
+impl Hash for Common {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.children.hash(state);
+        self.body.hash(state);
+    }
 }
 
 impl fmt::Debug for Common {
@@ -314,11 +417,25 @@ impl Default for Common {
         Common {
             children: None,
             body: None,
+            digest: None,
         }
     }
 }
 
 impl Common {
+    /// Returns the SHA-256 digest of the packet's body as it was
+    /// parsed.
+    ///
+    /// The digest is only available if it was computed by the
+    /// `PacketParser` (see [`PacketParserBuilder::content_digests`]),
+    /// and the packet has not been modified since.  It allows
+    /// identifying identical packets without serializing them.
+    ///
+    ///   [`PacketParserBuilder::content_digests`]: ../parse/struct.PacketParserBuilder.html#method.content_digests
+    pub fn content_digest(&self) -> Option<&[u8]> {
+        self.digest.as_ref().map(|d| d.as_slice())
+    }
+
     /// Returns an iterator over all of the packet's descendants, in
     /// depth-first order.
     pub fn descendants(&self) -> PacketIter {
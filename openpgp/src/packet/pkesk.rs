@@ -18,7 +18,7 @@ use Result;
 use SymmetricAlgorithm;
 use crypto::SessionKey;
 use crypto::ecdh;
-use nettle::{rsa, Yarrow};
+use nettle::rsa;
 use packet;
 
 /// Holds an asymmetrically encrypted session key.
@@ -60,7 +60,7 @@ impl PKESK3 {
                          session_key: &SessionKey, recipient: &Key)
                          -> Result<PKESK3> {
         use PublicKeyAlgorithm::*;
-        let mut rng = Yarrow::default();
+        let mut rng = ::crypto::random::rng();
 
         // We need to prefix the cipher specifier to the session key,
         // and a two-octet checksum.
@@ -335,7 +335,7 @@ mod tests {
         use constants::Curve;
         use packet::Key;
         use packet::key::Key4;
-        use nettle::{curve25519, Yarrow};
+        use nettle::curve25519;
         use time;
 
         // 20 byte sec key
@@ -365,8 +365,7 @@ mod tests {
         key.set_secret(Some(SecretKey::Unencrypted {
             mpis: private_mpis,
         }));
-        let mut rng = Yarrow::default();
-        let sess_key = SessionKey::new(&mut rng, 32);
+        let sess_key = SessionKey::new(32);
         let pkesk = PKESK3::for_recipient(SymmetricAlgorithm::AES256, &sess_key,
                                           &key).unwrap();
         let mut keypair = key.into_keypair().unwrap();
@@ -0,0 +1,196 @@
+//! Caches signature verification results.
+//!
+//! Some applications verify the same signatures over the same data
+//! over and over, e.g. package managers re-checking artifacts
+//! they have already downloaded.  The public key operation dominates
+//! the cost of a verification, and its outcome only depends on the
+//! key, the signature, and the digest of the signed data.  Hence, if
+//! the cache is enabled, [`Signature4::verify_hash`] and every
+//! function using it remember their results, keyed by these values.
+//!
+//! The cache is shared by all threads of the process.  It is
+//! disabled by default.
+//!
+//! [`Signature4::verify_hash`]: ../struct.Signature4.html#method.verify_hash
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate sequoia_openpgp as openpgp;
+//! # extern crate time;
+//! use openpgp::packet::signature::cache;
+//!
+//! // Remember up to 4096 results for an hour.
+//! cache::enable(4096, time::Duration::hours(1));
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use time;
+
+use constants::HashAlgorithm;
+use packet::Key;
+use packet::signature::Signature4;
+use serialize::SerializeInto;
+use Result;
+
+struct Cache {
+    capacity: usize,
+    ttl: time::Duration,
+    /// Maps digests of the inputs to the result and the time it was
+    /// computed.
+    results: HashMap<Vec<u8>, (bool, time::Timespec)>,
+}
+
+lazy_static! {
+    /// The cache.  `None` if disabled.
+    static ref CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+}
+
+/// Enables the cache.
+///
+/// At most `capacity` results are remembered.  If the cache grows
+/// larger, it is cleared.  Results are forgotten after `ttl`.
+///
+/// If the cache is already enabled, the remembered results are kept.
+pub fn enable(capacity: usize, ttl: time::Duration) {
+    let mut cache = CACHE.lock().unwrap();
+    let results = cache.take().map(|c| c.results).unwrap_or_default();
+    *cache = Some(Cache {
+        capacity: capacity,
+        ttl: ttl,
+        results: results,
+    });
+}
+
+/// Disables the cache, and forgets all results.
+pub fn disable() {
+    *CACHE.lock().unwrap() = None;
+}
+
+/// Forgets all results.
+pub fn clear() {
+    if let Some(ref mut cache) = *CACHE.lock().unwrap() {
+        cache.results.clear();
+    }
+}
+
+/// Returns the number of remembered results.
+pub fn len() -> usize {
+    CACHE.lock().unwrap().as_ref().map(|c| c.results.len()).unwrap_or(0)
+}
+
+/// Returns whether the cache is enabled.
+fn enabled() -> bool {
+    CACHE.lock().unwrap().is_some()
+}
+
+/// Identifies a verification.
+fn cache_key(sig: &Signature4, key: &Key, hash_algo: HashAlgorithm,
+             hash: &[u8])
+             -> Result<Vec<u8>> {
+    let mut ctx = HashAlgorithm::SHA256.context()?;
+    ctx.update(&[u8::from(key.pk_algo()), u8::from(sig.pk_algo()),
+                 u8::from(hash_algo)]);
+    // Length-prefix the variable-length fields, so that they cannot
+    // be confused with each other.
+    for field in &[key.mpis().to_vec()?, sig.mpis().to_vec()?,
+                   hash.to_vec()] {
+        ctx.update(&(field.len() as u64).to_be_bytes());
+        ctx.update(field);
+    }
+    let mut digest = vec![0u8; ctx.digest_size()];
+    ctx.digest(&mut digest);
+    Ok(digest)
+}
+
+/// Verifies `sig` using `f`, consulting the cache.
+///
+/// Only successful verifications are cached, errors are not.
+pub(crate) fn verify<F>(sig: &Signature4, key: &Key, hash_algo: HashAlgorithm,
+                        hash: &[u8], f: F)
+                        -> Result<bool>
+    where F: FnOnce() -> Result<bool>
+{
+    if ! enabled() {
+        return f();
+    }
+
+    let cache_key = cache_key(sig, key, hash_algo, hash)?;
+    let now = time::get_time();
+
+    if let Some(ref mut cache) = *CACHE.lock().unwrap() {
+        match cache.results.get(&cache_key).cloned() {
+            Some((result, t)) if now - t < cache.ttl => return Ok(result),
+            Some(_) => {
+                cache.results.remove(&cache_key);
+            },
+            None => (),
+        }
+    }
+
+    // Do not hold the lock during the public key operation.
+    let result = f()?;
+
+    if let Some(ref mut cache) = *CACHE.lock().unwrap() {
+        if cache.results.len() >= cache.capacity {
+            cache.results.clear();
+        }
+        if cache.capacity > 0 {
+            cache.results.insert(cache_key, (result, now));
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use constants::{Curve, SignatureType};
+    use packet::key::Key4;
+    use packet::signature;
+
+    #[test]
+    fn verify_cached() {
+        let key: Key = Key4::generate_ecc(true, Curve::Ed25519).unwrap().into();
+        let mut keypair = key.clone().into_keypair().unwrap();
+        let msg = b"Hello, world!";
+        let sig = signature::Builder::new(SignatureType::Binary)
+            .sign_message(&mut keypair, HashAlgorithm::SHA512, msg).unwrap();
+        let digest = sig.hash_for_message(msg).unwrap();
+
+        // Tests run in parallel and share the cache.  Make it large
+        // enough so that it is not cleared while we look.
+        enable(1 << 16, time::Duration::hours(1));
+        let mut calls = 0;
+        for _ in 0..2 {
+            assert!(verify(&sig, &key, sig.hash_algo(), &digest, || {
+                calls += 1;
+                Ok(true)
+            }).unwrap());
+        }
+        assert_eq!(calls, 1);
+
+        // Different data is verified again.
+        let mut other = digest.clone();
+        other[0] ^= 1;
+        assert!(! verify(&sig, &key, sig.hash_algo(), &other, || {
+            calls += 1;
+            Ok(false)
+        }).unwrap());
+        assert_eq!(calls, 2);
+
+        // Errors are not cached.
+        let mut third = digest.clone();
+        third[1] ^= 1;
+        for _ in 0..2 {
+            assert!(verify(&sig, &key, sig.hash_algo(), &third, || {
+                calls += 1;
+                Err(::Error::BadSignature("test".into()).into())
+            }).is_err());
+        }
+        assert_eq!(calls, 4);
+
+        disable();
+    }
+}
@@ -1,12 +1,14 @@
 //! Types for signatures.
 
 use std::fmt;
+use std::io;
 use std::ops::Deref;
 
 use constants::Curve;
 use Error;
 use Result;
 use crypto::{
+    self,
     mpis,
     Hash,
     Signer,
@@ -27,6 +29,7 @@ use serialize::SerializeInto;
 use nettle::{self, dsa, ecc, ecdsa, ed25519, rsa};
 use nettle::rsa::verify_digest_pkcs1;
 
+pub mod cache;
 pub mod subpacket;
 
 const TRACE : bool = false;
@@ -120,6 +123,7 @@ impl Builder {
                                     -> Result<Signature> {
         self.pk_algo = signer.public().pk_algo();
         self.hash_algo = algo;
+        self.check_area_sizes()?;
         let digest =
             Signature::primary_key_binding_hash(&self, signer.public())?;
 
@@ -136,6 +140,7 @@ impl Builder {
                                -> Result<Signature> {
         self.pk_algo = signer.public().pk_algo();
         self.hash_algo = algo;
+        self.check_area_sizes()?;
         let digest = Signature::userid_binding_hash(&self, key, userid)?;
 
         self.sign(signer, digest)
@@ -151,6 +156,7 @@ impl Builder {
                                -> Result<Signature> {
         self.pk_algo = signer.public().pk_algo();
         self.hash_algo = algo;
+        self.check_area_sizes()?;
         let digest = Signature::subkey_binding_hash(&self, primary, subkey)?;
 
         self.sign(signer, digest)
@@ -167,6 +173,7 @@ impl Builder {
                                        -> Result<Signature> {
         self.pk_algo = signer.public().pk_algo();
         self.hash_algo = algo;
+        self.check_area_sizes()?;
         let digest =
             Signature::user_attribute_binding_hash(&self, key, ua)?;
 
@@ -184,6 +191,7 @@ impl Builder {
         // Fill out some fields, then hash the packet.
         self.pk_algo = signer.public().pk_algo();
         self.hash_algo = hash_algo;
+        self.check_area_sizes()?;
         self.hash(&mut hash);
 
         // Compute the digest.
@@ -208,6 +216,7 @@ impl Builder {
         // Fill out some fields, then hash the packet.
         self.pk_algo = signer.public().pk_algo();
         self.hash_algo = hash_algo;
+        self.check_area_sizes()?;
         self.hash(&mut hash);
 
         // Compute the digest.
@@ -217,6 +226,151 @@ impl Builder {
         self.sign(signer, digest)
     }
 
+    /// Signs the data read from `reader` using `signer`.
+    ///
+    /// Unlike `sign_message`, the data is hashed a chunk at a time,
+    /// so arbitrarily large messages can be signed using a constant
+    /// amount of memory.
+    ///
+    /// The Signature's public-key algorithm field is set to the
+    /// algorithm used by `signer`, the hash-algorithm field is set to
+    /// `hash_algo`.
+    pub fn sign_reader<R: io::Read + ?Sized>(self, signer: &mut Signer,
+                                             hash_algo: HashAlgorithm,
+                                             reader: &mut R)
+                                             -> Result<Signature> {
+        let mut hash = hash_algo.context()?;
+        crypto::hash_reader(reader, &mut hash)?;
+        self.sign_hash(signer, hash_algo, hash)
+    }
+
+    /// Computes the digest to be signed over `hash`.
+    ///
+    /// This is the first half of creating a signature using an
+    /// external signer, e.g. an HSM, an air-gapped machine, or a
+    /// group of parties computing a threshold signature.  The
+    /// Signature's public-key algorithm field is set to `pk_algo`,
+    /// the hash-algorithm field is set to `hash_algo`.
+    ///
+    /// Returns the builder, which must not be modified afterwards,
+    /// and the digest.  The digest must be signed using the private
+    /// key, and the resulting signature be passed to
+    /// `finish_external`.  Alternatively, if a `Signer` is at hand,
+    /// `sign_prehashed` can be used.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use sequoia_openpgp::{*, packet::signature::*, crypto::*};
+    /// # use sequoia_openpgp::tpk::{TPKBuilder, CipherSuite};
+    /// # f().unwrap();
+    /// # fn f() -> Result<()> {
+    /// # let (tpk, _) = TPKBuilder::new()
+    /// #     .set_cipher_suite(CipherSuite::Cv25519)
+    /// #     .generate()?;
+    /// # let mut keypair = tpk.primary().clone().into_keypair()?;
+    /// let msg = b"Hello, World";
+    /// let hash_algo = HashAlgorithm::SHA512;
+    /// let mut hash = hash_algo.context()?;
+    /// hash.update(msg);
+    ///
+    /// let (builder, digest) = Builder::new(SignatureType::Binary)
+    ///     .prehash(tpk.primary().pk_algo(), hash_algo, hash)?;
+    ///
+    /// // Sign the digest elsewhere.
+    /// let mpis = keypair.sign(hash_algo, &digest)?;
+    ///
+    /// let sig = builder.finish_external(&digest, mpis)?;
+    /// assert!(sig.verify_message(tpk.primary(), msg)?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn prehash(mut self, pk_algo: PublicKeyAlgorithm,
+                   hash_algo: HashAlgorithm, mut hash: Box<nettle::Hash>)
+                   -> Result<(Self, Vec<u8>)> {
+        self.pk_algo = pk_algo;
+        self.hash_algo = hash_algo;
+        self.check_area_sizes()?;
+        self.hash(&mut hash);
+
+        let mut digest = vec![0u8; hash.digest_size()];
+        hash.digest(&mut digest);
+        Ok((self, digest))
+    }
+
+    /// Signs `digest` computed using `prehash` using `signer`.
+    ///
+    /// Fails if `signer` does not use the public-key algorithm given
+    /// to `prehash`.
+    pub fn sign_prehashed(self, signer: &mut Signer, digest: Vec<u8>)
+                          -> Result<Signature> {
+        if signer.public().pk_algo() != self.pk_algo {
+            return Err(Error::InvalidArgument(
+                format!("Signer uses {}, but the digest was computed for {}",
+                        signer.public().pk_algo(), self.pk_algo)).into());
+        }
+        self.check_digest(&digest)?;
+
+        self.sign(signer, digest)
+    }
+
+    /// Creates a signature from an externally computed signature
+    /// over `digest`.
+    ///
+    /// `digest` must have been computed using `prehash`, and `mpis`
+    /// must be a signature over `digest`.  This is not checked, use
+    /// `Signature::verify_hash` to make sure that the signature is
+    /// valid.
+    pub fn finish_external(self, digest: &[u8], mpis: mpis::Signature)
+                           -> Result<Signature> {
+        self.check_digest(digest)?;
+
+        Ok(Signature4 {
+            common: Default::default(),
+            hash_prefix: [digest[0], digest[1]],
+            computed_hash: Some((self.hash_algo, digest.to_vec())),
+            fields: self,
+            mpis: mpis,
+            level: 0,
+        }.into())
+    }
+
+    // Makes sure that `digest` plausibly was computed using
+    // `prehash`.
+    fn check_digest(&self, digest: &[u8]) -> Result<()> {
+        let size = self.hash_algo.context()?.digest_size();
+        if digest.len() != size {
+            return Err(Error::InvalidArgument(
+                format!("Expected a {} byte {} digest, got {} bytes",
+                        size, self.hash_algo, digest.len())).into());
+        }
+        Ok(())
+    }
+
+    /// Returns the combined size of the hashed and unhashed
+    /// subpacket areas.
+    pub fn subpacket_areas_len(&self) -> usize {
+        self.hashed_area.serialized_len()
+            + self.unhashed_area.serialized_len()
+    }
+
+    // Makes sure that both subpacket areas fit into their two-octet
+    // length fields.  Otherwise, we would hash a truncated length
+    // and emit a signature that nobody can parse.
+    fn check_area_sizes(&self) -> Result<()> {
+        for (name, area) in &[("Hashed", &self.hashed_area),
+                              ("Unhashed", &self.unhashed_area)] {
+            if area.serialized_len() > SubpacketArea::MAX_SIZE {
+                return Err(Error::InvalidArgument(
+                    format!("{} subpacket area too large: \
+                             {} bytes exceeds the limit of {} bytes",
+                            name, area.serialized_len(),
+                            SubpacketArea::MAX_SIZE)).into());
+            }
+        }
+        Ok(())
+    }
+
     fn sign(self, signer: &mut Signer, digest: Vec<u8>) -> Result<Signature> {
         let algo = self.hash_algo;
         let mpis = signer.sign(algo, &digest)?;
@@ -389,6 +543,7 @@ impl Signature4 {
 
     /// Gets a mutable reference to the unhashed area.
     pub fn unhashed_area_mut(&mut self) -> &mut SubpacketArea {
+        self.common.digest = None;
         &mut self.fields.unhashed_area
     }
 
@@ -399,6 +554,7 @@ impl Signature4 {
 
     /// Sets the hash prefix.
     pub fn set_hash_prefix(&mut self, prefix: [u8; 2]) -> [u8; 2] {
+        self.common.digest = None;
         ::std::mem::replace(&mut self.hash_prefix, prefix)
     }
 
@@ -409,9 +565,31 @@ impl Signature4 {
 
     /// Sets the signature packet's MPIs.
     pub fn set_mpis(&mut self, mpis: mpis::Signature) -> mpis::Signature {
+        self.common.digest = None;
         ::std::mem::replace(&mut self.mpis, mpis)
     }
 
+    /// Returns the SHA-256 digest of the signature packet's body.
+    ///
+    /// If the signature was parsed with content digests enabled (see
+    /// [`PacketParserBuilder::content_digests`]), the digest computed
+    /// by the parser is returned.  Otherwise, the signature is
+    /// serialized and hashed.  Identical signatures have the same
+    /// digest.
+    ///
+    ///   [`PacketParserBuilder::content_digests`]: ../../parse/struct.PacketParserBuilder.html#method.content_digests
+    pub fn content_digest(&self) -> Result<Vec<u8>> {
+        if let Some(digest) = self.common.content_digest() {
+            return Ok(digest.to_vec());
+        }
+
+        let mut hash = HashAlgorithm::SHA256.context()?;
+        hash.update(&self.to_vec()?);
+        let mut digest = vec![0; hash.digest_size()];
+        hash.digest(&mut digest);
+        Ok(digest)
+    }
+
     /// Gets the computed hash value.
     pub fn computed_hash(&self) -> Option<&(HashAlgorithm, Vec<u8>)> {
         self.computed_hash.as_ref()
@@ -443,9 +621,14 @@ impl Signature4 {
     }
 
     /// Gets the issuer.
+    ///
+    /// If there is no Issuer subpacket, the Key ID is derived from
+    /// the Issuer Fingerprint subpacket.
     pub fn get_issuer(&self) -> Option<KeyID> {
         if let Some(id) = self.issuer() {
             Some(id)
+        } else if let Some(fp) = self.issuer_fingerprint() {
+            Some(fp.to_keyid())
         } else {
             None
         }
@@ -462,8 +645,22 @@ impl Signature4 {
     /// is not revoked, not expired, has a valid self-signature, has a
     /// subkey binding signature (if appropriate), has the signing
     /// capability, etc.
+    ///
+    /// If the [verification cache] is enabled, the result may be
+    /// taken from it.
+    ///
+    ///   [verification cache]: cache/index.html
     pub fn verify_hash(&self, key: &Key, hash_algo: HashAlgorithm, hash: &[u8])
         -> Result<bool>
+    {
+        cache::verify(self, key, hash_algo, hash,
+                      || self.verify_hash_uncached(key, hash_algo, hash))
+    }
+
+    /// Verifies the signature against `hash`, bypassing the cache.
+    fn verify_hash_uncached(&self, key: &Key, hash_algo: HashAlgorithm,
+                            hash: &[u8])
+        -> Result<bool>
     {
         use PublicKeyAlgorithm::*;
         use crypto::mpis::PublicKey;
@@ -591,6 +788,130 @@ impl Signature4 {
         }
     }
 
+    /// Returns the digest of `msg` that this signature is over.
+    ///
+    /// This is the digest that `verify_message` verifies.  It can be
+    /// used to compare digests, or to hand them to an external
+    /// system, without doing any public key operation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # use openpgp::Result;
+    /// # use openpgp::constants::{HashAlgorithm, SignatureType};
+    /// # use openpgp::packet::signature;
+    /// # use openpgp::tpk::TPKBuilder;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// let (tpk, _) = TPKBuilder::new().generate()?;
+    /// let mut signer = tpk.primary().clone().into_keypair()?;
+    ///
+    /// let msg = b"Hello, world!";
+    /// let sig = signature::Builder::new(SignatureType::Binary)
+    ///     .sign_message(&mut signer, HashAlgorithm::SHA512, msg)?;
+    ///
+    /// let digest = sig.hash_for_message(msg)?;
+    /// assert_eq!(&digest[..2], sig.hash_prefix());
+    /// assert!(sig.verify_hash(tpk.primary(), sig.hash_algo(), &digest)?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn hash_for_message(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        self.hash_for_reader(&mut io::Cursor::new(msg))
+    }
+
+    /// Returns the digest of the data read from `reader` that this
+    /// signature is over.
+    ///
+    /// Like `hash_for_message`, but the data is hashed a chunk at a
+    /// time.
+    pub fn hash_for_reader<R: io::Read + ?Sized>(&self, reader: &mut R)
+                                                 -> Result<Vec<u8>>
+    {
+        if self.sigtype() != SignatureType::Binary &&
+            self.sigtype() != SignatureType::Text {
+            return Err(Error::UnsupportedSignatureType(self.sigtype()).into());
+        }
+
+        let mut hash = self.hash_algo().context()?;
+        let mut digest = vec![0u8; hash.digest_size()];
+
+        crypto::hash_reader(reader, &mut hash)?;
+        self.hash(&mut hash);
+        hash.digest(&mut digest);
+        Ok(digest)
+    }
+
+    /// Returns the digest over the primary key `pk` and, for subkey
+    /// bindings and revocations, the subkey `subkey` that this
+    /// signature is over.
+    ///
+    /// This is the digest that `verify_primary_key_binding`,
+    /// `verify_primary_key_revocation`, `verify_subkey_binding`, and
+    /// `verify_subkey_revocation` verify.
+    pub fn hash_for_key(&self, pk: &Key, subkey: Option<&Key>)
+                        -> Result<Vec<u8>>
+    {
+        match (self.sigtype(), subkey) {
+            (SignatureType::DirectKey, None)
+                | (SignatureType::KeyRevocation, None) =>
+                Signature::primary_key_binding_hash(self, pk),
+            (SignatureType::SubkeyBinding, Some(subkey))
+                | (SignatureType::SubkeyRevocation, Some(subkey))
+                | (SignatureType::PrimaryKeyBinding, Some(subkey)) =>
+                Signature::subkey_binding_hash(self, pk, subkey),
+            (SignatureType::SubkeyBinding, None)
+                | (SignatureType::SubkeyRevocation, None)
+                | (SignatureType::PrimaryKeyBinding, None) =>
+                Err(Error::InvalidArgument(
+                    format!("{} requires a subkey", self.sigtype())).into()),
+            (SignatureType::DirectKey, Some(_))
+                | (SignatureType::KeyRevocation, Some(_)) =>
+                Err(Error::InvalidArgument(
+                    format!("{} is not over a subkey", self.sigtype())).into()),
+            (t, _) => Err(Error::UnsupportedSignatureType(t).into()),
+        }
+    }
+
+    /// Returns the digest over the primary key `pk` and `userid` that
+    /// this certification or revocation is over.
+    ///
+    /// This is the digest that `verify_userid_binding` and
+    /// `verify_userid_revocation` verify.
+    pub fn hash_for_userid(&self, pk: &Key, userid: &UserID)
+                           -> Result<Vec<u8>>
+    {
+        if !(self.sigtype() == SignatureType::GenericCertificate
+             || self.sigtype() == SignatureType::PersonaCertificate
+             || self.sigtype() == SignatureType::CasualCertificate
+             || self.sigtype() == SignatureType::PositiveCertificate
+             || self.sigtype() == SignatureType::CertificateRevocation) {
+            return Err(Error::UnsupportedSignatureType(self.sigtype()).into());
+        }
+
+        Signature::userid_binding_hash(self, pk, userid)
+    }
+
+    /// Returns the digest over the primary key `pk` and `ua` that
+    /// this certification or revocation is over.
+    ///
+    /// This is the digest that `verify_user_attribute_binding` and
+    /// `verify_user_attribute_revocation` verify.
+    pub fn hash_for_user_attribute(&self, pk: &Key, ua: &UserAttribute)
+                                   -> Result<Vec<u8>>
+    {
+        if !(self.sigtype() == SignatureType::GenericCertificate
+             || self.sigtype() == SignatureType::PersonaCertificate
+             || self.sigtype() == SignatureType::CasualCertificate
+             || self.sigtype() == SignatureType::PositiveCertificate
+             || self.sigtype() == SignatureType::CertificateRevocation) {
+            return Err(Error::UnsupportedSignatureType(self.sigtype()).into());
+        }
+
+        Signature::user_attribute_binding_hash(self, pk, ua)
+    }
+
     /// Verifies the primary key binding.
     ///
     /// `self` is the primary key binding signature, `signer` is the
@@ -898,8 +1219,37 @@ impl Signature4 {
     pub fn verify_message(&self, signer: &Key, msg: &[u8])
         -> Result<bool>
     {
-        if self.sigtype() != SignatureType::Binary &&
-            self.sigtype() != SignatureType::Text {
+        let digest = self.hash_for_message(msg)?;
+        self.verify_hash(signer, self.hash_algo(), &digest[..])
+    }
+
+    /// Verifies a signature of the data read from `reader`.
+    ///
+    /// Like `verify_message`, but the data is hashed a chunk at a
+    /// time, so arbitrarily large messages can be verified using a
+    /// constant amount of memory.  The same caveats apply.
+    pub fn verify_reader<R: io::Read + ?Sized>(&self, signer: &Key,
+                                               reader: &mut R)
+                                               -> Result<bool>
+    {
+        let digest = self.hash_for_reader(reader)?;
+        self.verify_hash(signer, self.hash_algo(), &digest[..])
+    }
+
+    /// Verifies a timestamp signature over `document_digest`.
+    ///
+    /// Notaries issue timestamp signatures over the digest of a
+    /// document.  The digest is computed using the signature's hash
+    /// algorithm, and is signed as if it were a binary message.
+    ///
+    /// The same caveats as for `verify_message` apply.  In
+    /// particular, the signature's creation time is the timestamp,
+    /// and it is up to the caller to decide whether to trust the
+    /// notary.
+    pub fn verify_timestamp(&self, signer: &Key, document_digest: &[u8])
+                            -> Result<bool>
+    {
+        if self.sigtype() != SignatureType::Timestamp {
             return Err(Error::UnsupportedSignatureType(self.sigtype()).into());
         }
 
@@ -907,7 +1257,7 @@ impl Signature4 {
         let mut hash = self.hash_algo().context()?;
         let mut digest = vec![0u8; hash.digest_size()];
 
-        hash.update(msg);
+        hash.update(document_digest);
         self.hash(&mut hash);
         hash.digest(&mut digest);
 
@@ -930,7 +1280,6 @@ impl From<Signature4> for super::Signature {
 
 #[cfg(test)]
 mod test {
-    use nettle::{Random, Yarrow};
     use super::*;
     use crypto::mpis::MPI;
     use TPK;
@@ -1080,7 +1429,7 @@ mod test {
     fn sign_verify() {
         let hash_algo = HashAlgorithm::SHA512;
         let mut hash = vec![0; hash_algo.context().unwrap().digest_size()];
-        Yarrow::default().random(&mut hash);
+        ::crypto::random(&mut hash);
 
         for key in &[
             "testy-private.pgp",
@@ -1131,6 +1480,142 @@ mod test {
         assert!(sig.verify_message(pair.public(), msg).unwrap());
     }
 
+    #[test]
+    fn unhashed_contradictions() {
+        use time;
+        use constants::Curve;
+        use packet::signature::subpacket::{Subpacket, SubpacketValue};
+
+        let key: Key = Key4::generate_ecc(true, Curve::Ed25519)
+            .unwrap().into();
+        let msg = b"Hello, World";
+        let mut pair = key.into_keypair().unwrap();
+        let mut sig = Builder::new(SignatureType::Binary)
+            .set_signature_creation_time(time::now()).unwrap()
+            .set_issuer_fingerprint(pair.public().fingerprint()).unwrap()
+            .sign_message(&mut pair, HashAlgorithm::SHA512, msg).unwrap();
+        assert!(! sig.has_unhashed_contradictions());
+
+        // A matching issuer is fine.
+        sig.unhashed_area_mut().add(Subpacket::new(
+            SubpacketValue::Issuer(pair.public().keyid()), false)
+                                    .unwrap()).unwrap();
+        assert!(! sig.has_unhashed_contradictions());
+        assert_eq!(sig.get_issuer(), Some(pair.public().keyid()));
+
+        // Add one that contradicts the issuer fingerprint.
+        let bogus = KeyID::from_hex("AAAA BBBB CCCC DDDD").unwrap();
+        sig.unhashed_area_mut().add(Subpacket::new(
+            SubpacketValue::Issuer(bogus.clone()), false)
+                                    .unwrap()).unwrap();
+        assert!(sig.has_unhashed_contradictions());
+        assert_eq!(sig.get_issuer(), Some(bogus.clone()));
+        assert_eq!(sig.unhashed_contradictions().len(), 1);
+        assert_eq!(sig.unhashed_contradictions()[0].value,
+                   SubpacketValue::Issuer(bogus));
+
+        assert_eq!(sig.strip_unhashed_contradictions(), 1);
+        assert!(! sig.has_unhashed_contradictions());
+        assert_eq!(sig.get_issuer(), Some(pair.public().keyid()));
+        assert!(sig.verify_message(pair.public(), msg).unwrap());
+    }
+
+    #[test]
+    fn sign_reader() {
+        use std::io::{Cursor, Read};
+        use constants::Curve;
+
+        let key: Key = Key4::generate_ecc(true, Curve::Ed25519)
+            .unwrap().into();
+        let mut pair = key.into_keypair().unwrap();
+        let len = 1 << 20;
+        let sig = Builder::new(SignatureType::Binary)
+            .sign_reader(&mut pair, HashAlgorithm::SHA256,
+                         &mut io::repeat(0x23).take(len)).unwrap();
+
+        assert!(sig.verify_reader(pair.public(),
+                                  &mut io::repeat(0x23).take(len)).unwrap());
+        assert!(sig.verify_message(pair.public(),
+                                   &vec![0x23u8; len as usize]).unwrap());
+        assert!(! sig.verify_reader(pair.public(),
+                                    &mut io::repeat(0x23).take(len - 1))
+                .unwrap());
+
+        let msg = ::tests::manifesto();
+        let sig = Builder::new(SignatureType::Binary)
+            .sign_message(&mut pair, HashAlgorithm::SHA512, msg).unwrap();
+        assert!(sig.verify_reader(pair.public(), &mut Cursor::new(msg))
+                .unwrap());
+    }
+
+    #[test]
+    fn verify_timestamp() {
+        use constants::Curve;
+
+        let key: Key = Key4::generate_ecc(true, Curve::Ed25519)
+            .unwrap().into();
+        let mut pair = key.into_keypair().unwrap();
+
+        let mut hash = HashAlgorithm::SHA256.context().unwrap();
+        hash.update(::tests::manifesto());
+        let mut digest = vec![0u8; hash.digest_size()];
+        hash.digest(&mut digest);
+
+        let sig = Builder::new(SignatureType::Timestamp)
+            .sign_message(&mut pair, HashAlgorithm::SHA256, &digest)
+            .unwrap();
+        assert!(sig.verify_timestamp(pair.public(), &digest).unwrap());
+        digest[0] ^= 1;
+        assert!(! sig.verify_timestamp(pair.public(), &digest).unwrap());
+
+        // Only timestamp signatures are accepted.
+        let sig = Builder::new(SignatureType::Binary)
+            .sign_message(&mut pair, HashAlgorithm::SHA256, &digest)
+            .unwrap();
+        assert!(sig.verify_timestamp(pair.public(), &digest).is_err());
+    }
+
+    #[test]
+    fn sign_external() {
+        use constants::Curve;
+
+        let key: Key = Key4::generate_ecc(true, Curve::Ed25519)
+            .unwrap().into();
+        let msg = b"Hello, World";
+        let mut pair = key.into_keypair().unwrap();
+        let hash_algo = HashAlgorithm::SHA512;
+        let prehash = || {
+            let mut hash = hash_algo.context().unwrap();
+            hash.update(msg);
+            Builder::new(SignatureType::Binary)
+                .prehash(PublicKeyAlgorithm::EdDSA, hash_algo, hash).unwrap()
+        };
+
+        let (builder, digest) = prehash();
+        let sig = builder.sign_prehashed(&mut pair, digest).unwrap();
+        assert!(sig.verify_message(pair.public(), msg).unwrap());
+
+        let (builder, digest) = prehash();
+        let mpis = pair.sign(hash_algo, &digest).unwrap();
+        let sig = builder.finish_external(&digest, mpis).unwrap();
+        assert!(sig.verify_message(pair.public(), msg).unwrap());
+
+        // Digests of the wrong size are rejected.
+        let (builder, digest) = prehash();
+        assert!(builder.clone().sign_prehashed(&mut pair, digest[1..].to_vec())
+                .is_err());
+        let mpis = pair.sign(hash_algo, &digest).unwrap();
+        assert!(builder.finish_external(&digest[1..], mpis).is_err());
+
+        // The signer must use the algorithm the digest was computed
+        // for.
+        let mut hash = hash_algo.context().unwrap();
+        hash.update(msg);
+        let (builder, digest) = Builder::new(SignatureType::Binary)
+            .prehash(PublicKeyAlgorithm::ECDSA, hash_algo, hash).unwrap();
+        assert!(builder.sign_prehashed(&mut pair, digest).is_err());
+    }
+
     #[test]
     fn verify_message() {
         let tpk = TPK::from_bytes(::tests::key(
@@ -1148,6 +1633,33 @@ mod test {
         assert!(sig.verify_message(tpk.primary(), &msg[..]).unwrap());
     }
 
+    #[test]
+    fn hash_for() {
+        let tpk = TPK::from_bytes(::tests::key("neal.pgp")).unwrap();
+        let pk = tpk.primary();
+
+        for binding in tpk.userids() {
+            for sig in binding.selfsigs() {
+                let digest = sig.hash_for_userid(pk, binding.userid()).unwrap();
+                assert_eq!(&digest[..2], sig.hash_prefix());
+                assert!(sig.verify_hash(pk, sig.hash_algo(), &digest).unwrap());
+                // Not a key binding.
+                assert!(sig.hash_for_key(pk, None).is_err());
+            }
+        }
+
+        for binding in tpk.subkeys() {
+            for sig in binding.selfsigs() {
+                let digest =
+                    sig.hash_for_key(pk, Some(binding.subkey())).unwrap();
+                assert_eq!(&digest[..2], sig.hash_prefix());
+                assert!(sig.verify_hash(pk, sig.hash_algo(), &digest).unwrap());
+                // A subkey binding requires the subkey.
+                assert!(sig.hash_for_key(pk, None).is_err());
+            }
+        }
+    }
+
     #[test]
     fn sign_with_short_ed25519_secret_key() {
         use packet::key::SecretKey;
@@ -1188,6 +1700,46 @@ mod test {
             .sign_hash(&mut pair, HashAlgorithm::SHA256, hash).unwrap();
     }
 
+    #[test]
+    fn oversized_subpacket_area() {
+        use packet::signature::subpacket::{
+            NotationData, Subpacket, SubpacketValue};
+        use constants::Curve;
+
+        let key: Key = Key4::generate_ecc(true, Curve::Ed25519)
+            .unwrap().into();
+        let mut pair = key.into_keypair().unwrap();
+        let msg = b"Hello, World";
+
+        // Adding subpackets is refused once the area is full.
+        let mut sig = Builder::new(SignatureType::Binary);
+        let value = vec![0u8; 1024];
+        while sig.hashed_area().space_left() > 1100 {
+            sig.hashed_area_mut().add(Subpacket::new(
+                SubpacketValue::NotationData(
+                    NotationData::new("a@example.org", &value[..], None)),
+                false).unwrap())
+                .unwrap();
+        }
+        assert!(sig.hashed_area_mut().add(Subpacket::new(
+            SubpacketValue::PolicyURI(&vec![0u8; 2048][..]), false).unwrap())
+                .is_err());
+        assert!(sig.hashed_area().serialized_len()
+                <= SubpacketArea::MAX_SIZE);
+        assert_eq!(sig.subpacket_areas_len(),
+                   sig.hashed_area().serialized_len());
+        sig.clone().sign_message(&mut pair, HashAlgorithm::SHA256, msg)
+            .unwrap();
+
+        // Manipulating the raw data is caught when signing.
+        sig.hashed_area_mut().data.extend_from_slice(&value[..]);
+        assert!(sig.hashed_area().serialized_len()
+                > SubpacketArea::MAX_SIZE);
+        assert_eq!(sig.hashed_area().space_left(), 0);
+        assert!(sig.sign_message(&mut pair, HashAlgorithm::SHA256, msg)
+                .is_err());
+    }
+
     #[test]
     fn verify_gpg_3rd_party_cert() {
         use TPK;
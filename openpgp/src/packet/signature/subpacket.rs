@@ -54,14 +54,18 @@
 //! # }
 //! ```
 
+use std::borrow::Cow;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::iter::FromIterator;
 use std::fmt;
 use std::io;
+use std::str;
+use std::sync::RwLock;
 use time;
 
+use failure::ResultExt;
 use quickcheck::{Arbitrary, Gen};
 
 use buffered_reader::BufferedReader;
@@ -448,6 +452,13 @@ impl<'a> FromIterator<(usize, usize, Subpacket<'a>)> for SubpacketArea {
 }
 
 impl SubpacketArea {
+    /// The maximum size of a subpacket area.
+    ///
+    /// The size of each subpacket area is stored in a two-octet
+    /// field, hence neither the hashed nor the unhashed area may
+    /// exceed 64 KB.
+    pub const MAX_SIZE: usize = ::std::u16::MAX as usize;
+
     /// Returns a new subpacket area based on `data`.
     pub fn new(data: Vec<u8>) -> SubpacketArea {
         SubpacketArea { data: data, parsed: RefCell::new(None) }
@@ -508,9 +519,12 @@ impl SubpacketArea {
     pub fn add(&mut self, packet: Subpacket) -> Result<()> {
         use serialize::Serialize;
 
-        if self.data.len() + packet.len() > ::std::u16::MAX as usize {
+        if self.data.len() + packet.len() > Self::MAX_SIZE {
             return Err(Error::MalformedPacket(
-                "Subpacket area exceeds maximum size".into()).into());
+                format!("Subpacket area exceeds maximum size: \
+                         adding {} bytes to {} bytes exceeds {} bytes",
+                        packet.len(), self.data.len(), Self::MAX_SIZE))
+                       .into());
         }
 
         self.cache_invalidate();
@@ -567,6 +581,20 @@ impl SubpacketArea {
         self.data.clear();
     }
 
+    /// Returns the size of the serialized subpacket area.
+    ///
+    /// This does not include the two-octet length field that
+    /// precedes the area in a signature packet.
+    pub fn serialized_len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the number of bytes that can still be added to the
+    /// subpacket area.
+    pub fn space_left(&self) -> usize {
+        Self::MAX_SIZE.saturating_sub(self.data.len())
+    }
+
 }
 
 /// Payload of a NotationData subpacket.
@@ -599,10 +627,52 @@ impl<'a> NotationData<'a> {
         self.name
     }
 
+    /// Returns the name as string.
+    ///
+    /// Fails if the name is not valid UTF-8.
+    pub fn name_str(&self) -> Result<&'a str> {
+        Ok(str::from_utf8(self.name)
+           .context("Notation name is not valid UTF-8")?)
+    }
+
+    /// Returns the name as string, replacing invalid UTF-8
+    /// sequences with `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn name_lossy(&self) -> Cow<'a, str> {
+        String::from_utf8_lossy(self.name)
+    }
+
     /// Returns the value.
     pub fn value(&self) -> &'a [u8] {
         self.value
     }
+
+    /// Returns the value as string.
+    ///
+    /// Fails if the value is not valid UTF-8.  Only human-readable
+    /// values are required to be UTF-8, see
+    /// `NotationDataFlags::human_readable`.
+    pub fn value_str(&self) -> Result<&'a str> {
+        Ok(str::from_utf8(self.value)
+           .context("Notation value is not valid UTF-8")?)
+    }
+
+    /// Returns the value as string, replacing invalid UTF-8
+    /// sequences with `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn value_lossy(&self) -> Cow<'a, str> {
+        String::from_utf8_lossy(self.value)
+    }
+
+    /// Returns whether the notation is valid UTF-8 where [Section
+    /// 5.2.3.16 of RFC 4880] requires it.
+    ///
+    /// The name must always be UTF-8, the value only if it is
+    /// flagged as human-readable.
+    ///
+    ///   [Section 5.2.3.16 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-5.2.3.16
+    pub(crate) fn is_valid_utf8(&self) -> bool {
+        self.name_str().is_ok()
+            && (! self.flags.human_readable() || self.value_str().is_ok())
+    }
 }
 
 /// Flags for the Notation Data subpacket.
@@ -894,6 +964,102 @@ impl<'a> Subpacket<'a> {
     }
 }
 
+/// A subpacket in the private or experimental range.
+///
+/// [Section 5.2.3.1 of RFC 4880] reserves the subpacket tags 100 to
+/// 110 for private or experimental use.  Sequoia does not know their
+/// format, hence they are parsed into `SubpacketValue::Unknown`.
+/// Applications that use these subpackets implement this trait to
+/// get typed access to the values using
+/// [`Signature4::private_subpacket`] and
+/// [`signature::Builder::set_private_subpacket`].  If the subpacket
+/// is registered using [`register_private_subpacket`], it is also
+/// considered understood by the [`StandardPolicy`], and tools like
+/// `sq packet dump` can describe it.
+///
+/// [Section 5.2.3.1 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-5.2.3.1
+/// [`Signature4::private_subpacket`]: ../struct.Signature4.html#method.private_subpacket
+/// [`signature::Builder::set_private_subpacket`]: ../struct.Builder.html#method.set_private_subpacket
+/// [`register_private_subpacket`]: fn.register_private_subpacket.html
+/// [`StandardPolicy`]: ../../../policy/struct.StandardPolicy.html
+pub trait PrivateSubpacket: Sized + fmt::Debug {
+    /// The subpacket's tag, must be in the range 100 to 110.
+    const TAG: u8;
+
+    /// A human-readable name for the subpacket.
+    const NAME: &'static str;
+
+    /// Serializes the value.
+    fn encode(&self) -> Vec<u8>;
+
+    /// Parses a serialized value.
+    fn decode(value: &[u8]) -> Result<Self>;
+}
+
+/// Renders a private subpacket's value for humans.
+type Describe = fn(&[u8]) -> Result<String>;
+
+lazy_static! {
+    /// The registered private subpackets, indexed by tag.
+    static ref PRIVATE_SUBPACKETS: RwLock<HashMap<u8, (&'static str, Describe)>>
+        = RwLock::new(HashMap::new());
+}
+
+fn describe<T: PrivateSubpacket>(value: &[u8]) -> Result<String> {
+    T::decode(value).map(|v| format!("{:?}", v))
+}
+
+/// Returns an error if `tag` is not in the private range.
+fn check_private_tag(tag: u8) -> Result<()> {
+    match SubpacketTag::from(tag) {
+        SubpacketTag::Private(_) => Ok(()),
+        _ => Err(Error::InvalidArgument(
+            format!("Subpacket tag {} is not in the private range", tag))
+                 .into()),
+    }
+}
+
+/// Registers the private subpacket `T`.
+///
+/// Registering the same type twice is harmless.
+///
+/// # Errors
+///
+/// Returns `Error::InvalidArgument` if `T::TAG` is not in the range
+/// 100 to 110, or if a different subpacket has already been
+/// registered with this tag.
+pub fn register_private_subpacket<T: PrivateSubpacket>() -> Result<()> {
+    check_private_tag(T::TAG)?;
+
+    let mut registry = PRIVATE_SUBPACKETS.write().unwrap();
+    if let Some(&(name, _)) = registry.get(&T::TAG) {
+        if name != T::NAME {
+            return Err(Error::InvalidArgument(
+                format!("Subpacket tag {} is already registered for {}",
+                        T::TAG, name)).into());
+        }
+    }
+    registry.insert(T::TAG, (T::NAME, describe::<T> as Describe));
+    Ok(())
+}
+
+/// Describes the value of a registered private subpacket.
+///
+/// Returns `None` if no subpacket is registered for `tag`.
+/// Otherwise, returns the subpacket's name, and the decoded value
+/// formatted using its `Debug` implementation, or the error
+/// returned by the decoder.
+pub fn describe_private_subpacket(tag: SubpacketTag, value: &[u8])
+                                  -> Option<(&'static str, Result<String>)> {
+    let tag = match tag {
+        SubpacketTag::Private(t) => t,
+        _ => return None,
+    };
+
+    PRIVATE_SUBPACKETS.read().unwrap().get(&tag)
+        .map(|&(name, describe)| (name, describe(value)))
+}
+
 fn from_be_u16(value: &[u8]) -> Option<u16> {
     if value.len() >= 2 {
         Some((value[0] as u16) << 8
@@ -1263,6 +1429,79 @@ impl Signature4 {
         result
     }
 
+    /// Returns the unhashed subpackets that contradict the hashed
+    /// area.
+    ///
+    /// The unhashed area is not protected by the signature, hence
+    /// anyone can add subpackets to it in transit.  A subpacket is
+    /// considered contradicting if the hashed area contains a
+    /// subpacket of the same type with a different value, or if it
+    /// is an Issuer subpacket that does not match the hashed Issuer
+    /// Fingerprint subpacket, or vice versa.  Notation Data
+    /// subpackets may legitimately appear more than once, and are
+    /// never considered contradicting.
+    pub fn unhashed_contradictions(&self) -> Vec<Subpacket> {
+        self.unhashed_area().iter()
+            .map(|(_, _, sp)| sp)
+            .filter(|sp| self.contradicts(sp))
+            .collect()
+    }
+
+    /// Returns whether the unhashed area contradicts the hashed
+    /// area.
+    ///
+    /// See [`unhashed_contradictions`] for details.
+    ///
+    /// [`unhashed_contradictions`]: #method.unhashed_contradictions
+    pub fn has_unhashed_contradictions(&self) -> bool {
+        self.unhashed_area().iter().any(|(_, _, sp)| self.contradicts(&sp))
+    }
+
+    /// Removes the unhashed subpackets that contradict the hashed
+    /// area.
+    ///
+    /// Returns the number of subpackets removed.  See
+    /// [`unhashed_contradictions`] for details.
+    ///
+    /// [`unhashed_contradictions`]: #method.unhashed_contradictions
+    pub fn strip_unhashed_contradictions(&mut self) -> usize {
+        let before = self.unhashed_area().iter().count();
+        let area: SubpacketArea = self.unhashed_area().iter()
+            .filter(|&(_, _, ref sp)| ! self.contradicts(sp))
+            .collect();
+        let removed = before - area.iter().count();
+        if removed > 0 {
+            *self.unhashed_area_mut() = area;
+        }
+        removed
+    }
+
+    /// Returns whether the unhashed subpacket `sp` contradicts the
+    /// hashed area.
+    fn contradicts(&self, sp: &Subpacket) -> bool {
+        let hashed = self.hashed_area();
+        if sp.tag != SubpacketTag::NotationData {
+            if let Some(h) = hashed.lookup(sp.tag) {
+                if h.value != sp.value {
+                    return true;
+                }
+            }
+        }
+
+        let issuer = hashed.lookup(SubpacketTag::Issuer).map(|h| h.value);
+        let issuer_fp = hashed.lookup(SubpacketTag::IssuerFingerprint)
+            .map(|h| h.value);
+        match (&sp.value, &issuer, &issuer_fp) {
+            (SubpacketValue::Issuer(id), _,
+             Some(SubpacketValue::IssuerFingerprint(fp))) =>
+                fp.to_keyid() != *id,
+            (SubpacketValue::IssuerFingerprint(fp),
+             Some(SubpacketValue::Issuer(id)), _) =>
+                fp.to_keyid() != *id,
+            _ => false,
+        }
+    }
+
     /// Returns the value of the Creation Time subpacket, which
     /// contains the time when the signature was created as a unix
     /// timestamp.
@@ -1382,6 +1621,32 @@ impl Signature4 {
         }
     }
 
+    /// Returns whether or not the signature is expired at time `t`.
+    ///
+    /// If `t` is `None`, the current time is used.  This is the same
+    /// as [`signature_expired_at`], but is more convenient to use
+    /// with an optional reference time.
+    ///
+    ///   [`signature_expired_at`]: #method.signature_expired_at
+    pub fn expired_at<T>(&self, t: T) -> bool
+        where T: Into<Option<time::Tm>>
+    {
+        self.signature_expired_at(t.into().unwrap_or_else(time::now_utc))
+    }
+
+    /// Returns whether or not the signature is alive at time `t`.
+    ///
+    /// If `t` is `None`, the current time is used.  This is the same
+    /// as [`signature_alive_at`], but is more convenient to use with
+    /// an optional reference time.
+    ///
+    ///   [`signature_alive_at`]: #method.signature_alive_at
+    pub fn alive_at<T>(&self, t: T) -> bool
+        where T: Into<Option<time::Tm>>
+    {
+        self.signature_alive_at(t.into().unwrap_or_else(time::now_utc))
+    }
+
     /// Returns the value of the Exportable Certification subpacket,
     /// which contains whether the certification should be exported
     /// (i.e., whether the packet is *not* a local signature).
@@ -1532,14 +1797,7 @@ impl Signature4 {
     ///
     ///  [Section 5.2.3.6 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-5.2.3.6
     pub fn key_expired_at(&self, key: &Key, tm: time::Tm) -> bool {
-        match self.key_expiration_time() {
-            Some(e) if e.num_seconds() == 0 =>
-                false, // Zero expiration time, does not expire.
-            Some(e) =>
-                *key.creation_time() + e <= tm,
-            None =>
-                false, // No expiration time, does not expire.
-        }
+        key.expired_at(self, tm)
     }
 
     /// Returns whether or not the given key is alive, i.e. the
@@ -1564,7 +1822,7 @@ impl Signature4 {
     ///
     ///  [Section 5.2.3.6 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-5.2.3.6
     pub fn key_alive_at(&self, key: &Key, tm: time::Tm) -> bool {
-        *key.creation_time() <= tm && ! self.key_expired_at(key, tm)
+        key.alive_at(self, tm)
     }
 
     /// Returns the value of the Preferred Symmetric Algorithms
@@ -2046,6 +2304,21 @@ impl Signature4 {
 
         result
     }
+
+    /// Returns the value of the private subpacket `T`.
+    ///
+    /// The subpacket is only taken from the hashed area.  Returns
+    /// `Ok(None)` if the signature does not contain the subpacket,
+    /// and an error if its value cannot be decoded.
+    pub fn private_subpacket<T: PrivateSubpacket>(&self) -> Result<Option<T>> {
+        check_private_tag(T::TAG)?;
+
+        match self.subpacket(SubpacketTag::Private(T::TAG)) {
+            Some(Subpacket { value: SubpacketValue::Unknown(v), .. }) =>
+                T::decode(v).map(Some),
+            _ => Ok(None),
+        }
+    }
 }
 
 impl signature::Builder {
@@ -2401,6 +2674,25 @@ impl signature::Builder {
 
         Ok(self)
     }
+
+    /// Sets the value of the private subpacket `T`.
+    ///
+    /// The subpacket is stored in the hashed area, replacing any
+    /// subpacket with the same tag.
+    pub fn set_private_subpacket<T: PrivateSubpacket>(mut self, value: &T,
+                                                      critical: bool)
+                                                      -> Result<Self> {
+        check_private_tag(T::TAG)?;
+
+        let value = value.encode();
+        self.hashed_area.replace(Subpacket {
+            critical: critical,
+            tag: SubpacketTag::Private(T::TAG),
+            value: SubpacketValue::Unknown(&value),
+        })?;
+
+        Ok(self)
+    }
 }
 
 #[test]
@@ -2652,6 +2944,82 @@ fn accessors() {
     assert_eq!(sig_.notation("test@example.org"), vec![&[6, 7, 8]]);
 }
 
+#[test]
+fn private_subpackets() {
+    use constants::Curve;
+    use parse::Parse;
+    use serialize::SerializeInto;
+
+    #[derive(Debug, PartialEq)]
+    struct Counter(u32);
+
+    impl PrivateSubpacket for Counter {
+        const TAG: u8 = 105;
+        const NAME: &'static str = "Counter";
+
+        fn encode(&self) -> Vec<u8> {
+            vec![(self.0 >> 24) as u8, (self.0 >> 16) as u8,
+                 (self.0 >> 8) as u8, self.0 as u8]
+        }
+
+        fn decode(value: &[u8]) -> Result<Self> {
+            if value.len() != 4 {
+                return Err(Error::MalformedPacket(
+                    "Counter must be four bytes".into()).into());
+            }
+            Ok(Counter(from_be_u32(value).unwrap()))
+        }
+    }
+
+    #[derive(Debug)]
+    struct Impostor;
+
+    impl PrivateSubpacket for Impostor {
+        const TAG: u8 = 105;
+        const NAME: &'static str = "Impostor";
+        fn encode(&self) -> Vec<u8> { vec![] }
+        fn decode(_: &[u8]) -> Result<Self> { Ok(Impostor) }
+    }
+
+    #[derive(Debug)]
+    struct Public;
+
+    impl PrivateSubpacket for Public {
+        const TAG: u8 = 2;
+        const NAME: &'static str = "Public";
+        fn encode(&self) -> Vec<u8> { vec![] }
+        fn decode(_: &[u8]) -> Result<Self> { Ok(Public) }
+    }
+
+    register_private_subpacket::<Counter>().unwrap();
+    register_private_subpacket::<Counter>().unwrap();
+    assert!(register_private_subpacket::<Impostor>().is_err());
+    assert!(register_private_subpacket::<Public>().is_err());
+
+    let hash_algo = HashAlgorithm::SHA512;
+    let key: ::packet::Key =
+        ::packet::key::Key4::generate_ecc(true, Curve::Ed25519).unwrap().into();
+    let mut keypair = key.into_keypair().unwrap();
+    let sig = signature::Builder::new(::constants::SignatureType::Binary)
+        .set_private_subpacket(&Counter(0x01020304), false).unwrap()
+        .sign_hash(&mut keypair, hash_algo, hash_algo.context().unwrap())
+        .unwrap();
+
+    // The value survives serialization.
+    let sig = Signature::from_bytes(&sig.to_vec().unwrap()).unwrap();
+    assert_eq!(sig.private_subpacket::<Counter>().unwrap(),
+               Some(Counter(0x01020304)));
+
+    let (name, value) = describe_private_subpacket(
+        SubpacketTag::Private(105), &[1, 2, 3, 4]).unwrap();
+    assert_eq!(name, "Counter");
+    assert_eq!(value.unwrap(), "Counter(16909060)");
+    assert!(describe_private_subpacket(
+        SubpacketTag::Private(105), &[1]).unwrap().1.is_err());
+    assert!(describe_private_subpacket(
+        SubpacketTag::Private(106), &[1]).is_none());
+}
+
 #[cfg(feature = "compression-deflate")]
 #[test]
 fn subpacket_test_1 () {
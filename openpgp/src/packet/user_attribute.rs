@@ -81,6 +81,7 @@ impl UserAttribute {
     /// Gets a mutable reference to the user attribute packet's raw
     /// value.
     pub fn value_mut(&mut self) -> &mut Vec<u8> {
+        self.common.digest = None;
         &mut self.value
     }
 
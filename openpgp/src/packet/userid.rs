@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::fmt;
 use std::str;
 use std::hash::{Hash, Hasher};
@@ -289,6 +290,23 @@ impl UserID {
         self.value.as_slice()
     }
 
+    /// Gets the user ID packet's value as string.
+    ///
+    /// Fails if the value is not valid UTF-8.  See
+    /// `UserID::value_lossy` for a lenient alternative.
+    pub fn value_str(&self) -> Result<&str> {
+        Ok(str::from_utf8(&self.value)
+           .context("User ID is not valid UTF-8")?)
+    }
+
+    /// Gets the user ID packet's value as string.
+    ///
+    /// Invalid UTF-8 sequences are replaced with `U+FFFD REPLACEMENT
+    /// CHARACTER`.
+    pub fn value_lossy(&self) -> Cow<str> {
+        String::from_utf8_lossy(&self.value)
+    }
+
     fn do_parse(&self) -> Result<()> {
         if self.parsed.borrow().is_none() {
             let s = str::from_utf8(&self.value)?;
@@ -435,10 +453,13 @@ impl UserID {
             e @ Err(_) => e,
             Ok(None) => Ok(None),
             Ok(Some(address)) => {
-                let mut iter = address.split('@');
-                let localpart = iter.next().expect("Invalid email address");
-                let domain = iter.next().expect("Invalid email address");
-                assert!(iter.next().is_none(), "Invalid email address");
+                // The local part may contain '@' if it is quoted, the
+                // domain may not.
+                let mut iter = address.rsplitn(2, '@');
+                let domain = iter.next().expect("rsplitn returns one item");
+                let localpart = iter.next().ok_or_else(
+                    || failure::format_err!("Invalid email address: {:?}",
+                                            address))?;
 
                 // Normalize Unicode in domains.
                 let domain = idna::domain_to_ascii(domain)
@@ -621,4 +642,34 @@ mod tests {
                       .unwrap().value(),
                    b"\"Foo Q. Bar\" <foo@bar.com>");
     }
+
+    #[test]
+    fn utf8() {
+        use parse::{PacketParserBuilder, PacketParserResult};
+
+        let u = UserID::from(&b"Alice \xff <alice@example.org>"[..]);
+        assert!(u.value_str().is_err());
+        assert_eq!(u.value_lossy(), "Alice \u{FFFD} <alice@example.org>");
+
+        let u = UserID::from("Alice <alice@example.org>");
+        assert_eq!(u.value_str().unwrap(), "Alice <alice@example.org>");
+
+        let buf = Packet::UserID(
+            UserID::from(&b"Alice \xff <alice@example.org>"[..]))
+            .to_vec().unwrap();
+        for &strict in [false, true].iter() {
+            let ppr = PacketParserBuilder::from_bytes(&buf).unwrap()
+                .strict_utf8(strict)
+                .finalize().unwrap();
+            if let PacketParserResult::Some(pp) = ppr {
+                match pp.packet {
+                    Packet::UserID(_) => assert!(! strict),
+                    Packet::Unknown(_) => assert!(strict),
+                    ref p => panic!("Unexpected packet: {:?}", p),
+                }
+            } else {
+                panic!("No packet");
+            }
+        }
+    }
 }
@@ -148,7 +148,10 @@ impl PacketPile {
                 return Some(p)
             }
 
-            container = p.children.as_mut().unwrap();
+            container = match p.children.as_mut() {
+                Some(c) => c,
+                None => return None,
+            };
         }
 
         None
@@ -687,6 +690,10 @@ mod test {
 
         assert!(pile.path_ref(&[ 0, 2, 0 ]).is_none());
         assert!(pile.path_ref_mut(&[ 0, 2, 0 ]).is_none());
+
+        // Literal data packets have no children.
+        assert!(pile.path_ref(&[ 0, 0, 0, 0 ]).is_none());
+        assert!(pile.path_ref_mut(&[ 0, 0, 0, 0 ]).is_none());
     }
 
     #[test]
@@ -0,0 +1,230 @@
+use std::collections::VecDeque;
+use std::io;
+
+use buffered_reader::{self, BufferedReader};
+
+use {
+    BodyLength,
+    Error,
+    Packet,
+    PacketPile,
+    Result,
+};
+use packet::Header;
+use parse::Parse;
+
+/// Parses OpenPGP packets arriving in discrete frames.
+///
+/// A [`PacketParser`] pulls data from an `io::Read`.  This does not
+/// fit protocols that deliver data in frames, like WebSockets or
+/// length-prefixed protocols, where the application is handed a
+/// frame at a time.  A `FrameParser` is fed frames using [`push`],
+/// and parses a top-level packet as soon as it is complete.  Only
+/// the bytes of the incomplete packet are buffered between frames.
+///
+/// Packets need not be aligned with frames: a frame may contain
+/// several packets, and a packet may span several frames.  Old
+/// format packets of indeterminate length extend to the end of the
+/// stream, hence they are only parsed by [`finish`].
+///
+///   [`PacketParser`]: struct.PacketParser.html
+///   [`push`]: #method.push
+///   [`finish`]: #method.finish
+///
+/// # Example
+///
+/// ```rust
+/// # extern crate sequoia_openpgp as openpgp;
+/// # use openpgp::Result;
+/// # use openpgp::parse::FrameParser;
+/// # let _ = f(include_bytes!("../../tests/data/keys/public-key.gpg"));
+/// #
+/// # fn f(message_data: &[u8]) -> Result<()> {
+/// let mut fp = FrameParser::new();
+/// for frame in message_data.chunks(100) {
+///     fp.push(frame)?;
+///     while let Some(packet) = fp.next_packet() {
+///         // Do something interesting with `packet`.
+///         # let _ = packet;
+///     }
+/// }
+/// for packet in fp.finish()? {
+///     // Do something interesting with `packet`.
+///     # let _ = packet;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct FrameParser {
+    /// The bytes of the incomplete packet.
+    buffer: Vec<u8>,
+    /// Packets that have been parsed, but not yet returned.
+    packets: VecDeque<Packet>,
+}
+
+impl FrameParser {
+    /// Returns a new `FrameParser`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Feeds `frame` to the parser.
+    ///
+    /// All packets completed by `frame` are parsed, and can be
+    /// retrieved using [`next_packet`].
+    ///
+    ///   [`next_packet`]: #method.next_packet
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a packet header is malformed, or if a
+    /// complete packet cannot be parsed.  In this case, the parser
+    /// is in an undefined state and should be discarded.
+    pub fn push(&mut self, frame: &[u8]) -> Result<()> {
+        self.buffer.extend_from_slice(frame);
+        while let Some(len) = packet_len(&self.buffer)? {
+            self.parse(len)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the next complete packet, if any.
+    pub fn next_packet(&mut self) -> Option<Packet> {
+        self.packets.pop_front()
+    }
+
+    /// Returns the number of bytes buffered for the incomplete
+    /// packet.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Signals the end of the stream.
+    ///
+    /// Returns all packets that have not yet been retrieved,
+    /// including a trailing packet of indeterminate length.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MalformedPacket` if the stream ends in the
+    /// middle of a packet.
+    pub fn finish(mut self) -> Result<Vec<Packet>> {
+        if ! self.buffer.is_empty() {
+            // Complete packets have already been parsed, hence the
+            // buffer contains either a packet of indeterminate
+            // length, or a truncated packet.
+            let len = self.buffer.len();
+            match header(&self.buffer) {
+                Ok((Header { length: BodyLength::Indeterminate, .. }, _)) =>
+                    (),
+                _ => return Err(Error::MalformedPacket(
+                    format!("Truncated packet ({} bytes)", len)).into()),
+            }
+            self.parse(len)?;
+        }
+        Ok(self.packets.into_iter().collect())
+    }
+
+    /// Parses the first `len` bytes of the buffer.
+    fn parse(&mut self, len: usize) -> Result<()> {
+        let pile = PacketPile::from_bytes(&self.buffer[..len])?;
+        self.packets.extend(pile.into_children());
+        self.buffer.drain(..len);
+        Ok(())
+    }
+}
+
+/// Parses the header at the start of `data`.
+///
+/// Returns the header, and a reader positioned at the packet's
+/// body.
+fn header(data: &[u8]) -> Result<(Header, buffered_reader::Memory<()>)> {
+    let mut bio = buffered_reader::Memory::new(data);
+    let header = Header::parse(&mut bio)?;
+    header.valid(true)?;
+    Ok((header, bio))
+}
+
+/// Returns the length of the first packet in `data`.
+///
+/// Returns `None` if the packet is incomplete, or if its length is
+/// indeterminate.
+fn packet_len(data: &[u8]) -> Result<Option<usize>> {
+    // Distinguishes truncated data from malformed data.
+    fn incomplete<T>(e: ::failure::Error) -> Result<Option<T>> {
+        match e.downcast_ref::<io::Error>() {
+            Some(e) if e.kind() == io::ErrorKind::UnexpectedEof =>
+                return Ok(None),
+            _ => (),
+        }
+        Err(e)
+    }
+
+    if data.is_empty() {
+        return Ok(None);
+    }
+
+    let (header, mut bio) = match header(data) {
+        Ok(r) => r,
+        Err(e) => return incomplete(e),
+    };
+
+    let mut length = header.length;
+    loop {
+        match length {
+            BodyLength::Full(l) => {
+                let end = bio.total_out() + l as usize;
+                return Ok(if end <= data.len() { Some(end) } else { None });
+            },
+            BodyLength::Partial(l) => {
+                if bio.buffer().len() < l as usize {
+                    return Ok(None);
+                }
+                bio.consume(l as usize);
+                length = match BodyLength::parse_new_format(&mut bio) {
+                    Ok(l) => l,
+                    Err(e) => return incomplete(e.into()),
+                };
+            },
+            BodyLength::Indeterminate => return Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn frames() {
+        for name in &["messages/a-cypherpunks-manifesto.txt.ed25519.sig",
+                      "keys/public-key.gpg"] {
+            let data = ::tests::file(name);
+            let expected = PacketPile::from_bytes(data).unwrap()
+                .into_children().collect::<Vec<_>>();
+
+            for &size in &[1, 7, 100, data.len()] {
+                let mut fp = FrameParser::new();
+                let mut packets = Vec::new();
+                for frame in data.chunks(size) {
+                    fp.push(frame).unwrap();
+                    while let Some(p) = fp.next_packet() {
+                        packets.push(p);
+                    }
+                }
+                packets.extend(fp.finish().unwrap());
+                assert_eq!(packets, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let data = ::tests::key("public-key.gpg");
+        let mut fp = FrameParser::new();
+        fp.push(&data[..data.len() - 1]).unwrap();
+        assert!(fp.buffered() > 0);
+        assert!(fp.finish().is_err());
+    }
+}
@@ -111,20 +111,44 @@ impl mpis::PublicKey {
                 let q = MPI::parse("ecdh_public_len", "ecdh_public", php)?;
                 let kdf_len = php.parse_u8("kdf_len")?;
 
-                if kdf_len != 3 {
-                    return Err(Error::MalformedPacket(
-                            "wrong kdf length".into()).into());
-                }
-
-                let _reserved = php.parse_u8("kdf_reserved")?;
-                let hash: HashAlgorithm = php.parse_u8("kdf_hash")?.into();
-                let sym: SymmetricAlgorithm = php.parse_u8("kek_symm")?.into();
+                // We only understand version 1 of the KDF parameters.
+                // Keep anything else verbatim, so that the key
+                // survives a round trip.
+                let kdf = if kdf_len == 3 {
+                    let reserved = php.parse_u8("kdf_reserved")?;
+                    if reserved == 1 {
+                        let hash: HashAlgorithm =
+                            php.parse_u8("kdf_hash")?.into();
+                        let sym: SymmetricAlgorithm =
+                            php.parse_u8("kek_symm")?.into();
+
+                        return Ok(mpis::PublicKey::ECDH {
+                            curve: Curve::from_oid(&curve),
+                            q: q,
+                            hash: hash,
+                            sym: sym
+                        });
+                    }
+
+                    let mut kdf = vec![reserved];
+                    kdf.extend_from_slice(&php.parse_bytes("kdf_params", 2)?);
+                    kdf
+                } else {
+                    php.parse_bytes("kdf_params", kdf_len as usize)?
+                };
+
+                let mut rest = Vec::with_capacity(
+                    1 + curve.len() + 2 + q.value.len() + 1 + kdf.len());
+                rest.push(curve_len as u8);
+                rest.extend_from_slice(&curve);
+                rest.extend_from_slice(&[(q.bits >> 8) as u8, q.bits as u8]);
+                rest.extend_from_slice(&q.value);
+                rest.push(kdf_len);
+                rest.extend_from_slice(&kdf);
 
-                Ok(mpis::PublicKey::ECDH {
-                    curve: Curve::from_oid(&curve),
-                    q: q,
-                    hash: hash,
-                    sym: sym
+                Ok(mpis::PublicKey::Unknown {
+                    mpis: Vec::new().into_boxed_slice(),
+                    rest: rest.into_boxed_slice(),
                 })
             }
 
@@ -146,6 +170,37 @@ impl mpis::PublicKey {
 }
 
 impl mpis::SecretKey {
+    /// Parses secret key MPIs for `algo` plus their 16-bit checksum.
+    /// Fails if the checksum is wrong.
+    ///
+    /// This checksum is used by secret keys protected using the
+    /// deprecated mechanisms, see [`SecretKey::LegacyEncrypted`].
+    ///
+    ///   [`SecretKey::LegacyEncrypted`]: ../packet/key/enum.SecretKey.html#variant.LegacyEncrypted
+    pub fn parse_legacy_chksumd<T: Read>(algo: PublicKeyAlgorithm, cur: T)
+                                         -> Result<Self> {
+        use serialize::Serialize;
+
+        let bio = buffered_reader::Generic::with_cookie(
+            cur, None, Cookie::default());
+        let mut php = PacketHeaderParser::new_naked(Box::new(bio));
+        let mpis = Self::_parse(algo, &mut php)?;
+        let their_chksum = php.parse_be_u16("checksum")?;
+
+        let mut buf = Vec::new();
+        mpis.serialize(&mut buf)?;
+        let our_chksum: usize = buf.iter().map(|x| *x as usize).sum();
+        unsafe {
+            ::memsec::memzero(buf.as_mut_ptr(), buf.len());
+        }
+
+        if our_chksum as u16 != their_chksum {
+            Err(Error::MalformedMPI("checksum wrong".to_string()).into())
+        } else {
+            Ok(mpis)
+        }
+    }
+
     /// Parses secret key MPIs for `algo` plus their SHA1 checksum. Fails if the
     /// checksum is wrong.
     pub fn parse_chksumd<T: Read>(algo: PublicKeyAlgorithm, cur: T)
@@ -509,3 +564,29 @@ fn mpis_parse_test() {
     // not 2).
     assert!(MPI::from_bytes(b"\x00\x02\x01").is_err());
 }
+
+#[test]
+fn mpis_parse_unknown_curve_and_kdf() {
+    use serialize::Serialize;
+    use PublicKeyAlgorithm::*;
+
+    // An ECDSA key over an unknown curve.
+    let buf = b"\x03\x2b\x06\x09\x00\x03\x05".to_vec();
+    let mpis = mpis::PublicKey::parse(ECDSA, &buf).unwrap();
+    assert_eq!(mpis.curve().map(|c| c.oid()), Some(&b"\x2b\x06\x09"[..]));
+    let mut out = Vec::new();
+    mpis.serialize(&mut out).unwrap();
+    assert_eq!(out, buf);
+
+    // An ECDH key with KDF parameters of an unknown version.
+    let buf = b"\x03\x2b\x06\x09\x00\x03\x05\x04\x02\x08\x09\x00".to_vec();
+    let mpis = mpis::PublicKey::parse(ECDH, &buf).unwrap();
+    match mpis {
+        mpis::PublicKey::Unknown { .. } => (),
+        _ => panic!("expected unknown MPIs, got {:?}", mpis),
+    }
+    assert_eq!(mpis.serialized_len(), buf.len());
+    let mut out = Vec::new();
+    mpis.serialize(&mut out).unwrap();
+    assert_eq!(out, buf);
+}
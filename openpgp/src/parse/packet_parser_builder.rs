@@ -118,6 +118,51 @@ impl<'a> PacketParserBuilder<'a> {
         self
     }
 
+    /// Sets the largest chunk size of AEAD encrypted data packets
+    /// that will be decrypted.
+    ///
+    /// AEAD encrypted data is authenticated, and hence buffered, a
+    /// chunk at a time, so this bounds the amount of memory used for
+    /// decryption.  Decrypted data is returned as soon as the chunk
+    /// containing it is authenticated.  Trying to decrypt packets
+    /// with larger chunks fails with `Error::InvalidOperation`.  The
+    /// default is [`aed::MAX_CHUNK_SIZE`].
+    ///
+    ///   [`aed::MAX_CHUNK_SIZE`]: ../packet/aed/constant.MAX_CHUNK_SIZE.html
+    pub fn max_aead_chunk_size(mut self, value: usize) -> Self {
+        self.settings.max_aead_chunk_size = value;
+        self
+    }
+
+    /// Controls whether invalid UTF-8 is rejected.
+    ///
+    /// [RFC 4880] requires User IDs, the names of notations, and the
+    /// values of human-readable notations to be UTF-8 encoded.  If
+    /// enabled, User ID and signature packets violating this are
+    /// returned as `Packet::Unknown`.  By default, invalid UTF-8 is
+    /// accepted.
+    ///
+    ///   [RFC 4880]: https://tools.ietf.org/html/rfc4880
+    pub fn strict_utf8(mut self, enable: bool) -> Self {
+        self.settings.strict_utf8 = enable;
+        self
+    }
+
+    /// Controls whether the digests of packets are computed.
+    ///
+    /// If enabled, the SHA-256 digest of the body of every packet
+    /// that does not contain data or other packets, e.g. signatures,
+    /// keys, and User IDs, is computed while parsing.  It is
+    /// available using [`Common::content_digest`], and allows
+    /// identifying duplicate packets without serializing them.  This
+    /// is enabled when parsing TPKs.
+    ///
+    ///   [`Common::content_digest`]: ../packet/struct.Common.html#method.content_digest
+    pub fn content_digests(mut self, enable: bool) -> Self {
+        self.settings.content_digests = enable;
+        self
+    }
+
     /// How to treat the input stream.
     pub fn dearmor(mut self, mode: Dearmor) -> Self {
         self.dearmor = mode;
@@ -47,11 +47,15 @@ use message::MessageValidator;
 mod partial_body;
 use self::partial_body::BufferedReaderPartialBodyFilter;
 
-use packet::signature::subpacket::SubpacketArea;
+use packet::aed;
+use packet::signature::subpacket::{SubpacketArea, SubpacketValue};
 
 mod packet_pile_parser;
 pub use self::packet_pile_parser::PacketPileParser;
 
+mod frame_parser;
+pub use self::frame_parser::FrameParser;
+
 mod hashed_reader;
 pub(crate) use self::hashed_reader::HashedReader;
 
@@ -263,7 +267,7 @@ impl<'a> PacketHeaderParser<'a> {
     // Only call this function if the packet's header has been
     // completely and correctly parsed.  If a failure occurs while
     // parsing the header, use `fail()` instead.
-    fn ok(mut self, packet: Packet) -> Result<PacketParser<'a>> {
+    fn ok(mut self, mut packet: Packet) -> Result<PacketParser<'a>> {
         let total_out = self.reader.total_out();
 
         let mut reader = if self.state.settings.map {
@@ -296,6 +300,25 @@ impl<'a> PacketHeaderParser<'a> {
             Box::new(self.reader).into_inner().unwrap()
         };
 
+        // Only packets without a body are completely described by
+        // the parsed fields.
+        let has_body = match packet {
+            Packet::Signature(_) | Packet::OnePassSig(_)
+                | Packet::PublicKey(_) | Packet::PublicSubkey(_)
+                | Packet::SecretKey(_) | Packet::SecretSubkey(_)
+                | Packet::UserID(_) | Packet::UserAttribute(_)
+                | Packet::PKESK(_) | Packet::SKESK(_)
+                | Packet::Marker(_) | Packet::MDC(_) => false,
+            _ => true,
+        };
+        if self.state.settings.content_digests && ! has_body {
+            let mut hash = HashAlgorithm::SHA256.context()?;
+            hash.update(&reader.buffer()[..total_out]);
+            let mut digest = vec![0; hash.digest_size()];
+            hash.digest(&mut digest);
+            packet.digest = Some(digest);
+        }
+
         // We know the data has been read, so this cannot fail.
         reader.data_consume_hard(total_out).unwrap();
 
@@ -711,6 +734,16 @@ struct PacketParserSettings {
 
     // Whether or not to create a map.
     map: bool,
+
+    // The largest chunk size of AEAD encrypted data packets that we
+    // decrypt.  The decryptor buffers a whole chunk.
+    max_aead_chunk_size: usize,
+
+    // Whether to reject invalid UTF-8 where the RFC requires it.
+    strict_utf8: bool,
+
+    // Whether to compute the digests of packet bodies.
+    content_digests: bool,
 }
 
 // The default `PacketParser` settings.
@@ -720,6 +753,9 @@ impl Default for PacketParserSettings {
             max_recursion_depth: MAX_RECURSION_DEPTH,
             buffer_unread_content: false,
             map: false,
+            max_aead_chunk_size: aed::MAX_CHUNK_SIZE,
+            strict_utf8: false,
+            content_digests: false,
         }
     }
 }
@@ -902,11 +938,25 @@ impl Signature4 {
         let mpis = php_try!(
             crypto::mpis::Signature::_parse(pk_algo, &mut php));
 
+        let hashed_area = SubpacketArea::new(hashed_area);
+        let unhashed_area = SubpacketArea::new(unhashed_area);
+        if php.state.settings.strict_utf8 {
+            for area in [&hashed_area, &unhashed_area].iter() {
+                for (_, _, sb) in area.iter() {
+                    if let SubpacketValue::NotationData(ref n) = sb.value {
+                        if ! n.is_valid_utf8() {
+                            return php.fail("notation is not valid UTF-8");
+                        }
+                    }
+                }
+            }
+        }
+
         let hash_algo = hash_algo.into();
         let mut pp = php.ok(Packet::Signature(Signature4::new(
             sigtype.into(), pk_algo.into(), hash_algo,
-            SubpacketArea::new(hashed_area),
-            SubpacketArea::new(unhashed_area),
+            hashed_area,
+            unhashed_area,
             [hash_prefix1, hash_prefix2],
             mpis).into()))?;
 
@@ -1356,10 +1406,50 @@ impl Key4 {
 
                     SecretKey::Unencrypted{ mpis: sec }
                 }
-                // Encrypted & MD5 for key derivation: unsupported
-                1...253 => {
+                // Encrypted & MD5 for key derivation
+                #[cfg(feature = "legacy")]
+                1...252 => {
+                    let sk: SymmetricAlgorithm = s2k_usage.into();
+                    let cipher = php_try!(php.parse_bytes_eof("encrypted_mpis"));
+
+                    SecretKey::LegacyEncrypted{
+                        s2k: S2K::Simple { hash: HashAlgorithm::MD5 },
+                        algorithm: sk,
+                        ciphertext: cipher.into_boxed_slice(),
+                    }
+                }
+                #[cfg(not(feature = "legacy"))]
+                1...252 => {
                     return php.fail("unsupported secret key encryption");
                 }
+                // Encrypted, S2K & AEAD
+                253 => {
+                    let sk: SymmetricAlgorithm = php_try!(php.parse_u8("sym_algo")).into();
+                    let aead: AEADAlgorithm = php_try!(php.parse_u8("aead_algo")).into();
+                    let s2k = php_try!(S2K::parse(&mut php));
+                    let iv_size = php_try!(aead.iv_size());
+                    let iv = php_try!(php.parse_bytes("iv", iv_size));
+                    let cipher = php_try!(php.parse_bytes_eof("encrypted_mpis"));
+
+                    // The packet tag and the public key fields are
+                    // authenticated, too.
+                    let public = php_try!(Key4::new(
+                        time::Tm::from_pgp(creation_time), pk_algo,
+                        mpis.clone(), None));
+                    let ad = php_try!(public.secret_key_ad(match tag {
+                        Tag::Reserved => Tag::SecretKey,
+                        t => t,
+                    }));
+
+                    SecretKey::AEADEncrypted{
+                        s2k: s2k,
+                        algorithm: sk,
+                        aead: aead,
+                        iv: iv.into_boxed_slice(),
+                        ciphertext: cipher.into_boxed_slice(),
+                        ad: ad.into_boxed_slice(),
+                    }
+                }
                 // Encrypted, S2K & SHA-1 checksum
                 254 => {
                     let sk: SymmetricAlgorithm = php_try!(php.parse_u8("sym_algo")).into();
@@ -1372,7 +1462,20 @@ impl Key4 {
                         ciphertext: cipher.into_boxed_slice(),
                     }
                 }
-                // Encrypted, S2K & mod 65536 checksum: unsupported
+                // Encrypted, S2K & mod 65536 checksum
+                #[cfg(feature = "legacy")]
+                255 => {
+                    let sk: SymmetricAlgorithm = php_try!(php.parse_u8("sym_algo")).into();
+                    let s2k = php_try!(S2K::parse(&mut php));
+                    let cipher = php_try!(php.parse_bytes_eof("encrypted_mpis"));
+
+                    SecretKey::LegacyEncrypted{
+                        s2k: s2k,
+                        algorithm: sk,
+                        ciphertext: cipher.into_boxed_slice(),
+                    }
+                }
+                #[cfg(not(feature = "legacy"))]
                 255 => {
                     return php.fail("unsupported secret key encryption");
                 }
@@ -1499,6 +1602,9 @@ impl UserID {
         make_php_try!(php);
 
         let value = php_try!(php.parse_bytes_eof("value"));
+        if php.state.settings.strict_utf8 && str::from_utf8(&value).is_err() {
+            return php.fail("user id is not valid UTF-8");
+        }
 
         php.ok(Packet::UserID(UserID::from(value)))
     }
@@ -1933,8 +2039,12 @@ impl AED1 {
             php_try!(php.parse_u8("sym_algo")).into();
         let aead: AEADAlgorithm =
             php_try!(php.parse_u8("aead_algo")).into();
-        let chunk_size: usize =
-            1 << (php_try!(php.parse_u8("chunk_size")) as usize + 6);
+        let chunk_size_octet = php_try!(php.parse_u8("chunk_size"));
+        let chunk_size = php_try!(
+            1usize.checked_shl(chunk_size_octet as u32 + 6)
+                .ok_or_else(|| failure::Error::from(Error::MalformedPacket(
+                    format!("Unsupported chunk size octet: {}",
+                            chunk_size_octet)))));
 
         let iv_size = php_try!(aead.iv_size());
         let iv = php_try!(php.parse_bytes("iv", iv_size));
@@ -3520,6 +3630,16 @@ impl<'a> PacketParser<'a> {
             },
 
             Packet::AED(AED::V1(aed)) => {
+                // The chunks are authenticated, and hence buffered,
+                // one at a time.  Bound the amount of memory we use.
+                if aed.chunk_size() > self.state.settings.max_aead_chunk_size {
+                    return Err(Error::InvalidOperation(
+                        format!("AEAD chunk size {} exceeds the limit of {}",
+                                aed.chunk_size(),
+                                self.state.settings.max_aead_chunk_size))
+                               .into());
+                }
+
                 // Get the first chunk and check whether we can
                 // decrypt it using the provided key.  Don't actually
                 // comsume them in case we can't.
@@ -3768,6 +3888,8 @@ mod test {
             let mut ppr = PacketParserBuilder::from_bytes(
                 ::tests::message(test.filename)).unwrap()
                 .buffer_unread_content()
+                // One of the test vectors uses 128 MiB chunks.
+                .max_aead_chunk_size(1 << 27)
                 .finalize()
                 .expect(&format!("Error reading {}", test.filename)[..]);
 
@@ -3829,6 +3951,48 @@ mod test {
         }}
     }
 
+    #[test]
+    fn aead_chunk_size_limit() {
+        let test = &DECRYPT_TESTS[DECRYPT_TESTS.len() - 1];
+        let key: SessionKey = ::conversions::from_hex(test.key_hex, false)
+            .unwrap().into();
+        let decrypt = |max: Option<usize>| {
+            let mut ppb = PacketParserBuilder::from_bytes(
+                ::tests::message(test.filename)).unwrap();
+            if let Some(max) = max {
+                ppb = ppb.max_aead_chunk_size(max);
+            }
+            let ppr = consume_until(ppb.finalize().unwrap(), false,
+                                    &[ Tag::AED ][..], &[ Tag::SKESK ][..]);
+            let mut pp = ppr.unwrap();
+            assert_eq!(pp.packet.tag(), Tag::AED);
+            pp.decrypt(test.algo, &key)
+        };
+
+        // The test vector uses 128 MiB chunks.
+        assert!(decrypt(None).is_err());
+        assert!(decrypt(Some(1 << 26)).is_err());
+        assert!(decrypt(Some(1 << 27)).is_ok());
+    }
+
+    #[test]
+    fn aead_chunk_size_octet() {
+        use serialize::Serialize;
+
+        // A chunk size octet of 0xff does not fit into a usize.
+        let mut aed = Vec::new();
+        CTB::new(Tag::AED).serialize(&mut aed).unwrap();
+        BodyLength::Full(4 + 16).serialize(&mut aed).unwrap();
+        aed.extend_from_slice(&[1, 7, 1, 0xff]);
+        aed.extend_from_slice(&[0; 16]);
+
+        let ppr = PacketParser::from_bytes(&aed).unwrap();
+        match ppr.unwrap().packet {
+            Packet::Unknown(_) => (),
+            p => panic!("Expected an Unknown packet, got: {:?}", p),
+        }
+    }
+
     #[test]
     fn message_validator() {
         for test in DECRYPT_TESTS.iter() {
@@ -4095,4 +4259,27 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn content_digests() {
+        use TPK;
+
+        let tpk = TPK::from_bytes(::tests::key("testy.pgp")).unwrap();
+        let sig = tpk.primary_key_signature().unwrap();
+        let digest = sig.common.content_digest().unwrap().to_vec();
+
+        // Modifying the signature invalidates the digest.
+        let mut modified = sig.clone();
+        modified.set_hash_prefix(*sig.hash_prefix());
+        assert!(modified.common.content_digest().is_none());
+        assert_eq!(modified.content_digest().unwrap(), digest);
+
+        // Digests are not computed by default.
+        let ppr = PacketParser::from_bytes(::tests::key("testy.pgp")).unwrap();
+        if let PacketParserResult::Some(pp) = ppr {
+            assert!(pp.packet.content_digest().is_none());
+        } else {
+            panic!("No packet!?");
+        }
+    }
 }
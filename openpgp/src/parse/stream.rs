@@ -5,8 +5,30 @@
 //! to process OpenPGP messages.  These implementations use constant
 //! space.
 //!
+//! The filters wrap a [`PacketParser`], and implement `io::Read`.
+//! The caller reads the content of the literal data packet, while
+//! the filter takes care of the one-pass signature bookkeeping,
+//! hashes the data, and, once the message has been processed, looks
+//! up the issuers' keys and verifies the signatures:
+//!
+//!   - [`Verifier`] verifies inline-signed messages.
+//!   - [`DetachedVerifier`] verifies detached signatures over some
+//!     data.
+//!   - [`Decryptor`] decrypts messages, and verifies them if they
+//!     are signed.
+//!
+//! The caller supplies a [`VerificationHelper`], which provides the
+//! TPKs used to resolve the issuers, and receives the result of
+//! every signature's verification in [`VerificationHelper::check`].
+//!
 //! See the [verification example].
 //!
+//! [`PacketParser`]: ../struct.PacketParser.html
+//! [`Verifier`]: struct.Verifier.html
+//! [`DetachedVerifier`]: struct.DetachedVerifier.html
+//! [`Decryptor`]: struct.Decryptor.html
+//! [`VerificationHelper`]: trait.VerificationHelper.html
+//! [`VerificationHelper::check`]: trait.VerificationHelper.html#tymethod.check
 //! [verification example]: struct.Verifier.html#example
 
 use std::cmp;
@@ -42,7 +64,10 @@ use {
     packet,
     packet::Signature,
     TPK,
+    crypto,
+    crypto::Password,
     crypto::SessionKey,
+    policy::{Policy, StandardPolicy, UnhashedSubpackets},
     serialize::Serialize,
 };
 use parse::{
@@ -58,6 +83,17 @@ const TRACE : bool = false;
 /// How much data to buffer before giving it to the caller.
 const BUFFER_SIZE: usize = 25 * 1024 * 1024;
 
+/// Drops the unhashed subpackets of `sig` as requested by `policy`.
+fn sanitize_signature(policy: &Policy, sig: &mut Signature) {
+    match policy.unhashed_subpackets() {
+        UnhashedSubpackets::Keep => (),
+        UnhashedSubpackets::DropContradicting => {
+            sig.strip_unhashed_contradictions();
+        },
+        UnhashedSubpackets::DropAll => sig.unhashed_area_mut().clear(),
+    }
+}
+
 /// Verifies a signed OpenPGP message.
 ///
 /// Signature verification requires processing the whole message
@@ -130,6 +166,9 @@ pub struct Verifier<'a, H: VerificationHelper> {
 
     /// Signature verification relative to this time.
     time: time::Tm,
+
+    /// Decides which signatures and keys are acceptable.
+    policy: Box<Policy + 'a>,
 }
 
 /// Contains the result of a signature verification.
@@ -165,13 +204,78 @@ impl<'a> VerificationResult<'a> {
     }
 }
 
+/// Serializes a summary of the result.
+///
+/// The signature and the signer are described by their metadata and
+/// fingerprints, respectively.  Because the result borrows the
+/// signer's TPK, it cannot be deserialized.
+///
+/// Like the JSON descriptions of the FFI, names are in snake case,
+/// e.g. the revocation status is one of `revoked`, `could_be`, or
+/// `not_as_far_as_we_know`.
+#[cfg(feature = "serde")]
+impl<'a> ::serde::Serialize for VerificationResult<'a> {
+    fn serialize<S>(&self, s: S) -> ::std::result::Result<S::Ok, S::Error>
+        where S: ::serde::Serializer
+    {
+        use serde::ser::SerializeStruct;
+        use self::VerificationResult::*;
+
+        let (result, sig, tpk, key, revoked) = match self {
+            &GoodChecksum(ref sig, tpk, key, _, ref revoked) =>
+                ("good_checksum", sig, Some(tpk.fingerprint()),
+                 Some(key.fingerprint()), Some(revoked)),
+            &MissingKey(ref sig) => ("missing_key", sig, None, None, None),
+            &BadChecksum(ref sig) => ("bad_checksum", sig, None, None, None),
+        };
+        let revocation_status = revoked.map(|r| match r {
+            &RevocationStatus::Revoked(_) => "revoked",
+            &RevocationStatus::CouldBe(_) => "could_be",
+            &RevocationStatus::NotAsFarAsWeKnow => "not_as_far_as_we_know",
+        });
+
+        let mut s = s.serialize_struct("VerificationResult", 11)?;
+        s.serialize_field("result", result)?;
+        s.serialize_field("level", &sig.level())?;
+        s.serialize_field("signature_type", &sig.sigtype())?;
+        s.serialize_field("public_key_algorithm", &sig.pk_algo())?;
+        s.serialize_field("hash_algorithm", &sig.hash_algo())?;
+        s.serialize_field("creation_time",
+                          &sig.signature_creation_time()
+                          .map(|t| t.to_timespec().sec))?;
+        s.serialize_field("issuer", &sig.issuer())?;
+        s.serialize_field("issuer_fingerprint", &sig.issuer_fingerprint())?;
+        s.serialize_field("tpk_fingerprint", &tpk)?;
+        s.serialize_field("key_fingerprint", &key)?;
+        s.serialize_field("revocation_status", &revocation_status)?;
+        s.end()
+    }
+}
+
 /// Communicates the message structure to the VerificationHelper.
 #[derive(Debug)]
-pub struct MessageStructure<'a>(Vec<MessageLayer<'a>>);
+pub struct MessageStructure<'a>(Vec<MessageLayer<'a>>, bool);
 
 impl<'a> MessageStructure<'a> {
-    fn new() -> Self {
-        MessageStructure(Vec::new())
+    fn new(literal_metadata_authenticated: bool) -> Self {
+        MessageStructure(Vec::new(), literal_metadata_authenticated)
+    }
+
+    /// Returns whether the literal data packet's metadata is
+    /// authenticated.
+    ///
+    /// Signatures do not cover the format, file name, and date
+    /// stored in the literal data packet.  This metadata is only
+    /// authenticated if the literal data packet is inside an AEAD
+    /// encrypted container, which is authenticated before any of its
+    /// content is parsed.  Callers should not trust the metadata,
+    /// notably the file name, if this returns `false`.
+    ///
+    /// Note that the MDC of an SEIP container is only checked once
+    /// the whole message has been read, long after the metadata has
+    /// been parsed.  Therefore, we do not consider it authenticated.
+    pub fn literal_metadata_authenticated(&self) -> bool {
+        self.1
     }
 
     fn new_compression_layer(&mut self, algo: CompressionAlgorithm) {
@@ -194,13 +298,22 @@ impl<'a> MessageStructure<'a> {
         })
     }
 
+    fn new_literal_layer(&mut self, format: DataFormat,
+                         filename: Option<Vec<u8>>, date: Option<time::Tm>) {
+        self.0.push(MessageLayer::Literal {
+            format: format,
+            filename: filename,
+            date: date,
+        })
+    }
+
     fn push_verification_result(&mut self, sig: VerificationResult<'a>) {
         if let Some(MessageLayer::SignatureGroup { ref mut results }) =
             self.0.iter_mut().last()
         {
             results.push(sig);
         } else {
-            panic!("cannot push to encryption or compression layer");
+            panic!("cannot push to encryption, compression, or literal layer");
         }
     }
 
@@ -218,9 +331,25 @@ impl<'a> Iterator for MessageStructureIter<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         self.0.next()
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
 }
 
+impl<'a> ExactSizeIterator for MessageStructureIter<'a> {}
+
 /// Represents a layer of the message structure.
+///
+/// The layers are ordered from the outermost to the innermost layer.
+/// This allows enforcing structural policies, like requiring that
+/// signatures are inside an encryption container.
+///
+/// A signature group precedes the layers it covers.  This is true
+/// for one-pass signed messages, where the signatures follow the
+/// signed data, as well as for messages where bare signatures
+/// precede the signed data.  Hence, the last layer is always the
+/// literal data packet.
 #[derive(Debug)]
 pub enum MessageLayer<'a> {
     /// Represents an compression container.
@@ -239,7 +368,21 @@ pub enum MessageLayer<'a> {
     SignatureGroup {
         /// The results of the signature verifications.
         results: Vec<VerificationResult<'a>>,
-    }
+    },
+    /// Represents the literal data packet.
+    ///
+    /// Note that this metadata is not covered by signatures.  See
+    /// [`MessageStructure::literal_metadata_authenticated`].
+    ///
+    /// [`MessageStructure::literal_metadata_authenticated`]: struct.MessageStructure.html#method.literal_metadata_authenticated
+    Literal {
+        /// The format of the data.
+        format: DataFormat,
+        /// The file name, if any.
+        filename: Option<Vec<u8>>,
+        /// The date, if any.
+        date: Option<time::Tm>,
+    },
 }
 
 /// Internal version of the message structure.
@@ -258,6 +401,10 @@ struct IMessageStructure {
     // false.  To do that, we keep track of the fact that we saw such
     // a OPS packet.
     sig_group_counter: usize,
+
+    // Whether the literal data packet is inside an AEAD encrypted
+    // container.
+    literal_metadata_authenticated: bool,
 }
 
 impl IMessageStructure {
@@ -265,9 +412,25 @@ impl IMessageStructure {
         IMessageStructure {
             layers: Vec::new(),
             sig_group_counter: 0,
+            literal_metadata_authenticated: false,
         }
     }
 
+    /// Records that we encountered the literal data packet.
+    fn literal(&mut self, literal: &Literal) {
+        self.insert_missing_signature_group();
+        self.literal_metadata_authenticated =
+            self.layers.iter().any(|l| match l {
+                IMessageLayer::Encryption { aead_algo: Some(_), .. } => true,
+                _ => false,
+            });
+        self.layers.push(IMessageLayer::Literal {
+            format: literal.format(),
+            filename: literal.filename().map(|f| f.to_vec()),
+            date: literal.date().cloned(),
+        });
+    }
+
     fn new_compression_layer(&mut self, algo: CompressionAlgorithm) {
         self.insert_missing_signature_group();
         self.layers.push(IMessageLayer::Compression {
@@ -362,7 +525,12 @@ enum IMessageLayer {
     SignatureGroup {
         sigs: Vec<Signature>,
         count: usize,
-    }
+    },
+    Literal {
+        format: DataFormat,
+        filename: Option<Vec<u8>>,
+        date: Option<time::Tm>,
+    },
 }
 
 /// Helper for signature verification.
@@ -398,12 +566,24 @@ impl<'a, H: VerificationHelper> Verifier<'a, H> {
     pub fn from_reader<R, T>(reader: R, helper: H, t: T)
                           -> Result<Verifier<'a, H>>
         where R: io::Read + 'a, T: Into<Option<time::Tm>>
+    {
+        Self::from_reader_with_policy(reader, helper, t, StandardPolicy::new())
+    }
+
+    /// Creates a `Verifier` from the given reader using `policy`.
+    ///
+    /// Signature verifications are done relative to time `t`, or the
+    /// current time, if `t` is `None`.
+    pub fn from_reader_with_policy<R, T, P>(reader: R, helper: H, t: T,
+                                            policy: P)
+                                            -> Result<Verifier<'a, H>>
+        where R: io::Read + 'a, T: Into<Option<time::Tm>>, P: Policy + 'a
     {
         let t = t.into().unwrap_or_else(time::now_utc);
         Verifier::from_buffered_reader(
             Box::new(buffered_reader::Generic::with_cookie(reader, None,
                                                         Default::default())),
-            helper, t)
+            helper, t, Box::new(policy))
     }
 
     /// Creates a `Verifier` from the given file.
@@ -413,12 +593,25 @@ impl<'a, H: VerificationHelper> Verifier<'a, H> {
     pub fn from_file<P, T>(path: P, helper: H, t: T) -> Result<Verifier<'a, H>>
         where P: AsRef<Path>,
               T: Into<Option<time::Tm>>
+    {
+        Self::from_file_with_policy(path, helper, t, StandardPolicy::new())
+    }
+
+    /// Creates a `Verifier` from the given file using `policy`.
+    ///
+    /// Signature verifications are done relative to time `t`, or the
+    /// current time, if `t` is `None`.
+    pub fn from_file_with_policy<P, T, Q>(path: P, helper: H, t: T, policy: Q)
+                                          -> Result<Verifier<'a, H>>
+        where P: AsRef<Path>,
+              T: Into<Option<time::Tm>>,
+              Q: Policy + 'a
     {
         let t = t.into().unwrap_or_else(time::now_utc);
         Verifier::from_buffered_reader(
             Box::new(buffered_reader::File::with_cookie(path,
                                                      Default::default())?),
-            helper, t)
+            helper, t, Box::new(policy))
     }
 
     /// Creates a `Verifier` from the given buffer.
@@ -428,12 +621,24 @@ impl<'a, H: VerificationHelper> Verifier<'a, H> {
     pub fn from_bytes<T>(bytes: &'a [u8], helper: H, t: T)
                          -> Result<Verifier<'a, H>>
         where T: Into<Option<time::Tm>>
+    {
+        Self::from_bytes_with_policy(bytes, helper, t, StandardPolicy::new())
+    }
+
+    /// Creates a `Verifier` from the given buffer using `policy`.
+    ///
+    /// Signature verifications are done relative to time `t`, or the
+    /// current time, if `t` is `None`.
+    pub fn from_bytes_with_policy<T, P>(bytes: &'a [u8], helper: H, t: T,
+                                        policy: P)
+                                        -> Result<Verifier<'a, H>>
+        where T: Into<Option<time::Tm>>, P: Policy + 'a
     {
         let t = t.into().unwrap_or_else(time::now_utc);
         Verifier::from_buffered_reader(
             Box::new(buffered_reader::Memory::with_cookie(bytes,
                                                        Default::default())),
-            helper, t)
+            helper, t, Box::new(policy))
     }
 
     /// Returns a reference to the helper.
@@ -464,7 +669,8 @@ impl<'a, H: VerificationHelper> Verifier<'a, H> {
     /// Signature verifications are done relative to time `t`, or the
     /// current time, if `t` is `None`.
     pub(crate) fn from_buffered_reader(bio: Box<BufferedReader<Cookie> + 'a>,
-                                       helper: H, t: time::Tm)
+                                       helper: H, t: time::Tm,
+                                       policy: Box<Policy + 'a>)
                                        -> Result<Verifier<'a, H>>
     {
         let mut ppr = PacketParser::from_buffered_reader(bio)?;
@@ -477,6 +683,7 @@ impl<'a, H: VerificationHelper> Verifier<'a, H> {
             structure: IMessageStructure::new(),
             reserve: None,
             time: t,
+            policy: policy,
         };
 
         let mut issuers = Vec::new();
@@ -485,6 +692,7 @@ impl<'a, H: VerificationHelper> Verifier<'a, H> {
             if let Err(err) = pp.possible_message() {
                 return Err(err.context("Malformed OpenPGP message").into());
             }
+            v.policy.packet(&pp.packet)?;
 
             match pp.packet {
                 Packet::CompressedData(ref p) =>
@@ -493,15 +701,17 @@ impl<'a, H: VerificationHelper> Verifier<'a, H> {
                     v.structure.push_ops(ops);
                     issuers.push(ops.issuer().clone());
                 },
-                Packet::Literal(_) => {
-                    v.structure.insert_missing_signature_group();
+                Packet::Literal(ref l) => {
+                    v.structure.literal(l);
                     // Query keys.
                     v.tpks = v.helper.get_public_keys(&issuers)?;
 
                     for (i, tpk) in v.tpks.iter().enumerate() {
+                        let policy = &v.policy;
                         let can_sign = |key: &Key, sig: Option<&Signature>| -> bool {
                             if let Some(sig) = sig {
                                 sig.key_flags().can_sign()
+                                    && policy.key(key).is_ok()
                                 // Check expiry.
                                     && sig.signature_alive_at(t)
                                     && sig.key_alive_at(key, t)
@@ -532,7 +742,9 @@ impl<'a, H: VerificationHelper> Verifier<'a, H> {
             }
 
             let (p, ppr_tmp) = pp.recurse()?;
-            if let Packet::Signature(sig) = p {
+            if let Packet::Signature(mut sig) = p {
+                sanitize_signature(&v.policy, &mut sig);
+
                 // The following structure is allowed:
                 //
                 //   SIG LITERAL
@@ -562,7 +774,8 @@ impl<'a, H: VerificationHelper> Verifier<'a, H> {
     /// verification.
     fn push_sig(&mut self, p: Packet) -> Result<()> {
         match p {
-            Packet::Signature(sig) => {
+            Packet::Signature(mut sig) => {
+                sanitize_signature(&self.policy, &mut sig);
                 self.structure.push_signature(sig);
             },
             _ => (),
@@ -589,6 +802,7 @@ impl<'a, H: VerificationHelper> Verifier<'a, H> {
                         return Err(err.context(
                             "Malformed OpenPGP message").into());
                     }
+                    self.policy.packet(&pp.packet)?;
 
                     let (p, ppr_tmp) = pp.recurse()?;
                     self.push_sig(p)?;
@@ -596,16 +810,19 @@ impl<'a, H: VerificationHelper> Verifier<'a, H> {
                 }
 
                 // Verify the signatures.
-                let mut results = MessageStructure::new();
-                for layer in ::std::mem::replace(&mut self.structure,
-                                                 IMessageStructure::new())
-                    .layers.into_iter()
+                let structure = ::std::mem::replace(&mut self.structure,
+                                                    IMessageStructure::new());
+                let mut results = MessageStructure::new(
+                    structure.literal_metadata_authenticated);
+                for layer in structure.layers.into_iter()
                 {
                     match layer {
                         IMessageLayer::Compression { algo } =>
                             results.new_compression_layer(algo),
                         IMessageLayer::Encryption { .. } =>
                             unreachable!("not decrypting messages"),
+                        IMessageLayer::Literal { format, filename, date } =>
+                            results.new_literal_layer(format, filename, date),
                         IMessageLayer::SignatureGroup { sigs, .. } => {
                             results.new_signature_group();
                             for sig in sigs.into_iter() {
@@ -620,6 +837,8 @@ impl<'a, H: VerificationHelper> Verifier<'a, H> {
                                                 .unwrap();
                                             if sig.verify(key).unwrap_or(false)
                                                 && sig.signature_alive_at(self.time)
+                                                && self.policy.signature(&sig)
+                                                .is_ok()
                                             {
                                                 VerificationResult::GoodChecksum
                                                     (sig, tpk, key, binding,
@@ -952,13 +1171,27 @@ impl DetachedVerifier {
                                            -> Result<Verifier<'a, H>>
         where R: io::Read + 'a, S: io::Read + 's, H: VerificationHelper,
               T: Into<Option<time::Tm>>
+    {
+        Self::from_reader_with_policy(signature_reader, reader, helper, t,
+                                      StandardPolicy::new())
+    }
+
+    /// Creates a `Verifier` from the given readers using `policy`.
+    ///
+    /// Signature verifications are done relative to time `t`, or the
+    /// current time, if `t` is `None`.
+    pub fn from_reader_with_policy<'a, 's, H, R, S, T, P>(
+        signature_reader: S, reader: R, helper: H, t: T, policy: P)
+        -> Result<Verifier<'a, H>>
+        where R: io::Read + 'a, S: io::Read + 's, H: VerificationHelper,
+              T: Into<Option<time::Tm>>, P: Policy + 'a
     {
         let t = t.into().unwrap_or_else(time::now_utc);
         Self::from_buffered_reader(
             Box::new(buffered_reader::Generic::with_cookie(signature_reader, None,
                                                         Default::default())),
             Box::new(buffered_reader::Generic::new(reader, None)),
-            helper, t)
+            helper, t, Box::new(policy))
     }
 
     /// Creates a `Verifier` from the given files.
@@ -970,13 +1203,27 @@ impl DetachedVerifier {
                                      -> Result<Verifier<'a, H>>
         where P: AsRef<Path>, S: AsRef<Path>, H: VerificationHelper,
               T: Into<Option<time::Tm>>
+    {
+        Self::from_file_with_policy(signature_path, path, helper, t,
+                                    StandardPolicy::new())
+    }
+
+    /// Creates a `Verifier` from the given files using `policy`.
+    ///
+    /// Signature verifications are done relative to time `t`, or the
+    /// current time, if `t` is `None`.
+    pub fn from_file_with_policy<'a, H, P, S, T, Q>(
+        signature_path: S, path: P, helper: H, t: T, policy: Q)
+        -> Result<Verifier<'a, H>>
+        where P: AsRef<Path>, S: AsRef<Path>, H: VerificationHelper,
+              T: Into<Option<time::Tm>>, Q: Policy + 'a
     {
         let t = t.into().unwrap_or_else(time::now_utc);
         Self::from_buffered_reader(
             Box::new(buffered_reader::File::with_cookie(signature_path,
                                                      Default::default())?),
             Box::new(buffered_reader::File::open(path)?),
-            helper, t)
+            helper, t, Box::new(policy))
     }
 
     /// Creates a `Verifier` from the given buffers.
@@ -987,13 +1234,28 @@ impl DetachedVerifier {
                                     helper: H, t: T)
                                     -> Result<Verifier<'a, H>>
         where H: VerificationHelper, T: Into<Option<time::Tm>>
+    {
+        Self::from_bytes_with_policy(signature_bytes, bytes, helper, t,
+                                     StandardPolicy::new())
+    }
+
+    /// Creates a `Verifier` from the given buffers using `policy`.
+    ///
+    /// Signature verifications are done relative to time `t`, or the
+    /// current time, if `t` is `None`.
+    pub fn from_bytes_with_policy<'a, 's, H, T, P>(
+        signature_bytes: &'s [u8], bytes: &'a [u8], helper: H, t: T,
+        policy: P)
+        -> Result<Verifier<'a, H>>
+        where H: VerificationHelper, T: Into<Option<time::Tm>>,
+              P: Policy + 'a
     {
         let t = t.into().unwrap_or_else(time::now_utc);
         Self::from_buffered_reader(
             Box::new(buffered_reader::Memory::with_cookie(signature_bytes,
                                                        Default::default())),
             Box::new(buffered_reader::Memory::new(bytes)),
-            helper, t)
+            helper, t, Box::new(policy))
     }
 
     /// Creates the `Verifier`, and buffers the data up to `BUFFER_SIZE`.
@@ -1003,7 +1265,7 @@ impl DetachedVerifier {
     pub(crate) fn from_buffered_reader<'a, 's, H>
         (signature_bio: Box<BufferedReader<Cookie> + 's>,
          reader: Box<'a + BufferedReader<()>>,
-         helper: H, t: time::Tm)
+         helper: H, t: time::Tm, policy: Box<Policy + 'a>)
          -> Result<Verifier<'a, H>>
         where H: VerificationHelper
     {
@@ -1011,13 +1273,23 @@ impl DetachedVerifier {
             Box::new(buffered_reader::Generic::with_cookie(
                 Transformer::new(signature_bio, reader)?,
                 None, Default::default())),
-            helper, t)
+            helper, t, policy)
     }
 }
 
 /// Decrypts and verifies an encrypted and optionally signed OpenPGP
 /// message.
 ///
+/// Both SEIP and AED packets are supported.  The session key is
+/// obtained from the [`DecryptionHelper`], which may decrypt one of
+/// the PKESKs using a secret key, one of the SKESKs using a password,
+/// or supply a session key from elsewhere, e.g. a cache.  If the MDC
+/// of an SEIP packet is invalid, reading fails with
+/// `Error::ManipulatedMessage` once the end of the message is
+/// reached.
+///
+///   [`DecryptionHelper`]: trait.DecryptionHelper.html
+///
 /// Signature verification requires processing the whole message
 /// first.  Therefore, OpenPGP implementations supporting streaming
 /// operations necessarily must output unverified data.  This has been
@@ -1098,6 +1370,9 @@ pub struct Decryptor<'a, H: VerificationHelper + DecryptionHelper> {
 
     /// Signature verification relative to this time.
     time: time::Tm,
+
+    /// Decides which signatures, keys, and algorithms are acceptable.
+    policy: Box<Policy + 'a>,
 }
 
 /// Helper for decrypting messages.
@@ -1134,6 +1409,60 @@ pub trait DecryptionHelper {
         where D: FnMut(SymmetricAlgorithm, &SessionKey) -> Result<()>;
 }
 
+/// Decrypts the session key using keys, or a recovery password.
+///
+/// This implements a common strategy for
+/// [`DecryptionHelper::decrypt`].  First, every PKESK addressed to
+/// one of the `decryptors`, or to the wildcard KeyID, is tried.  If
+/// that fails and a `password` is given, the SKESKs are tried.
+/// Hence, messages encrypted using [`Encryptor::with_escrow`] can be
+/// decrypted using either the recipient's keys, or the escrow
+/// password.
+///
+/// Returns the fingerprint of the key that decrypted the session
+/// key, or `None` if the password did.
+///
+///   [`DecryptionHelper::decrypt`]: trait.DecryptionHelper.html#tymethod.decrypt
+///   [`Encryptor::with_escrow`]: ../../serialize/stream/struct.Encryptor.html#method.with_escrow
+pub fn decrypt_session_key<D>(pkesks: &[PKESK], skesks: &[SKESK],
+                              decryptors: &mut [&mut dyn crypto::Decryptor],
+                              password: Option<&Password>,
+                              mut decrypt: D)
+                              -> Result<Option<Fingerprint>>
+    where D: FnMut(SymmetricAlgorithm, &SessionKey) -> Result<()>
+{
+    for pkesk in pkesks {
+        let recipient = pkesk.recipient();
+        for d in decryptors.iter_mut() {
+            if ! recipient.is_wildcard() && *recipient != d.public().keyid() {
+                continue;
+            }
+
+            if pkesk.decrypt(&mut **d)
+                .and_then(|(algo, sk)| decrypt(algo, &sk))
+                .is_ok()
+            {
+                return Ok(Some(d.public().fingerprint()));
+            }
+        }
+    }
+
+    if let Some(password) = password {
+        for skesk in skesks {
+            if skesk.decrypt(password)
+                .and_then(|(algo, sk)| decrypt(algo, &sk))
+                .is_ok()
+            {
+                return Ok(None);
+            }
+        }
+    }
+
+    Err(Error::MissingSessionKey(
+        "Neither the keys nor the password decrypt the message".into())
+        .into())
+}
+
 impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
     /// Creates a `Decryptor` from the given reader.
     ///
@@ -1142,12 +1471,24 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
     pub fn from_reader<R, T>(reader: R, helper: H, t: T)
                           -> Result<Decryptor<'a, H>>
         where R: io::Read + 'a, T: Into<Option<time::Tm>>
+    {
+        Self::from_reader_with_policy(reader, helper, t, StandardPolicy::new())
+    }
+
+    /// Creates a `Decryptor` from the given reader using `policy`.
+    ///
+    /// Signature verifications are done relative to time `t`, or the
+    /// current time, if `t` is `None`.
+    pub fn from_reader_with_policy<R, T, P>(reader: R, helper: H, t: T,
+                                            policy: P)
+                                            -> Result<Decryptor<'a, H>>
+        where R: io::Read + 'a, T: Into<Option<time::Tm>>, P: Policy + 'a
     {
         let t = t.into().unwrap_or_else(time::now_utc);
         Decryptor::from_buffered_reader(
             Box::new(buffered_reader::Generic::with_cookie(reader, None,
                                                         Default::default())),
-            helper, t)
+            helper, t, Box::new(policy))
     }
 
     /// Creates a `Decryptor` from the given file.
@@ -1157,12 +1498,25 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
     pub fn from_file<P, T>(path: P, helper: H, t: T) -> Result<Decryptor<'a, H>>
         where P: AsRef<Path>,
               T: Into<Option<time::Tm>>
+    {
+        Self::from_file_with_policy(path, helper, t, StandardPolicy::new())
+    }
+
+    /// Creates a `Decryptor` from the given file using `policy`.
+    ///
+    /// Signature verifications are done relative to time `t`, or the
+    /// current time, if `t` is `None`.
+    pub fn from_file_with_policy<P, T, Q>(path: P, helper: H, t: T, policy: Q)
+                                          -> Result<Decryptor<'a, H>>
+        where P: AsRef<Path>,
+              T: Into<Option<time::Tm>>,
+              Q: Policy + 'a
     {
         let t = t.into().unwrap_or_else(time::now_utc);
         Decryptor::from_buffered_reader(
             Box::new(buffered_reader::File::with_cookie(path,
                                                      Default::default())?),
-            helper, t)
+            helper, t, Box::new(policy))
     }
 
     /// Creates a `Decryptor` from the given buffer.
@@ -1172,12 +1526,24 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
     pub fn from_bytes<T>(bytes: &'a [u8], helper: H, t: T)
                          -> Result<Decryptor<'a, H>>
         where T: Into<Option<time::Tm>>
+    {
+        Self::from_bytes_with_policy(bytes, helper, t, StandardPolicy::new())
+    }
+
+    /// Creates a `Decryptor` from the given buffer using `policy`.
+    ///
+    /// Signature verifications are done relative to time `t`, or the
+    /// current time, if `t` is `None`.
+    pub fn from_bytes_with_policy<T, P>(bytes: &'a [u8], helper: H, t: T,
+                                        policy: P)
+                                        -> Result<Decryptor<'a, H>>
+        where T: Into<Option<time::Tm>>, P: Policy + 'a
     {
         let t = t.into().unwrap_or_else(time::now_utc);
         Decryptor::from_buffered_reader(
             Box::new(buffered_reader::Memory::with_cookie(bytes,
                                                        Default::default())),
-            helper, t)
+            helper, t, Box::new(policy))
     }
 
     /// Returns a reference to the helper.
@@ -1205,7 +1571,8 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
 
     /// Creates the `Decryptor`, and buffers the data up to `BUFFER_SIZE`.
     pub(crate) fn from_buffered_reader(bio: Box<BufferedReader<Cookie> + 'a>,
-                                       helper: H, t: time::Tm)
+                                       helper: H, t: time::Tm,
+                                       policy: Box<Policy + 'a>)
                                        -> Result<Decryptor<'a, H>>
     {
         tracer!(TRACE, "Decryptor::from_buffered_reader", 0);
@@ -1222,6 +1589,7 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
             structure: IMessageStructure::new(),
             reserve: None,
             time: t,
+            policy: policy,
         };
 
         let mut issuers = Vec::new();
@@ -1235,19 +1603,33 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                 t!("Malformed message: {}", err);
                 return Err(err.context("Malformed OpenPGP message").into());
             }
+            v.policy.packet(&pp.packet)?;
 
             match pp.packet {
                 Packet::CompressedData(ref p) =>
                     v.structure.new_compression_layer(p.algorithm()),
                 Packet::SEIP(_) | Packet::AED(_) => {
                     saw_content = true;
+                    v.policy.aead_algorithm(
+                        if let Packet::AED(ref p) = pp.packet {
+                            Some(p.aead())
+                        } else {
+                            None
+                        })?;
 
                     // Get the symmetric algorithm from the decryption
                     // proxy function.  This is necessary because we
                     // cannot get the algorithm from the SEIP packet.
                     let mut sym_algo = None;
+                    let mut rejected = None;
                     {
+                        let policy = &v.policy;
                         let decryption_proxy = |algo, secret: &SessionKey| {
+                            if let Err(e) = policy.symmetric_algorithm(algo) {
+                                rejected = Some(algo);
+                                return Err(e);
+                            }
+
                             let result = pp.decrypt(algo, secret);
                             if let Ok(_) = result {
                                 sym_algo = Some(algo);
@@ -1260,6 +1642,10 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                                              decryption_proxy)?;
                     }
                     if ! pp.decrypted() {
+                        if let Some(algo) = rejected {
+                            v.policy.symmetric_algorithm(algo)?;
+                        }
+
                         // XXX: That is not quite the right error to return.
                         return Err(
                             Error::InvalidSessionKey("No session key".into())
@@ -1278,15 +1664,17 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                     v.structure.push_ops(ops);
                     issuers.push(ops.issuer().clone());
                 },
-                Packet::Literal(_) => {
-                    v.structure.insert_missing_signature_group();
+                Packet::Literal(ref l) => {
+                    v.structure.literal(l);
                     // Query keys.
                     v.tpks = v.helper.get_public_keys(&issuers)?;
 
                     for (i, tpk) in v.tpks.iter().enumerate() {
+                        let policy = &v.policy;
                         let can_sign = |key: &Key, sig: Option<&Signature>| -> bool {
                             if let Some(sig) = sig {
                                 sig.key_flags().can_sign()
+                                    && policy.key(key).is_ok()
                                 // Check expiry.
                                     && sig.signature_alive_at(t)
                                     && sig.key_alive_at(key, t)
@@ -1323,7 +1711,8 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
             match p {
                 Packet::PKESK(pkesk) => pkesks.push(pkesk),
                 Packet::SKESK(skesk) => skesks.push(skesk),
-                Packet::Signature(sig) => {
+                Packet::Signature(mut sig) => {
+                    sanitize_signature(&v.policy, &mut sig);
                     if ! saw_content {
                         // The following structure is allowed:
                         //
@@ -1354,7 +1743,8 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
     /// verification.
     fn push_sig(&mut self, p: Packet) -> Result<()> {
         match p {
-            Packet::Signature(sig) => {
+            Packet::Signature(mut sig) => {
+                sanitize_signature(&self.policy, &mut sig);
                 self.structure.push_signature(sig);
             },
             _ => (),
@@ -1390,6 +1780,7 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                         return Err(err.context(
                             "Malformed OpenPGP message").into());
                     }
+                    self.policy.packet(&pp.packet)?;
 
                     match pp.packet {
                         Packet::MDC(ref mdc) => if ! mdc.valid() {
@@ -1415,16 +1806,19 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
 
     /// Verifies the signatures.
     fn verify_signatures(&mut self) -> Result<()> {
-        let mut results = MessageStructure::new();
-        for layer in ::std::mem::replace(&mut self.structure,
-                                         IMessageStructure::new())
-            .layers.into_iter()
+        let structure = ::std::mem::replace(&mut self.structure,
+                                            IMessageStructure::new());
+        let mut results = MessageStructure::new(
+            structure.literal_metadata_authenticated);
+        for layer in structure.layers.into_iter()
         {
             match layer {
                 IMessageLayer::Compression { algo } =>
                     results.new_compression_layer(algo),
                 IMessageLayer::Encryption { sym_algo, aead_algo } =>
                     results.new_encryption_layer(sym_algo, aead_algo),
+                IMessageLayer::Literal { format, filename, date } =>
+                    results.new_literal_layer(format, filename, date),
                 IMessageLayer::SignatureGroup { sigs, .. } => {
                     results.new_signature_group();
                     for sig in sigs.into_iter() {
@@ -1435,7 +1829,8 @@ impl<'a, H: VerificationHelper + DecryptionHelper> Decryptor<'a, H> {
                                     let (binding, revocation, key)
                                         = tpk.keys_all().nth(*j).unwrap();
                                     if sig.verify(key).unwrap_or(false) &&
-                                        sig.signature_alive_at(self.time)
+                                        sig.signature_alive_at(self.time) &&
+                                        self.policy.signature(&sig).is_ok()
                                     {
                                         // Check intended recipients.
                                         if let Some(identity) =
@@ -1594,6 +1989,7 @@ mod test {
                             }
                         }
                     MessageLayer::Compression { .. } => (),
+                    MessageLayer::Literal { .. } => (),
                     _ => unreachable!(),
                 }
             }
@@ -1691,6 +2087,98 @@ mod test {
         }
     }
 
+    /// Tests the layers of one-pass signed messages and messages
+    /// with bare signatures.
+    #[test]
+    fn message_structure() {
+        use constants::{DataFormat, HashAlgorithm, SignatureType};
+        use packet::{Literal, signature};
+        use tpk::{TPKBuilder, CipherSuite};
+        use serialize::Serialize;
+        use serialize::stream::{LiteralWriter, Signer, Message};
+        use std::io::Write;
+
+        /// Records the kinds of the layers.
+        struct SHelper(Vec<TPK>, Vec<&'static str>);
+        impl VerificationHelper for SHelper {
+            fn get_public_keys(&mut self, _ids: &[KeyID]) -> Result<Vec<TPK>> {
+                Ok(self.0.clone())
+            }
+
+            fn check(&mut self, structure: &MessageStructure) -> Result<()> {
+                for layer in structure.iter() {
+                    self.1.push(match layer {
+                        MessageLayer::Compression { .. } => "compression",
+                        MessageLayer::Encryption { .. } => "encryption",
+                        MessageLayer::SignatureGroup { ref results } => {
+                            assert_eq!(results.len(), 1);
+                            if let VerificationResult::GoodChecksum(..) =
+                                results[0]
+                            {
+                            } else {
+                                panic!("Signature did not verify");
+                            }
+                            "signatures"
+                        },
+                        MessageLayer::Literal { .. } => "literal",
+                    });
+                }
+                Ok(())
+            }
+        }
+        impl DecryptionHelper for SHelper {
+            fn decrypt<D>(&mut self, _: &[PKESK], _: &[SKESK], _: D)
+                          -> Result<Option<Fingerprint>>
+                where D: FnMut(SymmetricAlgorithm, &SessionKey) -> Result<()>
+            {
+                unreachable!();
+            }
+        }
+
+        let (tpk, _) = TPKBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_signing_subkey()
+            .generate().unwrap();
+        let key = tpk.keys_all().signing_capable().nth(0).unwrap().2;
+        let mut keypair = key.clone().into_keypair().unwrap();
+        let data = b"Hello, world!";
+
+        // OPS LITERAL SIG
+        let mut one_pass = vec![];
+        {
+            let m = Message::new(&mut one_pass);
+            let signer = Signer::new(m, vec![&mut keypair], None).unwrap();
+            let mut ls = LiteralWriter::new(signer, DataFormat::Binary,
+                                            None, None).unwrap();
+            ls.write_all(data).unwrap();
+            ls.finalize().unwrap();
+        }
+
+        // SIG LITERAL
+        let sig = signature::Builder::new(SignatureType::Binary)
+            .set_issuer_fingerprint(key.fingerprint()).unwrap()
+            .set_issuer(key.keyid()).unwrap()
+            .sign_message(&mut keypair, HashAlgorithm::SHA512, data)
+            .unwrap();
+        let mut literal = Literal::new(DataFormat::Binary);
+        literal.set_body(data.to_vec());
+        let mut bare = vec![];
+        Packet::Signature(sig).serialize(&mut bare).unwrap();
+        Packet::Literal(literal).serialize(&mut bare).unwrap();
+
+        for message in &[one_pass, bare] {
+            let v = Verifier::from_bytes(
+                message, SHelper(vec![tpk.clone()], vec![]), None).unwrap();
+            assert!(v.message_processed());
+            assert_eq!(v.helper_ref().1, &["signatures", "literal"]);
+
+            let v = Decryptor::from_bytes(
+                message, SHelper(vec![tpk.clone()], vec![]), None).unwrap();
+            assert!(v.message_processed());
+            assert_eq!(v.helper_ref().1, &["signatures", "literal"]);
+        }
+    }
+
     /// Tests the order of signatures given to
     /// VerificationHelper::check().
     #[test]
@@ -1702,7 +2190,7 @@ mod test {
             }
 
             fn check(&mut self, structure: &MessageStructure) -> Result<()> {
-                assert_eq!(structure.iter().count(), 2);
+                assert_eq!(structure.iter().count(), 3);
                 for (i, layer) in structure.iter().enumerate() {
                     match layer {
                         MessageLayer::SignatureGroup { ref results } => {
@@ -1721,6 +2209,10 @@ mod test {
                                 );
                             }
                         },
+                        MessageLayer::Literal { format, .. } => {
+                            assert_eq!(i, 2);
+                            assert_eq!(*format, DataFormat::Binary);
+                        },
                         _ => unreachable!(),
                     }
                 }
@@ -1911,4 +2403,54 @@ mod test {
         assert!(v.helper_ref().unknown == 0);
         assert!(v.helper_ref().error == 0);
     }
+
+    #[test]
+    fn literal_metadata_authenticated() {
+        use crypto::Password;
+
+        struct Helper {
+            password: Password,
+            authenticated: Option<bool>,
+        }
+
+        impl VerificationHelper for Helper {
+            fn get_public_keys(&mut self, _ids: &[KeyID]) -> Result<Vec<TPK>> {
+                Ok(Vec::new())
+            }
+
+            fn check(&mut self, structure: &MessageStructure) -> Result<()> {
+                self.authenticated =
+                    Some(structure.literal_metadata_authenticated());
+                Ok(())
+            }
+        }
+
+        impl DecryptionHelper for Helper {
+            fn decrypt<D>(&mut self, _: &[PKESK], skesks: &[SKESK],
+                          mut decrypt: D) -> Result<Option<Fingerprint>>
+                where D: FnMut(SymmetricAlgorithm, &SessionKey) -> Result<()>
+            {
+                let (algo, key) = skesks[0].decrypt(&self.password)?;
+                decrypt(algo, &key)?;
+                Ok(None)
+            }
+        }
+
+        for (f, password, authenticated) in vec![
+            ("messages/aed/msg-aes128-eax-chunk-size-64-password-123.pgp",
+             "123", true),
+            ("messages/encrypted-aes128-password-123456789.gpg",
+             "123456789", false),
+        ] {
+            let h = Helper {
+                password: password.into(),
+                authenticated: None,
+            };
+            let mut d = Decryptor::from_bytes(::tests::file(f), h, None)
+                .unwrap();
+            let mut content = Vec::new();
+            d.read_to_end(&mut content).unwrap();
+            assert_eq!(d.helper_ref().authenticated, Some(authenticated));
+        }
+    }
 }
@@ -0,0 +1,535 @@
+//! Policies for accepting OpenPGP artifacts.
+//!
+//! Whether a signature made using SHA-1 can be relied upon, which
+//! symmetric algorithms may be used, or whether messages must be
+//! protected using AEAD is not a technical question, but a matter of
+//! policy.  The [`Policy`] trait makes these decisions explicit.  A
+//! policy is consulted by the [`Verifier`], the [`Decryptor`], the
+//! [`Encryptor`], and when canonicalizing TPKs (see
+//! [`TPK::with_policy`]).
+//!
+//! By default, [`StandardPolicy`] is used.  [`NullPolicy`] accepts
+//! everything, and is only useful for inspecting data.
+//!
+//!   [`Policy`]: trait.Policy.html
+//!   [`Verifier`]: ../parse/stream/struct.Verifier.html
+//!   [`Decryptor`]: ../parse/stream/struct.Decryptor.html
+//!   [`Encryptor`]: ../serialize/stream/struct.Encryptor.html
+//!   [`TPK::with_policy`]: ../struct.TPK.html#method.with_policy
+//!   [`StandardPolicy`]: struct.StandardPolicy.html
+//!   [`NullPolicy`]: struct.NullPolicy.html
+
+use std::fmt;
+use time;
+
+use {
+    Error,
+    Packet,
+    Result,
+};
+use constants::{
+    AEADAlgorithm,
+    HashAlgorithm,
+    PublicKeyAlgorithm,
+    SymmetricAlgorithm,
+};
+use packet::{Key, Signature};
+use packet::signature::subpacket::{
+    describe_private_subpacket,
+    Subpacket,
+    SubpacketTag,
+    SubpacketValue,
+};
+
+/// What to do with the unhashed subpackets of signatures.
+///
+/// The unhashed area is not protected by the signature, hence anyone
+/// can modify it in transit.  See [`Policy::unhashed_subpackets`].
+///
+///   [`Policy::unhashed_subpackets`]: trait.Policy.html#method.unhashed_subpackets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnhashedSubpackets {
+    /// Keeps all unhashed subpackets.
+    Keep,
+    /// Drops unhashed subpackets that contradict the hashed area.
+    ///
+    /// See [`Signature4::unhashed_contradictions`].
+    ///
+    ///   [`Signature4::unhashed_contradictions`]: ../packet/signature/struct.Signature4.html#method.unhashed_contradictions
+    DropContradicting,
+    /// Drops all unhashed subpackets.
+    ///
+    /// Signatures are then looked up using the hashed Issuer and
+    /// Issuer Fingerprint subpackets only.
+    DropAll,
+}
+
+/// Decides whether OpenPGP artifacts are acceptable.
+///
+/// Every method returns `Ok(())` if the artifact is acceptable, and
+/// an error, usually `Error::PolicyViolation`, otherwise.  The
+/// default implementations accept everything.
+pub trait Policy: fmt::Debug {
+    /// Decides whether `sig` can be relied upon.
+    ///
+    /// This is consulted for self-signatures when canonicalizing
+    /// TPKs, and for signatures over messages.
+    fn signature(&self, _sig: &Signature) -> Result<()> {
+        Ok(())
+    }
+
+    /// Decides whether `key` may be used.
+    fn key(&self, _key: &Key) -> Result<()> {
+        Ok(())
+    }
+
+    /// Decides whether `packet` may be processed.
+    ///
+    /// This can be used to reject unexpected packet versions.
+    fn packet(&self, _packet: &Packet) -> Result<()> {
+        Ok(())
+    }
+
+    /// Decides whether data may be encrypted or decrypted using
+    /// `algo`.
+    fn symmetric_algorithm(&self, _algo: SymmetricAlgorithm) -> Result<()> {
+        Ok(())
+    }
+
+    /// Decides whether a message may be protected using `algo`.
+    ///
+    /// `None` refers to a *Symmetrically Encrypted and Integrity
+    /// Protected Data Packet*, which is protected using a
+    /// *Modification Detection Code*.
+    fn aead_algorithm(&self, _algo: Option<AEADAlgorithm>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Decides what to do with the unhashed subpackets of signatures.
+    ///
+    /// The [`Verifier`] and the [`Decryptor`] apply this to every
+    /// signature before looking up the issuer, and hand the modified
+    /// signatures to the application.
+    ///
+    ///   [`Verifier`]: ../parse/stream/struct.Verifier.html
+    ///   [`Decryptor`]: ../parse/stream/struct.Decryptor.html
+    fn unhashed_subpackets(&self) -> UnhashedSubpackets {
+        UnhashedSubpackets::Keep
+    }
+}
+
+impl<'a, P: Policy + ?Sized> Policy for &'a P {
+    fn signature(&self, sig: &Signature) -> Result<()> {
+        (**self).signature(sig)
+    }
+
+    fn key(&self, key: &Key) -> Result<()> {
+        (**self).key(key)
+    }
+
+    fn packet(&self, packet: &Packet) -> Result<()> {
+        (**self).packet(packet)
+    }
+
+    fn symmetric_algorithm(&self, algo: SymmetricAlgorithm) -> Result<()> {
+        (**self).symmetric_algorithm(algo)
+    }
+
+    fn aead_algorithm(&self, algo: Option<AEADAlgorithm>) -> Result<()> {
+        (**self).aead_algorithm(algo)
+    }
+
+    fn unhashed_subpackets(&self) -> UnhashedSubpackets {
+        (**self).unhashed_subpackets()
+    }
+}
+
+/// The default policy.
+///
+/// By default, signatures using MD5 are rejected, only the packet
+/// versions specified in RFC 4880 and the AEAD draft are accepted,
+/// and all algorithms defined there are allowed.  Messages need not
+/// be protected using AEAD.
+///
+/// Signatures with critical subpackets that we do not understand are
+/// rejected, as required by [Section 5.2.3.1 of RFC 4880].  This
+/// includes critical notations, because we do not interpret any
+/// notation.  Applications that understand additional subpackets or
+/// notations can accept them using [`accept_critical_subpacket`]
+/// and [`accept_critical_notation`].  They are then responsible for
+/// honoring them.
+///
+/// Unhashed subpackets that contradict the hashed area, like an
+/// Issuer subpacket that does not match the Issuer Fingerprint, are
+/// dropped.  This can be changed using [`handle_unhashed_subpackets`].
+///
+///   [Section 5.2.3.1 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-5.2.3.1
+///   [`accept_critical_subpacket`]: #method.accept_critical_subpacket
+///   [`accept_critical_notation`]: #method.accept_critical_notation
+///   [`handle_unhashed_subpackets`]: #method.handle_unhashed_subpackets
+///
+/// # Example
+///
+/// ```
+/// extern crate sequoia_openpgp as openpgp;
+/// extern crate time;
+/// use openpgp::constants::{HashAlgorithm, SymmetricAlgorithm};
+/// use openpgp::policy::StandardPolicy;
+///
+/// # fn main() {
+/// // Reject SHA-1 signatures made after 2013, and CAST5.
+/// let cutoff = time::strptime("2013-01-01", "%Y-%m-%d").unwrap();
+/// let policy = StandardPolicy::new()
+///     .reject_hash_at(HashAlgorithm::SHA1, cutoff)
+///     .symmetric_algos(&[SymmetricAlgorithm::AES256,
+///                        SymmetricAlgorithm::AES128]);
+/// # let _ = policy;
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct StandardPolicy {
+    /// Rejected hash algorithms, and the time from which on they
+    /// are rejected, or `None` if they are always rejected.
+    hash_cutoffs: Vec<(HashAlgorithm, Option<time::Tm>)>,
+    pk_algos: Vec<PublicKeyAlgorithm>,
+    symmetric_algos: Vec<SymmetricAlgorithm>,
+    aead_algos: Vec<AEADAlgorithm>,
+    require_aead: bool,
+    /// Critical subpackets understood by the application.
+    critical_subpackets: Vec<SubpacketTag>,
+    /// Critical notations understood by the application.
+    critical_notations: Vec<String>,
+    unhashed_subpackets: UnhashedSubpackets,
+}
+
+impl Default for StandardPolicy {
+    fn default() -> Self {
+        #[allow(deprecated)]
+        StandardPolicy {
+            hash_cutoffs: vec![(HashAlgorithm::MD5, None)],
+            pk_algos: vec![
+                PublicKeyAlgorithm::RSAEncryptSign,
+                PublicKeyAlgorithm::RSAEncrypt,
+                PublicKeyAlgorithm::RSASign,
+                PublicKeyAlgorithm::ElgamalEncrypt,
+                PublicKeyAlgorithm::DSA,
+                PublicKeyAlgorithm::ECDH,
+                PublicKeyAlgorithm::ECDSA,
+                PublicKeyAlgorithm::ElgamalEncryptSign,
+                PublicKeyAlgorithm::EdDSA,
+            ],
+            symmetric_algos: vec![
+                SymmetricAlgorithm::IDEA,
+                SymmetricAlgorithm::TripleDES,
+                SymmetricAlgorithm::CAST5,
+                SymmetricAlgorithm::Blowfish,
+                SymmetricAlgorithm::AES128,
+                SymmetricAlgorithm::AES192,
+                SymmetricAlgorithm::AES256,
+                SymmetricAlgorithm::Twofish,
+                SymmetricAlgorithm::Camellia128,
+                SymmetricAlgorithm::Camellia192,
+                SymmetricAlgorithm::Camellia256,
+            ],
+            aead_algos: vec![AEADAlgorithm::EAX, AEADAlgorithm::OCB],
+            require_aead: false,
+            critical_subpackets: Vec::new(),
+            critical_notations: Vec::new(),
+            unhashed_subpackets: UnhashedSubpackets::DropContradicting,
+        }
+    }
+}
+
+impl StandardPolicy {
+    /// Returns the default policy.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Always rejects signatures using `algo`.
+    pub fn reject_hash(self, algo: HashAlgorithm) -> Self {
+        self.set_hash_cutoff(algo, None)
+    }
+
+    /// Rejects signatures using `algo` created at or after `cutoff`.
+    ///
+    /// Signatures without a creation time are always rejected.
+    pub fn reject_hash_at(self, algo: HashAlgorithm, cutoff: time::Tm)
+                          -> Self {
+        self.set_hash_cutoff(algo, Some(cutoff))
+    }
+
+    /// Accepts signatures using `algo`.
+    pub fn accept_hash(mut self, algo: HashAlgorithm) -> Self {
+        self.hash_cutoffs.retain(|&(a, _)| a != algo);
+        self
+    }
+
+    fn set_hash_cutoff(mut self, algo: HashAlgorithm,
+                       cutoff: Option<time::Tm>) -> Self {
+        self.hash_cutoffs.retain(|&(a, _)| a != algo);
+        self.hash_cutoffs.push((algo, cutoff));
+        self
+    }
+
+    /// Sets the allowed public key algorithms.
+    pub fn pk_algos(mut self, algos: &[PublicKeyAlgorithm]) -> Self {
+        self.pk_algos = algos.to_vec();
+        self
+    }
+
+    /// Sets the allowed symmetric algorithms.
+    pub fn symmetric_algos(mut self, algos: &[SymmetricAlgorithm]) -> Self {
+        self.symmetric_algos = algos.to_vec();
+        self
+    }
+
+    /// Sets the allowed AEAD algorithms.
+    pub fn aead_algos(mut self, algos: &[AEADAlgorithm]) -> Self {
+        self.aead_algos = algos.to_vec();
+        self
+    }
+
+    /// Sets whether messages must be protected using AEAD.
+    ///
+    /// If set, messages protected using a *Modification Detection
+    /// Code* are rejected.
+    pub fn require_aead(mut self, require: bool) -> Self {
+        self.require_aead = require;
+        self
+    }
+
+    /// Accepts signatures with critical subpackets of type `tag`.
+    ///
+    /// Use this for subpackets that we do not understand, but the
+    /// application does, e.g. private or experimental ones.  Private
+    /// subpackets registered using [`register_private_subpacket`]
+    /// are understood if their value can be decoded.
+    ///
+    /// [`register_private_subpacket`]: ../packet/signature/subpacket/fn.register_private_subpacket.html
+    pub fn accept_critical_subpacket(mut self, tag: SubpacketTag) -> Self {
+        if ! self.critical_subpackets.contains(&tag) {
+            self.critical_subpackets.push(tag);
+        }
+        self
+    }
+
+    /// Accepts signatures with critical notations named `name`.
+    pub fn accept_critical_notation<S: Into<String>>(mut self, name: S)
+                                                     -> Self {
+        let name = name.into();
+        if ! self.critical_notations.contains(&name) {
+            self.critical_notations.push(name);
+        }
+        self
+    }
+
+    /// Sets what to do with the unhashed subpackets of signatures.
+    pub fn handle_unhashed_subpackets(mut self, what: UnhashedSubpackets)
+                                      -> Self {
+        self.unhashed_subpackets = what;
+        self
+    }
+
+    /// Decides whether the critical subpacket `sp` is understood.
+    fn critical_subpacket(&self, sp: &Subpacket) -> Result<()> {
+        if self.critical_subpackets.contains(&sp.tag) {
+            return Ok(());
+        }
+
+        let understood = match (sp.tag, &sp.value) {
+            (_, SubpacketValue::Invalid(_)) => false,
+            (SubpacketTag::NotationData, SubpacketValue::NotationData(n)) =>
+                self.critical_notations.iter()
+                    .any(|name| name.as_bytes() == n.name()),
+            (SubpacketTag::Private(_), SubpacketValue::Unknown(v)) =>
+                describe_private_subpacket(sp.tag, v)
+                    .map(|(_, value)| value.is_ok())
+                    .unwrap_or(false),
+            (SubpacketTag::Reserved(_), _)
+                | (SubpacketTag::PlaceholderForBackwardCompatibility, _)
+                | (SubpacketTag::Private(_), _)
+                | (SubpacketTag::Unknown(_), _) => false,
+            _ => true,
+        };
+
+        if understood {
+            Ok(())
+        } else if let SubpacketValue::NotationData(n) = &sp.value {
+            Err(Error::PolicyViolation(
+                format!("critical notation {:?}",
+                        String::from_utf8_lossy(n.name()))).into())
+        } else {
+            Err(Error::PolicyViolation(
+                format!("critical subpacket {:?}", sp.tag)).into())
+        }
+    }
+}
+
+impl Policy for StandardPolicy {
+    fn signature(&self, sig: &Signature) -> Result<()> {
+        for (_, _, sp) in sig.hashed_area().iter() {
+            if sp.critical {
+                self.critical_subpacket(&sp)?;
+            }
+        }
+
+        let algo = sig.hash_algo();
+        match self.hash_cutoffs.iter().find(|&&(a, _)| a == algo) {
+            None => Ok(()),
+            Some(&(_, Some(cutoff)))
+                if sig.signature_creation_time()
+                .map(|t| t < cutoff).unwrap_or(false) => Ok(()),
+            Some(_) => Err(Error::PolicyViolation(
+                format!("{} signature", algo)).into()),
+        }
+    }
+
+    fn key(&self, key: &Key) -> Result<()> {
+        if self.pk_algos.contains(&key.pk_algo()) {
+            Ok(())
+        } else {
+            Err(Error::PolicyViolation(
+                format!("{} key", key.pk_algo())).into())
+        }
+    }
+
+    fn packet(&self, packet: &Packet) -> Result<()> {
+        let (what, version, ok) = match packet {
+            Packet::Signature(p) =>
+                ("signature", p.version(), p.version() == 4),
+            Packet::OnePassSig(p) =>
+                ("one-pass-signature", p.version(), p.version() == 3),
+            Packet::PublicKey(p) | Packet::PublicSubkey(p)
+                | Packet::SecretKey(p) | Packet::SecretSubkey(p) =>
+                ("key", p.version(), p.version() == 4),
+            Packet::PKESK(p) => ("PKESK", p.version(), p.version() == 3),
+            Packet::SKESK(p) =>
+                ("SKESK", p.version(), p.version() == 4 || p.version() == 5),
+            Packet::SEIP(p) => ("SEIP", p.version(), p.version() == 1),
+            Packet::AED(p) => ("AED", p.version(), p.version() == 1),
+            _ => return Ok(()),
+        };
+
+        if ok {
+            Ok(())
+        } else {
+            Err(Error::PolicyViolation(
+                format!("version {} {} packet", version, what)).into())
+        }
+    }
+
+    fn symmetric_algorithm(&self, algo: SymmetricAlgorithm) -> Result<()> {
+        if self.symmetric_algos.contains(&algo) {
+            Ok(())
+        } else {
+            Err(Error::PolicyViolation(algo.to_string()).into())
+        }
+    }
+
+    fn aead_algorithm(&self, algo: Option<AEADAlgorithm>) -> Result<()> {
+        match algo {
+            Some(algo) if self.aead_algos.contains(&algo) => Ok(()),
+            Some(algo) => Err(Error::PolicyViolation(algo.to_string()).into()),
+            None if self.require_aead => Err(Error::PolicyViolation(
+                "message without AEAD protection".into()).into()),
+            None => Ok(()),
+        }
+    }
+
+    fn unhashed_subpackets(&self) -> UnhashedSubpackets {
+        self.unhashed_subpackets
+    }
+}
+
+/// A policy that accepts everything.
+///
+/// This policy is unsafe: it accepts signatures that can be forged,
+/// and messages that can be decrypted by third parties.  It is only
+/// useful to inspect data, e.g. to see why it was rejected by
+/// another policy.  To make this explicit, the constructor is
+/// `unsafe`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NullPolicy(());
+
+impl NullPolicy {
+    /// Returns a policy that accepts everything.
+    ///
+    /// # Safety
+    ///
+    /// This does not affect memory safety, but the security of
+    /// everything relying on this policy.  See [`NullPolicy`].
+    ///
+    ///   [`NullPolicy`]: struct.NullPolicy.html
+    pub unsafe fn new() -> Self {
+        NullPolicy(())
+    }
+}
+
+impl Policy for NullPolicy {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TPK;
+    use packet::signature::subpacket::NotationData;
+    use parse::Parse;
+
+    #[test]
+    fn hash_cutoff() {
+        let tpk = TPK::from_bytes(
+            ::tests::key("testy.pgp")).unwrap();
+        let sig = tpk.primary_key_signature().unwrap();
+        let algo = sig.hash_algo();
+        let created = sig.signature_creation_time().unwrap();
+
+        let p = StandardPolicy::new();
+        assert!(p.signature(sig).is_ok());
+        assert!(p.clone().reject_hash(algo).signature(sig).is_err());
+        assert!(p.clone().reject_hash(algo).accept_hash(algo)
+                .signature(sig).is_ok());
+
+        // Signatures made before the cutoff are accepted.
+        let later = time::at_utc(created.to_timespec()
+                                 + time::Duration::seconds(1));
+        assert!(p.clone().reject_hash_at(algo, later)
+                .signature(sig).is_ok());
+        assert!(p.clone().reject_hash_at(algo, created)
+                .signature(sig).is_err());
+
+        assert!(unsafe { NullPolicy::new() }.signature(sig).is_ok());
+    }
+
+    #[test]
+    fn critical_subpackets() {
+        let tpk = TPK::from_bytes(
+            ::tests::key("testy.pgp")).unwrap();
+        let mut sig = tpk.primary_key_signature().unwrap().clone();
+        let p = StandardPolicy::new();
+        assert!(p.signature(&sig).is_ok());
+
+        // Critical notations are rejected unless accepted.
+        sig.fields.hashed_area_mut().add(Subpacket::new(
+            SubpacketValue::NotationData(
+                NotationData::new("frob@example.org", b"1", None)),
+            true).unwrap()).unwrap();
+        assert!(p.signature(&sig).is_err());
+        assert!(p.clone().accept_critical_notation("nitz@example.org")
+                .signature(&sig).is_err());
+        assert!(p.clone().accept_critical_notation("frob@example.org")
+                .signature(&sig).is_ok());
+        assert!(p.clone().accept_critical_subpacket(SubpacketTag::NotationData)
+                .signature(&sig).is_ok());
+    }
+
+    #[test]
+    fn aead() {
+        let p = StandardPolicy::new();
+        assert!(p.aead_algorithm(None).is_ok());
+        assert!(p.aead_algorithm(Some(AEADAlgorithm::EAX)).is_ok());
+        let p = p.require_aead(true).aead_algos(&[AEADAlgorithm::OCB]);
+        assert!(p.aead_algorithm(None).is_err());
+        assert!(p.aead_algorithm(Some(AEADAlgorithm::EAX)).is_err());
+        assert!(p.aead_algorithm(Some(AEADAlgorithm::OCB)).is_ok());
+    }
+}
@@ -0,0 +1,383 @@
+//! Estimates the size of encrypted messages.
+//!
+//! Some transports impose hard limits on the size of a message,
+//! e.g. MMS, QR codes, or LoRa.  The functions in this module predict
+//! the size of a message created using [`Encryptor`] and
+//! [`LiteralWriter`] before encrypting it.
+//!
+//! The estimates are upper bounds.  For messages smaller than 4 MiB
+//! they are exact, except for the encrypted session keys of RSA and
+//! ElGamal keys, which may be a few bytes shorter than estimated.
+//! Compression and signatures are not taken into account.
+//!
+//!   [`Encryptor`]: ../stream/struct.Encryptor.html
+//!   [`LiteralWriter`]: ../stream/struct.LiteralWriter.html
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate sequoia_openpgp as openpgp;
+//! # use openpgp::Result;
+//! use openpgp::serialize::estimate::{
+//!     estimate_ciphertext_size,
+//!     EstimateOptions,
+//! };
+//! use openpgp::serialize::stream::EncryptionMode;
+//! # fn main() { f().unwrap(); }
+//! # fn f() -> Result<()> {
+//!
+//! let options = EstimateOptions::new(EncryptionMode::ForTransport)
+//!     .passwords(1)
+//!     .armor(true);
+//! let size = estimate_ciphertext_size(1000, &[], &options)?;
+//! assert!(size > 1000);
+//! # Ok(())
+//! # }
+//! ```
+
+use {
+    Error,
+    Result,
+    TPK,
+};
+use armor;
+use constants::{
+    AEADAlgorithm,
+    SymmetricAlgorithm,
+};
+use crypto::mpis;
+use packet::{BodyLength, Key};
+use super::SerializeInto;
+use super::partial_body::PARTIAL_BODY_FILTER_BUFFER_THRESHOLD;
+use super::stream::{
+    classify_keys,
+    AEADMode,
+    EncryptionMode,
+    KeySelection,
+    AEAD_CHUNK_SIZE,
+};
+
+/// The size of the S2K specifier the [`Encryptor`] uses.
+///
+/// This is an iterated and salted S2K: type, hash algorithm, salt,
+/// and iteration count.
+///
+///   [`Encryptor`]: ../stream/struct.Encryptor.html
+const S2K_LEN: usize = 1 + 1 + 8 + 1;
+
+/// The size of the ECDH wrapped session key.
+///
+/// The session key is padded to 40 bytes before wrapping, and the
+/// wrapping adds 8 bytes.
+const ECDH_WRAPPED_KEY_LEN: usize = 40 + 8;
+
+/// The size of the MDC packet, including its header.
+const MDC_LEN: usize = 2 + 20;
+
+/// Controls how the message is encrypted.
+///
+/// The defaults match those of [`Encryptor::new`].
+///
+///   [`Encryptor::new`]: ../stream/struct.Encryptor.html#method.new
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EstimateOptions {
+    mode: EncryptionMode,
+    passwords: usize,
+    cipher: SymmetricAlgorithm,
+    aead_mode: AEADMode,
+    aead_chunk_size: usize,
+    filename_len: usize,
+    armor: bool,
+}
+
+impl EstimateOptions {
+    /// Returns options for a message encrypted using the given mode.
+    pub fn new(mode: EncryptionMode) -> Self {
+        EstimateOptions {
+            mode: mode,
+            passwords: 0,
+            cipher: SymmetricAlgorithm::AES256,
+            aead_mode: AEADMode::Negotiate,
+            aead_chunk_size: AEAD_CHUNK_SIZE,
+            filename_len: 0,
+            armor: false,
+        }
+    }
+
+    /// Sets the number of passwords the message is encrypted with.
+    pub fn passwords(mut self, n: usize) -> Self {
+        self.passwords = n;
+        self
+    }
+
+    /// Sets the symmetric algorithm.
+    pub fn symmetric_algo(mut self, algo: SymmetricAlgorithm) -> Self {
+        self.cipher = algo;
+        self
+    }
+
+    /// Sets whether the message is AEAD encrypted.
+    pub fn aead_mode(mut self, mode: AEADMode) -> Self {
+        self.aead_mode = mode;
+        self
+    }
+
+    /// Sets the AEAD chunk size.
+    pub fn aead_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.aead_chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets the length of the file name stored in the literal data
+    /// packet.
+    pub fn filename_len(mut self, len: usize) -> Self {
+        self.filename_len = len;
+        self
+    }
+
+    /// Sets whether the message is ASCII armored.
+    ///
+    /// The armor is assumed not to contain any headers.
+    pub fn armor(mut self, armor: bool) -> Self {
+        self.armor = armor;
+        self
+    }
+}
+
+/// Estimates the size of an encrypted message.
+///
+/// Returns the size of a message containing `plaintext_len` bytes of
+/// literal data, encrypted for the given recipients, and for the
+/// number of passwords given in `options`.  For every recipient, the
+/// same keys are used as by the [`Encryptor`].
+///
+/// Like the [`Encryptor`], this fails if neither recipients nor
+/// passwords are given, or if a recipient has no suitable encryption
+/// key.
+///
+///   [`Encryptor`]: ../stream/struct.Encryptor.html
+pub fn estimate_ciphertext_size(plaintext_len: usize, recipients: &[&TPK],
+                                options: &EstimateOptions)
+                                -> Result<usize> {
+    if recipients.len() + options.passwords == 0 {
+        return Err(Error::InvalidArgument(
+            "Neither recipient keys nor passwords given".into()).into());
+    }
+
+    let aead = if options.aead_mode.use_aead(recipients) {
+        Some(AEADAlgorithm::EAX)
+    } else {
+        None
+    };
+
+    let mut size = 0;
+    for tpk in recipients {
+        let pkesks: Vec<usize> = classify_keys(tpk, options.mode).into_iter()
+            .filter(|&(_, ref s)| *s == KeySelection::Selected)
+            .filter_map(|(key, _)| pkesk_size(key).ok())
+            .collect();
+        if pkesks.is_empty() {
+            return Err(Error::InvalidOperation(
+                format!("Key {} has no suitable encryption subkey",
+                        tpk.fingerprint())).into());
+        }
+        size += pkesks.iter().sum::<usize>();
+    }
+    size += options.passwords * skesk_size(options.cipher, aead)?;
+
+    let literal = literal_data_size(options.filename_len, plaintext_len);
+    size += match aead {
+        Some(aead) => aed_size(options.cipher, aead, options.aead_chunk_size,
+                               literal)?,
+        None => seip_size(options.cipher, literal)?,
+    };
+
+    if options.armor {
+        size = armored_size(size);
+    }
+    Ok(size)
+}
+
+/// Returns the size of a packet with a body of `len` bytes.
+fn packet_size(len: usize) -> usize {
+    1 + BodyLength::Full(len as u32).serialized_len() + len
+}
+
+/// Returns the size of a packet with a body of `len` bytes written
+/// using the partial body encoding.
+///
+/// For bodies larger than the buffer threshold, this is an upper
+/// bound.
+fn streamed_packet_size(len: usize) -> usize {
+    if len <= PARTIAL_BODY_FILTER_BUFFER_THRESHOLD {
+        packet_size(len)
+    } else {
+        // Every partial body chunk is at least as large as the
+        // threshold, and its length is encoded using one octet.  The
+        // last chunk's length may take up to five octets.
+        1 + len / PARTIAL_BODY_FILTER_BUFFER_THRESHOLD + 5 + len
+    }
+}
+
+/// Returns the size of a PKESK packet encrypting a session key to
+/// `key`.
+///
+/// For RSA and ElGamal keys, this is an upper bound.
+pub fn pkesk_size(key: &Key) -> Result<usize> {
+    let esk = match key.mpis() {
+        &mpis::PublicKey::RSA { ref n, .. } => n.serialized_len(),
+        &mpis::PublicKey::Elgamal { ref p, .. } => 2 * p.serialized_len(),
+        &mpis::PublicKey::ECDH { ref q, .. } =>
+            q.serialized_len() + 1 + ECDH_WRAPPED_KEY_LEN,
+        _ => return Err(Error::UnsupportedPublicKeyAlgorithm(
+            key.pk_algo()).into()),
+    };
+
+    Ok(packet_size(1 // Version.
+                   + 8 // Recipient's key id.
+                   + 1 // Algo.
+                   + esk))
+}
+
+/// Returns the size of an SKESK packet for a password.
+///
+/// If `aead` is given, this is the size of a version 5 SKESK packet,
+/// otherwise of a version 4 SKESK packet.
+pub fn skesk_size(cipher: SymmetricAlgorithm, aead: Option<AEADAlgorithm>)
+                  -> Result<usize> {
+    let key_size = cipher.key_size()?;
+    Ok(packet_size(match aead {
+        Some(aead) =>
+            1 // Version.
+            + 1 // Cipher algo.
+            + 1 // AEAD algo.
+            + S2K_LEN
+            + aead.iv_size()?
+            + key_size
+            + aead.digest_size()?,
+        None =>
+            1 // Version.
+            + 1 // Algo.
+            + S2K_LEN
+            + 1 + key_size, // Encrypted algo and session key.
+    }))
+}
+
+/// Returns the size of a literal data packet.
+pub fn literal_data_size(filename_len: usize, len: usize) -> usize {
+    streamed_packet_size(1 // Format.
+                         + 1 + filename_len
+                         + 4 // Date.
+                         + len)
+}
+
+/// Returns the size of a SEIP packet containing `len` bytes.
+pub fn seip_size(cipher: SymmetricAlgorithm, len: usize) -> Result<usize> {
+    Ok(streamed_packet_size(1 // Version.
+                            + cipher.block_size()? + 2 // IV, quick check.
+                            + len
+                            + MDC_LEN))
+}
+
+/// Returns the size of an AED packet containing `len` bytes.
+pub fn aed_size(cipher: SymmetricAlgorithm, aead: AEADAlgorithm,
+                chunk_size: usize, len: usize)
+                -> Result<usize> {
+    // Check that the cipher is supported.
+    cipher.block_size()?;
+    if chunk_size == 0 {
+        return Err(Error::InvalidArgument(
+            format!("Invalid AEAD chunk size: {}", chunk_size)).into());
+    }
+
+    let chunks = (len + chunk_size - 1) / chunk_size;
+    Ok(streamed_packet_size(1 // Version.
+                            + 1 // Cipher algo.
+                            + 1 // AEAD algo.
+                            + 1 // Chunk size.
+                            + aead.iv_size()?
+                            + len
+                            // One tag per chunk, and the final tag.
+                            + (chunks + 1) * aead.digest_size()?))
+}
+
+/// Returns the size of an ASCII armored message containing `len`
+/// bytes.
+pub fn armored_size(len: usize) -> usize {
+    let begin = "-----BEGIN PGP MESSAGE-----".len();
+    let end = "-----END PGP MESSAGE-----".len();
+    let base64 = (len + 2) / 3 * 4;
+    let lines = (base64 + armor::LINE_LENGTH - 1) / armor::LINE_LENGTH;
+
+    begin + 1
+        + 1 // Blank line.
+        + base64 + lines
+        + "=XXXX".len() + 1 // Checksum.
+        + end + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crypto::Password;
+    use serialize::stream::{Encryptor, LiteralWriter, Message};
+    use constants::DataFormat;
+    use tpk::{CipherSuite, TPKBuilder};
+
+    fn encrypt(len: usize, tpks: &[&TPK], passwords: &[&Password],
+               aead_mode: AEADMode, armored: bool) -> usize {
+        let mut o = vec![];
+        {
+            let inner: Box<Write> = if armored {
+                Box::new(armor::Writer::new(&mut o, armor::Kind::Message,
+                                            &[]).unwrap())
+            } else {
+                Box::new(&mut o)
+            };
+            let m = Message::new(inner);
+            let encryptor = Encryptor::with_aead_mode(
+                m, passwords, tpks, EncryptionMode::ForTransport, None,
+                aead_mode).unwrap();
+            let mut literal = LiteralWriter::new(encryptor, DataFormat::Binary,
+                                                 None, None).unwrap();
+            literal.write_all(&vec![0; len]).unwrap();
+            literal.finalize().unwrap();
+        }
+        o.len()
+    }
+
+    #[test]
+    fn matches_encryptor() {
+        let (tpk, _) = TPKBuilder::default()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_encryption_subkey()
+            .generate().unwrap();
+        let password: Password = "streng geheim".into();
+
+        for &len in &[0, 1, 1000, 4096, 10000] {
+            for &aead_mode in &[AEADMode::Always, AEADMode::Never] {
+                for &armored in &[false, true] {
+                    let options =
+                        EstimateOptions::new(EncryptionMode::ForTransport)
+                        .passwords(1)
+                        .aead_mode(aead_mode)
+                        .armor(armored);
+                    let estimate =
+                        estimate_ciphertext_size(len, &[&tpk], &options)
+                        .unwrap();
+                    let actual = encrypt(len, &[&tpk], &[&password],
+                                         aead_mode, armored);
+                    // For ECDH keys, the estimate is exact.
+                    assert_eq!(estimate, actual,
+                               "len: {}, aead: {:?}, armor: {}",
+                               len, aead_mode, armored);
+                }
+            }
+        }
+
+        assert!(estimate_ciphertext_size(
+            0, &[], &EstimateOptions::new(EncryptionMode::AtRest)).is_err());
+    }
+}
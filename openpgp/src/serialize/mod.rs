@@ -22,6 +22,7 @@ pub use self::tpk::TSK;
 use self::partial_body::PartialBodyFilter;
 pub mod writer;
 pub mod stream;
+pub mod estimate;
 use crypto::s2k::S2K;
 use packet::signature::subpacket::{
     Subpacket, SubpacketValue, SubpacketLengthTrait,
@@ -1220,6 +1221,34 @@ impl Key4 {
                     s2k.serialize(o)?;
                     o.write_all(ciphertext)?;
                 },
+                &SecretKey::AEADEncrypted {
+                    ref s2k,
+                    algorithm,
+                    aead,
+                    ref iv,
+                    ref ciphertext,
+                    ..
+                } => {
+                    // S2K usage.
+                    write_byte(o, 253)?;
+                    write_byte(o, algorithm.into())?;
+                    write_byte(o, aead.into())?;
+                    s2k.serialize(o)?;
+                    o.write_all(iv)?;
+                    o.write_all(ciphertext)?;
+                },
+                &SecretKey::LegacyEncrypted {
+                    ref s2k,
+                    algorithm,
+                    ref ciphertext,
+                } => {
+                    // S2K usage.  Keys using the implicit simple MD5
+                    // S2K are written using the explicit form.
+                    write_byte(o, 255)?;
+                    write_byte(o, algorithm.into())?;
+                    s2k.serialize(o)?;
+                    o.write_all(ciphertext)?;
+                },
             }
         }
 
@@ -1242,7 +1271,19 @@ impl Key4 {
                         ref s2k,
                         ref ciphertext,
                         ..
+                    }
+                    | &SecretKey::LegacyEncrypted {
+                        ref s2k,
+                        ref ciphertext,
+                        ..
                     } => 1 + s2k.serialized_len() + ciphertext.len(),
+                    &SecretKey::AEADEncrypted {
+                        ref s2k,
+                        ref iv,
+                        ref ciphertext,
+                        ..
+                    } => 1 + 1 + s2k.serialized_len() + iv.len()
+                        + ciphertext.len(),
                 }
             } else {
                 0
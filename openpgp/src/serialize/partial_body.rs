@@ -36,7 +36,7 @@ const PARTIAL_BODY_FILTER_MAX_CHUNK_SIZE : usize = 1 << 30;
 
 // The amount to buffer before flushing.  If this is small, we get
 // lots of small partial body packets, which is annoying.
-const PARTIAL_BODY_FILTER_BUFFER_THRESHOLD : usize = 4 * 1024 * 1024;
+pub(crate) const PARTIAL_BODY_FILTER_BUFFER_THRESHOLD : usize = 4 * 1024 * 1024;
 
 impl<'a, C: 'a> PartialBodyFilter<'a, C> {
     /// Returns a new partial body encoder.
@@ -13,7 +13,7 @@ use std::fmt;
 use std::io::{self, Write};
 use std::iter;
 use time;
-use nettle::{Hash, Yarrow, Random};
+use nettle::Hash;
 
 use {
     crypto,
@@ -21,12 +21,15 @@ use {
     Fingerprint,
     HashAlgorithm,
     Result,
+    RevocationStatus,
     crypto::Password,
     crypto::SessionKey,
+    crypto::s2k::S2K,
     packet::prelude::*,
     packet::signature,
     TPK,
 };
+use packet::aed;
 use packet::ctb::CTB;
 use packet::BodyLength;
 use super::{
@@ -42,6 +45,7 @@ use constants::{
     SymmetricAlgorithm,
 };
 use conversions::Time;
+use policy::{Policy, StandardPolicy};
 
 /// Cookie must be public because the writers are.
 #[doc(hidden)]
@@ -201,6 +205,7 @@ pub struct Signer<'a> {
     signers: Vec<&'a mut dyn crypto::Signer>,
     intended_recipients: Option<Vec<Fingerprint>>,
     detached: bool,
+    hash_algo: HashAlgorithm,
     hash: Box<Hash>,
     cookie: Cookie,
 }
@@ -397,6 +402,7 @@ impl<'a> Signer<'a> {
             signers: signers,
             intended_recipients: intended_recipients,
             detached: detached,
+            hash_algo: hash_algo,
             hash: hash_algo.context()?,
             cookie: Cookie {
                 level: level,
@@ -428,7 +434,7 @@ impl<'a> Signer<'a> {
                 }
 
                 // Compute the signature.
-                let sig = sig.sign_hash(*signer, HashAlgorithm::SHA512, hash)?;
+                let sig = sig.sign_hash(*signer, self.hash_algo, hash)?;
 
                 // And emit the packet.
                 Packet::Signature(sig).serialize(sink)?;
@@ -813,6 +819,17 @@ impl<'a> writer::Stackable<'a, Cookie> for Compressor<'a> {
     }
 }
 
+/// The default chunk size for AEAD encrypted messages.
+///
+/// A page, 3 per mille overhead.
+pub(crate) const AEAD_CHUNK_SIZE: usize = 4096;
+
+/// How long deriving the key from an escrow password should take.
+///
+/// The escrow password is only needed for recovery, hence we can
+/// afford to make guessing it more expensive than usual.
+const ESCROW_S2K_TARGET_MS: u32 = 1000;
+
 /// Encrypts a packet stream.
 pub struct Encryptor<'a> {
     inner: Option<writer::BoxStack<'a, Cookie>>,
@@ -822,6 +839,7 @@ pub struct Encryptor<'a> {
 
 /// Specifies whether to encrypt for archival purposes or for
 /// transport.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EncryptionMode {
     /// Encrypt data for long-term storage.
     ///
@@ -839,6 +857,459 @@ pub enum EncryptionMode {
     ForTransport,
 }
 
+/// Specifies whether to encrypt using AEAD.
+///
+/// AEAD encrypted messages (*AEAD Encrypted Data Packets* and
+/// version 5 *Symmetric-Key Encrypted Session Key Packets*) are not
+/// yet standardized, and many implementations cannot decrypt them.
+/// Therefore, by default, AEAD is only used if all recipients
+/// advertise support for it using the AEAD flag of the *Features*
+/// subpacket.  Otherwise, the message is encrypted using a
+/// *Symmetrically Encrypted and Integrity Protected Data Packet* with
+/// a *Modification Detection Code*.
+///
+/// See [`Encryptor::with_aead_mode`].
+///
+///   [`Encryptor::with_aead_mode`]: struct.Encryptor.html#method.with_aead_mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AEADMode {
+    /// Use AEAD if there are recipient keys, and all of them
+    /// advertise support for AEAD.
+    ///
+    /// This is the default.
+    Negotiate,
+
+    /// Always use AEAD, regardless of the recipients' features.
+    ///
+    /// This also applies to messages encrypted only using
+    /// passwords.
+    Always,
+
+    /// Never use AEAD.
+    Never,
+}
+
+impl Default for AEADMode {
+    fn default() -> Self {
+        AEADMode::Negotiate
+    }
+}
+
+impl AEADMode {
+    /// Returns whether a message for the given recipients should be
+    /// encrypted using AEAD.
+    pub fn use_aead(&self, tpks: &[&TPK]) -> bool {
+        match self {
+            AEADMode::Negotiate =>
+                tpks.len() > 0 && tpks.iter().all(|t| {
+                    t.primary_key_signature()
+                        .map(|s| s.features().supports_aead())
+                        .unwrap_or(false)
+                }),
+            AEADMode::Always => true,
+            AEADMode::Never => false,
+        }
+    }
+}
+
+/// Whether a key was selected for encryption, and if not, why.
+///
+/// See [`select_encryption_keys`].
+///
+///   [`select_encryption_keys`]: fn.select_encryption_keys.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySelection {
+    /// The key is used to encrypt the session key.
+    Selected,
+    /// The key has no binding signature.
+    NoBindingSignature,
+    /// The key is not marked as encryption-capable for the requested
+    /// encryption mode.
+    NotEncryptionCapable,
+    /// The key or its binding signature has expired.
+    Expired,
+    /// The key has been revoked.
+    ///
+    /// This is also used for all keys of a TPK whose primary key has
+    /// been revoked.
+    Revoked,
+    /// The session key could not be encrypted using the key, e.g.,
+    /// because the key uses an unsupported algorithm.
+    Unusable(String),
+}
+
+impl fmt::Display for KeySelection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeySelection::Selected => f.write_str("selected"),
+            KeySelection::NoBindingSignature =>
+                f.write_str("no binding signature"),
+            KeySelection::NotEncryptionCapable =>
+                f.write_str("not encryption-capable"),
+            KeySelection::Expired => f.write_str("expired"),
+            KeySelection::Revoked => f.write_str("revoked"),
+            KeySelection::Unusable(ref reason) =>
+                write!(f, "unusable: {}", reason),
+        }
+    }
+}
+
+/// Describes which keys of a recipient are used for encryption.
+///
+/// Returned by [`select_encryption_keys`] and
+/// [`pkesks_for_recipients`].
+///
+///   [`select_encryption_keys`]: fn.select_encryption_keys.html
+///   [`pkesks_for_recipients`]: fn.pkesks_for_recipients.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecipientReport {
+    recipient: Fingerprint,
+    keys: Vec<(Fingerprint, KeySelection)>,
+}
+
+impl RecipientReport {
+    /// Returns the fingerprint of the recipient's primary key.
+    pub fn recipient(&self) -> &Fingerprint {
+        &self.recipient
+    }
+
+    /// Returns all keys of the recipient together with the selection
+    /// decision.
+    ///
+    /// The primary key comes first, followed by the subkeys.
+    pub fn keys(&self) -> &[(Fingerprint, KeySelection)] {
+        &self.keys
+    }
+
+    /// Returns the fingerprints of the selected keys.
+    pub fn selected(&self) -> Vec<&Fingerprint> {
+        self.keys.iter()
+            .filter(|(_, s)| *s == KeySelection::Selected)
+            .map(|(fp, _)| fp)
+            .collect()
+    }
+
+    /// Returns whether at least one key has been selected.
+    ///
+    /// If this is not the case, the recipient will not be able to
+    /// decrypt the message.
+    pub fn is_usable(&self) -> bool {
+        self.keys.iter().any(|&(_, ref s)| *s == KeySelection::Selected)
+    }
+}
+
+// Classifies all keys of `tpk`.
+pub(crate) fn classify_keys<'a>(tpk: &'a TPK, mode: EncryptionMode)
+                                -> Vec<(&'a Key, KeySelection)> {
+    let tpk_revoked = tpk.revocation_status()
+        != RevocationStatus::NotAsFarAsWeKnow;
+
+    let classify = |key: &Key, sig: Option<&Signature>, revoked: bool| {
+        let sig = if let Some(sig) = sig {
+            sig
+        } else {
+            return KeySelection::NoBindingSignature;
+        };
+
+        if tpk_revoked || revoked {
+            KeySelection::Revoked
+        } else if ! match mode {
+            EncryptionMode::AtRest =>
+                sig.key_flags().can_encrypt_at_rest(),
+            EncryptionMode::ForTransport =>
+                sig.key_flags().can_encrypt_for_transport(),
+        } {
+            KeySelection::NotEncryptionCapable
+        } else if ! (sig.signature_alive() && sig.key_alive(key)) {
+            KeySelection::Expired
+        } else {
+            KeySelection::Selected
+        }
+    };
+
+    iter::once((tpk.primary(),
+                classify(tpk.primary(), tpk.primary_key_signature(),
+                         false)))
+        .chain(tpk.subkeys().map(|skb| {
+            let revoked = skb.revoked(None)
+                != RevocationStatus::NotAsFarAsWeKnow;
+            (skb.subkey(),
+             classify(skb.subkey(), skb.binding_signature(), revoked))
+        }))
+        .collect()
+}
+
+/// Selects the encryption-capable keys of the given recipients.
+///
+/// A key is selected if its binding signature marks it as
+/// encryption-capable for the given `mode`, and if neither the key
+/// nor its binding signature has expired or has been revoked.  For
+/// every recipient, a [`RecipientReport`] describes which keys are
+/// selected, and why the others are excluded.
+///
+/// This does not encrypt anything.  It can be used to show the user
+/// which keys are going to be used before encrypting a message.  Use
+/// [`pkesks_for_recipients`] to create the PKESK packets.
+///
+///   [`RecipientReport`]: struct.RecipientReport.html
+///   [`pkesks_for_recipients`]: fn.pkesks_for_recipients.html
+pub fn select_encryption_keys(tpks: &[&TPK], mode: EncryptionMode)
+                              -> Vec<RecipientReport> {
+    tpks.iter().map(|tpk| RecipientReport {
+        recipient: tpk.fingerprint(),
+        keys: classify_keys(tpk, mode).into_iter()
+            .map(|(key, selection)| (key.fingerprint(), selection))
+            .collect(),
+    }).collect()
+}
+
+/// Controls which algorithms [`negotiate_algorithms`] may select.
+///
+/// The algorithms are listed from strongest to weakest.  By default,
+/// the symmetric algorithms are AES, Camellia, Twofish, and
+/// TripleDES, which every OpenPGP implementation supports.  The
+/// AEAD algorithm is EAX.  The compression algorithms are ZLIB,
+/// DEFLATE, and bzip2.  Algorithms this library does not implement
+/// are never selected.
+///
+///   [`negotiate_algorithms`]: fn.negotiate_algorithms.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlgorithmPolicy {
+    symmetric_algos: Vec<SymmetricAlgorithm>,
+    aead_algos: Vec<AEADAlgorithm>,
+    compression_algos: Vec<CompressionAlgorithm>,
+}
+
+impl Default for AlgorithmPolicy {
+    fn default() -> Self {
+        AlgorithmPolicy {
+            symmetric_algos: vec![
+                SymmetricAlgorithm::AES256,
+                SymmetricAlgorithm::AES192,
+                SymmetricAlgorithm::AES128,
+                SymmetricAlgorithm::Camellia256,
+                SymmetricAlgorithm::Camellia192,
+                SymmetricAlgorithm::Camellia128,
+                SymmetricAlgorithm::Twofish,
+                SymmetricAlgorithm::TripleDES,
+            ],
+            aead_algos: vec![AEADAlgorithm::EAX],
+            compression_algos: vec![
+                CompressionAlgorithm::Zlib,
+                CompressionAlgorithm::Zip,
+                CompressionAlgorithm::BZip2,
+            ],
+        }
+    }
+}
+
+impl AlgorithmPolicy {
+    /// Sets the acceptable symmetric algorithms, strongest first.
+    pub fn symmetric_algos(mut self, algos: &[SymmetricAlgorithm]) -> Self {
+        self.symmetric_algos = algos.to_vec();
+        self
+    }
+
+    /// Sets the acceptable AEAD algorithms, strongest first.
+    ///
+    /// If none is given, AEAD is never selected.
+    pub fn aead_algos(mut self, algos: &[AEADAlgorithm]) -> Self {
+        self.aead_algos = algos.to_vec();
+        self
+    }
+
+    /// Sets the acceptable compression algorithms, best first.
+    ///
+    /// If none of them is supported by all recipients, the data is
+    /// not compressed.
+    pub fn compression_algos(mut self, algos: &[CompressionAlgorithm])
+                             -> Self {
+        self.compression_algos = algos.to_vec();
+        self
+    }
+}
+
+/// The algorithms selected by [`negotiate_algorithms`].
+///
+///   [`negotiate_algorithms`]: fn.negotiate_algorithms.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NegotiatedAlgorithms {
+    symmetric_algo: SymmetricAlgorithm,
+    aead_algo: Option<AEADAlgorithm>,
+    compression_algo: CompressionAlgorithm,
+}
+
+impl NegotiatedAlgorithms {
+    /// Returns the symmetric algorithm.
+    pub fn symmetric_algo(&self) -> SymmetricAlgorithm {
+        self.symmetric_algo
+    }
+
+    /// Returns the AEAD algorithm.
+    ///
+    /// If this is `None`, the message is encrypted using a *Symmetrically
+    /// Encrypted and Integrity Protected Data Packet* with a
+    /// *Modification Detection Code*.
+    pub fn aead_algo(&self) -> Option<AEADAlgorithm> {
+        self.aead_algo
+    }
+
+    /// Returns the compression algorithm.
+    pub fn compression_algo(&self) -> CompressionAlgorithm {
+        self.compression_algo
+    }
+}
+
+/// Returns the signatures that carry the algorithm preferences of
+/// `tpk`.
+///
+/// These are the binding signatures of the keys selected for
+/// encryption, followed by the primary key's self-signature.
+fn preference_signatures<'a>(tpk: &'a TPK, mode: EncryptionMode)
+                             -> Vec<&'a Signature> {
+    let selected = classify_keys(tpk, mode).into_iter()
+        .filter(|&(_, ref s)| *s == KeySelection::Selected)
+        .map(|(key, _)| key.fingerprint())
+        .collect::<Vec<_>>();
+
+    tpk.subkeys()
+        .filter(|skb| selected.contains(&skb.subkey().fingerprint()))
+        .filter_map(|skb| skb.binding_signature())
+        .chain(tpk.primary_key_signature())
+        .collect()
+}
+
+/// Selects the strongest algorithms supported by all recipients.
+///
+/// The preferences of every recipient are taken from the binding
+/// signatures of the keys selected for encryption (see
+/// [`select_encryption_keys`]), or, if they do not state any, from
+/// the primary key's self-signature.  As required by [Section 13.2
+/// of RFC 4880], all recipients are assumed to support TripleDES and
+/// uncompressed data, and, if they support AEAD, EAX.  Among the
+/// algorithms supported by all recipients, the first one allowed by
+/// `policy` is selected.  AEAD is only used if all recipients
+/// advertise support for it, which is also what [`Encryptor`] does
+/// by default (see [`AEADMode`]).
+///
+/// This does not encrypt anything.  It can be used to explain the
+/// parameters of a message before encrypting it.
+///
+/// # Errors
+///
+/// Fails with `Error::InvalidOperation` if no symmetric algorithm
+/// allowed by `policy` is supported by all recipients.
+///
+///   [`select_encryption_keys`]: fn.select_encryption_keys.html
+///   [Section 13.2 of RFC 4880]: https://tools.ietf.org/html/rfc4880#section-13.2
+///   [`Encryptor`]: struct.Encryptor.html
+///   [`AEADMode`]: enum.AEADMode.html
+pub fn negotiate_algorithms(tpks: &[&TPK], mode: EncryptionMode,
+                            policy: &AlgorithmPolicy)
+                            -> Result<NegotiatedAlgorithms> {
+    let sigs = tpks.iter()
+        .map(|tpk| preference_signatures(tpk, mode))
+        .collect::<Vec<_>>();
+
+    // Returns whether all recipients support `algo`, given a
+    // function returning a signature's preferences, and the
+    // algorithm implicitly supported by everyone.
+    fn supported_by_all<T, F>(sigs: &[Vec<&Signature>], algo: T, prefs: F,
+                              implicit: Option<T>)
+                              -> bool
+        where T: PartialEq, F: Fn(&Signature) -> Option<Vec<T>>
+    {
+        implicit.as_ref() == Some(&algo) || sigs.iter().all(|sigs| {
+            sigs.iter().filter_map(|s| prefs(s)).next()
+                .map(|p| p.contains(&algo))
+                .unwrap_or(false)
+        })
+    }
+
+    let symmetric_algo = policy.symmetric_algos.iter()
+        .filter(|a| a.is_supported())
+        .find(|&&a| supported_by_all(&sigs, a,
+                                     |s| s.preferred_symmetric_algorithms(),
+                                     Some(SymmetricAlgorithm::TripleDES)))
+        .cloned()
+        .ok_or_else(|| Error::InvalidOperation(
+            "No acceptable symmetric algorithm is supported by all \
+             recipients".into()))?;
+
+    let aead_algo = if AEADMode::Negotiate.use_aead(tpks) {
+        policy.aead_algos.iter()
+            .filter(|a| a.is_supported())
+            .find(|&&a| supported_by_all(&sigs, a,
+                                         |s| s.preferred_aead_algorithms(),
+                                         Some(AEADAlgorithm::EAX)))
+            .cloned()
+    } else {
+        None
+    };
+
+    let compression_algo = policy.compression_algos.iter()
+        .filter(|a| a.is_supported())
+        .find(|&&a| supported_by_all(&sigs, a,
+                                     |s| s.preferred_compression_algorithms(),
+                                     None))
+        .cloned()
+        .unwrap_or(CompressionAlgorithm::Uncompressed);
+
+    Ok(NegotiatedAlgorithms {
+        symmetric_algo: symmetric_algo,
+        aead_algo: aead_algo,
+        compression_algo: compression_algo,
+    })
+}
+
+/// Encrypts the session key for the given recipients.
+///
+/// The keys are selected as described in
+/// [`select_encryption_keys`].  Returns a PKESK packet for every
+/// selected key, and a [`RecipientReport`] for every recipient.  If
+/// the session key cannot be encrypted using a selected key, the key
+/// is reported as [`KeySelection::Unusable`].
+///
+/// Note: it is up to the caller to decide what to do if a recipient
+/// has no usable key, see [`RecipientReport::is_usable`].
+///
+///   [`select_encryption_keys`]: fn.select_encryption_keys.html
+///   [`RecipientReport`]: struct.RecipientReport.html
+///   [`KeySelection::Unusable`]: enum.KeySelection.html#variant.Unusable
+///   [`RecipientReport::is_usable`]: struct.RecipientReport.html#method.is_usable
+pub fn pkesks_for_recipients(tpks: &[&TPK], mode: EncryptionMode,
+                             algo: SymmetricAlgorithm, sk: &SessionKey)
+                             -> (Vec<PKESK>, Vec<RecipientReport>) {
+    let mut pkesks = Vec::new();
+    let mut reports = Vec::with_capacity(tpks.len());
+
+    for tpk in tpks {
+        let keys = classify_keys(tpk, mode).into_iter()
+            .map(|(key, selection)| {
+                let selection = if selection == KeySelection::Selected {
+                    match PKESK3::for_recipient(algo, sk, key) {
+                        Ok(pkesk) => {
+                            pkesks.push(pkesk.into());
+                            KeySelection::Selected
+                        },
+                        Err(e) => KeySelection::Unusable(e.to_string()),
+                    }
+                } else {
+                    selection
+                };
+                (key.fingerprint(), selection)
+            })
+            .collect();
+
+        reports.push(RecipientReport {
+            recipient: tpk.fingerprint(),
+            keys: keys,
+        });
+    }
+
+    (pkesks, reports)
+}
+
 impl<'a> Encryptor<'a> {
     /// Creates a new encryptor.
     ///
@@ -848,6 +1319,15 @@ impl<'a> Encryptor<'a> {
     ///
     /// Unless otherwise specified, the stream is encrypted using
     /// AES256.  Key preferences of the recipients are not honored.
+    /// AEAD is used if all recipients advertise support for it, see
+    /// [`AEADMode::Negotiate`].
+    ///
+    /// The encryption-capable keys are selected using
+    /// [`pkesks_for_recipients`].  If any recipient has no usable key,
+    /// this fails with `Error::InvalidOperation`.
+    ///
+    ///   [`pkesks_for_recipients`]: fn.pkesks_for_recipients.html
+    ///   [`AEADMode::Negotiate`]: enum.AEADMode.html#variant.Negotiate
     ///
     /// # Example
     ///
@@ -912,36 +1392,156 @@ impl<'a> Encryptor<'a> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn new<C>(mut inner: writer::Stack<'a, Cookie>,
+    pub fn new<C>(inner: writer::Stack<'a, Cookie>,
                   passwords: &[&Password], tpks: &[&TPK],
                   encryption_mode: EncryptionMode,
                   cipher_algo: C)
                   -> Result<writer::Stack<'a, Cookie>>
         where C: Into<Option<SymmetricAlgorithm>>
+    {
+        Self::make(inner, passwords, None, tpks, encryption_mode, cipher_algo,
+                   AEADMode::Negotiate, AEAD_CHUNK_SIZE,
+                   &StandardPolicy::new())
+    }
+
+    /// Creates a new encryptor with a recovery password.
+    ///
+    /// The session key is encrypted for the given TPKs, and
+    /// additionally using the `escrow` password.  The recipients
+    /// decrypt the message using their keys as usual, and if the
+    /// keys are lost, the message can be recovered using the
+    /// password, e.g. using [`decrypt_session_key`].
+    ///
+    /// Unlike passing the password to [`new`], this requires at least
+    /// one recipient, and derives the key from the password using an
+    /// S2K that is considerably more expensive to brute force than
+    /// the default one.  Note that this makes recovering the message
+    /// using the password slow.
+    ///
+    ///   [`decrypt_session_key`]: ../../parse/stream/fn.decrypt_session_key.html
+    ///   [`new`]: #method.new
+    pub fn with_escrow<C>(inner: writer::Stack<'a, Cookie>,
+                          tpks: &[&TPK], escrow: &Password,
+                          encryption_mode: EncryptionMode,
+                          cipher_algo: C)
+                          -> Result<writer::Stack<'a, Cookie>>
+        where C: Into<Option<SymmetricAlgorithm>>
+    {
+        if tpks.is_empty() {
+            return Err(Error::InvalidArgument(
+                "Escrow requires at least one recipient key".into()).into());
+        }
+
+        let s2k = S2K::calibrated(HashAlgorithm::SHA256,
+                                  ESCROW_S2K_TARGET_MS)?;
+        Self::make(inner, &[escrow], Some(s2k), tpks, encryption_mode,
+                   cipher_algo, AEADMode::Negotiate, AEAD_CHUNK_SIZE,
+                   &StandardPolicy::new())
+    }
+
+    /// Creates a new encryptor honoring `policy`.
+    ///
+    /// Like [`new`], but fails if `policy` rejects the symmetric
+    /// algorithm.  If `policy` rejects messages without AEAD
+    /// protection, AEAD is used regardless of the recipients'
+    /// features.
+    ///
+    ///   [`new`]: #method.new
+    pub fn with_policy<C>(inner: writer::Stack<'a, Cookie>,
+                          passwords: &[&Password], tpks: &[&TPK],
+                          encryption_mode: EncryptionMode,
+                          cipher_algo: C, policy: &Policy)
+                          -> Result<writer::Stack<'a, Cookie>>
+        where C: Into<Option<SymmetricAlgorithm>>
+    {
+        Self::make(inner, passwords, None, tpks, encryption_mode, cipher_algo,
+                   AEADMode::Negotiate, AEAD_CHUNK_SIZE, policy)
+    }
+
+    /// Creates a new encryptor, overriding whether to use AEAD.
+    ///
+    /// Like [`new`], but whether the message is AEAD encrypted is
+    /// controlled by `aead_mode`.  See [`AEADMode`].
+    ///
+    ///   [`new`]: #method.new
+    ///   [`AEADMode`]: enum.AEADMode.html
+    pub fn with_aead_mode<C>(inner: writer::Stack<'a, Cookie>,
+                             passwords: &[&Password], tpks: &[&TPK],
+                             encryption_mode: EncryptionMode,
+                             cipher_algo: C, aead_mode: AEADMode)
+                             -> Result<writer::Stack<'a, Cookie>>
+        where C: Into<Option<SymmetricAlgorithm>>
+    {
+        Self::make(inner, passwords, None, tpks, encryption_mode, cipher_algo,
+                   aead_mode, AEAD_CHUNK_SIZE, &StandardPolicy::new())
+    }
+
+    /// Creates a new encryptor using the given AEAD chunk size.
+    ///
+    /// Like [`new`], but if the message is AEAD encrypted, the data
+    /// is split into chunks of `chunk_size` bytes.  Larger chunks
+    /// reduce the overhead, but the recipient has to buffer and
+    /// authenticate a whole chunk before any of its data is
+    /// released.
+    ///
+    /// `chunk_size` must be a power of two, at least 64 bytes, and at
+    /// most [`aed::MAX_CHUNK_SIZE`].  Note that the chunk size is
+    /// ignored if the message is not AEAD encrypted.
+    ///
+    ///   [`new`]: #method.new
+    ///   [`aed::MAX_CHUNK_SIZE`]: ../../packet/aed/constant.MAX_CHUNK_SIZE.html
+    pub fn with_aead_chunk_size<C>(inner: writer::Stack<'a, Cookie>,
+                                   passwords: &[&Password], tpks: &[&TPK],
+                                   encryption_mode: EncryptionMode,
+                                   cipher_algo: C, chunk_size: usize)
+                                   -> Result<writer::Stack<'a, Cookie>>
+        where C: Into<Option<SymmetricAlgorithm>>
+    {
+        if chunk_size.count_ones() != 1 || chunk_size < 64
+            || chunk_size > aed::MAX_CHUNK_SIZE
+        {
+            return Err(Error::InvalidArgument(
+                format!("Invalid AEAD chunk size: {}", chunk_size)).into());
+        }
+
+        Self::make(inner, passwords, None, tpks, encryption_mode, cipher_algo,
+                   AEADMode::Negotiate, chunk_size, &StandardPolicy::new())
+    }
+
+    /// If `s2k` is given, it is used to protect the session key using
+    /// the passwords instead of the default S2K.
+    fn make<C>(mut inner: writer::Stack<'a, Cookie>,
+               passwords: &[&Password], s2k: Option<S2K>, tpks: &[&TPK],
+               encryption_mode: EncryptionMode,
+               cipher_algo: C, aead_mode: AEADMode, chunk_size: usize,
+               policy: &Policy)
+               -> Result<writer::Stack<'a, Cookie>>
+        where C: Into<Option<SymmetricAlgorithm>>
     {
         if tpks.len() + passwords.len() == 0 {
             return Err(Error::InvalidArgument(
                 "Neither recipient keys nor passwords given".into()).into());
         }
 
-        let mut rng = Yarrow::default();
-
         struct AEADParameters {
             algo: AEADAlgorithm,
             chunk_size: usize,
             nonce: Box<[u8]>,
         }
 
-        // Use AEAD if there are TPKs and all of them support AEAD.
-        let aead = if tpks.len() > 0 && tpks.iter().all(|t| {
-            t.primary_key_signature().map(|s| s.features().supports_aead())
-                .unwrap_or(false)
-        }) {
+        // Upgrade to AEAD if the policy rejects messages without it.
+        let use_aead = aead_mode.use_aead(tpks)
+            || (aead_mode != AEADMode::Never
+                && policy.aead_algorithm(None).is_err());
+        policy.aead_algorithm(
+            if use_aead { Some(AEADAlgorithm::EAX) } else { None })?;
+
+        let aead = if use_aead {
             let mut nonce = vec![0; AEADAlgorithm::EAX.iv_size()?];
-            rng.random(&mut nonce);
+            crypto::random(&mut nonce);
             Some(AEADParameters {
                 algo: AEADAlgorithm::EAX, // Must implement EAX.
-                chunk_size: 4096, // A page, 3 per mille overhead.
+                chunk_size: chunk_size,
                 nonce: nonce.into_boxed_slice(),
             })
         } else {
@@ -950,74 +1550,32 @@ impl<'a> Encryptor<'a> {
 
         let level = inner.as_ref().cookie_ref().level + 1;
         let algo = cipher_algo.into().unwrap_or(SymmetricAlgorithm::AES256);
+        policy.symmetric_algorithm(algo)?;
 
         // Generate a session key.
-        let sk = SessionKey::new(&mut rng, algo.key_size()?);
+        let sk = SessionKey::new(algo.key_size()?);
 
         // Write the PKESK packet(s).
-        for tpk in tpks {
-            // We need to find all applicable encryption (sub)keys.
-            let can_encrypt = |key: &Key, sig: Option<&Signature>| -> bool {
-                if let Some(sig) = sig {
-                    (match encryption_mode {
-                        EncryptionMode::AtRest =>
-                            sig.key_flags().can_encrypt_at_rest(),
-                        EncryptionMode::ForTransport =>
-                            sig.key_flags().can_encrypt_for_transport(),
-                    }
-                     // Check expiry.
-                     && sig.signature_alive()
-                     && sig.key_alive(key))
-                } else {
-                    false
-                }
-            };
-
-            // Gather all encryption-capable subkeys.
-            let subkeys = tpk.subkeys().filter_map(|skb| {
-                let key = skb.subkey();
-                if can_encrypt(key, skb.binding_signature()) {
-                    Some(key)
-                } else {
-                    None
-                }
-            });
-
-            // Check if the primary key is encryption-capable.
-            let primary_can_encrypt =
-                can_encrypt(tpk.primary(), tpk.primary_key_signature());
-
-            // If the primary key is encryption-capable, prepend to
-            // subkeys via iterator magic.
-            let keys =
-                iter::once(tpk.primary())
-                .filter(|_| primary_can_encrypt)
-                .chain(subkeys);
-
-            let mut count = 0;
-            for key in keys {
-                if let Ok(pkesk) = PKESK3::for_recipient(algo, &sk, key) {
-                    Packet::PKESK(pkesk.into()).serialize(&mut inner)?;
-                    count += 1;
-                }
-            }
-
-            if count == 0 {
-                return Err(Error::InvalidOperation(
-                    format!("Key {} has no suitable encryption subkey",
-                            tpk)).into());
-            }
+        let (pkesks, reports) =
+            pkesks_for_recipients(tpks, encryption_mode, algo, &sk);
+        if let Some(r) = reports.iter().find(|r| ! r.is_usable()) {
+            return Err(Error::InvalidOperation(
+                format!("Key {} has no suitable encryption subkey",
+                        r.recipient())).into());
+        }
+        for pkesk in pkesks {
+            Packet::PKESK(pkesk).serialize(&mut inner)?;
         }
 
         // Write the SKESK packet(s).
         for password in passwords {
+            let s2k = s2k.unwrap_or_default();
             if let Some(aead) = aead.as_ref() {
-                let skesk = SKESK5::with_password(algo, aead.algo,
-                                                  Default::default(),
+                let skesk = SKESK5::with_password(algo, aead.algo, s2k,
                                                   &sk, password).unwrap();
                 Packet::SKESK(skesk.into()).serialize(&mut inner)?;
             } else {
-                let skesk = SKESK4::with_password(algo, Default::default(),
+                let skesk = SKESK4::with_password(algo, s2k,
                                                   &sk, password).unwrap();
                 Packet::SKESK(skesk.into()).serialize(&mut inner)?;
             }
@@ -1062,7 +1620,7 @@ impl<'a> Encryptor<'a> {
 
             // Write the initialization vector, and the quick-check bytes.
             let mut iv = vec![0; algo.block_size()?];
-            rng.random(&mut iv);
+            crypto::random(&mut iv);
             encryptor.write_all(&iv)?;
             encryptor.write_all(&iv[iv.len() - 2..])?;
 
@@ -1169,6 +1727,138 @@ impl<'a> writer::Stackable<'a, Cookie> for Encryptor<'a> {
     }
 }
 
+/// Re-encrypts a message.
+///
+/// Reads the message from `input`, decrypts it using `helper`, and
+/// encrypts its payload using the given passwords and the
+/// encryption-capable keys of `recipients`, writing the new message
+/// to `output`.  This can be used to migrate archived messages to new
+/// keys, and away from weak algorithms.  The new message is encrypted
+/// using the algorithms chosen by [`Encryptor::new`].
+///
+/// The message is processed in a streaming fashion.  The payload,
+/// i.e. the literal data packet, is copied together with any one-pass
+/// signature and signature packets, so that signatures over the data
+/// remain valid.  Compression containers are removed.  Messages that
+/// are not encrypted are encrypted as well.
+///
+/// The old message must be well-formed: SEIP packets must end with a
+/// valid MDC packet, and unknown packets are rejected.
+///
+/// Note: The integrity of the old message can only be checked once
+/// it has been read completely.  If this function fails, `output`
+/// must be discarded.
+///
+///   [`Encryptor::new`]: struct.Encryptor.html#method.new
+pub fn reencrypt<'a, R, W, H>(input: R, output: W, helper: &mut H,
+                              passwords: &[&Password], recipients: &[&TPK],
+                              encryption_mode: EncryptionMode)
+                              -> Result<()>
+    where R: io::Read + 'a,
+          W: io::Write + 'a,
+          H: ::parse::stream::DecryptionHelper
+{
+    use parse::{Parse, PacketParser, PacketParserResult};
+
+    let mut output = Some(output);
+    let mut sink: Option<writer::Stack<'a, Cookie>> = None;
+    let mut pkesks: Vec<PKESK> = Vec::new();
+    let mut skesks: Vec<SKESK> = Vec::new();
+    // The recursion depth of the SEIP packet, and whether its MDC
+    // has been seen.
+    let mut seip: Option<(isize, bool)> = None;
+
+    let mut ppr = PacketParser::from_reader(input)?;
+    while let PacketParserResult::Some(mut pp) = ppr {
+        if let Some((_, true)) = seip {
+            // The MDC must be the last packet.
+            return Err(Error::MalformedMessage(
+                format!("Unexpected {} packet after MDC",
+                        pp.packet.tag())).into());
+        }
+
+        match pp.packet {
+            Packet::PKESK(ref p) if sink.is_none() => pkesks.push(p.clone()),
+            Packet::SKESK(ref s) if sink.is_none() => skesks.push(s.clone()),
+            Packet::SEIP(_) | Packet::AED(_)
+                if sink.is_none() && seip.is_none() =>
+            {
+                {
+                    let decrypt =
+                        |algo, key: &SessionKey| pp.decrypt(algo, key);
+                    helper.decrypt(&pkesks[..], &skesks[..], decrypt)?;
+                }
+                if ! pp.decrypted() {
+                    return Err(Error::InvalidSessionKey(
+                        "No session key".into()).into());
+                }
+                if let Packet::SEIP(_) = pp.packet {
+                    seip = Some((pp.recursion_depth(), false));
+                }
+            },
+            Packet::PKESK(_) | Packet::SKESK(_)
+                | Packet::SEIP(_) | Packet::AED(_) =>
+                return Err(Error::MalformedMessage(
+                    format!("Unexpected {} packet in payload",
+                            pp.packet.tag())).into()),
+            Packet::MDC(ref mdc) => match seip {
+                Some((depth, false)) if pp.recursion_depth() == depth + 1 =>
+                    if mdc.valid() {
+                        seip = Some((depth, true));
+                    } else {
+                        return Err(Error::ManipulatedMessage.into());
+                    },
+                _ => return Err(Error::MalformedMessage(
+                    "Unexpected MDC packet".into()).into()),
+            },
+            Packet::Marker(_) | Packet::CompressedData(_) => (),
+            Packet::Literal(_) | Packet::OnePassSig(_)
+                | Packet::Signature(_) => {
+                if sink.is_none() {
+                    let message = Message::new(
+                        output.take().expect("only taken once"));
+                    sink = Some(Encryptor::new(message, passwords, recipients,
+                                               encryption_mode, None)?);
+                }
+
+                let header = if let Packet::Literal(ref l) = pp.packet {
+                    Some((l.format(), l.filename().map(|f| f.to_vec()),
+                          l.date().cloned()))
+                } else {
+                    None
+                };
+
+                if let Some((format, filename, date)) = header {
+                    let mut literal = LiteralWriter::new(
+                        sink.take().expect("created above"), format,
+                        filename.as_ref().map(|f| &f[..]), date)?;
+                    io::copy(&mut pp, &mut literal)?;
+                    sink = literal.finalize_one()?;
+                } else {
+                    pp.packet.serialize(
+                        sink.as_mut().expect("created above"))?;
+                }
+            },
+            _ =>
+                return Err(Error::MalformedMessage(
+                    format!("Unexpected {} packet", pp.packet.tag())).into()),
+        }
+
+        ppr = pp.recurse()?.1;
+    }
+
+    if let Some((_, false)) = seip {
+        // The MDC is missing.
+        return Err(Error::ManipulatedMessage.into());
+    }
+
+    if let Some(sink) = sink {
+        sink.finalize()
+    } else {
+        Err(Error::MalformedMessage("Message has no payload".into()).into())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::io::Read;
@@ -1395,6 +2085,35 @@ mod test {
         assert_eq!(good, 2);
     }
 
+    #[test]
+    fn detached_signature_streaming() {
+        let tsk = TPK::from_bytes(::tests::key("testy-private.pgp")).unwrap();
+        let key = tsk.keys_all().signing_capable().nth(0).unwrap().2;
+        let mut pair = key.clone().into_keypair().unwrap();
+
+        // Stream the data through the signer, it is never held in
+        // memory as a whole.
+        let len = 4 << 20;
+        let mut o = vec![];
+        {
+            let m = Message::new(&mut o);
+            let mut signer = Signer::detached(
+                m, vec![&mut pair as &mut dyn crypto::Signer],
+                HashAlgorithm::SHA256)
+                .unwrap();
+            io::copy(&mut io::repeat(0x42).take(len), &mut signer).unwrap();
+            signer.finalize().unwrap();
+        }
+
+        let sig = match Packet::from_bytes(&o).unwrap() {
+            Packet::Signature(sig) => sig,
+            p => panic!("Expected a Signature, got: {:?}", p),
+        };
+        assert_eq!(sig.hash_algo(), HashAlgorithm::SHA256);
+        assert!(sig.verify_reader(key, &mut io::repeat(0x42).take(len))
+                .unwrap());
+    }
+
     #[test]
     fn encryptor() {
         let passwords: [Password; 2] = ["streng geheim".into(),
@@ -1502,4 +2221,462 @@ mod test {
             assert_eq!(state, State::Done);
         }
     }
+
+    #[test]
+    fn aead_chunk_size() {
+        use tpk::{CipherSuite, TPKBuilder};
+
+        // Keys generated by Sequoia advertise AEAD support.
+        let (tpk, _) = TPKBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_encryption_subkey()
+            .generate().unwrap();
+        let password: Password = "streng geheim".into();
+        let message = vec![0x42; 1000];
+
+        for &chunk_size in &[63, 96, aed::MAX_CHUNK_SIZE * 2] {
+            let m = Message::new(vec![]);
+            assert!(Encryptor::with_aead_chunk_size(
+                m, &[&password], &[&tpk], EncryptionMode::AtRest, None,
+                chunk_size).is_err());
+        }
+
+        let mut o = vec![];
+        {
+            let m = Message::new(&mut o);
+            let encryptor = Encryptor::with_aead_chunk_size(
+                m, &[&password], &[&tpk], EncryptionMode::AtRest, None, 64)
+                .unwrap();
+            let mut literal = LiteralWriter::new(encryptor, DataFormat::Binary,
+                                                 None, None)
+                .unwrap();
+            literal.write_all(&message).unwrap();
+            literal.finalize().unwrap();
+        }
+
+        let mut key = None;
+        let mut ppr = PacketParser::from_bytes(&o).unwrap();
+        while let PacketParserResult::Some(mut pp) = ppr {
+            match pp.packet {
+                Packet::SKESK(ref skesk) =>
+                    key = Some(skesk.decrypt(&password).unwrap()),
+                Packet::AED(_) => {
+                    if let Packet::AED(ref aed) = pp.packet {
+                        assert_eq!(aed.chunk_size(), 64);
+                    }
+                    let (algo, key) = key.take().unwrap();
+                    pp.decrypt(algo, &key).unwrap();
+                },
+                Packet::Literal(_) => {
+                    let mut body = Vec::new();
+                    pp.read_to_end(&mut body).unwrap();
+                    assert_eq!(body, message);
+                },
+                _ => (),
+            }
+            ppr = pp.recurse().unwrap().1;
+        }
+    }
+
+    #[test]
+    fn aead_mode() {
+        use tpk::{CipherSuite, TPKBuilder};
+
+        // Keys generated by Sequoia advertise AEAD support.
+        let (tpk, _) = TPKBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_encryption_subkey()
+            .generate().unwrap();
+        let password: Password = "streng geheim".into();
+
+        // Returns the tag of the encryption container.
+        let container = |passwords: &[&Password], tpks: &[&TPK], mode| {
+            let mut o = vec![];
+            {
+                let m = Message::new(&mut o);
+                let encryptor = Encryptor::with_aead_mode(
+                    m, passwords, tpks, EncryptionMode::AtRest, None, mode)
+                    .unwrap();
+                let mut literal = LiteralWriter::new(
+                    encryptor, DataFormat::Binary, None, None).unwrap();
+                literal.write_all(b"Hello world.").unwrap();
+                literal.finalize().unwrap();
+            }
+            PacketPile::from_bytes(&o).unwrap().children()
+                .map(|p| p.tag())
+                .find(|t| *t == Tag::AED || *t == Tag::SEIP)
+                .unwrap()
+        };
+
+        assert_eq!(container(&[], &[&tpk], AEADMode::Negotiate), Tag::AED);
+        assert_eq!(container(&[], &[&tpk], AEADMode::Never), Tag::SEIP);
+        assert_eq!(container(&[&password], &[], AEADMode::Negotiate),
+                   Tag::SEIP);
+        assert_eq!(container(&[&password], &[], AEADMode::Always), Tag::AED);
+    }
+
+    #[test]
+    fn encryptor_policy() {
+        let password: Password = "streng geheim".into();
+
+        // Returns the tag of the encryption container, or an error.
+        let container = |algo: Option<SymmetricAlgorithm>, policy: &Policy|
+                         -> Result<Tag> {
+            let mut o = vec![];
+            {
+                let m = Message::new(&mut o);
+                let encryptor = Encryptor::with_policy(
+                    m, &[&password], &[], EncryptionMode::AtRest, algo,
+                    policy)?;
+                let mut literal = LiteralWriter::new(
+                    encryptor, DataFormat::Binary, None, None)?;
+                literal.write_all(b"Hello world.")?;
+                literal.finalize()?;
+            }
+            Ok(PacketPile::from_bytes(&o)?.children()
+               .map(|p| p.tag())
+               .find(|t| *t == Tag::AED || *t == Tag::SEIP)
+               .unwrap())
+        };
+
+        let p = StandardPolicy::new();
+        assert_eq!(container(None, &p).unwrap(), Tag::SEIP);
+        assert_eq!(container(None, &p.clone().require_aead(true)).unwrap(),
+                   Tag::AED);
+        assert!(container(None, &p.clone().require_aead(true)
+                          .aead_algos(&[])).is_err());
+
+        let p = p.symmetric_algos(&[SymmetricAlgorithm::AES128]);
+        assert!(container(None, &p).is_err());
+        assert_eq!(container(Some(SymmetricAlgorithm::AES128), &p).unwrap(),
+                   Tag::SEIP);
+    }
+
+    #[test]
+    fn recipient_key_selection() {
+        use tpk::{CipherSuite, TPKBuilder};
+
+        let (tpk, _) = TPKBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_signing_subkey()
+            .add_encryption_subkey()
+            .generate().unwrap();
+
+        let reports = select_encryption_keys(&[&tpk],
+                                             EncryptionMode::AtRest);
+        assert_eq!(reports.len(), 1);
+        let report = &reports[0];
+        assert_eq!(report.recipient(), &tpk.fingerprint());
+        assert!(report.is_usable());
+        assert_eq!(report.keys().len(), 3);
+        assert_eq!(report.keys()[0].1, KeySelection::NotEncryptionCapable);
+        assert_eq!(report.keys()[1].1, KeySelection::NotEncryptionCapable);
+        assert_eq!(report.keys()[2].1, KeySelection::Selected);
+        assert_eq!(report.selected(),
+                   vec![&tpk.subkeys().nth(1).unwrap().subkey()
+                        .fingerprint()]);
+
+        let sk = SessionKey::new(32);
+        let (pkesks, reports_) =
+            pkesks_for_recipients(&[&tpk], EncryptionMode::ForTransport,
+                                  SymmetricAlgorithm::AES256, &sk);
+        assert_eq!(pkesks.len(), 1);
+        assert_eq!(reports, reports_);
+
+        // A TPK without encryption-capable keys is rejected.
+        let (tpk, _) = TPKBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_signing_subkey()
+            .generate().unwrap();
+        let (pkesks, reports) =
+            pkesks_for_recipients(&[&tpk], EncryptionMode::AtRest,
+                                  SymmetricAlgorithm::AES256, &sk);
+        assert_eq!(pkesks.len(), 0);
+        assert!(! reports[0].is_usable());
+
+        let mut o = vec![];
+        let m = Message::new(&mut o);
+        assert!(Encryptor::new(m, &[], &[&tpk], EncryptionMode::AtRest,
+                               None).is_err());
+    }
+
+    #[test]
+    fn negotiate_algorithms() {
+        use tpk::{CipherSuite, TPKBuilder};
+
+        let (a, _) = TPKBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_encryption_subkey()
+            .generate().unwrap();
+        let (b, _) = TPKBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_encryption_subkey()
+            .generate().unwrap();
+
+        let n = super::negotiate_algorithms(&[&a, &b], EncryptionMode::AtRest,
+                                            &Default::default()).unwrap();
+        assert_eq!(n.symmetric_algo(), SymmetricAlgorithm::AES256);
+        assert_eq!(n.aead_algo(), Some(AEADAlgorithm::EAX));
+        assert_eq!(n.compression_algo(), CompressionAlgorithm::Uncompressed);
+
+        // The recipients only prefer AES256, but TripleDES is
+        // implicitly supported.
+        let policy = AlgorithmPolicy::default()
+            .symmetric_algos(&[SymmetricAlgorithm::AES128,
+                               SymmetricAlgorithm::TripleDES])
+            .aead_algos(&[]);
+        let n = super::negotiate_algorithms(&[&a, &b], EncryptionMode::AtRest,
+                                            &policy).unwrap();
+        assert_eq!(n.symmetric_algo(), SymmetricAlgorithm::TripleDES);
+        assert_eq!(n.aead_algo(), None);
+
+        let policy = AlgorithmPolicy::default().symmetric_algos(&[]);
+        assert!(super::negotiate_algorithms(&[&a], EncryptionMode::AtRest,
+                                            &policy).is_err());
+    }
+
+    #[test]
+    fn reencrypt() {
+        use KeyID;
+        use parse::stream::{
+            Decryptor, DecryptionHelper, MessageLayer, MessageStructure,
+            VerificationHelper, VerificationResult,
+        };
+
+        struct Helper {
+            password: Password,
+            tpk: TPK,
+            good: usize,
+        }
+        impl VerificationHelper for Helper {
+            fn get_public_keys(&mut self, _: &[KeyID]) -> Result<Vec<TPK>> {
+                Ok(vec![self.tpk.clone()])
+            }
+            fn check(&mut self, structure: &MessageStructure) -> Result<()> {
+                for layer in structure.iter() {
+                    if let MessageLayer::SignatureGroup { ref results } = layer {
+                        for result in results {
+                            if let VerificationResult::GoodChecksum(..) = result {
+                                self.good += 1;
+                            }
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+        impl DecryptionHelper for Helper {
+            fn decrypt<D>(&mut self, _: &[PKESK], skesks: &[SKESK],
+                          mut decrypt: D) -> Result<Option<Fingerprint>>
+                where D: FnMut(SymmetricAlgorithm, &SessionKey) -> Result<()>
+            {
+                skesks[0].decrypt(&self.password)
+                    .and_then(|(algo, session_key)| decrypt(algo, &session_key))
+                    .map(|_| None)
+            }
+        }
+
+        let tsk = TPK::from_bytes(::tests::key("testy-private.pgp")).unwrap();
+        let key = tsk.keys_all().signing_capable().nth(0).unwrap().2;
+        let mut pair = key.clone().into_keypair().unwrap();
+        let old: Password = "streng geheim".into();
+        let new: Password = "top secret".into();
+        let message = b"Tis, tis, tis.  Tis is important.";
+
+        // Write a signed message using an old cipher...
+        let mut o = vec![];
+        {
+            let m = Message::new(&mut o);
+            let encryptor = Encryptor::new(
+                m, &[&old], &[], EncryptionMode::AtRest,
+                SymmetricAlgorithm::TripleDES).unwrap();
+            let signer = Signer::new(
+                encryptor, vec![&mut pair as &mut dyn crypto::Signer], None)
+                .unwrap();
+            let mut literal = LiteralWriter::new(signer, DataFormat::Binary,
+                                                 None, None).unwrap();
+            literal.write_all(message).unwrap();
+            literal.finalize().unwrap();
+        }
+
+        // ... re-encrypt it...
+        let mut h = Helper { password: old, tpk: tsk.clone(), good: 0 };
+        let mut r = vec![];
+        super::reencrypt(&o[..], &mut r, &mut h, &[&new], &[],
+                         EncryptionMode::AtRest).unwrap();
+
+        // ... and check that the signature is still good.
+        let h = Helper { password: new, tpk: tsk.clone(), good: 0 };
+        let mut d = Decryptor::from_bytes(&r, h, None).unwrap();
+        let mut body = Vec::new();
+        d.read_to_end(&mut body).unwrap();
+        assert_eq!(&body[..], &message[..]);
+        assert_eq!(d.into_helper().good, 1);
+
+        // The old password no longer works.
+        let h = Helper { password: "streng geheim".into(), tpk: tsk,
+                         good: 0 };
+        assert!(Decryptor::from_bytes(&r, h, None).is_err());
+    }
+
+    #[test]
+    fn reencrypt_mdc() {
+        use parse::stream::DecryptionHelper;
+
+        struct Helper {
+            password: Password,
+        }
+        impl DecryptionHelper for Helper {
+            fn decrypt<D>(&mut self, _: &[PKESK], skesks: &[SKESK],
+                          mut decrypt: D) -> Result<Option<Fingerprint>>
+                where D: FnMut(SymmetricAlgorithm, &SessionKey) -> Result<()>
+            {
+                skesks[0].decrypt(&self.password)
+                    .and_then(|(algo, session_key)| decrypt(algo, &session_key))
+                    .map(|_| None)
+            }
+        }
+
+        let password: Password = "streng geheim".into();
+        let algo = SymmetricAlgorithm::AES128;
+
+        // Encrypts a literal data packet, followed by the MDC mangled
+        // by `mangle`, or no MDC at all.
+        let message = |mangle: Option<&dyn Fn(&mut Vec<u8>)>| -> Vec<u8> {
+            let mut plaintext = vec![0; algo.block_size().unwrap()];
+            crypto::random(&mut plaintext);
+            let quick_check = plaintext[plaintext.len() - 2..].to_vec();
+            plaintext.extend_from_slice(&quick_check);
+            let mut literal = Literal::new(DataFormat::Binary);
+            literal.set_body(b"Hello world.".to_vec());
+            Packet::Literal(literal).serialize(&mut plaintext).unwrap();
+
+            if let Some(mangle) = mangle {
+                let mut hash = HashAlgorithm::SHA1.context().unwrap();
+                hash.update(&plaintext);
+                hash.update(&[0xd3, 0x14]);
+                let mut mdc = vec![];
+                Packet::MDC(MDC::from(hash)).serialize(&mut mdc).unwrap();
+                mangle(&mut mdc);
+                plaintext.extend_from_slice(&mdc);
+            }
+
+            let sk = SessionKey::new(algo.key_size().unwrap());
+            let mut ciphertext = vec![];
+            {
+                let sink = writer::Generic::new(&mut ciphertext,
+                                                Cookie::default());
+                let mut encryptor = writer::Encryptor::new(
+                    sink, Cookie::default(), algo, &sk).unwrap();
+                encryptor.write_all(&plaintext).unwrap();
+                encryptor.finalize().unwrap();
+            }
+            let mut seip = SEIP1::new();
+            seip.set_body(ciphertext);
+
+            let skesk = SKESK4::with_password(algo, S2K::default(), &sk,
+                                              &password).unwrap();
+            let mut o = vec![];
+            Packet::SKESK(skesk.into()).serialize(&mut o).unwrap();
+            Packet::from(seip).serialize(&mut o).unwrap();
+            o
+        };
+
+        let reencrypt = |o: Vec<u8>| {
+            let mut h = Helper { password: password.clone() };
+            super::reencrypt(&o[..], io::sink(), &mut h, &[&password], &[],
+                             EncryptionMode::AtRest)
+        };
+
+        reencrypt(message(Some(&|_| ()))).unwrap();
+
+        // The MDC is missing.
+        assert!(reencrypt(message(None)).is_err());
+
+        // The MDC is corrupted.
+        assert!(reencrypt(message(Some(&|mdc| *mdc.last_mut().unwrap() ^= 1)))
+                .is_err());
+
+        // A second MDC follows the first one.
+        assert!(reencrypt(message(Some(&|mdc| {
+            let copy = mdc.clone();
+            mdc.extend_from_slice(&copy);
+        }))).is_err());
+    }
+
+    #[test]
+    fn escrow() {
+        use KeyID;
+        use parse::stream::{
+            Decryptor, DecryptionHelper, MessageStructure, VerificationHelper,
+            decrypt_session_key,
+        };
+
+        struct Helper {
+            keys: Vec<crypto::KeyPair>,
+            password: Option<Password>,
+            identity: Option<Fingerprint>,
+        }
+        impl VerificationHelper for Helper {
+            fn get_public_keys(&mut self, _: &[KeyID]) -> Result<Vec<TPK>> {
+                Ok(Vec::new())
+            }
+            fn check(&mut self, _: &MessageStructure) -> Result<()> {
+                Ok(())
+            }
+        }
+        impl DecryptionHelper for Helper {
+            fn decrypt<D>(&mut self, pkesks: &[PKESK], skesks: &[SKESK],
+                          decrypt: D) -> Result<Option<Fingerprint>>
+                where D: FnMut(SymmetricAlgorithm, &SessionKey) -> Result<()>
+            {
+                let mut decryptors = self.keys.iter_mut()
+                    .map(|k| k as &mut dyn crypto::Decryptor)
+                    .collect::<Vec<_>>();
+                self.identity =
+                    decrypt_session_key(pkesks, skesks, &mut decryptors,
+                                        self.password.as_ref(), decrypt)?;
+                Ok(self.identity.clone())
+            }
+        }
+
+        let tsk = TPK::from_bytes(::tests::key("testy-private.pgp")).unwrap();
+        let subkey = tsk.subkeys().nth(0).unwrap().subkey().clone();
+        let escrow: Password = "streng geheim".into();
+        let message = b"Back me up.";
+
+        // Escrow requires a recipient.
+        assert!(Encryptor::with_escrow(Message::new(vec![]), &[], &escrow,
+                                       EncryptionMode::AtRest, None)
+                .is_err());
+
+        let mut o = vec![];
+        {
+            let m = Message::new(&mut o);
+            let encryptor = Encryptor::with_escrow(
+                m, &[&tsk], &escrow, EncryptionMode::AtRest, None).unwrap();
+            let mut literal = LiteralWriter::new(encryptor, DataFormat::Binary,
+                                                 None, None).unwrap();
+            literal.write_all(message).unwrap();
+            literal.finalize().unwrap();
+        }
+
+        // Both the key and the password decrypt the message.
+        for (keys, password, identity) in vec![
+            (vec![subkey.clone().into_keypair().unwrap()], None,
+             Some(subkey.fingerprint())),
+            (vec![], Some(escrow.clone()), None),
+        ] {
+            let h = Helper { keys, password, identity: None };
+            let mut d = Decryptor::from_bytes(&o, h, None).unwrap();
+            let mut body = Vec::new();
+            d.read_to_end(&mut body).unwrap();
+            assert_eq!(&body[..], &message[..]);
+            assert_eq!(d.into_helper().identity, identity);
+        }
+
+        let h = Helper { keys: vec![], password: Some("top secret".into()),
+                         identity: None };
+        assert!(Decryptor::from_bytes(&o, h, None).is_err());
+    }
 }
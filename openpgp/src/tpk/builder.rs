@@ -52,7 +52,8 @@ impl CipherSuite {
                 Key4::generate_rsa(3072),
             CipherSuite::Cv25519 | CipherSuite::P256 |
             CipherSuite::P384 | CipherSuite::P521 => {
-                let sign = flags.can_certify() || flags.can_sign();
+                let sign = flags.can_certify() || flags.can_sign()
+                    || flags.can_authenticate();
                 let encrypt = flags.can_encrypt_for_transport()
                     || flags.can_encrypt_at_rest();
                 let curve = match self {
@@ -240,6 +241,14 @@ impl TPKBuilder {
         self.add_subkey(KeyFlags::default().set_certify(true))
     }
 
+    /// Adds an authentication capable subkey.
+    ///
+    /// Such keys are used, for instance, to authenticate SSH
+    /// sessions.
+    pub fn add_authentication_subkey(self) -> Self {
+        self.add_subkey(KeyFlags::default().set_authenticate(true))
+    }
+
     /// Adds a custom subkey
     pub fn add_subkey(mut self, flags: KeyFlags) -> Self {
         self.subkeys.push(KeyBlueprint{
@@ -427,6 +436,24 @@ mod tests {
         assert_eq!(tpk.subkeys().count(), 3);
     }
 
+    #[test]
+    fn authentication_subkey() {
+        let (tpk, _) = TPKBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_userid("test@example.com")
+            .add_authentication_subkey()
+            .generate().unwrap();
+
+        let keys = tpk.keys_valid().authentication_capable()
+            .map(|(_, _, key)| key.fingerprint())
+            .collect::<Vec<_>>();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0], tpk.subkeys().next().unwrap().subkey().fingerprint());
+        assert_eq!(tpk.subkeys().next().unwrap().subkey().pk_algo(),
+                   PublicKeyAlgorithm::EdDSA);
+        assert_eq!(tpk.keys_valid().signing_capable().count(), 0);
+    }
+
     #[test]
     fn direct_key_sig() {
         let (tpk, _) = TPKBuilder::new()
@@ -0,0 +1,165 @@
+//! Caches the status of a TPK's components.
+//!
+//! Revocation and expiration checks walk the signatures of a
+//! component.  Applications like the store and the streaming
+//! verifier do the same checks for the same certificates over and
+//! over, usually with the current time as reference time.  Hence, we
+//! cache the results, keyed by time buckets.
+//!
+//! The status of a component only changes at a few points in time,
+//! e.g., when a signature is created, or when the key expires.  A
+//! result is only cached if the bucket contains none of these
+//! events, hence it holds for any time within the bucket.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use time;
+
+/// The width of the time buckets, in seconds.
+const BUCKET_SIZE: i64 = 60;
+
+/// Bound the size of the cache.
+///
+/// If the cache grows larger, it is cleared.
+const MAX_ENTRIES: usize = 1024;
+
+/// Identifies a component of a TPK.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Component {
+    Primary,
+    Subkey(usize),
+}
+
+/// The revocation status, without references to the revocations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Revoked {
+    Yes,
+    CouldBe,
+    No,
+}
+
+// Cached results.  `None` means that the result changes within the
+// bucket, and must be computed every time.
+type Map<T> = HashMap<(Component, i64), Option<T>>;
+
+/// Caches the status of a TPK's components.
+///
+/// The cache is not part of the TPK's value, it is ignored when
+/// comparing TPKs.  It must be cleared whenever the TPK is changed.
+#[derive(Default)]
+pub(crate) struct StatusCache {
+    revoked: Mutex<Map<Revoked>>,
+    alive: Mutex<Map<bool>>,
+}
+
+impl StatusCache {
+    /// Forgets all results.
+    pub(crate) fn clear(&mut self) {
+        *self = Default::default();
+    }
+
+    /// Returns the revocation status of `c` at time `t`.
+    ///
+    /// If the status is not cached, it is computed using `f`.
+    /// `events` returns the times at which the status of `c` may
+    /// change.
+    pub(crate) fn revoked<E, F>(&self, c: Component, t: time::Tm,
+                                events: E, f: F) -> Revoked
+        where E: FnOnce() -> Vec<time::Tm>,
+              F: FnOnce() -> Revoked
+    {
+        lookup(&self.revoked, c, t, events, f)
+    }
+
+    /// Returns whether `c` is alive at time `t`.
+    ///
+    /// See `revoked` for the arguments.
+    pub(crate) fn alive<E, F>(&self, c: Component, t: time::Tm,
+                              events: E, f: F) -> bool
+        where E: FnOnce() -> Vec<time::Tm>,
+              F: FnOnce() -> bool
+    {
+        lookup(&self.alive, c, t, events, f)
+    }
+}
+
+fn lookup<T, E, F>(map: &Mutex<Map<T>>, c: Component, t: time::Tm,
+                   events: E, f: F) -> T
+    where T: Copy,
+          E: FnOnce() -> Vec<time::Tm>,
+          F: FnOnce() -> T
+{
+    let t = t.to_timespec().sec;
+    // Round down, also for times before the epoch.
+    let bucket = if t >= 0 { t / BUCKET_SIZE }
+                 else { (t + 1) / BUCKET_SIZE - 1 };
+
+    match map.lock().unwrap().get(&(c, bucket)) {
+        Some(Some(v)) => return *v,
+        Some(None) => return f(),
+        None => (),
+    }
+
+    let start = bucket * BUCKET_SIZE;
+    let stable = events().into_iter()
+        .map(|e| e.to_timespec().sec)
+        .all(|e| e < start || e >= start + BUCKET_SIZE);
+    let v = f();
+
+    let mut map = map.lock().unwrap();
+    if map.len() >= MAX_ENTRIES {
+        map.clear();
+    }
+    map.insert((c, bucket), if stable { Some(v) } else { None });
+    v
+}
+
+impl Clone for StatusCache {
+    fn clone(&self) -> Self {
+        // The results are still valid, but copying them is not
+        // worth it.
+        Default::default()
+    }
+}
+
+impl PartialEq for StatusCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for StatusCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("StatusCache")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn buckets() {
+        let cache = StatusCache::default();
+        let calls = Cell::new(0);
+        let compute = || { calls.set(calls.get() + 1); Revoked::No };
+        let t = time::at_utc(time::Timespec::new(1_500_000_000, 0));
+        let later = t + time::Duration::seconds(1);
+
+        // Nothing happens in the bucket, the result is cached.
+        cache.revoked(Component::Primary, t, || vec![], &compute);
+        cache.revoked(Component::Primary, later, || vec![], &compute);
+        assert_eq!(calls.get(), 1);
+
+        // Other components are cached separately.
+        cache.revoked(Component::Subkey(0), t, || vec![], &compute);
+        assert_eq!(calls.get(), 2);
+
+        // Something happens in the bucket, the result is not cached.
+        cache.revoked(Component::Subkey(1), t, || vec![later], &compute);
+        cache.revoked(Component::Subkey(1), t, || vec![later], &compute);
+        assert_eq!(calls.get(), 4);
+    }
+}
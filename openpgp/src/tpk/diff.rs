@@ -0,0 +1,345 @@
+//! Computes the differences between two versions of a TPK.
+
+use std::fmt;
+
+use {
+    Error,
+    Fingerprint,
+    Result,
+    TPK,
+};
+use packet::{Key, Signature, UserID, UserAttribute};
+
+/// The component of a TPK that a signature in a [`TPKDiff`] belongs
+/// to.
+///
+///   [`TPKDiff`]: struct.TPKDiff.html
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffSubject {
+    /// The primary key.
+    PrimaryKey(Fingerprint),
+    /// A subkey.
+    Subkey(Fingerprint),
+    /// A User ID.
+    UserID(UserID),
+    /// A User Attribute.
+    UserAttribute(UserAttribute),
+}
+
+impl fmt::Display for DiffSubject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiffSubject::PrimaryKey(fp) => write!(f, "primary key {}", fp),
+            DiffSubject::Subkey(fp) => write!(f, "subkey {}", fp),
+            DiffSubject::UserID(uid) => write!(f, "user id {}", uid),
+            DiffSubject::UserAttribute(_) => f.write_str("user attribute"),
+        }
+    }
+}
+
+/// The differences between two versions of a TPK.
+///
+/// See [`TPK::diff`].
+///
+///   [`TPK::diff`]: ../struct.TPK.html#method.diff
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TPKDiff {
+    added_userids: Vec<UserID>,
+    removed_userids: Vec<UserID>,
+    added_user_attributes: Vec<UserAttribute>,
+    removed_user_attributes: Vec<UserAttribute>,
+    added_subkeys: Vec<Key>,
+    removed_subkeys: Vec<Key>,
+    added_signatures: Vec<(DiffSubject, Signature)>,
+    removed_signatures: Vec<(DiffSubject, Signature)>,
+}
+
+impl TPKDiff {
+    /// Returns whether the two versions are the same.
+    pub fn is_empty(&self) -> bool {
+        self.added_userids.is_empty()
+            && self.removed_userids.is_empty()
+            && self.added_user_attributes.is_empty()
+            && self.removed_user_attributes.is_empty()
+            && self.added_subkeys.is_empty()
+            && self.removed_subkeys.is_empty()
+            && self.added_signatures.is_empty()
+            && self.removed_signatures.is_empty()
+    }
+
+    /// Returns the User IDs only present in the new version.
+    pub fn added_userids(&self) -> &[UserID] {
+        &self.added_userids
+    }
+
+    /// Returns the User IDs only present in the old version.
+    pub fn removed_userids(&self) -> &[UserID] {
+        &self.removed_userids
+    }
+
+    /// Returns the User Attributes only present in the new version.
+    pub fn added_user_attributes(&self) -> &[UserAttribute] {
+        &self.added_user_attributes
+    }
+
+    /// Returns the User Attributes only present in the old version.
+    pub fn removed_user_attributes(&self) -> &[UserAttribute] {
+        &self.removed_user_attributes
+    }
+
+    /// Returns the subkeys only present in the new version.
+    pub fn added_subkeys(&self) -> &[Key] {
+        &self.added_subkeys
+    }
+
+    /// Returns the subkeys only present in the old version.
+    pub fn removed_subkeys(&self) -> &[Key] {
+        &self.removed_subkeys
+    }
+
+    /// Returns the signatures only present in the new version.
+    ///
+    /// This includes the signatures of added components.
+    pub fn added_signatures(&self) -> &[(DiffSubject, Signature)] {
+        &self.added_signatures
+    }
+
+    /// Returns the signatures only present in the old version.
+    ///
+    /// This includes the signatures of removed components.
+    pub fn removed_signatures(&self) -> &[(DiffSubject, Signature)] {
+        &self.removed_signatures
+    }
+}
+
+impl fmt::Display for TPKDiff {
+    /// Summarizes the differences in a single line.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("no changes");
+        }
+
+        let counts = [
+            (self.added_userids.len(), "user id", "added"),
+            (self.removed_userids.len(), "user id", "removed"),
+            (self.added_user_attributes.len(), "user attribute", "added"),
+            (self.removed_user_attributes.len(), "user attribute",
+             "removed"),
+            (self.added_subkeys.len(), "subkey", "added"),
+            (self.removed_subkeys.len(), "subkey", "removed"),
+            (self.added_signatures.len(), "signature", "added"),
+            (self.removed_signatures.len(), "signature", "removed"),
+        ];
+
+        let mut first = true;
+        for &(n, what, how) in counts.iter().filter(|c| c.0 > 0) {
+            if ! first {
+                f.write_str(", ")?;
+            }
+            first = false;
+            write!(f, "{} {}{} {}", n, what, if n == 1 { "" } else { "s" },
+                   how)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the signatures of `tpk` along with their components.
+///
+/// Unknown components and signatures that could not be associated
+/// with a component are not considered.
+fn signatures(tpk: &TPK) -> Vec<(DiffSubject, &Signature)> {
+    let mut sigs = Vec::new();
+
+    let subject = DiffSubject::PrimaryKey(tpk.fingerprint());
+    for s in tpk.selfsigs().iter()
+        .chain(tpk.certifications().iter())
+        .chain(tpk.self_revocations().iter())
+        .chain(tpk.other_revocations().iter())
+    {
+        sigs.push((subject.clone(), s));
+    }
+
+    for uidb in tpk.userids() {
+        let subject = DiffSubject::UserID(uidb.userid().clone());
+        for s in uidb.selfsigs().iter()
+            .chain(uidb.certifications().iter())
+            .chain(uidb.self_revocations().iter())
+            .chain(uidb.other_revocations().iter())
+        {
+            sigs.push((subject.clone(), s));
+        }
+    }
+
+    for uab in tpk.user_attributes() {
+        let subject =
+            DiffSubject::UserAttribute(uab.user_attribute().clone());
+        for s in uab.selfsigs().iter()
+            .chain(uab.certifications().iter())
+            .chain(uab.self_revocations().iter())
+            .chain(uab.other_revocations().iter())
+        {
+            sigs.push((subject.clone(), s));
+        }
+    }
+
+    for skb in tpk.subkeys() {
+        let subject = DiffSubject::Subkey(skb.subkey().fingerprint());
+        for s in skb.selfsigs().iter()
+            .chain(skb.certifications().iter())
+            .chain(skb.self_revocations().iter())
+            .chain(skb.other_revocations().iter())
+        {
+            sigs.push((subject.clone(), s));
+        }
+    }
+
+    sigs
+}
+
+/// Returns the elements of `a` that are not in `b`.
+fn missing<T: PartialEq + Clone>(a: &[T], b: &[T]) -> Vec<T> {
+    a.iter().filter(|x| ! b.contains(x)).cloned().collect()
+}
+
+/// Returns the signatures in `a` that are not in `b`.
+///
+/// Signatures are compared using their content digests, which are
+/// computed when parsing TPKs, so this avoids serializing them.
+fn missing_signatures(a: &[(DiffSubject, &Signature)],
+                      b: &[(DiffSubject, &Signature)])
+                      -> Result<Vec<(DiffSubject, Signature)>> {
+    let b = b.iter().map(|(c, s)| Ok((c, s.content_digest()?)))
+        .collect::<Result<Vec<_>>>()?;
+    let mut missing = Vec::new();
+    for (c, s) in a.iter() {
+        let digest = s.content_digest()?;
+        if ! b.iter().any(|(bc, bd)| *bc == c && *bd == digest) {
+            missing.push((c.clone(), (*s).clone()));
+        }
+    }
+    Ok(missing)
+}
+
+impl TPK {
+    /// Computes the differences between this TPK and `other`.
+    ///
+    /// `other` is considered to be the newer version, i.e. the
+    /// components and signatures only present in `other` are
+    /// reported as added.  Keys are compared using their public
+    /// parts, and signatures including their unhashed subpacket
+    /// areas.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the TPKs have different primary keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use sequoia_openpgp::{*, tpk::*};
+    /// # f().unwrap();
+    /// # fn f() -> Result<()> {
+    /// let (tpk, _) = TPKBuilder::new()
+    ///     .add_userid("alice@example.org")
+    ///     .generate()?;
+    /// let diff = tpk.diff(&tpk)?;
+    /// assert!(diff.is_empty());
+    /// assert_eq!(format!("{}", diff), "no changes");
+    /// # Ok(()) }
+    /// ```
+    pub fn diff(&self, other: &TPK) -> Result<TPKDiff> {
+        if self.fingerprint() != other.fingerprint() {
+            return Err(Error::InvalidArgument(
+                format!("Cannot compare {} with {}",
+                        self.fingerprint(), other.fingerprint())).into());
+        }
+
+        let userids = |tpk: &TPK| -> Vec<UserID> {
+            tpk.userids().map(|b| b.userid().clone()).collect()
+        };
+        let user_attributes = |tpk: &TPK| -> Vec<UserAttribute> {
+            tpk.user_attributes().map(|b| b.user_attribute().clone())
+                .collect()
+        };
+        let subkeys = |tpk: &TPK| -> Vec<Key> {
+            tpk.subkeys().map(|b| {
+                let mut key = b.subkey().clone();
+                key.set_secret(None);
+                key
+            }).collect()
+        };
+
+        let (a, b) = (userids(self), userids(other));
+        let (added_userids, removed_userids) =
+            (missing(&b, &a), missing(&a, &b));
+        let (a, b) = (user_attributes(self), user_attributes(other));
+        let (added_user_attributes, removed_user_attributes) =
+            (missing(&b, &a), missing(&a, &b));
+        let (a, b) = (subkeys(self), subkeys(other));
+        let (added_subkeys, removed_subkeys) =
+            (missing(&b, &a), missing(&a, &b));
+        let (a, b) = (signatures(self), signatures(other));
+        let (added_signatures, removed_signatures) =
+            (missing_signatures(&b, &a)?, missing_signatures(&a, &b)?);
+
+        Ok(TPKDiff {
+            added_userids: added_userids,
+            removed_userids: removed_userids,
+            added_user_attributes: added_user_attributes,
+            removed_user_attributes: removed_user_attributes,
+            added_subkeys: added_subkeys,
+            removed_subkeys: removed_subkeys,
+            added_signatures: added_signatures,
+            removed_signatures: removed_signatures,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use PacketPile;
+    use packet::Tag;
+    use tpk::{TPKBuilder, CipherSuite};
+
+    #[test]
+    fn diff() {
+        let (tpk, _) = TPKBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_userid("test@example.org")
+            .add_encryption_subkey()
+            .generate().unwrap();
+        assert!(tpk.diff(&tpk).unwrap().is_empty());
+
+        let (other, _) = TPKBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .generate().unwrap();
+        assert!(tpk.diff(&other).is_err());
+
+        // The subkey and its binding signature come last.
+        let old = TPK::from_packet_pile(PacketPile::from(
+            tpk.clone().into_packets().into_iter()
+                .take_while(|p| p.tag() != Tag::PublicSubkey)
+                .collect::<Vec<_>>())).unwrap();
+        let skb = tpk.subkeys().next().unwrap();
+
+        let diff = old.diff(&tpk).unwrap();
+        assert_eq!(diff.added_subkeys().len(), 1);
+        assert_eq!(diff.added_subkeys()[0].fingerprint(),
+                   skb.subkey().fingerprint());
+        assert_eq!(diff.added_signatures().len(), 1);
+        assert_eq!(diff.added_signatures()[0].0,
+                   DiffSubject::Subkey(skb.subkey().fingerprint()));
+        assert!(diff.added_userids().is_empty());
+        assert!(diff.removed_subkeys().is_empty());
+        assert!(diff.removed_signatures().is_empty());
+        assert_eq!(format!("{}", diff),
+                   "1 subkey added, 1 signature added");
+
+        let diff = tpk.diff(&old).unwrap();
+        assert_eq!(diff.removed_subkeys().len(), 1);
+        assert_eq!(diff.removed_signatures().len(), 1);
+        assert!(diff.added_subkeys().is_empty());
+    }
+}
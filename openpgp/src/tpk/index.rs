@@ -0,0 +1,396 @@
+//! Keyring indices.
+//!
+//! Finding a TPK in a keyring requires parsing the keyring from the
+//! start.  For large keyrings that are consulted repeatedly, this is
+//! wasteful.  A [`KeyringIndex`] maps the Key IDs of all primary keys
+//! and subkeys in a keyring to the offsets of the TPKs containing
+//! them, so that a TPK can be read directly.
+//!
+//! Indices are stored in sidecar files, see
+//! [`KeyringIndex::sidecar`].  Looking up a key only reads a
+//! logarithmic number of records from the index, and the TPKs at the
+//! offsets found, independently of the size of the keyring.
+//!
+//!   [`KeyringIndex`]: struct.KeyringIndex.html
+//!   [`KeyringIndex::sidecar`]: struct.KeyringIndex.html#method.sidecar
+//!
+//! # Format
+//!
+//! An index starts with the magic string `SQKRIDX\x01`, followed by
+//! the size of the keyring it was created for, and the number of
+//! records, both as big-endian 64-bit integers.  Every record
+//! consists of a Key ID (8 bytes) and the offset of the TPK in the
+//! keyring (big-endian 64-bit integer).  The records are sorted by
+//! Key ID and offset.
+//!
+//! Only binary keyrings can be indexed.
+
+use std::cmp;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use buffered_reader::{self, BufferedReader};
+
+use {
+    Error,
+    Fingerprint,
+    KeyID,
+    Packet,
+    Result,
+    TPK,
+    BodyLength,
+};
+use packet::{Header, Tag};
+use parse::Parse;
+use super::TPKParser;
+
+/// Identifies keyring indices.
+const MAGIC: &'static [u8] = b"SQKRIDX\x01";
+
+/// The size of the index header.
+const HEADER_SIZE: u64 = 8 + 8 + 8;
+
+/// The size of a record.
+const RECORD_SIZE: u64 = 8 + 8;
+
+/// The maximum size of a packet header.
+const MAX_PACKET_HEADER_SIZE: usize = 6;
+
+/// The number of bytes consumed at once when skipping packets.
+const SKIP_CHUNK_SIZE: usize = 8 * 1024;
+
+/// An index of a keyring.
+///
+/// # Example
+///
+/// ```
+/// # extern crate sequoia_openpgp as openpgp;
+/// # use std::io::Cursor;
+/// # use openpgp::Result;
+/// # use openpgp::tpk::{TPKBuilder, CipherSuite, KeyringIndex};
+/// # use openpgp::serialize::Serialize;
+/// # fn main() { f().unwrap(); }
+/// # fn f() -> Result<()> {
+/// let (alice, _) = TPKBuilder::new()
+///     .set_cipher_suite(CipherSuite::Cv25519)
+///     .add_userid("alice@example.org")
+///     .generate()?;
+/// let (bob, _) = TPKBuilder::new()
+///     .set_cipher_suite(CipherSuite::Cv25519)
+///     .add_userid("bob@example.org")
+///     .add_encryption_subkey()
+///     .generate()?;
+///
+/// let mut keyring = Vec::new();
+/// alice.serialize(&mut keyring)?;
+/// bob.serialize(&mut keyring)?;
+///
+/// let mut index = Vec::new();
+/// KeyringIndex::build(&keyring[..], &mut index)?;
+///
+/// let mut index = KeyringIndex::new(Cursor::new(index))?;
+/// let mut keyring = Cursor::new(keyring);
+/// let tpk = index.lookup(&mut keyring, &bob.fingerprint())?;
+/// assert_eq!(tpk, Some(bob));
+/// # Ok(())
+/// # }
+/// ```
+pub struct KeyringIndex<R: Read + Seek> {
+    index: R,
+    keyring_size: u64,
+    records: u64,
+}
+
+impl KeyringIndex<File> {
+    /// Opens the index stored in the file `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::new(File::open(path)?)
+    }
+
+    /// Returns the path of the sidecar index of the keyring `path`.
+    ///
+    /// The index of `keyring.pgp` is stored in `keyring.pgp.idx`.
+    pub fn sidecar<P: AsRef<Path>>(path: P) -> PathBuf {
+        let mut name = path.as_ref().as_os_str().to_os_string();
+        name.push(".idx");
+        PathBuf::from(name)
+    }
+
+    /// Indexes the keyring `path`, and writes the index to its
+    /// sidecar file.
+    pub fn build_sidecar<P: AsRef<Path>>(path: P) -> Result<()> {
+        let keyring = File::open(path.as_ref())?;
+        let mut index = File::create(Self::sidecar(path))?;
+        Self::build(keyring, &mut index)
+    }
+
+    /// Indexes `keyring`, and writes the index to `sink`.
+    ///
+    /// Reading the keyring requires memory proportional to the
+    /// largest key packet, and the number of keys in it.
+    pub fn build<K: Read, W: Write>(keyring: K, sink: &mut W) -> Result<()> {
+        let mut bio = buffered_reader::Generic::new(keyring, None);
+        let mut records: Vec<([u8; 8], u64)> = Vec::new();
+        let mut offset: u64 = 0;
+        let mut primary: Option<u64> = None;
+
+        while ! bio.data(1)?.is_empty() {
+            let (header, header_len) = {
+                let data = bio.data(MAX_PACKET_HEADER_SIZE)?;
+                let mut mem = buffered_reader::Memory::new(data);
+                let header = Header::parse(&mut mem)?;
+                (header, mem.total_out())
+            };
+
+            let body_len = match header.length {
+                BodyLength::Full(l) => l as usize,
+                _ => return Err(Error::MalformedPacket(
+                    format!("Unsupported length encoding of {} packet",
+                            header.ctb.tag)).into()),
+            };
+            let packet_len = header_len + body_len;
+
+            match header.ctb.tag {
+                Tag::PublicKey | Tag::SecretKey
+                    | Tag::PublicSubkey | Tag::SecretSubkey => {
+                    let key = match Packet::from_bytes(
+                        &bio.data_hard(packet_len)?[..packet_len])?
+                    {
+                        Packet::PublicKey(k) | Packet::SecretKey(k)
+                            | Packet::PublicSubkey(k)
+                            | Packet::SecretSubkey(k) => k,
+                        p => return Err(Error::MalformedPacket(
+                            format!("Expected a key, got {}", p.tag())).into()),
+                    };
+
+                    if header.ctb.tag == Tag::PublicKey
+                        || header.ctb.tag == Tag::SecretKey
+                    {
+                        primary = Some(offset);
+                    }
+
+                    let keyid = key.keyid();
+                    match (primary, keyid.as_slice().len()) {
+                        (Some(primary), 8) => {
+                            let mut id = [0; 8];
+                            id.copy_from_slice(keyid.as_slice());
+                            records.push((id, primary));
+                        },
+                        // A subkey without primary key, or a key
+                        // using a Key ID format we do not know.
+                        _ => (),
+                    }
+                    bio.consume(packet_len);
+                },
+                _ => {
+                    // Skip the packet in small steps to avoid
+                    // buffering large packets, like photos.
+                    let mut left = packet_len;
+                    while left > 0 {
+                        let n = cmp::min(left, SKIP_CHUNK_SIZE);
+                        bio.data_consume_hard(n)?;
+                        left -= n;
+                    }
+                },
+            }
+
+            offset += packet_len as u64;
+        }
+
+        records.sort();
+        records.dedup();
+
+        sink.write_all(MAGIC)?;
+        write_be_u64(sink, offset)?;
+        write_be_u64(sink, records.len() as u64)?;
+        for (keyid, offset) in records {
+            sink.write_all(&keyid)?;
+            write_be_u64(sink, offset)?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> KeyringIndex<R> {
+    /// Reads an index from `index`.
+    pub fn new(mut index: R) -> Result<Self> {
+        let mut header = [0; HEADER_SIZE as usize];
+        index.seek(SeekFrom::Start(0))?;
+        index.read_exact(&mut header)?;
+        if &header[..8] != MAGIC {
+            return Err(Error::MalformedMessage(
+                "Not a keyring index".into()).into());
+        }
+
+        let keyring_size = be_u64(&header[8..16]);
+        let records = be_u64(&header[16..24]);
+        if index.seek(SeekFrom::End(0))? != HEADER_SIZE + records * RECORD_SIZE
+        {
+            return Err(Error::MalformedMessage(
+                "Truncated keyring index".into()).into());
+        }
+
+        Ok(KeyringIndex {
+            index: index,
+            keyring_size: keyring_size,
+            records: records,
+        })
+    }
+
+    /// Returns the size of the keyring that this index was created
+    /// for.
+    pub fn keyring_size(&self) -> u64 {
+        self.keyring_size
+    }
+
+    /// Returns the offsets of the TPKs that may contain a key with
+    /// the given Key ID.
+    ///
+    /// The offsets are sorted.  Since Key IDs are not unique, the
+    /// TPKs must be checked after reading them.
+    pub fn offsets(&mut self, keyid: &KeyID) -> Result<Vec<u64>> {
+        let id = keyid.as_slice();
+        if id.len() != 8 {
+            return Ok(Vec::new());
+        }
+
+        // Find the first record with the given Key ID.
+        let (mut lo, mut hi) = (0, self.records);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if &self.record(mid)?.0[..] < id {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+
+        let mut offsets = Vec::new();
+        for i in lo..self.records {
+            let (k, offset) = self.record(i)?;
+            if &k[..] != id {
+                break;
+            }
+            offsets.push(offset);
+        }
+        Ok(offsets)
+    }
+
+    /// Returns the TPKs in `keyring` that contain a key with the
+    /// given Key ID.
+    ///
+    /// Fails if `keyring` does not have the size recorded in the
+    /// index, as the index is likely stale.
+    pub fn lookup_keyid<K: Read + Seek>(&mut self, keyring: &mut K,
+                                        keyid: &KeyID)
+                                        -> Result<Vec<TPK>> {
+        if keyring.seek(SeekFrom::End(0))? != self.keyring_size {
+            return Err(Error::InvalidOperation(
+                "Keyring index is stale".into()).into());
+        }
+
+        let mut tpks = Vec::new();
+        for offset in self.offsets(keyid)? {
+            keyring.seek(SeekFrom::Start(offset))?;
+            let tpk = TPKParser::from_reader(&mut *keyring)?.next()
+                .ok_or_else(|| Error::MalformedTPK(
+                    format!("No TPK at offset {}", offset)))??;
+            if tpk.keys_all().any(|(_, _, k)| k.keyid() == *keyid) {
+                tpks.push(tpk);
+            }
+        }
+        Ok(tpks)
+    }
+
+    /// Returns the TPK in `keyring` that contains a key with the
+    /// given fingerprint.
+    ///
+    /// Fails if `keyring` does not have the size recorded in the
+    /// index, as the index is likely stale.
+    pub fn lookup<K: Read + Seek>(&mut self, keyring: &mut K,
+                                  fp: &Fingerprint)
+                                  -> Result<Option<TPK>> {
+        Ok(self.lookup_keyid(keyring, &fp.to_keyid())?.into_iter()
+           .find(|tpk| tpk.keys_all().any(|(_, _, k)| k.fingerprint() == *fp)))
+    }
+
+    /// Reads the `i`th record.
+    fn record(&mut self, i: u64) -> io::Result<([u8; 8], u64)> {
+        let mut record = [0; RECORD_SIZE as usize];
+        self.index.seek(SeekFrom::Start(HEADER_SIZE + i * RECORD_SIZE))?;
+        self.index.read_exact(&mut record)?;
+        let mut keyid = [0; 8];
+        keyid.copy_from_slice(&record[..8]);
+        Ok((keyid, be_u64(&record[8..])))
+    }
+}
+
+fn write_be_u64<W: Write>(sink: &mut W, v: u64) -> io::Result<()> {
+    let mut b = [0; 8];
+    for i in 0..8 {
+        b[i] = (v >> (56 - 8 * i)) as u8;
+    }
+    sink.write_all(&b)
+}
+
+fn be_u64(b: &[u8]) -> u64 {
+    b[..8].iter().fold(0, |acc, &b| (acc << 8) | b as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tpk::{TPKBuilder, CipherSuite};
+    use serialize::Serialize;
+
+    #[test]
+    fn roundtrip() {
+        let tpks: Vec<TPK> = (0..5).map(|i| {
+            TPKBuilder::new()
+                .set_cipher_suite(CipherSuite::Cv25519)
+                .add_userid(format!("{}@example.org", i))
+                .add_encryption_subkey()
+                .generate().unwrap().0
+        }).collect();
+
+        let mut keyring = Vec::new();
+        for tpk in tpks.iter() {
+            tpk.serialize(&mut keyring).unwrap();
+        }
+
+        let mut index = Vec::new();
+        KeyringIndex::build(&keyring[..], &mut index).unwrap();
+        assert_eq!(index.len() as u64,
+                   HEADER_SIZE + 2 * tpks.len() as u64 * RECORD_SIZE);
+
+        let mut index = KeyringIndex::new(Cursor::new(index)).unwrap();
+        assert_eq!(index.keyring_size(), keyring.len() as u64);
+
+        let mut keyring = Cursor::new(keyring);
+        for tpk in tpks.iter() {
+            for (_, _, key) in tpk.keys_all() {
+                assert_eq!(index.lookup(&mut keyring, &key.fingerprint())
+                           .unwrap().as_ref(),
+                           Some(tpk));
+            }
+        }
+
+        let (stranger, _) = TPKBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .generate().unwrap();
+        assert_eq!(index.lookup(&mut keyring, &stranger.fingerprint())
+                   .unwrap(), None);
+
+        // Changing the keyring invalidates the index.
+        stranger.serialize(keyring.get_mut()).unwrap();
+        assert!(index.lookup(&mut keyring, &stranger.fingerprint()).is_err());
+    }
+
+    #[test]
+    fn not_an_index() {
+        assert!(KeyringIndex::new(Cursor::new(vec![0; 64])).is_err());
+        assert!(KeyringIndex::new(Cursor::new(MAGIC.to_vec())).is_err());
+    }
+}
@@ -0,0 +1,299 @@
+//! Checks TPKs for weak cryptography and other problems.
+
+use std::fmt;
+use time;
+
+use {
+    Fingerprint,
+    HashAlgorithm,
+    PublicKeyAlgorithm,
+    TPK,
+};
+use packet::{Key, Signature, UserID, UserAttribute};
+
+/// Controls what [`TPK::lint`] considers to be a problem.
+///
+/// The default policy flags self-signatures using MD5, SHA-1 or
+/// RIPEMD-160, and RSA, DSA, and ElGamal keys shorter than 2048
+/// bits.
+///
+///   [`TPK::lint`]: ../struct.TPK.html#method.lint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintPolicy {
+    weak_hash_algos: Vec<HashAlgorithm>,
+    min_bits: usize,
+    time: Option<time::Tm>,
+}
+
+impl Default for LintPolicy {
+    fn default() -> Self {
+        LintPolicy {
+            weak_hash_algos: vec![
+                HashAlgorithm::MD5,
+                HashAlgorithm::SHA1,
+                HashAlgorithm::RipeMD,
+            ],
+            min_bits: 2048,
+            time: None,
+        }
+    }
+}
+
+impl LintPolicy {
+    /// Sets the hash algorithms that are considered weak for
+    /// self-signatures.
+    pub fn weak_hash_algos(mut self, algos: &[HashAlgorithm]) -> Self {
+        self.weak_hash_algos = algos.to_vec();
+        self
+    }
+
+    /// Sets the minimum size of RSA, DSA, and ElGamal keys.
+    pub fn min_bits(mut self, bits: usize) -> Self {
+        self.min_bits = bits;
+        self
+    }
+
+    /// Sets the reference time for expiration checks.
+    ///
+    /// If not set, the current time is used.
+    pub fn at<T>(mut self, time: T) -> Self
+        where T: Into<Option<time::Tm>>
+    {
+        self.time = time.into();
+        self
+    }
+}
+
+/// The component of a TPK that a [`Lint`] refers to.
+///
+///   [`Lint`]: enum.Lint.html
+#[derive(Debug, Clone, PartialEq)]
+pub enum LintSubject {
+    /// The primary key.
+    PrimaryKey(Fingerprint),
+    /// A subkey.
+    Subkey(Fingerprint),
+    /// A User ID.
+    UserID(UserID),
+    /// A User Attribute.
+    UserAttribute(UserAttribute),
+}
+
+impl fmt::Display for LintSubject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LintSubject::PrimaryKey(fp) => write!(f, "primary key {}", fp),
+            LintSubject::Subkey(fp) => write!(f, "subkey {}", fp),
+            LintSubject::UserID(uid) => write!(f, "user id {}", uid),
+            LintSubject::UserAttribute(_) => f.write_str("user attribute"),
+        }
+    }
+}
+
+/// A problem found by [`TPK::lint`].
+///
+///   [`TPK::lint`]: ../struct.TPK.html#method.lint
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lint {
+    /// The binding signature uses a weak hash algorithm.
+    WeakHashAlgorithm {
+        /// The component the signature binds.
+        subject: LintSubject,
+        /// The hash algorithm.
+        hash_algo: HashAlgorithm,
+    },
+    /// The key is too short.
+    WeakKey {
+        /// The affected key.
+        subject: LintSubject,
+        /// The key's algorithm.
+        pk_algo: PublicKeyAlgorithm,
+        /// The key's size.
+        bits: usize,
+    },
+    /// The binding signature does not contain any key flags.
+    ///
+    /// Implementations differ in how they interpret such keys.
+    MissingKeyFlags {
+        /// The affected key.
+        subject: LintSubject,
+    },
+    /// The subkey has expired, but is still part of the TPK.
+    ExpiredSubkey {
+        /// The affected subkey.
+        subject: LintSubject,
+    },
+}
+
+impl Lint {
+    /// Returns the component of the TPK that this finding refers to.
+    pub fn subject(&self) -> &LintSubject {
+        match self {
+            Lint::WeakHashAlgorithm { subject, .. } => subject,
+            Lint::WeakKey { subject, .. } => subject,
+            Lint::MissingKeyFlags { subject } => subject,
+            Lint::ExpiredSubkey { subject } => subject,
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lint::WeakHashAlgorithm { subject, hash_algo } =>
+                write!(f, "The binding signature of the {} uses {}",
+                       subject, hash_algo),
+            Lint::WeakKey { subject, pk_algo, bits } =>
+                write!(f, "The {} is a {} bit {} key",
+                       subject, bits, pk_algo),
+            Lint::MissingKeyFlags { subject } =>
+                write!(f, "The binding signature of the {} has no key flags",
+                       subject),
+            Lint::ExpiredSubkey { subject } =>
+                write!(f, "The {} has expired", subject),
+        }
+    }
+}
+
+impl TPK {
+    /// Checks the TPK for weak cryptography and other problems.
+    ///
+    /// This looks at the current binding signatures only.  Returns
+    /// an empty vector if no problems were found.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use sequoia_openpgp::{*, tpk::*};
+    /// # f().unwrap();
+    /// # fn f() -> Result<()> {
+    /// let (tpk, _) = TPKBuilder::new()
+    ///     .set_cipher_suite(CipherSuite::Cv25519)
+    ///     .add_encryption_subkey()
+    ///     .generate()?;
+    /// assert!(tpk.lint(&LintPolicy::default()).is_empty());
+    /// # Ok(()) }
+    /// ```
+    pub fn lint(&self, policy: &LintPolicy) -> Vec<Lint> {
+        let t = policy.time.unwrap_or_else(time::now_utc);
+        let mut lints = Vec::new();
+
+        let check_sig = |lints: &mut Vec<Lint>, subject: &LintSubject,
+                         sig: Option<&Signature>| {
+            if let Some(sig) = sig {
+                if policy.weak_hash_algos.contains(&sig.hash_algo()) {
+                    lints.push(Lint::WeakHashAlgorithm {
+                        subject: subject.clone(),
+                        hash_algo: sig.hash_algo(),
+                    });
+                }
+            }
+        };
+
+        let check_key = |lints: &mut Vec<Lint>, subject: &LintSubject,
+                         key: &Key, sig: Option<&Signature>| {
+            #[allow(deprecated)]
+            let finite_field = match key.pk_algo() {
+                PublicKeyAlgorithm::RSAEncryptSign
+                    | PublicKeyAlgorithm::RSAEncrypt
+                    | PublicKeyAlgorithm::RSASign
+                    | PublicKeyAlgorithm::DSA
+                    | PublicKeyAlgorithm::ElgamalEncrypt
+                    | PublicKeyAlgorithm::ElgamalEncryptSign => true,
+                _ => false,
+            };
+            if finite_field {
+                if let Some(bits) = key.mpis().bits() {
+                    if bits < policy.min_bits {
+                        lints.push(Lint::WeakKey {
+                            subject: subject.clone(),
+                            pk_algo: key.pk_algo(),
+                            bits: bits,
+                        });
+                    }
+                }
+            }
+
+            if let Some(sig) = sig {
+                if sig.key_flags().is_empty() {
+                    lints.push(Lint::MissingKeyFlags {
+                        subject: subject.clone(),
+                    });
+                }
+            }
+        };
+
+        let subject = LintSubject::PrimaryKey(self.fingerprint());
+        check_sig(&mut lints, &subject, self.primary_key_signature());
+        check_key(&mut lints, &subject, self.primary(),
+                  self.primary_key_signature());
+
+        for skb in self.subkeys() {
+            let subject = LintSubject::Subkey(skb.subkey().fingerprint());
+            check_sig(&mut lints, &subject, skb.binding_signature());
+            check_key(&mut lints, &subject, skb.subkey(),
+                      skb.binding_signature());
+
+            if let Some(sig) = skb.binding_signature() {
+                if sig.key_expired_at(skb.subkey(), t) {
+                    lints.push(Lint::ExpiredSubkey { subject: subject });
+                }
+            }
+        }
+
+        for uidb in self.userids() {
+            let subject = LintSubject::UserID(uidb.userid().clone());
+            check_sig(&mut lints, &subject, uidb.binding_signature());
+        }
+
+        for uab in self.user_attributes() {
+            let subject =
+                LintSubject::UserAttribute(uab.user_attribute().clone());
+            check_sig(&mut lints, &subject, uab.binding_signature());
+        }
+
+        lints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse::Parse;
+    use tpk::{TPKBuilder, CipherSuite};
+
+    #[test]
+    fn lint_generated() {
+        let (tpk, _) = TPKBuilder::new()
+            .set_cipher_suite(CipherSuite::Cv25519)
+            .add_userid("test@example.org")
+            .add_encryption_subkey()
+            .generate().unwrap();
+        assert_eq!(tpk.lint(&LintPolicy::default()), vec![]);
+
+        // SHA-512 is not weak, but we can pretend it is.
+        let policy = LintPolicy::default()
+            .weak_hash_algos(&[HashAlgorithm::SHA512]);
+        let lints = tpk.lint(&policy);
+        assert_eq!(lints.len(), 3);
+        assert!(lints.iter().all(|l| match l {
+            Lint::WeakHashAlgorithm { .. } => true,
+            _ => false,
+        }));
+    }
+
+    #[test]
+    fn lint_weak_key() {
+        let tpk = TPK::from_bytes(::tests::key("testy.pgp")).unwrap();
+        let lints = tpk.lint(&LintPolicy::default().min_bits(4096));
+        assert!(lints.iter().any(|l| match l {
+            Lint::WeakKey { subject: LintSubject::PrimaryKey(_), .. } => true,
+            _ => false,
+        }));
+        assert!(tpk.lint(&LintPolicy::default().min_bits(1024)).iter()
+                .all(|l| match l {
+                    Lint::WeakKey { .. } => false,
+                    _ => true,
+                }));
+    }
+}
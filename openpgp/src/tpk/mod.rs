@@ -11,7 +11,7 @@ use time;
 use failure;
 
 use {
-    crypto::{Hash, Signer},
+    crypto::{Hash, KeyHashScope, Password, Signer},
     Error,
     Result,
     RevocationStatus,
@@ -21,7 +21,7 @@ use {
     packet::Signature,
     packet::signature,
     packet::Key,
-    packet::key::SecretKey,
+    packet::key::{Protection, SecretKey},
     packet::UserID,
     packet::UserAttribute,
     packet::Unknown,
@@ -32,19 +32,35 @@ use {
     KeyID,
     Fingerprint,
 };
-use parse::{Parse, PacketParserResult, PacketParser};
+use parse::{Parse, PacketParserResult, PacketParser, PacketParserBuilder};
 use serialize::SerializeInto;
 use constants::ReasonForRevocation;
+use policy::{Policy, StandardPolicy};
 
 pub mod armor;
 mod lexer;
 mod grammar;
 mod builder;
 mod bindings;
+mod lint;
+mod index;
+mod diff;
+mod trace;
+mod cache;
 
 use self::lexer::Lexer;
 pub use self::lexer::Token;
 pub use self::builder::{TPKBuilder, CipherSuite};
+pub use self::lint::{Lint, LintPolicy, LintSubject};
+pub use self::index::KeyringIndex;
+pub use self::diff::{DiffSubject, TPKDiff};
+pub use self::trace::{
+    CanonicalizationTrace,
+    RejectedSignature,
+    RejectionReason,
+};
+pub(crate) use self::cache::StatusCache;
+use self::cache::{Component, Revoked};
 
 use lalrpop_util::ParseError;
 
@@ -501,6 +517,50 @@ fn active_revocation(sigs: &[Signature], revs: &[Signature], t: time::Tm)
     }
 }
 
+// Returns whether the component with the given signatures is
+// revoked at time `t`.
+fn revoked_at(selfsigs: &[Signature], self_revocations: &[Signature],
+              other_revocations: &[Signature], t: time::Tm)
+    -> Revoked
+{
+    if active_revocation(selfsigs, self_revocations, t) {
+        Revoked::Yes
+    } else if active_revocation(selfsigs, other_revocations, t) {
+        Revoked::CouldBe
+    } else {
+        Revoked::No
+    }
+}
+
+// Adds the references to the revocations to `revoked`.
+fn revocation_status<'a>(revoked: Revoked,
+                         self_revocations: &'a [Signature],
+                         other_revocations: &'a [Signature])
+    -> RevocationStatus<'a>
+{
+    match revoked {
+        Revoked::Yes => RevocationStatus::Revoked(self_revocations),
+        Revoked::CouldBe => RevocationStatus::CouldBe(other_revocations),
+        Revoked::No => RevocationStatus::NotAsFarAsWeKnow,
+    }
+}
+
+// Returns the times at which the revocation status or liveness of
+// `key` may change given the signatures `sigs`.
+fn status_events(key: &Key, sigs: &[&[Signature]]) -> Vec<time::Tm> {
+    let created = key.creation_time();
+    let mut events = vec![created];
+    for sig in sigs.iter().flat_map(|s| s.iter()) {
+        if let Some(t) = sig.signature_creation_time() {
+            events.push(t);
+        }
+        if let Some(e) = sig.key_expiration_time() {
+            events.push(created + e);
+        }
+    }
+    events
+}
+
 /// A subkey and any associated signatures.
 #[derive(Debug, Clone, PartialEq)]
 pub struct SubkeyBinding {
@@ -520,6 +580,16 @@ pub struct SubkeyBinding {
     other_revocations: Vec<Signature>,
 }
 
+// Returns the newest signature in `sigs` that was created at or
+// before `t`.
+//
+// `sigs` must be sorted by creation time, the newest signature last.
+fn sig_at(sigs: &[Signature], t: time::Tm) -> Option<&Signature> {
+    sigs.iter().rev().find(|sig| {
+        sig.signature_creation_time().map(|c| c <= t).unwrap_or(false)
+    })
+}
+
 impl SubkeyBinding {
     /// The key.
     pub fn subkey(&self) -> &Key {
@@ -538,6 +608,16 @@ impl SubkeyBinding {
         self.selfsigs.last()
     }
 
+    /// Returns the binding signature that was in effect at time `t`.
+    ///
+    /// This is the newest self-signature that was created at or
+    /// before `t`.  If `t` is `None`, the current time is used.
+    pub fn binding_signature_at<T>(&self, t: T) -> Option<&Signature>
+        where T: Into<Option<time::Tm>>
+    {
+        sig_at(&self.selfsigs, t.into().unwrap_or_else(time::now_utc))
+    }
+
     /// The self-signatures.
     ///
     /// All self-signatures have been validated, and the newest
@@ -576,23 +656,16 @@ impl SubkeyBinding {
         where T: Into<Option<time::Tm>>
     {
         let t = t.into().unwrap_or_else(time::now_utc);
-        let has_self_revs =
-            active_revocation(&self.selfsigs,
-                              &self.self_revocations, t);
-
-        if has_self_revs {
-            return RevocationStatus::Revoked(&self.self_revocations);
-        }
-
-        let has_other_revs =
-            active_revocation(&self.selfsigs,
-                              &self.other_revocations, t);
+        revocation_status(revoked_at(&self.selfsigs, &self.self_revocations,
+                                     &self.other_revocations, t),
+                          &self.self_revocations, &self.other_revocations)
+    }
 
-        if has_other_revs {
-            RevocationStatus::CouldBe(&self.other_revocations)
-        } else {
-            RevocationStatus::NotAsFarAsWeKnow
-        }
+    // Returns the times at which the status of the subkey may change.
+    fn status_events(&self) -> Vec<time::Tm> {
+        status_events(&self.subkey,
+                      &[&self.selfsigs, &self.self_revocations,
+                        &self.other_revocations])
     }
 }
 
@@ -632,6 +705,16 @@ impl UserIDBinding {
         self.selfsigs.last()
     }
 
+    /// Returns the binding signature that was in effect at time `t`.
+    ///
+    /// This is the newest self-signature that was created at or
+    /// before `t`.  If `t` is `None`, the current time is used.
+    pub fn binding_signature_at<T>(&self, t: T) -> Option<&Signature>
+        where T: Into<Option<time::Tm>>
+    {
+        sig_at(&self.selfsigs, t.into().unwrap_or_else(time::now_utc))
+    }
+
     /// The self-signatures.
     ///
     /// All self-signatures have been validated, and the newest
@@ -727,6 +810,16 @@ impl UserAttributeBinding {
         self.selfsigs.last()
     }
 
+    /// Returns the binding signature that was in effect at time `t`.
+    ///
+    /// This is the newest self-signature that was created at or
+    /// before `t`.  If `t` is `None`, the current time is used.
+    pub fn binding_signature_at<T>(&self, t: T) -> Option<&Signature>
+        where T: Into<Option<time::Tm>>
+    {
+        sig_at(&self.selfsigs, t.into().unwrap_or_else(time::now_utc))
+    }
+
     /// The self-signatures.
     ///
     /// All self-signatures have been validated, and the newest
@@ -812,6 +905,8 @@ pub struct KeyIter<'a> {
     tpk: Option<&'a TPK>,
     primary: bool,
     subkey_iter: SubkeyBindingIter<'a>,
+    // The index of the next subkey.
+    subkey_index: usize,
 
     // If not None, only returns keys with the specified flags.
     flags: Option<KeyFlags>,
@@ -864,17 +959,23 @@ impl<'a> Iterator for KeyIter<'a> {
         }
 
         loop {
-            let (sigo, revoked, key) = if ! self.primary {
+            let alive_at = self.alive_at;
+            let (sigo, alive, revoked, key) = if ! self.primary {
                 self.primary = true;
 
                 (tpk.primary_key_signature(),
+                 alive_at.map(|t| tpk.alive_at(t)),
                  tpk.revocation_status(),
                  tpk.primary())
             } else {
-                self.subkey_iter.next()
-                    .map(|sk_binding| (sk_binding.binding_signature(),
-                                       sk_binding.revoked(None),
-                                       &sk_binding.subkey,))?
+                let i = self.subkey_index;
+                let sk_binding = self.subkey_iter.next()?;
+                self.subkey_index += 1;
+
+                (sk_binding.binding_signature(),
+                 alive_at.map(|t| tpk.subkey_alive_at(i, t)),
+                 tpk.subkey_revocation_status_at(i, time::now_utc()),
+                 &sk_binding.subkey)
             };
 
             t!("Considering key: {:?}", key);
@@ -893,17 +994,11 @@ impl<'a> Iterator for KeyIter<'a> {
                 }
             }
 
-            if let Some(alive_at) = self.alive_at {
-                if let Some(sig) = sigo {
-                    if ! sig.key_alive_at(key, alive_at) {
-                        t!("Key not alive... skipping.");
-                        continue;
-                    }
-                } else {
-                    // No self-signature, skip it.
-                    t!("No self-signature... skipping.");
-                    continue;
-                }
+            // This uses the binding signature that was in effect at
+            // the given time.
+            if alive == Some(false) {
+                t!("Key not alive or no self-signature... skipping.");
+                continue;
             }
 
             if let Some(want_revoked) = self.revoked {
@@ -969,6 +1064,7 @@ impl<'a> KeyIter<'a> {
             tpk: Some(tpk),
             primary: false,
             subkey_iter: tpk.subkeys(),
+            subkey_index: 0,
 
             // The filters.
             flags: None,
@@ -983,7 +1079,10 @@ impl<'a> KeyIter<'a> {
     ///
     /// This causes the `KeyIter` to return all keys in the TPK.
     pub fn unfiltered(self) -> Self {
-        KeyIter::new(self.tpk.unwrap())
+        match self.tpk {
+            Some(tpk) => KeyIter::new(tpk),
+            None => KeyIter::empty(),
+        }
     }
 
     /// Returns an empty KeyIter.
@@ -992,6 +1091,7 @@ impl<'a> KeyIter<'a> {
             tpk: None,
             primary: false,
             subkey_iter: SubkeyBindingIter { iter: None },
+            subkey_index: 0,
 
             // The filters.
             flags: None,
@@ -1005,8 +1105,9 @@ impl<'a> KeyIter<'a> {
     /// Returns keys that have the at least one of the flags specified
     /// in `flags`.
     ///
-    /// If you call this function (or one of `certification_capable`
-    /// or `signing_capable` functions) multiple times, the *union* of
+    /// If you call this function (or one of `certification_capable`,
+    /// `signing_capable`, or `authentication_capable` functions)
+    /// multiple times, the *union* of
     /// the values is used.  Thus,
     /// `tpk.flags().certification_capable().signing_capable()` will
     /// return keys that are certification capable or signing capable.
@@ -1039,6 +1140,13 @@ impl<'a> KeyIter<'a> {
         self.key_flags(KeyFlags::default().set_sign(true))
     }
 
+    /// Returns keys that are authentication capable.
+    ///
+    /// See `key_flags` for caveats.
+    pub fn authentication_capable(self) -> Self {
+        self.key_flags(KeyFlags::default().set_authenticate(true))
+    }
+
     /// Only returns keys that are live as of `now`.
     ///
     /// If `now` is none, then all keys are returned whether they are
@@ -1156,6 +1264,8 @@ pub struct TPKParser<'a, I: Iterator<Item=Packet>> {
     packets: Vec<Packet>,
     saw_error: bool,
     filter: Vec<Box<Fn(&TPK, bool) -> bool + 'a>>,
+    trace: bool,
+    last_trace: Option<CanonicalizationTrace>,
 }
 
 impl<'a, I: Iterator<Item=Packet>> Default for TPKParser<'a, I> {
@@ -1165,6 +1275,8 @@ impl<'a, I: Iterator<Item=Packet>> Default for TPKParser<'a, I> {
             packets: vec![],
             saw_error: false,
             filter: vec![],
+            trace: false,
+            last_trace: None,
         }
     }
 }
@@ -1188,17 +1300,21 @@ impl<'a> Parse<'a, TPKParser<'a, vec::IntoIter<Packet>>>
 {
     /// Initializes a `TPKParser` from a `Read`er.
     fn from_reader<R: 'a + io::Read>(reader: R) -> Result<Self> {
-        Ok(Self::from_packet_parser(PacketParser::from_reader(reader)?))
+        Ok(Self::from_packet_parser(PacketParserBuilder::from_reader(reader)?
+            .content_digests(true).finalize()?))
     }
 
     /// Initializes a `TPKParser` from a `File`.
     fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        Ok(Self::from_packet_parser(PacketParser::from_file(path)?))
+        Ok(Self::from_packet_parser(PacketParserBuilder::from_file(path)?
+            .content_digests(true).finalize()?))
     }
 
     /// Initializes a `TPKParser` from a byte string.
     fn from_bytes(data: &'a [u8]) -> Result<Self> {
-        Ok(Self::from_packet_parser(PacketParser::from_bytes(data)?))
+        Ok(Self::from_packet_parser(
+            PacketParserBuilder::from_bytes(data)?
+            .content_digests(true).finalize()?))
     }
 }
 
@@ -1287,6 +1403,51 @@ impl<'a, I: Iterator<Item=Packet>> TPKParser<'a, I> {
         self
     }
 
+    /// Records why signatures and components are dropped when
+    /// canonicalizing `TPK`s.
+    ///
+    /// Canonicalization drops self-signatures that do not verify,
+    /// and User IDs, User Attributes, and subkeys without a valid
+    /// self-signature.  If this is enabled, the changes made to the
+    /// last `TPK` returned by the iterator can be retrieved using
+    /// `TPKParser::last_trace`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # use openpgp::Result;
+    /// # use openpgp::parse::{Parse, PacketParser};
+    /// use openpgp::tpk::TPKParser;
+    ///
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// #     let ppr = PacketParser::from_bytes(b"")?;
+    /// let mut parser = TPKParser::from_packet_parser(ppr)
+    ///     .trace_canonicalization();
+    /// while let Some(tpk) = parser.next() {
+    ///     let tpk = tpk?;
+    ///     if let Some(trace) = parser.last_trace() {
+    ///         eprint!("{}: {}", tpk.primary().fingerprint(), trace);
+    ///     }
+    /// }
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn trace_canonicalization(mut self) -> Self {
+        self.trace = true;
+        self
+    }
+
+    /// Returns what was dropped when canonicalizing the last `TPK`
+    /// returned by the iterator.
+    ///
+    /// Returns `None` if `TPKParser::trace_canonicalization` has not
+    /// been called, or if the last `TPK` could not be parsed.
+    pub fn last_trace(&self) -> Option<&CanonicalizationTrace> {
+        self.last_trace.as_ref()
+    }
+
     // Parses the next packet in the packet stream.
     //
     // If we complete parsing a TPK, returns the TPK.  Otherwise,
@@ -1309,9 +1470,10 @@ impl<'a, I: Iterator<Item=Packet>> TPKParser<'a, I> {
     //
     // Returns the old state.  Note: the packet iterator is preserved.
     fn reset(&mut self) -> Self {
-        // We need to preserve `source`.
+        // We need to preserve `source` and `trace`.
         let mut orig = mem::replace(self, Default::default());
         self.source = mem::replace(&mut orig.source, PacketSource::EOF);
+        self.trace = orig.trace;
         orig
     }
 
@@ -1442,7 +1604,11 @@ impl<'a, I: Iterator<Item=Packet>> TPKParser<'a, I> {
                 b.other_revocations = other_revs;
             }
 
-            let tpk = tpk.canonicalize();
+            let (tpk, trace) =
+                tpk.canonicalize_traced(&StandardPolicy::new());
+            if self.trace {
+                self.last_trace = Some(trace);
+            }
 
             // Make sure it is still wanted.
             for filter in &self.filter {
@@ -1593,19 +1759,23 @@ impl<'a> ExactSizeIterator for SubkeyBindingIter<'a> {
 impl<'a> Parse<'a, TPK> for TPK {
     /// Returns the first TPK encountered in the reader.
     fn from_reader<R: io::Read>(reader: R) -> Result<Self> {
-        TPK::from_packet_parser(PacketParser::from_reader(reader)?)
+        TPK::from_packet_parser(PacketParserBuilder::from_reader(reader)?
+            .content_digests(true).finalize()?)
     }
 
     /// Returns the first TPK encountered in the file.
     fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        TPK::from_packet_parser(PacketParser::from_file(path)?)
+        TPK::from_packet_parser(PacketParserBuilder::from_file(path)?
+            .content_digests(true).finalize()?)
     }
 
     /// Returns the first TPK found in `buf`.
     ///
     /// `buf` must be an OpenPGP-encoded message.
     fn from_bytes(buf: &[u8]) -> Result<Self> {
-        TPK::from_packet_parser(PacketParser::from_bytes(buf)?)
+        TPK::from_packet_parser(
+            PacketParserBuilder::from_bytes(buf)?
+            .content_digests(true).finalize()?)
     }
 }
 
@@ -1617,6 +1787,7 @@ impl TPK {
 
     #[cfg(test)]
     pub(crate) fn primary_mut(&mut self) -> &mut Key {
+        self.status_cache.clear();
         &mut self.primary
     }
 
@@ -1655,6 +1826,42 @@ impl TPK {
         None
     }
 
+    /// Returns the primary key's self-signature that was in effect
+    /// at time `t`.
+    ///
+    /// This works like [`TPK::primary_key_signature_full()`], but
+    /// only considers self-signatures that were created at or before
+    /// `t`.  If `t` is `None`, the current time is used.
+    ///
+    ///   [`TPK::primary_key_signature_full()`]: #method.primary_key_signature_full
+    pub fn primary_key_signature_at<T>(&self, t: T) -> Option<&Signature>
+        where T: Into<Option<time::Tm>>
+    {
+        let t = t.into().unwrap_or_else(time::now_utc);
+
+        // 1. Self-signature from a non-revoked UserID.
+        if let Some(userid) = self.userids.get(0) {
+            if userid.self_revocations.len() == 0 {
+                if let Some(sig) = sig_at(&userid.selfsigs, t) {
+                    return Some(sig);
+                }
+            }
+        }
+
+        // 2. Direct signature.
+        if let Some(sig) = sig_at(&self.primary_selfsigs, t) {
+            return Some(sig);
+        }
+
+        // 3. Treat User IDs as if they were not revoked.
+        if let Some(userid) = self.userids.get(0) {
+            return sig_at(&userid.selfsigs, t);
+        }
+
+        // 4. No user ids and no direct signatures.
+        None
+    }
+
     /// Returns the primary key's current self-signature.
     ///
     /// This function is identical to
@@ -1706,23 +1913,61 @@ impl TPK {
         where T: Into<Option<time::Tm>>
     {
         let t = t.into().unwrap_or_else(time::now_utc);
-        let has_self_revs =
-            active_revocation(&self.primary_selfsigs,
-                              &self.primary_self_revocations, t);
-
-        if has_self_revs {
-            return RevocationStatus::Revoked(&self.primary_self_revocations);
-        }
-
-        let has_other_revs =
-            active_revocation(&self.primary_selfsigs,
-                              &self.primary_other_revocations, t);
+        let revoked = self.status_cache.revoked(
+            Component::Primary, t,
+            || self.primary_status_events(),
+            || revoked_at(&self.primary_selfsigs,
+                          &self.primary_self_revocations,
+                          &self.primary_other_revocations, t));
+        revocation_status(revoked, &self.primary_self_revocations,
+                          &self.primary_other_revocations)
+    }
+
+    // Returns the times at which the status of the primary key may
+    // change.
+    //
+    // The primary key's self-signature may come from the first user
+    // id, see `TPK::primary_key_signature_at`.
+    fn primary_status_events(&self) -> Vec<time::Tm> {
+        let no_sigs: &[Signature] = &[];
+        status_events(&self.primary,
+                      &[&self.primary_selfsigs,
+                        &self.primary_self_revocations,
+                        &self.primary_other_revocations,
+                        self.userids.get(0).map(|u| &u.selfsigs[..])
+                            .unwrap_or(no_sigs),
+                        self.userids.get(0).map(|u| &u.self_revocations[..])
+                            .unwrap_or(no_sigs)])
+    }
+
+    // Returns the revocation status of the `i`th subkey at time `t`.
+    //
+    // Like `SubkeyBinding::revoked`, but the result is cached.
+    fn subkey_revocation_status_at(&self, i: usize, t: time::Tm)
+                                   -> RevocationStatus
+    {
+        let binding = &self.subkeys[i];
+        let revoked = self.status_cache.revoked(
+            Component::Subkey(i), t,
+            || binding.status_events(),
+            || revoked_at(&binding.selfsigs, &binding.self_revocations,
+                          &binding.other_revocations, t));
+        revocation_status(revoked, &binding.self_revocations,
+                          &binding.other_revocations)
+    }
 
-        if has_other_revs {
-            RevocationStatus::CouldBe(&self.primary_other_revocations)
-        } else {
-            RevocationStatus::NotAsFarAsWeKnow
-        }
+    // Returns whether the `i`th subkey is alive at time `t`.
+    //
+    // This uses the binding signature that was in effect at time
+    // `t`.  The result is cached.
+    fn subkey_alive_at(&self, i: usize, t: time::Tm) -> bool {
+        let binding = &self.subkeys[i];
+        self.status_cache.alive(
+            Component::Subkey(i), t,
+            || binding.status_events(),
+            || binding.binding_signature_at(t)
+                .map(|sig| sig.key_alive_at(&binding.subkey, t))
+                .unwrap_or(false))
     }
 
     /// Returns the TPK's current revocation status.
@@ -1833,6 +2078,88 @@ impl TPK {
         self.merge_packets(vec![sig.into()])
     }
 
+    /// Applies a revocation certificate.
+    ///
+    /// A revocation certificate is a bare revocation signature.  It
+    /// is usually created together with the key and stored away, so
+    /// that the key can be revoked even if the secret key material is
+    /// lost.  Revocations of the primary key as well as of subkeys
+    /// are supported.
+    ///
+    /// Unlike [`merge_packets`], which silently drops signatures
+    /// that do not belong to the TPK, this checks that `sig` has been
+    /// issued by the primary key, and verifies it.
+    ///
+    ///   [`merge_packets`]: #method.merge_packets
+    ///
+    /// # Errors
+    ///
+    /// Fails if `sig` is neither a key nor a subkey revocation, if it
+    /// has not been issued by the primary key, or if it is bad.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # use openpgp::Result;
+    /// use openpgp::RevocationStatus;
+    /// use openpgp::constants::ReasonForRevocation;
+    /// use openpgp::tpk::{CipherSuite, TPKBuilder};
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()>
+    /// # {
+    /// let (tpk, _) = TPKBuilder::new()
+    ///     .set_cipher_suite(CipherSuite::Cv25519)
+    ///     .generate()?;
+    ///
+    /// // Create a revocation certificate, and store it away.
+    /// let mut keypair = tpk.primary().clone().into_keypair()?;
+    /// let rev = tpk.revoke(&mut keypair, ReasonForRevocation::Unspecified,
+    ///                      b"")?;
+    ///
+    /// // Later, apply it.
+    /// let tpk = tpk.apply_revocation(rev)?;
+    /// if let RevocationStatus::Revoked(_) = tpk.revocation_status() {
+    ///     // Ok.
+    /// } else {
+    ///     unreachable!()
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn apply_revocation(self, sig: Signature) -> Result<TPK> {
+        let issued_by_primary = match (sig.issuer_fingerprint(), sig.issuer()) {
+            (Some(fp), _) => fp == self.fingerprint(),
+            (None, Some(keyid)) => keyid == self.primary().keyid(),
+            (None, None) => false,
+        };
+        if ! issued_by_primary {
+            return Err(Error::InvalidArgument(
+                format!("Revocation certificate was not issued by {}",
+                        self.fingerprint())).into());
+        }
+
+        let good = match sig.sigtype() {
+            SignatureType::KeyRevocation =>
+                sig.verify_primary_key_revocation(self.primary(),
+                                                  self.primary())?,
+            SignatureType::SubkeyRevocation =>
+                self.subkeys().any(|skb| {
+                    sig.verify_subkey_revocation(self.primary(),
+                                                 self.primary(),
+                                                 skb.subkey())
+                        .unwrap_or(false)
+                }),
+            t => return Err(Error::UnsupportedSignatureType(t).into()),
+        };
+        if ! good {
+            return Err(Error::BadSignature(
+                "Revocation certificate does not verify".into()).into());
+        }
+
+        self.merge_packets(vec![sig.into()])
+    }
+
     /// Returns whether or not the TPK has expired.
     pub fn expired(&self) -> bool {
         if let Some(Signature::V4(sig)) = self.primary_key_signature() {
@@ -1843,8 +2170,13 @@ impl TPK {
     }
 
     /// Returns whether or not the key is expired at the given time.
+    ///
+    /// This uses the self-signature that was in effect at the given
+    /// time, see [`TPK::primary_key_signature_at()`].
+    ///
+    ///   [`TPK::primary_key_signature_at()`]: #method.primary_key_signature_at
     pub fn expired_at(&self, tm: time::Tm) -> bool {
-        if let Some(Signature::V4(sig)) = self.primary_key_signature() {
+        if let Some(Signature::V4(sig)) = self.primary_key_signature_at(tm) {
             sig.key_expired_at(self.primary(), tm)
         } else {
             false
@@ -1861,12 +2193,20 @@ impl TPK {
     }
 
     /// Returns whether or not the key is alive at the given time.
+    ///
+    /// This uses the self-signature that was in effect at the given
+    /// time, see [`TPK::primary_key_signature_at()`].
+    ///
+    ///   [`TPK::primary_key_signature_at()`]: #method.primary_key_signature_at
     pub fn alive_at(&self, tm: time::Tm) -> bool {
-        if let Some(sig) = self.primary_key_signature() {
-            sig.key_alive_at(self.primary(), tm)
-        } else {
-            false
-        }
+        self.status_cache.alive(
+            Component::Primary, tm,
+            || self.primary_status_events(),
+            || if let Some(sig) = self.primary_key_signature_at(tm) {
+                sig.key_alive_at(self.primary(), tm)
+            } else {
+                false
+            })
     }
 
     /// Sets the key to expire in delta seconds.
@@ -1992,11 +2332,26 @@ impl TPK {
         }
     }
 
-    fn canonicalize(mut self) -> Self {
+    fn canonicalize(self) -> Self {
+        self.canonicalize_traced(&StandardPolicy::new()).0
+    }
+
+    // Like `canonicalize`, but also returns what was dropped.  Only
+    // self-signatures accepted by `policy` are considered.
+    fn canonicalize_traced(mut self, policy: &Policy)
+                           -> (Self, CanonicalizationTrace) {
+        let mut trace = CanonicalizationTrace::default();
+        self.status_cache.clear();
+
+        // Most signatures checked below are self-signatures, which
+        // all start by hashing the primary key.
+        let _key_hashes = KeyHashScope::new(&self.primary);
+
         // Helper functions.
-        // Turn a signature into a key for use by dedup.
+        // Turn a signature into a key for use by dedup.  If the
+        // signature was parsed, this avoids serializing it.
         fn sig_key(a: &mut Signature) -> Box<[u8]> {
-            a.to_vec().expect("XXX: this better not fail")
+            a.content_digest().expect("XXX: this better not fail")
                 .into_boxed_slice()
         }
 
@@ -2015,74 +2370,78 @@ impl TPK {
         //    we don't drop a userid or subkey that is actually
         //    valid.
 
-        // We collect bad signatures here in self.bad.  Below, we'll
-        // test whether they are just out of order by checking them
-        // against all userids and subkeys.  Furthermore, this may be
-        // a partial TPK that is merged into an older copy.
+        // We collect bad signatures here in bad along with the
+        // component they were attached to.  Below, we'll test whether
+        // they are just out of order by checking them against all
+        // userids and subkeys.  Furthermore, this may be a partial
+        // TPK that is merged into an older copy.
+        let mut bad: Vec<(Option<DiffSubject>, Signature)> =
+            mem::replace(&mut self.bad, Vec::new()).into_iter()
+            .map(|sig| (None, sig)).collect();
 
-        // desc: a description of the component
+        // subject: the component, a DiffSubject
         // binding: the binding to check
         // sigs: a vector of sigs in $binding to check
         // verify_method: the method to call on a signature to verify it
         // verify_args: additional arguments to pass to verify_method
         macro_rules! check {
-            ($desc:expr, $binding:expr, $sigs:ident,
+            ($subject:expr, $binding:expr, $sigs:ident,
              $verify_method:ident, $($verify_args:expr),*) => ({
                 for sig in mem::replace(&mut $binding.$sigs, Vec::new())
                     .into_iter()
                 {
-                    if let Ok(true) = sig.$verify_method(&self.primary,
-                                                         &self.primary,
-                                                         $($verify_args),*) {
+                    if sig.$verify_method(&self.primary, &self.primary,
+                                          $($verify_args),*).unwrap_or(false)
+                        && policy.signature(&sig).is_ok()
+                    {
                         $binding.$sigs.push(sig);
                     } else {
+                        let subject = $subject;
                         if TRACE {
                             eprintln!("Sig {:02X}{:02X}, type = {} \
                                        doesn't belong to {}",
                                       sig.hash_prefix()[0], sig.hash_prefix()[1],
-                                      sig.sigtype(), $desc);
+                                      sig.sigtype(), subject);
                         }
 
-                        self.bad.push(sig);
+                        bad.push((Some(subject), sig));
                     }
                 }
             });
-            ($desc:expr, $binding:expr, $sigs:ident,
+            ($subject:expr, $binding:expr, $sigs:ident,
              $verify_method:ident) => ({
-                check!($desc, $binding, $sigs, $verify_method,)
+                check!($subject, $binding, $sigs, $verify_method,)
             });
         }
 
-        check!("primary key",
+        check!(DiffSubject::PrimaryKey(self.primary.fingerprint()),
                self, primary_selfsigs, verify_primary_key_binding);
-        check!("primary key",
+        check!(DiffSubject::PrimaryKey(self.primary.fingerprint()),
                self, primary_self_revocations, verify_primary_key_revocation);
 
         for binding in self.userids.iter_mut() {
-            check!(format!("userid \"{}\"",
-                           String::from_utf8_lossy(binding.userid.value())),
+            check!(DiffSubject::UserID(binding.userid.clone()),
                    binding, selfsigs, verify_userid_binding,
                    &binding.userid);
-            check!(format!("userid \"{}\"",
-                           String::from_utf8_lossy(binding.userid.value())),
+            check!(DiffSubject::UserID(binding.userid.clone()),
                    binding, self_revocations, verify_userid_revocation,
                    &binding.userid);
         }
 
         for binding in self.user_attributes.iter_mut() {
-            check!("user attribute",
+            check!(DiffSubject::UserAttribute(binding.user_attribute.clone()),
                    binding, selfsigs, verify_user_attribute_binding,
                    &binding.user_attribute);
-            check!("user attribute",
+            check!(DiffSubject::UserAttribute(binding.user_attribute.clone()),
                    binding, self_revocations, verify_user_attribute_revocation,
                    &binding.user_attribute);
         }
 
         for binding in self.subkeys.iter_mut() {
-            check!(format!("subkey {}", binding.subkey.keyid()),
+            check!(DiffSubject::Subkey(binding.subkey.fingerprint()),
                    binding, selfsigs, verify_subkey_binding,
                    &binding.subkey);
-            check!(format!("subkey {}", binding.subkey.keyid()),
+            check!(DiffSubject::Subkey(binding.subkey.fingerprint()),
                    binding, self_revocations, verify_subkey_revocation,
                    &binding.subkey);
         }
@@ -2090,14 +2449,14 @@ impl TPK {
         // See if the signatures that didn't validate are just out of
         // place.
 
-        'outer: for sig in mem::replace(&mut self.bad, Vec::new()) {
+        'outer: for (subject, sig) in bad {
             macro_rules! check_one {
                 ($desc:expr, $sigs:expr, $sig:expr,
                  $verify_method:ident, $($verify_args:expr),*) => ({
-                     if let Ok(true)
-                         = $sig.$verify_method(&self.primary,
-                                               &self.primary,
-                                               $($verify_args),*)
+                     if $sig.$verify_method(&self.primary, &self.primary,
+                                            $($verify_args),*)
+                         .unwrap_or(false)
+                         && policy.signature(&$sig).is_ok()
                      {
                          if TRACE {
                              eprintln!("Sig {:02X}{:02X}, {:?} \
@@ -2162,6 +2521,11 @@ impl TPK {
                           sig.hash_prefix()[0], sig.hash_prefix()[1],
                           sig.sigtype());
             }
+            trace.rejected_signatures.push(trace::RejectedSignature {
+                subject: subject,
+                reason: trace::rejection_reason(&self.primary, &sig, policy),
+                signature: sig.clone(),
+            });
             self.bad.push(sig);
         }
 
@@ -2171,18 +2535,52 @@ impl TPK {
         }
 
         // Only keep user ids / user attributes / subkeys with at
-        // least one valid self-signature or self-revocation.
-        self.userids.retain(|userid| {
-            userid.selfsigs.len() > 0 || userid.self_revocations.len() > 0
-        });
+        // least one valid self-signature or self-revocation.  A
+        // component may appear more than once, so only those without
+        // any valid copy are recorded as dropped.
+        let (userids, dropped): (Vec<_>, Vec<_>) =
+            mem::replace(&mut self.userids, Vec::new()).into_iter()
+            .partition(|userid| {
+                userid.selfsigs.len() > 0 || userid.self_revocations.len() > 0
+            });
+        self.userids = userids;
+        for b in dropped {
+            if ! self.userids.iter().any(|u| u.userid == b.userid)
+                && ! trace.dropped_userids.contains(&b.userid)
+            {
+                trace.dropped_userids.push(b.userid);
+            }
+        }
 
-        self.user_attributes.retain(|ua| {
-            ua.selfsigs.len() > 0 || ua.self_revocations.len() > 0
-        });
+        let (user_attributes, dropped): (Vec<_>, Vec<_>) =
+            mem::replace(&mut self.user_attributes, Vec::new()).into_iter()
+            .partition(|ua| {
+                ua.selfsigs.len() > 0 || ua.self_revocations.len() > 0
+            });
+        self.user_attributes = user_attributes;
+        for b in dropped {
+            if ! self.user_attributes.iter()
+                .any(|u| u.user_attribute == b.user_attribute)
+                && ! trace.dropped_user_attributes.contains(&b.user_attribute)
+            {
+                trace.dropped_user_attributes.push(b.user_attribute);
+            }
+        }
 
-        self.subkeys.retain(|subkey| {
-            subkey.selfsigs.len() > 0 || subkey.self_revocations.len() > 0
-        });
+        let (subkeys, dropped): (Vec<_>, Vec<_>) =
+            mem::replace(&mut self.subkeys, Vec::new()).into_iter()
+            .partition(|subkey| {
+                subkey.selfsigs.len() > 0 || subkey.self_revocations.len() > 0
+            });
+        self.subkeys = subkeys;
+        for b in dropped {
+            let fp = b.subkey.fingerprint();
+            if ! self.subkeys.iter().any(|k| k.subkey.fingerprint() == fp)
+                && ! trace.dropped_subkeys.iter().any(|k| k.fingerprint() == fp)
+            {
+                trace.dropped_subkeys.push(b.subkey);
+            }
+        }
 
         fn sig_cmp(a: &Signature, b: &Signature) -> Ordering {
             canonical_signature_order(a.signature_creation_time(),
@@ -2646,7 +3044,7 @@ impl TPK {
 
         // XXX Do some more canonicalization.
 
-        self
+        (self, trace)
     }
 
     /// Returns the TPK's fingerprint.
@@ -2739,6 +3137,118 @@ impl TPK {
         PacketPile::from(self.into_packets())
     }
 
+    /// Returns the smallest TPK that can be used to encrypt to
+    /// `address`.
+    ///
+    /// The returned TPK contains only the primary key, the User ID
+    /// with the given email address and its newest self-signature,
+    /// and the newest encryption-capable subkey that is neither
+    /// expired nor revoked, together with its newest binding
+    /// signature.  Any secret key material is stripped.  This is
+    /// useful for exchanging keys using media with little capacity,
+    /// e.g. QR codes or NFC tags.
+    ///
+    /// Fails if the TPK is revoked, if no User ID with the given
+    /// address is valid, or if there is no usable encryption key.
+    pub fn export_minimal_for(&self, address: &str) -> Result<TPK> {
+        if let RevocationStatus::Revoked(_) = self.revocation_status() {
+            return Err(Error::InvalidOperation(
+                format!("{} is revoked", self.fingerprint())).into());
+        }
+
+        let address = UserID::from_address(None, None, address)?
+            .address_normalized()?
+            .ok_or_else(|| Error::InvalidArgument(
+                format!("Invalid address: {:?}", address)))?;
+        let uidb = self.userids()
+            .filter(|u| match u.revoked(None) {
+                RevocationStatus::Revoked(_) => false,
+                _ => true,
+            })
+            .find(|u| match u.userid().address_normalized() {
+                Ok(Some(ref a)) => *a == address,
+                _ => false,
+            })
+            .ok_or_else(|| Error::InvalidArgument(
+                format!("{} has no valid User ID for {}",
+                        self.fingerprint(), address)))?;
+        let uid_sig = uidb.binding_signature()
+            .ok_or_else(|| Error::InvalidArgument(
+                format!("User ID {} has no self-signature", uidb.userid())))?;
+
+        let encryption = KeyFlags::default()
+            .set_encrypt_for_transport(true)
+            .set_encrypt_at_rest(true);
+        let mut keys = self.keys_valid().key_flags(encryption)
+            .map(|(sig, _, key)| (sig, key))
+            .collect::<Vec<_>>();
+        let primary_can_encrypt =
+            keys.first().map(|&(_, k)| k == self.primary()).unwrap_or(false);
+        // The newest subkey comes last.
+        keys.retain(|&(_, k)| k != self.primary());
+        keys.sort_by_key(|&(_, k)| k.creation_time().to_timespec());
+        let subkey = keys.pop();
+        if subkey.is_none() && ! primary_can_encrypt {
+            return Err(Error::InvalidOperation(
+                format!("{} has no usable encryption key",
+                        self.fingerprint())).into());
+        }
+
+        let mut primary = self.primary().clone();
+        primary.set_secret(None);
+        let mut packets = vec![
+            primary.into_packet(Tag::PublicKey)?,
+            uidb.userid().clone().into(),
+            uid_sig.clone().into(),
+        ];
+        if let Some((sig, key)) = subkey {
+            let mut key = key.clone();
+            key.set_secret(None);
+            packets.push(key.into_packet(Tag::PublicSubkey)?);
+            packets.push(sig.expect("keys with flags have a signature")
+                         .clone().into());
+        }
+
+        TPK::from_packet_pile(PacketPile::from(packets))
+    }
+
+    /// Canonicalizes the TPK using `policy`.
+    ///
+    /// By default, TPKs are canonicalized using the
+    /// [`StandardPolicy`].  This re-evaluates the self-signatures,
+    /// and sets aside those that `policy` rejects.  As usual,
+    /// components without a valid self-signature are dropped.
+    /// Self-signatures that were set aside by a previous policy, but
+    /// are accepted by `policy`, are considered again.
+    ///
+    ///   [`StandardPolicy`]: ../policy/struct.StandardPolicy.html
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # use openpgp::{TPK, Result};
+    /// # use openpgp::constants::HashAlgorithm;
+    /// # use openpgp::policy::StandardPolicy;
+    /// # use openpgp::tpk::TPKBuilder;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// let (tpk, _) = TPKBuilder::new()
+    ///     .add_userid("Alice")
+    ///     .generate()?;
+    ///
+    /// // A policy rejecting the hash algorithm used for the
+    /// // self-signatures leaves the TPK without a User ID.
+    /// let algo = tpk.primary_key_signature().unwrap().hash_algo();
+    /// let tpk = tpk.with_policy(&StandardPolicy::new().reject_hash(algo));
+    /// assert_eq!(tpk.userids().count(), 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_policy(self, policy: &Policy) -> Self {
+        self.canonicalize_traced(policy).0
+    }
+
     /// Merges `other` into `self`.
     ///
     /// If `other` is a different key, then nothing is merged into
@@ -2792,6 +3302,61 @@ impl TPK {
             sk.binding_signature().is_some() && sk.subkey().secret().is_some()
         })
     }
+
+    /// Encrypts all unencrypted secret keys using `password`.
+    ///
+    /// The secret keys are protected as described by `protection`.
+    /// This can be used to export keys in a form other
+    /// implementations, like GnuPG, expect.  Secret keys that are
+    /// already encrypted are left alone.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # use openpgp::{Result, TPK};
+    /// # use openpgp::constants::SymmetricAlgorithm;
+    /// # use openpgp::crypto::s2k::S2K;
+    /// # use openpgp::packet::key::Protection;
+    /// # use openpgp::serialize::Serialize;
+    /// # use openpgp::tpk::TPKBuilder;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// let (tpk, _) = TPKBuilder::new().add_encryption_subkey().generate()?;
+    ///
+    /// // Protect the keys like GnuPG does.
+    /// let tpk = tpk.encrypt_secrets(&"streng geheim".into(),
+    ///                               &Protection::CFB {
+    ///     s2k: S2K::default(),
+    ///     algorithm: SymmetricAlgorithm::AES128,
+    /// })?;
+    /// assert!(tpk.primary().secret().unwrap().is_encrypted());
+    ///
+    /// let mut buf = Vec::new();
+    /// tpk.as_tsk().serialize(&mut buf)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn encrypt_secrets(mut self, password: &Password,
+                           protection: &Protection)
+                           -> Result<Self> {
+        fn encrypt(key: &mut Key, tag: Tag, password: &Password,
+                   protection: &Protection)
+                   -> Result<()> {
+            match key.secret() {
+                Some(SecretKey::Unencrypted { .. }) => (),
+                _ => return Ok(()),
+            }
+            key.encrypt_secret(tag, password, protection)
+        }
+
+        encrypt(&mut self.primary, Tag::SecretKey, password, protection)?;
+        for binding in self.subkeys.iter_mut() {
+            encrypt(&mut binding.subkey, Tag::SecretSubkey, password,
+                    protection)?;
+        }
+        Ok(self)
+    }
 }
 
 #[cfg(test)]
@@ -3518,6 +4083,49 @@ mod test {
         }
     }
 
+    #[test]
+    fn expiry_time_travel() {
+        let (tpk, _) = TPKBuilder::new()
+            .set_expiration(time::Duration::minutes(30))
+            .generate().unwrap();
+        let creation = *tpk.primary().creation_time();
+        let mut keypair = tpk.primary().clone().into_keypair().unwrap();
+
+        // An hour after the key was created, extend the expiry.
+        let tpk = tpk.set_expiry_as_of(
+            &mut keypair,
+            Some(time::Duration::days(10)),
+            creation + time::Duration::hours(1)).unwrap();
+
+        let sig = tpk.primary_key_signature().unwrap();
+        assert_eq!(tpk.primary().expiration_time(sig),
+                   Some(creation + time::Duration::days(10)));
+
+        // Before the key was created, there is no self-signature.
+        let before = creation - time::Duration::hours(1);
+        assert!(tpk.primary_key_signature_at(before).is_none());
+        assert!(! tpk.alive_at(before));
+
+        // After 45 minutes, the original self-signature is in effect,
+        // and the key has expired.
+        let t = creation + time::Duration::minutes(45);
+        let sig = tpk.primary_key_signature_at(t).unwrap();
+        assert_eq!(sig.key_expiration_time(),
+                   Some(time::Duration::minutes(30)));
+        assert!(tpk.primary().expired_at(sig, t));
+        assert!(tpk.expired_at(t));
+        assert!(! tpk.alive_at(t));
+
+        // After two hours, the new self-signature is in effect.
+        let t = creation + time::Duration::hours(2);
+        assert!(! tpk.expired_at(t));
+        assert!(tpk.alive_at(t));
+        assert!(tpk.primary_key_signature_at(t).unwrap().alive_at(t));
+
+        // And after eleven days, the key has expired again.
+        assert!(tpk.expired_at(creation + time::Duration::days(11)));
+    }
+
     #[test]
     fn direct_key_sig() {
         use constants::SignatureType;
@@ -3643,6 +4251,34 @@ mod test {
         assert_match!(RevocationStatus::Revoked(_) = tpk.revocation_status());
     }
 
+    #[test]
+    fn apply_revocation() {
+        let (tpk, _) = TPKBuilder::autocrypt(None, Some("Test"))
+            .generate().unwrap();
+        let (other, _) = TPKBuilder::autocrypt(None, Some("Other"))
+            .generate().unwrap();
+
+        let mut keypair = tpk.primary().clone().into_keypair().unwrap();
+        let sig = tpk.revoke(&mut keypair,
+                             ReasonForRevocation::KeyCompromised,
+                             b"It was the maid :/").unwrap();
+
+        // The revocation certificate does not apply to other keys.
+        assert!(other.apply_revocation(sig.clone()).is_err());
+
+        // Only revocations are accepted.
+        let selfsig = tpk.primary_key_signature().unwrap().clone();
+        assert!(tpk.clone().apply_revocation(selfsig).is_err());
+
+        // Bare signatures roundtrip.
+        let sig = match Packet::from_bytes(&sig.to_vec().unwrap()).unwrap() {
+            Packet::Signature(sig) => sig,
+            p => panic!("Unexpected packet: {:?}", p),
+        };
+        let tpk = tpk.apply_revocation(sig).unwrap();
+        assert_match!(RevocationStatus::Revoked(_) = tpk.revocation_status());
+    }
+
     #[test]
     fn revoke_uid() {
         use std::{thread, time};
@@ -3950,4 +4586,75 @@ Pu1xwz57O4zo1VYf6TqHJzVC3OMvMUM2hhdecMUe5x6GorNaj6g=
         assert_eq!(uidb.selfsigs().last().unwrap(),
                    uidb.binding_signature().unwrap());
     }
+
+    #[test]
+    fn canonicalization_trace() {
+        let (tpk, _) = TPKBuilder::new()
+            .add_userid("alice@example.org")
+            .generate().unwrap();
+
+        // Attach Alice's self-signature to a different user id.
+        let bob = UserID::from("bob@example.org");
+        let packets = tpk.into_packets().into_iter()
+            .take_while(|p| p.tag() != Tag::PublicSubkey)
+            .map(|p| match p {
+                Packet::UserID(_) => Packet::UserID(bob.clone()),
+                p => p,
+            })
+            .collect::<Vec<_>>();
+
+        let mut parser = TPKParser::from_iter(packets.clone().into_iter());
+        parser.next().unwrap().unwrap();
+        assert!(parser.last_trace().is_none());
+
+        let mut parser = TPKParser::from_iter(packets.into_iter())
+            .trace_canonicalization();
+        let tpk = parser.next().unwrap().unwrap();
+        assert_eq!(tpk.userids().len(), 0);
+
+        let trace = parser.last_trace().unwrap();
+        assert_eq!(trace.dropped_userids(), &[bob.clone()]);
+        assert_eq!(trace.rejected_signatures().len(), 1);
+        let rejected = &trace.rejected_signatures()[0];
+        assert_eq!(rejected.subject(), Some(&DiffSubject::UserID(bob)));
+        assert_eq!(rejected.reason(), RejectionReason::BadSignature);
+        assert_eq!(rejected.signature().sigtype(),
+                   SignatureType::PositiveCertificate);
+        assert!(trace.dropped_subkeys().is_empty());
+
+        let pile = PacketPile::from_bytes(
+            ::tests::key("testy-broken-no-sig-on-subkey.pgp")).unwrap();
+        let mut parser = TPKParser::from_iter(pile.into_children())
+            .trace_canonicalization();
+        parser.next().unwrap().unwrap();
+        let trace = parser.last_trace().unwrap();
+        assert!(trace.rejected_signatures().is_empty());
+        assert_eq!(trace.dropped_subkeys().len(), 1);
+    }
+
+    #[test]
+    fn export_minimal_for() {
+        let (tpk, _) = TPKBuilder::new()
+            .add_userid("Alice <alice@example.org>")
+            .add_userid("alice@example.net")
+            .add_encryption_subkey()
+            .add_encryption_subkey()
+            .add_signing_subkey()
+            .generate().unwrap();
+        assert!(tpk.is_tsk());
+
+        let minimal = tpk.export_minimal_for("Alice@Example.org").unwrap();
+        assert_eq!(minimal.fingerprint(), tpk.fingerprint());
+        assert!(! minimal.is_tsk());
+        assert_eq!(minimal.userids().len(), 1);
+        assert_eq!(minimal.userids().nth(0).unwrap().userid().value(),
+                   b"Alice <alice@example.org>");
+        assert_eq!(minimal.subkeys().len(), 1);
+        assert!(minimal.keys_valid().key_flags(
+            KeyFlags::default().set_encrypt_for_transport(true))
+                .any(|(_, _, k)| k != minimal.primary()));
+        assert!(minimal.to_vec().unwrap().len() < tpk.to_vec().unwrap().len());
+
+        assert!(tpk.export_minimal_for("bob@example.org").is_err());
+    }
 }
@@ -0,0 +1,187 @@
+//! Records what happens to a TPK during canonicalization.
+
+use std::fmt;
+
+use {
+    KeyID,
+    SignatureType,
+};
+use packet::{Key, Signature, UserID, UserAttribute};
+use packet::signature::subpacket::{SubpacketTag, SubpacketValue};
+use policy::Policy;
+use super::DiffSubject;
+
+/// Why a signature was rejected during canonicalization.
+///
+/// Note: expired self-signatures are not rejected.  They are kept so
+/// that the TPK can be evaluated at other points in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectionReason {
+    /// The signature does not verify for any of the TPK's
+    /// components.
+    BadSignature,
+    /// The signature's type cannot be used to bind or revoke a
+    /// component.
+    WrongType,
+    /// The signature's unhashed area names the primary key as
+    /// issuer, but the hashed area names a different key.
+    ///
+    /// The unhashed area is not protected by the signature.  The
+    /// signature was probably not issued by the primary key.
+    IssuerMismatch,
+    /// The signature was rejected by the policy.
+    PolicyViolation,
+}
+
+impl fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RejectionReason::BadSignature => "bad signature",
+            RejectionReason::WrongType => "wrong signature type",
+            RejectionReason::IssuerMismatch => "unhashed issuer mismatch",
+            RejectionReason::PolicyViolation => "rejected by policy",
+        })
+    }
+}
+
+/// A signature rejected during canonicalization.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedSignature {
+    pub(crate) subject: Option<DiffSubject>,
+    pub(crate) signature: Signature,
+    pub(crate) reason: RejectionReason,
+}
+
+impl RejectedSignature {
+    /// Returns the component that the signature was attached to.
+    ///
+    /// This is `None` if the signature could not be associated with
+    /// a component when the TPK was parsed.
+    pub fn subject(&self) -> Option<&DiffSubject> {
+        self.subject.as_ref()
+    }
+
+    /// Returns the signature.
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// Returns why the signature was rejected.
+    pub fn reason(&self) -> RejectionReason {
+        self.reason
+    }
+}
+
+impl fmt::Display for RejectedSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} signature {:02X}{:02X}",
+               self.signature.sigtype(),
+               self.signature.hash_prefix()[0],
+               self.signature.hash_prefix()[1])?;
+        if let Some(ref subject) = self.subject {
+            write!(f, " on {}", subject)?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// Records the signatures and components dropped while
+/// canonicalizing a TPK.
+///
+/// See [`TPKParser::trace_canonicalization`].
+///
+///   [`TPKParser::trace_canonicalization`]: struct.TPKParser.html#method.trace_canonicalization
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CanonicalizationTrace {
+    pub(crate) rejected_signatures: Vec<RejectedSignature>,
+    pub(crate) dropped_userids: Vec<UserID>,
+    pub(crate) dropped_user_attributes: Vec<UserAttribute>,
+    pub(crate) dropped_subkeys: Vec<Key>,
+}
+
+impl CanonicalizationTrace {
+    /// Returns whether nothing was dropped.
+    pub fn is_empty(&self) -> bool {
+        self.rejected_signatures.is_empty()
+            && self.dropped_userids.is_empty()
+            && self.dropped_user_attributes.is_empty()
+            && self.dropped_subkeys.is_empty()
+    }
+
+    /// Returns the rejected self-signatures.
+    ///
+    /// Rejected signatures are retained by the TPK, but not
+    /// considered when evaluating it.
+    pub fn rejected_signatures(&self) -> &[RejectedSignature] {
+        &self.rejected_signatures
+    }
+
+    /// Returns the User IDs dropped for lack of a valid
+    /// self-signature.
+    pub fn dropped_userids(&self) -> &[UserID] {
+        &self.dropped_userids
+    }
+
+    /// Returns the User Attributes dropped for lack of a valid
+    /// self-signature.
+    pub fn dropped_user_attributes(&self) -> &[UserAttribute] {
+        &self.dropped_user_attributes
+    }
+
+    /// Returns the subkeys dropped for lack of a valid binding
+    /// signature.
+    pub fn dropped_subkeys(&self) -> &[Key] {
+        &self.dropped_subkeys
+    }
+}
+
+impl fmt::Display for CanonicalizationTrace {
+    /// Lists the dropped signatures and components, one per line.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for sig in self.rejected_signatures.iter() {
+            writeln!(f, "Rejected {}", sig)?;
+        }
+        for uid in self.dropped_userids.iter() {
+            writeln!(f, "Dropped user id {}", uid)?;
+        }
+        for _ in self.dropped_user_attributes.iter() {
+            writeln!(f, "Dropped user attribute")?;
+        }
+        for key in self.dropped_subkeys.iter() {
+            writeln!(f, "Dropped subkey {}", key.fingerprint())?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns why `sig` did not verify for any component of the TPK
+/// with the given primary key.
+pub(crate) fn rejection_reason(primary: &Key, sig: &Signature,
+                               policy: &Policy)
+                               -> RejectionReason {
+    use SignatureType::*;
+    match sig.sigtype() {
+        GenericCertificate | PersonaCertificate | CasualCertificate
+            | PositiveCertificate | SubkeyBinding | DirectKey
+            | KeyRevocation | SubkeyRevocation | CertificateRevocation => (),
+        _ => return RejectionReason::WrongType,
+    }
+
+    let hashed = sig.hashed_area();
+    let issuer: Option<KeyID> =
+        match hashed.lookup(SubpacketTag::IssuerFingerprint).map(|sb| sb.value) {
+            Some(SubpacketValue::IssuerFingerprint(fp)) => Some(fp.to_keyid()),
+            _ => match hashed.lookup(SubpacketTag::Issuer).map(|sb| sb.value) {
+                Some(SubpacketValue::Issuer(keyid)) => Some(keyid),
+                _ => None,
+            },
+        };
+
+    match issuer {
+        Some(ref keyid) if *keyid != primary.keyid() =>
+            RejectionReason::IssuerMismatch,
+        _ if policy.signature(sig).is_err() =>
+            RejectionReason::PolicyViolation,
+        _ => RejectionReason::BadSignature,
+    }
+}
@@ -0,0 +1,87 @@
+/// Caches parsed keyrings.
+///
+/// Parsing and validating a large keyring takes a long time.  If sqv
+/// is invoked repeatedly with the same keyrings, we store the
+/// canonicalized TPKs of every keyring in a binary keyring in the
+/// cache directory, and index it.  Then, only the TPKs of the alleged
+/// issuers have to be read.
+///
+/// The cached copy of a keyring is named after the hash of the
+/// keyring's canonical path.  It is rebuilt whenever the size or the
+/// modification time of the keyring changes.
+
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use failure;
+use openpgp::TPK;
+use openpgp::constants::HashAlgorithm;
+use openpgp::serialize::Serialize;
+use openpgp::tpk::{KeyringIndex, TPKParser};
+use sequoia_core::trace::Level;
+
+/// Returns the cached copy of `keyring` in `dir`.
+///
+/// If there is no up-to-date copy, it is created.
+pub fn cached_keyring(dir: &Path, keyring: &OsStr)
+                      -> Result<PathBuf, failure::Error> {
+    let stamp = stamp(keyring)?;
+
+    let name = {
+        let path = fs::canonicalize(keyring)?;
+        let mut hash = HashAlgorithm::SHA256.context()?;
+        hash.update(path.to_string_lossy().as_bytes());
+        let mut digest = vec![0u8; hash.digest_size()];
+        hash.digest(&mut digest);
+        digest.iter().map(|b| format!("{:02X}", b)).collect::<String>()
+    };
+    let copy = dir.join(format!("{}.pgp", name));
+    let index = KeyringIndex::sidecar(&copy);
+    let stamp_file = dir.join(format!("{}.stamp", name));
+
+    if fs::read_to_string(&stamp_file).ok().as_ref() == Some(&stamp) {
+        return Ok(copy);
+    }
+
+    trace!(Level::Info, "Caching {:?} in {:?}.", keyring, copy);
+    fs::create_dir_all(dir)?;
+
+    // Invalidate the old copy first.  The new files are written
+    // under temporary names, and moved into place.
+    if stamp_file.exists() {
+        fs::remove_file(&stamp_file)?;
+    }
+
+    let tmp = |p: &Path| {
+        let mut name = p.as_os_str().to_os_string();
+        name.push(".tmp");
+        PathBuf::from(name)
+    };
+
+    {
+        let mut sink = File::create(tmp(&copy))?;
+        for tpk in TPKParser::from_file(keyring)? {
+            let tpk: TPK = tpk?;
+            tpk.serialize(&mut sink)?;
+        }
+    }
+    KeyringIndex::build(File::open(tmp(&copy))?,
+                        &mut File::create(tmp(&index))?)?;
+    File::create(tmp(&stamp_file))?.write_all(stamp.as_bytes())?;
+
+    fs::rename(tmp(&copy), &copy)?;
+    fs::rename(tmp(&index), &index)?;
+    fs::rename(tmp(&stamp_file), &stamp_file)?;
+    Ok(copy)
+}
+
+/// Returns a string identifying the version of `keyring`.
+fn stamp(keyring: &OsStr) -> Result<String, failure::Error> {
+    let metadata = fs::metadata(keyring)?;
+    let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+    Ok(format!("{} {}.{:09}\n",
+               metadata.len(), mtime.as_secs(), mtime.subsec_nanos()))
+}
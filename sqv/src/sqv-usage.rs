@@ -10,10 +10,12 @@
 //!
 //! FLAGS:
 //!     -h, --help       Prints help information
-//!         --trace      Trace execution.
+//!         --trace      Trace execution.  Same as -vvv.
+//!     -v, --verbose    Be more verbose.  Can be given multiple times.  See also SEQUOIA_LOG.
 //!     -V, --version    Prints version information
 //!
 //! OPTIONS:
+//!         --cache-dir <DIR>            Cache the parsed keyrings in DIR.  Speeds up repeated invocations with the same keyrings.
 //!         --keyring <FILE>...          A keyring.  Can be given multiple times.
 //!         --not-after <YYYY-MM-DD>     Consider signatures created after YYYY-MM-DD as invalid.  Default: now
 //!         --not-before <YYYY-MM-DD>    Consider signatures created before YYYY-MM-DD as invalid.  Default: no constraint
@@ -9,23 +9,65 @@ use failure::ResultExt;
 extern crate time;
 
 extern crate sequoia_openpgp as openpgp;
+#[macro_use]
+extern crate sequoia_core;
 
 use std::process::exit;
+use std::ffi::OsStr;
 use std::fs::File;
+use std::path::Path;
 use std::collections::{HashMap, HashSet};
 
 use openpgp::{TPK, Packet, packet::Signature, KeyID, RevocationStatus};
 use openpgp::constants::HashAlgorithm;
 use openpgp::crypto::Hash;
 use openpgp::parse::{Parse, PacketParserResult, PacketParser};
-use openpgp::tpk::TPKParser;
+use openpgp::tpk::{KeyringIndex, TPKParser};
+use sequoia_core::trace::{self, Level};
 
+mod cache;
 mod sqv_cli;
 
+/// Looks up the given issuers using the keyring's sidecar index.
+///
+/// Returns `None` if there is no usable index.
+fn lookup_indexed(keyring: &OsStr, issuers: &[KeyID]) -> Option<Vec<TPK>> {
+    let mut index = KeyringIndex::open(KeyringIndex::sidecar(keyring)).ok()?;
+    let mut keyring = File::open(keyring).ok()?;
+    let mut tpks: Vec<TPK> = Vec::new();
+    for issuer in issuers {
+        for tpk in index.lookup_keyid(&mut keyring, issuer).ok()? {
+            if ! tpks.contains(&tpk) {
+                tpks.push(tpk);
+            }
+        }
+    }
+    Some(tpks)
+}
+
+/// Looks up the given issuers using the cached copy of the keyring
+/// in `cache_dir`.
+///
+/// Returns `None` if the keyring cannot be cached.
+fn lookup_cached(cache_dir: &Path, keyring: &OsStr, issuers: &[KeyID])
+                 -> Option<Vec<TPK>> {
+    match cache::cached_keyring(cache_dir, keyring) {
+        Ok(copy) => lookup_indexed(copy.as_os_str(), issuers),
+        Err(err) => {
+            trace!(Level::Info, "Not caching {:?}: {}", keyring, err);
+            None
+        },
+    }
+}
+
 fn real_main() -> Result<(), failure::Error> {
     let matches = sqv_cli::build().get_matches();
 
-    let trace = matches.is_present("trace");
+    if matches.is_present("trace") {
+        trace::set_level(Level::Trace);
+    } else {
+        trace::init(matches.occurrences_of("verbose"))?;
+    }
 
     let good_threshold
         = if let Some(good_threshold) = matches.value_of("signatures") {
@@ -60,6 +102,8 @@ fn real_main() -> Result<(), failure::Error> {
         None
     }.unwrap_or_else(|| time::now_utc());
 
+    let cache_dir = matches.value_of_os("cache-dir").map(Path::new);
+
     // First, we collect the signatures and the alleged issuers.
     // Then, we scan the keyrings exactly once to find the associated
     // TPKs.
@@ -67,6 +111,7 @@ fn real_main() -> Result<(), failure::Error> {
     // .unwrap() is safe, because "sig-file" is required.
     let sig_file = matches.value_of_os("sig-file").unwrap();
 
+    let parsing = trace_span!(Level::Info, "Parsing {:?}", sig_file);
     let mut ppr = PacketParser::from_file(sig_file)?;
 
     let mut sigs_seen = HashSet::new();
@@ -95,19 +140,16 @@ fn real_main() -> Result<(), failure::Error> {
 
                 sig_i += 1;
                 if let Some(fp) = sig.issuer_fingerprint() {
-                    if trace {
-                        eprintln!("Will check signature allegedly issued by {}.",
-                                  fp);
-                    }
+                    trace!(Level::Debug,
+                           "Will check signature allegedly issued by {}.", fp);
 
                     // XXX: We use a KeyID even though we have a
                     // fingerprint!
                     sigs.push((sig, fp.to_keyid(), None));
                 } else if let Some(keyid) = sig.issuer() {
-                    if trace {
-                        eprintln!("Will check signature allegedly issued by {}.",
-                                  keyid);
-                    }
+                    trace!(Level::Debug,
+                           "Will check signature allegedly issued by {}.",
+                           keyid);
 
                     sigs.push((sig, keyid, None));
                 } else {
@@ -128,6 +170,8 @@ fn real_main() -> Result<(), failure::Error> {
         }
     }
 
+    drop(parsing);
+
     if sigs.len() == 0 {
         eprintln!("{:?} does not contain an OpenPGP signature.", sig_file);
         exit(2);
@@ -138,11 +182,15 @@ fn real_main() -> Result<(), failure::Error> {
 
     // .unwrap() is safe, because "file" is required.
     let file = matches.value_of_os("file").unwrap();
+    let hashing = trace_span!(Level::Info, "Hashing {:?}", file);
+    // Signatures using unsupported algorithms are reported below.
     let hash_algos : Vec<HashAlgorithm>
-        = sigs.iter().map(|&(ref sig, _, _)| sig.hash_algo()).collect();
+        = sigs.iter().map(|&(ref sig, _, _)| sig.hash_algo())
+        .filter(|algo| algo.is_supported()).collect();
     let hashes: HashMap<_, _> =
         openpgp::crypto::hash_file(File::open(file)?, &hash_algos[..])?
         .into_iter().collect();
+    drop(hashing);
 
     fn tpk_has_key(tpk: &TPK, keyid: &KeyID) -> bool {
         // Even if a key is revoked or expired, we can still use it to
@@ -154,6 +202,34 @@ fn real_main() -> Result<(), failure::Error> {
     for filename in matches.values_of_os("keyring")
         .expect("No keyring specified.")
     {
+        let _span = trace_span!(Level::Info, "Scanning {:?}", filename);
+
+        // If the keyring has an up-to-date index, or is cached, only
+        // read the TPKs we need.
+        let issuers: Vec<KeyID> =
+            sigs.iter().map(|&(_, ref issuer, _)| issuer.clone()).collect();
+        let indexed = lookup_indexed(filename, &issuers)
+            .or_else(|| cache_dir.and_then(|dir| {
+                lookup_cached(dir, filename, &issuers)
+            }));
+        if let Some(tpks) = indexed {
+            trace!(Level::Debug, "Using the index of {:?}.", filename);
+            for tpk in tpks {
+                for &mut (_, ref issuer, ref mut issuer_tpko)
+                    in sigs.iter_mut()
+                {
+                    if tpk_has_key(&tpk, issuer) {
+                        *issuer_tpko = match issuer_tpko.take() {
+                            Some(issuer_tpk) =>
+                                issuer_tpk.merge(tpk.clone()).ok(),
+                            None => Some(tpk.clone()),
+                        };
+                    }
+                }
+            }
+            continue;
+        }
+
         // Load the keyring.
         let tpks : Vec<TPK> = TPKParser::from_file(filename)?
             .unvalidated_tpk_filter(|tpk, _| {
@@ -180,17 +256,13 @@ fn real_main() -> Result<(), failure::Error> {
             for &mut (_, ref issuer, ref mut issuer_tpko) in sigs.iter_mut() {
                 if tpk_has_key(&tpk, issuer) {
                     if let Some(issuer_tpk) = issuer_tpko.take() {
-                        if trace {
-                            eprintln!("Found key {} again.  Merging.",
-                                      issuer);
-                        }
+                        trace!(Level::Debug, "Found key {} again.  Merging.",
+                               issuer);
 
                         *issuer_tpko
                             = issuer_tpk.merge(tpk.clone()).ok();
                     } else {
-                        if trace {
-                            eprintln!("Found key {}.", issuer);
-                        }
+                        trace!(Level::Debug, "Found key {}.", issuer);
 
                         *issuer_tpko = Some(tpk.clone());
                     }
@@ -200,12 +272,12 @@ fn real_main() -> Result<(), failure::Error> {
     }
 
     // Verify the signatures.
+    let verifying = trace_span!(Level::Info, "Verifying signatures");
     let mut sigs_seen_from_tpk = HashSet::new();
     let mut good = 0;
     'sig_loop: for (mut sig, issuer, tpko) in sigs.into_iter() {
-        if trace {
-            eprintln!("Checking signature allegedly issued by {}.", issuer);
-        }
+        trace!(Level::Debug, "Checking signature allegedly issued by {}.",
+               issuer);
 
         if let Some(ref tpk) = tpko {
             // Find the right key.
@@ -289,9 +361,8 @@ fn real_main() -> Result<(), failure::Error> {
                                 break;
                             }
 
-                            if trace {
-                                eprintln!("Signature by {} is good.", issuer);
-                            }
+                            trace!(Level::Info, "Signature by {} is good.",
+                                   issuer);
 
                             if sigs_seen_from_tpk.replace(tpk.fingerprint())
                                 .is_some()
@@ -306,14 +377,12 @@ fn real_main() -> Result<(), failure::Error> {
                             good += 1;
                         },
                         Ok(false) => {
-                            if trace {
-                                eprintln!("Signature by {} is bad.", issuer);
-                            }
+                            trace!(Level::Info, "Signature by {} is bad.",
+                                   issuer);
                         },
                         Err(err) => {
-                            if trace {
-                                eprintln!("Verifying signature: {}.", err);
-                            }
+                            trace!(Level::Info, "Verifying signature: {}.",
+                                   err);
                         },
                     }
 
@@ -326,10 +395,10 @@ fn real_main() -> Result<(), failure::Error> {
         }
     }
 
-    if trace {
-        eprintln!("{} of {} signatures are valid (threshold is: {}).",
-                  good, sig_i, good_threshold);
-    }
+    drop(verifying);
+
+    trace!(Level::Info, "{} of {} signatures are valid (threshold is: {}).",
+           good, sig_i, good_threshold);
 
     exit(if good >= good_threshold { 0 } else { 1 });
 }
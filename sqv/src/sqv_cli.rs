@@ -24,6 +24,11 @@ pub fn build() -> App<'static, 'static> {
              .long("signatures")
              .short("n")
              .takes_value(true))
+        .arg(Arg::with_name("cache-dir").value_name("DIR")
+             .help("Cache the parsed keyrings in DIR.  Speeds up \
+                    repeated invocations with the same keyrings.")
+             .long("cache-dir")
+             .takes_value(true))
         .arg(Arg::with_name("not-before").value_name("YYYY-MM-DD")
              .help("Consider signatures created before YYYY-MM-DD as invalid.  \
                     Default: no constraint")
@@ -40,7 +45,13 @@ pub fn build() -> App<'static, 'static> {
         .arg(Arg::with_name("file").value_name("FILE")
              .help("File to verify.")
              .required(true))
+        .arg(Arg::with_name("verbose")
+             .help("Be more verbose.  Can be given multiple times.  \
+                    See also SEQUOIA_LOG.")
+             .long("verbose")
+             .short("v")
+             .multiple(true))
         .arg(Arg::with_name("trace")
-             .help("Trace execution.")
+             .help("Trace execution.  Same as -vvv.")
              .long("trace"))
 }
@@ -0,0 +1,36 @@
+extern crate assert_cli;
+extern crate tempfile;
+
+#[cfg(test)]
+mod integration {
+    use std::fs;
+    use std::path;
+
+    use assert_cli::Assert;
+    use tempfile::TempDir;
+
+    #[test]
+    fn cached_keyring() {
+        let cache = TempDir::new().unwrap();
+        let cache_dir = cache.path().to_string_lossy().to_string();
+
+        // The first invocation populates the cache, the second one
+        // uses it.
+        for _ in 0..2 {
+            Assert::cargo_binary("sqv")
+                .current_dir(path::Path::new("tests").join("data"))
+                .with_args(&["--cache-dir", &cache_dir,
+                             "--keyring", "bad-subkey-keyring.pgp",
+                             "bad-subkey.txt.sig", "bad-subkey.txt"])
+                .stdout().is("8F17 7771 18A3 3DDA 9BA4  8E62 AACB 3243 6300 52D9")
+                .unwrap();
+        }
+
+        let files = fs::read_dir(cache.path()).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert!(files.iter().any(|f| f.ends_with(".pgp")));
+        assert!(files.iter().any(|f| f.ends_with(".pgp.idx")));
+        assert!(files.iter().any(|f| f.ends_with(".stamp")));
+    }
+}
@@ -0,0 +1,85 @@
+//! Reading GnuPG's configuration.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Returns the keyserver GnuPG is configured to use, if any.
+///
+/// A keyserver configured in `gpg.conf` takes precedence over one
+/// configured in `dirmngr.conf`, like it does in GnuPG.
+pub fn keyserver(gnupg_home: &Path) -> Option<String> {
+    // In gpg.conf, the last occurrence of an option wins.
+    option_values(&gnupg_home.join("gpg.conf"), "keyserver")
+        .pop()
+        // Dirmngr accepts several keyservers, but uses the first.
+        .or_else(|| option_values(&gnupg_home.join("dirmngr.conf"),
+                                  "keyserver")
+                 .into_iter().next())
+}
+
+/// Returns the values of the given option in a configuration file.
+///
+/// GnuPG's configuration files contain one option per line,
+/// optionally followed by whitespace and a value.  Empty lines and
+/// lines starting with `#` are ignored.  If the file cannot be read,
+/// no values are returned.
+fn option_values(path: &Path, option: &str) -> Vec<String> {
+    let f = match File::open(path) {
+        Ok(f) => f,
+        Err(_) => return Vec::new(),
+    };
+
+    BufReader::new(f).lines()
+        .filter_map(|l| l.ok())
+        .filter_map(|l| {
+            let l = l.trim();
+            if l.starts_with('#') {
+                return None;
+            }
+
+            let mut fields = l.splitn(2, char::is_whitespace);
+            if fields.next() != Some(option) {
+                return None;
+            }
+
+            // Old versions of GnuPG allowed keyserver options after
+            // the URI.  Ignore them.
+            fields.next()
+                .and_then(|v| v.split_whitespace().next())
+                .map(|v| v.trim_matches('"').to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+    use sequoia_core::Context;
+
+    #[test]
+    fn keyserver_precedence() {
+        // Use an ephemeral home as stand-in for GnuPG's.
+        let home = Context::configure().ephemeral().build().unwrap();
+        let home = home.home();
+        fs::create_dir_all(home).unwrap();
+        assert_eq!(keyserver(home), None);
+
+        fs::File::create(home.join("dirmngr.conf")).unwrap()
+            .write_all(b"# Comment\n\
+                         keyserver hkps://one.example.org\n\
+                         keyserver hkps://two.example.org\n").unwrap();
+        assert_eq!(keyserver(home),
+                   Some("hkps://one.example.org".into()));
+
+        fs::File::create(home.join("gpg.conf")).unwrap()
+            .write_all(b"keyserver hkp://three.example.org\n\
+                         keyserver-options auto-key-retrieve\n\
+                         keyserver  hkps://four.example.org  no-honor\n")
+            .unwrap();
+        assert_eq!(keyserver(home),
+                   Some("hkps://four.example.org".into()));
+    }
+}
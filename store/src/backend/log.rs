@@ -7,6 +7,7 @@ use super::{
     StoreServer, BindingServer, KeyServer,
     Promise, capnp, capnp_rpc
 };
+use rusqlite;
 
 /// Models entries referring to other objects.
 pub struct Refers {
@@ -75,16 +76,73 @@ pub enum Selector {
     Key(ID),
 }
 
+/// A log entry.
+///
+/// Id, timestamp, referenced store, binding, and key, slug, message,
+/// and error.
+type Entry = (ID, Timestamp, Option<ID>, Option<ID>, Option<ID>,
+              String, String, Option<String>);
+
 /// Iterator for log entries.
+///
+/// Only entries not referring to stores or bindings outside of the
+/// realms with the given scope are returned.
 pub struct IterServer {
     c: Rc<Connection>,
+    scope: String,
     selector: Selector,
     n: ID,
 }
 
 impl IterServer {
-    pub fn new(c: Rc<Connection>, selector: Selector) -> Self {
-        IterServer{c: c, selector: selector, n: ID::max()}
+    pub fn new(c: Rc<Connection>, scope: &str, selector: Selector) -> Self {
+        IterServer{c: c, scope: scope.into(), selector: selector,
+                   n: ID::max()}
+    }
+
+    /// Returns the entry preceding the current one.
+    fn query(&self) -> rusqlite::Result<Entry> {
+        let (condition, target) = match self.selector {
+            Selector::All => ("1", None),
+            Selector::Store(store) =>
+                ("(store = ?3
+                   OR binding IN (SELECT id FROM bindings WHERE store = ?3)
+                   OR key IN (SELECT key FROM bindings WHERE store = ?3))",
+                 Some(store)),
+            Selector::Binding(binding) =>
+                ("(binding = ?3
+                   OR key IN (SELECT key FROM bindings WHERE id = ?3))",
+                 Some(binding)),
+            Selector::Key(key) => ("key = ?3", Some(key)),
+        };
+
+        let pattern = format!("{}%", self.scope);
+        let mut params: Vec<&rusqlite::types::ToSql> =
+            vec![&self.n, &pattern];
+        if let Some(ref target) = target {
+            params.push(target);
+        }
+
+        self.c.query_row(
+            &format!(
+                "SELECT id, timestamp,
+                        store, binding, key,
+                        slug, message, error
+                     FROM log
+                     WHERE id < ?1
+                       AND (store IS NULL
+                            OR store IN (SELECT id FROM stores
+                                         WHERE realm LIKE ?2))
+                       AND (binding IS NULL
+                            OR binding IN (SELECT bindings.id FROM bindings
+                                           JOIN stores ON stores.id = bindings.store
+                                           WHERE stores.realm LIKE ?2))
+                       AND {}
+                     ORDER BY id DESC LIMIT 1", condition),
+            &params,
+            |row| (row.get(0), row.get(1),
+                   row.get(2), row.get(3), row.get(4),
+                   row.get(5), row.get(6), row.get(7)))
     }
 }
 
@@ -99,89 +157,27 @@ impl node::log_iter::Server for IterServer {
             id, timestamp,
             store, binding, key,
             slug, message, error
-        ): (
-            ID, Timestamp,
-            Option<ID>, Option<ID>, Option<ID>,
-            String, String, Option<String>
-        ) = sry!(match self.selector {
-            Selector::All =>
-                self.c.query_row(
-                    "SELECT id, timestamp,
-                            store, binding, key,
-                            slug, message, error
-                         FROM log
-                         WHERE id < ?1
-                         ORDER BY id DESC LIMIT 1",
-                    &[&self.n],
-                    |row| (row.get(0), row.get(1),
-                           row.get(2), row.get(3), row.get(4),
-                           row.get(5), row.get(6), row.get(7))),
-
-            Selector::Store(store) =>
-                self.c.query_row(
-                    "SELECT id, timestamp,
-                            store, binding, key,
-                            slug, message, error
-                         FROM log
-                         WHERE id < ?1
-                           AND (store = ?2
-                                OR binding IN (SELECT id FROM bindings WHERE store = ?2)
-                                OR key IN (SELECT key FROM bindings WHERE store = ?2))
-                         ORDER BY id DESC LIMIT 1",
-                    &[&self.n, &store],
-                    |row| (row.get(0), row.get(1),
-                           row.get(2), row.get(3), row.get(4),
-                           row.get(5), row.get(6), row.get(7))),
-
-            Selector::Binding(binding) =>
-                self.c.query_row(
-                    "SELECT id, timestamp,
-                            store, binding, key,
-                            slug, message, error
-                         FROM log
-                         WHERE id < ?1
-                           AND (binding = ?2
-                                OR key IN (SELECT key FROM bindings WHERE id = ?2))
-                         ORDER BY id DESC LIMIT 1",
-                    &[&self.n, &binding],
-                    |row| (row.get(0), row.get(1),
-                           row.get(2), row.get(3), row.get(4),
-                           row.get(5), row.get(6), row.get(7))),
-
-            Selector::Key(key) =>
-                self.c.query_row(
-                    "SELECT id, timestamp,
-                            store, binding, key,
-                            slug, message, error
-                         FROM log
-                         WHERE id < ?1
-                           AND key = ?2
-                         ORDER BY id DESC LIMIT 1",
-                    &[&self.n, &key],
-                    |row| (row.get(0), row.get(1),
-                           row.get(2), row.get(3), row.get(4),
-                           row.get(5), row.get(6), row.get(7))),
-        });
+        ) = sry!(self.query());
 
         let mut entry = pry!(results.get().get_result()).init_ok();
         entry.set_timestamp(timestamp.unix());
 
         if let Some(store) = store {
             entry.set_store(node::store::ToClient::new(
-                StoreServer::new(self.c.clone(), store))
+                StoreServer::new(self.c.clone(), store, &self.scope))
                             .into_client::<capnp_rpc::Server>());
         }
 
         if let Some(binding) = binding {
             entry.set_binding(node::binding::ToClient::new(
-                BindingServer::new(self.c.clone(), binding))
+                BindingServer::new(self.c.clone(), binding, &self.scope))
                             .into_client::<capnp_rpc::Server>());
         }
 
         if let Some(key) = key {
             entry.set_key(node::key::ToClient::new(
-                KeyServer::new(self.c.clone(), key))
-                            .into_client::<capnp_rpc::Server>());
+                KeyServer::new(self.c.clone(), key, &self.scope))
+                          .into_client::<capnp_rpc::Server>());
         }
 
         entry.set_slug(&slug);
@@ -194,3 +190,88 @@ impl node::log_iter::Server for IterServer {
         Promise::ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{ipc, NodeServer};
+    use super::super::{BindingServer, Fingerprint};
+    use super::super::test::{node, store};
+
+    /// Returns the ids of the selected log entries.
+    fn entries(node: &NodeServer, selector: Selector) -> Vec<ID> {
+        let mut iter = IterServer::new(node.c.clone(), &node.scope, selector);
+        let mut entries = vec![];
+        while let Ok(entry) = iter.query() {
+            iter.n = entry.0;
+            entries.push(entry.0);
+        }
+        entries
+    }
+
+    #[test]
+    fn scope() {
+        let owner = node();
+        let alice = owner.for_peer(ipc::Peer::User(1000));
+        let bob = owner.for_peer(ipc::Peer::User(1001));
+
+        let sa = store(&alice);
+        let sb = store(&bob);
+
+        let global = message(&owner.c, Refers::to(), "server", "Hello")
+            .unwrap();
+        let ea = message(&owner.c, Refers::to().store(sa), "alice", "Hi")
+            .unwrap();
+        let eb = message(&owner.c, Refers::to().store(sb), "bob", "Hi")
+            .unwrap();
+
+        let log = entries(&alice, Selector::All);
+        assert!(log.contains(&global) && log.contains(&ea));
+        assert!(! log.contains(&eb));
+
+        let log = entries(&bob, Selector::All);
+        assert!(log.contains(&global) && log.contains(&eb));
+        assert!(! log.contains(&ea));
+
+        // The owner reads the whole log.
+        let log = entries(&owner, Selector::All);
+        assert!(log.contains(&global) && log.contains(&ea)
+                && log.contains(&eb));
+    }
+
+    #[test]
+    fn key_scope() {
+        let owner = node();
+        let alice = owner.for_peer(ipc::Peer::User(1000));
+        let bob = owner.for_peer(ipc::Peer::User(1001));
+
+        // Both users bind the same key.
+        let fp = Fingerprint::from_bytes(b"aaaaaaaaaaaaaaaaaaaa");
+        let (ba, key, _) =
+            BindingServer::lookup_or_create(&owner.c, store(&alice),
+                                            "label", &fp).unwrap();
+        let (bb, _, _) =
+            BindingServer::lookup_or_create(&owner.c, store(&bob),
+                                            "label", &fp).unwrap();
+
+        let ek = message(&owner.c, Refers::to().key(key), "key", "Updated")
+            .unwrap();
+        let ea = message(&owner.c, Refers::to().binding(ba).key(key),
+                         "alice", "Hi").unwrap();
+        let eb = message(&owner.c, Refers::to().binding(bb).key(key),
+                         "bob", "Hi").unwrap();
+
+        // The key's log does not leak other users' bindings.
+        let log = entries(&alice, Selector::Key(key));
+        assert!(log.contains(&ek) && log.contains(&ea));
+        assert!(! log.contains(&eb));
+        let log = entries(&bob, Selector::Binding(bb));
+        assert!(log.contains(&ek) && log.contains(&eb));
+        assert!(! log.contains(&ea));
+        assert!(entries(&alice, Selector::Binding(bb)) == vec![ek]);
+
+        let log = entries(&owner, Selector::Key(key));
+        assert!(log.contains(&ek) && log.contains(&ea)
+                && log.contains(&eb));
+    }
+}
@@ -2,15 +2,22 @@
 
 use failure;
 use std::cmp;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
 use std::io;
+use std::iter;
+use std::net::IpAddr;
+use std::path::Path;
+use std::process::{Command, Stdio};
 use std::rc::Rc;
-use time::Duration;
+use std::thread;
+use std::time::{Duration as StdDuration, UNIX_EPOCH};
+use time::{self, Duration, Timespec};
 
 use capnp::capability::Promise;
 use capnp;
 use capnp_rpc::rpc_twoparty_capnp::Side;
-use capnp_rpc::{self, RpcSystem, twoparty};
+use capnp_rpc::{self, RpcSystem};
 use futures::Future;
 use futures::future::{self, loop_fn, Loop};
 use rand::distributions::{Distribution, Uniform};
@@ -18,19 +25,21 @@ use rand::thread_rng;
 use rusqlite::Connection;
 use rusqlite;
 use tokio_core::reactor::{Handle, Timeout};
-use tokio_core;
-use tokio_io::io::ReadHalf;
 
-use openpgp::{self, TPK, KeyID, Fingerprint};
-use openpgp::parse::Parse;
-use openpgp::serialize::Serialize;
+use openpgp::{self, TPK, KeyID, Fingerprint, Packet, PacketPile,
+              RevocationStatus};
+use openpgp::constants::HashAlgorithm;
+use openpgp::conversions::hex;
+use openpgp::tpk::{Lint, LintPolicy};
+use openpgp::parse::{Parse, PacketParser, PacketParserResult};
+use openpgp::serialize::{Serialize, SerializeInto};
 use sequoia_core as core;
 use sequoia_net as net;
 use sequoia_ipc as ipc;
 
 use store_protocol_capnp::node;
 
-use super::Result;
+use super::{AutocryptLevel, MergeStrategy, Result, Trust};
 
 // Data types for working with `rusqlite`.
 pub mod support;
@@ -39,6 +48,9 @@ use self::support::{ID, Timestamp};
 // Logging.
 mod log;
 
+// Interoperability with GnuPG.
+mod gnupg;
+
 /* Configuration and policy.  */
 
 /// Minimum sleep time.
@@ -51,6 +63,55 @@ fn refresh_interval() -> Duration {
     Duration::weeks(1)
 }
 
+/// The network policy of stores whose keys are refreshed.
+///
+/// For now, we only update keys in stores with this or a more
+/// permissive policy.
+fn refresh_network_policy() -> core::NetworkPolicy {
+    core::NetworkPolicy::Encrypted
+}
+
+/// Keys expiring within this interval are refreshed sooner.
+fn expiry_horizon() -> Duration {
+    Duration::weeks(4)
+}
+
+/// Interval after which keys needing attention are refreshed again.
+fn attention_interval() -> Duration {
+    cmp::max(refresh_interval() / 4, min_sleep_time())
+}
+
+/// Interval after which keys that have not been refreshed are stale.
+///
+/// This allows for the randomization of refresh times.
+fn stale_interval() -> Duration {
+    refresh_interval() * 2
+}
+
+/// How long to wait for the database if another process holds a lock.
+///
+/// The store server and the housekeeping service access the database
+/// concurrently.  This must be well below the clients' request
+/// timeout, so that clients see `Error::Timeout` from us instead of
+/// giving up on their own.
+fn busy_timeout() -> ::std::time::Duration {
+    ::std::time::Duration::from_secs(5)
+}
+
+/// Maximum number of weeks returned by usage queries.
+const MAX_USAGE_WEEKS: u32 = 520;
+
+/// Updates growing a key beyond this size are quarantined.
+const QUARANTINE_MAX_SIZE: usize = 1 << 20;
+
+/// Updates adding third-party certifications beyond this number are
+/// quarantined.
+const QUARANTINE_MAX_CERTIFICATIONS: usize = 1024;
+
+/// Value of `housekeeping.paused_until` if key updates are paused
+/// until they are resumed.
+const PAUSED_INDEFINITELY: i64 = -1;
+
 /// Returns a value from the uniform distribution over [0, 2*d).
 ///
 /// This function is used to randomize key refresh times.
@@ -69,52 +130,149 @@ pub fn factory(descriptor: ipc::Descriptor, handle: Handle)
         .map(|b| -> Box<ipc::Handler> { Box::new(b) })
 }
 
+/// Makes housekeeping services.
+pub fn housekeeping_factory(descriptor: ipc::Descriptor, handle: Handle)
+                            -> Result<Box<ipc::Handler>> {
+    Housekeeping::new(descriptor, handle)
+        .map(|h| -> Box<ipc::Handler> { Box::new(h) })
+}
+
+/// Opens the database.
+fn open_database(descriptor: &ipc::Descriptor) -> Result<Connection> {
+    let mut db_path = descriptor.context().home().to_path_buf();
+    db_path.push("public-key-store.sqlite");
+
+    let c = Connection::open(db_path)?;
+    c.busy_timeout(busy_timeout())?;
+    c.execute_batch("PRAGMA secure_delete = true;")?;
+    c.execute_batch("PRAGMA foreign_keys = true;")?;
+    Ok(c)
+}
+
 struct Backend {
-    store: node::Client,
+    node: NodeServer,
 }
 
 impl Backend {
     fn new(descriptor: ipc::Descriptor, handle: Handle) -> Result<Self> {
         Ok(Backend {
-            store: node::ToClient::new(NodeServer::new(descriptor, handle)?)
-                .into_client::<capnp_rpc::Server>(),
+            node: NodeServer::new(descriptor, handle)?,
         })
     }
 }
 
 impl ipc::Handler for Backend {
-    fn handle(&self,
-              network: twoparty::VatNetwork<ReadHalf<tokio_core::net::TcpStream>>)
+    fn handle(&self, network: ipc::Network, peer: ipc::Peer)
+              -> RpcSystem<Side> {
+        let node = node::ToClient::new(self.node.for_peer(peer))
+            .into_client::<capnp_rpc::Server>();
+        RpcSystem::new(Box::new(network), Some(node.client))
+    }
+}
+
+/// Periodically updates the keys in the store.
+///
+/// The housekeeping service runs independently of the store server.
+/// It does not serve any requests, it merely updates keys as
+/// scheduled by the `update_at` column.
+struct Housekeeping;
+
+impl Housekeeping {
+    fn new(descriptor: ipc::Descriptor, handle: Handle) -> Result<Self> {
+        let c = open_database(&descriptor)?;
+        let keyserver = descriptor.context().gnupg_home()
+            .and_then(gnupg::keyserver);
+        KeyServer::start_housekeeping(Rc::new(c), keyserver, handle)?;
+        Ok(Housekeeping)
+    }
+}
+
+impl ipc::Handler for Housekeeping {
+    fn handle(&self, network: ipc::Network, _: ipc::Peer)
               -> RpcSystem<Side> {
-        RpcSystem::new(Box::new(network), Some(self.store.clone().client))
+        RpcSystem::new(Box::new(network), None)
     }
 }
 
 /* Server implementation.  */
 
 struct NodeServer {
-    _descriptor: ipc::Descriptor,
+    descriptor: ipc::Descriptor,
     c: Rc<Connection>,
+    // The realms of users of a system daemon are prefixed with this.
+    scope: String,
+}
+
+/// Returns the prefix of the realms of the given peer.
+///
+/// The store of a system daemon is shared by all users.  The key
+/// pool is public, but every user gets their own realms.
+fn realm_scope(peer: ipc::Peer) -> String {
+    match peer {
+        ipc::Peer::Owner => String::new(),
+        ipc::Peer::User(uid) => format!("uid:{}/", uid),
+    }
 }
 
 impl NodeServer {
     fn new(descriptor: ipc::Descriptor, handle: Handle) -> Result<Self> {
-        let mut db_path = descriptor.context().home().to_path_buf();
-        db_path.push("public-key-store.sqlite");
-
-        let c = Connection::open(db_path)?;
-        c.execute_batch("PRAGMA secure_delete = true;")?;
-        c.execute_batch("PRAGMA foreign_keys = true;")?;
+        let c = open_database(&descriptor)?;
         let server = NodeServer {
-            _descriptor: descriptor,
+            descriptor: descriptor,
             c: Rc::new(c),
+            scope: realm_scope(ipc::Peer::Owner),
         };
         server.init()?;
 
-        KeyServer::start_housekeeping(server.c.clone(), handle)?;
+        // Updating keys is delegated to the housekeeping service.
+        // There is at most one per home, so applications sharing a
+        // store do not compete refreshing keys.  Connecting starts
+        // it if necessary.  It keeps running after we disconnect.
+        ::housekeeping_descriptor(server.descriptor.context())
+            .connect(&handle)?;
         Ok(server)
     }
 
+    /// Returns a server for the given peer.
+    fn for_peer(&self, peer: ipc::Peer) -> Self {
+        NodeServer {
+            descriptor: self.descriptor.clone(),
+            c: self.c.clone(),
+            scope: realm_scope(peer),
+        }
+    }
+
+    /// Returns the name of the given realm of the peer.
+    fn realm(&self, realm: &str) -> String {
+        format!("{}{}", self.scope, realm)
+    }
+
+    /// Returns whether the peer owns the store.
+    ///
+    /// Only the owner may control the housekeeping service, which
+    /// is shared by all users.
+    fn is_owner(&self) -> bool {
+        self.scope.is_empty()
+    }
+
+    /// Returns the keys in the peer's realms that are refreshed.
+    ///
+    /// Returns their fingerprints and when they are updated next.
+    fn refresh_schedule_keys(&self) -> Result<Vec<(String, i64)>> {
+        let network_policy_u8 = u8::from(&refresh_network_policy());
+        let mut stmt = self.c.prepare(
+            "SELECT DISTINCT keys.fingerprint, keys.update_at FROM keys
+                 JOIN bindings on keys.id = bindings.key
+                 JOIN stores on stores.id = bindings.store
+                 WHERE stores.network_policy >= ?1
+                   AND stores.realm LIKE ?2
+                 ORDER BY keys.update_at, keys.fingerprint")?;
+        let rows = stmt.query_map(
+            &[&network_policy_u8, &format!("{}%", self.scope)],
+            |row| -> (String, i64) { (row.get(0), row.get(1)) })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
     /// Initializes or migrates the database.
     fn init(&self) -> Result<()> {
         let v = self.c.query_row(
@@ -123,14 +281,222 @@ impl NodeServer {
 
         if let Ok(v) = v {
             match v {
-                1 => return Ok(()),
-                _ => unimplemented!(),
+                1 | 2 | 3 | 4 | 5 | 6 | 7 | 8 | 9 | 10 | 11 | 12 | 13 => {
+                    if v < 2 {
+                        self.c.execute_batch(DB_MIGRATION_1_2)?;
+                    }
+                    if v < 3 {
+                        self.c.execute_batch(DB_MIGRATION_2_3)?;
+                    }
+                    if v < 4 {
+                        self.c.execute_batch(DB_MIGRATION_3_4)?;
+                    }
+                    if v < 5 {
+                        self.c.execute_batch(DB_MIGRATION_4_5)?;
+                    }
+                    if v < 6 {
+                        self.c.execute_batch(DB_MIGRATION_5_6)?;
+                    }
+                    if v < 7 {
+                        self.c.execute_batch(DB_MIGRATION_6_7)?;
+                    }
+                    if v < 8 {
+                        self.c.execute_batch(DB_MIGRATION_7_8)?;
+                    }
+                    if v < 9 {
+                        self.c.execute_batch(DB_MIGRATION_8_9)?;
+                    }
+                    if v < 10 {
+                        self.c.execute_batch(DB_MIGRATION_9_10)?;
+                    }
+                    if v < 11 {
+                        self.c.execute_batch(DB_MIGRATION_10_11)?;
+                    }
+                    if v < 12 {
+                        self.c.execute_batch(DB_MIGRATION_11_12)?;
+                    }
+
+                    if v < 13 {
+                        // The tables are recreated, which must not
+                        // cascade to the rows referring to them.
+                        self.c.execute_batch("PRAGMA foreign_keys = false;")?;
+                        let r = self.c.execute_batch(
+                            &format!("BEGIN; {} COMMIT;", DB_MIGRATION_12_13));
+                        if r.is_err() {
+                            let _ = self.c.execute_batch("ROLLBACK;");
+                        }
+                        self.c.execute_batch("PRAGMA foreign_keys = true;")?;
+                        r?;
+                    }
+                    normalize_labels(&self.c)?;
+                    self.c.execute_batch(DB_MIGRATION_13_14)?;
+
+                    log::message(&self.c, log::Refers::to(), "server",
+                                 "Migrated database to version 14")?;
+                    return Ok(());
+                },
+                14 => return Ok(()),
+                _ => return Err(
+                    format_err!("Unsupported database version {}", v)),
+            }
+        }
+
+        // Overlay contexts start with a copy of the lower layer.
+        if let Some(lower) = self.descriptor.context().lower_home() {
+            let lower = lower.join("public-key-store.sqlite");
+            if lower.exists() {
+                return self.copy_up(&lower);
             }
         }
 
         self.c.execute_batch(DB_SCHEMA_1)?;
+        self.c.execute_batch(DB_MIGRATION_1_2)?;
+        self.c.execute_batch(DB_MIGRATION_2_3)?;
+        self.c.execute_batch(DB_MIGRATION_3_4)?;
+        self.c.execute_batch(DB_MIGRATION_4_5)?;
+        self.c.execute_batch(DB_MIGRATION_5_6)?;
+        self.c.execute_batch(DB_MIGRATION_6_7)?;
+        self.c.execute_batch(DB_MIGRATION_7_8)?;
+        self.c.execute_batch(DB_MIGRATION_8_9)?;
+        self.c.execute_batch(DB_MIGRATION_9_10)?;
+        self.c.execute_batch(DB_MIGRATION_10_11)?;
+        self.c.execute_batch(DB_MIGRATION_11_12)?;
+        self.c.execute_batch(DB_MIGRATION_12_13)?;
+        self.c.execute_batch(DB_MIGRATION_13_14)?;
+        log::message(&self.c, log::Refers::to(), "server",
+                     "Created database version 14")?;
+        Ok(())
+    }
+
+    /// Initializes the database with the content of `lower`.
+    ///
+    /// `lower` is only read from.
+    fn copy_up(&self, lower: &Path) -> Result<()> {
+        self.c.execute("ATTACH DATABASE ?1 AS lower",
+                       &[&lower.to_string_lossy().into_owned()])?;
+
+        let r = (|| -> Result<i64> {
+            let v: i64 = self.c.query_row(
+                "SELECT version FROM lower.version WHERE id=1",
+                &[], |row| row.get(0))?;
+            let stores = match v {
+                1 => "INSERT INTO main.stores (id, realm, network_policy, name)
+                      SELECT * FROM lower.stores;",
+                2 => "INSERT INTO main.stores
+                          (id, realm, network_policy, name, merge_strategy)
+                      SELECT * FROM lower.stores;",
+                3 | 4 | 5 | 6 | 7 =>
+                    "INSERT INTO main.stores
+                          (id, realm, network_policy, name, merge_strategy,
+                           publish_to)
+                      SELECT * FROM lower.stores;",
+                8 | 9 | 10 | 11 | 12 | 13 | 14 =>
+                    "INSERT INTO main.stores SELECT * FROM lower.stores;",
+                _ => return Err(
+                    format_err!("Unsupported database version {}", v)),
+            };
+            let bindings = if v >= 7 {
+                "INSERT INTO main.bindings SELECT * FROM lower.bindings;"
+            } else if v >= 5 {
+                "INSERT INTO main.bindings
+                     (id, store, label, key, created, updated,
+                      encryption_count, encryption_first, encryption_last,
+                      verification_count, verification_first, verification_last,
+                      trust)
+                 SELECT * FROM lower.bindings;"
+            } else {
+                "INSERT INTO main.bindings
+                     (id, store, label, key, created, updated,
+                      encryption_count, encryption_first, encryption_last,
+                      verification_count, verification_first, verification_last)
+                 SELECT * FROM lower.bindings;"
+            };
+            let usage_events = if v >= 4 {
+                "INSERT INTO main.usage_events SELECT * FROM lower.usage_events;"
+            } else {
+                ""
+            };
+            let keyserver_health = if v >= 6 {
+                "INSERT INTO main.keyserver_health
+                     SELECT * FROM lower.keyserver_health;"
+            } else {
+                ""
+            };
+            let keyserver_failover = if v >= 9 {
+                "INSERT INTO main.keyserver_failover
+                     SELECT * FROM lower.keyserver_failover;"
+            } else {
+                ""
+            };
+            let quarantine = if v >= 10 {
+                "INSERT INTO main.quarantine SELECT * FROM lower.quarantine;"
+            } else {
+                ""
+            };
+            let housekeeping = if v >= 12 {
+                "INSERT OR REPLACE INTO main.housekeeping
+                     SELECT * FROM lower.housekeeping;"
+            } else {
+                ""
+            };
+            let keys = if v >= 11 {
+                "INSERT INTO main.keys SELECT * FROM lower.keys;"
+            } else {
+                "INSERT INTO main.keys
+                     (id, fingerprint, key, created, updated, update_at,
+                      encryption_count, encryption_first, encryption_last,
+                      verification_count, verification_first, verification_last)
+                 SELECT * FROM lower.keys;"
+            };
+
+            self.c.execute_batch(&format!("
+BEGIN;
+{}
+{}
+{}
+{}
+{}
+{}
+{}
+{}
+{}
+{}
+{}
+{}
+{}
+{}
+{}
+INSERT INTO main.key_by_keyid SELECT * FROM lower.key_by_keyid;
+INSERT INTO main.log SELECT * FROM lower.log;
+{}
+{}
+{}
+{}
+{}
+{}
+{}
+COMMIT;", DB_SCHEMA_1, DB_MIGRATION_1_2, DB_MIGRATION_2_3, DB_MIGRATION_3_4,
+                                          DB_MIGRATION_4_5, DB_MIGRATION_5_6,
+                                          DB_MIGRATION_6_7, DB_MIGRATION_7_8,
+                                          DB_MIGRATION_8_9, DB_MIGRATION_9_10,
+                                          DB_MIGRATION_10_11, DB_MIGRATION_11_12,
+                                          DB_MIGRATION_12_13, DB_MIGRATION_13_14,
+                                          stores, keys, bindings, usage_events,
+                                          keyserver_health,
+                                          keyserver_failover,
+                                          quarantine, housekeeping))?;
+            Ok(v)
+        })();
+        if r.is_err() {
+            let _ = self.c.execute_batch("ROLLBACK;");
+        }
+
+        self.c.execute_batch("DETACH DATABASE lower;")?;
+        if r? < 14 {
+            normalize_labels(&self.c)?;
+        }
         log::message(&self.c, log::Refers::to(), "server",
-                     "Created database version 1")?;
+                     &format!("Copied database from {}", lower.display()))?;
         Ok(())
     }
 }
@@ -145,8 +511,10 @@ impl node::Server for NodeServer {
 
         // XXX maybe check ephemeral and use in-core sqlite db
 
+        let realm = self.realm(pry!(params.get_realm()));
         let store = sry!(StoreServer::open(self.c.clone(),
-                                           pry!(params.get_realm()),
+                                           &self.scope,
+                                           &realm,
                                            pry!(params.get_network_policy()).into(),
                                            pry!(params.get_name())));
         pry!(pry!(results.get().get_result()).set_ok(
@@ -160,7 +528,8 @@ impl node::Server for NodeServer {
             -> Promise<(), capnp::Error> {
         bind_results!(results);
         let prefix = pry!(pry!(params.get()).get_realm_prefix());
-        let iter = StoreIterServer::new(self.c.clone(), prefix);
+        let iter = sry!(StoreIterServer::new(self.c.clone(), &self.scope,
+                                             prefix));
         pry!(pry!(results.get().get_result()).set_ok(
             node::store_iter::ToClient::new(iter).into_client::<capnp_rpc::Server>()));
         Promise::ok(())
@@ -171,7 +540,7 @@ impl node::Server for NodeServer {
                  mut results: node::IterKeysResults)
                  -> Promise<(), capnp::Error> {
         bind_results!(results);
-        let iter = KeyIterServer::new(self.c.clone());
+        let iter = sry!(KeyIterServer::new(self.c.clone(), &self.scope));
         pry!(pry!(results.get().get_result()).set_ok(
             node::key_iter::ToClient::new(iter).into_client::<capnp_rpc::Server>()));
         Promise::ok(())
@@ -182,7 +551,8 @@ impl node::Server for NodeServer {
            mut results: node::LogResults)
            -> Promise<(), capnp::Error> {
         bind_results!(results);
-        let iter = log::IterServer::new(self.c.clone(), log::Selector::All);
+        let iter = log::IterServer::new(self.c.clone(), &self.scope,
+                                        log::Selector::All);
         pry!(pry!(results.get().get_result()).set_ok(
             node::log_iter::ToClient::new(iter).into_client::<capnp_rpc::Server>()));
         Promise::ok(())
@@ -193,11 +563,20 @@ impl node::Server for NodeServer {
               mut results: node::ImportResults)
               -> Promise<(), capnp::Error> {
         bind_results!(results);
-        let new = sry!(TPK::from_bytes(&pry!(pry!(params.get()).get_key())));
-        let fp = new.fingerprint();
-        let key_id = sry!(KeyServer::lookup_or_create(&self.c, &fp));
-        let key = KeyServer::new(self.c.clone(), key_id);
-        sry!(key.merge(new));
+        let blob = pry!(pry!(params.get()).get_key());
+        let key = match sry!(KeyServer::lookup_unchanged_by(&self.c, blob)) {
+            // Importing a key we already have is a no-op.
+            Some(key_id) =>
+                KeyServer::new(self.c.clone(), key_id, &self.scope),
+            None => {
+                let new = sry!(TPK::from_bytes(blob));
+                let fp = new.fingerprint();
+                let key_id = sry!(KeyServer::lookup_or_create(&self.c, &fp));
+                let key = KeyServer::new(self.c.clone(), key_id, &self.scope);
+                sry!(key.merge(new));
+                key
+            },
+        };
         pry!(pry!(results.get().get_result())
              .set_ok(node::key::ToClient::new(key)
                      .into_client::<capnp_rpc::Server>()));
@@ -213,9 +592,10 @@ impl node::Server for NodeServer {
         let keyid = KeyID::new(keyid);
         let key_id = sry!(KeyServer::lookup_by_id(&self.c, &keyid));
 
+        sry!(key_status(&self.c, key_id, results.get().init_status()));
         pry!(pry!(results.get().get_result()).set_ok(
             node::key::ToClient::new(
-                KeyServer::new(self.c.clone(), key_id))
+                KeyServer::new(self.c.clone(), key_id, &self.scope))
                 .into_client::<capnp_rpc::Server>()));
         Promise::ok(())
     }
@@ -229,9 +609,10 @@ impl node::Server for NodeServer {
         let fingerprint = sry!(Fingerprint::from_hex(fingerprint));
         let key_id = sry!(KeyServer::lookup(&self.c, &fingerprint));
 
+        sry!(key_status(&self.c, key_id, results.get().init_status()));
         pry!(pry!(results.get().get_result()).set_ok(
             node::key::ToClient::new(
-                KeyServer::new(self.c.clone(), key_id))
+                KeyServer::new(self.c.clone(), key_id, &self.scope))
                 .into_client::<capnp_rpc::Server>()));
         Promise::ok(())
     }
@@ -249,17 +630,83 @@ impl node::Server for NodeServer {
                  WHERE key_by_keyid.keyid = ?1",
                 &[&(keyid as i64)], |row| row.get(0)));
 
+        sry!(key_status(&self.c, key_id, results.get().init_status()));
         pry!(pry!(results.get().get_result()).set_ok(
             node::key::ToClient::new(
-                KeyServer::new(self.c.clone(), key_id))
+                KeyServer::new(self.c.clone(), key_id, &self.scope))
                 .into_client::<capnp_rpc::Server>()));
         Promise::ok(())
     }
+
+    fn refresh_schedule(&mut self,
+                        _: node::RefreshScheduleParams,
+                        mut results: node::RefreshScheduleResults)
+                        -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let paused_until: i64 = sry!(
+            self.c.query_row(
+                "SELECT paused_until FROM housekeeping WHERE id = 1",
+                &[], |row| row.get(0)));
+
+        let keys = sry!(self.refresh_schedule_keys());
+
+        let mut schedule = pry!(results.get().get_result()).init_ok();
+        schedule.set_paused_until(paused_until);
+        let mut list = schedule.init_keys(keys.len() as u32);
+        for (i, &(ref fingerprint, update_at)) in keys.iter().enumerate() {
+            let mut entry = list.reborrow().get(i as u32);
+            entry.set_fingerprint(fingerprint);
+            entry.set_update_at(update_at);
+        }
+        Promise::ok(())
+    }
+
+    fn pause_refresh(&mut self,
+                     params: node::PauseRefreshParams,
+                     mut results: node::PauseRefreshResults)
+                     -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        if ! self.is_owner() {
+            fail!(node::Error::PermissionDenied);
+        }
+        let until = pry!(params.get()).get_until();
+        if until < PAUSED_INDEFINITELY {
+            fail!(node::Error::Unspecified);
+        }
+
+        sry!(self.c.execute(
+            "UPDATE housekeeping SET paused_until = ?1 WHERE id = 1",
+            &[&until]));
+        let message: String = match until {
+            0 => "Resumed key updates".into(),
+            PAUSED_INDEFINITELY => "Paused key updates".into(),
+            t => format!("Paused key updates until {}",
+                         time::at_utc(Timespec::new(t, 0)).rfc3339()),
+        };
+        sry!(log::message(&self.c, log::Refers::to(), "housekeeping",
+                          &message));
+        Promise::ok(())
+    }
+
+    fn refresh_all(&mut self,
+                   _: node::RefreshAllParams,
+                   mut results: node::RefreshAllResults)
+                   -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        if ! self.is_owner() {
+            fail!(node::Error::PermissionDenied);
+        }
+        sry!(self.c.execute("UPDATE keys SET update_at = 0", &[]));
+        sry!(log::message(&self.c, log::Refers::to(), "housekeeping",
+                          "Scheduled all keys for an update"));
+        Promise::ok(())
+    }
 }
 
 struct StoreServer {
     c: Rc<Connection>,
     id: ID,
+    scope: String,
 }
 
 impl Query for StoreServer {
@@ -288,11 +735,12 @@ impl Query for StoreServer {
 }
 
 impl StoreServer {
-    fn new(c: Rc<Connection>, id: ID) -> StoreServer {
-        StoreServer{c: c, id: id}
+    fn new(c: Rc<Connection>, id: ID, scope: &str) -> StoreServer {
+        StoreServer{c: c, id: id, scope: scope.into()}
     }
 
-    fn open(c: Rc<Connection>, realm: &str, policy: core::NetworkPolicy, name: &str)
+    fn open(c: Rc<Connection>, scope: &str, realm: &str,
+            policy: core::NetworkPolicy, name: &str)
            -> Result<Self> {
         // We cannot implement ToSql and friends for
         // core::NetworkPolicy, hence we need to do it by foot.
@@ -317,7 +765,7 @@ impl StoreServer {
                        .into());
         }
 
-        Ok(Self::new(c, id))
+        Ok(Self::new(c, id, scope))
     }
 }
 
@@ -331,10 +779,10 @@ impl node::store::Server for StoreServer {
         let fp = pry!(params.get_fingerprint());
         let fp = sry!(Fingerprint::from_hex(fp)
                       .map_err(|_| node::Error::MalformedFingerprint));
-        let label = pry!(params.get_label());
+        let label = ::normalize_label(pry!(params.get_label()));
 
         let (binding_id, key_id, created) = sry!(
-            BindingServer::lookup_or_create(&self.c, self.id, label, &fp));
+            BindingServer::lookup_or_create(&self.c, self.id, &label, &fp));
 
         if created {
             sry!(log::message(
@@ -347,7 +795,7 @@ impl node::store::Server for StoreServer {
 
         pry!(pry!(results.get().get_result()).set_ok(
             node::binding::ToClient::new(
-                BindingServer::new(self.c.clone(), binding_id))
+                BindingServer::new(self.c.clone(), binding_id, &self.scope))
                 .into_client::<capnp_rpc::Server>()));
         Promise::ok(())
     }
@@ -359,14 +807,12 @@ impl node::store::Server for StoreServer {
         bind_results!(results);
         let label = pry!(pry!(params.get()).get_label());
 
-        let binding_id: ID = sry!(
-            self.c.query_row(
-                "SELECT id FROM bindings WHERE store = ?1 AND label = ?2",
-                &[&self.id, &label], |row| row.get(0)));
+        let (binding_id, key_id) = sry!(lookup_binding(&self.c, self.id, label));
 
+        sry!(key_status(&self.c, key_id, results.get().init_status()));
         pry!(pry!(results.get().get_result()).set_ok(
             node::binding::ToClient::new(
-                BindingServer::new(self.c.clone(), binding_id))
+                BindingServer::new(self.c.clone(), binding_id, &self.scope))
                 .into_client::<capnp_rpc::Server>()));
         Promise::ok(())
     }
@@ -378,16 +824,13 @@ impl node::store::Server for StoreServer {
         bind_results!(results);
         let keyid = pry!(params.get()).get_keyid();
 
-        let binding_id: ID = sry!(
-            self.c.query_row(
-                "SELECT bindings.id FROM bindings
-                 JOIN key_by_keyid on bindings.key = key_by_keyid.key
-                 WHERE key_by_keyid.keyid = ?1",
-                &[&(keyid as i64)], |row| row.get(0)));
+        let (binding_id, key_id) =
+            sry!(lookup_binding_by_subkeyid(&self.c, self.id, keyid));
 
+        sry!(key_status(&self.c, key_id, results.get().init_status()));
         pry!(pry!(results.get().get_result()).set_ok(
             node::binding::ToClient::new(
-                BindingServer::new(self.c.clone(), binding_id))
+                BindingServer::new(self.c.clone(), binding_id, &self.scope))
                 .into_client::<capnp_rpc::Server>()));
         Promise::ok(())
     }
@@ -407,7 +850,21 @@ impl node::store::Server for StoreServer {
             mut results: node::store::IterResults)
             -> Promise<(), capnp::Error> {
         bind_results!(results);
-        let iter = BindingIterServer::new(self.c.clone(), self.id);
+        let iter = sry!(BindingIterServer::new(self.c.clone(), &self.scope,
+                                               self.id, None));
+        pry!(pry!(results.get().get_result()).set_ok(
+            node::binding_iter::ToClient::new(iter).into_client::<capnp_rpc::Server>()));
+        Promise::ok(())
+    }
+
+    fn iter_by_trust(&mut self,
+                     params: node::store::IterByTrustParams,
+                     mut results: node::store::IterByTrustResults)
+                     -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let trust: Trust = pry!(pry!(params.get()).get_trust()).into();
+        let iter = sry!(BindingIterServer::new(self.c.clone(), &self.scope,
+                                               self.id, Some(trust)));
         pry!(pry!(results.get().get_result()).set_ok(
             node::binding_iter::ToClient::new(iter).into_client::<capnp_rpc::Server>()));
         Promise::ok(())
@@ -418,41 +875,540 @@ impl node::store::Server for StoreServer {
            mut results: node::store::LogResults)
            -> Promise<(), capnp::Error> {
         bind_results!(results);
-        let iter = log::IterServer::new(self.c.clone(), log::Selector::Store(self.id));
+        let iter = log::IterServer::new(self.c.clone(), &self.scope,
+                                        log::Selector::Store(self.id));
         pry!(pry!(results.get().get_result()).set_ok(
             node::log_iter::ToClient::new(iter).into_client::<capnp_rpc::Server>()));
         Promise::ok(())
     }
+
+    fn duplicates(&mut self,
+                  _: node::store::DuplicatesParams,
+                  mut results: node::store::DuplicatesResults)
+                  -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let groups: Vec<_> = sry!(bindings_by_normalized_label(&self.c, self.id))
+            .into_iter().filter(|&(_, ref bindings)| bindings.len() > 1)
+            .collect();
+
+        let mut list = pry!(results.get().get_result()).init_ok()
+            .init_groups(groups.len() as u32);
+        for (i, (label, bindings)) in groups.into_iter().enumerate() {
+            let mut group = list.reborrow().get(i as u32);
+            group.set_label(&label);
+            let mut items = group.init_bindings(bindings.len() as u32);
+            for (j, (id, label, fingerprint)) in bindings.into_iter().enumerate() {
+                let mut item = items.reborrow().get(j as u32);
+                item.set_label(&label);
+                item.set_fingerprint(&fingerprint);
+                item.set_binding(node::binding::ToClient::new(
+                    BindingServer::new(self.c.clone(), id, &self.scope))
+                                 .into_client::<capnp_rpc::Server>());
+            }
+        }
+        Promise::ok(())
+    }
+
+    fn merge_duplicates(&mut self,
+                        params: node::store::MergeDuplicatesParams,
+                        mut results: node::store::MergeDuplicatesResults)
+                        -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let label = pry!(pry!(params.get()).get_label());
+        let (binding_id, merged) =
+            sry!(merge_duplicates(&self.c, self.id, label));
+
+        if merged > 0 {
+            let key_id: ID = sry!(self.c.query_row(
+                "SELECT key FROM bindings WHERE id = ?1",
+                &[&binding_id], |row| row.get(0)));
+            sry!(log::message(
+                &self.c,
+                log::Refers::to().store(self.id).binding(binding_id)
+                    .key(key_id),
+                &self.slug(),
+                &format!("Merged {} duplicate bindings into {}",
+                         merged, ::normalize_label(label))));
+        }
+
+        pry!(pry!(results.get().get_result()).set_ok(
+            node::binding::ToClient::new(
+                BindingServer::new(self.c.clone(), binding_id, &self.scope))
+                .into_client::<capnp_rpc::Server>()));
+        Promise::ok(())
+    }
+
+    fn settings(&mut self,
+                _: node::store::SettingsParams,
+                mut results: node::store::SettingsResults)
+                -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let strategy = sry!(merge_strategy(&self.c, self.id));
+        let uri = sry!(publish_to(&self.c, self.id));
+        let command = sry!(notify_command(&self.c, self.id));
+        let mut settings = pry!(results.get().get_result()).init_ok();
+        settings.set_merge_strategy(strategy.into());
+        settings.set_publish_to(uri.as_ref().map(|u| u.as_str()).unwrap_or(""));
+        settings.set_notify_command(
+            command.as_ref().map(|c| c.as_str()).unwrap_or(""));
+        Promise::ok(())
+    }
+
+    fn set_merge_strategy(&mut self,
+                          params: node::store::SetMergeStrategyParams,
+                          mut results: node::store::SetMergeStrategyResults)
+                          -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let strategy: MergeStrategy =
+            pry!(pry!(params.get()).get_strategy()).into();
+        sry!(self.c.execute(
+            "UPDATE stores SET merge_strategy = ?1 WHERE id = ?2",
+            &[&i64::from(strategy), &self.id]));
+        sry!(log::message(&self.c, log::Refers::to().store(self.id),
+                          &self.slug(),
+                          &format!("Merge strategy set to {:?}", strategy)));
+        Promise::ok(())
+    }
+
+    fn set_publish_to(&mut self,
+                      params: node::store::SetPublishToParams,
+                      mut results: node::store::SetPublishToResults)
+                      -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let uri = pry!(pry!(params.get()).get_uri());
+        let uri = if uri == "" { None } else { Some(uri) };
+
+        // Reject keyservers that do not comply with the network
+        // policy.
+        if let Some(uri) = uri {
+            let ctx = sry!(core::Context::configure()
+                           .network_policy(sry!(network_policy(&self.c, self.id)))
+                           .build());
+            sry!(net::async::KeyServer::new(&ctx, uri));
+        }
+
+        sry!(self.c.execute(
+            "UPDATE stores SET publish_to = ?1 WHERE id = ?2",
+            &[&uri, &self.id]));
+        sry!(log::message(&self.c, log::Refers::to().store(self.id),
+                          &self.slug(),
+                          &match uri {
+                              Some(uri) => format!("Publishing keys to {}", uri),
+                              None => "Not publishing keys".into(),
+                          }));
+        Promise::ok(())
+    }
+
+    fn set_notify_command(&mut self,
+                          params: node::store::SetNotifyCommandParams,
+                          mut results: node::store::SetNotifyCommandResults)
+                          -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let command = pry!(pry!(params.get()).get_command());
+        let command = if command == "" { None } else { Some(command) };
+
+        sry!(self.c.execute(
+            "UPDATE stores SET notify_command = ?1 WHERE id = ?2",
+            &[&command, &self.id]));
+        sry!(log::message(&self.c, log::Refers::to().store(self.id),
+                          &self.slug(),
+                          &match command {
+                              Some(command) =>
+                                  format!("Running {:?} when keys change",
+                                          command),
+                              None => "Not running a command when keys change"
+                                  .into(),
+                          }));
+        Promise::ok(())
+    }
+}
+
+/// Looks up a binding by label, returning its id and its key's id.
+///
+/// The label is normalized first.  See `normalize_labels` for
+/// bindings added before labels were normalized.
+fn lookup_binding(c: &Connection, store: ID, label: &str)
+                  -> rusqlite::Result<(ID, ID)> {
+    c.query_row(
+        "SELECT id, key FROM bindings WHERE store = ?1 AND label = ?2",
+        &[&store, &::normalize_label(label)], |row| (row.get(0), row.get(1)))
+}
+
+/// Looks up a binding in `store` by the keyid of one of its key's
+/// (sub)keys, returning its id and its key's id.
+fn lookup_binding_by_subkeyid(c: &Connection, store: ID, keyid: u64)
+                              -> rusqlite::Result<(ID, ID)> {
+    c.query_row(
+        "SELECT bindings.id, bindings.key FROM bindings
+         JOIN key_by_keyid on bindings.key = key_by_keyid.key
+         WHERE key_by_keyid.keyid = ?1 AND bindings.store = ?2",
+        &[&(keyid as i64), &store], |row| (row.get(0), row.get(1)))
+}
+
+/// A binding's id, label, and key fingerprint.
+type BindingRow = (ID, String, String);
+
+/// Groups the bindings of `store` by their normalized label.
+///
+/// The bindings are ordered by creation time, oldest first.
+fn bindings_by_normalized_label(c: &Connection, store: ID)
+                                -> Result<BTreeMap<String, Vec<BindingRow>>> {
+    let mut stmt = c.prepare(
+        "SELECT bindings.id, bindings.label, keys.fingerprint FROM bindings
+             JOIN keys ON bindings.key = keys.id
+             WHERE bindings.store = ?1
+             ORDER BY bindings.created, bindings.id")?;
+    let rows = stmt.query_map(&[&store],
+                              |row| (row.get(0), row.get(1), row.get(2)))?;
+
+    let mut groups = BTreeMap::new();
+    for row in rows {
+        let row: BindingRow = row?;
+        groups.entry(::normalize_label(&row.1))
+            .or_insert_with(Vec::new).push(row);
+    }
+    Ok(groups)
+}
+
+/// Merges the bindings of `store` whose labels normalize to `label`.
+///
+/// Returns the id of the surviving binding, and the number of
+/// bindings merged into it.  See `Store::merge_duplicates`.
+fn merge_duplicates(c: &Connection, store: ID, label: &str)
+                    -> Result<(ID, usize)> {
+    let label = ::normalize_label(label);
+    let group = bindings_by_normalized_label(c, store)?.remove(&label)
+        .ok_or(super::Error::NotFound)?;
+    if group.iter().any(|&(_, _, ref fp)| fp != &group[0].2) {
+        return Err(super::Error::Conflict.into());
+    }
+
+    let keep = group[0].0;
+    let ids = group.iter().map(|b| b.0.to_string())
+        .collect::<Vec<_>>().join(", ");
+    let others = group[1..].iter().map(|b| b.0.to_string())
+        .collect::<Vec<_>>().join(", ");
+
+    // The bindings' trust states, ignoring unknown.  If they
+    // disagree, the user has to sort it out.
+    let trust: Vec<i64> = {
+        let mut stmt = c.prepare(&format!(
+            "SELECT DISTINCT trust FROM bindings
+             WHERE id IN ({}) AND trust != ?1", ids))?;
+        let rows = stmt.query_map(&[&i64::from(Trust::Unknown)],
+                                  |row| row.get(0))?;
+        rows.collect::<::std::result::Result<Vec<_>, _>>()?
+    };
+    let trust = match trust.len() {
+        0 => i64::from(Trust::Unknown),
+        1 => trust[0],
+        _ => i64::from(Trust::TOFUConflict),
+    };
+
+    let (updated, encryption_count, encryption_first, encryption_last,
+         verification_count, verification_first, verification_last,
+         autocrypt_level)
+        : (Option<i64>, i64, Option<i64>, Option<i64>,
+           i64, Option<i64>, Option<i64>, i64) =
+        c.query_row(&format!(
+            "SELECT max(updated),
+                    sum(encryption_count), min(encryption_first),
+                    max(encryption_last),
+                    sum(verification_count), min(verification_first),
+                    max(verification_last),
+                    max(autocrypt_level)
+             FROM bindings WHERE id IN ({})", ids),
+                    &[], |row| (row.get(0), row.get(1), row.get(2), row.get(3),
+                                row.get(4), row.get(5), row.get(6),
+                                row.get(7)))?;
+
+    c.execute_batch("BEGIN;")?;
+    let r = (|| -> Result<()> {
+        c.execute(&format!(
+            "UPDATE usage_events SET binding = ?1 WHERE binding IN ({})",
+            others), &[&keep])?;
+        c.execute(&format!(
+            "UPDATE log SET binding = ?1 WHERE binding IN ({})",
+            others), &[&keep])?;
+        c.execute(&format!("DELETE FROM bindings WHERE id IN ({})", others),
+                  &[])?;
+        c.execute(
+            "UPDATE bindings SET label = ?2, updated = ?3,
+                 encryption_count = ?4, encryption_first = ?5,
+                 encryption_last = ?6,
+                 verification_count = ?7, verification_first = ?8,
+                 verification_last = ?9,
+                 autocrypt_level = ?10, trust = ?11
+             WHERE id = ?1",
+            &[&keep, &label, &updated,
+              &encryption_count, &encryption_first, &encryption_last,
+              &verification_count, &verification_first, &verification_last,
+              &autocrypt_level, &trust])?;
+        c.execute_batch("COMMIT;")?;
+        Ok(())
+    })();
+    if r.is_err() {
+        let _ = c.execute_batch("ROLLBACK;");
+    }
+    r?;
+
+    Ok((keep, group.len() - 1))
+}
+
+/// Normalizes the labels of bindings added before labels were
+/// normalized.
+///
+/// Bindings whose labels normalize to the same label are merged if
+/// they are bound to the same key, see `merge_duplicates`.
+/// Otherwise, the oldest binding gets the normalized label unless
+/// one of them already has it, and the others are left to the user,
+/// see `Store::duplicates`.
+fn normalize_labels(c: &Connection) -> Result<()> {
+    let stores: Vec<ID> = {
+        let mut stmt = c.prepare("SELECT id FROM stores")?;
+        let rows = stmt.query_map(&[], |row| row.get(0))?;
+        rows.collect::<::std::result::Result<Vec<_>, _>>()?
+    };
+
+    for store in stores {
+        for (label, group) in bindings_by_normalized_label(c, store)? {
+            if group.len() == 1 && group[0].1 == label {
+                continue;
+            }
+
+            if group.iter().all(|&(_, _, ref fp)| fp == &group[0].2) {
+                let (binding, merged) = merge_duplicates(c, store, &label)?;
+                log::message(c, log::Refers::to().store(store)
+                             .binding(binding),
+                             "server",
+                             &if merged > 0 {
+                                 format!("Merged {} duplicate bindings into {}",
+                                         merged, label)
+                             } else {
+                                 format!("Normalized label to {}", label)
+                             })?;
+            } else if group.iter().all(|&(_, ref l, _)| l != &label) {
+                c.execute("UPDATE bindings SET label = ?2 WHERE id = ?1",
+                          &[&group[0].0, &label])?;
+                log::message(c, log::Refers::to().store(store)
+                             .binding(group[0].0),
+                             "server",
+                             &format!("Normalized label to {}, other \
+                                       bindings conflict", label))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the merge strategy of the given store.
+fn merge_strategy(c: &Connection, store: ID) -> Result<MergeStrategy> {
+    let strategy: i64 = c.query_row(
+        "SELECT merge_strategy FROM stores WHERE id = ?1",
+        &[&store], |row| row.get(0))?;
+    match strategy {
+        0 => Ok(MergeStrategy::Strict),
+        1 => Ok(MergeStrategy::Rebind),
+        2 => Ok(MergeStrategy::PreferNewer),
+        _ => Err(super::Error::ProtocolError.into()),
+    }
+}
+
+/// Returns the keyserver that keys updated in the given store are
+/// published to, if any.
+fn publish_to(c: &Connection, store: ID) -> Result<Option<String>> {
+    Ok(c.query_row(
+        "SELECT publish_to FROM stores WHERE id = ?1",
+        &[&store], |row| row.get(0))?)
+}
+
+/// Returns the command run when a key bound in the given store
+/// changes, if any.
+fn notify_command(c: &Connection, store: ID) -> Result<Option<String>> {
+    Ok(c.query_row(
+        "SELECT notify_command FROM stores WHERE id = ?1",
+        &[&store], |row| row.get(0))?)
+}
+
+/// Runs the notify commands of the stores binding the given key.
+///
+/// This must be called after the key changed.  For every binding of
+/// the key in a store that has a notify command, the command is run
+/// using the shell.  The store's realm and name, the binding's label,
+/// and the key's fingerprint are passed in the environment.  The
+/// commands run asynchronously.  Failing to run a command is not an
+/// error, but is recorded in the log.
+fn notify_key_changed(c: &Rc<Connection>, key: ID) -> Result<()> {
+    let mut stmt = c.prepare(
+        "SELECT stores.id, stores.realm, stores.name, stores.notify_command,
+                bindings.id, bindings.label, keys.fingerprint
+             FROM bindings
+             JOIN stores ON stores.id = bindings.store
+             JOIN keys ON keys.id = bindings.key
+             WHERE bindings.key = ?1 AND stores.notify_command IS NOT NULL")?;
+    let rows = stmt.query_map(&[&key], |row| -> (ID, String, String, String,
+                                                  ID, String, String) {
+        (row.get(0), row.get(1), row.get(2), row.get(3), row.get(4),
+         row.get(5), row.get(6))
+    })?.collect::<::std::result::Result<Vec<_>, _>>()?;
+
+    for (store, realm, name, command, binding, label, fingerprint) in rows {
+        let refers = log::Refers::to().store(store).binding(binding).key(key);
+        let r = run_notify_command(&command, &[
+            ("SEQUOIA_STORE_REALM", &realm),
+            ("SEQUOIA_STORE_NAME", &name),
+            ("SEQUOIA_BINDING_LABEL", &label),
+            ("SEQUOIA_KEY_FINGERPRINT", &fingerprint),
+        ]);
+        match r {
+            Ok(()) =>
+                log::message(c, refers, &label,
+                             &format!("Ran {:?}", command)),
+            Err(e) =>
+                log::error(c, refers, &label,
+                           &format!("Running {:?} failed", command),
+                           &format!("{:?}", e)),
+        }?;
+    }
+    Ok(())
+}
+
+/// Runs `command` using the shell, without waiting for it.
+fn run_notify_command(command: &str, env: &[(&str, &str)]) -> Result<()> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null());
+    for &(key, value) in env {
+        shell.env(key, value);
+    }
+
+    let mut child = shell.spawn()?;
+    // Reap the child without blocking the server.
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Returns the network policy of the given store.
+fn network_policy(c: &Connection, store: ID) -> Result<core::NetworkPolicy> {
+    let policy: i64 = c.query_row(
+        "SELECT network_policy FROM stores WHERE id = ?1",
+        &[&store], |row| row.get(0))?;
+
+    // We cannot implement FromSql and friends for
+    // core::NetworkPolicy, hence we need to do it by foot.
+    if policy < 0 || policy > 3 {
+        return Err(super::Error::ProtocolError.into());
+    }
+    Ok(core::NetworkPolicy::from(policy as u8))
+}
+
+/// Returns the creation time of the current self-signature of
+/// `tpk`'s primary key.
+fn selfsig_time(tpk: &TPK) -> Option<Timespec> {
+    tpk.primary_key_signature()
+        .and_then(|sig| sig.signature_creation_time())
+        .map(|t| t.to_timespec())
 }
 
 struct BindingServer {
     c: Rc<Connection>,
     id: ID,
+    /// The prefix of the realms of the peer, see `realm_scope`.
+    scope: String,
 }
 
 impl BindingServer {
-    fn new(c: Rc<Connection>, id: ID) -> Self {
+    fn new(c: Rc<Connection>, id: ID, scope: &str) -> Self {
         BindingServer {
             c: c,
             id: id,
+            scope: scope.into(),
         }
     }
 
+    /// Returns a server for the bound key, restricted to the peer's
+    /// realms.
+    fn key_server(&mut self) -> Result<KeyServer> {
+        let id = self.key_id()?;
+        Ok(KeyServer::new(self.c.clone(), id, &self.scope))
+    }
+
     fn key_id(&mut self) -> Result<ID> {
         self.query("key").map(|id| id.into())
     }
 
+    fn store_id(&mut self) -> Result<ID> {
+        self.query("store").map(|id| id.into())
+    }
+
+    /// Publishes the given key, if the store is configured to.
+    ///
+    /// The key is sent to the store's keyserver, honoring the
+    /// store's network policy.  The attempt is recorded in the log.
+    /// Failing to publish the key is not an error.
+    fn publish(&mut self, key_id: ID, tpk: TPK) -> Promise<(), capnp::Error> {
+        let c = self.c.clone();
+        let slug = self.slug();
+        let store = match self.store_id() {
+            Ok(store) => store,
+            Err(_) => return Promise::ok(()),
+        };
+        let refers =
+            log::Refers::to().store(store).binding(self.id).key(key_id);
+        let uri = match publish_to(&c, store) {
+            Ok(Some(uri)) => uri,
+            _ => return Promise::ok(()),
+        };
+
+        let keyserver = network_policy(&c, store)
+            .and_then(|policy| core::Context::configure()
+                      .network_policy(policy).build())
+            .and_then(|ctx| net::async::KeyServer::new(&ctx, &uri));
+        let mut keyserver = match keyserver {
+            Ok(keyserver) => keyserver,
+            Err(e) => {
+                let _ = log::error(&c, refers, &slug,
+                                   &format!("Publishing to {} failed", uri),
+                                   &format!("{:?}", e));
+                return Promise::ok(());
+            },
+        };
+
+        Promise::from_future(
+            keyserver.send(&tpk)
+                .then(move |r| -> ::std::result::Result<(), capnp::Error> {
+                    let _ = match r {
+                        Ok(()) =>
+                            log::message(&c, refers, &slug,
+                                         &format!("Published to {}", uri)),
+                        Err(e) =>
+                            log::error(&c, refers, &slug,
+                                       &format!("Publishing to {} failed", uri),
+                                       &format!("{:?}", e)),
+                    };
+                    Ok(())
+                }))
+    }
+
 
     /// Looks up a binding, creating a binding if necessary.
     ///
-    /// On success, the id of the binding and the key is returned, and
-    /// whether or not the entry was just created.
+    /// `label` must be normalized.  On success, the id of the binding
+    /// and the key is returned, and whether or not the entry was just
+    /// created.
     fn lookup_or_create(c: &Connection, store: ID, label: &str, fp: &Fingerprint)
                         -> Result<(ID, ID, bool)> {
         let key_id = KeyServer::lookup_or_create(c, fp)?;
-        if let Ok((binding, key)) = c.query_row(
-            "SELECT id, key FROM bindings WHERE store = ?1 AND label = ?2",
-            &[&store, &label], |row| -> (ID, ID) {(row.get(0), row.get(1))}) {
+        if let Ok((binding, key)) = lookup_binding(c, store, label) {
             if key == key_id {
                 Ok((binding, key_id, false))
             } else {
@@ -469,9 +1425,7 @@ impl BindingServer {
                 Err(rusqlite::Error::SqliteFailure(f, _)) => match f.code {
                     // We lost.  Retry the lookup.
                     rusqlite::ErrorCode::ConstraintViolation => {
-                        let (binding, key): (ID, ID) = c.query_row(
-                            "SELECT id, key FROM bindings WHERE store = ?1 AND label = ?2",
-                            &[&store, &label], |row| (row.get(0), row.get(1)))?;
+                        let (binding, key) = lookup_binding(c, store, label)?;
                         if key == key_id {
                             Ok((binding, key_id, false))
                         } else {
@@ -528,11 +1482,10 @@ impl node::binding::Server for BindingServer {
            mut results: node::binding::KeyResults)
            -> Promise<(), capnp::Error> {
         bind_results!(results);
-        let key = sry!(self.key_id());
+        let key = sry!(self.key_server());
 
         pry!(pry!(results.get().get_result()).set_ok(
-            node::key::ToClient::new(
-                KeyServer::new(self.c.clone(), key)).into_client::<capnp_rpc::Server>()));
+            node::key::ToClient::new(key).into_client::<capnp_rpc::Server>()));
         Promise::ok(())
     }
 
@@ -542,12 +1495,20 @@ impl node::binding::Server for BindingServer {
               -> Promise<(), capnp::Error> {
         bind_results!(results);
         let force = pry!(params.get()).get_force();
+        let key_id = sry!(self.key_id());
+
+        // Importing a key we already have is a no-op.
+        if let Some(current) = sry!(KeyServer::unchanged_by(
+            &self.c, key_id, &pry!(pry!(params.get()).get_key())))
+        {
+            pry!(pry!(results.get().get_result()).set_ok(&current[..]));
+            return Promise::ok(());
+        }
 
         // This is the key to import.
         let mut new = sry!(TPK::from_bytes(&pry!(pry!(params.get()).get_key())));
 
         // Check in the database for the current key.
-        let key_id = sry!(self.key_id());
         let (fingerprint, key): (String, Option<Vec<u8>>)
             = sry!(self.c.query_row(
                 "SELECT fingerprint, key FROM keys WHERE id = ?1",
@@ -555,8 +1516,8 @@ impl node::binding::Server for BindingServer {
                 |row| (row.get(0), row.get_checked(1).ok())));
 
         // If we found one, convert it to TPK.
-        let current = if let Some(current) = key {
-            let current = sry!(TPK::from_bytes(&current));
+        let current = if let Some(ref current) = key {
+            let current = sry!(TPK::from_bytes(current));
             if current.fingerprint().to_hex() != fingerprint {
                 // Inconsistent database.
                 fail!(node::Error::SystemError);
@@ -568,7 +1529,22 @@ impl node::binding::Server for BindingServer {
 
         // Check for conflicts.
         if new.fingerprint().to_hex() != fingerprint {
-            if force {
+            let store: ID = sry!(self.c.query_row(
+                "SELECT store FROM bindings WHERE id = ?1",
+                &[&self.id], |row| row.get(0)));
+            let rebind = force || match sry!(merge_strategy(&self.c, store)) {
+                MergeStrategy::Strict => false,
+                MergeStrategy::Rebind => true,
+                MergeStrategy::PreferNewer =>
+                    match (current.as_ref().and_then(selfsig_time),
+                           selfsig_time(&new)) {
+                        (Some(current), Some(new)) => new > current,
+                        (None, Some(_)) => true,
+                        (_, None) => false,
+                    },
+            };
+
+            if rebind {
                 // Update binding, and retry.
                 let key_id =
                     sry!(KeyServer::lookup_or_create(
@@ -589,11 +1565,16 @@ impl node::binding::Server for BindingServer {
         let mut blob = vec![];
         sry!(new.serialize(&mut blob));
 
-        sry!(self.c.execute("UPDATE keys SET key = ?1 WHERE id = ?2",
-                            &[&blob, &key_id]));
-        sry!(KeyServer::reindex_subkeys(&self.c, key_id, &new));
+        sry!(KeyServer::store(&self.c, key_id, &blob, &new));
 
         pry!(pry!(results.get().get_result()).set_ok(&blob[..]));
+
+        // Notify interested parties, and push the updated key to the
+        // store's keyserver, if any.
+        if key.as_ref() != Some(&blob) {
+            let _ = notify_key_changed(&self.c, key_id);
+            return self.publish(key_id, new);
+        }
         Promise::ok(())
     }
 
@@ -629,6 +1610,7 @@ impl node::binding::Server for BindingServer {
                            encryption_last = ?2
                        WHERE id = ?1",
                       &[&key, &now]));
+        sry!(record_usage(&self.c, UsageEvent::Encryption, self.id, key));
 
         sry!(self.query_stats( pry!(results.get().get_result()).init_ok()));
         Promise::ok(())
@@ -656,6 +1638,7 @@ impl node::binding::Server for BindingServer {
                            verification_last = ?2
                        WHERE id = ?1",
                       &[&key, &now]));
+        sry!(record_usage(&self.c, UsageEvent::Verification, self.id, key));
 
         sry!(self.query_stats( pry!(results.get().get_result()).init_ok()));
         Promise::ok(())
@@ -666,7 +1649,8 @@ impl node::binding::Server for BindingServer {
            mut results: node::binding::LogResults)
            -> Promise<(), capnp::Error> {
         bind_results!(results);
-        let iter = log::IterServer::new(self.c.clone(), log::Selector::Binding(self.id));
+        let iter = log::IterServer::new(self.c.clone(), &self.scope,
+                                        log::Selector::Binding(self.id));
         pry!(pry!(results.get().get_result()).set_ok(
             node::log_iter::ToClient::new(iter).into_client::<capnp_rpc::Server>()));
         Promise::ok(())
@@ -686,21 +1670,119 @@ impl node::binding::Server for BindingServer {
         pry!(pry!(results.get().get_result()).set_ok(label.as_str()));
         Promise::ok(())
     }
+
+    fn usage(&mut self,
+             params: node::binding::UsageParams,
+             mut results: node::binding::UsageResults)
+             -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let weeks = pry!(params.get()).get_weeks();
+        sry!(query_usage(&self.c, "binding", self.id, weeks,
+                         pry!(results.get().get_result()).init_ok()));
+        Promise::ok(())
+    }
+
+    fn trust(&mut self,
+             _: node::binding::TrustParams,
+             mut results: node::binding::TrustResults)
+             -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let trust = sry!(self.query("trust"));
+        let trust = sry!(Trust::from_i64(trust)
+                         .ok_or(super::Error::ProtocolError));
+        pry!(results.get().get_result()).init_ok().set_trust(trust.into());
+        Promise::ok(())
+    }
+
+    fn set_trust(&mut self,
+                 params: node::binding::SetTrustParams,
+                 mut results: node::binding::SetTrustResults)
+                 -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let trust: Trust = pry!(pry!(params.get()).get_trust()).into();
+        sry!(self.c.execute(
+            "UPDATE bindings SET trust = ?1 WHERE id = ?2",
+            &[&i64::from(trust), &self.id]));
+        sry!(log::message(&self.c, log::Refers::to().binding(self.id),
+                          &self.slug(), &format!("Trust set to {:?}", trust)));
+        Promise::ok(())
+    }
+
+    fn autocrypt_level(&mut self,
+                       _: node::binding::AutocryptLevelParams,
+                       mut results: node::binding::AutocryptLevelResults)
+                       -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let level = sry!(self.query("autocrypt_level"));
+        let level = sry!(AutocryptLevel::from_i64(level)
+                         .ok_or(super::Error::ProtocolError));
+        pry!(results.get().get_result()).init_ok().set_level(level.into());
+        Promise::ok(())
+    }
+
+    fn set_autocrypt_level(&mut self,
+                           params: node::binding::SetAutocryptLevelParams,
+                           mut results: node::binding::SetAutocryptLevelResults)
+                           -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let level: AutocryptLevel =
+            pry!(pry!(params.get()).get_level()).into();
+        sry!(self.c.execute(
+            "UPDATE bindings SET autocrypt_level = ?1 WHERE id = ?2",
+            &[&i64::from(level), &self.id]));
+        sry!(log::message(&self.c, log::Refers::to().binding(self.id),
+                          &self.slug(),
+                          &format!("Autocrypt level set to {:?}", level)));
+        Promise::ok(())
+    }
+
+    fn status(&mut self,
+              _: node::binding::StatusParams,
+              mut results: node::binding::StatusResults)
+              -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let key = sry!(self.key_id());
+        sry!(key_status(&self.c, key,
+                        pry!(results.get().get_result()).init_ok()));
+        Promise::ok(())
+    }
 }
 
 struct KeyServer {
     c: Rc<Connection>,
     id: ID,
+    /// The prefix of the realms of the peer, see `realm_scope`.
+    scope: String,
 }
 
 impl KeyServer {
-    fn new(c: Rc<Connection>, id: ID) -> Self {
+    /// Returns a server for the key restricted to the realms with the
+    /// given prefix.
+    ///
+    /// Keys are shared by all peers.  Keys looked up in the key pool
+    /// on behalf of a user of a system daemon may only be scheduled
+    /// for an update if they are bound in one of the user's realms.
+    fn new(c: Rc<Connection>, id: ID, scope: &str) -> Self {
         KeyServer {
             c: c,
             id: id,
+            scope: scope.into(),
         }
     }
 
+    /// Returns whether the key is bound in one of the peer's realms.
+    fn in_scope(&self) -> Result<bool> {
+        if self.scope.is_empty() {
+            return Ok(true);
+        }
+        let n: i64 = self.c.query_row(
+            "SELECT count(*) FROM bindings
+                 JOIN stores on stores.id = bindings.store
+                 WHERE bindings.key = ?1 AND stores.realm LIKE ?2",
+            &[&self.id, &format!("{}%", self.scope)], |row| row.get(0))?;
+        Ok(n > 0)
+    }
+
     /// Looks up a key by fingerprint.
     ///
     /// On success, the id of the key is returned.
@@ -766,8 +1848,8 @@ impl KeyServer {
                 |row| (row.get(0), row.get_checked(1).ok()))?;
 
         // If there was a key stored there, merge it.
-        if let Some(current) = key {
-            let current = TPK::from_bytes(&current)?;
+        if let Some(ref current) = key {
+            let current = TPK::from_bytes(current)?;
 
             if current.fingerprint().to_hex() != fingerprint {
                 // Inconsistent database.
@@ -778,28 +1860,147 @@ impl KeyServer {
                 return Err(node::Error::Conflict.into());
             }
 
-            new = current.merge(new)?;
+            new = current.clone().merge(new)?;
+
+            // Record what changed.
+            let diff = current.diff(&new)?;
+            if ! diff.is_empty() {
+                log::message(&self.c, log::Refers::to().key(self.id),
+                             &self.slug(), &format!("Merged: {}", diff))?;
+            }
         }
 
         // Write key back to the database.
         let mut blob = vec![];
         new.serialize(&mut blob)?;
 
-        self.c.execute("UPDATE keys SET key = ?1 WHERE id = ?2",
-                       &[&blob, &self.id])?;
-        KeyServer::reindex_subkeys(&self.c, self.id, &new)?;
+        KeyServer::store(&self.c, self.id, &blob, &new)?;
+
+        if key.as_ref() != Some(&blob) {
+            // Failing to notify is not an error.
+            let _ = notify_key_changed(&self.c, self.id);
+        }
 
         Ok(blob)
     }
 
-    /// Keeps the mapping of (sub)KeyIDs to keys up-to-date.
-    fn reindex_subkeys(c: &Connection, key_id: ID, tpk: &TPK) -> Result<()> {
-        for (_, _, key) in tpk.keys_all() {
-            let keyid = key.keyid().as_u64()
-                .expect("computed keyid is valid");
+    /// Merges an update fetched from the network into this key.
+    ///
+    /// Unlike `merge`, updates that look suspicious are not merged,
+    /// but put into quarantine until they are reviewed.
+    fn refresh(&self, update: TPK) -> Result<Refresh> {
+        let mut blob = vec![];
+        update.serialize(&mut blob)?;
 
-            let r = c.execute(
-                "INSERT INTO key_by_keyid (keyid, key) VALUES (?1, ?2)",
+        // Most of the time, nothing changed.
+        if let Some(current) = Self::unchanged_by(&self.c, self.id, &blob)? {
+            return Ok(Refresh::Merged(current));
+        }
+
+        let current: Option<Vec<u8>> = self.c.query_row(
+            "SELECT key FROM keys WHERE id = ?1",
+            &[&self.id], |row| row.get_checked(0).ok())?;
+
+        if let Some(current) = current {
+            let current = TPK::from_bytes(&current)?;
+            if let Some(reason) = suspicious(&current, &update)? {
+                self.c.execute(
+                    "INSERT INTO quarantine (key, created, tpk, reason)
+                     VALUES (?1, ?2, ?3, ?4)",
+                    &[&self.id, &Timestamp::now(), &blob, &reason])?;
+                return Ok(Refresh::Quarantined(reason));
+            }
+        }
+
+        Ok(Refresh::Merged(self.merge(update)?))
+    }
+
+    /// Stores `blob`, the serialized form of `tpk`, as the key
+    /// `key_id`.
+    fn store(c: &Connection, key_id: ID, blob: &[u8], tpk: &TPK)
+             -> Result<()> {
+        c.execute("UPDATE keys SET key = ?1, key_hash = ?2 WHERE id = ?3",
+                  &[&blob, &content_hash(blob)?, &key_id])?;
+        KeyServer::reindex_subkeys(c, key_id, tpk)
+    }
+
+    /// Checks whether importing `blob` leaves the key `key_id`
+    /// unchanged.
+    ///
+    /// This is the case if `blob` is byte-identical to the stored
+    /// key, or if all of its packets are already part of the stored
+    /// key.  Checking this is much cheaper than parsing, merging,
+    /// and re-serializing the key.
+    ///
+    /// If so, the stored key is returned.
+    fn unchanged_by(c: &Connection, key_id: ID, blob: &[u8])
+                    -> Result<Option<Vec<u8>>> {
+        let (current, hash): (Option<Vec<u8>>, Option<String>) = c.query_row(
+            "SELECT key, key_hash FROM keys WHERE id = ?1",
+            &[&key_id],
+            |row| (row.get_checked(0).ok(), row.get_checked(1).ok()))?;
+        let current = match current {
+            Some(current) => current,
+            None => return Ok(None),
+        };
+
+        // Keys stored by older versions have no hash.
+        let hash = match hash {
+            Some(hash) => hash,
+            None => content_hash(&current)?,
+        };
+        if content_hash(blob)? == hash {
+            return Ok(Some(current));
+        }
+
+        // Compare the packets.  If anything goes wrong, we let the
+        // caller do a proper merge.
+        let packets = |blob: &[u8]| -> Option<Vec<Vec<u8>>> {
+            PacketPile::from_bytes(blob).ok()?.into_children()
+                .map(|p| p.to_vec().ok())
+                .collect()
+        };
+        let known: HashSet<Vec<u8>> = match packets(&current) {
+            Some(p) => p.into_iter().collect(),
+            None => return Ok(None),
+        };
+        match packets(blob) {
+            Some(ref p) if ! p.is_empty()
+                && p.iter().all(|p| known.contains(p)) =>
+                Ok(Some(current)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Looks up the key that `blob` would be imported into, if
+    /// importing it leaves the key unchanged.
+    ///
+    /// See `unchanged_by`.
+    fn lookup_unchanged_by(c: &Connection, blob: &[u8]) -> Result<Option<ID>> {
+        // We only need the fingerprint of the primary key.
+        let fp = match PacketParser::from_bytes(blob) {
+            Ok(PacketParserResult::Some(ref pp)) => match pp.packet {
+                Packet::PublicKey(ref k) | Packet::SecretKey(ref k) =>
+                    k.fingerprint(),
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+
+        let key_id = match KeyServer::lookup(c, &fp) {
+            Ok(key_id) => key_id,
+            Err(_) => return Ok(None),
+        };
+        Ok(KeyServer::unchanged_by(c, key_id, blob)?.map(|_| key_id))
+    }
+
+    /// Keeps the mapping of (sub)KeyIDs to keys up-to-date.
+    fn reindex_subkeys(c: &Connection, key_id: ID, tpk: &TPK) -> Result<()> {
+        for (_, _, key) in tpk.keys_all() {
+            let keyid = key.keyid().as_u64()?;
+
+            let r = c.execute(
+                "INSERT INTO key_by_keyid (keyid, key) VALUES (?1, ?2)",
                 &[&(keyid as i64), &key_id]);
 
             // The mapping might already be present.  This is not an error.
@@ -873,11 +2074,16 @@ impl KeyServer {
     }
 
     /// Helper for `update`.
+    ///
+    /// If `uri` is given, that keyserver is tried first, falling back
+    /// to the SKS pool, unless it does not comply with the network
+    /// policy.
     fn update_helper(c: &Rc<Connection>,
-                     network_policy: core::NetworkPolicy)
+                     network_policy: core::NetworkPolicy,
+                     uri: Option<&str>)
                      -> Result<(KeyServer,
                                 openpgp::KeyID,
-                                net::async::KeyServer)> {
+                                net::async::FailoverKeyServer)> {
         assert!(network_policy != core::NetworkPolicy::Offline);
         let network_policy_u8 = u8::from(&network_policy);
 
@@ -896,19 +2102,39 @@ impl KeyServer {
 
         let ctx = core::Context::configure()
             .network_policy(network_policy).build()?;
-        let keyserver = net::async::KeyServer::sks_pool(&ctx)?;
+        let mut keyservers = Vec::new();
+        if let Some(uri) = uri {
+            if let Ok(keyserver) = net::async::KeyServer::new(&ctx, uri) {
+                keyservers.push(keyserver);
+            }
+        }
+        keyservers.push(net::async::KeyServer::sks_pool(&ctx)?);
+        let keyserver = net::async::FailoverKeyServer::new(keyservers)?;
+        let health = load_keyserver_health(c)?;
+        for h in keyserver.health() {
+            h.restore(health.iter().cloned());
+        }
+        keyserver.failover().restore(load_keyserver_failover(c)?);
 
-        Ok((KeyServer::new(c.clone(), id),
+        // Housekeeping acts on behalf of the owner.
+        Ok((KeyServer::new(c.clone(), id, &realm_scope(ipc::Peer::Owner)),
             fingerprint.to_keyid(),
             keyserver))
     }
 
     /// Updates the key that was least recently updated.
     fn update(c: &Rc<Connection>,
-              network_policy: core::NetworkPolicy)
+              network_policy: core::NetworkPolicy,
+              uri: Option<&str>)
               -> Box<Future<Item=Duration, Error=failure::Error> + 'static> {
+        match refresh_paused(c) {
+            Ok(false) => (),
+            Ok(true) => return Box::new(future::ok(min_sleep_time())),
+            Err(e) => return Box::new(future::err(e.into())),
+        }
+
         let (key, id, mut keyserver)
-            = match Self::update_helper(c, network_policy) {
+            = match Self::update_helper(c, network_policy, uri) {
             Ok((key, id, keyserver)) => (key, id, keyserver),
             Err(e) => return Box::new(future::err(e.into())),
         };
@@ -919,23 +2145,65 @@ impl KeyServer {
             .unwrap_or(now + min_sleep_time());
 
         if at <= now {
+            let health = keyserver.health();
+            let failover = keyserver.failover();
+            let primary = keyserver.uris()[0].clone();
+            let decision = failover.get(&primary);
             Box::new(
                 keyserver.get(&id)
                     .then(move |tpk| {
+                        for h in health {
+                            store_keyserver_health(&c, &h.stats())
+                                .unwrap_or(());
+                        }
+                        store_keyserver_failover(&c, &failover.decisions())
+                            .unwrap_or(());
+                        match failover.get(&primary) {
+                            Some(ref d) if Some(d) != decision.as_ref() => {
+                                let _ = log::message(
+                                    &c, log::Refers::to(), "housekeeping",
+                                    &format!("{} is failing, using {}",
+                                             primary, d.endpoint));
+                            },
+                            _ => (),
+                        }
+
                         let next = Self::need_update(&c, network_policy)
                             .map(|c| refresh_interval() / c)
                             .unwrap_or(min_sleep_time());
 
-                        if let Err(e) = tpk.map(|t| key.merge(t)) {
-                            key.error("Update unsuccessful",
-                                      &format!("{:?}", e), next / 2)
-                                .unwrap_or(());
-                        } else {
-                            key.success("Update successful", next)
-                                .unwrap_or(());
+                        match tpk.and_then(|t| key.refresh(t)) {
+                            Err(e) =>
+                                key.error("Update unsuccessful",
+                                          &format!("{:?}", e), next / 2)
+                                .unwrap_or(()),
+                            Ok(Refresh::Quarantined(reason)) =>
+                                key.success(
+                                    &format!("Update quarantined: {}", reason),
+                                    next)
+                                .unwrap_or(()),
+                            Ok(Refresh::Merged(ref blob)) if TPK::from_bytes(blob)
+                                .map(|t| needs_attention(&t))
+                                .unwrap_or(false) =>
+                                // Refresh the key again after a
+                                // quarter of the refresh interval.
+                                key.success(
+                                    "Update successful, key needs attention",
+                                    attention_interval())
+                                .unwrap_or(()),
+                            Ok(Refresh::Merged(_)) =>
+                                key.success("Update successful", next)
+                                .unwrap_or(()),
                         }
 
-                        future::ok(next)
+                        // Do not let keys that the user wants to be
+                        // updated now wait for the next regular
+                        // round.
+                        match Self::next_update_at(&c, network_policy) {
+                            Some(ref t) if t.unix() == 0 =>
+                                future::ok(min_sleep_time()),
+                            _ => future::ok(next),
+                        }
                     }))
         } else {
             assert!(at > now);
@@ -944,15 +2212,19 @@ impl KeyServer {
     }
 
     /// Starts the periodic housekeeping.
-    fn start_housekeeping(c: Rc<Connection>, handle: Handle) -> Result<()> {
+    ///
+    /// Keys are fetched from the keyserver `uri`, falling back to the
+    /// SKS pool, or from the SKS pool if none is given.  If `uri`
+    /// fails, we stick to the SKS pool for a while.
+    fn start_housekeeping(c: Rc<Connection>, uri: Option<String>,
+                          handle: Handle) -> Result<()> {
         let h0 = handle.clone();
 
         let forever = loop_fn(0, move |_| {
-            // For now, we only update keys with this network policy.
-            let network_policy = core::NetworkPolicy::Encrypted;
+            let network_policy = refresh_network_policy();
 
             let h1 = h0.clone();
-            Self::update(&c, network_policy)
+            Self::update(&c, network_policy, uri.as_ref().map(|u| u.as_str()))
                 .then(move |d| {
                     let d = d.unwrap_or(min_sleep_time());
                      Timeout::new(
@@ -974,6 +2246,14 @@ impl KeyServer {
     }
 }
 
+/// The outcome of `KeyServer::refresh`.
+enum Refresh {
+    /// The update has been merged, this is the resulting key.
+    Merged(Vec<u8>),
+    /// The update has been quarantined for the given reason.
+    Quarantined(String),
+}
+
 impl Query for KeyServer {
     fn table_name() -> &'static str {
         "keys"
@@ -993,7 +2273,9 @@ impl Query for KeyServer {
             &[&self.id], |row| -> String { row.get(0) })
             .ok()
             .and_then(|fp| Fingerprint::from_hex(&fp).ok())
-            .map(|fp| fp.to_keyid().to_string())
+            // Honor the identifier format, so that slugs match the
+            // output of other tools.
+            .map(|fp| format!("{}", fp.to_keyid()))
             .unwrap_or(
                 format!("{}::{}", Self::table_name(), self.id())
             )
@@ -1006,7 +2288,20 @@ impl node::key::Server for KeyServer {
              mut results: node::key::StatsResults)
              -> Promise<(), capnp::Error> {
         bind_results!(results);
-        sry!(self.query_stats( pry!(results.get().get_result()).init_ok()));
+        let mut stats = pry!(results.get().get_result()).init_ok();
+        sry!(self.query_stats(stats.reborrow()));
+
+        let blob: Option<Vec<u8>> = sry!(
+            self.c.query_row(
+                "SELECT key FROM keys WHERE id = ?1",
+                &[&self.id],
+                |row| row.get(0)));
+        if let Some(blob) = blob {
+            let tpk = sry!(TPK::from_bytes(&blob));
+            if let Some(t) = next_expiration(&tpk) {
+                stats.set_expiry(t.sec);
+            }
+        }
         Promise::ok(())
     }
 
@@ -1029,8 +2324,12 @@ impl node::key::Server for KeyServer {
               mut results: node::key::ImportResults)
               -> Promise<(), capnp::Error> {
         bind_results!(results);
-        let new = sry!(TPK::from_bytes(&pry!(pry!(params.get()).get_key())));
-        let blob = sry!(self.merge(new));
+        let blob = pry!(pry!(params.get()).get_key());
+        let blob = match sry!(Self::unchanged_by(&self.c, self.id, blob)) {
+            // Importing a key we already have is a no-op.
+            Some(current) => current,
+            None => sry!(TPK::from_bytes(blob).and_then(|t| self.merge(t))),
+        };
         pry!(pry!(results.get().get_result()).set_ok(&blob[..]));
         Promise::ok(())
     }
@@ -1040,11 +2339,382 @@ impl node::key::Server for KeyServer {
            mut results: node::key::LogResults)
            -> Promise<(), capnp::Error> {
         bind_results!(results);
-        let iter = log::IterServer::new(self.c.clone(), log::Selector::Key(self.id));
+        let iter = log::IterServer::new(self.c.clone(), &self.scope,
+                                        log::Selector::Key(self.id));
         pry!(pry!(results.get().get_result()).set_ok(
             node::log_iter::ToClient::new(iter).into_client::<capnp_rpc::Server>()));
         Promise::ok(())
     }
+
+    fn usage(&mut self,
+             params: node::key::UsageParams,
+             mut results: node::key::UsageResults)
+             -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let weeks = pry!(params.get()).get_weeks();
+        sry!(query_usage(&self.c, "key", self.id, weeks,
+                         pry!(results.get().get_result()).init_ok()));
+        Promise::ok(())
+    }
+
+    fn status(&mut self,
+              _: node::key::StatusParams,
+              mut results: node::key::StatusResults)
+              -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        sry!(key_status(&self.c, self.id,
+                        pry!(results.get().get_result()).init_ok()));
+        Promise::ok(())
+    }
+
+    fn quarantine(&mut self,
+                  _: node::key::QuarantineParams,
+                  mut results: node::key::QuarantineResults)
+                  -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let mut stmt = sry!(self.c.prepare(
+            "SELECT id, created, tpk, reason FROM quarantine
+                 WHERE key = ?1 ORDER BY id"));
+        let rows = sry!(stmt.query_map(
+            &[&self.id],
+            |row| -> (i64, Timestamp, Vec<u8>, String) {
+                (row.get(0), row.get(1), row.get(2), row.get(3))
+            }));
+        let updates = sry!(rows.collect::<rusqlite::Result<Vec<_>>>());
+
+        let mut list = pry!(results.get().get_result()).init_ok()
+            .init_updates(updates.len() as u32);
+        for (i, &(id, ref created, ref tpk, ref reason))
+            in updates.iter().enumerate()
+        {
+            let mut u = list.reborrow().get(i as u32);
+            u.set_id(id);
+            u.set_created(created.unix());
+            u.set_key(tpk);
+            u.set_reason(reason);
+        }
+        Promise::ok(())
+    }
+
+    fn accept_quarantined(&mut self,
+                          params: node::key::AcceptQuarantinedParams,
+                          mut results: node::key::AcceptQuarantinedResults)
+                          -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let id: ID = pry!(params.get()).get_id().into();
+        let (tpk, reason): (Vec<u8>, String) = sry!(
+            self.c.query_row(
+                "SELECT tpk, reason FROM quarantine WHERE id = ?1 AND key = ?2",
+                &[&id, &self.id], |row| (row.get(0), row.get(1))));
+        let blob = sry!(TPK::from_bytes(&tpk).and_then(|t| self.merge(t)));
+        sry!(self.c.execute("DELETE FROM quarantine WHERE id = ?1",
+                            &[&id]));
+        sry!(log::message(&self.c, log::Refers::to().key(self.id),
+                          &self.slug(),
+                          &format!("Accepted quarantined update: {}",
+                                   reason)));
+        pry!(pry!(results.get().get_result()).set_ok(&blob[..]));
+        Promise::ok(())
+    }
+
+    fn reject_quarantined(&mut self,
+                          params: node::key::RejectQuarantinedParams,
+                          mut results: node::key::RejectQuarantinedResults)
+                          -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let id: ID = pry!(params.get()).get_id().into();
+        let reason: String = sry!(
+            self.c.query_row(
+                "SELECT reason FROM quarantine WHERE id = ?1 AND key = ?2",
+                &[&id, &self.id], |row| row.get(0)));
+        sry!(self.c.execute("DELETE FROM quarantine WHERE id = ?1",
+                            &[&id]));
+        sry!(log::message(&self.c, log::Refers::to().key(self.id),
+                          &self.slug(),
+                          &format!("Rejected quarantined update: {}",
+                                   reason)));
+        Promise::ok(())
+    }
+
+    fn refresh(&mut self,
+               _: node::key::RefreshParams,
+               mut results: node::key::RefreshResults)
+               -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        if ! sry!(self.in_scope()) {
+            fail!(node::Error::PermissionDenied);
+        }
+        // Keys are updated in the order of their `update_at` column,
+        // see `KeyServer::update`.
+        sry!(self.c.execute("UPDATE keys SET update_at = 0 WHERE id = ?1",
+                            &[&self.id]));
+        sry!(log::message(&self.c, log::Refers::to().key(self.id),
+                          &self.slug(), "Scheduled for an update"));
+        Promise::ok(())
+    }
+}
+
+/// Returns why `update` should not be merged into `current`, if it
+/// looks suspicious.
+///
+/// Updates are suspicious if they make the key huge, flood it with
+/// third-party certifications, or bind components using weaker
+/// algorithms than before.
+fn suspicious(current: &TPK, update: &TPK) -> Result<Option<String>> {
+    if current.fingerprint() != update.fingerprint() {
+        // This is a conflict, let `merge` report it.
+        return Ok(None);
+    }
+    let merged = current.clone().merge(update.clone())?;
+
+    let mut blob = vec![];
+    merged.serialize(&mut blob)?;
+    if blob.len() > QUARANTINE_MAX_SIZE {
+        return Ok(Some(format!("Key would grow to {} bytes", blob.len())));
+    }
+
+    let certifications = |tpk: &TPK| -> usize {
+        tpk.userids().map(|b| b.certifications().len()).sum::<usize>()
+            + tpk.user_attributes().map(|b| b.certifications().len())
+            .sum::<usize>()
+    };
+    let added = certifications(&merged)
+        .saturating_sub(certifications(current));
+    if added > QUARANTINE_MAX_CERTIFICATIONS {
+        return Ok(Some(format!("Update adds {} third-party certifications",
+                               added)));
+    }
+
+    let policy = LintPolicy::default();
+    let weak = |l: &Lint| match l {
+        Lint::WeakHashAlgorithm { .. } | Lint::WeakKey { .. } => true,
+        _ => false,
+    };
+    let before = current.lint(&policy);
+    let downgrades = merged.lint(&policy).into_iter()
+        .filter(|l| weak(l) && ! before.contains(l))
+        .map(|l| l.to_string())
+        .collect::<Vec<_>>();
+    if ! downgrades.is_empty() {
+        return Ok(Some(format!("Algorithm downgrade: {}",
+                               downgrades.join(", "))));
+    }
+
+    Ok(None)
+}
+
+/// Computes the validity and freshness of the given key.
+fn key_status(c: &Connection, key: ID, mut status: node::key_status::Builder)
+              -> Result<()> {
+    let (blob, created, updated)
+        : (Option<Vec<u8>>, Timestamp, Option<Timestamp>)
+        = c.query_row(
+            "SELECT key, created, updated FROM keys WHERE id = ?1",
+            &[&key], |row| (row.get(0), row.get(1), row.get(2)))?;
+
+    if let Some(blob) = blob {
+        let tpk = TPK::from_bytes(&blob)?;
+        status.set_expired(tpk.expired());
+        status.set_revoked(match tpk.revoked(None) {
+            RevocationStatus::Revoked(_) => true,
+            _ => false,
+        });
+    } else {
+        status.set_missing(true);
+    }
+
+    status.set_stale(
+        Timestamp::now() - updated.unwrap_or(created) > stale_interval());
+    if let Some(updated) = updated {
+        status.set_updated(updated.unix());
+    }
+    Ok(())
+}
+
+/// Returns when the key or one of its subkeys expires next.
+///
+/// Keys that already expired and revoked subkeys are not considered.
+fn next_expiration(tpk: &TPK) -> Option<Timespec> {
+    let now = time::now_utc().to_timespec();
+    let expiration = |key: &openpgp::packet::Key,
+                      sig: Option<&openpgp::packet::Signature>| {
+        sig.and_then(|s| s.key_expiration_time())
+            // A zero expiration time means the key does not expire.
+            .filter(|d| *d > Duration::zero())
+            .map(|d| key.creation_time().to_timespec() + d)
+    };
+
+    iter::once(expiration(tpk.primary(), tpk.primary_key_signature()))
+        .chain(tpk.subkeys()
+               .filter(|skb| skb.revoked(None)
+                       == RevocationStatus::NotAsFarAsWeKnow)
+               .map(|skb| expiration(skb.subkey(), skb.binding_signature())))
+        .filter_map(|t| t)
+        .filter(|t| *t > now)
+        .min()
+}
+
+/// Returns whether the key should be refreshed sooner than usual.
+///
+/// This is the case if the key or one of its subkeys expires soon,
+/// because the owner is likely to extend the expiration time, or if
+/// the key carries revocation certificates, which are often
+/// accompanied by replacement subkeys.
+fn needs_attention(tpk: &TPK) -> bool {
+    let now = time::now_utc().to_timespec();
+    next_expiration(tpk).map(|t| t - now < expiry_horizon()).unwrap_or(false)
+        || tpk.revocation_status() != RevocationStatus::NotAsFarAsWeKnow
+        || tpk.subkeys().any(|skb| ! skb.self_revocations().is_empty())
+}
+
+/// Kinds of recorded key usage.
+#[derive(Clone, Copy)]
+enum UsageEvent {
+    Encryption = 0,
+    Verification = 1,
+}
+
+/// Records that the key bound by `binding` has been used.
+fn record_usage(c: &Connection, event: UsageEvent, binding: ID, key: ID)
+                -> Result<()> {
+    c.execute("INSERT INTO usage_events (timestamp, kind, binding, key)
+               VALUES (?1, ?2, ?3, ?4)",
+              &[&Timestamp::now(), &(event as i64), &binding, &key])?;
+    Ok(())
+}
+
+/// Counts the encryptions and verifications per week.
+///
+/// `column` is either `binding` or `key`, and selects the events of
+/// the binding or key with the given `id`.  Covers the last `weeks`
+/// weeks, the last of which ends now.
+fn query_usage(c: &Connection, column: &str, id: ID, weeks: u32,
+               usage: node::usage::Builder) -> Result<()> {
+    let weeks = cmp::min(weeks, MAX_USAGE_WEEKS);
+    let week = Duration::weeks(1).num_seconds();
+    let start = Timestamp::now().unix() - week * weeks as i64;
+
+    let mut counts = vec![(0u32, 0u32); weeks as usize];
+    let mut stmt = c.prepare(&format!(
+        "SELECT timestamp, kind FROM usage_events
+             WHERE {} = ?1 AND timestamp >= ?2", column))?;
+    let rows = stmt.query_map(&[&id, &start], |row| -> (i64, i64) {
+        (row.get(0), row.get(1))
+    })?;
+    for row in rows {
+        let (timestamp, kind) = row?;
+        let i = ((timestamp - start) / week) as usize;
+        if let Some(count) = counts.get_mut(i) {
+            match kind {
+                k if k == UsageEvent::Encryption as i64 => count.0 += 1,
+                k if k == UsageEvent::Verification as i64 => count.1 += 1,
+                _ => (),
+            }
+        }
+    }
+
+    let mut list = usage.init_weeks(weeks);
+    for (i, &(encryptions, verifications)) in counts.iter().enumerate() {
+        let mut w = list.reborrow().get(i as u32);
+        w.set_start(start + week * i as i64);
+        w.set_encryption_count(encryptions);
+        w.set_verification_count(verifications);
+    }
+    Ok(())
+}
+
+/// Returns the persisted keyserver health statistics.
+fn load_keyserver_health(c: &Connection)
+                         -> Result<Vec<(IpAddr, net::health::ServerStats)>> {
+    let mut stmt = c.prepare(
+        "SELECT address, successes, failures, consecutive_failures,
+                latency, last_seen
+             FROM keyserver_health")?;
+    let rows = stmt.query_map(&[], |row| -> (String, i64, i64, i64,
+                                             Option<i64>, Option<i64>) {
+        (row.get(0), row.get(1), row.get(2), row.get(3), row.get(4),
+         row.get(5))
+    })?;
+
+    let mut health = Vec::new();
+    for row in rows {
+        let (address, successes, failures, consecutive_failures,
+             latency, last_seen) = row?;
+        let address = match address.parse() {
+            Ok(a) => a,
+            // Ignore garbage.
+            Err(_) => continue,
+        };
+        health.push((address, net::health::ServerStats {
+            successes: successes as u64,
+            failures: failures as u64,
+            consecutive_failures: consecutive_failures as u32,
+            latency: latency.map(|ms| StdDuration::from_millis(ms as u64)),
+            last_seen: last_seen.map(|t| UNIX_EPOCH
+                                     + StdDuration::from_secs(t as u64)),
+        }));
+    }
+    Ok(health)
+}
+
+/// Loads the keyserver failover decisions.
+fn load_keyserver_failover(c: &Connection)
+                           -> Result<Vec<(String, net::failover::Decision)>> {
+    let mut stmt = c.prepare(
+        "SELECT scope, endpoint, expires FROM keyserver_failover")?;
+    let rows = stmt.query_map(&[], |row| -> (String, String, i64) {
+        (row.get(0), row.get(1), row.get(2))
+    })?;
+
+    let mut decisions = Vec::new();
+    for row in rows {
+        let (scope, endpoint, expires) = row?;
+        decisions.push((scope, net::failover::Decision {
+            endpoint: endpoint,
+            expires: UNIX_EPOCH + StdDuration::from_secs(expires as u64),
+        }));
+    }
+    Ok(decisions)
+}
+
+/// Persists the keyserver failover decisions.
+///
+/// Expired decisions are removed.
+fn store_keyserver_failover(c: &Connection,
+                            decisions: &[(String, net::failover::Decision)])
+                            -> Result<()> {
+    c.execute("DELETE FROM keyserver_failover", &[])?;
+    for &(ref scope, ref decision) in decisions {
+        let expires = decision.expires.duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        c.execute("INSERT INTO keyserver_failover (scope, endpoint, expires)
+                   VALUES (?1, ?2, ?3)",
+                  &[scope, &decision.endpoint, &expires])?;
+    }
+    Ok(())
+}
+
+/// Persists the keyserver health statistics.
+fn store_keyserver_health(c: &Connection,
+                          health: &[(IpAddr, net::health::ServerStats)])
+                          -> Result<()> {
+    for &(ref address, ref stats) in health {
+        let latency = stats.latency.map(
+            |d| d.as_secs() as i64 * 1000 + d.subsec_millis() as i64);
+        let last_seen = stats.last_seen
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64);
+        c.execute("INSERT OR REPLACE INTO keyserver_health
+                       (address, successes, failures, consecutive_failures,
+                        latency, last_seen)
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                  &[&address.to_string(), &(stats.successes as i64),
+                    &(stats.failures as i64),
+                    &(stats.consecutive_failures as i64),
+                    &latency, &last_seen])?;
+    }
+    Ok(())
 }
 
 /// Common code for BindingServer and KeyServer.
@@ -1110,15 +2780,52 @@ trait Query {
 
 /* Iterators.  */
 
+/// The maximum number of items returned by a single `nextBatch` call.
+const MAX_BATCH_SIZE: u32 = 1024;
+
+/// Returns the largest id in `table`.
+///
+/// Iterators walk the rows in the order of their ids, and only
+/// return rows up to the fence taken when the iterator is created.
+/// Hence, rows inserted while iterating, e.g. by the housekeeping
+/// service merging keys, do not show up halfway through the
+/// iteration.  This relies on ids never being reused, which is why
+/// the iterated tables use AUTOINCREMENT.
+///
+/// Note that iterators do not operate on a snapshot.  Rows deleted
+/// in the meantime are skipped, and rows updated in the meantime are
+/// returned with their new values.
+fn fence(c: &Connection, table: &str) -> Result<ID> {
+    Ok(c.query_row(&format!("SELECT coalesce(max(id), 0) FROM {}", table),
+                   &[], |row| row.get(0))?)
+}
+
 struct StoreIterServer {
     c: Rc<Connection>,
+    // Stripped from the realms returned, and handed to the stores.
+    scope: String,
     prefix: String,
     n: ID,
+    fence: ID,
 }
 
 impl StoreIterServer {
-    fn new(c: Rc<Connection>, prefix: &str) -> Self {
-        StoreIterServer{c: c, prefix: String::from(prefix) + "%", n: ID::null()}
+    fn new(c: Rc<Connection>, scope: &str, prefix: &str) -> Result<Self> {
+        let fence = fence(&c, "stores")?;
+        Ok(StoreIterServer{c: c, scope: scope.into(),
+                           prefix: format!("{}{}%", scope, prefix),
+                           n: ID::null(), fence: fence})
+    }
+
+    /// Returns the id, realm, name, and network policy of the next
+    /// store.
+    fn query(&self) -> rusqlite::Result<(ID, String, String, i64)> {
+        self.c.query_row(
+            "SELECT id, realm, name, network_policy FROM stores
+                 WHERE id > ?1 AND id <= ?3 AND realm like ?2
+                 ORDER BY id LIMIT 1",
+            &[&self.n, &self.prefix, &self.fence],
+            |row| (row.get(0), row.get(1), row.get(2), row.get(3)))
     }
 }
 
@@ -1128,13 +2835,7 @@ impl node::store_iter::Server for StoreIterServer {
             mut results: node::store_iter::NextResults)
             -> Promise<(), capnp::Error> {
         bind_results!(results);
-        let (id, realm, name, network_policy): (ID, String, String, i64) =
-            sry!(self.c.query_row(
-                 "SELECT id, realm, name, network_policy FROM stores
-                      WHERE id > ?1 AND realm like ?2
-                      ORDER BY id LIMIT 1",
-                &[&self.n, &self.prefix],
-                |row| (row.get(0), row.get(1), row.get(2), row.get(3))));
+        let (id, realm, name, network_policy) = sry!(self.query());
 
         // We cannot implement FromSql and friends for
         // core::NetworkPolicy, hence we need to do it by foot.
@@ -1144,11 +2845,12 @@ impl node::store_iter::Server for StoreIterServer {
         let network_policy = core::NetworkPolicy::from(network_policy as u8);
 
         let mut entry = pry!(results.get().get_result()).init_ok();
-        entry.set_realm(&realm);
+        entry.set_realm(&realm[self.scope.len()..]);
         entry.set_name(&name);
         entry.set_network_policy(network_policy.into());
         entry.set_store(node::store::ToClient::new(
-            StoreServer::new(self.c.clone(), id)).into_client::<capnp_rpc::Server>());
+            StoreServer::new(self.c.clone(), id, &self.scope))
+                        .into_client::<capnp_rpc::Server>());
         self.n = id;
         Promise::ok(())
     }
@@ -1156,13 +2858,22 @@ impl node::store_iter::Server for StoreIterServer {
 
 struct BindingIterServer {
     c: Rc<Connection>,
+    scope: String,
     store_id: ID,
+    // If not None, only bindings with this trust state are returned.
+    trust: Option<Trust>,
     n: ID,
+    fence: ID,
 }
 
 impl BindingIterServer {
-    fn new(c: Rc<Connection>, store_id: ID) -> Self {
-        BindingIterServer{c: c, store_id: store_id, n: ID::null()}
+    fn new(c: Rc<Connection>, scope: &str, store_id: ID,
+           trust: Option<Trust>)
+           -> Result<Self> {
+        let fence = fence(&c, "bindings")?;
+        Ok(BindingIterServer{c: c, scope: scope.into(),
+                             store_id: store_id, trust: trust,
+                             n: ID::null(), fence: fence})
     }
 }
 
@@ -1176,29 +2887,78 @@ impl node::binding_iter::Server for BindingIterServer {
             sry!(self.c.query_row(
                  "SELECT bindings.id, bindings.label, keys.fingerprint FROM bindings
                       JOIN keys ON bindings.key = keys.id
-                      WHERE bindings.id > ?1 AND bindings.store = ?2
+                      WHERE bindings.id > ?1 AND bindings.id <= ?4
+                        AND bindings.store = ?2
+                        AND (?3 IS NULL OR bindings.trust = ?3)
                       ORDER BY bindings.id LIMIT 1",
-                &[&self.n, &self.store_id],
+                &[&self.n, &self.store_id, &self.trust.map(i64::from),
+                  &self.fence],
                 |row| (row.get(0), row.get(1), row.get(2))));
 
         let mut entry = pry!(results.get().get_result()).init_ok();
         entry.set_label(&label);
         entry.set_fingerprint(&fingerprint);
         entry.set_binding(node::binding::ToClient::new(
-            BindingServer::new(self.c.clone(), id)).into_client::<capnp_rpc::Server>());
+            BindingServer::new(self.c.clone(), id, &self.scope))
+                        .into_client::<capnp_rpc::Server>());
         self.n = id;
         Promise::ok(())
     }
+
+    fn next_batch(&mut self,
+                  params: node::binding_iter::NextBatchParams,
+                  mut results: node::binding_iter::NextBatchResults)
+                  -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let count = cmp::min(pry!(params.get()).get_count(), MAX_BATCH_SIZE);
+        let rows: Vec<(ID, String, String)> = {
+            let mut stmt = sry!(self.c.prepare(
+                "SELECT bindings.id, bindings.label, keys.fingerprint FROM bindings
+                     JOIN keys ON bindings.key = keys.id
+                     WHERE bindings.id > ?1 AND bindings.id <= ?5
+                       AND bindings.store = ?2
+                       AND (?3 IS NULL OR bindings.trust = ?3)
+                     ORDER BY bindings.id LIMIT ?4"));
+            let rows = sry!(stmt.query_map(
+                &[&self.n, &self.store_id, &self.trust.map(i64::from),
+                  &(count as i64), &self.fence],
+                |row| (row.get(0), row.get(1), row.get(2))));
+            sry!(rows.collect::<::std::result::Result<Vec<_>, _>>())
+        };
+
+        let mut items = pry!(results.get().get_result()).init_ok()
+            .init_items(rows.len() as u32);
+        for (i, (id, label, fingerprint)) in rows.into_iter().enumerate() {
+            let mut entry = items.reborrow().get(i as u32);
+            entry.set_label(&label);
+            entry.set_fingerprint(&fingerprint);
+            entry.set_binding(node::binding::ToClient::new(
+                BindingServer::new(self.c.clone(), id, &self.scope))
+                            .into_client::<capnp_rpc::Server>());
+            self.n = id;
+        }
+        Promise::ok(())
+    }
 }
 
 struct KeyIterServer {
     c: Rc<Connection>,
+    scope: String,
     n: ID,
+    fence: ID,
 }
 
 impl KeyIterServer {
-    fn new(c: Rc<Connection>) -> Self {
-        KeyIterServer{c: c, n: ID::null()}
+    fn new(c: Rc<Connection>, scope: &str) -> Result<Self> {
+        let fence = fence(&c, "keys")?;
+        Ok(KeyIterServer{c: c, scope: scope.into(),
+                         n: ID::null(), fence: fence})
+    }
+
+    /// Returns a server for the given key, restricted to the peer's
+    /// realms.
+    fn key_server(&self, id: ID) -> KeyServer {
+        KeyServer::new(self.c.clone(), id, &self.scope)
     }
 }
 
@@ -1211,18 +2971,47 @@ impl node::key_iter::Server for KeyIterServer {
         let (id, fingerprint): (ID, String) =
             sry!(self.c.query_row(
                  "SELECT id, fingerprint FROM keys
-                      WHERE keys.id > ?1
+                      WHERE keys.id > ?1 AND keys.id <= ?2
                       ORDER BY id LIMIT 1",
-                &[&self.n],
+                &[&self.n, &self.fence],
                 |row| (row.get(0), row.get(1))));
 
         let mut entry = pry!(results.get().get_result()).init_ok();
         entry.set_fingerprint(&fingerprint);
-        entry.set_key(node::key::ToClient::new(
-            KeyServer::new(self.c.clone(), id)).into_client::<capnp_rpc::Server>());
+        entry.set_key(node::key::ToClient::new(self.key_server(id))
+                      .into_client::<capnp_rpc::Server>());
         self.n = id;
         Promise::ok(())
     }
+
+    fn next_batch(&mut self,
+                  params: node::key_iter::NextBatchParams,
+                  mut results: node::key_iter::NextBatchResults)
+                  -> Promise<(), capnp::Error> {
+        bind_results!(results);
+        let count = cmp::min(pry!(params.get()).get_count(), MAX_BATCH_SIZE);
+        let rows: Vec<(ID, String)> = {
+            let mut stmt = sry!(self.c.prepare(
+                "SELECT id, fingerprint FROM keys
+                     WHERE keys.id > ?1 AND keys.id <= ?3
+                     ORDER BY id LIMIT ?2"));
+            let rows = sry!(stmt.query_map(
+                &[&self.n, &(count as i64), &self.fence],
+                |row| (row.get(0), row.get(1))));
+            sry!(rows.collect::<::std::result::Result<Vec<_>, _>>())
+        };
+
+        let mut items = pry!(results.get().get_result()).init_ok()
+            .init_items(rows.len() as u32);
+        for (i, (id, fingerprint)) in rows.into_iter().enumerate() {
+            let mut entry = items.reborrow().get(i as u32);
+            entry.set_fingerprint(&fingerprint);
+            entry.set_key(node::key::ToClient::new(self.key_server(id))
+                          .into_client::<capnp_rpc::Server>());
+            self.n = id;
+        }
+        Promise::ok(())
+    }
 }
 
 /* Error handling.  */
@@ -1245,6 +3034,8 @@ impl fmt::Debug for node::Error {
                        "NetworkPolicyViolation(Encrypted)",
                    &node::Error::NetworkPolicyViolationInsecure =>
                        "NetworkPolicyViolation(Insecure)",
+                   &node::Error::Timeout => "Timeout",
+                   &node::Error::PermissionDenied => "PermissionDenied",
                })
     }
 }
@@ -1255,6 +3046,9 @@ impl From<rusqlite::Error> for node::Error {
             rusqlite::Error::SqliteFailure(f, _) => match f.code {
                 rusqlite::ErrorCode::ConstraintViolation =>
                     node::Error::NotFound,
+                rusqlite::ErrorCode::DatabaseBusy
+                    | rusqlite::ErrorCode::DatabaseLocked =>
+                    node::Error::Timeout,
                 _ => node::Error::SystemError,
             },
             rusqlite::Error::QueryReturnedNoRows =>
@@ -1278,7 +3072,8 @@ impl From<failure::Error> for node::Error {
             return match e {
                 &super::Error::NotFound => node::Error::NotFound,
                 &super::Error::Conflict => node::Error::Conflict,
-                _ => unreachable!(),
+                // E.g. ProtocolError for malformed database entries.
+                _ => node::Error::SystemError,
             }
         }
 
@@ -1304,6 +3099,9 @@ impl From<failure::Error> for node::Error {
                 &rusqlite::Error::SqliteFailure(f, _) => match f.code {
                     rusqlite::ErrorCode::ConstraintViolation =>
                         node::Error::NotFound,
+                    rusqlite::ErrorCode::DatabaseBusy
+                        | rusqlite::ErrorCode::DatabaseLocked =>
+                        node::Error::Timeout,
                     _ => node::Error::SystemError,
                 },
                 &rusqlite::Error::QueryReturnedNoRows =>
@@ -1424,8 +3222,268 @@ CREATE TABLE log (
     FOREIGN KEY (key) REFERENCES keys(id) ON DELETE CASCADE);
 ";
 
+/* Version 2.  */
+const DB_MIGRATION_1_2: &'static str = "
+ALTER TABLE stores ADD COLUMN merge_strategy INTEGER NOT NULL DEFAULT 0;
+
+UPDATE version SET version = 2 WHERE id = 1;
+";
+
+/* Version 3.  */
+const DB_MIGRATION_2_3: &'static str = "
+ALTER TABLE stores ADD COLUMN publish_to TEXT;
+
+UPDATE version SET version = 3 WHERE id = 1;
+";
+
+/* Version 4.  */
+const DB_MIGRATION_3_4: &'static str = "
+CREATE TABLE usage_events (
+    id INTEGER PRIMARY KEY,
+    timestamp INTEGER NOT NULL,
+    kind INTEGER NOT NULL,
+    binding INTEGER NOT NULL,
+    key INTEGER NOT NULL,
+    FOREIGN KEY (binding) REFERENCES bindings(id) ON DELETE CASCADE,
+    FOREIGN KEY (key) REFERENCES keys(id) ON DELETE CASCADE);
+
+UPDATE version SET version = 4 WHERE id = 1;
+";
+
+/* Version 5.  */
+const DB_MIGRATION_4_5: &'static str = "
+ALTER TABLE bindings ADD COLUMN trust INTEGER NOT NULL DEFAULT 0;
+
+UPDATE version SET version = 5 WHERE id = 1;
+";
+
+/* Version 6.  */
+const DB_MIGRATION_5_6: &'static str = "
+CREATE TABLE keyserver_health (
+    address TEXT PRIMARY KEY,
+    successes INTEGER NOT NULL,
+    failures INTEGER NOT NULL,
+    consecutive_failures INTEGER NOT NULL,
+    latency INTEGER NULL,
+    last_seen INTEGER NULL);
+
+UPDATE version SET version = 6 WHERE id = 1;
+";
+
+/* Version 7.  */
+const DB_MIGRATION_6_7: &'static str = "
+ALTER TABLE bindings ADD COLUMN autocrypt_level INTEGER NOT NULL DEFAULT 0;
+
+UPDATE version SET version = 7 WHERE id = 1;
+";
+
+/* Version 8.  */
+const DB_MIGRATION_7_8: &'static str = "
+ALTER TABLE stores ADD COLUMN notify_command TEXT;
+
+UPDATE version SET version = 8 WHERE id = 1;
+";
+
+/* Version 9.  */
+const DB_MIGRATION_8_9: &'static str = "
+CREATE TABLE keyserver_failover (
+    scope TEXT PRIMARY KEY,
+    endpoint TEXT NOT NULL,
+    expires INTEGER NOT NULL);
+
+UPDATE version SET version = 9 WHERE id = 1;
+";
+
+/* Version 10.  */
+const DB_MIGRATION_9_10: &'static str = "
+CREATE TABLE quarantine (
+    id INTEGER PRIMARY KEY,
+    key INTEGER NOT NULL,
+    created INTEGER NOT NULL,
+    tpk BLOB NOT NULL,
+    reason TEXT NOT NULL,
+
+    FOREIGN KEY (key) REFERENCES keys(id) ON DELETE CASCADE);
+
+UPDATE version SET version = 10 WHERE id = 1;
+";
+
+/* Version 11.  */
+const DB_MIGRATION_10_11: &'static str = "
+ALTER TABLE keys ADD COLUMN key_hash TEXT;
+
+UPDATE version SET version = 11 WHERE id = 1;
+";
+
+/* Version 12.  */
+const DB_MIGRATION_11_12: &'static str = "
+CREATE TABLE housekeeping (
+    id INTEGER PRIMARY KEY,
+    paused_until INTEGER NOT NULL);
+
+INSERT INTO housekeeping (id, paused_until) VALUES (1, 0);
+
+UPDATE version SET version = 12 WHERE id = 1;
+";
+
+/* Version 13.  */
+
+/// Recreates the tables that are iterated over using AUTOINCREMENT,
+/// so that the ids of deleted rows are never reused.  See `fence`.
+///
+/// When migrating an existing database, foreign key constraints must
+/// be disabled, otherwise dropping the old tables deletes the rows
+/// referring to them.
+const DB_MIGRATION_12_13: &'static str = "
+CREATE TABLE stores_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    realm TEXT NOT NULL,
+    network_policy INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    merge_strategy INTEGER NOT NULL DEFAULT 0,
+    publish_to TEXT,
+    notify_command TEXT,
+    UNIQUE (realm, name));
+
+INSERT INTO stores_new SELECT * FROM stores;
+DROP TABLE stores;
+ALTER TABLE stores_new RENAME TO stores;
+
+CREATE TABLE bindings_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    store INTEGER NOT NULL,
+    label TEXT NOT NULL,
+    key INTEGER NOT NULL,
+
+    created INTEGER NOT NULL,
+    updated INTEGER NULL,
+
+    encryption_count DEFAULT 0,
+    encryption_first INTEGER NULL,
+    encryption_last INTEGER NULL,
+    verification_count DEFAULT 0,
+    verification_first INTEGER NULL,
+    verification_last INTEGER NULL,
+
+    trust INTEGER NOT NULL DEFAULT 0,
+    autocrypt_level INTEGER NOT NULL DEFAULT 0,
+
+    UNIQUE(store, label),
+    FOREIGN KEY (store) REFERENCES stores(id) ON DELETE CASCADE,
+    FOREIGN KEY (key) REFERENCES keys(id) ON DELETE CASCADE);
+
+INSERT INTO bindings_new SELECT * FROM bindings;
+DROP TABLE bindings;
+ALTER TABLE bindings_new RENAME TO bindings;
+
+CREATE TABLE keys_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    fingerprint TEXT NOT NULL,
+    key BLOB,
+
+    created INTEGER NOT NULL,
+    updated INTEGER NULL,
+    update_at INTEGER NOT NULL,
+
+    encryption_count DEFAULT 0,
+    encryption_first INTEGER NULL,
+    encryption_last INTEGER NULL,
+    verification_count DEFAULT 0,
+    verification_first INTEGER NULL,
+    verification_last INTEGER NULL,
+
+    key_hash TEXT,
+
+    UNIQUE (fingerprint));
+
+INSERT INTO keys_new SELECT * FROM keys;
+DROP TABLE keys;
+ALTER TABLE keys_new RENAME TO keys;
+
+UPDATE version SET version = 13 WHERE id = 1;
+";
+
+/* Version 14.  */
+
+/// Labels are normalized from this version on.  Existing labels are
+/// normalized by `normalize_labels` before bumping the version.
+const DB_MIGRATION_13_14: &'static str = "
+UPDATE version SET version = 14 WHERE id = 1;
+";
+
 /* Miscellaneous.  */
 
+/// Returns whether key updates are paused.
+fn refresh_paused(c: &Connection) -> Result<bool> {
+    let until: i64 = c.query_row(
+        "SELECT paused_until FROM housekeeping WHERE id = 1",
+        &[], |row| row.get(0))?;
+    Ok(until == PAUSED_INDEFINITELY || until > Timestamp::now().unix())
+}
+
+/// Returns the content hash of a key blob.
+fn content_hash(blob: &[u8]) -> Result<String> {
+    let mut hash = HashAlgorithm::SHA256.context()?;
+    hash.update(blob);
+    let mut digest = vec![0u8; hash.digest_size()];
+    hash.digest(&mut digest);
+    Ok(hex::encode(digest))
+}
+
+impl From<MergeStrategy> for i64 {
+    fn from(strategy: MergeStrategy) -> Self {
+        match strategy {
+            MergeStrategy::Strict      => 0,
+            MergeStrategy::Rebind      => 1,
+            MergeStrategy::PreferNewer => 2,
+        }
+    }
+}
+
+impl From<Trust> for i64 {
+    fn from(trust: Trust) -> Self {
+        match trust {
+            Trust::Unknown      => 0,
+            Trust::Accepted     => 1,
+            Trust::Rejected     => 2,
+            Trust::TOFUConflict => 3,
+        }
+    }
+}
+
+impl Trust {
+    fn from_i64(trust: i64) -> Option<Self> {
+        match trust {
+            0 => Some(Trust::Unknown),
+            1 => Some(Trust::Accepted),
+            2 => Some(Trust::Rejected),
+            3 => Some(Trust::TOFUConflict),
+            _ => None,
+        }
+    }
+}
+
+impl From<AutocryptLevel> for i64 {
+    fn from(level: AutocryptLevel) -> Self {
+        match level {
+            AutocryptLevel::Manual => 0,
+            AutocryptLevel::Gossip => 1,
+            AutocryptLevel::Mutual => 2,
+        }
+    }
+}
+
+impl AutocryptLevel {
+    fn from_i64(level: i64) -> Option<Self> {
+        match level {
+            0 => Some(AutocryptLevel::Manual),
+            1 => Some(AutocryptLevel::Gossip),
+            2 => Some(AutocryptLevel::Mutual),
+            _ => None,
+        }
+    }
+}
+
 impl<'a> From<&'a core::NetworkPolicy> for node::NetworkPolicy {
     fn from(policy: &core::NetworkPolicy) -> Self {
         match policy {
@@ -1453,3 +3511,335 @@ impl From<node::NetworkPolicy> for core::NetworkPolicy {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use openpgp::tpk::{CipherSuite, TPKBuilder};
+
+    fn generate(expiration: Option<Duration>)
+                -> (TPK, openpgp::packet::Signature) {
+        TPKBuilder::general_purpose(CipherSuite::Cv25519,
+                                    Some("foo@example.org"))
+            .set_expiration(expiration)
+            .generate().unwrap()
+    }
+
+    #[test]
+    fn attention() {
+        let now = time::now_utc().to_timespec();
+
+        let (tpk, rev) = generate(None);
+        assert_eq!(next_expiration(&tpk), None);
+        assert!(! needs_attention(&tpk));
+
+        // Revoked keys need attention.
+        let tpk = tpk.merge_packets(vec![rev.into()]).unwrap();
+        assert!(needs_attention(&tpk));
+
+        let (tpk, _) = generate(Some(Duration::weeks(52)));
+        let t = next_expiration(&tpk).unwrap();
+        assert!(t > now + Duration::weeks(51));
+        assert!(t <= now + Duration::weeks(53));
+        assert!(! needs_attention(&tpk));
+
+        let (tpk, _) = generate(Some(Duration::weeks(1)));
+        let t = next_expiration(&tpk).unwrap();
+        assert!(t > now);
+        assert!(t <= now + Duration::weeks(2));
+        assert!(needs_attention(&tpk));
+    }
+
+    #[test]
+    fn attention_schedule() {
+        // Keys needing attention are refreshed in the future, but
+        // sooner than other keys.
+        assert!(attention_interval() >= min_sleep_time());
+        assert!(attention_interval() < refresh_interval());
+        assert_eq!(attention_interval(), refresh_interval() / 4);
+    }
+
+    /// Returns a node server using an empty in-memory database.
+    fn server() -> NodeServer {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .build().unwrap();
+        let c = Connection::open_in_memory().unwrap();
+        c.execute_batch("PRAGMA foreign_keys = true;").unwrap();
+        NodeServer {
+            descriptor: ::descriptor(&ctx),
+            c: Rc::new(c),
+            scope: realm_scope(ipc::Peer::Owner),
+        }
+    }
+
+    /// Returns a node server using an in-memory database.
+    pub fn node() -> NodeServer {
+        let node = server();
+        node.init().unwrap();
+        node
+    }
+
+    /// Returns the id of the default store in the peer's realm.
+    pub fn store(node: &NodeServer) -> ID {
+        StoreServer::open(node.c.clone(), &node.scope,
+                          &node.realm(::REALM_CONTACTS),
+                          refresh_network_policy(), "default")
+            .unwrap().id
+    }
+
+    /// Binds the key with the given fingerprint in the peer's realm.
+    fn bind(node: &NodeServer, fp: &Fingerprint) -> ID {
+        BindingServer::lookup_or_create(&node.c, store(node), "label", fp)
+            .unwrap().1
+    }
+
+    #[test]
+    fn refresh_scope() {
+        let owner = node();
+        let alice = owner.for_peer(ipc::Peer::User(1000));
+        let bob = owner.for_peer(ipc::Peer::User(1001));
+        assert!(owner.is_owner());
+        assert!(! alice.is_owner());
+        assert!(! bob.is_owner());
+
+        let fp_a = Fingerprint::from_bytes(b"aaaaaaaaaaaaaaaaaaaa");
+        let fp_b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let a = bind(&alice, &fp_a);
+        let b = bind(&bob, &fp_b);
+
+        // Users only see the keys in their own realms.
+        let schedule = |node: &NodeServer| {
+            node.refresh_schedule_keys().unwrap().into_iter()
+                .map(|(fp, _)| fp).collect::<Vec<_>>()
+        };
+        assert_eq!(schedule(&alice), vec![fp_a.to_hex()]);
+        assert_eq!(schedule(&bob), vec![fp_b.to_hex()]);
+        let mut all = schedule(&owner);
+        all.sort();
+        assert_eq!(all, vec![fp_a.to_hex(), fp_b.to_hex()]);
+
+        // Users may only schedule keys in their realms for an update.
+        let key = |node: &NodeServer, id| {
+            KeyServer::new(node.c.clone(), id, &node.scope)
+        };
+        assert!(key(&alice, a).in_scope().unwrap());
+        assert!(! key(&alice, b).in_scope().unwrap());
+        assert!(! key(&bob, a).in_scope().unwrap());
+        assert!(key(&owner, a).in_scope().unwrap());
+        assert!(key(&owner, b).in_scope().unwrap());
+    }
+
+    #[test]
+    fn servers_scope() {
+        let owner = node();
+        let alice = owner.for_peer(ipc::Peer::User(1000));
+        let bob = owner.for_peer(ipc::Peer::User(1001));
+
+        let fp_a = Fingerprint::from_bytes(b"aaaaaaaaaaaaaaaaaaaa");
+        let fp_b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let (binding_a, a, _) =
+            BindingServer::lookup_or_create(&owner.c, store(&alice),
+                                            "label", &fp_a).unwrap();
+        let b = bind(&bob, &fp_b);
+        owner.c.execute("INSERT INTO key_by_keyid (keyid, key)
+                         VALUES (1, ?1), (2, ?2)", &[&a, &b]).unwrap();
+
+        // Keys handed out by bindings and key iterators are
+        // restricted to the peer's realms.
+        let mut binding = BindingServer::new(owner.c.clone(), binding_a,
+                                             &alice.scope);
+        assert!(binding.key_server().unwrap().in_scope().unwrap());
+        let keys = KeyIterServer::new(owner.c.clone(), &alice.scope)
+            .unwrap();
+        assert!(keys.key_server(a).in_scope().unwrap());
+        assert!(! keys.key_server(b).in_scope().unwrap());
+        let keys = KeyIterServer::new(owner.c.clone(), &owner.scope)
+            .unwrap();
+        assert!(keys.key_server(b).in_scope().unwrap());
+
+        // Looking up by subkeyid only finds bindings in the store.
+        assert!(lookup_binding_by_subkeyid(&owner.c, store(&alice), 1)
+                .is_ok());
+        assert!(lookup_binding_by_subkeyid(&owner.c, store(&alice), 2)
+                .is_err());
+        assert!(lookup_binding_by_subkeyid(&owner.c, store(&bob), 2)
+                .is_ok());
+    }
+
+    #[test]
+    fn ids_not_reused() {
+        // Start with version 12 of the schema.
+        let node = server();
+        for migration in &[DB_SCHEMA_1, DB_MIGRATION_1_2, DB_MIGRATION_2_3,
+                           DB_MIGRATION_3_4, DB_MIGRATION_4_5,
+                           DB_MIGRATION_5_6, DB_MIGRATION_6_7,
+                           DB_MIGRATION_7_8, DB_MIGRATION_8_9,
+                           DB_MIGRATION_9_10, DB_MIGRATION_10_11,
+                           DB_MIGRATION_11_12] {
+            node.c.execute_batch(migration).unwrap();
+        }
+
+        let fp_a = Fingerprint::from_bytes(b"aaaaaaaaaaaaaaaaaaaa");
+        let fp_b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let fp_c = Fingerprint::from_bytes(b"cccccccccccccccccccc");
+        let s = store(&node);
+        let (_, a, _) =
+            BindingServer::lookup_or_create(&node.c, s, "a", &fp_a).unwrap();
+        let (binding_b, b, _) =
+            BindingServer::lookup_or_create(&node.c, s, "b", &fp_b).unwrap();
+
+        // Migrating keeps the rows, and the references to them.
+        node.init().unwrap();
+        let version: i64 = node.c.query_row(
+            "SELECT version FROM version WHERE id = 1", &[],
+            |row| row.get(0)).unwrap();
+        assert_eq!(version, 14);
+        let count = |table: &str| -> i64 {
+            node.c.query_row(&format!("SELECT count(*) FROM {}", table),
+                             &[], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(count("keys"), 2);
+        assert_eq!(count("bindings"), 2);
+        assert!(KeyServer::lookup(&node.c, &fp_a).unwrap() == a);
+
+        // Deleting the key with the largest id cascades to its
+        // binding, and neither id is handed out again.
+        node.c.execute("DELETE FROM keys WHERE id = ?1", &[&b]).unwrap();
+        assert_eq!(count("bindings"), 1);
+        let (binding_c, c, _) =
+            BindingServer::lookup_or_create(&node.c, s, "c", &fp_c).unwrap();
+        assert!(c != b && binding_c != binding_b);
+        assert!(fence(&node.c, "keys").unwrap() == c);
+
+        node.c.execute("DELETE FROM stores WHERE id = ?1", &[&s]).unwrap();
+        assert_eq!(count("bindings"), 0);
+        assert!(store(&node) != s);
+    }
+
+    #[test]
+    fn labels_normalized() {
+        // Start with version 13 of the schema.
+        let node = server();
+        for migration in &[DB_SCHEMA_1, DB_MIGRATION_1_2, DB_MIGRATION_2_3,
+                           DB_MIGRATION_3_4, DB_MIGRATION_4_5,
+                           DB_MIGRATION_5_6, DB_MIGRATION_6_7,
+                           DB_MIGRATION_7_8, DB_MIGRATION_8_9,
+                           DB_MIGRATION_9_10, DB_MIGRATION_10_11,
+                           DB_MIGRATION_11_12, DB_MIGRATION_12_13] {
+            node.c.execute_batch(migration).unwrap();
+        }
+
+        // Labels used to be stored as given.
+        let fp_a = Fingerprint::from_bytes(b"aaaaaaaaaaaaaaaaaaaa");
+        let fp_b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let a = KeyServer::lookup_or_create(&node.c, &fp_a).unwrap();
+        let b = KeyServer::lookup_or_create(&node.c, &fp_b).unwrap();
+        let s = store(&node);
+        let add = |label: &str, key: ID| -> ID {
+            node.c.execute(
+                "INSERT INTO bindings (store, label, key, created)
+                 VALUES (?1, ?2, ?3, ?4)",
+                &[&s, &label, &key, &Timestamp::now()]).unwrap();
+            node.c.last_insert_rowid().into()
+        };
+        let alice = add("Alice@Example.COM", a);
+        add("alice@example.com", a);
+        let bob = add("Bob@Example.COM", b);
+        let carol = add("Carol@Example.COM", a);
+        let other_carol = add("CAROL@example.com", b);
+        let mister_b = add("Mister B.", b);
+
+        node.init().unwrap();
+        let version: i64 = node.c.query_row(
+            "SELECT version FROM version WHERE id = 1", &[],
+            |row| row.get(0)).unwrap();
+        assert_eq!(version, 14);
+        let count: i64 = node.c.query_row(
+            "SELECT count(*) FROM bindings", &[], |row| row.get(0)).unwrap();
+        assert_eq!(count, 5);
+
+        // Bindings to the same key are merged, and the oldest
+        // binding is found using the normalized label.
+        assert!(lookup_binding(&node.c, s, "alice@example.com").unwrap()
+                == (alice, a));
+        assert!(lookup_binding(&node.c, s, "ALICE@example.com").unwrap()
+                == (alice, a));
+        assert!(lookup_binding(&node.c, s, "bob@example.com").unwrap()
+                == (bob, b));
+        assert!(lookup_binding(&node.c, s, "Mister B.").unwrap()
+                == (mister_b, b));
+        assert!(BindingServer::lookup_or_create(&node.c, s, "alice@example.com",
+                                                &fp_a).unwrap()
+                == (alice, a, false));
+
+        // Conflicting bindings are kept.
+        assert!(lookup_binding(&node.c, s, "carol@example.com").unwrap()
+                == (carol, a));
+        let label: String = node.c.query_row(
+            "SELECT label FROM bindings WHERE id = ?1", &[&other_carol],
+            |row| row.get(0)).unwrap();
+        assert_eq!(label, "CAROL@example.com");
+        assert_eq!(bindings_by_normalized_label(&node.c, s).unwrap()
+                   ["carol@example.com"].len(), 2);
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let node = server();
+        node.init().unwrap();
+        node.c.execute_batch("UPDATE version SET version = 15 WHERE id = 1;")
+            .unwrap();
+        assert!(node.init().is_err());
+    }
+
+    #[test]
+    fn realms() {
+        let owner = node();
+        let alice = owner.for_peer(ipc::Peer::User(1000));
+        let bob = owner.for_peer(ipc::Peer::User(1001));
+
+        assert_eq!(owner.realm(::REALM_CONTACTS), ::REALM_CONTACTS);
+        assert_eq!(alice.realm(::REALM_CONTACTS),
+                   format!("uid:1000/{}", ::REALM_CONTACTS));
+
+        // Opening the same realm yields different stores.
+        let open = |node: &NodeServer| {
+            StoreServer::open(node.c.clone(), &node.scope,
+                              &node.realm(::REALM_CONTACTS),
+                              core::NetworkPolicy::Offline, "default")
+                .unwrap().id
+        };
+        let so = open(&owner);
+        let sa = open(&alice);
+        let sb = open(&bob);
+        assert!(so != sa && so != sb && sa != sb);
+
+        // Users only list the stores in their own realms, the owner
+        // lists all stores.
+        let list = |node: &NodeServer, prefix: &str| {
+            let mut iter =
+                StoreIterServer::new(node.c.clone(), &node.scope, prefix)
+                .unwrap();
+            let mut stores = vec![];
+            while let Ok((id, realm, _, _)) = iter.query() {
+                assert!(realm.starts_with(&node.scope));
+                iter.n = id;
+                stores.push(id);
+            }
+            stores
+        };
+        let stores = list(&alice, "");
+        assert!(stores.len() == 1 && stores.contains(&sa));
+        let stores = list(&bob, "");
+        assert!(stores.len() == 1 && stores.contains(&sb));
+        assert!(list(&alice, "uid:1001/").is_empty());
+        assert!(list(&alice, "%").len() == 1);
+        let stores = list(&owner, "");
+        assert!(stores.len() == 3);
+        assert!(stores.contains(&so) && stores.contains(&sa)
+                && stores.contains(&sb));
+        assert!(list(&owner, "uid:1001/").len() == 1);
+    }
+}
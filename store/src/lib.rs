@@ -42,7 +42,8 @@
 //! //     created: Some(Timespec { tv_sec: 1513704042, tv_nsec: 0 }),
 //! //     updated: None,
 //! //     encryption: Stamps { count: 0, first: None, last: None },
-//! //     verification: Stamps { count: 0, first: None, last: None }
+//! //     verification: Stamps { count: 0, first: None, last: None },
+//! //     expiry: None
 //! // }
 //! # Ok(())
 //! # }
@@ -63,14 +64,17 @@ extern crate tokio_core;
 extern crate tokio_io;
 
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::fmt;
 use std::rc::Rc;
+use std::time::Duration;
 
 use capnp::capability::Promise;
 use capnp_rpc::rpc_twoparty_capnp::Side;
 use futures::{Future};
+use futures::future::Either;
 use time::Timespec;
-use tokio_core::reactor::Core;
+use tokio_core::reactor::{Core, Timeout};
 
 extern crate sequoia_openpgp as openpgp;
 #[allow(unused_imports)]
@@ -82,6 +86,8 @@ extern crate sequoia_net;
 use openpgp::Fingerprint;
 use openpgp::KeyID;
 use openpgp::TPK;
+use openpgp::ownertrust::{OwnerTrust, OwnerTrustEntry, OwnerTrustList};
+use openpgp::packet::UserID;
 use openpgp::parse::Parse;
 use openpgp::serialize::Serialize;
 use sequoia_core as core;
@@ -97,6 +103,10 @@ use store_protocol_capnp::node;
 pub(crate) mod backend;
 
 /// Returns the service descriptor.
+///
+/// If the context names a system socket, the store is provided by
+/// a system daemon.  It shares the key pool between all users, but
+/// every user gets their own realms.
 #[doc(hidden)]
 pub fn descriptor(c: &Context) -> ipc::Descriptor {
     ipc::Descriptor::new(
@@ -104,6 +114,20 @@ pub fn descriptor(c: &Context) -> ipc::Descriptor {
         c.home().join("public-key-store.cookie"),
         c.lib().join("sequoia-public-key-store"),
         backend::factory,
+    ).system_socket(c.system_socket())
+}
+
+/// Returns the descriptor of the housekeeping service.
+///
+/// The housekeeping service updates the keys in the store.  It is
+/// started by the store server.
+#[doc(hidden)]
+pub fn housekeeping_descriptor(c: &Context) -> ipc::Descriptor {
+    ipc::Descriptor::new(
+        c,
+        c.home().join("public-key-store-housekeeping.cookie"),
+        c.lib().join("sequoia-public-key-store-housekeeping"),
+        backend::housekeeping_factory,
     )
 }
 
@@ -115,6 +139,83 @@ pub const REALM_CONTACTS: &'static str =
 pub const REALM_SOFTWARE_UPDATES: &'static str =
     "org.sequoia-pgp.software-updates";
 
+/// Returns the normalized form of a binding label.
+///
+/// Labels that are email addresses are normalized like
+/// [`UserID::address_normalized`] does, i.e. the domain is converted
+/// to punycode, and the address is lowercased.  Hence,
+/// `Alice@Example.COM` and `alice@example.com` refer to the same
+/// binding.  Other labels are returned as is.
+///
+/// The store normalizes labels when bindings are added and looked
+/// up.
+///
+///   [`UserID::address_normalized`]: ../sequoia_openpgp/packet/struct.UserID.html#method.address_normalized
+///
+/// # Example
+///
+/// ```
+/// # extern crate sequoia_store;
+/// # use sequoia_store::normalize_label;
+/// assert_eq!(normalize_label("Alice@Example.COM"), "alice@example.com");
+/// assert_eq!(normalize_label("hans@bücher.tld"), "hans@xn--bcher-kva.tld");
+/// assert_eq!(normalize_label("Mister B."), "Mister B.");
+/// ```
+pub fn normalize_label(label: &str) -> String {
+    let userid = UserID::from(label);
+    match userid.address() {
+        Ok(Some(ref address)) if address == label =>
+            match userid.address_normalized() {
+                Ok(Some(address)) => address,
+                _ => label.into(),
+            },
+        _ => label.into(),
+    }
+}
+
+/// How long to wait for the backend to answer a request.
+///
+/// The backend waits at most a few seconds for database locks, so
+/// this is only reached if the backend hangs.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An event loop that runs requests with a deadline.
+struct RpcCore {
+    core: Core,
+    timeout: Option<Duration>,
+}
+
+impl RpcCore {
+    fn new() -> Result<Self> {
+        Ok(RpcCore {
+            core: Core::new()?,
+            timeout: Some(DEFAULT_TIMEOUT),
+        })
+    }
+
+    /// Runs the given request to completion.
+    ///
+    /// If the request does not complete within the timeout, it is
+    /// dropped, which cancels the call in the backend, and
+    /// `Error::Timeout` is returned.
+    fn run<F>(&mut self, f: F) -> Result<F::Item>
+        where F: Future<Error=capnp::Error>
+    {
+        let timeout = match self.timeout {
+            Some(t) => t,
+            None => return Ok(self.core.run(f)?),
+        };
+
+        let deadline = Timeout::new(timeout, &self.core.handle())?;
+        match self.core.run(f.select2(deadline)) {
+            Ok(Either::A((x, _))) => Ok(x),
+            Ok(Either::B(((), _))) => Err(Error::Timeout.into()),
+            Err(Either::A((e, _))) => Err(e.into()),
+            Err(Either::B((e, _))) => Err(e.into()),
+        }
+    }
+}
+
 /// The common key pool.
 pub struct Pool {
 }
@@ -281,7 +382,7 @@ impl Pool {
 /// A public key store.
 pub struct Store {
     name: String,
-    core: Rc<RefCell<Core>>,
+    core: Rc<RefCell<RpcCore>>,
     store: node::store::Client,
 }
 
@@ -293,10 +394,10 @@ impl fmt::Debug for Store {
 
 impl Store {
     /// Establishes a connection to the backend.
-    fn connect(c: &Context) -> Result<(Core, node::Client)> {
+    fn connect(c: &Context) -> Result<(RpcCore, node::Client)> {
         let descriptor = descriptor(c);
-        let core = Core::new()?;
-        let handle = core.handle();
+        let core = RpcCore::new()?;
+        let handle = core.core.handle();
 
         let mut rpc_system
             = match descriptor.connect(&handle) {
@@ -334,10 +435,23 @@ impl Store {
         Ok(Self::new(Rc::new(RefCell::new(core)), name, store))
     }
 
-    fn new(core: Rc<RefCell<Core>>, name: &str, store: node::store::Client) -> Self {
+    fn new(core: Rc<RefCell<RpcCore>>, name: &str, store: node::store::Client) -> Self {
         Store{core: core, name: name.into(), store: store}
     }
 
+    /// Sets the timeout for requests to the backend.
+    ///
+    /// If the backend does not answer a request within the timeout,
+    /// the request is cancelled, and `Error::Timeout` is returned.
+    /// `None` disables the timeout.  The default is
+    /// `DEFAULT_TIMEOUT`.
+    ///
+    /// The timeout also applies to bindings, keys, and iterators
+    /// obtained from this store.
+    pub fn set_timeout(&self, timeout: Option<Duration>) {
+        self.core.borrow_mut().timeout = timeout;
+    }
+
     /// Lists all stores with the given prefix.
     pub fn list(c: &Context, realm_prefix: &str) -> Result<StoreIter> {
         let (mut core, client) = Self::connect(c)?;
@@ -352,7 +466,7 @@ impl Store {
         let (mut core, client) = Self::connect(c)?;
         let request = client.iter_keys_request();
         let iter = make_request!(&mut core, request)?;
-        Ok(KeyIter{core: Rc::new(RefCell::new(core)), iter: iter})
+        Ok(KeyIter::new(Rc::new(RefCell::new(core)), iter))
     }
 
     /// Lists all log entries.
@@ -363,6 +477,63 @@ impl Store {
         Ok(LogIter{core: Rc::new(RefCell::new(core)), iter: iter})
     }
 
+    /// Returns when stored keys are updated from the network.
+    ///
+    /// Keys bound in stores whose network policy allows it are
+    /// updated by the housekeeping service in the background.  The
+    /// updates are spread over time, so that every key is updated
+    /// about once a week.
+    pub fn refresh_schedule(c: &Context) -> Result<RefreshSchedule> {
+        let (mut core, client) = Self::connect(c)?;
+        let request = client.refresh_schedule_request();
+        make_request_map!(
+            &mut core,
+            request,
+            |s: node::refresh_schedule::Reader| {
+                let pause = match s.get_paused_until() {
+                    0 => RefreshPause::Running,
+                    -1 => RefreshPause::Indefinitely,
+                    t if t <= time::get_time().sec => RefreshPause::Running,
+                    t => RefreshPause::Until(Timespec::new(t, 0)),
+                };
+                let mut keys = Vec::new();
+                for e in s.get_keys()?.iter() {
+                    keys.push((Fingerprint::from_hex(e.get_fingerprint()?)?,
+                               from_unix(e.get_update_at())));
+                }
+                Ok(RefreshSchedule { pause: pause, keys: keys })
+            })
+    }
+
+    /// Pauses or resumes key updates.
+    ///
+    /// While paused, the housekeeping service does not update any
+    /// keys.  Keys that are due when updates are resumed are updated
+    /// first.
+    pub fn pause_refresh(c: &Context, pause: RefreshPause) -> Result<()> {
+        let (mut core, client) = Self::connect(c)?;
+        let mut request = client.pause_refresh_request();
+        request.get().set_until(match pause {
+            RefreshPause::Running => 0,
+            RefreshPause::Until(t) if t.sec > 0 => t.sec,
+            RefreshPause::Until(_) => 0,
+            RefreshPause::Indefinitely => -1,
+        });
+        make_request_map!(&mut core, request, |_| Ok(()))
+    }
+
+    /// Schedules all stored keys for an update.
+    ///
+    /// The keys are updated by the housekeeping service one after
+    /// the other, starting within minutes.  See [`Key::refresh`].
+    ///
+    ///   [`Key::refresh`]: struct.Key.html#method.refresh
+    pub fn refresh_all(c: &Context) -> Result<()> {
+        let (mut core, client) = Self::connect(c)?;
+        let request = client.refresh_all_request();
+        make_request_map!(&mut core, request, |_| Ok(()))
+    }
+
     /// Adds a key identified by fingerprint to the store.
     ///
     /// # Example
@@ -391,7 +562,60 @@ impl Store {
         request.get().set_label(label);
         request.get().set_fingerprint(fingerprint.to_hex().as_ref());
         let binding = make_request!(self.core.borrow_mut(), request)?;
-        Ok(Binding::new(self.core.clone(), Some(label), binding))
+        Ok(Binding::new(self.core.clone(), Some(&normalize_label(label)),
+                        binding))
+    }
+
+    /// Adds several keys identified by fingerprint to the store.
+    ///
+    /// All requests are sent at once, which is considerably faster
+    /// than calling `Store::add` for every key.  The bindings are
+    /// returned in the same order as the keys.
+    ///
+    /// If any of the keys cannot be added, an error is returned.
+    /// Note that the other keys may have been added nevertheless.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::Fingerprint;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// let b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+    /// let c = Fingerprint::from_bytes(b"cccccccccccccccccccc");
+    /// let bindings = store.add_bindings(&[("Mister B.", &b),
+    ///                                     ("Mister C.", &c)])?;
+    /// assert_eq!(bindings.len(), 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_bindings(&self, keys: &[(&str, &Fingerprint)])
+                        -> Result<Vec<Binding>> {
+        let requests: Vec<_> = keys.iter().map(|&(label, fingerprint)| {
+            let mut request = self.store.add_request();
+            request.get().set_label(label);
+            request.get().set_fingerprint(fingerprint.to_hex().as_ref());
+            let core = self.core.clone();
+            send_request_map!(
+                request,
+                move |binding| Ok(Binding::new(core,
+                                               Some(&normalize_label(label)),
+                                               binding)))
+        }).collect();
+
+        let r: Result<Vec<Result<Binding>>> =
+            self.core.borrow_mut().run(futures::future::join_all(requests));
+        r?.into_iter().collect()
     }
 
     /// Imports a key into the store.
@@ -425,12 +649,17 @@ impl Store {
         request.get().set_label(label);
         request.get().set_fingerprint(fingerprint.to_hex().as_ref());
         let binding = make_request!(self.core.borrow_mut(), request)?;
-        let binding = Binding::new(self.core.clone(), Some(label), binding);
+        let binding = Binding::new(self.core.clone(),
+                                   Some(&normalize_label(label)), binding);
         binding.import(tpk)
     }
 
     /// Returns the binding for the given label.
     ///
+    /// The label is normalized, see [`normalize_label`].
+    ///
+    ///   [`normalize_label`]: fn.normalize_label.html
+    ///
     /// # Example
     ///
     /// ```
@@ -460,7 +689,54 @@ impl Store {
         let mut request = self.store.lookup_request();
         request.get().set_label(label);
         let binding = make_request!(self.core.borrow_mut(), request)?;
-        Ok(Binding::new(self.core.clone(), Some(label), binding))
+        Ok(Binding::new(self.core.clone(), Some(&normalize_label(label)),
+                        binding))
+    }
+
+    /// Looks up a binding, and returns the status of its key.
+    ///
+    /// This is like `Store::lookup`, but also returns whether the
+    /// key is expired, revoked, or has not been refreshed recently,
+    /// without fetching and evaluating the key.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::TPK;
+    /// # use openpgp::parse::Parse;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// # let tpk = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/testy.pgp")).unwrap();
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// store.import("Testy McTestface", &tpk)?;
+    /// let (binding, status) = store.lookup_with_status("Testy McTestface")?;
+    /// if status.revoked || status.expired {
+    ///     eprintln!("Warning: {} is not usable", binding.label()?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn lookup_with_status(&self, label: &str)
+                              -> Result<(Binding, KeyStatus)> {
+        let mut request = self.store.lookup_request();
+        request.get().set_label(label);
+        let (binding, status) = make_request_map_response!(
+            self.core.borrow_mut(), request,
+            |binding, r: node::store::lookup_results::Reader|
+            Ok((binding, KeyStatus::new(r.get_status()?))))?;
+        Ok((Binding::new(self.core.clone(), Some(&normalize_label(label)),
+                         binding),
+            status))
     }
 
     /// Looks up a key by (Sub)KeyID.
@@ -543,66 +819,65 @@ impl Store {
         make_request_map!(self.core.borrow_mut(), request, |_| Ok(()))
     }
 
-    /// Lists all bindings.
-    pub fn iter(&self) -> Result<BindingIter> {
-        let request = self.store.iter_request();
-        let iter = make_request!(self.core.borrow_mut(), request)?;
-        Ok(BindingIter{core: self.core.clone(), iter: iter})
-    }
-
-    /// Lists all log entries related to this store.
-    pub fn log(&self) -> Result<LogIter> {
-        let request = self.store.log_request();
-        let iter = make_request!(self.core.borrow_mut(), request)?;
-        Ok(LogIter{core: self.core.clone(), iter: iter})
-    }
-}
+    /// Deletes the bindings with the given labels.
+    ///
+    /// Like `Store::add_bindings`, this sends all requests at once.
+    /// If any of the bindings cannot be deleted, an error is
+    /// returned.  Note that the other bindings may have been deleted
+    /// nevertheless.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::Fingerprint;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// let b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+    /// let c = Fingerprint::from_bytes(b"cccccccccccccccccccc");
+    /// store.add_bindings(&[("Mister B.", &b), ("Mister C.", &c)])?;
+    /// store.delete_bindings(&["Mister B.", "Mister C."])?;
+    /// assert_eq!(store.iter()?.count(), 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn delete_bindings(&self, labels: &[&str]) -> Result<()> {
+        let requests: Vec<_> = labels.iter().map(|&label| {
+            let mut request = self.store.lookup_request();
+            request.get().set_label(label);
+            send_request_map!(request, |binding| Ok(binding))
+        }).collect();
 
-/// Makes a stats request and parses the result.
-macro_rules! make_stats_request {
-    ( $core: expr, $request: expr ) => {{
-        make_request_map!(
-            $core, $request,
-            |s: node::stats::Reader| Ok(Stats{
-                created: from_unix(s.get_created()),
-                updated: from_unix(s.get_updated()),
-                encryption: Stamps::new(
-                    s.get_encryption_count(),
-                    from_unix(s.get_encryption_first()),
-                    from_unix(s.get_encryption_last())),
-                verification: Stamps::new(
-                    s.get_verification_count(),
-                    from_unix(s.get_verification_first()),
-                    from_unix(s.get_verification_last())),
-            }))
-    }}
-}
+        let r: Result<Vec<Result<node::binding::Client>>> =
+            self.core.borrow_mut().run(futures::future::join_all(requests));
+        let bindings = r?.into_iter().collect::<Result<Vec<_>>>()?;
 
-/// Represents an entry in a Store.
-///
-/// Stores map labels to TPKs.  A `Binding` represents a pair in this
-/// relation.  We make this explicit because we associate metadata
-/// with these pairs.
-pub struct Binding {
-    label: Option<String>,
-    core: Rc<RefCell<Core>>,
-    binding: node::binding::Client,
-}
+        let requests: Vec<_> = bindings.iter().map(|binding| {
+            send_request_map!(binding.delete_request(), |_| Ok(()))
+        }).collect();
 
-impl fmt::Debug for Binding {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Binding {{ label: {:?} }}", self.label)
+        let r: Result<Vec<Result<()>>> =
+            self.core.borrow_mut().run(futures::future::join_all(requests));
+        r?.into_iter().collect()
     }
-}
 
-impl Binding {
-    fn new(core: Rc<RefCell<Core>>,
-           label: Option<&str>,
-           binding: node::binding::Client) -> Self {
-        Binding{label: label.map(|l| l.into()), core: core, binding: binding}
+    /// Lists all bindings.
+    pub fn iter(&self) -> Result<BindingIter> {
+        let request = self.store.iter_request();
+        let iter = make_request!(self.core.borrow_mut(), request)?;
+        Ok(BindingIter::new(self.core.clone(), iter))
     }
 
-    /// Returns stats for this binding.
+    /// Lists all bindings with the given trust state.
     ///
     /// # Example
     ///
@@ -620,52 +895,123 @@ impl Binding {
     /// #     .ipc_policy(IPCPolicy::Internal)
     /// #     .ephemeral().build()?;
     /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
-    ///
     /// let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
-    /// let binding = store.add("Mister B.", &fp)?;
+    /// store.add("Mister B.", &fp)?;
+    /// store.add("B4", &fp)?.set_trust(Trust::Accepted)?;
     ///
-    /// println!("Binding {:?}", binding.stats()?);
-    /// // prints:
-    /// // Binding Stats {
-    /// //     created: Some(Timespec { tv_sec: 1513704042, tv_nsec: 0 }),
-    /// //     updated: None,
-    /// //     encryption: Stamps { count: 0, first: None, last: None },
-    /// //     verification: Stamps { count: 0, first: None, last: None }
-    /// // }
+    /// let labels: Vec<String> = store.iter_by_trust(Trust::Accepted)?
+    ///     .map(|(label, _, _)| label).collect();
+    /// assert_eq!(labels, vec!["B4".to_string()]);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn stats(&self) -> Result<Stats> {
-        make_stats_request!(self.core.borrow_mut(),
-                            self.binding.stats_request())
+    pub fn iter_by_trust(&self, trust: Trust) -> Result<BindingIter> {
+        let mut request = self.store.iter_by_trust_request();
+        request.get().set_trust(trust.into());
+        let iter = make_request!(self.core.borrow_mut(), request)?;
+        Ok(BindingIter::new(self.core.clone(), iter))
     }
 
-    /// Returns the `Key` of this binding.
-    pub fn key(&self) -> Result<Key> {
-        make_request_map!(self.core.borrow_mut(),
-                          self.binding.key_request(),
-                          |tpk| Ok(Key::new(self.core.clone(), tpk)))
+    /// Sets the trust state of bindings according to GnuPG's
+    /// ownertrust.
+    ///
+    /// This allows users migrating from GnuPG to carry over their
+    /// assessments.  The trust state of every binding whose key is
+    /// listed in `list` is set as described in
+    /// [`Trust::from_ownertrust`].  Returns the number of bindings
+    /// updated.
+    ///
+    ///   [`Trust::from_ownertrust`]: enum.Trust.html#method.from_ownertrust
+    pub fn import_ownertrust(&self, list: &OwnerTrustList) -> Result<usize> {
+        let mut updated = 0;
+        for (_, fp, binding) in self.iter()? {
+            if let Some(trust) = list.get(&fp).and_then(Trust::from_ownertrust)
+            {
+                binding.set_trust(trust)?;
+                updated += 1;
+            }
+        }
+        Ok(updated)
     }
 
-    /// Returns the `Tpk` of this binding.
+    /// Lists bindings whose labels only differ before normalization.
     ///
-    /// A shortcut for `self.key()?.tpk()`.
-    pub fn tpk(&self) -> Result<TPK> {
-        self.key()?.tpk()
+    /// Bindings added before labels were normalized, e.g.
+    /// `Alice@Example.COM` and `alice@example.com`, are merged when
+    /// the database is upgraded, unless they are bound to different
+    /// keys.  Then, only one of them is found by its normalized
+    /// label.  Such bindings are returned grouped by their
+    /// normalized label, see [`normalize_label`].  Bindings bound to
+    /// the same key can be merged using [`merge_duplicates`].
+    ///
+    ///   [`normalize_label`]: fn.normalize_label.html
+    ///   [`merge_duplicates`]: #method.merge_duplicates
+    pub fn duplicates(&self) -> Result<Vec<DuplicateBindings>> {
+        let request = self.store.duplicates_request();
+        let core = self.core.clone();
+        make_request_map!(
+            self.core.borrow_mut(), request,
+            |d: node::duplicates::Reader| {
+                let mut groups = Vec::new();
+                for g in d.get_groups()?.iter() {
+                    let mut bindings = Vec::new();
+                    for r in g.get_bindings()?.iter() {
+                        bindings.push((
+                            String::from(r.get_label()?),
+                            openpgp::Fingerprint::from_hex(
+                                r.get_fingerprint()?)?,
+                            Binding::new(core.clone(), Some(r.get_label()?),
+                                         r.get_binding()?)));
+                    }
+                    groups.push(DuplicateBindings {
+                        label: g.get_label()?.into(),
+                        bindings: bindings,
+                    });
+                }
+                Ok(groups)
+            })
+    }
+
+    /// Merges all bindings whose labels normalize to `label`.
+    ///
+    /// The oldest binding is kept, and renamed to the normalized
+    /// label.  The usage statistics and log entries of the other
+    /// bindings are folded into it, and they are deleted.  If the
+    /// bindings disagree on the trust state, the merged binding is
+    /// marked as [`Trust::TOFUConflict`].
+    ///
+    /// Fails with `Error::Conflict` if the bindings are bound to
+    /// different keys, and with `Error::NotFound` if there is no such
+    /// binding.
+    ///
+    ///   [`Trust::TOFUConflict`]: enum.Trust.html#variant.TOFUConflict
+    pub fn merge_duplicates(&self, label: &str) -> Result<Binding> {
+        let mut request = self.store.merge_duplicates_request();
+        request.get().set_label(label);
+        let binding = make_request!(self.core.borrow_mut(), request)?;
+        Ok(Binding::new(self.core.clone(), Some(&normalize_label(label)),
+                        binding))
     }
 
-    /// Updates this binding with the given TPK.
-    ///
-    /// If the new key `tpk` matches the current key, i.e. they have
-    /// the same fingerprint, both keys are merged and normalized.
-    /// The returned key contains all packets known to Sequoia, and
-    /// should be used instead of `tpk`.
+    /// Lists all log entries related to this store.
+    pub fn log(&self) -> Result<LogIter> {
+        let request = self.store.log_request();
+        let iter = make_request!(self.core.borrow_mut(), request)?;
+        Ok(LogIter{core: self.core.clone(), iter: iter})
+    }
+
+    /// Returns how conflicts are resolved when importing keys.
+    pub fn merge_strategy(&self) -> Result<MergeStrategy> {
+        let request = self.store.settings_request();
+        make_request_map!(self.core.borrow_mut(), request,
+                          |s: node::settings::Reader|
+                          Ok(s.get_merge_strategy()?.into()))
+    }
+
+    /// Changes how conflicts are resolved when importing keys.
     ///
-    /// If the new key does not match the current key, and it does not
-    /// carry a valid signature from the current key, an
-    /// `Error::Conflict` is returned, and you have to resolve the
-    /// conflict, either by ignoring the new key, or by using
-    /// `Binding::rotate` to force a rotation.
+    /// The setting is stored in the backend, and applies to all
+    /// users of this store.
     ///
     /// # Example
     ///
@@ -688,43 +1034,439 @@ impl Binding {
     /// # let new = TPK::from_bytes(
     /// #     include_bytes!("../../openpgp/tests/data/keys/testy-new.pgp")).unwrap();
     /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// assert_eq!(store.merge_strategy()?, MergeStrategy::Strict);
+    /// store.set_merge_strategy(MergeStrategy::Rebind)?;
     /// store.import("Testy McTestface", &old)?;
     /// // later...
     /// let binding = store.lookup("Testy McTestface")?;
-    /// let r = binding.import(&new);
-    /// assert!(r.is_err()); // Conflict!
+    /// let r = binding.import(&new)?; // No conflict.
+    /// assert_eq!(new.fingerprint(), r.fingerprint());
     /// # Ok(())
     /// # }
     /// ```
-    pub fn import(&self, tpk: &TPK) -> Result<TPK> {
-        let mut blob = vec![];
-        tpk.serialize(&mut blob)?;
-        let mut request = self.binding.import_request();
-        request.get().set_force(false);
-        request.get().set_key(&blob);
-        make_request_map!(
-            self.core.borrow_mut(),
-            request,
-            |data| TPK::from_bytes(data).map_err(|e| e.into()))
+    pub fn set_merge_strategy(&self, strategy: MergeStrategy) -> Result<()> {
+        let mut request = self.store.set_merge_strategy_request();
+        request.get().set_strategy(strategy.into());
+        make_request_map!(self.core.borrow_mut(), request, |_| Ok(()))
     }
 
-    /// Forces a keyrotation to the given TPK.
-    ///
-    /// The current key is replaced with the new key `tpk`, even if
-    /// they do not have the same fingerprint.  If a key with the same
-    /// fingerprint as `tpk` is already in the store, is merged with
-    /// `tpk` and normalized.  The returned key contains all packets
-    /// known to Sequoia, and should be used instead of `tpk`.
-    ///
-    /// Use this function to resolve conflicts returned from
-    /// `Binding::import`.  Make sure that you have authenticated
-    /// `tpk` properly.  How to do that depends on your thread model.
-    /// You could simply ask Alice to call her communication partner
-    /// Bob and confirm that he rotated his keys.
+    /// Returns the keyserver that updated keys are published to, if
+    /// any.
+    pub fn publish_to(&self) -> Result<Option<String>> {
+        let request = self.store.settings_request();
+        make_request_map!(self.core.borrow_mut(), request,
+                          |s: node::settings::Reader| {
+                              let uri = s.get_publish_to()?;
+                              Ok(if uri == "" { None } else { Some(uri.to_string()) })
+                          })
+    }
+
+    /// Publishes keys updated in this store to the given keyserver.
     ///
-    /// # Example
+    /// If set, keys are sent to the keyserver `uri` whenever a
+    /// binding's key changes on import.  The keyserver must comply
+    /// with the store's network policy.  Publishing happens on a best
+    /// effort basis: failures do not fail the import, but are
+    /// recorded in the log.  Pass `None` to stop publishing keys.
     ///
-    /// ```
+    /// The setting is stored in the backend, and applies to all
+    /// users of this store.
+    pub fn set_publish_to(&self, uri: Option<&str>) -> Result<()> {
+        let mut request = self.store.set_publish_to_request();
+        request.get().set_uri(uri.unwrap_or(""));
+        make_request_map!(self.core.borrow_mut(), request, |_| Ok(()))
+    }
+
+    /// Returns the command that is run when a key changes, if any.
+    pub fn notify_command(&self) -> Result<Option<String>> {
+        let request = self.store.settings_request();
+        make_request_map!(self.core.borrow_mut(), request,
+                          |s: node::settings::Reader| {
+                              let command = s.get_notify_command()?;
+                              Ok(if command == "" {
+                                  None
+                              } else {
+                                  Some(command.to_string())
+                              })
+                          })
+    }
+
+    /// Runs the given command whenever a key bound in this store
+    /// changes.
+    ///
+    /// This allows integrating the store with other software, e.g.,
+    /// to invalidate a mail client's cache.  The command is run using
+    /// the shell whenever a key is updated, be it by an import or by
+    /// the periodic key refresh.  It is run once for every binding
+    /// of the key in this store.  The environment variables
+    /// `SEQUOIA_STORE_REALM`, `SEQUOIA_STORE_NAME`,
+    /// `SEQUOIA_BINDING_LABEL`, and `SEQUOIA_KEY_FINGERPRINT`
+    /// identify the binding and the key.
+    ///
+    /// The command runs asynchronously.  Failures are recorded in the
+    /// log.  Pass `None` to stop running the command.
+    ///
+    /// The setting is stored in the backend, and applies to all
+    /// users of this store.
+    pub fn set_notify_command(&self, command: Option<&str>) -> Result<()> {
+        let mut request = self.store.set_notify_command_request();
+        request.get().set_command(command.unwrap_or(""));
+        make_request_map!(self.core.borrow_mut(), request, |_| Ok(()))
+    }
+}
+
+/// Specifies how conflicts are resolved when importing keys.
+///
+/// A conflict arises if a key is imported into a binding that is
+/// bound to a key with a different fingerprint.  The strategy is a
+/// per-store setting, see `Store::set_merge_strategy`.
+/// `Binding::rotate` replaces the key regardless of the strategy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Conflicting keys are rejected.
+    ///
+    /// `Binding::import` returns `Error::Conflict`.  This is the
+    /// default.
+    Strict,
+    /// Conflicting keys replace the current key.
+    Rebind,
+    /// Conflicting keys replace the current key if their primary
+    /// key's self-signature is newer than the current key's.
+    ///
+    /// Otherwise, `Binding::import` returns `Error::Conflict`.
+    PreferNewer,
+}
+
+impl Default for MergeStrategy {
+    fn default() -> Self {
+        MergeStrategy::Strict
+    }
+}
+
+impl From<MergeStrategy> for node::MergeStrategy {
+    fn from(strategy: MergeStrategy) -> Self {
+        match strategy {
+            MergeStrategy::Strict => node::MergeStrategy::Strict,
+            MergeStrategy::Rebind => node::MergeStrategy::Rebind,
+            MergeStrategy::PreferNewer => node::MergeStrategy::PreferNewer,
+        }
+    }
+}
+
+impl From<node::MergeStrategy> for MergeStrategy {
+    fn from(strategy: node::MergeStrategy) -> Self {
+        match strategy {
+            node::MergeStrategy::Strict => MergeStrategy::Strict,
+            node::MergeStrategy::Rebind => MergeStrategy::Rebind,
+            node::MergeStrategy::PreferNewer => MergeStrategy::PreferNewer,
+        }
+    }
+}
+
+/// The user's assessment of a binding.
+///
+/// The trust state is set by the user, or by an application on the
+/// user's behalf.  The store does not interpret it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trust {
+    /// The binding has not been assessed.  This is the default.
+    Unknown,
+    /// The binding has been accepted.
+    Accepted,
+    /// The binding has been rejected.
+    Rejected,
+    /// A conflicting key has been seen for the binding, and
+    /// trust-on-first-use cannot resolve the conflict.
+    TOFUConflict,
+}
+
+impl Default for Trust {
+    fn default() -> Self {
+        Trust::Unknown
+    }
+}
+
+impl Trust {
+    /// Maps GnuPG's ownertrust to a trust state.
+    ///
+    /// GnuPG's ownertrust states how much a key's owner is trusted to
+    /// certify other keys, whereas the trust state is the user's
+    /// assessment of a binding.  Hence, only the unambiguous cases
+    /// are mapped: bindings of keys with full or ultimate ownertrust
+    /// are accepted, and bindings of disabled keys, or of keys whose
+    /// owner is never trusted, are rejected.  For all other entries,
+    /// `None` is returned.
+    pub fn from_ownertrust(entry: &OwnerTrustEntry) -> Option<Self> {
+        if entry.disabled() {
+            return Some(Trust::Rejected);
+        }
+        match entry.trust() {
+            OwnerTrust::Full | OwnerTrust::Ultimate => Some(Trust::Accepted),
+            OwnerTrust::Never => Some(Trust::Rejected),
+            _ => None,
+        }
+    }
+}
+
+impl From<Trust> for node::Trust {
+    fn from(trust: Trust) -> Self {
+        match trust {
+            Trust::Unknown => node::Trust::Unknown,
+            Trust::Accepted => node::Trust::Accepted,
+            Trust::Rejected => node::Trust::Rejected,
+            Trust::TOFUConflict => node::Trust::TofuConflict,
+        }
+    }
+}
+
+impl From<node::Trust> for Trust {
+    fn from(trust: node::Trust) -> Self {
+        match trust {
+            node::Trust::Unknown => Trust::Unknown,
+            node::Trust::Accepted => Trust::Accepted,
+            node::Trust::Rejected => Trust::Rejected,
+            node::Trust::TofuConflict => Trust::TOFUConflict,
+        }
+    }
+}
+
+/// How a binding's key was learned, in terms of Autocrypt.
+///
+/// Together with the binding's statistics, this is the peer state
+/// that Autocrypt's recommendation logic is based on.  It is set by
+/// the application importing the key.  The store does not interpret
+/// it.
+///
+/// The levels are ordered by confidence, so that an application can
+/// refuse to downgrade a binding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AutocryptLevel {
+    /// The key was not learned using Autocrypt, e.g. it was imported
+    /// manually.  This is the default.
+    Manual,
+    /// The key was learned from an `Autocrypt-Gossip` header.
+    Gossip,
+    /// The key was learned from an `Autocrypt` header of a message
+    /// sent by the peer, and the peer prefers encryption
+    /// (`prefer-encrypt=mutual`).
+    Mutual,
+}
+
+impl Default for AutocryptLevel {
+    fn default() -> Self {
+        AutocryptLevel::Manual
+    }
+}
+
+impl From<AutocryptLevel> for node::AutocryptLevel {
+    fn from(level: AutocryptLevel) -> Self {
+        match level {
+            AutocryptLevel::Manual => node::AutocryptLevel::Manual,
+            AutocryptLevel::Gossip => node::AutocryptLevel::Gossip,
+            AutocryptLevel::Mutual => node::AutocryptLevel::Mutual,
+        }
+    }
+}
+
+impl From<node::AutocryptLevel> for AutocryptLevel {
+    fn from(level: node::AutocryptLevel) -> Self {
+        match level {
+            node::AutocryptLevel::Manual => AutocryptLevel::Manual,
+            node::AutocryptLevel::Gossip => AutocryptLevel::Gossip,
+            node::AutocryptLevel::Mutual => AutocryptLevel::Mutual,
+        }
+    }
+}
+
+/// Makes a stats request and parses the result.
+macro_rules! make_stats_request {
+    ( $core: expr, $request: expr ) => {{
+        make_request_map!(
+            $core, $request,
+            |s: node::stats::Reader| Ok(Stats{
+                created: from_unix(s.get_created()),
+                updated: from_unix(s.get_updated()),
+                encryption: Stamps::new(
+                    s.get_encryption_count(),
+                    from_unix(s.get_encryption_first()),
+                    from_unix(s.get_encryption_last())),
+                verification: Stamps::new(
+                    s.get_verification_count(),
+                    from_unix(s.get_verification_first()),
+                    from_unix(s.get_verification_last())),
+                expiry: from_unix(s.get_expiry()),
+            }))
+    }}
+}
+
+/// Makes a usage request and parses the result.
+macro_rules! make_usage_request {
+    ( $core: expr, $request: expr ) => {{
+        make_request_map!(
+            $core, $request,
+            |u: node::usage::Reader| {
+                let mut weeks = Vec::new();
+                for w in u.get_weeks()?.iter() {
+                    weeks.push(WeeklyUsage {
+                        start: Timespec::new(w.get_start(), 0),
+                        encryption_count: w.get_encryption_count() as usize,
+                        verification_count: w.get_verification_count() as usize,
+                    });
+                }
+                Ok(weeks)
+            })
+    }}
+}
+
+/// Represents an entry in a Store.
+///
+/// Stores map labels to TPKs.  A `Binding` represents a pair in this
+/// relation.  We make this explicit because we associate metadata
+/// with these pairs.
+pub struct Binding {
+    label: Option<String>,
+    core: Rc<RefCell<RpcCore>>,
+    binding: node::binding::Client,
+}
+
+impl fmt::Debug for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Binding {{ label: {:?} }}", self.label)
+    }
+}
+
+impl Binding {
+    fn new(core: Rc<RefCell<RpcCore>>,
+           label: Option<&str>,
+           binding: node::binding::Client) -> Self {
+        Binding{label: label.map(|l| l.into()), core: core, binding: binding}
+    }
+
+    /// Returns stats for this binding.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::Fingerprint;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    ///
+    /// let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+    /// let binding = store.add("Mister B.", &fp)?;
+    ///
+    /// println!("Binding {:?}", binding.stats()?);
+    /// // prints:
+    /// // Binding Stats {
+    /// //     created: Some(Timespec { tv_sec: 1513704042, tv_nsec: 0 }),
+    /// //     updated: None,
+    /// //     encryption: Stamps { count: 0, first: None, last: None },
+    /// //     verification: Stamps { count: 0, first: None, last: None },
+    /// //     expiry: None
+    /// // }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn stats(&self) -> Result<Stats> {
+        make_stats_request!(self.core.borrow_mut(),
+                            self.binding.stats_request())
+    }
+
+    /// Returns the `Key` of this binding.
+    pub fn key(&self) -> Result<Key> {
+        make_request_map!(self.core.borrow_mut(),
+                          self.binding.key_request(),
+                          |tpk| Ok(Key::new(self.core.clone(), tpk)))
+    }
+
+    /// Returns the `Tpk` of this binding.
+    ///
+    /// A shortcut for `self.key()?.tpk()`.
+    pub fn tpk(&self) -> Result<TPK> {
+        self.key()?.tpk()
+    }
+
+    /// Updates this binding with the given TPK.
+    ///
+    /// If the new key `tpk` matches the current key, i.e. they have
+    /// the same fingerprint, both keys are merged and normalized.
+    /// The returned key contains all packets known to Sequoia, and
+    /// should be used instead of `tpk`.
+    ///
+    /// If the new key does not match the current key, and it does not
+    /// carry a valid signature from the current key, the conflict is
+    /// resolved according to the store's `MergeStrategy`.  By
+    /// default, an `Error::Conflict` is returned, and you have to
+    /// resolve the conflict, either by ignoring the new key, or by
+    /// using `Binding::rotate` to force a rotation.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # #[macro_use] extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::TPK;
+    /// # use openpgp::parse::Parse;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// # let old = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/testy.pgp")).unwrap();
+    /// # let new = TPK::from_bytes(
+    /// #     include_bytes!("../../openpgp/tests/data/keys/testy-new.pgp")).unwrap();
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// store.import("Testy McTestface", &old)?;
+    /// // later...
+    /// let binding = store.lookup("Testy McTestface")?;
+    /// let r = binding.import(&new);
+    /// assert!(r.is_err()); // Conflict!
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn import(&self, tpk: &TPK) -> Result<TPK> {
+        let mut blob = vec![];
+        tpk.serialize(&mut blob)?;
+        let mut request = self.binding.import_request();
+        request.get().set_force(false);
+        request.get().set_key(&blob);
+        make_request_map!(
+            self.core.borrow_mut(),
+            request,
+            |data| TPK::from_bytes(data).map_err(|e| e.into()))
+    }
+
+    /// Forces a keyrotation to the given TPK.
+    ///
+    /// The current key is replaced with the new key `tpk`, even if
+    /// they do not have the same fingerprint.  If a key with the same
+    /// fingerprint as `tpk` is already in the store, is merged with
+    /// `tpk` and normalized.  The returned key contains all packets
+    /// known to Sequoia, and should be used instead of `tpk`.
+    ///
+    /// Use this function to resolve conflicts returned from
+    /// `Binding::import`.  Make sure that you have authenticated
+    /// `tpk` properly.  How to do that depends on your thread model.
+    /// You could simply ask Alice to call her communication partner
+    /// Bob and confirm that he rotated his keys.
+    ///
+    /// # Example
+    ///
+    /// ```
     /// # extern crate sequoia_openpgp as openpgp;
     /// # #[macro_use] extern crate sequoia_core;
     /// # extern crate sequoia_store;
@@ -810,6 +1552,44 @@ impl Binding {
             self.binding.register_verification_request())
     }
 
+    /// Returns the number of encryptions and verifications per week.
+    ///
+    /// The result covers the last `weeks` weeks, oldest first.  The
+    /// last week ends now.  At most ten years are returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::Fingerprint;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+    /// let binding = store.add("Mister B.", &fp)?;
+    ///
+    /// let usage = binding.usage(52)?;
+    /// assert_eq!(usage.len(), 52);
+    /// if usage.iter().all(|w| w.encryption_count == 0) {
+    ///     println!("You have not encrypted to Mister B. in a year.");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn usage(&self, weeks: u32) -> Result<Vec<WeeklyUsage>> {
+        let mut request = self.binding.usage_request();
+        request.get().set_weeks(weeks);
+        make_usage_request!(self.core.borrow_mut(), request)
+    }
+
     /// Lists all log entries related to this binding.
     pub fn log(&self) -> Result<LogIter> {
         let request = self.binding.log_request();
@@ -817,6 +1597,75 @@ impl Binding {
         Ok(LogIter{core: self.core.clone(), iter: iter})
     }
 
+    /// Returns the status of this binding's key.
+    pub fn status(&self) -> Result<KeyStatus> {
+        make_request_map!(self.core.borrow_mut(),
+                          self.binding.status_request(),
+                          |s| Ok(KeyStatus::new(s)))
+    }
+
+    /// Returns this binding's trust state.
+    pub fn trust(&self) -> Result<Trust> {
+        let request = self.binding.trust_request();
+        make_request_map!(self.core.borrow_mut(), request,
+                          |t: node::trust_state::Reader|
+                          Ok(t.get_trust()?.into()))
+    }
+
+    /// Sets this binding's trust state.
+    ///
+    /// See `Store::iter_by_trust` for an example.
+    pub fn set_trust(&self, trust: Trust) -> Result<()> {
+        let mut request = self.binding.set_trust_request();
+        request.get().set_trust(trust.into());
+        make_request_map!(self.core.borrow_mut(), request, |_| Ok(()))
+    }
+
+    /// Returns how this binding's key was learned.
+    pub fn autocrypt_level(&self) -> Result<AutocryptLevel> {
+        let request = self.binding.autocrypt_level_request();
+        make_request_map!(self.core.borrow_mut(), request,
+                          |l: node::autocrypt_level_state::Reader|
+                          Ok(l.get_level()?.into()))
+    }
+
+    /// Records how this binding's key was learned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate sequoia_openpgp as openpgp;
+    /// # extern crate sequoia_core;
+    /// # extern crate sequoia_store;
+    /// # use openpgp::Fingerprint;
+    /// # use sequoia_core::{Context, NetworkPolicy, IPCPolicy};
+    /// # use sequoia_store::*;
+    /// # fn main() { f().unwrap(); }
+    /// # fn f() -> Result<()> {
+    /// # let ctx = Context::configure()
+    /// #     .network_policy(NetworkPolicy::Offline)
+    /// #     .ipc_policy(IPCPolicy::Internal)
+    /// #     .ephemeral().build()?;
+    /// let store = Store::open(&ctx, REALM_CONTACTS, "default")?;
+    /// let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+    /// let binding = store.add("Mister B.", &fp)?;
+    /// assert_eq!(binding.autocrypt_level()?, AutocryptLevel::Manual);
+    ///
+    /// // Do not downgrade a binding when gossip is received.
+    /// let level = AutocryptLevel::Gossip;
+    /// if binding.autocrypt_level()? < level {
+    ///     binding.set_autocrypt_level(level)?;
+    /// }
+    /// assert_eq!(binding.autocrypt_level()?, AutocryptLevel::Gossip);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_autocrypt_level(&self, level: AutocryptLevel) -> Result<()> {
+        let mut request = self.binding.set_autocrypt_level_request();
+        request.get().set_level(level.into());
+        make_request_map!(self.core.borrow_mut(), request, |_| Ok(()))
+    }
+
     /// Gets this binding's label.
     pub fn label(&self) -> Result<String> {
         if let Some(ref label) = self.label {
@@ -835,7 +1684,7 @@ impl Binding {
 /// A `Key` is a handle to a stored TPK.  We make this explicit
 /// because we associate metadata with TPKs.
 pub struct Key {
-    core: Rc<RefCell<Core>>,
+    core: Rc<RefCell<RpcCore>>,
     key: node::key::Client,
 }
 
@@ -846,7 +1695,7 @@ impl fmt::Debug for Key {
 }
 
 impl Key {
-    fn new(core: Rc<RefCell<Core>>, key: node::key::Client) -> Self {
+    fn new(core: Rc<RefCell<RpcCore>>, key: node::key::Client) -> Self {
         Key{core: core, key: key}
     }
 
@@ -916,12 +1765,90 @@ impl Key {
             |data| TPK::from_bytes(data).map_err(|e| e.into()))
     }
 
+    /// Returns the status of this key.
+    pub fn status(&self) -> Result<KeyStatus> {
+        make_request_map!(self.core.borrow_mut(),
+                          self.key.status_request(),
+                          |s| Ok(KeyStatus::new(s)))
+    }
+
+    /// Returns the number of encryptions and verifications per week.
+    ///
+    /// This counts the uses of all bindings of this key.  See
+    /// `Binding::usage` for details.
+    pub fn usage(&self, weeks: u32) -> Result<Vec<WeeklyUsage>> {
+        let mut request = self.key.usage_request();
+        request.get().set_weeks(weeks);
+        make_usage_request!(self.core.borrow_mut(), request)
+    }
+
     /// Lists all log entries related to this key.
     pub fn log(&self) -> Result<LogIter> {
         let request = self.key.log_request();
         let iter = make_request!(self.core.borrow_mut(), request)?;
         Ok(LogIter{core: self.core.clone(), iter: iter})
     }
+
+    /// Lists the quarantined updates of this key, oldest first.
+    ///
+    /// When refreshing keys from the network, updates that look
+    /// suspicious, e.g. because they flood the key with third-party
+    /// certifications or downgrade its algorithms, are not merged.
+    /// Instead, they are quarantined until they are either accepted
+    /// using [`accept_quarantined`], or rejected using
+    /// [`reject_quarantined`].
+    ///
+    ///   [`accept_quarantined`]: #method.accept_quarantined
+    ///   [`reject_quarantined`]: #method.reject_quarantined
+    pub fn quarantined(&self) -> Result<Vec<QuarantinedUpdate>> {
+        make_request_map!(
+            self.core.borrow_mut(),
+            self.key.quarantine_request(),
+            |q: node::quarantine::Reader| {
+                let mut updates = Vec::new();
+                for u in q.get_updates()?.iter() {
+                    updates.push(QuarantinedUpdate {
+                        id: u.get_id(),
+                        created: Timespec::new(u.get_created(), 0),
+                        tpk: TPK::from_bytes(u.get_key()?)?,
+                        reason: u.get_reason()?.into(),
+                    });
+                }
+                Ok(updates)
+            })
+    }
+
+    /// Merges the quarantined update `id` into this key.
+    ///
+    /// The update is removed from the quarantine.  Returns the merged
+    /// key.
+    pub fn accept_quarantined(&self, id: i64) -> Result<TPK> {
+        let mut request = self.key.accept_quarantined_request();
+        request.get().set_id(id);
+        make_request_map!(
+            self.core.borrow_mut(),
+            request,
+            |data| TPK::from_bytes(data).map_err(|e| e.into()))
+    }
+
+    /// Discards the quarantined update `id`.
+    pub fn reject_quarantined(&self, id: i64) -> Result<()> {
+        let mut request = self.key.reject_quarantined_request();
+        request.get().set_id(id);
+        make_request_map!(self.core.borrow_mut(), request, |_| Ok(()))
+    }
+
+    /// Schedules this key for an update.
+    ///
+    /// Instead of waiting for its regular turn, the key is updated
+    /// by the housekeeping service within minutes, unless key
+    /// updates are paused.  This does not wait for the update.  The
+    /// outcome is recorded in the key's log.
+    pub fn refresh(&self) -> Result<()> {
+        make_request_map!(self.core.borrow_mut(),
+                          self.key.refresh_request(),
+                          |_| Ok(()))
+    }
 }
 
 
@@ -952,6 +1879,12 @@ pub struct Stats {
 
     /// Records counters and timestamps of verifications.
     pub verification: Stamps,
+
+    /// Records when the key or one of its subkeys expires next.
+    ///
+    /// This is only set for keys, not for bindings.  Expired and
+    /// revoked subkeys are not considered.
+    pub expiry: Option<Timespec>,
 }
 
 /// Represents a log entry.
@@ -1042,25 +1975,150 @@ pub struct Stamps {
     /// Records the time when this has been used first.
     pub first:  Option<Timespec>,
 
-    /// Records the time when this has been used last.
-    pub last: Option<Timespec>,
+    /// Records the time when this has been used last.
+    pub last: Option<Timespec>,
+}
+
+impl Stamps {
+    fn new(count: i64, first: Option<Timespec>, last: Option<Timespec>) -> Self {
+        Stamps {
+            count: count as usize,
+            first: first,
+            last: last,
+        }
+    }
+}
+
+/// The validity and freshness of a stored key.
+///
+/// The status is computed by the backend at the time of the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyStatus {
+    /// Whether no key has been stored yet.
+    ///
+    /// This is the case for bindings created using `Store::add`
+    /// before the key has been imported or fetched.
+    pub missing: bool,
+
+    /// Whether the key is expired.
+    pub expired: bool,
+
+    /// Whether the key is revoked.
+    pub revoked: bool,
+
+    /// Whether the key has not been refreshed recently.
+    ///
+    /// Keys are periodically refreshed from the network, unless the
+    /// store's network policy forbids it.
+    pub stale: bool,
+
+    /// Records the time the key was last refreshed.
+    pub updated: Option<Timespec>,
+}
+
+impl KeyStatus {
+    fn new(s: node::key_status::Reader) -> Self {
+        KeyStatus {
+            missing: s.get_missing(),
+            expired: s.get_expired(),
+            revoked: s.get_revoked(),
+            stale: s.get_stale(),
+            updated: from_unix(s.get_updated()),
+        }
+    }
+
+    /// Returns whether the key is present, valid, and fresh.
+    pub fn is_ok(&self) -> bool {
+        ! (self.missing || self.expired || self.revoked || self.stale)
+    }
+}
+
+/// An update of a stored key that has been quarantined.
+///
+/// See [`Key::quarantined`] for details.
+///
+///   [`Key::quarantined`]: struct.Key.html#method.quarantined
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuarantinedUpdate {
+    /// Identifies the update.
+    pub id: i64,
+
+    /// Records when the update was quarantined.
+    pub created: Timespec,
+
+    /// The update as fetched from the network.
+    pub tpk: TPK,
+
+    /// Why the update was quarantined.
+    pub reason: String,
+}
+
+/// Bindings whose labels are the same after normalization.
+///
+/// See [`Store::duplicates`].
+///
+///   [`Store::duplicates`]: struct.Store.html#method.duplicates
+#[derive(Debug)]
+pub struct DuplicateBindings {
+    /// The normalized label.
+    pub label: String,
+
+    /// The bindings, oldest first.
+    pub bindings: Vec<(String, Fingerprint, Binding)>,
+}
+
+/// Whether stored keys are updated from the network.
+///
+/// See [`Store::pause_refresh`].
+///
+///   [`Store::pause_refresh`]: struct.Store.html#method.pause_refresh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshPause {
+    /// Keys are updated.
+    Running,
+
+    /// Key updates are paused until the given time.
+    Until(Timespec),
+
+    /// Key updates are paused until they are resumed.
+    Indefinitely,
+}
+
+/// When stored keys are updated from the network.
+///
+/// See [`Store::refresh_schedule`].
+///
+///   [`Store::refresh_schedule`]: struct.Store.html#method.refresh_schedule
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshSchedule {
+    /// Whether key updates are paused.
+    pub pause: RefreshPause,
+
+    /// The keys that are updated, and when they are updated next,
+    /// soonest first.
+    ///
+    /// `None` means that the key is updated as soon as possible.
+    pub keys: Vec<(Fingerprint, Option<Timespec>)>,
 }
 
-impl Stamps {
-    fn new(count: i64, first: Option<Timespec>, last: Option<Timespec>) -> Self {
-        Stamps {
-            count: count as usize,
-            first: first,
-            last: last,
-        }
-    }
+/// Counts how often a binding or key was used during one week.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeeklyUsage {
+    /// Records the start of the week.
+    pub start: Timespec,
+
+    /// Counts the encryptions during the week.
+    pub encryption_count: usize,
+
+    /// Counts the verifications during the week.
+    pub verification_count: usize,
 }
 
 /* Iterators.  */
 
 /// Iterates over stores.
 pub struct StoreIter {
-    core: Rc<RefCell<Core>>,
+    core: Rc<RefCell<RpcCore>>,
     iter: node::store_iter::Client,
 }
 
@@ -1083,55 +2141,159 @@ impl Iterator for StoreIter {
     }
 }
 
+/// The number of items `BindingIter` and `KeyIter` fetch at once,
+/// unless changed using `set_batch_size`.
+const DEFAULT_BATCH_SIZE: usize = 32;
+
 /// Iterates over bindings in a store.
+///
+/// To reduce the number of round trips to the backend, bindings are
+/// fetched in batches.  See [`set_batch_size`].
+///
+///   [`set_batch_size`]: #method.set_batch_size
 pub struct BindingIter {
-    core: Rc<RefCell<Core>>,
+    core: Rc<RefCell<RpcCore>>,
     iter: node::binding_iter::Client,
+    batch_size: usize,
+    buffer: VecDeque<(String, openpgp::Fingerprint, Binding)>,
+    exhausted: bool,
+}
+
+impl BindingIter {
+    fn new(core: Rc<RefCell<RpcCore>>, iter: node::binding_iter::Client) -> Self {
+        BindingIter {
+            core: core,
+            iter: iter,
+            batch_size: DEFAULT_BATCH_SIZE,
+            buffer: VecDeque::new(),
+            exhausted: false,
+        }
+    }
+
+    /// Sets the number of bindings fetched from the backend at once.
+    ///
+    /// A batch size of zero is treated like one, larger batch sizes
+    /// than `u32::MAX` like `u32::MAX`.
+    pub fn set_batch_size(&mut self, n: usize) {
+        self.batch_size = ::std::cmp::min(::std::cmp::max(n, 1),
+                                          u32::max_value() as usize);
+    }
+
+    /// Fetches the next batch of bindings into the buffer.
+    fn fetch(&mut self) -> Result<()> {
+        let mut request = self.iter.next_batch_request();
+        request.get().set_count(self.batch_size as u32);
+        let core = self.core.clone();
+        let items = make_request_map!(
+            self.core.borrow_mut(), request,
+            |r: node::binding_iter::batch::Reader| {
+                let mut items = Vec::new();
+                for r in r.get_items()?.iter() {
+                    items.push((
+                        String::from(r.get_label()?),
+                        openpgp::Fingerprint::from_hex(r.get_fingerprint()?)?,
+                        Binding::new(core.clone(), Some(r.get_label()?),
+                                     r.get_binding()?)));
+                }
+                Ok(items)
+            })?;
+
+        if items.is_empty() {
+            self.exhausted = true;
+        }
+        self.buffer.extend(items);
+        Ok(())
+    }
 }
 
 impl Iterator for BindingIter {
     type Item = (String, openpgp::Fingerprint, Binding);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let request = self.iter.next_request();
-        let doit = || {
-            make_request_map!(
-                self.core.borrow_mut(), request,
-                |r: node::binding_iter::item::Reader|
-                Ok((String::from(r.get_label()?),
-                    openpgp::Fingerprint::from_hex(r.get_fingerprint()?).unwrap(),
-                    Binding::new(self.core.clone(), Some(r.get_label()?),
-                                 r.get_binding()?))))
-        };
-        doit().ok()
+        if self.buffer.is_empty() && ! self.exhausted {
+            if self.fetch().is_err() {
+                self.exhausted = true;
+            }
+        }
+        self.buffer.pop_front()
     }
 }
 
 /// Iterates over keys in the common key pool.
+///
+/// To reduce the number of round trips to the backend, keys are
+/// fetched in batches.  See [`set_batch_size`].
+///
+///   [`set_batch_size`]: #method.set_batch_size
 pub struct KeyIter {
-    core: Rc<RefCell<Core>>,
+    core: Rc<RefCell<RpcCore>>,
     iter: node::key_iter::Client,
+    batch_size: usize,
+    buffer: VecDeque<(openpgp::Fingerprint, Key)>,
+    exhausted: bool,
+}
+
+impl KeyIter {
+    fn new(core: Rc<RefCell<RpcCore>>, iter: node::key_iter::Client) -> Self {
+        KeyIter {
+            core: core,
+            iter: iter,
+            batch_size: DEFAULT_BATCH_SIZE,
+            buffer: VecDeque::new(),
+            exhausted: false,
+        }
+    }
+
+    /// Sets the number of keys fetched from the backend at once.
+    ///
+    /// A batch size of zero is treated like one, larger batch sizes
+    /// than `u32::MAX` like `u32::MAX`.
+    pub fn set_batch_size(&mut self, n: usize) {
+        self.batch_size = ::std::cmp::min(::std::cmp::max(n, 1),
+                                          u32::max_value() as usize);
+    }
+
+    /// Fetches the next batch of keys into the buffer.
+    fn fetch(&mut self) -> Result<()> {
+        let mut request = self.iter.next_batch_request();
+        request.get().set_count(self.batch_size as u32);
+        let core = self.core.clone();
+        let items = make_request_map!(
+            self.core.borrow_mut(), request,
+            |r: node::key_iter::batch::Reader| {
+                let mut items = Vec::new();
+                for r in r.get_items()?.iter() {
+                    items.push((
+                        openpgp::Fingerprint::from_hex(r.get_fingerprint()?)?,
+                        Key::new(core.clone(), r.get_key()?)));
+                }
+                Ok(items)
+            })?;
+
+        if items.is_empty() {
+            self.exhausted = true;
+        }
+        self.buffer.extend(items);
+        Ok(())
+    }
 }
 
 impl Iterator for KeyIter {
     type Item = (openpgp::Fingerprint, Key);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let request = self.iter.next_request();
-        let doit = || {
-            make_request_map!(
-                self.core.borrow_mut(), request,
-                |r: node::key_iter::item::Reader|
-                Ok((openpgp::Fingerprint::from_hex(r.get_fingerprint()?).unwrap(),
-                    Key::new(self.core.clone(), r.get_key()?))))
-        };
-        doit().ok()
+        if self.buffer.is_empty() && ! self.exhausted {
+            if self.fetch().is_err() {
+                self.exhausted = true;
+            }
+        }
+        self.buffer.pop_front()
     }
 }
 
 /// Iterates over logs.
 pub struct LogIter {
-    core: Rc<RefCell<Core>>,
+    core: Rc<RefCell<RpcCore>>,
     iter: node::log_iter::Client,
 }
 
@@ -1188,6 +2350,8 @@ impl From<node::Error> for failure::Error {
                 core::Error::NetworkPolicyViolation(core::NetworkPolicy::Encrypted).into(),
             node::Error::NetworkPolicyViolationInsecure =>
                 core::Error::NetworkPolicyViolation(core::NetworkPolicy::Insecure).into(),
+            node::Error::Timeout => Error::Timeout.into(),
+            node::Error::PermissionDenied => Error::PermissionDenied.into(),
         }
     }
 }
@@ -1214,6 +2378,12 @@ pub enum Error {
     /// A fingerprint is malformed.
     #[fail(display = "Malformed fingerprint")]
     MalformedFingerprint,
+    /// The backend did not answer in time.
+    #[fail(display = "Request timed out")]
+    Timeout,
+    /// The peer is not allowed to perform the operation.
+    #[fail(display = "Permission denied")]
+    PermissionDenied,
     /// A `capnp::Error` occurred.
     #[fail(display = "Internal RPC error")]
     RpcError(capnp::Error),
@@ -1275,6 +2445,31 @@ mod test {
         assert_eq!(tpk.fingerprint(), tpk_retrieved.fingerprint());
     }
 
+    #[test]
+    fn import_unchanged() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let tpk = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        let binding = store.add("Mr. McTestface", &tpk.fingerprint()).unwrap();
+        assert_eq!(binding.import(&tpk).unwrap(), tpk);
+
+        // The same key again.
+        assert_eq!(binding.import(&tpk).unwrap(), tpk);
+
+        // A subset of the key, i.e. without the subkeys.
+        let primary_only = TPK::from_packet_pile(
+            tpk.clone().into_packets()
+                .take_while(|p| p.tag() != openpgp::packet::Tag::PublicSubkey)
+                .collect::<Vec<_>>().into()).unwrap();
+        assert_eq!(primary_only.subkeys().count(), 0);
+        assert_eq!(binding.import(&primary_only).unwrap(), tpk);
+        assert_eq!(binding.key().unwrap().import(&primary_only).unwrap(), tpk);
+    }
+
     #[test]
     fn key_not_found() {
         let ctx = core::Context::configure()
@@ -1304,6 +2499,92 @@ mod test {
                       = r.err().unwrap().downcast::<Error>().unwrap());
     }
 
+    #[test]
+    fn merge_strategy_prefer_newer() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        store.set_merge_strategy(MergeStrategy::PreferNewer).unwrap();
+        assert_eq!(store.merge_strategy().unwrap(),
+                   MergeStrategy::PreferNewer);
+
+        let old = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        let new = TPK::from_bytes(bytes!("testy-new.pgp")).unwrap();
+        store.import("Mr. McTestface", &new).unwrap();
+        let binding = store.lookup("Mr. McTestface").unwrap();
+
+        // The old key's self-signature is older.
+        let r = binding.import(&old);
+        assert_match!(Error::Conflict
+                      = r.err().unwrap().downcast::<Error>().unwrap());
+
+        binding.rotate(&old).unwrap();
+        let r = binding.import(&new).unwrap();
+        assert_eq!(r.fingerprint(), new.fingerprint());
+        assert_eq!(binding.tpk().unwrap().fingerprint(), new.fingerprint());
+    }
+
+    #[test]
+    fn publish_to() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        assert_eq!(store.publish_to().unwrap(), None);
+
+        // Offline stores cannot publish keys.
+        let r = store.set_publish_to(Some("hkps://keys.openpgp.org"));
+        assert_match!(core::Error::NetworkPolicyViolation(_)
+                      = r.err().unwrap().downcast::<core::Error>().unwrap());
+        assert_eq!(store.publish_to().unwrap(), None);
+
+        store.set_publish_to(None).unwrap();
+        assert_eq!(store.publish_to().unwrap(), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn notify_command() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        assert_eq!(store.notify_command().unwrap(), None);
+
+        let notified = ctx.home().join("notified");
+        let command = format!(
+            "echo \"$SEQUOIA_BINDING_LABEL $SEQUOIA_KEY_FINGERPRINT\" > {:?}",
+            notified);
+        store.set_notify_command(Some(&command)).unwrap();
+        assert_eq!(store.notify_command().unwrap(), Some(command));
+
+        let tpk = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        store.import("Mr. McTestface", &tpk).unwrap();
+
+        // The command runs asynchronously.
+        let expected = format!("Mr. McTestface {}\n", tpk.fingerprint().to_hex());
+        let mut content = String::new();
+        for _ in 0..100 {
+            content = ::std::fs::read_to_string(&notified)
+                .unwrap_or_default();
+            if content == expected {
+                break;
+            }
+            ::std::thread::sleep(::std::time::Duration::from_millis(100));
+        }
+        assert_eq!(content, expected);
+
+        store.set_notify_command(None).unwrap();
+        assert_eq!(store.notify_command().unwrap(), None);
+    }
+
     #[test]
     fn add_then_add_different_key() {
         let ctx = core::Context::configure()
@@ -1446,6 +2727,195 @@ mod test {
         assert_eq!(stats1.verification.first, stats1.verification.last);
     }
 
+    #[test]
+    fn key_status() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        store.add("Mister B.", &fp).unwrap();
+        let (_, status) = store.lookup_with_status("Mister B.").unwrap();
+        assert!(status.missing);
+        assert!(! status.is_ok());
+
+        let tpk = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        store.import("Testy McTestface", &tpk).unwrap();
+        let (binding, status) =
+            store.lookup_with_status("Testy McTestface").unwrap();
+        assert!(! status.missing);
+        assert!(! status.revoked);
+        assert_eq!(binding.status().unwrap(), status);
+        assert_eq!(binding.key().unwrap().status().unwrap(), status);
+    }
+
+    #[test]
+    fn quarantine() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let tpk = TPK::from_bytes(bytes!("testy.pgp")).unwrap();
+        store.import("Testy McTestface", &tpk).unwrap();
+        let key = store.lookup("Testy McTestface").unwrap().key().unwrap();
+        assert_eq!(key.quarantined().unwrap(), vec![]);
+
+        let r = key.accept_quarantined(1);
+        assert_match!(Error::NotFound
+                      = r.err().unwrap().downcast::<Error>().unwrap());
+        let r = key.reject_quarantined(1);
+        assert_match!(Error::NotFound
+                      = r.err().unwrap().downcast::<Error>().unwrap());
+    }
+
+    #[test]
+    fn trust() {
+        let ctx = make_some_stores();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let b = store.lookup("Mister B.").unwrap();
+        let b4 = store.lookup("B4").unwrap();
+        assert_eq!(b.trust().unwrap(), Trust::Unknown);
+        assert_eq!(store.iter_by_trust(Trust::Accepted).unwrap().count(), 0);
+        assert_eq!(store.iter_by_trust(Trust::Unknown).unwrap().count(), 2);
+
+        b4.set_trust(Trust::Accepted).unwrap();
+        b.set_trust(Trust::TOFUConflict).unwrap();
+        assert_eq!(b.trust().unwrap(), Trust::TOFUConflict);
+        assert_eq!(b4.trust().unwrap(), Trust::Accepted);
+
+        let accepted: Vec<String> = store.iter_by_trust(Trust::Accepted)
+            .unwrap().map(|(label, _, _)| label).collect();
+        assert_eq!(accepted, vec!["B4".to_string()]);
+        assert_eq!(store.iter().unwrap().count(), 2);
+    }
+
+    #[test]
+    fn timeout() {
+        let mut core = RpcCore::new().unwrap();
+        core.timeout = Some(Duration::from_millis(10));
+
+        // A request the backend never answers.
+        let r = core.run(futures::future::empty::<(), capnp::Error>());
+        assert_match!(Error::Timeout
+                      = r.err().unwrap().downcast::<Error>().unwrap());
+
+        // A request the backend answers late.
+        let late = |core: &RpcCore| {
+            Timeout::new(Duration::from_millis(100), &core.core.handle())
+                .unwrap()
+                .map_err(|e| capnp::Error::failed(e.to_string()))
+        };
+        let r = late(&core);
+        assert_match!(Error::Timeout
+                      = core.run(r).err().unwrap()
+                      .downcast::<Error>().unwrap());
+
+        // Without a deadline, we wait for it.
+        core.timeout = None;
+        let r = late(&core);
+        core.run(r).unwrap();
+    }
+
+    #[test]
+    fn set_timeout() {
+        let ctx = make_some_stores();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        assert_eq!(store.core.borrow().timeout, Some(DEFAULT_TIMEOUT));
+
+        store.set_timeout(None);
+        assert_eq!(store.core.borrow().timeout, None);
+        let b = store.lookup("Mister B.").unwrap();
+
+        // Bindings share the store's event loop, and hence the
+        // timeout.
+        store.set_timeout(Some(Duration::from_secs(10)));
+        assert_eq!(b.core.borrow().timeout, Some(Duration::from_secs(10)));
+        assert_eq!(b.trust().unwrap(), Trust::Unknown);
+    }
+
+    #[test]
+    fn import_ownertrust() {
+        let ctx = make_some_stores();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let c = Fingerprint::from_bytes(b"cccccccccccccccccccc");
+
+        // Marginal ownertrust is not mapped.
+        let mut list = OwnerTrustList::new();
+        list.insert(OwnerTrustEntry::new(b.clone(), OwnerTrust::Marginal,
+                                         false));
+        list.insert(OwnerTrustEntry::new(c, OwnerTrust::Ultimate, false));
+        assert_eq!(store.import_ownertrust(&list).unwrap(), 0);
+        assert_eq!(store.iter_by_trust(Trust::Unknown).unwrap().count(), 2);
+
+        // Both bindings refer to b.
+        list.insert(OwnerTrustEntry::new(b.clone(), OwnerTrust::Full, false));
+        assert_eq!(store.import_ownertrust(&list).unwrap(), 2);
+        assert_eq!(store.iter_by_trust(Trust::Accepted).unwrap().count(), 2);
+
+        list.insert(OwnerTrustEntry::new(b, OwnerTrust::Full, true));
+        assert_eq!(store.import_ownertrust(&list).unwrap(), 2);
+        assert_eq!(store.iter_by_trust(Trust::Rejected).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn autocrypt_level() {
+        let ctx = make_some_stores();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let b = store.lookup("Mister B.").unwrap();
+        let b4 = store.lookup("B4").unwrap();
+        assert_eq!(b.autocrypt_level().unwrap(), AutocryptLevel::Manual);
+
+        b.set_autocrypt_level(AutocryptLevel::Mutual).unwrap();
+        assert_eq!(b.autocrypt_level().unwrap(), AutocryptLevel::Mutual);
+        assert_eq!(b4.autocrypt_level().unwrap(), AutocryptLevel::Manual);
+
+        // The level is kept across lookups.
+        let b = store.lookup("Mister B.").unwrap();
+        assert_eq!(b.autocrypt_level().unwrap(), AutocryptLevel::Mutual);
+        assert!(AutocryptLevel::Gossip < AutocryptLevel::Mutual);
+    }
+
+    #[test]
+    fn usage() {
+        let ctx = make_some_stores();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let fp = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let binding = store.add("Mister B.", &fp).unwrap();
+
+        let usage = binding.usage(4).unwrap();
+        assert_eq!(usage.len(), 4);
+        assert!(usage.iter().all(|w| w.encryption_count == 0
+                                 && w.verification_count == 0));
+        assert!(usage.windows(2).all(|w| w[0].start < w[1].start));
+
+        binding.register_encryption().unwrap();
+        binding.register_encryption().unwrap();
+        binding.register_verification().unwrap();
+
+        for usage in [binding.usage(4).unwrap(),
+                      binding.key().unwrap().usage(4).unwrap()].iter() {
+            assert_eq!(usage[3].encryption_count, 2);
+            assert_eq!(usage[3].verification_count, 1);
+            assert!(usage[..3].iter().all(|w| w.encryption_count == 0
+                                         && w.verification_count == 0));
+        }
+
+        // Another binding of the same key.
+        let other = Store::open(&ctx, REALM_CONTACTS, "another store")
+            .unwrap().add("Mister B.", &fp).unwrap();
+        other.register_encryption().unwrap();
+        assert_eq!(binding.usage(1).unwrap()[0].encryption_count, 2);
+        assert_eq!(binding.key().unwrap().usage(1).unwrap()[0]
+                   .encryption_count, 3);
+
+        assert!(binding.usage(0).unwrap().is_empty());
+    }
+
 
     #[test]
     fn store_iterator() {
@@ -1494,5 +2964,208 @@ mod test {
         key.stats().unwrap();
         assert!(iter.next().is_none());
     }
-}
 
+    #[test]
+    fn iterator_snapshot() {
+        let ctx = make_some_stores();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let d = Fingerprint::from_bytes(b"dddddddddddddddddddd");
+
+        // Items added while iterating are not returned.
+        let mut bindings = store.iter().unwrap();
+        bindings.set_batch_size(1);
+        let mut keys = Store::list_keys(&ctx).unwrap();
+        keys.set_batch_size(1);
+        assert_eq!(bindings.next().unwrap().0, "Mister B.");
+        assert_eq!(keys.next().unwrap().0,
+                   Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb"));
+
+        store.add("Mister D.", &d).unwrap();
+        assert_eq!(bindings.map(|(label, _, _)| label).collect::<Vec<_>>(),
+                   vec!["B4".to_string()]);
+        assert_eq!(keys.map(|(fp, _)| fp).collect::<Vec<_>>(),
+                   vec![Fingerprint::from_bytes(b"cccccccccccccccccccc")]);
+
+        // New iterators see them.
+        assert_eq!(store.iter().unwrap().count(), 3);
+        assert_eq!(Store::list_keys(&ctx).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn bulk_add_and_delete() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+
+        let labels: Vec<String> = (0..100).map(|i| format!("{}", i)).collect();
+        let fps: Vec<Fingerprint> = (0..100u8)
+            .map(|i| Fingerprint::from_bytes(&[i; 20])).collect();
+        let keys: Vec<(&str, &Fingerprint)> =
+            labels.iter().map(|l| l.as_str()).zip(fps.iter()).collect();
+        let bindings = store.add_bindings(&keys).unwrap();
+        assert_eq!(bindings.len(), 100);
+        assert_eq!(bindings[42].label().unwrap(), "42");
+
+        // Iterate using various batch sizes.
+        for &n in [0, 1, 7, 100, 1000, usize::max_value()].iter() {
+            let mut iter = store.iter().unwrap();
+            iter.set_batch_size(n);
+            let got: Vec<(String, Fingerprint)> =
+                iter.map(|(label, fp, _)| (label, fp)).collect();
+            assert_eq!(got.len(), 100);
+            for (i, (label, fp)) in got.into_iter().enumerate() {
+                assert_eq!(label, labels[i]);
+                assert_eq!(fp, fps[i]);
+            }
+
+            let mut iter = Store::list_keys(&ctx).unwrap();
+            iter.set_batch_size(n);
+            assert_eq!(iter.count(), 100);
+        }
+
+        let delete: Vec<&str> =
+            labels[..50].iter().map(|l| l.as_str()).collect();
+        store.delete_bindings(&delete).unwrap();
+        assert_eq!(store.iter().unwrap().count(), 50);
+        assert!(store.lookup("0").is_err());
+        assert!(store.lookup("50").is_ok());
+
+        // Deleting nonexistent bindings fails.
+        assert!(store.delete_bindings(&["0"]).is_err());
+    }
+
+    #[test]
+    fn overlay() {
+        let ctx = core::Context::configure()
+            .ephemeral()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        store.add("Mister B.", &b).unwrap();
+
+        let overlay = core::Context::configure()
+            .home(ctx.home())
+            .overlay()
+            .network_policy(core::NetworkPolicy::Offline)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let upper = Store::open(&overlay, REALM_CONTACTS, "default").unwrap();
+        assert!(upper.lookup("Mister B.").is_ok());
+        let c = Fingerprint::from_bytes(b"cccccccccccccccccccc");
+        upper.add("Mister C.", &c).unwrap();
+        upper.delete_bindings(&["Mister B."]).unwrap();
+
+        // The lower layer is not modified.
+        assert!(store.lookup("Mister B.").is_ok());
+        assert!(store.lookup("Mister C.").is_err());
+    }
+
+    #[test]
+    fn label_normalization() {
+        let ctx = make_some_stores();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let c = Fingerprint::from_bytes(b"cccccccccccccccccccc");
+
+        let binding = store.add("Alice@Example.COM", &b).unwrap();
+        assert_eq!(binding.label().unwrap(), "alice@example.com");
+        store.add("alice@example.com", &b).unwrap();
+        assert!(store.add("ALICE@example.com", &c).is_err());
+        assert_eq!(store.lookup("aLiCe@eXample.com").unwrap().label().unwrap(),
+                   "alice@example.com");
+        assert_eq!(store.iter().unwrap().count(), 3);
+        assert!(store.duplicates().unwrap().is_empty());
+    }
+
+    #[test]
+    fn refresh_schedule() {
+        let ctx0 = make_some_stores();
+        Store::pause_refresh(&ctx0, RefreshPause::Indefinitely).unwrap();
+        assert_eq!(Store::refresh_schedule(&ctx0).unwrap().pause,
+                   RefreshPause::Indefinitely);
+
+        // Keys in offline stores are not updated.
+        assert!(Store::refresh_schedule(&ctx0).unwrap().keys.is_empty());
+
+        let ctx1 = core::Context::configure()
+            .home(ctx0.home())
+            .network_policy(core::NetworkPolicy::Encrypted)
+            .ipc_policy(core::IPCPolicy::Internal)
+            .build().unwrap();
+        let store = Store::open(&ctx1, REALM_CONTACTS, "online").unwrap();
+        let d = Fingerprint::from_bytes(b"dddddddddddddddddddd");
+        let e = Fingerprint::from_bytes(b"eeeeeeeeeeeeeeeeeeee");
+        store.add("Mister D.", &d).unwrap();
+        let key = store.add("Mister E.", &e).unwrap().key().unwrap();
+
+        let schedule = Store::refresh_schedule(&ctx0).unwrap();
+        assert_eq!(schedule.keys.len(), 2);
+        assert!(schedule.keys.iter().all(|&(_, ref t)| t.is_some()));
+
+        key.refresh().unwrap();
+        let schedule = Store::refresh_schedule(&ctx0).unwrap();
+        assert_eq!(schedule.keys[0], (e.clone(), None));
+        assert!(schedule.keys[1].1.is_some());
+
+        Store::refresh_all(&ctx0).unwrap();
+        let schedule = Store::refresh_schedule(&ctx0).unwrap();
+        assert!(schedule.keys.iter().all(|&(_, ref t)| t.is_none()));
+
+        let until = time::get_time() + time::Duration::days(1);
+        Store::pause_refresh(&ctx0, RefreshPause::Until(until)).unwrap();
+        assert_eq!(Store::refresh_schedule(&ctx0).unwrap().pause,
+                   RefreshPause::Until(Timespec::new(until.sec, 0)));
+        Store::pause_refresh(&ctx0, RefreshPause::Running).unwrap();
+        assert_eq!(Store::refresh_schedule(&ctx0).unwrap().pause,
+                   RefreshPause::Running);
+    }
+
+    #[test]
+    fn merge_duplicates() {
+        let ctx = make_some_stores();
+        let store = Store::open(&ctx, REALM_CONTACTS, "default").unwrap();
+        let b = Fingerprint::from_bytes(b"bbbbbbbbbbbbbbbbbbbb");
+        let c = Fingerprint::from_bytes(b"cccccccccccccccccccc");
+        store.add("alice@example.com", &b).unwrap()
+            .set_trust(Trust::Accepted).unwrap();
+        store.add("bob@example.com", &b).unwrap();
+        store.add("carol@example.com", &c).unwrap();
+        store.add("dave@example.com", &c).unwrap();
+
+        // Simulate bindings added before labels were normalized.
+        {
+            let db = ::rusqlite::Connection::open(
+                ctx.home().join("public-key-store.sqlite")).unwrap();
+            db.execute_batch(
+                "UPDATE bindings SET label = 'Alice@Example.COM'
+                     WHERE label = 'bob@example.com';
+                 UPDATE bindings SET label = 'CAROL@example.com'
+                     WHERE label = 'carol@example.com';
+                 UPDATE bindings SET label = 'Dave@Example.COM'
+                     WHERE label = 'dave@example.com';
+                 INSERT INTO bindings (store, label, key, created)
+                     SELECT store, 'carol@example.com', key, created
+                     FROM bindings WHERE label = 'alice@example.com';")
+                .unwrap();
+        }
+        let duplicates = store.duplicates().unwrap();
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(duplicates[0].label, "alice@example.com");
+        assert_eq!(duplicates[0].bindings.len(), 2);
+        assert_eq!(duplicates[1].label, "carol@example.com");
+
+        // Different keys.
+        assert!(store.merge_duplicates("carol@example.com").is_err());
+
+        let binding = store.merge_duplicates("ALICE@example.com").unwrap();
+        assert_eq!(binding.label().unwrap(), "alice@example.com");
+        assert_eq!(binding.trust().unwrap(), Trust::Accepted);
+        assert_eq!(store.duplicates().unwrap().len(), 1);
+        assert_eq!(store.iter().unwrap().count(), 6);
+    }
+}
@@ -14,7 +14,7 @@ macro_rules! make_request {
     ( $core: expr, $request: expr ) => {{
         use node::result::Which;
 
-        let r: std::result::Result<Result<_>, capnp::Error> = $core.run(
+        let r: Result<Result<_>> = $core.run(
             $request.send().promise
                 .and_then(|response| -> Promise<Result<_>, capnp::Error> {
                     let r = pry!(pry!(pry!(response.get()).get_result()).which());
@@ -32,17 +32,53 @@ macro_rules! make_request {
     }}
 }
 
+// Sends request and returns a future of the decoded result.
+//
+// This allows several requests to be in flight at the same time.
+macro_rules! send_request_map {
+    ( $request: expr, $map: expr ) => {{
+        use node::result::Which;
+
+        $request.send().promise
+            .and_then(|response| -> Promise<Result<_>, capnp::Error> {
+                let r = pry!(pry!(pry!(response.get()).get_result()).which());
+                let r = match r {
+                    /* The Result.  */
+                    Which::Ok(Ok(x)) => $map(x),
+                    Which::Err(Ok(e)) => Err(failure::Error::from(e)),
+                    /* Protocol violations.  */
+                    Which::Ok(Err(e)) => Err(failure::Error::from(e)),
+                    Which::Err(Err(e)) => Err(failure::Error::from(e)),
+                };
+                Promise::ok(r)
+            })
+    }}
+}
+
 macro_rules! make_request_map {
+    ( $core: expr, $request: expr, $map: expr ) => {{
+        let r: Result<Result<_>> = $core.run(
+            send_request_map!($request, $map));
+        r?
+    }}
+}
+
+// Sends request and decodes result, passing along the response.
+//
+// Like make_request_map, but `$map` is also given the response, so
+// that fields besides the result can be decoded.
+macro_rules! make_request_map_response {
     ( $core: expr, $request: expr, $map: expr ) => {{
         use node::result::Which;
 
-        let r: std::result::Result<Result<_>, capnp::Error> = $core.run(
+        let r: Result<Result<_>> = $core.run(
             $request.send().promise
                 .and_then(|response| -> Promise<Result<_>, capnp::Error> {
-                    let r = pry!(pry!(pry!(response.get()).get_result()).which());
+                    let response = pry!(response.get());
+                    let r = pry!(pry!(response.get_result()).which());
                     let r = match r {
                         /* The Result.  */
-                        Which::Ok(Ok(x)) => $map(x),
+                        Which::Ok(Ok(x)) => $map(x, response),
                         Which::Err(Ok(e)) => Err(failure::Error::from(e)),
                         /* Protocol violations.  */
                         Which::Ok(Err(e)) => Err(failure::Error::from(e)),
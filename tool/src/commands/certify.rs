@@ -0,0 +1,170 @@
+use failure::{self, ResultExt};
+use std::io::{self, BufRead, Write};
+use clap::ArgMatches;
+
+use sequoia_core::Context;
+use sequoia_net::KeyServer;
+use openpgp::{Fingerprint, TPK};
+use openpgp::constants::SignatureType;
+use openpgp::packet::signature;
+use openpgp::parse::Parse;
+use openpgp::serialize::Serialize;
+use openpgp::tpk::armor::Encoder;
+use super::store::Pool;
+
+use ::create_or_stdout;
+use config::Config;
+
+/// The certification levels, in the order of the signature types.
+const LEVELS: [(SignatureType, &str); 4] = [
+    (SignatureType::GenericCertificate, "I will not answer"),
+    (SignatureType::PersonaCertificate, "I have not checked at all"),
+    (SignatureType::CasualCertificate, "I have done casual checking"),
+    (SignatureType::PositiveCertificate,
+     "I have done very careful checking"),
+];
+
+/// Certifies the binding between a user id and a key.
+///
+/// Displays the fingerprint for comparison, and asks for the
+/// certification level and expiration unless they are given on the
+/// command line.  The certified key is exported, or published.
+pub fn certify(ctx: &Context, m: &ArgMatches, force: bool, config: &Config)
+               -> failure::Fallible<()> {
+    let fp = Fingerprint::from_hex(m.value_of("fingerprint").unwrap())
+        .context("Malformed fingerprint")?;
+    let userid = m.value_of("userid").unwrap();
+
+    let tpk = match m.value_of("input") {
+        Some(f) => TPK::from_file(f)
+            .context(format!("Failed to load key from file {:?}", f))?,
+        None => Pool::lookup(ctx, &fp)
+            .context("No such key in the key pool, use --input")?
+            .tpk()?,
+    };
+    if tpk.fingerprint() != fp {
+        return Err(format_err!("Key {} does not match the fingerprint {}",
+                               tpk.fingerprint(), fp));
+    }
+
+    let uid = tpk.userids()
+        .map(|b| b.userid())
+        .find(|u| u.value() == userid.as_bytes())
+        .cloned()
+        .ok_or_else(|| format_err!(
+            "Key {} has no user id {:?}, it has: {}", fp, userid,
+            tpk.userids()
+                .map(|b| format!("{:?}", String::from_utf8_lossy(
+                    b.userid().value())))
+                .collect::<Vec<_>>().join(", ")))?;
+
+    let tsk = match m.value_of("secret-key-file") {
+        Some(f) => Some(f),
+        None => config.get_str(&["certify"], "certifying-key")?,
+    }.ok_or_else(|| failure::err_msg(
+        "No certifying key given, use --secret-key-file"))?;
+    let tsk = TPK::from_file(tsk)
+        .context(format!("Failed to load key from file {:?}", tsk))?;
+    let key = tsk.keys_valid()
+        .certification_capable()
+        .map(|k| k.2)
+        .find(|key| key.secret().is_some())
+        .ok_or_else(|| format_err!(
+            "Found no suitable certification key on {}", tsk))?;
+
+    eprintln!("Certifying the binding between");
+    eprintln!();
+    eprintln!("  User ID: {}", userid);
+    eprintln!("  Key:     {}", fp.to_string());
+    eprintln!();
+    eprintln!("using {}.", tsk.fingerprint().to_string());
+    eprintln!();
+    eprintln!("Compare the fingerprint carefully with the one the key \
+               holder gave you.");
+    if ! m.is_present("yes") {
+        let answer = prompt("Does it match? [y/N] ")?;
+        if answer != "y" && answer != "yes" {
+            return Err(failure::err_msg("Certification aborted"));
+        }
+    }
+
+    let level = match m.value_of("level") {
+        Some(l) => l.parse::<usize>()?,
+        None => {
+            eprintln!();
+            eprintln!("How carefully have you verified the identity of \
+                       the key holder?");
+            for (i, (_, description)) in LEVELS.iter().enumerate() {
+                eprintln!("  {}  {}", i, description);
+            }
+            let answer = prompt("Your choice [0]: ")?;
+            if answer == "" {
+                0
+            } else {
+                answer.parse::<usize>()
+                    .ok().filter(|l| *l < LEVELS.len())
+                    .ok_or_else(|| format_err!(
+                        "Invalid certification level {:?}", answer))?
+            }
+        },
+    };
+    let (typ, _) = LEVELS[level];
+
+    let expiry = match m.value_of("expiry") {
+        Some(e) => super::parse_expiry("--expiry", e)?,
+        None => {
+            eprintln!();
+            let answer = prompt("When should the certification expire? \
+                                 ('N[ymwd]' or 'never') [never]: ")?;
+            if answer == "" {
+                None
+            } else {
+                super::parse_expiry("expiration", &answer)?
+            }
+        },
+    };
+
+    let mut signer = super::unlock(&tsk, key)?;
+    let builder = signature::Builder::new(typ)
+        .set_signature_expiration_time(expiry)?;
+    let certification = uid.bind(&mut signer, &tpk, builder, None, None)?;
+    let tpk = tpk.merge_packets(vec![certification.into()])?;
+
+    if m.is_present("publish") {
+        let server = match m.value_of("keyserver") {
+            Some(uri) => Some(uri),
+            None => config.get_str(&["keyserver"], "server")?,
+        };
+        let mut ks = if let Some(uri) = server {
+            KeyServer::new(ctx, &uri)
+        } else {
+            KeyServer::sks_pool(ctx)
+        }.context("Malformed keyserver URI")?;
+        ks.send(&tpk)
+            .context("Failed to send key to server")?;
+        eprintln!("Published the certification.");
+    }
+
+    if m.is_present("output") || ! m.is_present("publish") {
+        let mut output = create_or_stdout(m.value_of("output"), force)?;
+        if m.is_present("binary") || ! config.armor(&["certify"])? {
+            tpk.serialize(&mut output)?;
+        } else {
+            Encoder::new(&tpk).serialize(&mut output)?;
+        }
+    }
+    Ok(())
+}
+
+/// Asks the user a question, and returns the trimmed answer.
+///
+/// The question is written to stderr, the answer read from stdin.
+fn prompt(question: &str) -> failure::Fallible<String> {
+    eprint!("{}", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        return Err(failure::err_msg("Unexpected end of input"));
+    }
+    Ok(answer.trim().to_lowercase())
+}
@@ -17,7 +17,7 @@ use openpgp::parse::stream::{
 };
 extern crate sequoia_store as store;
 
-use super::{dump::PacketDumper, VHelper};
+use super::{dump::PacketDumper, StructurePolicy, VHelper};
 
 struct Helper<'a> {
     vhelper: VHelper<'a>,
@@ -31,7 +31,8 @@ struct Helper<'a> {
 
 impl<'a> Helper<'a> {
     fn new(ctx: &'a Context, store: &'a mut store::Store,
-           signatures: usize, tpks: Vec<TPK>, secrets: Vec<TPK>,
+           signatures: usize, policy: StructurePolicy,
+           tpks: Vec<TPK>, secrets: Vec<TPK>,
            dump_session_key: bool, dump: bool, hex: bool)
            -> Self {
         let mut keys: HashMap<KeyID, Key> = HashMap::new();
@@ -72,7 +73,7 @@ impl<'a> Helper<'a> {
         }
 
         Helper {
-            vhelper: VHelper::new(ctx, store, signatures, tpks),
+            vhelper: VHelper::new(ctx, store, signatures, policy, tpks),
             secret_keys: keys,
             key_identities: identities,
             key_hints: hints,
@@ -80,7 +81,8 @@ impl<'a> Helper<'a> {
             dumper: if dump || hex {
                 let width =
                     termsize::get().map(|s| s.cols as usize).unwrap_or(80);
-                Some(PacketDumper::new(width, false))
+                Some(PacketDumper::new(width, false,
+                                      ctx.time_format().clone()))
             } else {
                 None
             },
@@ -223,23 +225,18 @@ impl<'a> DecryptionHelper for Helper<'a> {
 
 pub fn decrypt(ctx: &Context, store: &mut store::Store,
                input: &mut io::Read, output: &mut io::Write,
-               signatures: usize, tpks: Vec<TPK>, secrets: Vec<TPK>,
+               signatures: usize, policy: StructurePolicy,
+               tpks: Vec<TPK>, secrets: Vec<TPK>,
                dump_session_key: bool,
                dump: bool, hex: bool)
                -> Result<()> {
-    let helper = Helper::new(ctx, store, signatures, tpks, secrets,
+    let helper = Helper::new(ctx, store, signatures, policy, tpks, secrets,
                              dump_session_key, dump, hex);
     let mut decryptor = Decryptor::from_reader(input, helper, None)
         .context("Decryption failed")?;
 
-    io::copy(&mut decryptor, output)
-        .map_err(|e| if e.get_ref().is_some() {
-            // Wrapped failure::Error.  Recover it.
-            failure::Error::from_boxed_compat(e.into_inner().unwrap())
-        } else {
-            // Plain io::Error.
-            e.into()
-        }).context("Decryption failed")?;
+    io::copy(&mut decryptor, output).map_err(super::recover_error)
+        .context("Decryption failed")?;
 
     let helper = decryptor.into_helper();
     if let Some(dumper) = helper.dumper.as_ref() {
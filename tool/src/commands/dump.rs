@@ -1,38 +1,89 @@
+use std::fmt;
 use std::io::{self, Read};
-use time;
 
 extern crate termsize;
 
 extern crate sequoia_openpgp as openpgp;
+use sequoia_core::TimeFormat;
+use openpgp::armor;
 use openpgp::constants::SymmetricAlgorithm;
 use openpgp::conversions::hex;
 use openpgp::{Packet, Result};
 use openpgp::packet::ctb::CTB;
 use openpgp::packet::{Header, BodyLength, Signature};
-use openpgp::packet::signature::subpacket::{Subpacket, SubpacketValue};
-use openpgp::crypto::{SessionKey, s2k::S2K};
-use openpgp::parse::{map::Map, Parse, PacketParserResult};
-
-use super::TIMEFMT;
+use openpgp::packet::signature::subpacket::{
+    describe_private_subpacket,
+    Subpacket,
+    SubpacketTag,
+    SubpacketValue,
+};
+use openpgp::crypto::{SessionKey, mpis::MPI, s2k::S2K};
+use openpgp::parse::{map::Map, Parse, PacketParserResult, PacketParserBuilder};
+
+use serde_json::{Map as JsonMap, Value};
+
+/// Output formats of the packet dumper.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// A human-readable tree.
+    Human,
+    /// A JSON array of packet trees.
+    ///
+    /// Every packet is an object with the fields `tag` (number),
+    /// `type` (string), `header` (object, absent for embedded
+    /// signatures), `fields` (object with packet-specific
+    /// information), and `children` (array of packets).  Depending on
+    /// the packet, there are also `subpackets`, `mpis` (array of
+    /// objects with the fields `name`, `bits` if applicable,
+    /// `length`, and, if MPIs are requested, `value`), and, if a
+    /// hexdump is requested, `map`.
+    JSON,
+}
 
 pub fn dump(input: &mut io::Read, output: &mut io::Write, mpis: bool, hex: bool,
-            sk: Option<&SessionKey>)
+            sk: Option<&SessionKey>, format: Format, time_format: &TimeFormat)
         -> Result<()> {
-    let mut ppr
-        = openpgp::parse::PacketParserBuilder::from_reader(input)?
-        .map(hex).finalize()?;
+    // Peek at the start of the input to see whether it is armored.
+    let mut prefix = vec![0; ARMOR_SNIFF_SIZE];
+    let mut n = 0;
+    while n < prefix.len() {
+        match input.read(&mut prefix[n..])? {
+            0 => break,
+            read => n += read,
+        }
+    }
+    prefix.truncate(n);
+    let armored = {
+        let start = prefix.iter().position(|c| ! c.is_ascii_whitespace())
+            .unwrap_or(prefix.len());
+        prefix[start..].starts_with(b"-----BEGIN PGP ")
+    };
+
+    // If so, we need to look at all of it to check the checksum.
+    let mut armored_data = Vec::new();
+    let mut input = io::Cursor::new(prefix).chain(input);
+    let mut ppr = if armored {
+        input.read_to_end(&mut armored_data)?;
+        if format == Format::Human {
+            dump_armor(output, &armored_data)?;
+        }
+        PacketParserBuilder::from_bytes(&armored_data)?
+    } else {
+        PacketParserBuilder::from_reader(input)?
+    }.map(hex).finalize()?;
     let width = termsize::get().map(|s| s.cols as usize).unwrap_or(80);
-    let mut dumper = PacketDumper::new(width, mpis);
+    let mut dumper = PacketDumper::with_format(width, mpis, format,
+                                               time_format.clone());
 
     while let PacketParserResult::Some(mut pp) = ppr {
         let additional_fields = match pp.packet {
             Packet::Literal(_) => {
                 let mut prefix = vec![0; 40];
                 let n = pp.read(&mut prefix)?;
+                let truncated = n == prefix.len();
+                prefix.truncate(n);
                 Some(vec![
-                    format!("Content: {:?}{}",
-                            String::from_utf8_lossy(&prefix[..n]),
-                            if n == prefix.len() { "..." } else { "" }),
+                    Field::Content { prefix: prefix, truncated: truncated },
                 ])
             },
             Packet::SEIP(_) if sk.is_some() => {
@@ -52,12 +103,12 @@ pub fn dump(input: &mut io::Read, output: &mut io::Write, mpis: bool, hex: bool,
                     }
                 }
                 let mut fields = Vec::new();
-                fields.push(format!("Session key: {}", hex::encode(sk)));
+                fields.push(Field::SessionKey(hex::encode(sk)));
                 if let Some(algo) = decrypted_with {
-                    fields.push(format!("Symmetric algo: {}", algo));
-                    fields.push("Decryption successful".into());
+                    fields.push(Field::SymmetricAlgo(algo));
+                    fields.push(Field::Decrypted(true));
                 } else {
-                    fields.push("Decryption failed".into());
+                    fields.push(Field::Decrypted(false));
                 }
                 Some(fields)
             },
@@ -72,12 +123,8 @@ pub fn dump(input: &mut io::Read, output: &mut io::Write, mpis: bool, hex: bool,
                 let _ = pp.decrypt(algo, sk);
 
                 let mut fields = Vec::new();
-                fields.push(format!("Session key: {}", hex::encode(sk)));
-                if pp.decrypted() {
-                    fields.push("Decryption successful".into());
-                } else {
-                    fields.push("Decryption failed".into());
-                }
+                fields.push(Field::SessionKey(hex::encode(sk)));
+                fields.push(Field::Decrypted(pp.decrypted()));
                 Some(fields)
             },
             _ => None,
@@ -97,17 +144,228 @@ pub fn dump(input: &mut io::Read, output: &mut io::Write, mpis: bool, hex: bool,
     dumper.flush(output)
 }
 
+/// Additional information about a packet gathered while parsing it.
+pub enum Field {
+    /// The start of a literal data packet's content.
+    Content {
+        prefix: Vec<u8>,
+        truncated: bool,
+    },
+    /// The session key used to decrypt an encryption container, hex
+    /// encoded.
+    SessionKey(String),
+    /// The algorithm an encryption container was decrypted with.
+    SymmetricAlgo(SymmetricAlgorithm),
+    /// Whether an encryption container was decrypted.
+    Decrypted(bool),
+}
+
+impl Field {
+    /// Adds the field to the JSON object `o`.
+    fn json(&self, o: &mut Object) {
+        match self {
+            Field::Content { prefix, truncated } => {
+                o.string("content", String::from_utf8_lossy(prefix));
+                o.value("content_truncated", *truncated);
+            },
+            Field::SessionKey(sk) => { o.string("session_key", sk); },
+            Field::SymmetricAlgo(algo) => { o.string("symmetric_algo", algo); },
+            Field::Decrypted(d) => { o.value("decrypted", *d); },
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Field::Content { prefix, truncated } =>
+                write!(f, "Content: {:?}{}", String::from_utf8_lossy(prefix),
+                       if *truncated { "..." } else { "" }),
+            Field::SessionKey(sk) => write!(f, "Session key: {}", sk),
+            Field::SymmetricAlgo(algo) => write!(f, "Symmetric algo: {}", algo),
+            Field::Decrypted(true) => f.write_str("Decryption successful"),
+            Field::Decrypted(false) => f.write_str("Decryption failed"),
+        }
+    }
+}
+
+/// A JSON object under construction.
+#[derive(Default)]
+struct Object(JsonMap<String, Value>);
+
+impl Object {
+    /// Adds `value`.
+    fn value<V: Into<Value>>(&mut self, key: &str, value: V) -> &mut Self {
+        self.0.insert(key.into(), value.into());
+        self
+    }
+
+    /// Adds `value` as string.
+    fn string<V: fmt::Display>(&mut self, key: &str, value: V) -> &mut Self {
+        self.value(key, value.to_string())
+    }
+}
+
+impl From<Object> for Value {
+    fn from(o: Object) -> Self {
+        Value::Object(o.0)
+    }
+}
+
+/// Encodes `items` as JSON array.
+fn json_array<I, T>(items: I) -> Value
+    where I: IntoIterator<Item=T>, T: Into<Value>
+{
+    Value::Array(items.into_iter().map(Into::into).collect())
+}
+
+/// Number of bytes inspected to decide whether the input is armored.
+const ARMOR_SNIFF_SIZE: usize = 1024;
+
+/// Maximum line length permitted by RFC 4880, section 6.3.
+const ARMOR_MAX_LINE_LENGTH: usize = 76;
+
+/// Reports details about the ASCII Armor of `data`.
+///
+/// In addition to the armor's kind and headers, this reports whether
+/// the checksum matches, and points out anomalies like overlong
+/// lines or misplaced padding that other implementations may choke
+/// on.
+fn dump_armor(output: &mut io::Write, data: &[u8]) -> Result<()> {
+    writeln!(output, "ASCII Armor")?;
+
+    let mut reader = armor::Reader::from_bytes(
+        data, armor::ReaderMode::Tolerant(None));
+    match reader.headers() {
+        Ok(headers) => for (key, value) in headers {
+            writeln!(output, "    Header: {}: {}", key, value)?;
+        },
+        Err(e) => {
+            writeln!(output, "    Error: {}", e)?;
+            writeln!(output)?;
+            return Ok(());
+        },
+    }
+    if let Some(kind) = reader.kind() {
+        writeln!(output, "    Kind: {:?}", kind)?;
+    }
+    let decoded = io::copy(&mut reader, &mut io::sink());
+
+    let text = String::from_utf8_lossy(data);
+    let mut warnings = Vec::new();
+    let mut lines = text.lines().enumerate()
+        .map(|(i, l)| (i + 1, l.trim_right()))
+        .skip_while(|(_, l)| ! l.starts_with("-----BEGIN PGP "));
+    let begin = lines.next().map(|(_, l)| l.to_string()).unwrap_or_default();
+
+    // Skip the headers.  They are separated from the body by an
+    // empty line.
+    let mut body = Vec::new();
+    for (n, l) in lines.by_ref() {
+        if l.is_empty() {
+            break;
+        }
+        if ! l.contains(": ") {
+            warnings.push(format!("Line {}: Missing empty line after \
+                                   the headers", n));
+            body.push((n, l));
+            break;
+        }
+    }
+
+    let mut crc = None;
+    let mut end = None;
+    for (n, l) in lines {
+        if l.starts_with("-----") {
+            end = Some(l);
+            break;
+        } else if l.starts_with('=') && crc.is_none() {
+            crc = Some((n, l));
+        } else if crc.is_some() {
+            warnings.push(format!("Line {}: Data after the checksum", n));
+        } else {
+            body.push((n, l));
+        }
+    }
+
+    // Line lengths.
+    if let Some(&(_, first)) = body.first() {
+        writeln!(output, "    Line length: {}", first.len())?;
+    }
+    let last = body.len().saturating_sub(1);
+    for (i, &(n, l)) in body.iter().enumerate() {
+        if l.len() > ARMOR_MAX_LINE_LENGTH {
+            warnings.push(format!("Line {}: {} characters exceed the \
+                                   maximum of {}",
+                                  n, l.len(), ARMOR_MAX_LINE_LENGTH));
+        } else if i < last && l.len() != body[0].1.len() {
+            warnings.push(format!("Line {}: {} characters, expected {}",
+                                  n, l.len(), body[0].1.len()));
+        }
+        if let Some(c) = l.chars().find(|&c| ! (c.is_ascii_alphanumeric()
+                                                 || c == '+' || c == '/'
+                                                 || c == '=')) {
+            warnings.push(format!("Line {}: Invalid character {:?}", n, c));
+        }
+    }
+
+    // Padding.
+    let encoded: String = body.iter().map(|&(_, l)| l).collect();
+    let padding = encoded.len() - encoded.trim_right_matches('=').len();
+    if encoded.trim_right_matches('=').contains('=') {
+        warnings.push("Padding in the middle of the data".into());
+    }
+    if padding > 2 {
+        warnings.push(format!("Excess padding: {} padding characters",
+                              padding));
+    }
+    if encoded.len() % 4 != 0 {
+        warnings.push(format!("Missing padding: {} characters are not a \
+                               multiple of 4", encoded.len()));
+    }
+
+    // Checksum.
+    match (decoded, crc) {
+        (Ok(_), Some(_)) =>
+            writeln!(output, "    Checksum: valid")?,
+        (Ok(_), None) =>
+            writeln!(output, "    Checksum: absent")?,
+        (Err(e), _) =>
+            writeln!(output, "    Checksum: not verified: {}", e)?,
+    }
+    if let Some((n, l)) = crc {
+        if l.len() != 5 {
+            warnings.push(format!("Line {}: Malformed checksum", n));
+        }
+    }
+
+    // Footer.
+    match end {
+        Some(l) if l != begin.replace("BEGIN", "END") =>
+            warnings.push(format!("Footer {:?} does not match header {:?}",
+                                  l, begin)),
+        Some(_) => (),
+        None => warnings.push("Missing footer".into()),
+    }
+
+    for w in warnings {
+        writeln!(output, "    Warning: {}", w)?;
+    }
+    writeln!(output)?;
+    Ok(())
+}
+
 struct Node {
     header: Header,
     packet: Packet,
     map: Option<Map>,
-    additional_fields: Option<Vec<String>>,
+    additional_fields: Option<Vec<Field>>,
     children: Vec<Node>,
 }
 
 impl Node {
     fn new(header: Header, packet: Packet, map: Option<Map>,
-           additional_fields: Option<Vec<String>>) -> Self {
+           additional_fields: Option<Vec<Field>>) -> Self {
         Node {
             header: header,
             packet: packet,
@@ -129,21 +387,33 @@ impl Node {
 pub struct PacketDumper {
     width: usize,
     mpis: bool,
+    format: Format,
+    time_format: TimeFormat,
+    /// Number of trees dumped so far.
+    dumped: usize,
     root: Option<Node>,
 }
 
 impl PacketDumper {
-    pub fn new(width: usize, mpis: bool) -> Self {
+    pub fn new(width: usize, mpis: bool, time_format: TimeFormat) -> Self {
+        Self::with_format(width, mpis, Format::Human, time_format)
+    }
+
+    pub fn with_format(width: usize, mpis: bool, format: Format,
+                       time_format: TimeFormat) -> Self {
         PacketDumper {
             width: width,
             mpis: mpis,
+            format: format,
+            time_format: time_format,
+            dumped: 0,
             root: None,
         }
     }
 
     pub fn packet(&mut self, output: &mut io::Write, depth: usize,
                   header: Header, p: Packet, map: Option<Map>,
-                  additional_fields: Option<Vec<String>>)
+                  additional_fields: Option<Vec<Field>>)
                   -> Result<()> {
         let node = Node::new(header, p, map, additional_fields);
         if self.root.is_none() {
@@ -152,7 +422,8 @@ impl PacketDumper {
         } else {
             if depth == 0 {
                 let root = self.root.take().unwrap();
-                self.dump_tree(output, "", &root)?;
+                self.dump_root(output, &root)?;
+                self.dumped += 1;
                 self.root = Some(node);
             } else {
                 self.root.as_mut().unwrap().append(depth - 1, node);
@@ -163,11 +434,30 @@ impl PacketDumper {
 
     pub fn flush(&self, output: &mut io::Write) -> Result<()> {
         if let Some(root) = self.root.as_ref() {
-            self.dump_tree(output, "", &root)?;
+            self.dump_root(output, &root)?;
+        }
+        if self.format == Format::JSON {
+            if self.dumped == 0 && self.root.is_none() {
+                write!(output, "[")?;
+            }
+            writeln!(output, "]")?;
         }
         Ok(())
     }
 
+    /// Dumps the tree of a top-level packet.
+    fn dump_root(&self, output: &mut io::Write, root: &Node) -> Result<()> {
+        match self.format {
+            Format::Human => self.dump_tree(output, "", root),
+            Format::JSON => {
+                write!(output, "{}{}",
+                       if self.dumped == 0 { "[" } else { "," },
+                       self.json_tree(root))?;
+                Ok(())
+            },
+        }
+    }
+
     fn dump_tree(&self, output: &mut io::Write, indent: &str, node: &Node)
                  -> Result<()> {
         let indent_node =
@@ -194,7 +484,7 @@ impl PacketDumper {
 
     fn dump_packet(&self, output: &mut io::Write, i: &str,
                   header: Option<&Header>, p: &Packet, map: Option<&Map>,
-                  additional_fields: Option<&Vec<String>>)
+                  additional_fields: Option<&Vec<Field>>)
                   -> Result<()> {
         use self::openpgp::Packet::*;
 
@@ -307,7 +597,7 @@ impl PacketDumper {
                 writeln!(output, "{}", p.tag())?;
                 writeln!(output, "{}  Version: {}", i, k.version())?;
                 writeln!(output, "{}  Creation time: {}", i,
-                         time::strftime(TIMEFMT, k.creation_time()).unwrap())?;
+                         self.time_format.format(k.creation_time()))?;
                 writeln!(output, "{}  Pk algo: {}", i, k.pk_algo())?;
                 if let Some(bits) = k.mpis().bits() {
                     writeln!(output, "{}  Pk size: {} bits", i, bits)?;
@@ -421,6 +711,32 @@ impl PacketDumper {
                                 self.dump_mpis(output, &ii, &[&ciphertext[..]],
                                                &["ciphertext"])?;
                             },
+                            openpgp::packet::key::SecretKey::AEADEncrypted {
+                                s2k, algorithm, aead, iv, ciphertext, ..
+                            } => {
+                                writeln!(output, "{}", i)?;
+                                write!(output, "{}  S2K: ", ii)?;
+                                self.dump_s2k(output, &ii, s2k)?;
+                                writeln!(output, "{}  Sym. algo: {}", ii,
+                                         algorithm)?;
+                                writeln!(output, "{}  AEAD: {}", ii, aead)?;
+                                self.dump_mpis(output, &ii,
+                                               &[&iv[..], &ciphertext[..]],
+                                               &["iv", "ciphertext"])?;
+                            },
+                            openpgp::packet::key::SecretKey::LegacyEncrypted {
+                                s2k, algorithm, ciphertext,
+                            } => {
+                                writeln!(output, "{}", i)?;
+                                write!(output, "{}  S2K: ", ii)?;
+                                self.dump_s2k(output, &ii, s2k)?;
+                                writeln!(output, "{}  Sym. algo: {}", ii,
+                                         algorithm)?;
+                                writeln!(output, "{}  Checksum: legacy 16-bit \
+                                                  (deprecated)", ii)?;
+                                self.dump_mpis(output, &ii, &[&ciphertext[..]],
+                                               &["ciphertext"])?;
+                            },
                         }
                     }
                 }
@@ -481,7 +797,7 @@ impl PacketDumper {
                 }
                 if let Some(timestamp) = l.date() {
                     writeln!(output, "{}  Timestamp: {}", i,
-                             time::strftime(TIMEFMT, timestamp).unwrap())?;
+                             self.time_format.format(timestamp))?;
                 }
             },
 
@@ -617,109 +933,518 @@ impl PacketDumper {
     fn dump_subpacket(&self, output: &mut io::Write, i: &str,
                       s: Subpacket, sig: &Signature)
                       -> Result<()> {
-        use self::SubpacketValue::*;
+        let (label, value) = self.subpacket_field(s.tag, &s.value, sig);
+        write!(output, "{}    {}: {}", i, label, value)?;
+        if s.critical {
+            write!(output, " (critical)")?;
+        }
+        writeln!(output)?;
 
         match s.value {
-            Unknown(ref b) =>
-                write!(output, "{}    Unknown: {:?}", i, b)?,
+            SubpacketValue::EmbeddedSignature(ref sig) => {
+                let indent = format!("{}      ", i);
+                self.dump_packet(output, &indent, None, sig, None, None)?;
+            },
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Returns a label and a human-readable rendering of `value`.
+    ///
+    /// Embedded signatures are rendered as empty string.
+    fn subpacket_field(&self, tag: SubpacketTag, value: &SubpacketValue,
+                       sig: &Signature)
+                       -> (&'static str, String) {
+        use self::SubpacketValue::*;
+
+        match *value {
+            Unknown(ref b) => match describe_private_subpacket(tag, b) {
+                Some((name, Ok(v))) => (name, v),
+                Some((name, Err(e))) =>
+                    (name, format!("{:?} (invalid: {})", b, e)),
+                None => ("Unknown", format!("{:?}", b)),
+            },
             Invalid(ref b) =>
-                write!(output, "{}    Invalid: {:?}", i, b)?,
+                ("Invalid", format!("{:?}", b)),
             SignatureCreationTime(ref t) =>
-                write!(output, "{}    Signature creation time: {}", i,
-                       time::strftime(TIMEFMT, t).unwrap())?,
+                ("Signature creation time",
+                 self.time_format.format(t)),
             SignatureExpirationTime(ref t) =>
-                write!(output, "{}    Signature expiration time: {} ({})",
-                       i, t,
-                       if let Some(creation) = sig.signature_creation_time() {
-                           time::strftime(TIMEFMT, &(creation + *t))
-                               .unwrap()
-                       } else {
-                           " (no Signature Creation Time subpacket)".into()
-                       })?,
+                ("Signature expiration time",
+                 format!("{} ({})", t,
+                         if let Some(creation) = sig.signature_creation_time() {
+                             self.time_format.format(&(creation + *t))
+                         } else {
+                             " (no Signature Creation Time subpacket)".into()
+                         })),
             ExportableCertification(e) =>
-                write!(output, "{}    Exportable certification: {}", i, e)?,
+                ("Exportable certification", format!("{}", e)),
             TrustSignature{level, trust} =>
-                write!(output, "{}    Trust signature: level {} trust {}", i,
-                       level, trust)?,
+                ("Trust signature", format!("level {} trust {}", level, trust)),
             RegularExpression(ref r) =>
-                write!(output, "{}    Regular expression: {}", i,
-                       String::from_utf8_lossy(r))?,
+                ("Regular expression", String::from_utf8_lossy(r).into()),
             Revocable(r) =>
-                write!(output, "{}    Revocable: {}", i, r)?,
+                ("Revocable", format!("{}", r)),
             KeyExpirationTime(ref t) =>
-                write!(output, "{}    Key expiration time: {}", i, t)?,
+                ("Key expiration time", format!("{}", t)),
             PreferredSymmetricAlgorithms(ref c) =>
-                write!(output, "{}    Symmetric algo preferences: {}", i,
-                       c.iter().map(|c| format!("{:?}", c))
-                       .collect::<Vec<String>>().join(", "))?,
+                ("Symmetric algo preferences",
+                 c.iter().map(|c| format!("{:?}", c))
+                 .collect::<Vec<String>>().join(", ")),
             RevocationKey{class, pk_algo, ref fp} =>
-                write!(output,
-                       "{}    Revocation key: class {} algo {} fingerprint {}", i,
-                       class, pk_algo, fp)?,
+                ("Revocation key",
+                 format!("class {} algo {} fingerprint {}", class, pk_algo, fp)),
             Issuer(ref is) =>
-                write!(output, "{}    Issuer: {}", i, is)?,
+                ("Issuer", format!("{}", is)),
             NotationData(ref n) =>
-                write!(output, "{}    Notation: {:?}", i, n)?,
+                ("Notation", format!("{:?}", n)),
             PreferredHashAlgorithms(ref h) =>
-                write!(output, "{}    Hash preferences: {}", i,
-                       h.iter().map(|h| format!("{:?}", h))
-                       .collect::<Vec<String>>().join(", "))?,
+                ("Hash preferences",
+                 h.iter().map(|h| format!("{:?}", h))
+                 .collect::<Vec<String>>().join(", ")),
             PreferredCompressionAlgorithms(ref c) =>
-                write!(output, "{}    Compression preferences: {}", i,
-                       c.iter().map(|c| format!("{:?}", c))
-                       .collect::<Vec<String>>().join(", "))?,
+                ("Compression preferences",
+                 c.iter().map(|c| format!("{:?}", c))
+                 .collect::<Vec<String>>().join(", ")),
             KeyServerPreferences(ref p) =>
-                write!(output, "{}    Keyserver preferences: {:?}", i, p)?,
+                ("Keyserver preferences", format!("{:?}", p)),
             PreferredKeyServer(ref k) =>
-                write!(output, "{}    Preferred keyserver: {}", i,
-                       String::from_utf8_lossy(k))?,
+                ("Preferred keyserver", String::from_utf8_lossy(k).into()),
             PrimaryUserID(p) =>
-                write!(output, "{}    Primary User ID: {}", i, p)?,
+                ("Primary User ID", format!("{}", p)),
             PolicyURI(ref p) =>
-                write!(output, "{}    Policy URI: {}", i,
-                       String::from_utf8_lossy(p))?,
+                ("Policy URI", String::from_utf8_lossy(p).into()),
             KeyFlags(ref k) =>
-                write!(output, "{}    Key flags: {:?}", i, k)?,
+                ("Key flags", format!("{:?}", k)),
             SignersUserID(ref u) =>
-                write!(output, "{}    Signer's User ID: {}", i,
-                       String::from_utf8_lossy(u))?,
+                ("Signer's User ID", String::from_utf8_lossy(u).into()),
             ReasonForRevocation{code, ref reason} => {
                 let reason = String::from_utf8_lossy(reason);
-                write!(output, "{}    Reason for revocation: {}{}{}", i, code,
-                       if reason.len() > 0 { ", " } else { "" }, reason)?
+                ("Reason for revocation",
+                 format!("{}{}{}", code,
+                         if reason.len() > 0 { ", " } else { "" }, reason))
             }
             Features(ref f) =>
-                write!(output, "{}    Features: {:?}", i, f)?,
+                ("Features", format!("{:?}", f)),
             SignatureTarget{pk_algo, hash_algo, ref digest} =>
-                write!(output, "{}    Signature target: {}, {}, {}", i,
-                       pk_algo, hash_algo, hex::encode(digest))?,
+                ("Signature target",
+                 format!("{}, {}, {}", pk_algo, hash_algo, hex::encode(digest))),
             EmbeddedSignature(_) =>
-            // Embedded signature is dumped below.
-                write!(output, "{}    Embedded signature: ", i)?,
+            // Embedded signature is dumped separately.
+                ("Embedded signature", String::new()),
             IssuerFingerprint(ref fp) =>
-                write!(output, "{}    Issuer Fingerprint: {}", i, fp)?,
+                ("Issuer Fingerprint", format!("{}", fp)),
             PreferredAEADAlgorithms(ref c) =>
-                write!(output, "{}    AEAD preferences: {}", i,
-                       c.iter().map(|c| format!("{:?}", c))
-                       .collect::<Vec<String>>().join(", "))?,
+                ("AEAD preferences",
+                 c.iter().map(|c| format!("{:?}", c))
+                 .collect::<Vec<String>>().join(", ")),
             IntendedRecipient(ref fp) =>
-                write!(output, "{}    Intended Recipient: {}", i, fp)?,
+                ("Intended Recipient", format!("{}", fp)),
         }
+    }
 
-        if s.critical {
-            write!(output, " (critical)")?;
+    /// Returns `node` and its children as JSON object.
+    fn json_tree(&self, node: &Node) -> Value {
+        let mut o = self.json_packet(Some(&node.header), &node.packet,
+                                     node.map.as_ref(),
+                                     node.additional_fields.as_ref());
+        o.value("children",
+                json_array(node.children.iter().map(|c| self.json_tree(c))));
+        o.into()
+    }
+
+    /// Returns the packet `p` as JSON object.
+    fn json_packet(&self, header: Option<&Header>, p: &Packet,
+                   map: Option<&Map>, additional_fields: Option<&Vec<Field>>)
+                   -> Object {
+        use self::openpgp::Packet::*;
+
+        let mut o = Object::default();
+        o.value("tag", u8::from(p.tag()));
+        o.string("type", p.tag());
+
+        if let Some(h) = header {
+            let mut ho = Object::default();
+            ho.string("ctb", if let CTB::Old(_) = h.ctb { "old" } else { "new" });
+            match h.length {
+                BodyLength::Full(n) => {
+                    ho.string("length_type", "full").value("length", n);
+                },
+                BodyLength::Partial(n) => {
+                    ho.string("length_type", "partial")
+                        .value("first_chunk_length", n);
+                },
+                BodyLength::Indeterminate => {
+                    ho.string("length_type", "indeterminate");
+                },
+            }
+            o.value("header", ho);
         }
-        writeln!(output)?;
 
-        match s.value {
-            EmbeddedSignature(ref sig) => {
-                let indent = format!("{}      ", i);
-                self.dump_packet(output, &indent, None, sig, None, None)?;
+        let mut f = Object::default();
+        let mut mpis = Vec::new();
+        match p {
+            Unknown(ref u) => {
+                f.string("error", u.error());
+            },
+
+            Signature(ref s) => {
+                f.value("version", s.version())
+                    .string("sigtype", s.sigtype())
+                    .string("pk_algo", s.pk_algo())
+                    .string("hash_algo", s.hash_algo())
+                    .string("hash_prefix", hex::encode(s.hash_prefix()))
+                    .value("level", s.level());
+
+                let mut so = Object::default();
+                so.value("hashed", json_array(
+                    s.hashed_area().iter()
+                        .map(|(_, _, pkt)| self.json_subpacket(pkt, s))));
+                so.value("unhashed", json_array(
+                    s.unhashed_area().iter()
+                        .map(|(_, _, pkt)| self.json_subpacket(pkt, s))));
+                o.value("subpackets", so);
+
+                use openpgp::crypto::mpis::Signature::*;
+                match s.mpis() {
+                    RSA { s } =>
+                        mpis.push(self.json_mpi("s", s)),
+                    DSA { r, s } | Elgamal { r, s } | EdDSA { r, s }
+                        | ECDSA { r, s } => {
+                            mpis.push(self.json_mpi("r", r));
+                            mpis.push(self.json_mpi("s", s));
+                        },
+                    Unknown { mpis: m, rest } => {
+                        for (i, mpi) in m.iter().enumerate() {
+                            mpis.push(self.json_mpi(&format!("mpi{}", i), mpi));
+                        }
+                        mpis.push(self.json_bytes("rest", rest));
+                    },
+                }
+            },
+
+            OnePassSig(ref ops) => {
+                f.value("version", ops.version())
+                    .string("sigtype", ops.sigtype())
+                    .string("pk_algo", ops.pk_algo())
+                    .string("hash_algo", ops.hash_algo())
+                    .string("issuer", ops.issuer())
+                    .value("last", ops.last());
+            },
+
+            PublicKey(ref k) | PublicSubkey(ref k)
+                | SecretKey(ref k) | SecretSubkey(ref k) =>
+            {
+                f.value("version", k.version())
+                    .value("creation_time", k.creation_time().to_timespec().sec)
+                    .string("pk_algo", k.pk_algo());
+                if let Some(bits) = k.mpis().bits() {
+                    f.value("pk_size", bits);
+                }
+
+                use openpgp::crypto::mpis::PublicKey::*;
+                match k.mpis() {
+                    RSA { e, n } => {
+                        mpis.push(self.json_mpi("e", e));
+                        mpis.push(self.json_mpi("n", n));
+                    },
+                    DSA { p, q, g, y } => {
+                        mpis.push(self.json_mpi("p", p));
+                        mpis.push(self.json_mpi("q", q));
+                        mpis.push(self.json_mpi("g", g));
+                        mpis.push(self.json_mpi("y", y));
+                    },
+                    Elgamal { p, g, y } => {
+                        mpis.push(self.json_mpi("p", p));
+                        mpis.push(self.json_mpi("g", g));
+                        mpis.push(self.json_mpi("y", y));
+                    },
+                    EdDSA { curve, q } | ECDSA { curve, q } => {
+                        f.string("curve", curve);
+                        mpis.push(self.json_mpi("q", q));
+                    },
+                    ECDH { curve, q, hash, sym } => {
+                        f.string("curve", curve)
+                            .string("kdf_hash_algo", hash)
+                            .string("kdf_symmetric_algo", sym);
+                        mpis.push(self.json_mpi("q", q));
+                    },
+                    Unknown { mpis: m, rest } => {
+                        for (i, mpi) in m.iter().enumerate() {
+                            mpis.push(self.json_mpi(&format!("mpi{}", i), mpi));
+                        }
+                        mpis.push(self.json_bytes("rest", rest));
+                    },
+                }
+
+                match k.secret() {
+                    None => (),
+                    Some(openpgp::packet::key::SecretKey::Unencrypted {
+                        mpis: secrets,
+                    }) => {
+                        f.string("secret", "unencrypted");
+
+                        use openpgp::crypto::mpis::SecretKey::*;
+                        match secrets {
+                            RSA { d, p, q, u } => {
+                                mpis.push(self.json_mpi("d", d));
+                                mpis.push(self.json_mpi("p", p));
+                                mpis.push(self.json_mpi("q", q));
+                                mpis.push(self.json_mpi("u", u));
+                            },
+                            DSA { x } | Elgamal { x } =>
+                                mpis.push(self.json_mpi("x", x)),
+                            EdDSA { scalar } | ECDSA { scalar }
+                                | ECDH { scalar } =>
+                                mpis.push(self.json_mpi("scalar", scalar)),
+                            Unknown { mpis: m, rest } => {
+                                for (i, mpi) in m.iter().enumerate() {
+                                    mpis.push(self.json_mpi(
+                                        &format!("mpi{}", i), mpi));
+                                }
+                                mpis.push(self.json_bytes("rest", rest));
+                            },
+                        }
+                    },
+                    Some(openpgp::packet::key::SecretKey::Encrypted {
+                        s2k, algorithm, ciphertext,
+                    }) => {
+                        f.string("secret", "encrypted")
+                            .value("s2k", self.json_s2k(s2k))
+                            .string("symmetric_algo", algorithm);
+                        mpis.push(self.json_bytes("ciphertext", ciphertext));
+                    },
+                    Some(openpgp::packet::key::SecretKey::AEADEncrypted {
+                        s2k, algorithm, aead, iv, ciphertext, ..
+                    }) => {
+                        f.string("secret", "aead-encrypted")
+                            .value("s2k", self.json_s2k(s2k))
+                            .string("symmetric_algo", algorithm)
+                            .string("aead_algo", aead);
+                        mpis.push(self.json_bytes("iv", iv));
+                        mpis.push(self.json_bytes("ciphertext", ciphertext));
+                    },
+                    Some(openpgp::packet::key::SecretKey::LegacyEncrypted {
+                        s2k, algorithm, ciphertext,
+                    }) => {
+                        f.string("secret", "legacy-encrypted")
+                            .value("s2k", self.json_s2k(s2k))
+                            .string("symmetric_algo", algorithm);
+                        mpis.push(self.json_bytes("ciphertext", ciphertext));
+                    },
+                }
+            },
+
+            Trust(ref p) => {
+                f.string("value", hex::encode(p.value()));
+            },
+
+            UserID(ref u) => {
+                f.string("value", String::from_utf8_lossy(u.value()));
+            },
+
+            UserAttribute(ref u) => {
+                use openpgp::packet::user_attribute::{Subpacket, Image};
+
+                o.value("subpackets", json_array(u.subpackets().map(|s| {
+                    let mut so = Object::default();
+                    match s {
+                        Ok(Subpacket::Image(Image::JPEG(data))) => {
+                            so.string("type", "jpeg")
+                                .value("length", data.len());
+                        },
+                        Ok(Subpacket::Image(Image::Private(n, data))) => {
+                            so.string("type", "private-image").value("value", n)
+                                .value("length", data.len());
+                        },
+                        Ok(Subpacket::Image(Image::Unknown(n, data))) => {
+                            so.string("type", "unknown-image").value("value", n)
+                                .value("length", data.len());
+                        },
+                        Ok(Subpacket::Unknown(n, data)) => {
+                            so.string("type", "unknown").value("value", n)
+                                .value("length", data.len());
+                        },
+                        Err(e) => {
+                            so.string("type", "invalid").string("error", e);
+                        },
+                    }
+                    so
+                })));
+            },
+
+            Marker(_) => (),
+
+            Literal(ref l) => {
+                f.string("format", l.format());
+                if let Some(filename) = l.filename() {
+                    f.string("filename", String::from_utf8_lossy(filename));
+                }
+                if let Some(timestamp) = l.date() {
+                    f.value("timestamp", timestamp.to_timespec().sec);
+                }
+            },
+
+            CompressedData(ref c) => {
+                f.string("algorithm", c.algorithm());
+            },
+
+            PKESK(ref p) => {
+                f.value("version", p.version())
+                    .string("recipient", p.recipient())
+                    .string("pk_algo", p.pk_algo());
+
+                use openpgp::crypto::mpis::Ciphertext::*;
+                match p.esk() {
+                    RSA { c } =>
+                        mpis.push(self.json_mpi("c", c)),
+                    Elgamal { e, c } => {
+                        mpis.push(self.json_mpi("e", e));
+                        mpis.push(self.json_mpi("c", c));
+                    },
+                    ECDH { e, key } => {
+                        mpis.push(self.json_mpi("e", e));
+                        mpis.push(self.json_bytes("key", key));
+                    },
+                    Unknown { mpis: m, rest } => {
+                        for (i, mpi) in m.iter().enumerate() {
+                            mpis.push(self.json_mpi(&format!("mpi{}", i), mpi));
+                        }
+                        mpis.push(self.json_bytes("rest", rest));
+                    },
+                }
+            },
+
+            SKESK(ref s) => {
+                f.value("version", s.version());
+                match s {
+                    openpgp::packet::SKESK::V4(ref s) => {
+                        f.string("symmetric_algo", s.symmetric_algo())
+                            .value("s2k", self.json_s2k(s.s2k()));
+                        if let Some(esk) = s.esk() {
+                            f.string("esk", hex::encode(esk));
+                        }
+                    },
+
+                    openpgp::packet::SKESK::V5(ref s) => {
+                        f.string("symmetric_algo", s.symmetric_algo())
+                            .string("aead_algo", s.aead_algo())
+                            .value("s2k", self.json_s2k(s.s2k()))
+                            .string("iv", hex::encode(s.aead_iv()));
+                        if let Some(esk) = s.esk() {
+                            f.string("esk", hex::encode(esk));
+                        }
+                        f.string("digest", hex::encode(s.aead_digest()));
+                    },
+                }
+            },
+
+            SEIP(ref s) => {
+                f.value("version", s.version());
+            },
+
+            MDC(ref m) => {
+                f.string("hash", hex::encode(m.hash()))
+                    .string("computed_hash", hex::encode(m.computed_hash()));
+            },
+
+            AED(ref a) => {
+                f.value("version", a.version())
+                    .string("symmetric_algo", a.symmetric_algo())
+                    .string("aead_algo", a.aead())
+                    .value("chunk_size", a.chunk_size())
+                    .string("iv", hex::encode(a.iv()));
             },
-            _ => (),
         }
 
-        Ok(())
+        if let Some(fields) = additional_fields {
+            for field in fields {
+                field.json(&mut f);
+            }
+        }
+        o.value("fields", f);
+
+        if ! mpis.is_empty() {
+            o.value("mpis", json_array(mpis));
+        }
+
+        if let Some(map) = map {
+            o.value("map", json_array(map.iter().map(|field| {
+                let mut fo = Object::default();
+                fo.string("name", field.name)
+                    .value("offset", field.offset)
+                    .value("length", field.length)
+                    .string("data", hex::encode(field.data));
+                fo
+            })));
+        }
+
+        o
+    }
+
+    /// Returns the subpacket `s` of `sig` as JSON object.
+    fn json_subpacket(&self, s: Subpacket, sig: &Signature) -> Object {
+        let (label, value) = self.subpacket_field(s.tag, &s.value, sig);
+        let mut o = Object::default();
+        o.value("tag", u8::from(s.tag))
+            .string("type", label)
+            .value("critical", s.critical);
+        if let SubpacketValue::EmbeddedSignature(ref sig) = s.value {
+            o.value("signature", self.json_packet(None, sig, None, None));
+        } else {
+            o.string("value", value);
+        }
+        o
+    }
+
+    /// Describes the MPI `name`.
+    ///
+    /// The value is only included if MPIs are requested.
+    fn json_mpi(&self, name: &str, mpi: &MPI) -> Object {
+        let mut o = self.json_bytes(name, &mpi.value);
+        o.value("bits", mpi.bits);
+        o
+    }
+
+    /// Describes the opaque value `name`.
+    ///
+    /// The value is only included if MPIs are requested.
+    fn json_bytes(&self, name: &str, value: &[u8]) -> Object {
+        let mut o = Object::default();
+        o.string("name", name).value("length", value.len());
+        if self.mpis {
+            o.string("value", hex::encode(value));
+        }
+        o
+    }
+
+    /// Returns `s2k` as JSON object.
+    fn json_s2k(&self, s2k: &S2K) -> Object {
+        use self::S2K::*;
+        let mut o = Object::default();
+        match s2k {
+            Simple { hash } => {
+                o.string("type", "simple").string("hash_algo", hash);
+            },
+            Salted { hash, ref salt } => {
+                o.string("type", "salted").string("hash_algo", hash)
+                    .string("salt", hex::encode(salt));
+            },
+            Iterated { hash, ref salt, hash_bytes } => {
+                o.string("type", "iterated").string("hash_algo", hash)
+                    .string("salt", hex::encode(salt))
+                    .value("hash_bytes", *hash_bytes);
+            },
+            Private(n) => {
+                o.string("type", "private").value("value", *n);
+            },
+            Unknown(n) => {
+                o.string("type", "unknown").value("value", *n);
+            },
+        }
+        o
     }
 
     fn dump_s2k(&self, output: &mut io::Write, i: &str, s2k: &S2K)
@@ -0,0 +1,250 @@
+//! Explains what `sq encrypt` and `sq sign` do.
+//!
+//! This is used to implement `--explain`, which lists the selected
+//! keys and algorithms, and estimates the size of the output.  The
+//! estimate is based on the packets the streaming serializer emits,
+//! but the sizes of some fields, like signature subpackets, are
+//! approximated.
+
+use std::io;
+
+extern crate sequoia_openpgp as openpgp;
+use openpgp::TPK;
+use openpgp::constants::{HashAlgorithm, SymmetricAlgorithm};
+use openpgp::crypto::mpis;
+use openpgp::packet::Key;
+use openpgp::serialize::stream::{NegotiatedAlgorithms, RecipientReport};
+
+/// The hash algorithm `Signer` uses by default.
+const HASH_ALGO: HashAlgorithm = HashAlgorithm::SHA512;
+
+/// The chunk size `Encryptor` uses for AEAD encrypted messages.
+const AEAD_CHUNK_SIZE: u64 = 4096;
+
+/// The largest chunk the partial body filter emits.
+const PARTIAL_BODY_CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Describes the output of `sq encrypt` or `sq sign`.
+pub struct Plan<'a> {
+    /// The recipients, and which of their keys are used.
+    pub recipients: Vec<(&'a TPK, RecipientReport)>,
+    /// The number of passwords.
+    pub passwords: usize,
+    /// The algorithms used for encryption.
+    pub algos: Option<NegotiatedAlgorithms>,
+    /// The signing keys.
+    pub signers: Vec<&'a Key>,
+    /// Whether to create a detached signature.
+    pub detached: bool,
+    /// Whether the input already is an OpenPGP message.
+    pub message: bool,
+    /// Whether to ASCII-armor the output.
+    pub armor: bool,
+    /// The size of the input, if known.
+    pub input_size: Option<u64>,
+}
+
+impl<'a> Plan<'a> {
+    /// Returns a plan for signing.
+    pub fn sign(signers: Vec<&'a Key>, detached: bool, message: bool,
+                armor: bool, input_size: Option<u64>) -> Self {
+        Plan {
+            recipients: Vec::new(),
+            passwords: 0,
+            algos: None,
+            signers: signers,
+            detached: detached,
+            message: message,
+            armor: armor,
+            input_size: input_size,
+        }
+    }
+
+    /// Prints the plan to stderr.
+    pub fn print(&self) {
+        for (tpk, report) in self.recipients.iter() {
+            eprintln!("Recipient {}:", tpk.primary().fingerprint());
+            for (fp, selection) in report.keys() {
+                eprintln!("  {}: {}", fp, selection);
+            }
+        }
+        if self.passwords > 0 {
+            eprintln!("Passwords: {}", self.passwords);
+        }
+        if let Some(ref algos) = self.algos {
+            eprintln!("Symmetric algorithm: {}", algos.symmetric_algo());
+            match algos.aead_algo() {
+                Some(aead) =>
+                    eprintln!("Encryption container: AEAD ({})", aead),
+                None => eprintln!("Encryption container: SEIP with MDC"),
+            }
+        }
+        for key in self.signers.iter() {
+            eprintln!("Signing key: {} ({})", key.fingerprint(),
+                      key.pk_algo());
+        }
+        if ! self.signers.is_empty() {
+            eprintln!("Hash algorithm: {}", HASH_ALGO);
+        }
+        eprintln!("Armor: {}",
+                  match (self.armor, self.detached) {
+                      (false, _) => "none",
+                      (true, false) => "PGP MESSAGE",
+                      (true, true) => "PGP SIGNATURE",
+                  });
+        match self.estimated_size() {
+            Some(n) => eprintln!("Estimated output size: {} bytes", n),
+            None => eprintln!("Estimated output size: unknown"),
+        }
+    }
+
+    /// Estimates the size of the output.
+    ///
+    /// Returns `None` if the size of the input is needed, but not
+    /// known.
+    pub fn estimated_size(&self) -> Option<u64> {
+        let n = if self.detached { 0 } else { self.input_size? };
+        let recipients = if self.signers.is_empty() {
+            0
+        } else {
+            self.recipients.len() as u64
+        };
+        let signatures: u64 = self.signers.iter()
+            .map(|k| packet_len(signature_len(k, recipients)))
+            .sum();
+        let one_pass_sigs = self.signers.len() as u64 * packet_len(13);
+
+        let mut size = if self.detached {
+            signatures
+        } else if self.message {
+            n + one_pass_sigs + signatures
+        } else {
+            // Literal data packet without a filename.
+            let literal = streamed_len(6 + n);
+            if self.signers.is_empty() {
+                literal
+            } else {
+                one_pass_sigs + literal + signatures
+            }
+        };
+
+        if let Some(ref algos) = self.algos {
+            let sym = algos.symmetric_algo();
+            let key_size = sym.key_size().unwrap_or(32) as u64;
+            size = match algos.aead_algo() {
+                Some(aead) => {
+                    let iv = aead.iv_size().unwrap_or(16) as u64;
+                    let tag = aead.digest_size().unwrap_or(16) as u64;
+                    let chunks = (size + AEAD_CHUNK_SIZE - 1)
+                        / AEAD_CHUNK_SIZE;
+                    // Version 5 SKESKs.
+                    let skesk = packet_len(1 + 1 + 1 + 11 + iv + key_size
+                                           + tag);
+                    self.passwords as u64 * skesk
+                        + streamed_len(4 + iv + size + (chunks + 1) * tag)
+                },
+                None => {
+                    let block = sym.block_size().unwrap_or(16) as u64;
+                    let skesk = packet_len(1 + 1 + 11 + 1 + key_size);
+                    // The MDC packet is 22 bytes.
+                    self.passwords as u64 * skesk
+                        + streamed_len(1 + block + 2 + size + 22)
+                },
+            };
+
+            for (tpk, report) in self.recipients.iter() {
+                for fp in report.selected() {
+                    if let Some((_, _, key)) = tpk.keys_all()
+                        .find(|(_, _, k)| k.fingerprint() == *fp)
+                    {
+                        size += packet_len(pkesk_len(key, sym));
+                    }
+                }
+            }
+        }
+
+        if self.armor {
+            size = armored_len(size, if self.detached {
+                "SIGNATURE"
+            } else {
+                "MESSAGE"
+            });
+        }
+
+        Some(size)
+    }
+}
+
+/// Returns the size of the input, reading it if necessary.
+pub fn input_size(input: &mut io::Read, size: Option<u64>)
+                  -> io::Result<u64> {
+    match size {
+        Some(n) => Ok(n),
+        None => io::copy(input, &mut io::sink()),
+    }
+}
+
+/// Returns the size of a packet with a body of `len` bytes.
+fn packet_len(len: u64) -> u64 {
+    1 + if len < 192 {
+        1
+    } else if len < 8384 {
+        2
+    } else {
+        5
+    } + len
+}
+
+/// Returns the size of a streamed packet with a body of `len` bytes.
+///
+/// Streamed packets use partial body lengths, which adds a length
+/// octet to every chunk.
+fn streamed_len(len: u64) -> u64 {
+    1 + len + 5 * (len / PARTIAL_BODY_CHUNK_SIZE + 1)
+}
+
+/// Returns the size of a signature body made using `key`.
+fn signature_len(key: &Key, recipients: u64) -> u64 {
+    use self::mpis::PublicKey::*;
+    let mpis = match key.mpis() {
+        RSA { n, .. } => 2 + n.value.len(),
+        DSA { q, .. } => 2 * (2 + q.value.len()),
+        EdDSA { .. } => 2 * (2 + 32),
+        ECDSA { curve, .. } => 2 * (2 + (curve.bits().unwrap_or(521) + 7) / 8),
+        _ => 2 + 512,
+    } as u64;
+
+    // Version, type, algorithms, hashed area with creation time,
+    // issuer fingerprint, and intended recipients, unhashed area
+    // with issuer, and the hash prefix.
+    4 + 2 + 6 + 23 + 23 * recipients + 2 + 10 + 2 + mpis
+}
+
+/// Returns the size of a PKESK body for `key`.
+fn pkesk_len(key: &Key, sym: SymmetricAlgorithm) -> u64 {
+    use self::mpis::PublicKey::*;
+    let esk = match key.mpis() {
+        RSA { n, .. } => 2 + n.value.len(),
+        Elgamal { p, .. } => 2 * (2 + p.value.len()),
+        ECDH { q, .. } => {
+            // The session key, the algorithm, and a checksum are
+            // padded and wrapped.
+            let key_size = sym.key_size().unwrap_or(32);
+            2 + q.value.len() + 1 + (key_size + 3 + 7) / 8 * 8 + 8
+        },
+        _ => 2 + 512,
+    } as u64;
+
+    // Version, recipient, algorithm.
+    1 + 8 + 1 + esk
+}
+
+/// Returns the size of `len` bytes armored as `PGP <blurb>`.
+fn armored_len(len: u64, blurb: &str) -> u64 {
+    let encoded = (len + 2) / 3 * 4;
+    let lines = (encoded + 63) / 64;
+    let begin = "-----BEGIN PGP -----\n".len() + blurb.len();
+    let end = "-----END PGP -----\n".len() + blurb.len();
+    // The empty line after the headers, and the checksum.
+    begin as u64 + 1 + encoded + lines + 6 + end as u64
+}
@@ -0,0 +1,125 @@
+//! Generates shell completions and man pages.
+
+use failure;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use clap::{self, App, ArgMatches, Shell};
+
+use sq_cli;
+
+pub fn completions(m: &ArgMatches) -> failure::Fallible<()> {
+    let shell: Shell = m.value_of("shell").expect("required argument")
+        .parse().map_err(failure::err_msg)?;
+    let mut app = sq_cli::build();
+
+    if let Some(dir) = m.value_of("directory") {
+        fs::create_dir_all(dir)?;
+        app.gen_completions("sq", shell, dir);
+    } else {
+        app.gen_completions_to("sq", shell, &mut io::stdout());
+    }
+    Ok(())
+}
+
+pub fn man(m: &ArgMatches) -> failure::Fallible<()> {
+    let dir = Path::new(m.value_of("directory").expect("required argument"));
+    fs::create_dir_all(dir)?;
+
+    let mut commands = vec![(vec![], sq_cli::build().p.meta.about)];
+    collect_subcommands(&sq_cli::build(), &[], &mut commands);
+
+    for &(ref path, about) in commands.iter() {
+        let children = commands.iter()
+            .filter(|&&(ref p, _)| p.len() == path.len() + 1
+                    && p.starts_with(path))
+            .map(|&(ref p, _)| page_name(p))
+            .collect::<Vec<_>>();
+        let parent = if path.is_empty() {
+            None
+        } else {
+            Some(page_name(&path[..path.len() - 1]))
+        };
+
+        let name = page_name(path);
+        let mut sink = File::create(dir.join(format!("{}.1", name)))?;
+        write_man_page(&mut sink, &name, about, &help(path)?,
+                       parent.as_ref().map(|p| p.as_str()), &children)?;
+    }
+    Ok(())
+}
+
+/// Collects the paths to all subcommands of `app`, and their
+/// descriptions.
+fn collect_subcommands(app: &App<'static, 'static>, prefix: &[String],
+                       acc: &mut Vec<(Vec<String>, Option<&'static str>)>) {
+    // clap 2 has no public interface to enumerate subcommands.
+    for sc in app.p.subcommands.iter() {
+        let mut path = prefix.to_vec();
+        path.push(sc.get_name().into());
+        acc.push((path.clone(), sc.p.meta.about));
+        collect_subcommands(sc, &path, acc);
+    }
+}
+
+/// Returns the name of the man page for the given subcommand.
+fn page_name(path: &[String]) -> String {
+    let mut name = String::from("sq");
+    for c in path {
+        name.push('-');
+        name.push_str(c);
+    }
+    name
+}
+
+/// Returns the output of `sq <path> --help`.
+fn help(path: &[String]) -> failure::Fallible<String> {
+    let mut args = vec!["sq".to_string()];
+    args.extend(path.iter().cloned());
+    args.push("--help".into());
+
+    match sq_cli::build().get_matches_from_safe(args) {
+        Err(ref e) if e.kind == clap::ErrorKind::HelpDisplayed =>
+            Ok(e.message.clone()),
+        Err(e) => Err(e.into()),
+        Ok(_) => Err(failure::err_msg("Failed to get the help text")),
+    }
+}
+
+/// Escapes `s` for use in roff.
+fn roff_escape(s: &str) -> String {
+    let s = s.replace('\\', "\\e");
+    if s.starts_with('.') || s.starts_with('\'') {
+        format!("\\&{}", s)
+    } else {
+        s
+    }
+}
+
+fn write_man_page(sink: &mut Write, name: &str, about: Option<&str>,
+                  help: &str, parent: Option<&str>, children: &[String])
+                  -> io::Result<()> {
+    writeln!(sink, ".TH {} 1 \"\" \"sq {}\" \"User Commands\"",
+             name.to_uppercase(), env!("CARGO_PKG_VERSION"))?;
+    writeln!(sink, ".SH NAME")?;
+    writeln!(sink, "{} \\- {}", name,
+             roff_escape(about.and_then(|a| a.lines().next()).unwrap_or("")))?;
+    writeln!(sink, ".SH DESCRIPTION")?;
+    writeln!(sink, ".nf")?;
+    // The first line is the name of the command.
+    for line in help.lines().skip(1) {
+        writeln!(sink, "{}", roff_escape(line))?;
+    }
+    writeln!(sink, ".fi")?;
+
+    let see_also = parent.into_iter().chain(children.iter().map(|c| c.as_str()))
+        .collect::<Vec<_>>();
+    if ! see_also.is_empty() {
+        writeln!(sink, ".SH SEE ALSO")?;
+        for (i, page) in see_also.iter().enumerate() {
+            writeln!(sink, ".BR {} (1){}", page,
+                     if i + 1 < see_also.len() { "," } else { "" })?;
+        }
+    }
+    Ok(())
+}
@@ -1,18 +1,103 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
 use std::io::{self, Read};
-use time;
+use std::path::Path;
 
 use clap;
 
 extern crate sequoia_openpgp as openpgp;
-use openpgp::{Packet, Result};
+use sequoia_core::{Context, TimeFormat};
+use openpgp::{KeyID, Packet, Result, TPK};
+use openpgp::packet::{Key, Signature, UserAttribute};
+use openpgp::packet::user_attribute::{Image, Subpacket};
 use openpgp::parse::{Parse, PacketParserResult};
+use sequoia_store::Pool;
 
-use super::TIMEFMT;
+/// Keys that can be used to verify third-party certifications.
+struct Certifiers<'a> {
+    /// Keys given on the command line, indexed by keyid.
+    keys: HashMap<KeyID, Vec<Key>>,
+    /// If set, the key pool is consulted as well.
+    ctx: Option<&'a Context>,
+    /// Keys found in the key pool, indexed by keyid.
+    pool: RefCell<HashMap<KeyID, Vec<Key>>>,
+}
+
+impl<'a> Certifiers<'a> {
+    fn new(tpks: Vec<TPK>, ctx: Option<&'a Context>) -> Self {
+        Certifiers {
+            keys: index(tpks.iter()),
+            ctx: ctx,
+            pool: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Returns whether we try to verify certifications at all.
+    fn enabled(&self) -> bool {
+        ! self.keys.is_empty() || self.ctx.is_some()
+    }
+
+    /// Returns all candidate keys that may have made `sig`.
+    fn lookup(&self, sig: &Signature) -> Vec<Key> {
+        let issuer = if let Some(fp) = sig.issuer_fingerprint() {
+            fp.to_keyid()
+        } else if let Some(keyid) = sig.issuer() {
+            keyid
+        } else {
+            return Vec::new();
+        };
+
+        let mut keys =
+            self.keys.get(&issuer).cloned().unwrap_or_else(Vec::new);
+        if let Some(ctx) = self.ctx {
+            let mut pool = self.pool.borrow_mut();
+            if ! pool.contains_key(&issuer) {
+                let tpks = Pool::lookup_by_subkeyid(ctx, &issuer)
+                    .and_then(|key| key.tpk())
+                    .map(|tpk| vec![tpk])
+                    .unwrap_or_else(|_| Vec::new());
+                let found = index(tpks.iter()).remove(&issuer)
+                    .unwrap_or_else(Vec::new);
+                pool.insert(issuer.clone(), found);
+            }
+            keys.extend(pool[&issuer].iter().cloned());
+        }
+        keys
+    }
+}
 
-pub fn inspect(m: &clap::ArgMatches, output: &mut io::Write)
+/// Indexes the (sub)keys of the given TPKs by keyid.
+fn index<'a, I>(tpks: I) -> HashMap<KeyID, Vec<Key>>
+    where I: Iterator<Item=&'a TPK>
+{
+    let mut keys = HashMap::new();
+    for tpk in tpks {
+        for (_, _, key) in tpk.keys_all() {
+            keys.entry(key.keyid()).or_insert_with(Vec::new)
+                .push(key.clone());
+        }
+    }
+    keys
+}
+
+pub fn inspect(ctx: &Context, m: &clap::ArgMatches, force: bool,
+               output: &mut io::Write)
                -> Result<()> {
     let print_keygrips = m.is_present("keygrips");
     let print_certifications = m.is_present("certifications");
+    let lint = m.is_present("lint");
+    let certifiers = Certifiers::new(
+        m.values_of("keyring")
+            .map(::load_tpks)
+            .unwrap_or(Ok(vec![]))?,
+        if m.is_present("use-store") { Some(ctx) } else { None });
+    let photos = m.value_of("export-photos").map(|d| Photos {
+        dir: Path::new(d),
+        force,
+    });
+    // Verifying certifications implies listing them.
+    let print_certifications = print_certifications || certifiers.enabled();
 
     let input = m.value_of("input");
     let input_name = input.unwrap_or("-");
@@ -42,8 +127,9 @@ pub fn inspect(m: &clap::ArgMatches, output: &mut io::Write)
                     let pp = openpgp::PacketPile::from(
                         ::std::mem::replace(&mut packets, Vec::new()));
                     let tpk = openpgp::TPK::from_packet_pile(pp)?;
-                    inspect_tpk(output, &tpk, print_keygrips,
-                                print_certifications)?;
+                    inspect_tpk(output, ctx.time_format(), &tpk,
+                                print_keygrips, print_certifications,
+                                &certifiers, lint, photos.as_ref())?;
                 }
             },
             Packet::Literal(_) => {
@@ -101,7 +187,9 @@ pub fn inspect(m: &clap::ArgMatches, output: &mut io::Write)
         } else if is_tpk.is_ok() || is_keyring.is_ok() {
             let pp = openpgp::PacketPile::from(packets);
             let tpk = openpgp::TPK::from_packet_pile(pp)?;
-            inspect_tpk(output, &tpk, print_keygrips, print_certifications)?;
+            inspect_tpk(output, ctx.time_format(), &tpk, print_keygrips,
+                        print_certifications, &certifiers, lint,
+                        photos.as_ref())?;
         } else if packets.is_empty() && ! sigs.is_empty() {
             writeln!(output, "Detached signature{}.",
                      if sigs.len() > 1 { "s" } else { "" })?;
@@ -124,24 +212,32 @@ pub fn inspect(m: &clap::ArgMatches, output: &mut io::Write)
     Ok(())
 }
 
-fn inspect_tpk(output: &mut io::Write, tpk: &openpgp::TPK,
-               print_keygrips: bool, print_certifications: bool) -> Result<()> {
+fn inspect_tpk(output: &mut io::Write, time_format: &TimeFormat,
+               tpk: &openpgp::TPK, print_keygrips: bool, print_certifications: bool,
+               certifiers: &Certifiers, lint: bool, photos: Option<&Photos>)
+               -> Result<()> {
     writeln!(output, "Transferable {} Key.",
              if tpk.is_tsk() { "Secret" } else { "Public" })?;
     writeln!(output)?;
     writeln!(output, "    Fingerprint: {}", tpk.fingerprint())?;
     inspect_revocation(output, "", tpk.revocation_status())?;
-    inspect_key(output, "", tpk.primary(), tpk.primary_key_signature(),
-                tpk.certifications(),
-                print_keygrips, print_certifications)?;
+    inspect_key(output, time_format, "", tpk.primary(),
+                tpk.primary_key_signature(), print_keygrips)?;
+    inspect_certifications(output, tpk.certifications(),
+                           print_certifications, certifiers,
+                           &|sig, signer| sig.verify_primary_key_binding(
+                               signer, tpk.primary()))?;
     writeln!(output)?;
 
     for skb in tpk.subkeys() {
         writeln!(output, "         Subkey: {}", skb.subkey().fingerprint())?;
         inspect_revocation(output, "", skb.revoked(None))?;
-        inspect_key(output, "", skb.subkey(), skb.binding_signature(),
-                    skb.certifications(),
-                    print_keygrips, print_certifications)?;
+        inspect_key(output, time_format, "", skb.subkey(),
+                    skb.binding_signature(), print_keygrips)?;
+        inspect_certifications(output, skb.certifications(),
+                               print_certifications, certifiers,
+                               &|sig, signer| sig.verify_subkey_binding(
+                                   signer, tpk.primary(), skb.subkey()))?;
         writeln!(output)?;
     }
 
@@ -157,20 +253,108 @@ fn inspect_tpk(output: &mut io::Write, tpk: &openpgp::TPK,
         }
         inspect_certifications(output,
                                uidb.certifications(),
-                               print_certifications)?;
+                               print_certifications, certifiers,
+                               &|sig, signer| sig.verify_userid_binding(
+                                   signer, tpk.primary(), uidb.userid()))?;
+        writeln!(output)?;
+    }
+
+    for (i, uab) in tpk.user_attributes().enumerate() {
+        inspect_user_attribute(output, tpk, i, uab.user_attribute(), photos)?;
+        inspect_revocation(output, "", uab.revoked(None))?;
+        inspect_certifications(output,
+                               uab.certifications(),
+                               print_certifications, certifiers,
+                               &|sig, signer| sig.verify_user_attribute_binding(
+                                   signer, tpk.primary(),
+                                   uab.user_attribute()))?;
         writeln!(output)?;
     }
 
+    if lint {
+        let lints = tpk.lint(&openpgp::tpk::LintPolicy::default());
+        if lints.is_empty() {
+            writeln!(output, "           Lint: No problems found")?;
+        }
+        for l in lints {
+            writeln!(output, "           Lint: {}", l)?;
+        }
+        writeln!(output)?;
+    }
+
+    Ok(())
+}
+
+/// Where to export photos to.
+struct Photos<'a> {
+    dir: &'a Path,
+    /// Whether existing files are overwritten.
+    force: bool,
+}
+
+/// Describes the subpackets of the `n`th user attribute of `tpk`.
+///
+/// If `photos` is given, JPEG images are written to files named
+/// after the fingerprint and the user attribute.
+fn inspect_user_attribute(output: &mut io::Write, tpk: &TPK, n: usize,
+                          ua: &UserAttribute, photos: Option<&Photos>)
+                          -> Result<()> {
+    for (i, subpacket) in ua.subpackets().enumerate() {
+        let label = if i == 0 { "  UserAttribute" } else { "               " };
+        let image = match subpacket {
+            Ok(Subpacket::Image(Image::JPEG(image))) => {
+                writeln!(output, "{}: JPEG image, {} bytes",
+                         label, image.len())?;
+                image
+            },
+            Ok(Subpacket::Image(Image::Private(format, image))) => {
+                writeln!(output, "{}: Private image format {}, {} bytes",
+                         label, format, image.len())?;
+                continue;
+            },
+            Ok(Subpacket::Image(Image::Unknown(format, image))) => {
+                writeln!(output, "{}: Unknown image format {}, {} bytes",
+                         label, format, image.len())?;
+                continue;
+            },
+            Ok(Subpacket::Unknown(tag, data)) => {
+                writeln!(output, "{}: Unknown subpacket {}, {} bytes",
+                         label, tag, data.len())?;
+                continue;
+            },
+            Err(e) => {
+                writeln!(output, "{}: Malformed: {}", label, e)?;
+                continue;
+            },
+        };
+
+        if let Some(photos) = photos {
+            let name = if i == 0 {
+                format!("{}-{}.jpg", tpk.fingerprint().to_hex(), n)
+            } else {
+                format!("{}-{}-{}.jpg", tpk.fingerprint().to_hex(), n, i)
+            };
+            let path = photos.dir.join(name);
+            if path.exists() && ! photos.force {
+                return Err(format_err!(
+                    "File {:?} exists, use --force to overwrite", path));
+            }
+            fs::create_dir_all(photos.dir)?;
+            fs::write(&path, &image)?;
+            writeln!(output, "                 Exported to {}",
+                     path.display())?;
+        }
+    }
+
     Ok(())
 }
 
 fn inspect_key(output: &mut io::Write,
+               time_format: &TimeFormat,
                indent: &str,
                key: &openpgp::packet::Key,
                binding_signature: Option<&openpgp::packet::Signature>,
-               certs: &[openpgp::packet::Signature],
-               print_keygrips: bool,
-               print_certifications: bool)
+               print_keygrips: bool)
                -> Result<()> {
     if let Some(sig) = binding_signature {
         if sig.key_expired(key) {
@@ -182,20 +366,20 @@ fn inspect_key(output: &mut io::Write,
 
     if print_keygrips {
         writeln!(output, "{}        Keygrip: {}", indent,
-                 key.mpis().keygrip()?)?;
+                 key.keygrip()?)?;
     }
     writeln!(output, "{}Public-key algo: {}", indent, key.pk_algo())?;
     if let Some(bits) = key.mpis().bits() {
         writeln!(output, "{}Public-key size: {} bits", indent, bits)?;
     }
     writeln!(output, "{}  Creation time: {}", indent,
-             time::strftime(TIMEFMT, key.creation_time())?)?;
+             time_format.format(key.creation_time()))?;
     if let Some(sig) = binding_signature {
         if let Some(expires) = sig.key_expiration_time() {
             let expiration_time = *key.creation_time() + expires;
             writeln!(output, "{}Expiration time: {} (creation time + {})",
                      indent,
-                     time::strftime(TIMEFMT, &expiration_time)?,
+                     time_format.format(&expiration_time),
                      expires)?;
         }
 
@@ -203,7 +387,6 @@ fn inspect_key(output: &mut io::Write,
             writeln!(output, "{}       Keyflags: {}", indent, keyflags)?;
         }
     }
-    inspect_certifications(output, certs, print_certifications)?;
 
     Ok(())
 }
@@ -275,16 +458,45 @@ fn inspect_signatures(output: &mut io::Write,
 
 fn inspect_certifications(output: &mut io::Write,
                           certs: &[openpgp::packet::Signature],
-                          print_certifications: bool) -> Result<()> {
+                          print_certifications: bool,
+                          certifiers: &Certifiers,
+                          verify: &dyn Fn(&Signature, &Key) -> Result<bool>)
+                          -> Result<()> {
     if print_certifications {
         for sig in certs {
-            if let Some(fp) = sig.issuer_fingerprint() {
-                writeln!(output, "   Certified by: {}", fp)?;
+            let issuer = if let Some(fp) = sig.issuer_fingerprint() {
+                fp.to_string()
             } else if let Some(kid) = sig.issuer() {
-                writeln!(output, "   Certified by: {}", kid)?;
+                kid.to_string()
+            } else {
+                "unknown issuer".into()
+            };
+
+            if ! certifiers.enabled() {
+                writeln!(output, "   Certified by: {}", issuer)?;
+                continue;
             }
+
+            // A bad signature is invalid, but if we fail to check
+            // it, e.g. because the algorithm is not supported, we
+            // do not know.
+            let results: Vec<Result<bool>> = certifiers.lookup(sig).iter()
+                .map(|signer| verify(sig, signer)).collect();
+            let status = if results.is_empty() {
+                "unverifiable, missing key".into()
+            } else if results.iter().any(|r| r.as_ref().ok() == Some(&true)) {
+                "good".into()
+            } else if results.iter().any(|r| r.as_ref().ok() == Some(&false)) {
+                "invalid".into()
+            } else {
+                match results[0] {
+                    Err(ref e) => format!("unverifiable, {}", e),
+                    Ok(_) => unreachable!(),
+                }
+            };
+            writeln!(output, "   Certified by: {} ({})", issuer, status)?;
         }
-        if ! certs.is_empty() {
+        if ! certs.is_empty() && ! certifiers.enabled() {
             writeln!(output, "                 \
                               Certifications have NOT been verified!")?;
         }
@@ -1,17 +1,31 @@
 use failure;
-use failure::Fail;
+use failure::ResultExt;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use base64;
 use clap::ArgMatches;
 use itertools::Itertools;
+use serde_json;
 
-use openpgp::Packet;
-use openpgp::tpk::{TPKBuilder, CipherSuite};
-use openpgp::packet::KeyFlags;
+use openpgp::{Fingerprint, KeyID, Packet, PacketPile, TPK};
+use openpgp::constants::{Curve, SignatureType};
+use openpgp::crypto::mpis::{MPI, PublicKey};
+use openpgp::parse::Parse;
+use openpgp::tpk::{TPKBuilder, CipherSuite, TPKDiff, TPKParser};
+use openpgp::packet::{self, Key, KeyFlags, Signature, UserAttribute};
+use openpgp::packet::{signature, user_attribute};
 use openpgp::armor::{Writer, Kind};
 use openpgp::serialize::Serialize;
 
-use ::create_or_stdout;
+use sequoia_core::Context;
+use super::store::Pool;
 
-pub fn generate(m: &ArgMatches, force: bool) -> failure::Fallible<()> {
+use ::{create_or_stdout, open_or_stdin};
+use config::Config;
+
+pub fn generate(m: &ArgMatches, force: bool, config: &Config)
+                -> failure::Fallible<()> {
     let mut builder = TPKBuilder::new();
 
     // User ID
@@ -23,96 +37,26 @@ pub fn generate(m: &ArgMatches, force: bool) -> failure::Fallible<()> {
     }
 
     // Expiration.
-    const SECONDS_IN_DAY : i64 = 24 * 60 * 60;
-    const SECONDS_IN_YEAR : i64 =
-        // Average number of days in a year.
-        (365.2422222 * SECONDS_IN_DAY as f64) as i64;
-
-    let even_off = |s| {
-        if s < 7 * SECONDS_IN_DAY {
-            // Don't round down, too small.
-            s
-        } else {
-            s - (s % SECONDS_IN_DAY)
-        }
-    };
-
     match m.value_of("expiry") {
-        Some(expiry) if expiry == "never" =>
-            builder = builder.set_expiration(None),
-
-        Some(expiry) => {
-            let mut expiry = expiry.chars().peekable();
-
-            let _ = expiry.by_ref()
-                .peeking_take_while(|c| c.is_whitespace())
-                .for_each(|_| ());
-            let digits = expiry.by_ref()
-                .peeking_take_while(|c| {
-                    *c == '+' || *c == '-' || c.is_digit(10)
-                }).collect::<String>();
-            let _ = expiry.by_ref()
-                .peeking_take_while(|c| c.is_whitespace())
-                .for_each(|_| ());
-            let suffix = expiry.next();
-            let _ = expiry.by_ref()
-                .peeking_take_while(|c| c.is_whitespace())
-                .for_each(|_| ());
-            let junk = expiry.collect::<String>();
-
-            if digits == "" {
-                return Err(format_err!(
-                    "--expiry: missing count \
-                     (try: '2y' for 2 years)"));
-            }
-
-            let count : i64 = match digits.parse::<i32>() {
-                Ok(count) if count < 0 =>
-                    return Err(format_err!(
-                        "--expiry: Expiration can't be in the past")),
-                Ok(count) => count as i64,
-                Err(err) =>
-                    return Err(err.context(
-                        "--expiry: count is out of range").into()),
-            };
-
-            let factor = match suffix {
-                Some('y') | Some('Y') => SECONDS_IN_YEAR,
-                Some('m') | Some('M') => SECONDS_IN_YEAR / 12,
-                Some('w') | Some('W') => 7 * SECONDS_IN_DAY,
-                Some('d') | Some('D') => SECONDS_IN_DAY,
-                None =>
-                    return Err(format_err!(
-                        "--expiry: missing suffix \
-                         (try: '{}y', '{}m', '{}w' or '{}d' instead)",
-                        digits, digits, digits, digits)),
-                Some(suffix) =>
-                    return Err(format_err!(
-                        "--expiry: invalid suffix '{}' \
-                         (try: '{}y', '{}m', '{}w' or '{}d' instead)",
-                        suffix, digits, digits, digits, digits)),
-            };
-
-            if junk != "" {
-                return Err(format_err!(
-                    "--expiry: contains trailing junk ('{:?}') \
-                     (try: '{}{}')",
-                    junk, count, factor));
-            }
-
-            builder = builder.set_expiration(
-                Some(time::Duration::seconds(even_off(count * factor))));
-        }
+        Some(expiry) => builder = builder.set_expiration(
+            super::parse_expiry("--expiry", expiry)?),
 
         // Not specified.  Use the default.
         None => {
             builder = builder.set_expiration(
-                Some(time::Duration::seconds(even_off(3 * SECONDS_IN_YEAR))));
+                Some(time::Duration::seconds(
+                    super::even_off(3 * super::SECONDS_IN_YEAR))));
         }
     };
 
     // Cipher Suite
-    match m.value_of("cipher-suite") {
+    let cipher_suite = if m.occurrences_of("cipher-suite") > 0 {
+        m.value_of("cipher-suite")
+    } else {
+        config.get_str(&["key", "generate"], "cipher-suite")?
+            .or(m.value_of("cipher-suite"))
+    };
+    match cipher_suite {
         None | Some("rsa3k") => {
             builder = builder.set_cipher_suite(CipherSuite::RSA3k);
         }
@@ -161,6 +105,11 @@ pub fn generate(m: &ArgMatches, force: bool) -> failure::Fallible<()> {
         }
     }
 
+    // Authentication Capability
+    if m.is_present("can-authenticate") {
+        builder = builder.add_authentication_subkey();
+    }
+
     if m.is_present("with-password") {
         let p0 = rpassword::read_password_from_tty(Some(
             "Enter password to protect the key: "))?.into();
@@ -222,3 +171,491 @@ pub fn generate(m: &ArgMatches, force: bool) -> failure::Fallible<()> {
 
     Ok(())
 }
+
+/// Parses a Key ID or fingerprint given on the command line.
+/// Exports keys, stripping secret key material if `--public` is
+/// given.
+pub fn export(m: &ArgMatches, force: bool, config: &Config)
+              -> failure::Fallible<()> {
+    let input = open_or_stdin(m.value_of("input"))?;
+    let mut output = create_or_stdout(m.value_of("output"), force)?;
+    let armor = ! m.is_present("binary") && config.armor(&["key", "export"])?;
+
+    if m.is_present("public") {
+        // Stream the packets, so that large keyrings can be
+        // exported.
+        if armor {
+            let mut output = Writer::new(&mut output, Kind::PublicKey, &[])?;
+            packet::strip_secrets(input, &mut output)?;
+            output.finalize()?;
+        } else {
+            packet::strip_secrets(input, &mut output)?;
+        }
+        return Ok(());
+    }
+
+    let tpks = TPKParser::from_reader(input)?
+        .collect::<openpgp::Result<Vec<TPK>>>()
+        .context("Malformed key")?;
+    if armor {
+        let kind = if tpks.iter().any(|tpk| tpk.is_tsk()) {
+            Kind::SecretKey
+        } else {
+            Kind::PublicKey
+        };
+        let mut output = Writer::new(&mut output, kind, &[])?;
+        for tpk in tpks {
+            tpk.as_tsk().serialize(&mut output)?;
+        }
+        output.finalize()?;
+    } else {
+        for tpk in tpks {
+            tpk.as_tsk().serialize(&mut output)?;
+        }
+    }
+    Ok(())
+}
+
+fn parse_handle(handle: &str) -> failure::Fallible<(KeyID, Option<Fingerprint>)> {
+    match Fingerprint::from_hex(handle) {
+        Ok(fp @ Fingerprint::V4(_)) => Ok((fp.to_keyid(), Some(fp))),
+        _ => match KeyID::from_hex(handle) {
+            Ok(keyid @ KeyID::V4(_)) => Ok((keyid, None)),
+            _ => Err(format_err!("Malformed Key ID or fingerprint: {:?}",
+                                 handle)),
+        },
+    }
+}
+
+/// Returns the TPK containing the key designated by `handle`.
+///
+/// If a file is given, the TPK is read from there.  Otherwise, it is
+/// looked up in the common key pool.
+fn find_tpk(ctx: &Context, m: &ArgMatches, keyid: &KeyID,
+            fp: Option<&Fingerprint>)
+            -> failure::Fallible<TPK> {
+    if let Some(input) = m.value_of("input") {
+        let mut input = open_or_stdin(Some(input))?;
+        return Ok(TPK::from_reader(&mut input)?);
+    }
+
+    let key = if let Some(fp) = fp {
+        Pool::lookup(ctx, fp)
+    } else {
+        Pool::lookup_by_keyid(ctx, keyid)
+    }.or_else(|_| Pool::lookup_by_subkeyid(ctx, keyid))
+        .context("No such key in the key pool")?;
+    Ok(key.tpk()?)
+}
+
+/// Selects the key to export.
+///
+/// If `handle` designates a subkey, it is returned, provided that it
+/// is live and, if `flags` is given, has one of the given
+/// capabilities.  If it designates the primary key, and `flags` is
+/// given, the first live key that has one of the given capabilities
+/// is returned.
+fn select_key<'a>(tpk: &'a TPK, keyid: &KeyID, fp: Option<&Fingerprint>,
+                  flags: Option<KeyFlags>)
+                  -> failure::Fallible<&'a Key> {
+    let matches = |key: &Key| if let Some(fp) = fp {
+        key.fingerprint() == *fp
+    } else {
+        key.keyid() == *keyid
+    };
+
+    if ! matches(tpk.primary()) {
+        let key = tpk.keys_all().map(|(_, _, key)| key)
+            .find(|key| matches(key))
+            .ok_or_else(|| format_err!("{} does not contain the key {}",
+                                       tpk.primary(), keyid))?;
+        if let Some(flags) = flags {
+            if ! tpk.keys_valid().key_flags(flags)
+                .any(|(_, _, key)| matches(key))
+            {
+                return Err(format_err!("{} is not live or lacks the \
+                                        required capabilities", keyid));
+            }
+        }
+        return Ok(key);
+    }
+
+    if let Some(flags) = flags {
+        tpk.keys_valid().key_flags(flags).map(|(_, _, key)| key).next()
+            .ok_or_else(|| format_err!("{} has no suitable key",
+                                       tpk.primary()))
+    } else {
+        Ok(tpk.primary())
+    }
+}
+
+/// Exports an authentication-capable key in OpenSSH's format.
+pub fn export_ssh(ctx: &Context, m: &ArgMatches, force: bool)
+                  -> failure::Fallible<()> {
+    let (keyid, fp) = parse_handle(m.value_of("key").unwrap())?;
+    let tpk = find_tpk(ctx, m, &keyid, fp.as_ref())?;
+    let key = select_key(&tpk, &keyid, fp.as_ref(),
+                         Some(KeyFlags::default().set_authenticate(true)))?;
+
+    let mut blob = Vec::new();
+    match key.mpis() {
+        PublicKey::RSA { e, n } => {
+            ssh_string(&mut blob, b"ssh-rsa");
+            ssh_mpint(&mut blob, &e.value);
+            ssh_mpint(&mut blob, &n.value);
+        },
+        PublicKey::EdDSA { curve: Curve::Ed25519, q } => {
+            ssh_string(&mut blob, b"ssh-ed25519");
+            ssh_string(&mut blob, native_point(key, q)?);
+        },
+        PublicKey::ECDSA { curve, q } => {
+            let name: &[u8] = match curve {
+                Curve::NistP256 => b"nistp256",
+                Curve::NistP384 => b"nistp384",
+                Curve::NistP521 => b"nistp521",
+                _ => return Err(format_err!(
+                    "OpenSSH does not support ECDSA over {}", curve)),
+            };
+            ssh_string(&mut blob, &[&b"ecdsa-sha2-"[..], name].concat());
+            ssh_string(&mut blob, name);
+            ssh_string(&mut blob, &q.value);
+        },
+        _ => return Err(format_err!("OpenSSH does not support {} keys",
+                                    key.pk_algo())),
+    }
+
+    // The blob starts with the key type.
+    let algo = String::from_utf8_lossy(&blob[4..4 + be_u32(&blob)])
+        .into_owned();
+    let mut output = create_or_stdout(m.value_of("output"), force)?;
+    writeln!(output, "{} {} openpgp:0x{}", algo, base64::encode(&blob),
+             key.keyid().to_hex())?;
+    Ok(())
+}
+
+/// Exports a key as X.509 SubjectPublicKeyInfo.
+///
+/// This is the format OpenSSL and other X.509-based tools use for
+/// raw public keys.
+pub fn export_raw(ctx: &Context, m: &ArgMatches, force: bool)
+                  -> failure::Fallible<()> {
+    let (keyid, fp) = parse_handle(m.value_of("key").unwrap())?;
+    let tpk = find_tpk(ctx, m, &keyid, fp.as_ref())?;
+    let key = select_key(&tpk, &keyid, fp.as_ref(), None)?;
+
+    // DER encoded object identifiers, without tag and length.
+    const RSA_ENCRYPTION: &[u8] =
+        &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+    const EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+    const ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+    const X25519: &[u8] = &[0x2b, 0x65, 0x6e];
+
+    let (algorithm, public_key) = match key.mpis() {
+        PublicKey::RSA { e, n } =>
+            ([der(0x06, RSA_ENCRYPTION), der(0x05, &[])].concat(),
+             der(0x30, &[der_integer(&n.value), der_integer(&e.value)]
+                 .concat())),
+        PublicKey::EdDSA { curve: Curve::Ed25519, q } =>
+            (der(0x06, ED25519), native_point(key, q)?.to_vec()),
+        PublicKey::ECDH { curve: Curve::Cv25519, q, .. } =>
+            (der(0x06, X25519), native_point(key, q)?.to_vec()),
+        PublicKey::ECDSA { curve, q } | PublicKey::ECDH { curve, q, .. } =>
+            match curve {
+                Curve::Ed25519 | Curve::Cv25519 | Curve::Unknown(_) =>
+                    return Err(format_err!("Cannot export keys over {}",
+                                           curve)),
+                _ => ([der(0x06, EC_PUBLIC_KEY), der(0x06, curve.oid())]
+                      .concat(),
+                      q.value.to_vec()),
+            },
+        _ => return Err(format_err!("Cannot export {} keys",
+                                    key.pk_algo())),
+    };
+
+    // The public key is a BIT STRING without unused bits.
+    let spki = der(0x30, &[
+        der(0x30, &algorithm),
+        der(0x03, &[&[0][..], &public_key].concat()),
+    ].concat());
+
+    let mut output = create_or_stdout(m.value_of("output"), force)?;
+    if m.is_present("binary") {
+        output.write_all(&spki)?;
+    } else {
+        writeln!(output, "-----BEGIN PUBLIC KEY-----")?;
+        for line in base64::encode(&spki).as_bytes().chunks(64) {
+            output.write_all(line)?;
+            writeln!(output)?;
+        }
+        writeln!(output, "-----END PUBLIC KEY-----")?;
+    }
+    Ok(())
+}
+
+/// Shows what changed between two versions of a TPK.
+///
+/// This is meant to review updates, e.g. from a keyserver, before
+/// accepting them.
+pub fn diff(ctx: &Context, m: &ArgMatches, force: bool)
+            -> failure::Fallible<()> {
+    let old = load_tpk(ctx, m.value_of("old").unwrap())
+        .context("Failed to read the old version")?;
+    let new = load_tpk(ctx, m.value_of("new").unwrap())
+        .context("Failed to read the new version")?;
+    let diff = old.diff(&new)?;
+
+    let mut output = create_or_stdout(m.value_of("output"), force)?;
+    match m.value_of("format") {
+        Some("json") => diff_json(&mut output, &diff)?,
+        _ => diff_human(&mut output, &diff)?,
+    }
+    Ok(())
+}
+
+/// Reads a TPK from the file `name`, or, if `name` is a
+/// fingerprint, from the key pool.
+fn load_tpk(ctx: &Context, name: &str) -> failure::Fallible<TPK> {
+    if ! Path::new(name).exists() {
+        if let Ok(fp @ Fingerprint::V4(_)) = Fingerprint::from_hex(name) {
+            return Ok(Pool::lookup(ctx, &fp)
+                      .context("No such key in the key pool")?
+                      .tpk()?);
+        }
+    }
+    Ok(TPK::from_file(name)?)
+}
+
+/// Returns the issuer of `sig` for display purposes.
+fn issuer(sig: &Signature) -> String {
+    sig.issuer_fingerprint().map(|fp| fp.to_hex())
+        .or_else(|| sig.issuer().map(|id| id.to_hex()))
+        .unwrap_or_else(|| "unknown issuer".into())
+}
+
+fn diff_human(output: &mut Write, diff: &TPKDiff) -> failure::Fallible<()> {
+    for (prefix, userids, user_attributes, subkeys, signatures) in vec![
+        ("+", diff.added_userids(), diff.added_user_attributes(),
+         diff.added_subkeys(), diff.added_signatures()),
+        ("-", diff.removed_userids(), diff.removed_user_attributes(),
+         diff.removed_subkeys(), diff.removed_signatures()),
+    ] {
+        for u in userids {
+            writeln!(output, "{} User ID: {}", prefix,
+                     String::from_utf8_lossy(u.value()))?;
+        }
+        for _ in user_attributes {
+            writeln!(output, "{} User Attribute", prefix)?;
+        }
+        for k in subkeys {
+            writeln!(output, "{} Subkey: {}", prefix, k.fingerprint())?;
+        }
+        for (subject, s) in signatures {
+            writeln!(output, "{} {} by {} on {}", prefix, s.sigtype(),
+                     issuer(s), subject)?;
+        }
+    }
+
+    if diff.is_empty() {
+        writeln!(output, "No changes.")?;
+    } else {
+        writeln!(output)?;
+        writeln!(output, "Summary: {}", diff)?;
+    }
+    Ok(())
+}
+
+/// Emits the diff as JSON object.
+///
+/// The object has the fields `added` and `removed`, each holding an
+/// object with the fields `userids` (array of strings),
+/// `user_attributes` (number), `subkeys` (array of hex fingerprints),
+/// and `signatures` (array of objects with the fields `sigtype`,
+/// `issuer`, and `subject`).
+fn diff_json(output: &mut Write, diff: &TPKDiff) -> failure::Fallible<()> {
+    let mut sections = serde_json::Map::new();
+    for (name, userids, user_attributes, subkeys, signatures) in vec![
+        ("added", diff.added_userids(), diff.added_user_attributes(),
+         diff.added_subkeys(), diff.added_signatures()),
+        ("removed", diff.removed_userids(), diff.removed_user_attributes(),
+         diff.removed_subkeys(), diff.removed_signatures()),
+    ] {
+        let userids = userids.iter()
+            .map(|u| String::from_utf8_lossy(u.value()))
+            .collect::<Vec<_>>();
+        let subkeys = subkeys.iter()
+            .map(|k| k.fingerprint().to_hex())
+            .collect::<Vec<_>>();
+        let signatures = signatures.iter()
+            .map(|(subject, s)| json!({
+                "sigtype": u8::from(s.sigtype()),
+                "issuer": issuer(s),
+                "subject": subject.to_string(),
+            }))
+            .collect::<Vec<_>>();
+        sections.insert(name.into(), json!({
+            "userids": userids,
+            "user_attributes": user_attributes.len(),
+            "subkeys": subkeys,
+            "signatures": signatures,
+        }));
+    }
+
+    writeln!(output, "{}", serde_json::Value::Object(sections))?;
+    Ok(())
+}
+
+/// Returns the native encoding of a point on Curve25519.
+///
+/// OpenPGP prefixes these points with 0x40.
+fn native_point<'a>(key: &Key, q: &'a MPI) -> failure::Fallible<&'a [u8]> {
+    if q.value.len() == 33 && q.value[0] == 0x40 {
+        Ok(&q.value[1..])
+    } else {
+        Err(format_err!("Malformed {} key", key.pk_algo()))
+    }
+}
+
+/// Appends `s` as SSH string.
+fn ssh_string(buf: &mut Vec<u8>, s: &[u8]) {
+    let l = s.len() as u32;
+    buf.extend_from_slice(&[(l >> 24) as u8, (l >> 16) as u8,
+                            (l >> 8) as u8, l as u8]);
+    buf.extend_from_slice(s);
+}
+
+/// Appends the unsigned big-endian integer `v` as SSH mpint.
+fn ssh_mpint(buf: &mut Vec<u8>, v: &[u8]) {
+    if v.first().map(|b| b & 0x80 != 0).unwrap_or(false) {
+        ssh_string(buf, &[&[0][..], v].concat());
+    } else {
+        ssh_string(buf, v);
+    }
+}
+
+fn be_u32(b: &[u8]) -> usize {
+    (b[0] as usize) << 24 | (b[1] as usize) << 16
+        | (b[2] as usize) << 8 | b[3] as usize
+}
+
+/// Encodes `content` as DER object with the given tag.
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut o = vec![tag];
+    let l = content.len();
+    if l < 0x80 {
+        o.push(l as u8);
+    } else {
+        let bytes: Vec<u8> = (0..8).rev().map(|i| (l >> (8 * i)) as u8)
+            .skip_while(|&b| b == 0).collect();
+        o.push(0x80 | bytes.len() as u8);
+        o.extend_from_slice(&bytes);
+    }
+    o.extend_from_slice(content);
+    o
+}
+
+/// Encodes the unsigned big-endian integer `v` as DER INTEGER.
+fn der_integer(v: &[u8]) -> Vec<u8> {
+    if v.is_empty() {
+        der(0x02, &[0])
+    } else if v[0] & 0x80 != 0 {
+        der(0x02, &[&[0][..], v].concat())
+    } else {
+        der(0x02, v)
+    }
+}
+
+/// GnuPG prefixes the armor header of the revocation certificates it
+/// stores with a colon to prevent accidental imports.
+const GNUPG_REVOCATION_HEADER: &[u8] =
+    b":-----BEGIN PGP PUBLIC KEY BLOCK-----";
+
+/// Applies revocation certificates to keys in the key pool.
+pub fn revoke(ctx: &Context, m: &ArgMatches) -> failure::Fallible<()> {
+    let mut input = open_or_stdin(m.value_of("import"))?;
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+    if let Some(i) = data.windows(GNUPG_REVOCATION_HEADER.len())
+        .position(|w| w == GNUPG_REVOCATION_HEADER)
+    {
+        data.remove(i);
+    }
+
+    let sigs = PacketPile::from_bytes(&data)?.into_children()
+        .filter_map(|p| if let Packet::Signature(sig) = p {
+            Some(sig)
+        } else {
+            None
+        })
+        .collect::<Vec<_>>();
+    if sigs.is_empty() {
+        return Err(format_err!("No revocation certificate found"));
+    }
+
+    for sig in sigs {
+        let key = if let Some(fp) = sig.issuer_fingerprint() {
+            Pool::lookup(ctx, &fp)
+        } else if let Some(keyid) = sig.issuer() {
+            Pool::lookup_by_keyid(ctx, &keyid)
+        } else {
+            return Err(format_err!("Revocation certificate has no issuer"));
+        }.context("Issuer not found in the key pool")?;
+
+        let tpk = key.tpk()?.apply_revocation(sig)?;
+        key.import(&tpk)?;
+        eprintln!("Revoked {}", tpk.fingerprint());
+    }
+    Ok(())
+}
+
+/// Attaches a photo to a key.
+///
+/// Like GnuPG, we store the photo as a JPEG image in a user
+/// attribute, and bind it to the key using a positive certification.
+/// This requires the secret primary key.
+pub fn attach_photo(ctx: &Context, m: &ArgMatches, force: bool,
+                    config: &Config)
+                    -> failure::Fallible<()> {
+    let fp = Fingerprint::from_hex(m.value_of("fingerprint").unwrap())
+        .context("Malformed fingerprint")?;
+    let tpk = find_tpk(ctx, m, &fp.to_keyid(), Some(&fp))?;
+    if tpk.fingerprint() != fp {
+        return Err(format_err!("Key {} does not match the fingerprint {}",
+                               tpk.fingerprint(), fp));
+    }
+
+    let path = m.value_of("image").unwrap();
+    let image = fs::read(path)
+        .context(format!("Failed to read the photo from {:?}", path))?;
+    // JPEG images start with a start of image marker, followed by
+    // another marker.
+    if ! image.starts_with(&[0xff, 0xd8, 0xff]) {
+        return Err(format_err!("{:?} is not a JPEG image", path));
+    }
+
+    let ua = UserAttribute::new(&[
+        user_attribute::Subpacket::Image(
+            user_attribute::Image::JPEG(image.into_boxed_slice())),
+    ])?;
+    if tpk.user_attributes().any(|b| b.user_attribute().value() == ua.value())
+    {
+        return Err(format_err!("The photo is already attached to {}", fp));
+    }
+
+    let mut signer = super::unlock(&tpk, tpk.primary())?;
+    let binding = ua.bind(
+        &mut signer, &tpk,
+        signature::Builder::new(SignatureType::PositiveCertificate),
+        None, None)?;
+    let tpk = tpk.merge_packets(vec![ua.into(), binding.into()])?;
+
+    let mut output = create_or_stdout(m.value_of("output"), force)?;
+    if m.is_present("binary") || ! config.armor(&["key", "attach-photo"])? {
+        tpk.as_tsk().serialize(&mut output)?;
+    } else {
+        let kind = if tpk.is_tsk() { Kind::SecretKey } else { Kind::PublicKey };
+        let mut output = Writer::new(&mut output, kind, &[])?;
+        tpk.as_tsk().serialize(&mut output)?;
+        output.finalize()?;
+    }
+    Ok(())
+}
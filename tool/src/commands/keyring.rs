@@ -0,0 +1,169 @@
+use failure::{self, ResultExt};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use clap::ArgMatches;
+
+use sequoia_core::trace::Level;
+use openpgp::{Fingerprint, TPK};
+use openpgp::armor::{Writer, Kind};
+use openpgp::packet::UserID;
+use openpgp::parse::Parse;
+use openpgp::serialize::Serialize;
+use openpgp::tpk::TPKParser;
+
+use ::{create_or_stdout, open_or_stdin};
+use config::Config;
+
+/// Joins keyrings, merging all copies of a key.
+pub fn merge(m: &ArgMatches, force: bool, config: &Config)
+             -> failure::Fallible<()> {
+    let tpks = merge_duplicates(read_keyrings(m, |_, _| true)?)?;
+    write_keyring(m, force, config.armor(&["keyring", "merge"])?, &tpks)
+}
+
+/// Merges all copies of a key in a keyring.
+pub fn dedupe(m: &ArgMatches, force: bool, config: &Config)
+              -> failure::Fallible<()> {
+    let tpks = read_keyrings(m, |_, _| true)?;
+    let n = tpks.len();
+    let tpks = merge_duplicates(tpks)?;
+    trace!(Level::Info, "Merged {} duplicate keys", n - tpks.len());
+    write_keyring(m, force, config.armor(&["keyring", "dedupe"])?, &tpks)
+}
+
+/// Keeps only the keys with one of the given email addresses or
+/// fingerprints.
+pub fn filter(m: &ArgMatches, force: bool, config: &Config)
+              -> failure::Fallible<()> {
+    let mut addresses = Vec::new();
+    for a in m.values_of("email").into_iter().flat_map(|v| v) {
+        addresses.push(UserID::from_address(None, None, a)?
+                       .address_normalized()?
+                       .ok_or_else(|| format_err!("Invalid address: {:?}", a))?);
+    }
+    let mut fingerprints = Vec::new();
+    for f in m.values_of("fingerprint").into_iter().flat_map(|v| v) {
+        fingerprints.push(Fingerprint::from_hex(f)
+                          .context(format!("Malformed fingerprint: {:?}", f))?);
+    }
+
+    let tpks = read_keyrings(m, |tpk, _| {
+        tpk.keys_all().unfiltered()
+            .any(|(_, _, key)| fingerprints.contains(&key.fingerprint()))
+            || tpk.userids().any(|u| match u.userid().address_normalized() {
+                Ok(Some(ref a)) => addresses.contains(a),
+                _ => false,
+            })
+    })?;
+    trace!(Level::Info, "Selected {} keys", tpks.len());
+    write_keyring(m, force, config.armor(&["keyring", "filter"])?, &tpks)
+}
+
+/// Writes every key of a keyring to its own file.
+pub fn split(m: &ArgMatches, force: bool, config: &Config)
+             -> failure::Fallible<()> {
+    let prefix =
+        // The prefix is either specified explicitly...
+        m.value_of("prefix").map(|p| p.to_owned())
+        .unwrap_or(
+            // ... or we derive it from the input file...
+            m.value_of("input").and_then(|i| {
+                let p = PathBuf::from(i);
+                // (but only use the filename)
+                p.file_name().map(|f| String::from(f.to_string_lossy()))
+            })
+            // ... or we use a generic prefix...
+            .unwrap_or(String::from("output"))
+            // ... finally, add a hyphen to the derived prefix.
+            + "-");
+    let armor = ! m.is_present("binary")
+        && config.armor(&["keyring", "split"])?;
+
+    let input = open_or_stdin(m.value_of("input"))?;
+    for (i, tpk) in TPKParser::from_reader(input)?.enumerate() {
+        let tpk = tpk.context("Malformed key")?;
+        let filename = format!("{}{}-{}", prefix, i, tpk.fingerprint().to_hex());
+        let mut sink = create_or_stdout(Some(filename.as_str()), force)?;
+        serialize_keys(&mut sink, armor, &[tpk])?;
+    }
+    Ok(())
+}
+
+/// Reads all keys accepted by `filter` from the input files, or
+/// stdin.
+///
+/// See `TPKParser::unvalidated_tpk_filter` for how `filter` is
+/// called.
+fn read_keyrings<F>(m: &ArgMatches, filter: F) -> failure::Fallible<Vec<TPK>>
+    where F: Fn(&TPK, bool) -> bool
+{
+    let inputs: Vec<Option<&str>> = match m.values_of("input") {
+        Some(v) => v.map(Some).collect(),
+        None => vec![None],
+    };
+
+    let mut tpks = Vec::new();
+    for input in inputs {
+        let name = input.unwrap_or("stdin");
+        let parser = TPKParser::from_reader(open_or_stdin(input)?)
+            .context(format!("Failed to read keyring {:?}", name))?
+            .unvalidated_tpk_filter(|tpk, sanity| filter(tpk, sanity));
+        for tpk in parser {
+            tpks.push(tpk.context(format!("Malformed key in {:?}", name))?);
+        }
+    }
+    Ok(tpks)
+}
+
+/// Merges keys with the same fingerprint.
+///
+/// The merged key takes the position of the first copy.
+fn merge_duplicates(tpks: Vec<TPK>) -> failure::Fallible<Vec<TPK>> {
+    let mut order = Vec::new();
+    let mut merged: HashMap<Fingerprint, TPK> = HashMap::new();
+    for tpk in tpks {
+        let fp = tpk.fingerprint();
+        let tpk = match merged.remove(&fp) {
+            Some(other) => other.merge(tpk)?,
+            None => {
+                order.push(fp.clone());
+                tpk
+            },
+        };
+        merged.insert(fp, tpk);
+    }
+
+    Ok(order.into_iter()
+       .map(|fp| merged.remove(&fp).expect("inserted above"))
+       .collect())
+}
+
+fn write_keyring(m: &ArgMatches, force: bool, armor: bool, tpks: &[TPK])
+                 -> failure::Fallible<()> {
+    let mut output = create_or_stdout(m.value_of("output"), force)?;
+    serialize_keys(&mut output, armor && ! m.is_present("binary"), tpks)
+}
+
+/// Writes the keys, including any secret key material.
+fn serialize_keys(output: &mut Write, armor: bool, tpks: &[TPK])
+                  -> failure::Fallible<()> {
+    if ! armor {
+        for tpk in tpks {
+            tpk.as_tsk().serialize(output)?;
+        }
+        return Ok(());
+    }
+
+    let kind = if tpks.iter().any(|tpk| tpk.is_tsk()) {
+        Kind::SecretKey
+    } else {
+        Kind::PublicKey
+    };
+    let mut output = Writer::new(output, kind, &[])?;
+    for tpk in tpks {
+        tpk.as_tsk().serialize(&mut output)?;
+    }
+    output.finalize()?;
+    Ok(())
+}
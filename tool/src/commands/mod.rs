@@ -1,16 +1,19 @@
-use failure::{self, ResultExt};
+use failure::{self, Fail, ResultExt};
+use itertools::Itertools;
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs::File;
 use std::io::{self, Write};
-use time;
 use rpassword;
 
 extern crate sequoia_openpgp as openpgp;
-use sequoia_core::Context;
+use sequoia_core::{Context, TimeFormat};
+use sequoia_core::trace::Level;
 use openpgp::constants::DataFormat;
 use openpgp::crypto;
 use openpgp::{TPK, KeyID, Result};
+use openpgp::packet::Key;
 use openpgp::packet::key::SecretKey;
 use openpgp::parse::{
     Parse,
@@ -22,69 +25,175 @@ use openpgp::parse::stream::{
 };
 use openpgp::serialize::stream::{
     Message, Signer, LiteralWriter, Encryptor, EncryptionMode,
+    select_encryption_keys, negotiate_algorithms,
 };
 extern crate sequoia_store as store;
 
 mod decrypt;
 pub use self::decrypt::decrypt;
+mod explain;
+use self::explain::Plan;
 mod sign;
-pub use self::sign::sign;
+pub use self::sign::{sign, sign_detached_files};
 mod dump;
-pub use self::dump::dump;
+pub use self::dump::{dump, Format as DumpFormat};
 mod inspect;
 pub use self::inspect::inspect;
+mod timestamp;
+pub use self::timestamp::timestamp;
 pub mod key;
+pub mod keyring;
+pub mod generate;
+mod certify;
+pub use self::certify::certify;
 
-const TIMEFMT: &'static str = "%Y-%m-%dT%H:%M";
-
-fn tm2str(t: &time::Tm) -> String {
-    time::strftime(TIMEFMT, t).expect("TIMEFMT is correct")
+/// Returns a suitable signing key for every TPK in the given list.
+fn select_signing_keys(tpks: &[openpgp::TPK]) -> Result<Vec<&Key>> {
+    let mut keys = Vec::new();
+    for tsk in tpks {
+        keys.push(tsk.keys_valid()
+                  .signing_capable()
+                  .map(|k| k.2)
+                  .find(|key| key.secret().is_some())
+                  .ok_or_else(|| failure::err_msg(
+                      format!("Found no suitable signing key on {}", tsk)))?);
+    }
+    Ok(keys)
 }
 
 /// Returns suitable signing keys from a given list of TPKs.
 fn get_signing_keys(tpks: &[openpgp::TPK]) -> Result<Vec<crypto::KeyPair>> {
     let mut keys = Vec::new();
-    'next_tpk: for tsk in tpks {
-        for key in tsk.keys_valid()
-            .signing_capable()
-            .map(|k| k.2)
-        {
-            if let Some(mut secret) = key.secret() {
-                let secret_mpis = match secret {
-                    SecretKey::Encrypted { .. } => {
-                        let password = rpassword::read_password_from_tty(Some(
-                            &format!("Please enter password to decrypt {}/{}: ",
-                                     tsk, key))).unwrap();
-                        secret.decrypt(key.pk_algo(), &password.into())
-                            .expect("decryption failed")
-                    },
-                    SecretKey::Unencrypted { ref mpis } =>
-                        mpis.clone(),
-                };
-
-                keys.push(crypto::KeyPair::new(key.clone(), secret_mpis)
-                          .unwrap());
-                break 'next_tpk;
-            }
-        }
-
-        return Err(failure::err_msg(
-            format!("Found no suitable signing key on {}", tsk)));
+    for (tsk, key) in tpks.iter().zip(select_signing_keys(tpks)?) {
+        keys.push(unlock(tsk, key)?);
     }
 
     Ok(keys)
 }
 
+/// Turns `key` of `tsk` into a keypair, asking for the password if
+/// necessary.
+fn unlock(tsk: &TPK, key: &Key) -> Result<crypto::KeyPair> {
+    let secret = key.secret().ok_or_else(|| failure::err_msg(
+        format!("No secret key material for {}/{}", tsk, key)))?;
+    let secret_mpis = match secret {
+        SecretKey::Encrypted { .. }
+        | SecretKey::AEADEncrypted { .. }
+        | SecretKey::LegacyEncrypted { .. } => {
+            let password = rpassword::read_password_from_tty(Some(
+                &format!("Please enter password to decrypt {}/{}: ",
+                         tsk, key)))
+                .context("Failed to read password")?;
+            secret.decrypt(key.pk_algo(), &password.into())
+                .context(format!("Failed to decrypt {}/{}",
+                                 tsk, key))?
+        },
+        SecretKey::Unencrypted { ref mpis } =>
+            mpis.clone(),
+    };
+
+    crypto::KeyPair::new(key.clone(), secret_mpis)
+}
+
+const SECONDS_IN_DAY : i64 = 24 * 60 * 60;
+const SECONDS_IN_YEAR : i64 =
+    // Average number of days in a year.
+    (365.2422222 * SECONDS_IN_DAY as f64) as i64;
+
+/// Rounds durations of a week or more down to full days.
+fn even_off(s: i64) -> i64 {
+    if s < 7 * SECONDS_IN_DAY {
+        // Don't round down, too small.
+        s
+    } else {
+        s - (s % SECONDS_IN_DAY)
+    }
+}
+
+/// Parses an expiration given as 'N[ymwd]', or 'never'.
+///
+/// `option` names the option in error messages.  Returns `None` for
+/// 'never'.
+fn parse_expiry(option: &str, expiry: &str)
+                -> failure::Fallible<Option<time::Duration>> {
+    if expiry == "never" {
+        return Ok(None);
+    }
+
+    let mut expiry = expiry.chars().peekable();
+
+    let _ = expiry.by_ref()
+        .peeking_take_while(|c| c.is_whitespace())
+        .for_each(|_| ());
+    let digits = expiry.by_ref()
+        .peeking_take_while(|c| {
+            *c == '+' || *c == '-' || c.is_digit(10)
+        }).collect::<String>();
+    let _ = expiry.by_ref()
+        .peeking_take_while(|c| c.is_whitespace())
+        .for_each(|_| ());
+    let suffix = expiry.next();
+    let _ = expiry.by_ref()
+        .peeking_take_while(|c| c.is_whitespace())
+        .for_each(|_| ());
+    let junk = expiry.collect::<String>();
+
+    if digits == "" {
+        return Err(format_err!(
+            "{}: missing count \
+             (try: '2y' for 2 years)", option));
+    }
+
+    let count : i64 = match digits.parse::<i32>() {
+        Ok(count) if count < 0 =>
+            return Err(format_err!(
+                "{}: Expiration can't be in the past", option)),
+        Ok(count) => count as i64,
+        Err(err) =>
+            return Err(err.context(
+                format!("{}: count is out of range", option)).into()),
+    };
+
+    let factor = match suffix {
+        Some('y') | Some('Y') => SECONDS_IN_YEAR,
+        Some('m') | Some('M') => SECONDS_IN_YEAR / 12,
+        Some('w') | Some('W') => 7 * SECONDS_IN_DAY,
+        Some('d') | Some('D') => SECONDS_IN_DAY,
+        None =>
+            return Err(format_err!(
+                "{}: missing suffix \
+                 (try: '{}y', '{}m', '{}w' or '{}d' instead)",
+                option, digits, digits, digits, digits)),
+        Some(suffix) =>
+            return Err(format_err!(
+                "{}: invalid suffix '{}' \
+                 (try: '{}y', '{}m', '{}w' or '{}d' instead)",
+                option, suffix, digits, digits, digits, digits)),
+    };
+
+    if junk != "" {
+        return Err(format_err!(
+            "{}: contains trailing junk ('{:?}') \
+             (try: '{}{}')",
+            option, junk, count, factor));
+    }
+
+    Ok(Some(time::Duration::seconds(even_off(count * factor))))
+}
+
 pub fn encrypt(store: &mut store::Store,
                input: &mut io::Read, output: &mut io::Write,
                npasswords: usize, recipients: Vec<&str>,
-               mut tpks: Vec<openpgp::TPK>, signers: Vec<openpgp::TPK>)
+               mut tpks: Vec<openpgp::TPK>, signers: Vec<openpgp::TPK>,
+               verbose: bool, armor: bool, dry_run: bool, explain: bool,
+               input_size: Option<u64>)
                -> Result<()> {
     for r in recipients {
         tpks.push(store.lookup(r).context("No such key found")?.tpk()?);
     }
     let mut passwords = Vec::with_capacity(npasswords);
-    for n in 0..npasswords {
+    // Don't ask for passwords we won't use.
+    for n in 0..if dry_run { 0 } else { npasswords } {
         let nprompt = format!("Enter password {}: ", n + 1);
         passwords.push(rpassword::read_password_from_tty(Some(
             if npasswords > 1 {
@@ -94,13 +203,62 @@ pub fn encrypt(store: &mut store::Store,
             }))?.into());
     }
 
-    let mut signers = get_signing_keys(&signers)?;
+    let signing_keys = select_signing_keys(&signers)?;
 
     // Build a vector of references to hand to Encryptor.
     let recipients: Vec<&openpgp::TPK> = tpks.iter().collect();
     let passwords_: Vec<&openpgp::crypto::Password> =
         passwords.iter().collect();
 
+    // Make sure that every recipient has a usable encryption key,
+    // and explain why if not.
+    let reports = select_encryption_keys(&recipients, EncryptionMode::AtRest);
+    for report in reports.iter().filter(|r| ! r.is_usable()) {
+        eprintln!("No suitable encryption key found for {}:",
+                  report.recipient());
+        for (fp, selection) in report.keys() {
+            eprintln!("  {}: {}", fp, selection);
+        }
+    }
+    if let Some(report) = reports.iter().find(|r| ! r.is_usable()) {
+        return Err(failure::err_msg(
+            format!("Cannot encrypt for {}", report.recipient())));
+    }
+
+    // Pick algorithms that all recipients support.
+    let algos = negotiate_algorithms(&recipients, EncryptionMode::AtRest,
+                                     &Default::default())?;
+    if explain {
+        let input_size = if dry_run {
+            Some(explain::input_size(input, input_size)?)
+        } else {
+            input_size
+        };
+        Plan {
+            recipients: recipients.iter().cloned()
+                .zip(reports.into_iter()).collect(),
+            passwords: npasswords,
+            algos: Some(algos.clone()),
+            signers: signing_keys,
+            detached: false,
+            message: false,
+            armor: armor,
+            input_size: input_size,
+        }.print();
+    } else if verbose {
+        eprintln!("Symmetric algorithm: {}", algos.symmetric_algo());
+        match algos.aead_algo() {
+            Some(aead) => eprintln!("Encryption container: AEAD ({})", aead),
+            None => eprintln!("Encryption container: SEIP with MDC"),
+        }
+        eprintln!("Compression supported by all recipients: {} \
+                   (not applied)", algos.compression_algo());
+    }
+    if dry_run {
+        return Ok(());
+    }
+    let mut signers = get_signing_keys(&signers)?;
+
     // Stream an OpenPGP message.
     let message = Message::new(output);
 
@@ -109,7 +267,7 @@ pub fn encrypt(store: &mut store::Store,
                                   &passwords_,
                                   &recipients,
                                   EncryptionMode::AtRest,
-                                  None)
+                                  algos.symmetric_algo())
         .context("Failed to create encryptor")?;
 
     // Optionally sign message.
@@ -136,10 +294,27 @@ pub fn encrypt(store: &mut store::Store,
     Ok(())
 }
 
+/// Requirements on the structure of a message.
+///
+/// These are checked by `sq decrypt` and `sq verify` once all
+/// signatures have been verified.  Only signatures by trusted keys,
+/// i.e. those that count towards `--signatures`, are considered.
+#[derive(Clone, Debug, Default)]
+pub struct StructurePolicy {
+    /// Requires at least one good signature.
+    pub require_signed: bool,
+    /// Requires a good signature inside an encryption container.
+    pub require_encrypted_then_signed: bool,
+    /// Limits the number of encryption, compression, and signature
+    /// layers.
+    pub max_nesting: Option<usize>,
+}
+
 struct VHelper<'a> {
     ctx: &'a Context,
     store: &'a mut store::Store,
     signatures: usize,
+    policy: StructurePolicy,
     tpks: Option<Vec<TPK>>,
     labels: HashMap<KeyID, String>,
     trusted: HashSet<KeyID>,
@@ -152,12 +327,13 @@ struct VHelper<'a> {
 
 impl<'a> VHelper<'a> {
     fn new(ctx: &'a Context, store: &'a mut store::Store, signatures: usize,
-           tpks: Vec<TPK>)
+           policy: StructurePolicy, tpks: Vec<TPK>)
            -> Self {
         VHelper {
             ctx: ctx,
             store: store,
             signatures: signatures,
+            policy: policy,
             tpks: Some(tpks),
             labels: HashMap::new(),
             trusted: HashSet::new(),
@@ -267,6 +443,7 @@ impl<'a> VerificationHelper for VHelper<'a> {
 
         // Try to get missing TPKs from the store.
         for id in ids.iter().filter(|i| !seen.contains(i)) {
+            trace!(Level::Debug, "Looking up {} in the store", id);
             let _ =
                 self.store.lookup_by_subkeyid(id)
                 .and_then(|binding| {
@@ -288,6 +465,7 @@ impl<'a> VerificationHelper for VHelper<'a> {
 
         // Try to get missing TPKs from the pool.
         for id in ids.iter().filter(|i| !seen.contains(i)) {
+            trace!(Level::Debug, "Looking up {} in the key pool", id);
             let _ =
                 store::Pool::lookup_by_subkeyid(self.ctx, id)
                 .and_then(|key| {
@@ -303,53 +481,117 @@ impl<'a> VerificationHelper for VHelper<'a> {
     }
 
     fn check(&mut self, structure: &MessageStructure) -> Result<()> {
+        let mut nesting = 0;
+        let mut encrypted = false;
+        let mut signed = false;
+        let mut signed_inside_encryption = false;
         for layer in structure.iter() {
             match layer {
-                MessageLayer::Compression { algo } =>
-                    eprintln!("Compressed using {}", algo),
-                MessageLayer::Encryption { sym_algo, aead_algo } =>
+                MessageLayer::Compression { algo } => {
+                    nesting += 1;
+                    eprintln!("Compressed using {}", algo);
+                },
+                MessageLayer::Encryption { sym_algo, aead_algo } => {
+                    nesting += 1;
+                    encrypted = true;
                     if let Some(aead_algo) = aead_algo {
                         eprintln!("Encrypted and protected using {}/{}",
                                   sym_algo, aead_algo);
                     } else {
                         eprintln!("Encrypted using {}", sym_algo);
-                    },
-                MessageLayer::SignatureGroup { ref results } =>
-                    self.print_sigs(results),
+                    }
+                },
+                MessageLayer::SignatureGroup { ref results } => {
+                    nesting += 1;
+                    let good_signatures = self.good_signatures;
+                    self.print_sigs(results);
+                    if self.good_signatures > good_signatures {
+                        signed = true;
+                        signed_inside_encryption |= encrypted;
+                    }
+                },
+                MessageLayer::Literal { .. } => (),
             }
         }
 
-        if self.good_signatures >= self.signatures
+        let violation = if self.policy.require_signed && ! signed {
+            Some("Message is not signed".into())
+        } else if self.policy.require_encrypted_then_signed
+            && ! signed_inside_encryption
+        {
+            Some("Message is not signed inside an encryption container"
+                 .into())
+        } else {
+            match self.policy.max_nesting {
+                Some(n) if nesting > n =>
+                    Some(format!("Message has {} layers, at most {} \
+                                  are allowed", nesting, n)),
+                _ => None,
+            }
+        };
+
+        if let Some(violation) = violation {
+            self.print_status();
+            Err(VerificationFailed.context(violation).into())
+        } else if self.good_signatures >= self.signatures
             && self.bad_signatures + self.bad_checksums == 0 {
             Ok(())
         } else {
             self.print_status();
-            Err(failure::err_msg("Verification failed"))
+            Err(VerificationFailed.into())
         }
     }
 }
 
+/// Signals that a message did not verify.
+///
+/// `sq` exits with status 1 if this is the cause of an error, and
+/// with status 2 on all other errors.
+#[derive(Debug)]
+pub struct VerificationFailed;
+
+impl fmt::Display for VerificationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Verification failed")
+    }
+}
+
+impl failure::Fail for VerificationFailed {}
+
+/// Recovers an error wrapped in an `io::Error`.
+///
+/// The streaming verifier and decryptor return errors raised by
+/// their helpers from `io::Read::read`, wrapped in an `io::Error`.
+fn recover_error(e: io::Error) -> failure::Error {
+    if e.get_ref().is_none() {
+        // Plain io::Error.
+        return e.into();
+    }
+
+    // Wrapped failure::Error.  Recover it.
+    match e.into_inner().unwrap()
+        .downcast::<failure::Compat<failure::Error>>()
+    {
+        Ok(compat) => compat.into_inner(),
+        Err(e) => failure::Error::from_boxed_compat(e),
+    }
+}
+
 pub fn verify(ctx: &Context, store: &mut store::Store,
               input: &mut io::Read,
               detached: Option<&mut io::Read>,
               output: &mut io::Write,
-              signatures: usize, tpks: Vec<TPK>)
+              signatures: usize, policy: StructurePolicy, tpks: Vec<TPK>)
               -> Result<()> {
-    let helper = VHelper::new(ctx, store, signatures, tpks);
+    let _span = trace_span!(Level::Info, "Verifying message");
+    let helper = VHelper::new(ctx, store, signatures, policy, tpks);
     let mut verifier = if let Some(dsig) = detached {
         DetachedVerifier::from_reader(dsig, input, helper, None)?
     } else {
         Verifier::from_reader(input, helper, None)?
     };
 
-    io::copy(&mut verifier, output)
-        .map_err(|e| if e.get_ref().is_some() {
-            // Wrapped failure::Error.  Recover it.
-            failure::Error::from_boxed_compat(e.into_inner().unwrap())
-        } else {
-            // Plain io::Error.
-            e.into()
-        })?;
+    io::copy(&mut verifier, output).map_err(recover_error)?;
 
     verifier.into_helper().print_status();
     Ok(())
@@ -400,37 +642,90 @@ pub fn split(input: &mut io::Read, prefix: &str)
     Ok(())
 }
 
-pub fn store_print_stats(store: &store::Store, label: &str) -> Result<()> {
-    fn print_stamps(st: &store::Stamps) -> Result<()> {
+pub fn store_print_stats(ctx: &Context, store: &store::Store, label: &str)
+                         -> Result<()> {
+    fn print_stamps(tf: &TimeFormat, st: &store::Stamps) -> Result<()> {
         println!("{} messages using this key", st.count);
         if let Some(t) = st.first {
-            println!("    First: {}", tm2str(&time::at(t)));
+            println!("    First: {}", tf.format_timespec(t));
         }
         if let Some(t) = st.last {
-            println!("    Last: {}", tm2str(&time::at(t)));
+            println!("    Last: {}", tf.format_timespec(t));
         }
         Ok(())
     }
 
-    fn print_stats(st: &store::Stats) -> Result<()> {
+    fn print_stats(tf: &TimeFormat, st: &store::Stats) -> Result<()> {
         if let Some(t) = st.created {
-            println!("  Created: {}", tm2str(&time::at(t)));
+            println!("  Created: {}", tf.format_timespec(t));
         }
         if let Some(t) = st.updated {
-            println!("  Updated: {}", tm2str(&time::at(t)));
+            println!("  Updated: {}", tf.format_timespec(t));
+        }
+        if let Some(t) = st.expiry {
+            println!("  Expires: {}", tf.format_timespec(t));
         }
         print!("  Encrypted ");
-        print_stamps(&st.encryption)?;
+        print_stamps(tf, &st.encryption)?;
         print!("  Verified ");
-        print_stamps(&st.verification)?;
+        print_stamps(tf, &st.verification)?;
         Ok(())
     }
 
+    let tf = ctx.time_format();
     let binding = store.lookup(label)?;
     println!("Binding {:?}", label);
-    print_stats(&binding.stats().context("Failed to get stats")?)?;
+    print_stats(tf, &binding.stats().context("Failed to get stats")?)?;
     let key = binding.key().context("Failed to get key")?;
     println!("Key");
-    print_stats(&key.stats().context("Failed to get stats")?)?;
+    print_stats(tf, &key.stats().context("Failed to get stats")?)?;
+    Ok(())
+}
+
+/// Pauses key updates for `duration`, or until they are resumed.
+pub fn store_pause_refresh(ctx: &Context, duration: Option<&str>,
+                           resume: bool)
+                           -> Result<()> {
+    let pause = if resume {
+        store::RefreshPause::Running
+    } else if let Some(d) = duration {
+        match parse_expiry("DURATION", d)? {
+            Some(d) => store::RefreshPause::Until(time::get_time() + d),
+            None => store::RefreshPause::Indefinitely,
+        }
+    } else {
+        store::RefreshPause::Indefinitely
+    };
+
+    store::Store::pause_refresh(ctx, pause)
+        .context("Failed to pause key updates")?;
+    Ok(())
+}
+
+/// Prints when keys are updated from the network.
+pub fn store_print_schedule(ctx: &Context) -> Result<()> {
+    let tf = ctx.time_format();
+    let schedule = store::Store::refresh_schedule(ctx)
+        .context("Failed to get the schedule")?;
+
+    match schedule.pause {
+        store::RefreshPause::Running =>
+            println!("Key updates are running."),
+        store::RefreshPause::Until(t) =>
+            println!("Key updates are paused until {}.",
+                     tf.format_timespec(t)),
+        store::RefreshPause::Indefinitely =>
+            println!("Key updates are paused until resumed."),
+    }
+
+    let now = time::get_time();
+    for (fingerprint, update_at) in schedule.keys {
+        let at = match update_at {
+            None => "as soon as possible".into(),
+            Some(t) if t <= now => "due".into(),
+            Some(t) => tf.format_timespec(t),
+        };
+        println!("{}  {}", fingerprint, at);
+    }
     Ok(())
 }
@@ -1,5 +1,5 @@
 use failure::{self, ResultExt};
-use std::fs;
+use std::fs::{self, File};
 use std::io;
 use std::path::PathBuf;
 use tempfile::NamedTempFile;
@@ -19,22 +19,79 @@ use openpgp::serialize::stream::{
     Message, Signer, LiteralWriter,
 };
 use create_or_stdout;
+use super::explain::{self, Plan};
 
 pub fn sign(input: &mut io::Read, output_path: Option<&str>,
             secrets: Vec<openpgp::TPK>, detached: bool, binary: bool,
-            append: bool, notarize: bool, force: bool)
+            append: bool, notarize: bool, force: bool,
+            dry_run: bool, explain: bool, input_size: Option<u64>)
             -> Result<()> {
+    if dry_run || explain {
+        let keys = super::select_signing_keys(&secrets)?;
+        let input_size = if dry_run {
+            Some(explain::input_size(input, input_size)?)
+        } else {
+            input_size
+        };
+        if explain {
+            Plan::sign(keys, detached, ! detached && (append || notarize),
+                       ! binary, input_size).print();
+        }
+        if dry_run {
+            return Ok(());
+        }
+    }
+
     match (detached, append|notarize) {
-        (_, false) | (true, true) =>
-            sign_data(input, output_path, secrets, detached, binary, append,
-                      force),
+        (_, false) | (true, true) => {
+            let mut keypairs = super::get_signing_keys(&secrets)?;
+            sign_data(input, output_path, &mut keypairs, detached, binary,
+                      append, force)
+        },
         (false, true) =>
             sign_message(input, output_path, secrets, binary, notarize, force),
     }
 }
 
+/// Creates detached signatures for the given files.
+///
+/// The signature of every file is written next to it, using the
+/// extension `.sig`, or `.asc` if it is ASCII-armored.  The secret
+/// keys are unlocked only once.
+pub fn sign_detached_files(inputs: &[&str], secrets: Vec<openpgp::TPK>,
+                           binary: bool, append: bool, force: bool,
+                           dry_run: bool, explain: bool)
+                           -> Result<()> {
+    if dry_run || explain {
+        let keys = super::select_signing_keys(&secrets)?;
+        if explain {
+            for input in inputs {
+                eprintln!("{}:", input);
+                let size = fs::metadata(input)
+                    .context(format!("Failed to open {:?}", input))?.len();
+                Plan::sign(keys.clone(), true, false, ! binary, Some(size))
+                    .print();
+            }
+        }
+        if dry_run {
+            return Ok(());
+        }
+    }
+
+    let mut keypairs = super::get_signing_keys(&secrets)?;
+    for input in inputs {
+        let output = format!("{}.{}", input, if binary { "sig" } else { "asc" });
+        let mut f = File::open(input)
+            .context(format!("Failed to open {:?}", input))?;
+        sign_data(&mut f, Some(&output), &mut keypairs, true, binary, append,
+                  force)
+            .context(format!("Failed to sign {:?}", input))?;
+    }
+    Ok(())
+}
+
 fn sign_data(input: &mut io::Read, output_path: Option<&str>,
-             secrets: Vec<openpgp::TPK>, detached: bool, binary: bool,
+             keypairs: &mut [crypto::KeyPair], detached: bool, binary: bool,
              append: bool, force: bool)
              -> Result<()> {
     let (mut output, prepend_sigs, tmp_path):
@@ -82,7 +139,6 @@ fn sign_data(input: &mut io::Read, output_path: Option<&str>,
         output
     };
 
-    let mut keypairs = super::get_signing_keys(&secrets)?;
     let signers = keypairs.iter_mut()
         .map(|s| -> &mut dyn crypto::Signer { s })
         .collect();
@@ -0,0 +1,108 @@
+use failure::{self, ResultExt};
+use std::fs::File;
+use clap::ArgMatches;
+
+use sequoia_core::Context;
+use sequoia_net::KeyServer;
+use openpgp::{TPK, crypto};
+use openpgp::armor;
+use openpgp::constants::HashAlgorithm;
+use openpgp::packet::Signature;
+use openpgp::parse::Parse;
+use openpgp::serialize::Serialize;
+
+use ::{create_or_stdout, open_or_stdin};
+use config::Config;
+
+/// The hash algorithm used to compute the digest sent to notaries.
+const HASH_ALGO: HashAlgorithm = HashAlgorithm::SHA256;
+
+/// Requests a timestamp signature for a file from a notary, or
+/// verifies one if `--verify` is given.
+pub fn timestamp(ctx: &Context, m: &ArgMatches, force: bool, config: &Config)
+                 -> failure::Fallible<()> {
+    let notary = match m.value_of("notary-key") {
+        Some(f) => Some(TPK::from_file(f)
+                        .context(format!("Failed to load key from file {:?}",
+                                         f))?),
+        None => None,
+    };
+
+    if let Some(f) = m.value_of("verify") {
+        let notary = notary.ok_or_else(
+            || failure::err_msg("Verifying a timestamp requires --notary-key"))?;
+        let sig = Signature::from_reader(
+            armor::Reader::new(File::open(f)
+                               .context("Failed to open signature file")?,
+                               armor::ReaderMode::Tolerant(
+                                   Some(armor::Kind::Signature))))
+            .context("Malformed signature")?;
+        let digest = digest(m.value_of("input"), sig.hash_algo())?;
+        return check(ctx, &notary, &sig, &digest);
+    }
+
+    let service = match m.value_of("service") {
+        Some(uri) => uri,
+        None => config.get_str(&["timestamp"], "service")?
+            .ok_or_else(|| failure::err_msg(
+                "No notary given, use --service"))?,
+    };
+    let mut ks = KeyServer::new(ctx, service)
+        .context("Malformed notary URI")?;
+
+    let digest = digest(m.value_of("input"), HASH_ALGO)?;
+    let sig = ks.timestamp(HASH_ALGO, &digest)
+        .context("Failed to obtain a timestamp")?;
+    if let Some(ref notary) = notary {
+        check(ctx, notary, &sig, &digest)?;
+    }
+
+    let mut output = create_or_stdout(m.value_of("output"), force)?;
+    if m.is_present("binary") || ! config.armor(&["timestamp"])? {
+        sig.serialize(&mut output)?;
+    } else {
+        let mut output = armor::Writer::new(&mut output,
+                                            armor::Kind::Signature, &[])?;
+        sig.serialize(&mut output)?;
+        output.finalize()?;
+    }
+    Ok(())
+}
+
+/// Computes the digest of the input file, or stdin.
+fn digest(input: Option<&str>, hash_algo: HashAlgorithm)
+          -> failure::Fallible<Vec<u8>> {
+    let mut input = open_or_stdin(input)?;
+    let mut hash = hash_algo.context()?;
+    crypto::hash_reader(&mut input, &mut hash)?;
+    let mut digest = vec![0; hash.digest_size()];
+    hash.digest(&mut digest);
+    Ok(digest)
+}
+
+/// Checks that `sig` is a valid timestamp over `digest` made by
+/// `notary`, and reports the time.
+fn check(ctx: &Context, notary: &TPK, sig: &Signature, digest: &[u8])
+         -> failure::Fallible<()> {
+    let issuer = sig.issuer();
+    let issuer_fp = sig.issuer_fingerprint();
+    for (_, _, key) in notary.keys_all().unfiltered() {
+        if issuer_fp.as_ref().map(|fp| fp != &key.fingerprint())
+            .unwrap_or(false)
+            || issuer.as_ref().map(|id| id != &key.keyid()).unwrap_or(false)
+        {
+            continue;
+        }
+
+        if sig.verify_timestamp(key, digest).unwrap_or(false) {
+            let time = sig.signature_creation_time()
+                .ok_or_else(|| failure::err_msg(
+                    "Timestamp signature lacks a creation time"))?;
+            eprintln!("Good timestamp from {}: {}",
+                      key.fingerprint(), ctx.time_format().format(&time));
+            return Ok(());
+        }
+    }
+
+    Err(failure::err_msg("Timestamp signature does not verify"))
+}
@@ -0,0 +1,111 @@
+//! Configuration file support.
+//!
+//! `sq` reads default values for some options from a TOML file.
+//! Unless given using `--config`, the file is `sequoia/sq.toml` in
+//! the user's configuration directory, e.g.
+//! `~/.config/sequoia/sq.toml`.  Options given on the command line
+//! take precedence.
+//!
+//! Options can be set at the top level, or in a table named after
+//! the subcommand they apply to.  The most specific setting wins:
+//!
+//! ```toml
+//! # Emit binary data by default...
+//! armor = false
+//!
+//! # ... except for signatures.
+//! [sign]
+//! armor = true
+//! signing-key = "/home/alice/alice.tsk"
+//!
+//! [keyserver]
+//! server = "hkps://keys.openpgp.org"
+//!
+//! [key.generate]
+//! cipher-suite = "cv25519"
+//! ```
+
+use failure::{self, ResultExt};
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use dirs;
+use toml;
+
+/// Default values for command-line options.
+#[derive(Debug, Default)]
+pub struct Config {
+    table: toml::value::Table,
+}
+
+impl Config {
+    /// Returns the path of the default configuration file.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join("sequoia").join("sq.toml"))
+    }
+
+    /// Loads the configuration.
+    ///
+    /// If `path` is `None`, the default configuration file is used,
+    /// if it exists.
+    pub fn load(path: Option<&str>) -> failure::Fallible<Self> {
+        let path = match path.map(PathBuf::from).or_else(Self::default_path) {
+            Some(ref p) if path.is_some() || p.exists() => p.clone(),
+            _ => return Ok(Default::default()),
+        };
+
+        let mut s = String::new();
+        File::open(&path).and_then(|mut f| f.read_to_string(&mut s))
+            .context(format!("Failed to read {}", path.display()))?;
+        Ok(Config {
+            table: toml::from_str(&s)
+                .context(format!("Failed to parse {}", path.display()))?,
+        })
+    }
+
+    /// Looks up `key` for the subcommand `path`.
+    fn get(&self, path: &[&str], key: &str) -> Option<&toml::Value> {
+        'outer: for n in (0..path.len() + 1).rev() {
+            let mut table = &self.table;
+            for c in &path[..n] {
+                match table.get(*c).and_then(|v| v.as_table()) {
+                    Some(t) => table = t,
+                    None => continue 'outer,
+                }
+            }
+            if let Some(v) = table.get(key) {
+                return Some(v);
+            }
+        }
+        None
+    }
+
+    /// Looks up a string `key` for the subcommand `path`.
+    pub fn get_str(&self, path: &[&str], key: &str)
+                   -> failure::Fallible<Option<&str>> {
+        match self.get(path, key) {
+            None => Ok(None),
+            Some(v) => v.as_str().map(Some).ok_or_else(|| {
+                format_err!("Configuration option {:?} must be a string", key)
+            }),
+        }
+    }
+
+    /// Looks up a boolean `key` for the subcommand `path`.
+    pub fn get_bool(&self, path: &[&str], key: &str)
+                    -> failure::Fallible<Option<bool>> {
+        match self.get(path, key) {
+            None => Ok(None),
+            Some(v) => v.as_bool().map(Some).ok_or_else(|| {
+                format_err!("Configuration option {:?} must be a boolean", key)
+            }),
+        }
+    }
+
+    /// Returns whether the subcommand `path` should ASCII-armor its
+    /// output, unless `--binary` is given.
+    pub fn armor(&self, path: &[&str]) -> failure::Fallible<bool> {
+        Ok(self.get_bool(path, "armor")?.unwrap_or(true))
+    }
+}
+
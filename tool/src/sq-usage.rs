@@ -11,30 +11,44 @@
 //! FLAGS:
 //!     -f, --force      Overwrite existing files
 //!     -h, --help       Prints help information
+//!     -v, --verbose    Be more verbose (can be given multiple times, see also SEQUOIA_LOG)
 //!     -V, --version    Prints version information
 //!
 //! OPTIONS:
+//!         --config <FILE>              Sets the configuration file to use
 //!     -d, --domain <DOMAIN>            Sets the domain to use
 //!         --home <DIRECTORY>           Sets the home directory to use
+//!         --id-format <FORMAT>
+//!             Sets how fingerprints and key IDs are displayed.  'legacy-short' emits short key IDs, which are easy to
+//!             forge, and should only be used to match old logs [default: full]  [possible values: full, long, legacy-
+//!             short]
 //!     -p, --policy <NETWORK-POLICY>    Sets the network policy to use
 //!     -s, --store <STORE>              Sets the store to use (default: 'default')
+//!         --time-format <FORMAT>       Sets the strftime(3) format timestamps are displayed in (default: '%Y-%m-%dT%H:%M')
+//!         --time-zone <TIME-ZONE>
+//!             Sets the time zone timestamps are displayed in [default: utc]  [possible values: utc, local]
 //!
 //! SUBCOMMANDS:
-//!     decrypt      Decrypts an OpenPGP message
-//!     encrypt      Encrypts a message
-//!     sign         Signs a message
-//!     verify       Verifies a message
-//!     store        Interacts with key stores
-//!     keyserver    Interacts with keyservers
-//!     autocrypt    Autocrypt support
-//!     dearmor      Removes ASCII Armor from a file
-//!     enarmor      Applies ASCII Armor to a file
-//!     help         Prints this message or the help of the given subcommand(s)
-//!     inspect      Inspects a sequence of OpenPGP packets
-//!     key          Manipulates keys
-//!     list         Lists key stores and known keys
-//!     packet       OpenPGP Packet manipulation
-//!     wkd          Interacts with Web Key Directories
+//!     decrypt                 Decrypts an OpenPGP message
+//!     encrypt                 Encrypts a message
+//!     sign                    Signs a message
+//!     verify                  Verifies a message
+//!     timestamp               Timestamps a file using a notary
+//!     certify                 Certifies a user id of a key
+//!     store                   Interacts with key stores
+//!     keyserver               Interacts with keyservers
+//!     autocrypt               Autocrypt support
+//!     dearmor                 Removes ASCII Armor from a file
+//!     enarmor                 Applies ASCII Armor to a file
+//!     generate-completions    Generates shell completions
+//!     generate-man            Generates man pages
+//!     help                    Prints this message or the help of the given subcommand(s)
+//!     inspect                 Inspects a sequence of OpenPGP packets
+//!     key                     Manipulates keys
+//!     keyring                 Manipulates keyrings
+//!     list                    Lists key stores and known keys
+//!     packet                  OpenPGP Packet manipulation
+//!     wkd                     Interacts with Web Key Directories
 //! ```
 //!
 //! ## Subcommand decrypt
@@ -46,13 +60,16 @@
 //!     sq decrypt [FLAGS] [OPTIONS] [--] [FILE]
 //!
 //! FLAGS:
-//!         --dump                Print a packet dump to stderr
-//!         --dump-session-key    Prints the session key to stderr
-//!     -h, --help                Prints help information
-//!     -x, --hex                 Print a hexdump (implies --dump)
-//!     -V, --version             Prints version information
+//!         --dump                             Print a packet dump to stderr
+//!         --dump-session-key                 Prints the session key to stderr
+//!     -h, --help                             Prints help information
+//!     -x, --hex                              Print a hexdump (implies --dump)
+//!         --require-encrypted-then-signed    Requires a valid signature inside the encryption container
+//!         --require-signed                   Requires at least one valid signature
+//!     -V, --version                          Prints version information
 //!
 //! OPTIONS:
+//!         --max-nesting <N>                  Rejects messages with more than N encryption, compression, and signature layers
 //!     -o, --output <FILE>                    Sets the output file to use
 //!         --public-key-file <TPK-FILE>...    Public key to verify with, given as a file (can be given multiple times)
 //!         --secret-key-file <TSK-FILE>...    Secret key to decrypt with, given as a file (can be given multiple times)
@@ -72,8 +89,11 @@
 //!
 //! FLAGS:
 //!     -B, --binary       Don't ASCII-armor encode the OpenPGP data
+//!         --dry-run      Checks the arguments, but doesn't produce any output
+//!         --explain      Explains the selected keys and algorithms, and estimates the size of the output
 //!     -h, --help         Prints help information
 //!     -s, --symmetric    Encrypt with a password (can be given multiple times)
+//!     -v, --verbose      Explains the negotiated algorithms
 //!     -V, --version      Prints version information
 //!
 //! OPTIONS:
@@ -92,12 +112,14 @@
 //! Signs a message
 //!
 //! USAGE:
-//!     sq sign [FLAGS] [OPTIONS] [--] [FILE]
+//!     sq sign [FLAGS] [OPTIONS] [--] [FILE]...
 //!
 //! FLAGS:
 //!     -a, --append      Append signature to existing signature
 //!     -B, --binary      Don't ASCII-armor encode the OpenPGP data
 //!         --detached    Create a detached signature
+//!         --dry-run     Checks the arguments, but doesn't produce any output
+//!         --explain     Explains the selected keys and algorithms, and estimates the size of the output
 //!     -h, --help        Prints help information
 //!     -n, --notarize    Signs a message and all existing signatures
 //!     -V, --version     Prints version information
@@ -107,7 +129,8 @@
 //!         --secret-key-file <TSK-FILE>...    Secret key to sign with, given as a file (can be given multiple times)
 //!
 //! ARGS:
-//!     <FILE>    Sets the input file to use
+//!     <FILE>...    Sets the input files to use.  Multiple files require --detached, the signatures are written to
+//!                  FILE.asc, or FILE.sig with --binary
 //! ```
 //!
 //! ## Subcommand verify
@@ -116,14 +139,16 @@
 //! Verifies a message
 //!
 //! USAGE:
-//!     sq verify [OPTIONS] [--] [FILE]
+//!     sq verify [FLAGS] [OPTIONS] [--] [FILE]
 //!
 //! FLAGS:
-//!     -h, --help       Prints help information
-//!     -V, --version    Prints version information
+//!     -h, --help              Prints help information
+//!         --require-signed    Requires at least one valid signature
+//!     -V, --version           Prints version information
 //!
 //! OPTIONS:
 //!         --detached <SIG-FILE>              Verifies a detached signature
+//!         --max-nesting <N>                  Rejects messages with more than N encryption, compression, and signature layers
 //!     -o, --output <FILE>                    Sets the output file to use
 //!         --public-key-file <TPK-FILE>...    Public key to verify with, given as a file (can be given multiple times)
 //!     -n, --signatures <N>                   The number of valid signatures required.  Default: 0
@@ -132,6 +157,59 @@
 //!     <FILE>    Sets the input file to use
 //! ```
 //!
+//! ## Subcommand timestamp
+//!
+//! ```text
+//! Timestamps a file using a notary
+//!
+//! USAGE:
+//!     sq timestamp [FLAGS] [OPTIONS] [FILE]
+//!
+//! FLAGS:
+//!     -B, --binary     Don't ASCII-armor encode the OpenPGP data
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! OPTIONS:
+//!         --notary-key <TPK-FILE>    Verifies the timestamp using the notary's key, given as a file
+//!     -o, --output <FILE>            Sets the output file to use
+//!     -s, --service <URI>            Sets the notary to use
+//!         --verify <SIG-FILE>        Verifies the timestamp signature SIG-FILE instead of requesting one
+//!
+//! ARGS:
+//!     <FILE>    Sets the input file to use
+//! ```
+//!
+//! ## Subcommand certify
+//!
+//! ```text
+//! Certifies a user id of a key
+//!
+//! USAGE:
+//!     sq certify [FLAGS] [OPTIONS] <FINGERPRINT> <USERID>
+//!
+//! FLAGS:
+//!     -B, --binary     Don't ASCII-armor encode the OpenPGP data
+//!     -h, --help       Prints help information
+//!         --publish    Sends the certified key to a keyserver instead of exporting it, unless --output is given
+//!     -V, --version    Prints version information
+//!         --yes        Does not ask whether the fingerprint matches
+//!
+//! OPTIONS:
+//!         --expiry <EXPIRY>               When the certification should expire.  Either 'N[ymwd]', for N years,
+//!                                         months, weeks, or days, or 'never'.  Asked for if not given
+//!     -i, --input <TPK-FILE>              Reads the key from TPK-FILE instead of the key pool
+//!         --keyserver <URI>               Sets the keyserver to publish to
+//!         --level <LEVEL>                 How carefully the identity of the key holder was verified, from 0 (no answer)
+//!                                         to 3 (very careful).  Asked for if not given [possible values: 0, 1, 2, 3]
+//!     -o, --output <FILE>                 Sets the output file to use
+//!         --secret-key-file <TSK-FILE>    Secret key to certify with, given as a file
+//!
+//! ARGS:
+//!     <FINGERPRINT>    Fingerprint of the key to certify
+//!     <USERID>         User id to certify
+//! ```
+//!
 //! ## Subcommand store
 //!
 //! ```text
@@ -145,14 +223,18 @@
 //!     -V, --version    Prints version information
 //!
 //! SUBCOMMANDS:
-//!     add       Add a key identified by fingerprint
-//!     delete    Deletes bindings or stores
-//!     export    Exports a key
-//!     help      Prints this message or the help of the given subcommand(s)
-//!     import    Imports a key
-//!     list      Lists keys in the store
-//!     log       Lists the keystore log
-//!     stats     Get stats for the given label
+//!     add                  Add a key identified by fingerprint
+//!     delete               Deletes bindings or stores
+//!     export               Exports a key
+//!     help                 Prints this message or the help of the given subcommand(s)
+//!     import               Imports a key
+//!     import-ownertrust    Sets the trust of bindings according to GnuPG's ownertrust
+//!     list                 Lists keys in the store
+//!     log                  Lists the keystore log
+//!     pause-refresh        Pauses or resumes background key updates
+//!     refresh              Schedules keys for an update from the network
+//!     schedule             Lists when keys are updated from the network
+//!     stats                Get stats for the given label
 //! ```
 //!
 //! ### Subcommand store add
@@ -226,6 +308,22 @@
 //!     <FILE>     Sets the input file to use
 //! ```
 //!
+//! ### Subcommand store import-ownertrust
+//!
+//! ```text
+//! Sets the trust of bindings according to GnuPG's ownertrust
+//!
+//! USAGE:
+//!     sq store import-ownertrust [FILE]
+//!
+//! FLAGS:
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! ARGS:
+//!     <FILE>    Reads the output of 'gpg --export-ownertrust' from FILE
+//! ```
+//!
 //! ### Subcommand store list
 //!
 //! ```text
@@ -255,6 +353,53 @@
 //!     <LABEL>    List messages related to this label
 //! ```
 //!
+//! ### Subcommand store pause-refresh
+//!
+//! ```text
+//! Pauses or resumes background key updates
+//!
+//! USAGE:
+//!     sq store pause-refresh [FLAGS] [DURATION]
+//!
+//! FLAGS:
+//!     -h, --help       Prints help information
+//!         --resume     Resumes updates
+//!     -V, --version    Prints version information
+//!
+//! ARGS:
+//!     <DURATION>    Pauses updates for DURATION, given as 'N[ymwd]' (default: until resumed)
+//! ```
+//!
+//! ### Subcommand store refresh
+//!
+//! ```text
+//! Schedules keys for an update from the network
+//!
+//! USAGE:
+//!     sq store refresh <FINGERPRINT|--all>
+//!
+//! FLAGS:
+//!         --all        Update all keys
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! ARGS:
+//!     <FINGERPRINT>    Key to update
+//! ```
+//!
+//! ### Subcommand store schedule
+//!
+//! ```text
+//! Lists when keys are updated from the network
+//!
+//! USAGE:
+//!     sq store schedule
+//!
+//! FLAGS:
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//! ```
+//!
 //! ### Subcommand store stats
 //!
 //! ```text
@@ -427,20 +572,61 @@
 //!     <FILE>    Sets the input file to use
 //! ```
 //!
+//! ## Subcommand generate-completions
+//!
+//! ```text
+//! Generates shell completions
+//!
+//! USAGE:
+//!     sq generate-completions <SHELL> [DIRECTORY]
+//!
+//! FLAGS:
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! ARGS:
+//!     <SHELL>        The shell to generate completions for [possible values: bash, fish, zsh, powershell, elvish]
+//!     <DIRECTORY>    Writes the completions to this directory instead of stdout
+//! ```
+//!
+//! ## Subcommand generate-man
+//!
+//! ```text
+//! Generates man pages
+//!
+//! USAGE:
+//!     sq generate-man <DIRECTORY>
+//!
+//! FLAGS:
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! ARGS:
+//!     <DIRECTORY>    Writes the man pages to this directory
+//! ```
+//!
 //! ## Subcommand inspect
 //!
 //! ```text
 //! Inspects a sequence of OpenPGP packets
 //!
 //! USAGE:
-//!     sq inspect [FLAGS] [FILE]
+//!     sq inspect [FLAGS] [OPTIONS] [FILE]
 //!
 //! FLAGS:
 //!         --certifications    Print third-party certifications
 //!     -h, --help              Prints help information
 //!         --keygrips          Print keygrips of keys and subkeys
+//!         --lint              Check keys for weak cryptography and other problems
+//!         --use-store         Verifies third-party certifications using the keys in the key pool (implies
+//!                             --certifications)
 //!     -V, --version           Prints version information
 //!
+//! OPTIONS:
+//!         --export-photos <DIR>      Writes the photos attached to keys to files in DIR
+//!         --keyring <TPK-FILE>...    Verifies third-party certifications using the keys in the given file (can be given
+//!                                    multiple times, implies --certifications)
+//!
 //! ARGS:
 //!     <FILE>    Sets the input file to use
 //! ```
@@ -458,8 +644,120 @@
 //!     -V, --version    Prints version information
 //!
 //! SUBCOMMANDS:
-//!     generate    Generates a new key
-//!     help        Prints this message or the help of the given subcommand(s)
+//!     attach-photo    Attaches a photo to a key
+//!     diff            Shows what changed between two versions of a key
+//!     export          Exports keys, optionally without secret key material
+//!     export-raw      Exports a key as X.509 SubjectPublicKeyInfo
+//!     export-ssh      Exports a key in OpenSSH's format
+//!     generate        Generates a new key
+//!     help            Prints this message or the help of the given subcommand(s)
+//!     revoke          Revokes keys in the key pool using revocation certificates
+//! ```
+//!
+//! ### Subcommand key attach-photo
+//!
+//! ```text
+//! Attaches a photo to a key
+//!
+//! USAGE:
+//!     sq key attach-photo [FLAGS] [OPTIONS] <FINGERPRINT> <IMAGE>
+//!
+//! FLAGS:
+//!     -B, --binary     Don't ASCII-armor encode the OpenPGP data
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! OPTIONS:
+//!     -i, --input <FILE>     Reads the key from FILE instead of the key pool
+//!     -o, --output <FILE>    Sets the output file to use
+//!
+//! ARGS:
+//!     <FINGERPRINT>    Fingerprint of the key
+//!     <IMAGE>          Reads the photo from the JPEG image IMAGE
+//! ```
+//!
+//! ### Subcommand key diff
+//!
+//! ```text
+//! Shows what changed between two versions of a key
+//!
+//! USAGE:
+//!     sq key diff [OPTIONS] <OLD> <NEW>
+//!
+//! FLAGS:
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! OPTIONS:
+//!         --format <FORMAT>    Selects the output format [default: human]  [possible values: human, json]
+//!     -o, --output <FILE>      Sets the output file to use
+//!
+//! ARGS:
+//!     <OLD>    Reads the old version from the file OLD, or, if OLD is a fingerprint, from the key pool
+//!     <NEW>    Reads the new version from the file NEW, or, if NEW is a fingerprint, from the key pool
+//! ```
+//!
+//! ### Subcommand key export
+//!
+//! ```text
+//! Exports keys, optionally without secret key material
+//!
+//! USAGE:
+//!     sq key export [FLAGS] [OPTIONS] [FILE]
+//!
+//! FLAGS:
+//!     -B, --binary     Don't ASCII-armor encode the OpenPGP data
+//!     -h, --help       Prints help information
+//!         --public     Strips all secret key material
+//!     -V, --version    Prints version information
+//!
+//! OPTIONS:
+//!     -o, --output <FILE>    Sets the output file to use
+//!
+//! ARGS:
+//!     <FILE>    Sets the input file to use
+//! ```
+//!
+//! ### Subcommand key export-raw
+//!
+//! ```text
+//! Exports a key as X.509 SubjectPublicKeyInfo
+//!
+//! USAGE:
+//!     sq key export-raw [FLAGS] [OPTIONS] <KEYID>
+//!
+//! FLAGS:
+//!     -B, --binary     Emits DER instead of PEM
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! OPTIONS:
+//!     -i, --input <FILE>     Reads the TPK from FILE instead of the key pool
+//!     -o, --output <FILE>    Sets the output file to use
+//!
+//! ARGS:
+//!     <KEYID>    Key ID or fingerprint of the key to export
+//! ```
+//!
+//! ### Subcommand key export-ssh
+//!
+//! ```text
+//! Exports a key in OpenSSH's format
+//!
+//! USAGE:
+//!     sq key export-ssh [OPTIONS] <KEYID>
+//!
+//! FLAGS:
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! OPTIONS:
+//!     -i, --input <FILE>     Reads the TPK from FILE instead of the key pool
+//!     -o, --output <FILE>    Sets the output file to use
+//!
+//! ARGS:
+//!     <KEYID>    Key ID or fingerprint of the key to export.  If the primary key is given, the first
+//!                authentication-capable key is exported.
 //! ```
 //!
 //! ### Subcommand key generate
@@ -471,12 +769,13 @@
 //!     sq key generate [FLAGS] [OPTIONS] --export <OUTFILE>
 //!
 //! FLAGS:
-//!         --can-sign          The key has a signing-capable subkey (default)
-//!         --cannot-encrypt    The key will not be able to encrypt data
-//!         --cannot-sign       The key will not be able to sign data
-//!     -h, --help              Prints help information
-//!     -V, --version           Prints version information
-//!         --with-password     Prompt for a password to protect the generated key with.
+//!         --can-authenticate    The key has an authentication-capable subkey, e.g., for use with SSH
+//!         --can-sign            The key has a signing-capable subkey (default)
+//!         --cannot-encrypt      The key will not be able to encrypt data
+//!         --cannot-sign         The key will not be able to sign data
+//!     -h, --help                Prints help information
+//!     -V, --version             Prints version information
+//!         --with-password       Prompt for a password to protect the generated key with.
 //!
 //! OPTIONS:
 //!         --can-encrypt <PURPOSE>          The key has an encryption-capable subkey (default) [default: all]  [possible
@@ -489,6 +788,127 @@
 //!     -u, --userid <EMAIL>                 Primary user ID
 //! ```
 //!
+//! ### Subcommand key revoke
+//!
+//! ```text
+//! Revokes keys in the key pool using revocation certificates
+//!
+//! USAGE:
+//!     sq key revoke --import <FILE>
+//!
+//! FLAGS:
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! OPTIONS:
+//!         --import <FILE>    Reads the revocation certificates from FILE
+//! ```
+//!
+//! ## Subcommand keyring
+//!
+//! ```text
+//! Manipulates keyrings
+//!
+//! USAGE:
+//!     sq keyring [SUBCOMMAND]
+//!
+//! FLAGS:
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! SUBCOMMANDS:
+//!     dedupe    Merges all copies of a key in a keyring
+//!     filter    Keeps only the keys matching the given email addresses or fingerprints
+//!     help      Prints this message or the help of the given subcommand(s)
+//!     merge     Joins keyrings, merging all copies of a key
+//!     split     Writes every key of a keyring to its own file
+//! ```
+//!
+//! ### Subcommand keyring dedupe
+//!
+//! ```text
+//! Merges all copies of a key in a keyring
+//!
+//! USAGE:
+//!     sq keyring dedupe [FLAGS] [OPTIONS] [FILE]
+//!
+//! FLAGS:
+//!     -B, --binary     Don't ASCII-armor encode the OpenPGP data
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! OPTIONS:
+//!     -o, --output <FILE>    Sets the output file to use
+//!
+//! ARGS:
+//!     <FILE>    Sets the input file to use
+//! ```
+//!
+//! ### Subcommand keyring filter
+//!
+//! ```text
+//! Keeps only the keys matching the given email addresses or fingerprints
+//!
+//! USAGE:
+//!     sq keyring filter [FLAGS] [OPTIONS] <--email <ADDRESS>...|--fingerprint <FINGERPRINT>...> [FILE]...
+//!
+//! FLAGS:
+//!     -B, --binary     Don't ASCII-armor encode the OpenPGP data
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! OPTIONS:
+//!         --email <ADDRESS>...            Keeps keys with a user ID with this email address (can be given multiple
+//!                                         times)
+//!         --fingerprint <FINGERPRINT>...    Keeps keys with this primary key or subkey fingerprint (can be given
+//!                                           multiple times)
+//!     -o, --output <FILE>                 Sets the output file to use
+//!
+//! ARGS:
+//!     <FILE>...    Sets the input files to use
+//! ```
+//!
+//! ### Subcommand keyring merge
+//!
+//! ```text
+//! Joins keyrings, merging all copies of a key
+//!
+//! USAGE:
+//!     sq keyring merge [FLAGS] [OPTIONS] [FILE]...
+//!
+//! FLAGS:
+//!     -B, --binary     Don't ASCII-armor encode the OpenPGP data
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! OPTIONS:
+//!     -o, --output <FILE>    Sets the output file to use
+//!
+//! ARGS:
+//!     <FILE>...    Sets the input files to use
+//! ```
+//!
+//! ### Subcommand keyring split
+//!
+//! ```text
+//! Writes every key of a keyring to its own file
+//!
+//! USAGE:
+//!     sq keyring split [FLAGS] [OPTIONS] [FILE]
+//!
+//! FLAGS:
+//!     -B, --binary     Don't ASCII-armor encode the OpenPGP data
+//!     -h, --help       Prints help information
+//!     -V, --version    Prints version information
+//!
+//! OPTIONS:
+//!     -p, --prefix <FILE>    Sets the prefix to use for output files (defaults to the input filename with a dash, or
+//!                            'output')
+//!
+//! ARGS:
+//!     <FILE>    Sets the input file to use
+//! ```
+//!
 //! ## Subcommand list
 //!
 //! ```text
@@ -600,6 +1020,7 @@
 //!     -V, --version    Prints version information
 //!
 //! OPTIONS:
+//!         --format <FORMAT>              Selects the output format [default: human]  [possible values: human, json]
 //!     -o, --output <FILE>                Sets the output file to use
 //!         --session-key <SESSION-KEY>    Session key to decrypt encryption containers
 //!
@@ -3,12 +3,17 @@
 extern crate clap;
 #[macro_use]
 extern crate failure;
+extern crate base64;
 #[macro_use]
 extern crate prettytable;
 extern crate rpassword;
 extern crate tempfile;
 extern crate time;
 extern crate itertools;
+extern crate dirs;
+extern crate toml;
+#[macro_use]
+extern crate serde_json;
 
 use failure::ResultExt;
 use prettytable::{Table, Cell, Row};
@@ -18,22 +23,27 @@ use std::path::{Path, PathBuf};
 use std::process::exit;
 
 extern crate sequoia_openpgp as openpgp;
+#[macro_use]
 extern crate sequoia_core;
 extern crate sequoia_net;
 extern crate sequoia_store;
 
-use openpgp::{armor, autocrypt, Fingerprint, TPK};
+use openpgp::{armor, autocrypt, Fingerprint, IdentifierFormat, TPK};
 use openpgp::conversions::hex;
+use openpgp::ownertrust::OwnerTrustList;
 use openpgp::parse::Parse;
 use openpgp::serialize::Serialize;
 use openpgp::tpk::armor::Encoder;
 use openpgp::tpk::TPKParser;
-use sequoia_core::{Context, NetworkPolicy};
+use sequoia_core::{Context, NetworkPolicy, TimeFormat, TimeZone};
+use sequoia_core::trace::{self, Level};
 use sequoia_net::{KeyServer, wkd};
 use sequoia_store::{Store, LogIter};
 
 mod sq_cli;
 mod commands;
+mod config;
+use config::Config;
 
 fn open_or_stdin(f: Option<&str>) -> Result<Box<io::Read>, failure::Error> {
     match f {
@@ -65,6 +75,29 @@ fn create_or_stdout(f: Option<&str>, force: bool)
     }
 }
 
+/// Returns the size of the input file, if it is a file.
+fn input_size(f: Option<&str>) -> Option<u64> {
+    f.and_then(|f| std::fs::metadata(f).ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+}
+
+/// Returns the message structure requirements given on the command
+/// line.
+fn structure_policy(m: &clap::ArgMatches)
+                    -> Result<commands::StructurePolicy, failure::Error> {
+    Ok(commands::StructurePolicy {
+        require_signed: m.is_present("require-signed"),
+        require_encrypted_then_signed:
+            m.is_present("require-encrypted-then-signed"),
+        max_nesting: match m.value_of("max-nesting") {
+            Some(n) => Some(n.parse::<usize>()
+                       .context("Malformed --max-nesting")?),
+            None => None,
+        },
+    })
+}
+
 fn load_tpks<'a, I>(files: I) -> openpgp::Result<Vec<TPK>>
     where I: Iterator<Item=&'a str>
 {
@@ -90,6 +123,22 @@ fn help_warning(arg: &str) {
 
 fn real_main() -> Result<(), failure::Error> {
     let matches = sq_cli::build().get_matches();
+    trace::init(matches.occurrences_of("verbose"))?;
+    IdentifierFormat::set(match matches.value_of("id-format") {
+        Some("long") => IdentifierFormat::LongKeyID,
+        Some("legacy-short") => IdentifierFormat::LegacyShortKeyID,
+        _ => IdentifierFormat::Full,
+    });
+
+    // These do not need a context.
+    match matches.subcommand() {
+        ("generate-completions", Some(m)) =>
+            return commands::generate::completions(m),
+        ("generate-man", Some(m)) => return commands::generate::man(m),
+        _ => (),
+    }
+
+    let config = Config::load(matches.value_of("config"))?;
 
     let policy = match matches.value_of("policy") {
         None => NetworkPolicy::Encrypted,
@@ -116,8 +165,18 @@ fn real_main() -> Result<(), failure::Error> {
     if let Some(dir) = matches.value_of("home") {
         builder = builder.home(dir);
     }
+    match matches.value_of("time-zone") {
+        Some("local") => builder = builder.time_zone(TimeZone::Local),
+        _ => builder = builder.time_zone(TimeZone::UTC),
+    }
+    if let Some(format) = matches.value_of("time-format") {
+        builder = builder.time_format(format);
+    }
     let ctx = builder.build()?;
+    trace!(Level::Debug, "Using home directory {}", ctx.home().display());
 
+    let _span = trace_span!(Level::Info, "Running {}",
+                            matches.subcommand_name().unwrap_or("sq"));
     match matches.subcommand() {
         ("decrypt",  Some(m)) => {
             let mut input = open_or_stdin(m.value_of("input"))?;
@@ -134,14 +193,21 @@ fn real_main() -> Result<(), failure::Error> {
                 .context("Failed to open the store")?;
             commands::decrypt(&ctx, &mut store,
                               &mut input, &mut output,
-                              signatures, tpks, secrets,
+                              signatures, structure_policy(m)?,
+                              tpks, secrets,
                               m.is_present("dump-session-key"),
                               m.is_present("dump"), m.is_present("hex"))?;
         },
         ("encrypt",  Some(m)) => {
             let mut input = open_or_stdin(m.value_of("input"))?;
-            let mut output = create_or_stdout(m.value_of("output"), force)?;
-            let mut output = if ! m.is_present("binary") {
+            let dry_run = m.is_present("dry-run");
+            let mut output: Box<io::Write> = if dry_run {
+                Box::new(io::sink())
+            } else {
+                create_or_stdout(m.value_of("output"), force)?
+            };
+            let binary = m.is_present("binary") || ! config.armor(&["encrypt"])?;
+            let mut output = if ! binary && ! dry_run {
                 Box::new(armor::Writer::new(&mut output,
                                             armor::Kind::Message,
                                             &[])?)
@@ -161,20 +227,47 @@ fn real_main() -> Result<(), failure::Error> {
                 .unwrap_or(Ok(vec![]))?;
             commands::encrypt(&mut store, &mut input, &mut output,
                               m.occurrences_of("symmetric") as usize,
-                              recipients, additional_tpks, additional_secrets)?;
+                              recipients, additional_tpks, additional_secrets,
+                              m.is_present("verbose"), ! binary, dry_run,
+                              m.is_present("explain"),
+                              input_size(m.value_of("input")))?;
         },
         ("sign",  Some(m)) => {
-            let mut input = open_or_stdin(m.value_of("input"))?;
+            let inputs = m.values_of("input")
+                .map(|i| i.collect::<Vec<_>>())
+                .unwrap_or(vec![]);
             let output = m.value_of("output");
             let detached = m.is_present("detached");
-            let binary = m.is_present("binary");
+            let binary = m.is_present("binary") || ! config.armor(&["sign"])?;
             let append = m.is_present("append");
             let notarize = m.is_present("notarize");
-            let secrets = m.values_of("secret-key-file")
-                .map(load_tpks)
-                .unwrap_or(Ok(vec![]))?;
-            commands::sign(&mut input, output, secrets, detached, binary,
-                           append, notarize, force)?;
+            let dry_run = m.is_present("dry-run");
+            let explain = m.is_present("explain");
+            let secrets = if let Some(files) = m.values_of("secret-key-file") {
+                load_tpks(files)?
+            } else if let Some(f) = config.get_str(&["sign"], "signing-key")? {
+                load_tpks(Some(f).into_iter())?
+            } else {
+                vec![]
+            };
+            if inputs.len() > 1 {
+                if ! detached {
+                    return Err(failure::err_msg(
+                        "Signing multiple files requires --detached"));
+                }
+                if output.is_some() {
+                    return Err(failure::err_msg(
+                        "--output cannot be used with multiple files"));
+                }
+                commands::sign_detached_files(&inputs, secrets, binary,
+                                              append, force, dry_run,
+                                              explain)?;
+            } else {
+                let mut input = open_or_stdin(inputs.get(0).cloned())?;
+                commands::sign(&mut input, output, secrets, detached, binary,
+                               append, notarize, force, dry_run, explain,
+                               input_size(inputs.get(0).cloned()))?;
+            }
         },
         ("verify",  Some(m)) => {
             let mut input = open_or_stdin(m.value_of("input"))?;
@@ -193,9 +286,15 @@ fn real_main() -> Result<(), failure::Error> {
                 .context("Failed to open the store")?;
             commands::verify(&ctx, &mut store, &mut input,
                              detached.as_mut().map(|r| r as &mut io::Read),
-                             &mut output, signatures, tpks)?;
+                             &mut output, signatures,
+                             structure_policy(m)?, tpks)?;
         },
 
+        ("timestamp",  Some(m)) =>
+            commands::timestamp(&ctx, m, force, &config)?,
+        ("certify",  Some(m)) =>
+            commands::certify(&ctx, m, force, &config)?,
+
         ("enarmor",  Some(m)) => {
             let mut input = open_or_stdin(m.value_of("input"))?;
             let mut output = create_or_stdout(m.value_of("output"), force)?;
@@ -260,7 +359,7 @@ fn real_main() -> Result<(), failure::Error> {
 
         ("inspect",  Some(m)) => {
             let mut output = create_or_stdout(m.value_of("output"), force)?;
-            commands::inspect(m, &mut output)?;
+            commands::inspect(&ctx, m, force, &mut output)?;
         },
 
         ("packet", Some(m)) => match m.subcommand() {
@@ -273,9 +372,14 @@ fn real_main() -> Result<(), failure::Error> {
                     } else {
                         None
                     };
+                let format = match m.value_of("format") {
+                    Some("json") => commands::DumpFormat::JSON,
+                    _ => commands::DumpFormat::Human,
+                };
                 commands::dump(&mut input, &mut output,
                                m.is_present("mpis"), m.is_present("hex"),
-                               session_key.as_ref())?;
+                               session_key.as_ref(), format,
+                               ctx.time_format())?;
             },
             ("split",  Some(m)) => {
                 let mut input = open_or_stdin(m.value_of("input"))?;
@@ -299,7 +403,11 @@ fn real_main() -> Result<(), failure::Error> {
         },
 
         ("keyserver",  Some(m)) => {
-            let mut ks = if let Some(uri) = m.value_of("server") {
+            let server = match m.value_of("server") {
+                Some(uri) => Some(uri),
+                None => config.get_str(&["keyserver"], "server")?,
+            };
+            let mut ks = if let Some(uri) = server {
                 KeyServer::new(&ctx, &uri)
             } else {
                 KeyServer::sks_pool(&ctx)
@@ -318,7 +426,9 @@ fn real_main() -> Result<(), failure::Error> {
                     let id = id.unwrap();
 
                     let mut output = create_or_stdout(m.value_of("output"), force)?;
-                    let mut output = if ! m.is_present("binary") {
+                    let binary = m.is_present("binary")
+                        || ! config.armor(&["keyserver", "get"])?;
+                    let mut output = if ! binary {
                         Box::new(armor::Writer::new(&mut output,
                                                     armor::Kind::PublicKey,
                                                     &[])?)
@@ -352,7 +462,7 @@ fn real_main() -> Result<(), failure::Error> {
                 },
                 ("add",  Some(m)) => {
                     let fp = Fingerprint::from_hex(m.value_of("fingerprint").unwrap())
-                        .expect("Malformed fingerprint");
+                        .context("Malformed fingerprint")?;
                     store.add(m.value_of("label").unwrap(), &fp)?;
                 },
                 ("import",  Some(m)) => {
@@ -362,10 +472,18 @@ fn real_main() -> Result<(), failure::Error> {
                     let tpk = TPK::from_reader(&mut input)?;
                     store.import(label, &tpk)?;
                 },
+                ("import-ownertrust",  Some(m)) => {
+                    let input = open_or_stdin(m.value_of("input"))?;
+                    let list = OwnerTrustList::from_reader(input)?;
+                    let n = store.import_ownertrust(&list)?;
+                    eprintln!("Updated the trust of {} bindings.", n);
+                },
                 ("export",  Some(m)) => {
                     let tpk = store.lookup(m.value_of("label").unwrap())?.tpk()?;
                     let mut output = create_or_stdout(m.value_of("output"), force)?;
-                    if m.is_present("binary") {
+                    if m.is_present("binary")
+                        || ! config.armor(&["store", "export"])?
+                    {
                         tpk.serialize(&mut output)?;
                     } else {
                         Encoder::new(&tpk).serialize(&mut output)?;
@@ -386,18 +504,39 @@ fn real_main() -> Result<(), failure::Error> {
                     }
                 },
                 ("stats",  Some(m)) => {
-                    commands::store_print_stats(&store,
+                    commands::store_print_stats(&ctx, &store,
                                                 m.value_of("label").unwrap())?;
                 },
                 ("log",  Some(m)) => {
                     if m.is_present("label") {
                         let binding = store.lookup(m.value_of("label").unwrap())
                             .context("No such key")?;
-                        print_log(binding.log().context("Failed to get log")?, false);
+                        print_log(binding.log().context("Failed to get log")?,
+                                  ctx.time_format(), false);
+                    } else {
+                        print_log(store.log().context("Failed to get log")?,
+                                  ctx.time_format(), true);
+                    }
+                },
+                ("refresh",  Some(m)) => {
+                    if m.is_present("all") {
+                        Store::refresh_all(&ctx)
+                            .context("Failed to schedule updates")?;
                     } else {
-                        print_log(store.log().context("Failed to get log")?, true);
+                        let fp = Fingerprint::from_hex(
+                            m.value_of("fingerprint").unwrap())
+                            .context("Malformed fingerprint")?;
+                        Store::lookup(&ctx, &fp).context("No such key")?
+                            .refresh().context("Failed to schedule update")?;
                     }
                 },
+                ("pause-refresh",  Some(m)) => {
+                    commands::store_pause_refresh(&ctx, m.value_of("duration"),
+                                                  m.is_present("resume"))?;
+                },
+                ("schedule",  Some(_)) => {
+                    commands::store_print_schedule(&ctx)?;
+                },
                 _ => unreachable!(),
             }
         },
@@ -436,7 +575,8 @@ fn real_main() -> Result<(), failure::Error> {
                             table.add_row(Row::new(vec![
                                 Cell::new(&fingerprint.to_string()),
                                 if let Some(ref t) = stats.updated {
-                                    Cell::new(&format_time(t))
+                                    Cell::new(&ctx.time_format()
+                                              .format_timespec(*t))
                                 } else {
                                     Cell::new("")
                                 },
@@ -447,13 +587,33 @@ fn real_main() -> Result<(), failure::Error> {
                     table.printstd();
                 },
                 ("log",  Some(_)) => {
-                    print_log(Store::server_log(&ctx)?, true);
+                    print_log(Store::server_log(&ctx)?, ctx.time_format(), true);
                 },
                 _ => unreachable!(),
             }
         },
         ("key", Some(m)) => match m.subcommand() {
-            ("generate", Some(m)) => commands::key::generate(m, force)?,
+            ("generate", Some(m)) => commands::key::generate(m, force, &config)?,
+            ("export", Some(m)) => commands::key::export(m, force, &config)?,
+            ("export-ssh", Some(m)) =>
+                commands::key::export_ssh(&ctx, m, force)?,
+            ("export-raw", Some(m)) =>
+                commands::key::export_raw(&ctx, m, force)?,
+            ("diff", Some(m)) => commands::key::diff(&ctx, m, force)?,
+            ("revoke", Some(m)) => commands::key::revoke(&ctx, m)?,
+            ("attach-photo", Some(m)) =>
+                commands::key::attach_photo(&ctx, m, force, &config)?,
+            _ => unreachable!(),
+        },
+        ("keyring", Some(m)) => match m.subcommand() {
+            ("merge", Some(m)) =>
+                commands::keyring::merge(m, force, &config)?,
+            ("split", Some(m)) =>
+                commands::keyring::split(m, force, &config)?,
+            ("filter", Some(m)) =>
+                commands::keyring::filter(m, force, &config)?,
+            ("dedupe", Some(m)) =>
+                commands::keyring::dedupe(m, force, &config)?,
             _ => unreachable!(),
         },
         ("wkd",  Some(m)) => {
@@ -478,7 +638,9 @@ fn real_main() -> Result<(), failure::Error> {
                     // Since the output is always bytes.
                     // XXX: Still give the possibility to write to a file.
                     let mut output = create_or_stdout(m.value_of("output"), force)?;
-                    let mut output = if ! m.is_present("binary") {
+                    let binary = m.is_present("binary")
+                        || ! config.armor(&["wkd", "get"])?;
+                    let mut output = if ! binary {
                         Box::new(armor::Writer::new(&mut output,
                                                     armor::Kind::PublicKey,
                                                     &[])?)
@@ -535,7 +697,7 @@ fn list_bindings(store: &Store, realm: &str, name: &str) -> Result<(), failure::
     Ok(())
 }
 
-fn print_log(iter: LogIter, with_slug: bool) {
+fn print_log(iter: LogIter, time_format: &TimeFormat, with_slug: bool) {
     let mut table = Table::new();
     table.set_format(*prettytable::format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
     let mut head = row!["timestamp", "message"];
@@ -545,7 +707,7 @@ fn print_log(iter: LogIter, with_slug: bool) {
     table.set_titles(head);
 
     for entry in iter {
-        let mut row = row![&format_time(&entry.timestamp),
+        let mut row = row![&time_format.format_timespec(entry.timestamp),
                            &entry.short()];
         if with_slug {
             row.insert_cell(1, Cell::new(&entry.slug));
@@ -556,20 +718,23 @@ fn print_log(iter: LogIter, with_slug: bool) {
     table.printstd();
 }
 
-fn format_time(t: &time::Timespec) -> String {
-    time::strftime("%F %H:%M", &time::at(*t))
-    .unwrap() // Only parse errors can happen.
-}
-
 fn main() {
     if let Err(e) = real_main() {
         let mut cause = e.as_fail();
+        let mut verification_failed = false;
         eprint!("{}", cause);
-        while let Some(c) = cause.cause() {
-            eprint!(":\n  {}", c);
-            cause = c;
+        loop {
+            verification_failed |=
+                cause.downcast_ref::<commands::VerificationFailed>().is_some();
+            match cause.cause() {
+                Some(c) => {
+                    eprint!(":\n  {}", c);
+                    cause = c;
+                },
+                None => break,
+            }
         }
         eprintln!();
-        exit(2);
+        exit(if verification_failed { 1 } else { 2 });
     }
 }
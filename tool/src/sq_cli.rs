@@ -4,7 +4,7 @@
 /// update-usage`, and commit the resulting changes to
 /// `tool/src/sq-usage.rs`.
 
-use clap::{App, Arg, ArgGroup, SubCommand, AppSettings};
+use clap::{App, Arg, ArgGroup, SubCommand, AppSettings, Shell};
 
 pub fn build() -> App<'static, 'static> {
     App::new("sq")
@@ -14,6 +14,9 @@ pub fn build() -> App<'static, 'static> {
         .arg(Arg::with_name("home").value_name("DIRECTORY")
              .long("home")
              .help("Sets the home directory to use"))
+        .arg(Arg::with_name("config").value_name("FILE")
+             .long("config")
+             .help("Sets the configuration file to use"))
         .arg(Arg::with_name("store").value_name("STORE")
              .long("store")
              .short("s")
@@ -23,10 +26,32 @@ pub fn build() -> App<'static, 'static> {
              .long("policy")
              .short("p")
              .help("Sets the network policy to use"))
+        .arg(Arg::with_name("id-format").value_name("FORMAT")
+             .long("id-format")
+             .possible_values(&["full", "long", "legacy-short"])
+             .default_value("full")
+             .help("Sets how fingerprints and key IDs are displayed.  \
+                    'legacy-short' emits short key IDs, which are easy \
+                    to forge, and should only be used to match old logs"))
+        .arg(Arg::with_name("time-zone").value_name("TIME-ZONE")
+             .long("time-zone")
+             .possible_values(&["utc", "local"])
+             .default_value("utc")
+             .help("Sets the time zone timestamps are displayed in"))
+        .arg(Arg::with_name("time-format").value_name("FORMAT")
+             .long("time-format")
+             .help("Sets the strftime(3) format timestamps are displayed \
+                    in (default: '%Y-%m-%dT%H:%M')"))
         .arg(Arg::with_name("force")
              .long("force")
              .short("f")
              .help("Overwrite existing files"))
+        .arg(Arg::with_name("verbose")
+             .long("verbose")
+             .short("v")
+             .multiple(true)
+             .help("Be more verbose (can be given multiple times, \
+                    see also SEQUOIA_LOG)"))
         .subcommand(SubCommand::with_name("decrypt")
                     .display_order(10)
                     .about("Decrypts an OpenPGP message")
@@ -42,6 +67,19 @@ pub fn build() -> App<'static, 'static> {
                          .long("signatures")
                          .short("n")
                          .takes_value(true))
+                    .arg(Arg::with_name("require-signed")
+                         .long("require-signed")
+                         .help("Requires at least one valid signature"))
+                    .arg(Arg::with_name("max-nesting").value_name("N")
+                         .long("max-nesting")
+                         .takes_value(true)
+                         .help("Rejects messages with more than N \
+                                encryption, compression, and signature \
+                                layers"))
+                    .arg(Arg::with_name("require-encrypted-then-signed")
+                         .long("require-encrypted-then-signed")
+                         .help("Requires a valid signature inside the \
+                                encryption container"))
                     .arg(Arg::with_name("public-key-file")
                          .long("public-key-file")
                          .multiple(true)
@@ -111,12 +149,28 @@ pub fn build() -> App<'static, 'static> {
                          .short("s")
                          .multiple(true)
                          .help("Encrypt with a password \
-                                (can be given multiple times)")))
+                                (can be given multiple times)"))
+                    .arg(Arg::with_name("verbose")
+                         .long("verbose")
+                         .short("v")
+                         .help("Explains the negotiated algorithms"))
+                    .arg(Arg::with_name("dry-run")
+                         .long("dry-run")
+                         .help("Checks the arguments, but doesn't produce \
+                                any output"))
+                    .arg(Arg::with_name("explain")
+                         .long("explain")
+                         .help("Explains the selected keys and algorithms, \
+                                and estimates the size of the output")))
         .subcommand(SubCommand::with_name("sign")
                     .display_order(25)
                     .about("Signs a message")
                     .arg(Arg::with_name("input").value_name("FILE")
-                         .help("Sets the input file to use"))
+                         .multiple(true)
+                         .help("Sets the input files to use.  Multiple \
+                                files require --detached, the signatures \
+                                are written to FILE.asc, or FILE.sig with \
+                                --binary"))
                     .arg(Arg::with_name("output").value_name("FILE")
                          .long("output")
                          .short("o")
@@ -145,7 +199,15 @@ pub fn build() -> App<'static, 'static> {
                          .value_name("TSK-FILE")
                          .number_of_values(1)
                          .help("Secret key to sign with, given as a file \
-                                (can be given multiple times)")))
+                                (can be given multiple times)"))
+                    .arg(Arg::with_name("dry-run")
+                         .long("dry-run")
+                         .help("Checks the arguments, but doesn't produce \
+                                any output"))
+                    .arg(Arg::with_name("explain")
+                         .long("explain")
+                         .help("Explains the selected keys and algorithms, \
+                                and estimates the size of the output")))
         .subcommand(SubCommand::with_name("verify")
                     .display_order(26)
                     .about("Verifies a message")
@@ -166,6 +228,15 @@ pub fn build() -> App<'static, 'static> {
                          .long("signatures")
                          .short("n")
                          .takes_value(true))
+                    .arg(Arg::with_name("require-signed")
+                         .long("require-signed")
+                         .help("Requires at least one valid signature"))
+                    .arg(Arg::with_name("max-nesting").value_name("N")
+                         .long("max-nesting")
+                         .takes_value(true)
+                         .help("Rejects messages with more than N \
+                                encryption, compression, and signature \
+                                layers"))
                     .arg(Arg::with_name("public-key-file")
                          .long("public-key-file")
                          .multiple(true)
@@ -174,6 +245,85 @@ pub fn build() -> App<'static, 'static> {
                          .number_of_values(1)
                          .help("Public key to verify with, given as a file \
                                 (can be given multiple times)")))
+        .subcommand(SubCommand::with_name("timestamp")
+                    .display_order(27)
+                    .about("Timestamps a file using a notary")
+                    .arg(Arg::with_name("input").value_name("FILE")
+                         .help("Sets the input file to use"))
+                    .arg(Arg::with_name("output").value_name("FILE")
+                         .long("output")
+                         .short("o")
+                         .help("Sets the output file to use"))
+                    .arg(Arg::with_name("binary")
+                         .long("binary")
+                         .short("B")
+                         .help("Don't ASCII-armor encode the OpenPGP data"))
+                    .arg(Arg::with_name("service").value_name("URI")
+                         .long("service")
+                         .short("s")
+                         .help("Sets the notary to use"))
+                    .arg(Arg::with_name("notary-key").value_name("TPK-FILE")
+                         .long("notary-key")
+                         .help("Verifies the timestamp using the notary's \
+                                key, given as a file"))
+                    .arg(Arg::with_name("verify").value_name("SIG-FILE")
+                         .long("verify")
+                         .requires("notary-key")
+                         .conflicts_with("service")
+                         .help("Verifies the timestamp signature SIG-FILE \
+                                instead of requesting one")))
+        .subcommand(SubCommand::with_name("certify")
+                    .display_order(28)
+                    .about("Certifies a user id of a key")
+                    .arg(Arg::with_name("fingerprint").value_name("FINGERPRINT")
+                         .required(true)
+                         .help("Fingerprint of the key to certify"))
+                    .arg(Arg::with_name("userid").value_name("USERID")
+                         .required(true)
+                         .help("User id to certify"))
+                    .arg(Arg::with_name("input").value_name("TPK-FILE")
+                         .long("input")
+                         .short("i")
+                         .help("Reads the key from TPK-FILE instead of the \
+                                key pool"))
+                    .arg(Arg::with_name("output").value_name("FILE")
+                         .long("output")
+                         .short("o")
+                         .help("Sets the output file to use"))
+                    .arg(Arg::with_name("binary")
+                         .long("binary")
+                         .short("B")
+                         .help("Don't ASCII-armor encode the OpenPGP data"))
+                    .arg(Arg::with_name("secret-key-file").value_name("TSK-FILE")
+                         .long("secret-key-file")
+                         .help("Secret key to certify with, given as a file"))
+                    .arg(Arg::with_name("level").value_name("LEVEL")
+                         .long("level")
+                         .possible_values(&["0", "1", "2", "3"])
+                         .help("How carefully the identity of the key \
+                                holder was verified, from 0 (no answer) to \
+                                3 (very careful).  Asked for if not given"))
+                    .arg(Arg::with_name("expiry").value_name("EXPIRY")
+                         .long("expiry")
+                         // Catch negative numbers.
+                         .allow_hyphen_values(true)
+                         .help("When the certification should expire.  \
+                                Either 'N[ymwd]', for N years, months, \
+                                weeks, or days, or 'never'.  Asked for if \
+                                not given"))
+                    .arg(Arg::with_name("yes")
+                         .long("yes")
+                         .help("Does not ask whether the fingerprint \
+                                matches"))
+                    .arg(Arg::with_name("publish")
+                         .long("publish")
+                         .help("Sends the certified key to a keyserver \
+                                instead of exporting it, unless --output \
+                                is given"))
+                    .arg(Arg::with_name("keyserver").value_name("URI")
+                         .long("keyserver")
+                         .requires("publish")
+                         .help("Sets the keyserver to publish to")))
         .subcommand(SubCommand::with_name("enarmor")
                     .about("Applies ASCII Armor to a file")
                     .arg(Arg::with_name("input").value_name("FILE")
@@ -239,7 +389,31 @@ pub fn build() -> App<'static, 'static> {
                          .help("Print keygrips of keys and subkeys"))
                     .arg(Arg::with_name("certifications")
                          .long("certifications")
-                         .help("Print third-party certifications")))
+                         .help("Print third-party certifications"))
+                    .arg(Arg::with_name("keyring")
+                         .long("keyring")
+                         .multiple(true)
+                         .takes_value(true)
+                         .value_name("TPK-FILE")
+                         .number_of_values(1)
+                         .help("Verifies third-party certifications using \
+                                the keys in the given file (can be given \
+                                multiple times, implies --certifications)"))
+                    .arg(Arg::with_name("use-store")
+                         .long("use-store")
+                         .help("Verifies third-party certifications using \
+                                the keys in the key pool (implies \
+                                --certifications)"))
+                    .arg(Arg::with_name("lint")
+                         .long("lint")
+                         .help("Check keys for weak cryptography and \
+                                other problems"))
+                    .arg(Arg::with_name("export-photos")
+                         .long("export-photos")
+                         .takes_value(true)
+                         .value_name("DIR")
+                         .help("Writes the photos attached to keys to \
+                                files in DIR")))
 
         .subcommand(SubCommand::with_name("keyserver")
                     .display_order(40)
@@ -287,6 +461,12 @@ pub fn build() -> App<'static, 'static> {
                                      .help("Label to use"))
                                 .arg(Arg::with_name("input").value_name("FILE")
                                      .help("Sets the input file to use")))
+                    .subcommand(SubCommand::with_name("import-ownertrust")
+                                .about("Sets the trust of bindings according \
+                                        to GnuPG's ownertrust")
+                                .arg(Arg::with_name("input").value_name("FILE")
+                                     .help("Reads the output of 'gpg \
+                                            --export-ownertrust' from FILE")))
                     .subcommand(SubCommand::with_name("export")
                                 .about("Exports a key")
                                 .arg(Arg::with_name("label").value_name("LABEL")
@@ -317,7 +497,28 @@ pub fn build() -> App<'static, 'static> {
                                 .about("Lists the keystore log")
                                 .arg(Arg::with_name("label")
                                      .value_name("LABEL")
-                                     .help("List messages related to this label"))))
+                                     .help("List messages related to this label")))
+                    .subcommand(SubCommand::with_name("refresh")
+                                .about("Schedules keys for an update from the network")
+                                .group(ArgGroup::with_name("keys")
+                                       .args(&["fingerprint", "all"])
+                                       .required(true))
+                                .arg(Arg::with_name("fingerprint").value_name("FINGERPRINT")
+                                     .help("Key to update"))
+                                .arg(Arg::with_name("all")
+                                     .long("all")
+                                     .help("Update all keys")))
+                    .subcommand(SubCommand::with_name("pause-refresh")
+                                .about("Pauses or resumes background key updates")
+                                .arg(Arg::with_name("duration").value_name("DURATION")
+                                     .help("Pauses updates for DURATION, given as \
+                                            'N[ymwd]' (default: until resumed)"))
+                                .arg(Arg::with_name("resume")
+                                     .long("resume")
+                                     .conflicts_with("duration")
+                                     .help("Resumes updates")))
+                    .subcommand(SubCommand::with_name("schedule")
+                                .about("Lists when keys are updated from the network")))
         .subcommand(SubCommand::with_name("list")
                     .about("Lists key stores and known keys")
                     .setting(AppSettings::SubcommandRequiredElseHelp)
@@ -386,6 +587,10 @@ pub fn build() -> App<'static, 'static> {
                         .arg(Arg::with_name("cannot-encrypt")
                              .long("cannot-encrypt")
                              .help("The key will not be able to encrypt data"))
+                        .arg(Arg::with_name("can-authenticate")
+                             .long("can-authenticate")
+                             .help("The key has an authentication-capable \
+                                    subkey, e.g., for use with SSH"))
                         .arg(Arg::with_name("export").value_name("OUTFILE")
                              .long("export")
                              .short("e")
@@ -397,7 +602,200 @@ pub fn build() -> App<'static, 'static> {
                              .required_if("export", "-")
                              .help("Sets the output file for the revocation \
                                     certificate. Default is <OUTFILE>.rev, \
-                                    mandatory if OUTFILE is '-'."))))
+                                    mandatory if OUTFILE is '-'.")))
+                .subcommand(
+                    SubCommand::with_name("export")
+                        .about("Exports keys, optionally without secret \
+                                key material")
+                        .arg(Arg::with_name("input").value_name("FILE")
+                             .help("Sets the input file to use"))
+                        .arg(Arg::with_name("output").value_name("FILE")
+                             .long("output")
+                             .short("o")
+                             .help("Sets the output file to use"))
+                        .arg(Arg::with_name("binary")
+                             .long("binary")
+                             .short("B")
+                             .help("Don't ASCII-armor encode the OpenPGP data"))
+                        .arg(Arg::with_name("public")
+                             .long("public")
+                             .help("Strips all secret key material")))
+                .subcommand(
+                    SubCommand::with_name("export-ssh")
+                        .about("Exports a key in OpenSSH's format")
+                        .arg(Arg::with_name("key").value_name("KEYID")
+                             .required(true)
+                             .help("Key ID or fingerprint of the key to \
+                                    export.  If the primary key is given, \
+                                    the first authentication-capable key \
+                                    is exported."))
+                        .arg(Arg::with_name("input").value_name("FILE")
+                             .long("input")
+                             .short("i")
+                             .help("Reads the TPK from FILE instead of \
+                                    the key pool"))
+                        .arg(Arg::with_name("output").value_name("FILE")
+                             .long("output")
+                             .short("o")
+                             .help("Sets the output file to use")))
+                .subcommand(
+                    SubCommand::with_name("export-raw")
+                        .about("Exports a key as X.509 SubjectPublicKeyInfo")
+                        .arg(Arg::with_name("key").value_name("KEYID")
+                             .required(true)
+                             .help("Key ID or fingerprint of the key to \
+                                    export"))
+                        .arg(Arg::with_name("input").value_name("FILE")
+                             .long("input")
+                             .short("i")
+                             .help("Reads the TPK from FILE instead of \
+                                    the key pool"))
+                        .arg(Arg::with_name("output").value_name("FILE")
+                             .long("output")
+                             .short("o")
+                             .help("Sets the output file to use"))
+                        .arg(Arg::with_name("binary")
+                             .long("binary")
+                             .short("B")
+                             .help("Emits DER instead of PEM")))
+                .subcommand(
+                    SubCommand::with_name("diff")
+                        .about("Shows what changed between two versions \
+                                of a key")
+                        .arg(Arg::with_name("old").value_name("OLD")
+                             .required(true)
+                             .help("Reads the old version from the file \
+                                    OLD, or, if OLD is a fingerprint, \
+                                    from the key pool"))
+                        .arg(Arg::with_name("new").value_name("NEW")
+                             .required(true)
+                             .help("Reads the new version from the file \
+                                    NEW, or, if NEW is a fingerprint, \
+                                    from the key pool"))
+                        .arg(Arg::with_name("output").value_name("FILE")
+                             .long("output")
+                             .short("o")
+                             .help("Sets the output file to use"))
+                        .arg(Arg::with_name("format").value_name("FORMAT")
+                             .long("format")
+                             .possible_values(&["human", "json"])
+                             .default_value("human")
+                             .help("Selects the output format")))
+                .subcommand(
+                    SubCommand::with_name("revoke")
+                        .about("Revokes keys in the key pool using \
+                                revocation certificates")
+                        .arg(Arg::with_name("import").value_name("FILE")
+                             .long("import")
+                             .required(true)
+                             .help("Reads the revocation certificates \
+                                    from FILE")))
+                .subcommand(
+                    SubCommand::with_name("attach-photo")
+                        .about("Attaches a photo to a key")
+                        .arg(Arg::with_name("fingerprint")
+                             .value_name("FINGERPRINT")
+                             .required(true)
+                             .help("Fingerprint of the key"))
+                        .arg(Arg::with_name("image").value_name("IMAGE")
+                             .required(true)
+                             .help("Reads the photo from the JPEG image \
+                                    IMAGE"))
+                        .arg(Arg::with_name("input").value_name("FILE")
+                             .long("input")
+                             .short("i")
+                             .help("Reads the key from FILE instead of \
+                                    the key pool"))
+                        .arg(Arg::with_name("output").value_name("FILE")
+                             .long("output")
+                             .short("o")
+                             .help("Sets the output file to use"))
+                        .arg(Arg::with_name("binary")
+                             .long("binary")
+                             .short("B")
+                             .help("Don't ASCII-armor encode the OpenPGP data"))))
+
+        .subcommand(
+            SubCommand::with_name("keyring")
+                .about("Manipulates keyrings")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("merge")
+                        .about("Joins keyrings, merging all copies of a key")
+                        .arg(Arg::with_name("input").value_name("FILE")
+                             .multiple(true)
+                             .help("Sets the input files to use"))
+                        .arg(Arg::with_name("output").value_name("FILE")
+                             .long("output")
+                             .short("o")
+                             .help("Sets the output file to use"))
+                        .arg(Arg::with_name("binary")
+                             .long("binary")
+                             .short("B")
+                             .help("Don't ASCII-armor encode the OpenPGP data")))
+                .subcommand(
+                    SubCommand::with_name("split")
+                        .about("Writes every key of a keyring to its own \
+                                file")
+                        .arg(Arg::with_name("input").value_name("FILE")
+                             .help("Sets the input file to use"))
+                        .arg(Arg::with_name("prefix").value_name("FILE")
+                             .long("prefix")
+                             .short("p")
+                             .help("Sets the prefix to use for output files \
+                                    (defaults to the input filename with a \
+                                    dash, or 'output')"))
+                        .arg(Arg::with_name("binary")
+                             .long("binary")
+                             .short("B")
+                             .help("Don't ASCII-armor encode the OpenPGP data")))
+                .subcommand(
+                    SubCommand::with_name("filter")
+                        .about("Keeps only the keys matching the given \
+                                email addresses or fingerprints")
+                        .group(ArgGroup::with_name("criteria")
+                               .args(&["email", "fingerprint"])
+                               .multiple(true)
+                               .required(true))
+                        .arg(Arg::with_name("email").value_name("ADDRESS")
+                             .long("email")
+                             .multiple(true)
+                             .number_of_values(1)
+                             .help("Keeps keys with a user ID with this \
+                                    email address (can be given multiple \
+                                    times)"))
+                        .arg(Arg::with_name("fingerprint")
+                             .value_name("FINGERPRINT")
+                             .long("fingerprint")
+                             .multiple(true)
+                             .number_of_values(1)
+                             .help("Keeps keys with this primary key or \
+                                    subkey fingerprint (can be given \
+                                    multiple times)"))
+                        .arg(Arg::with_name("input").value_name("FILE")
+                             .multiple(true)
+                             .help("Sets the input files to use"))
+                        .arg(Arg::with_name("output").value_name("FILE")
+                             .long("output")
+                             .short("o")
+                             .help("Sets the output file to use"))
+                        .arg(Arg::with_name("binary")
+                             .long("binary")
+                             .short("B")
+                             .help("Don't ASCII-armor encode the OpenPGP data")))
+                .subcommand(
+                    SubCommand::with_name("dedupe")
+                        .about("Merges all copies of a key in a keyring")
+                        .arg(Arg::with_name("input").value_name("FILE")
+                             .help("Sets the input file to use"))
+                        .arg(Arg::with_name("output").value_name("FILE")
+                             .long("output")
+                             .short("o")
+                             .help("Sets the output file to use"))
+                        .arg(Arg::with_name("binary")
+                             .long("binary")
+                             .short("B")
+                             .help("Don't ASCII-armor encode the OpenPGP data"))))
 
         .subcommand(SubCommand::with_name("packet")
                     .about("OpenPGP Packet manipulation")
@@ -422,7 +820,12 @@ pub fn build() -> App<'static, 'static> {
                                 .arg(Arg::with_name("hex")
                                      .long("hex")
                                      .short("x")
-                                     .help("Print a hexdump")))
+                                     .help("Print a hexdump"))
+                                .arg(Arg::with_name("format").value_name("FORMAT")
+                                     .long("format")
+                                     .possible_values(&["human", "json"])
+                                     .default_value("human")
+                                     .help("Selects the output format")))
                     .subcommand(SubCommand::with_name("split")
                                 .about("Splits a message into OpenPGP packets")
                                 .arg(Arg::with_name("input").value_name("FILE")
@@ -483,4 +886,18 @@ pub fn build() -> App<'static, 'static> {
                                     .help("The keyring file with the keys to add to the WKD."))
                     )
         )
+        .subcommand(SubCommand::with_name("generate-completions")
+                    .about("Generates shell completions")
+                    .arg(Arg::with_name("shell").value_name("SHELL")
+                         .required(true)
+                         .possible_values(&Shell::variants())
+                         .help("The shell to generate completions for"))
+                    .arg(Arg::with_name("directory").value_name("DIRECTORY")
+                         .help("Writes the completions to this directory \
+                                instead of stdout")))
+        .subcommand(SubCommand::with_name("generate-man")
+                    .about("Generates man pages")
+                    .arg(Arg::with_name("directory").value_name("DIRECTORY")
+                         .required(true)
+                         .help("Writes the man pages to this directory")))
 }
@@ -0,0 +1,67 @@
+extern crate assert_cli;
+use assert_cli::Assert;
+extern crate tempfile;
+use tempfile::TempDir;
+
+extern crate sequoia_openpgp as openpgp;
+use openpgp::TPK;
+use openpgp::constants::SignatureType;
+use openpgp::parse::Parse;
+
+fn p(filename: &str) -> String {
+    format!("../openpgp/tests/data/{}", filename)
+}
+
+const TESTY: &str = "3E8877C877274692975189F5D03F6F865226FE8B";
+const TESTY_UID: &str = "Testy McTestface <testy@example.org>";
+
+#[test]
+fn sq_certify() {
+    let tmp_dir = TempDir::new().unwrap();
+    let certified = tmp_dir.path().join("certified");
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "certify",
+              "--input", &p("keys/testy.pgp"),
+              "--secret-key-file",
+              &p("keys/dennis-simon-anton-private.pgp"),
+              "--level", "3",
+              "--expiry", "1y",
+              "--binary",
+              "--output", &certified.to_string_lossy(),
+              TESTY, TESTY_UID])
+        .stdin("y\n")
+        .unwrap();
+
+    let tpk = TPK::from_file(&certified).unwrap();
+    let uid = tpk.userids().nth(0).unwrap();
+    assert_eq!(uid.certifications().len(), 1);
+    let sig = &uid.certifications()[0];
+    assert_eq!(sig.sigtype(), SignatureType::PositiveCertificate);
+    assert!(sig.signature_expiration_time().is_some());
+}
+
+#[test]
+fn sq_certify_mismatch() {
+    let tmp_dir = TempDir::new().unwrap();
+    let certified = tmp_dir.path().join("certified");
+
+    // The user says the fingerprint does not match.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "certify",
+              "--input", &p("keys/testy.pgp"),
+              "--secret-key-file",
+              &p("keys/dennis-simon-anton-private.pgp"),
+              "--output", &certified.to_string_lossy(),
+              TESTY, TESTY_UID])
+        .stdin("n\n")
+        .fails()
+        .unwrap();
+    assert!(! certified.exists());
+}
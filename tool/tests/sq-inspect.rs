@@ -0,0 +1,51 @@
+extern crate assert_cli;
+use assert_cli::Assert;
+extern crate tempfile;
+use tempfile::TempDir;
+
+fn p(filename: &str) -> String {
+    format!("../openpgp/tests/data/{}", filename)
+}
+
+#[test]
+fn sq_inspect_certifications() {
+    let tmp_dir = TempDir::new().unwrap();
+
+    // Ivanka certified Bannon's key.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "inspect",
+              "--keyring", &p("keys/ivanka-private.gpg"),
+              &p("keys/bannon-ivanka-signs-base.gpg")])
+        .stdout().contains("Certified by: ")
+        .stdout().contains(" (good)")
+        .stdout().doesnt_contain(" (invalid)")
+        .unwrap();
+
+    // Without her key, the certification cannot be verified.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "inspect",
+              "--keyring", &p("keys/the-donald-private.gpg"),
+              &p("keys/bannon-ivanka-signs-base.gpg")])
+        .stdout().contains(" (unverifiable, missing key)")
+        .stdout().doesnt_contain(" (good)")
+        .unwrap();
+
+    // The Donald's certifications are not Ivanka's.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "inspect",
+              "--keyring", &p("keys/ivanka-private.gpg"),
+              "--keyring", &p("keys/the-donald-private.gpg"),
+              &p("keys/bannon-the-donald-signs-base.gpg")])
+        .stdout().contains(" (good)")
+        .stdout().doesnt_contain(" (unverifiable")
+        .unwrap();
+}
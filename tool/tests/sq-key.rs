@@ -0,0 +1,189 @@
+use std::fs;
+
+extern crate assert_cli;
+use assert_cli::Assert;
+extern crate tempfile;
+use tempfile::TempDir;
+
+extern crate sequoia_openpgp as openpgp;
+use openpgp::TPK;
+use openpgp::crypto::mpis::PublicKey;
+use openpgp::packet::{Key, KeyFlags};
+use openpgp::parse::Parse;
+use openpgp::serialize::SerializeInto;
+use openpgp::tpk::{CipherSuite, TPKBuilder};
+
+fn p(filename: &str) -> String {
+    format!("../openpgp/tests/data/{}", filename)
+}
+
+#[test]
+fn sq_key_export_public() {
+    let tmp_dir = TempDir::new().unwrap();
+    let public = tmp_dir.path().join("public");
+    let tsk = TPK::from_file(p("keys/testy-private.pgp")).unwrap();
+    assert!(tsk.is_tsk());
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "key", "export",
+              "--public",
+              "--output", &public.to_string_lossy(),
+              &p("keys/testy-private.pgp")])
+        .unwrap();
+    let tpk = TPK::from_file(&public).unwrap();
+    assert!(! tpk.is_tsk());
+    assert_eq!(tpk, TPK::from_bytes(&tsk.to_vec().unwrap()).unwrap());
+}
+
+#[test]
+fn sq_key_attach_photo() {
+    let tmp_dir = TempDir::new().unwrap();
+    let photo = tmp_dir.path().join("photo.jpg");
+    let with_photo = tmp_dir.path().join("with-photo");
+    let photos = tmp_dir.path().join("photos");
+    let tsk = TPK::from_file(p("keys/testy-private.pgp")).unwrap();
+
+    // Just the start of a JPEG image.
+    let image = b"\xff\xd8\xff\xe0\x00\x10JFIF\x00\x01\x01\x00\x00\x01";
+    fs::write(&photo, &image[..]).unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "key", "attach-photo",
+              "--input", &p("keys/testy-private.pgp"),
+              "--binary",
+              "--output", &with_photo.to_string_lossy(),
+              &tsk.fingerprint().to_hex(),
+              &photo.to_string_lossy()])
+        .unwrap();
+    let tpk = TPK::from_file(&with_photo).unwrap();
+    assert_eq!(tpk.user_attributes().count(), 1);
+    assert_eq!(tpk.userids().count(), tsk.userids().count());
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "inspect",
+              "--export-photos", &photos.to_string_lossy(),
+              &with_photo.to_string_lossy()])
+        .stdout().contains("UserAttribute: JPEG image")
+        .unwrap();
+    assert_eq!(
+        fs::read(photos.join(format!("{}-0.jpg", tsk.fingerprint().to_hex())))
+            .unwrap(),
+        &image[..]);
+
+    // Only JPEG images are accepted.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "key", "attach-photo",
+              "--input", &p("keys/testy-private.pgp"),
+              &tsk.fingerprint().to_hex(),
+              &p("keys/testy.pgp")])
+        .fails()
+        .unwrap();
+}
+
+/// Generates a TPK with an authentication and an encryption subkey,
+/// and stores it in `dir`.
+fn generate_auth_key(dir: &TempDir) -> (TPK, String) {
+    let (tpk, _) = TPKBuilder::new()
+        .set_cipher_suite(CipherSuite::Cv25519)
+        .add_userid("alice@example.org")
+        .add_authentication_subkey()
+        .add_encryption_subkey()
+        .generate().unwrap();
+    let file = dir.path().join("alice");
+    fs::write(&file, &tpk.to_vec().unwrap()).unwrap();
+    (tpk, file.to_string_lossy().into_owned())
+}
+
+/// Returns the subkey with the given capabilities.
+fn subkey<'a>(tpk: &'a TPK, flags: KeyFlags) -> &'a Key {
+    tpk.keys_valid().key_flags(flags).map(|(_, _, key)| key)
+        .find(|key| key.fingerprint() != tpk.fingerprint()).unwrap()
+}
+
+#[test]
+fn sq_key_export_ssh() {
+    let tmp_dir = TempDir::new().unwrap();
+    let (tpk, file) = generate_auth_key(&tmp_dir);
+    let auth = subkey(&tpk, KeyFlags::default().set_authenticate(true));
+    let enc = subkey(&tpk, KeyFlags::default()
+                     .set_encrypt_for_transport(true));
+
+    // Given the primary key, the authentication subkey is exported.
+    // So it is if it is named explicitly.
+    for handle in &[tpk.fingerprint(), auth.fingerprint()] {
+        Assert::cargo_binary("sq")
+            .with_args(
+                &["--home",
+                  &tmp_dir.path().to_string_lossy(),
+                  "key", "export-ssh",
+                  "--input", &file,
+                  &handle.to_hex()])
+            .stdout().contains("ssh-ed25519 ")
+            .stdout().contains(format!("openpgp:0x{}",
+                                       auth.keyid().to_hex()))
+            .unwrap();
+    }
+
+    // A subkey that is not authentication-capable is rejected.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "key", "export-ssh",
+              "--input", &file,
+              &enc.fingerprint().to_hex()])
+        .fails()
+        .unwrap();
+}
+
+#[test]
+fn sq_key_export_raw() {
+    let tmp_dir = TempDir::new().unwrap();
+    let (tpk, file) = generate_auth_key(&tmp_dir);
+    let auth = subkey(&tpk, KeyFlags::default().set_authenticate(true));
+    let spki = tmp_dir.path().join("spki");
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "key", "export-raw",
+              "--input", &file,
+              "--binary",
+              "--output", &spki.to_string_lossy(),
+              &auth.fingerprint().to_hex()])
+        .unwrap();
+
+    // An Ed25519 SubjectPublicKeyInfo is a fixed header followed by
+    // the native public key, i.e. the point without its prefix.
+    let q = match auth.mpis() {
+        PublicKey::EdDSA { q, .. } => &q.value[1..],
+        _ => unreachable!(),
+    };
+    let expected = [&b"\x30\x2a\x30\x05\x06\x03\x2b\x65\x70\x03\x21\x00"[..],
+                    q].concat();
+    assert_eq!(fs::read(&spki).unwrap(), expected);
+
+    // Without --binary, the key is PEM encoded.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "key", "export-raw",
+              "--input", &file,
+              &auth.fingerprint().to_hex()])
+        .stdout().contains("-----BEGIN PUBLIC KEY-----")
+        .unwrap();
+}
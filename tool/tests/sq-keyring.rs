@@ -0,0 +1,52 @@
+extern crate assert_cli;
+use assert_cli::Assert;
+extern crate tempfile;
+use tempfile::TempDir;
+
+extern crate sequoia_openpgp as openpgp;
+use openpgp::TPK;
+use openpgp::parse::Parse;
+use openpgp::tpk::TPKParser;
+
+fn p(filename: &str) -> String {
+    format!("../openpgp/tests/data/{}", filename)
+}
+
+#[test]
+fn sq_keyring() {
+    let tmp_dir = TempDir::new().unwrap();
+    let merged = tmp_dir.path().join("merged");
+    let filtered = tmp_dir.path().join("filtered");
+    let neal = TPK::from_file(p("keys/neal.pgp")).unwrap();
+    let testy = TPK::from_file(p("keys/testy.pgp")).unwrap();
+
+    // Merge, with a duplicate.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "keyring", "merge",
+              "--binary",
+              "--output", &merged.to_string_lossy(),
+              &p("keys/neal.pgp"),
+              &p("keys/testy.pgp"),
+              &p("keys/neal.pgp")])
+        .unwrap();
+    let tpks = TPKParser::from_file(&merged).unwrap()
+        .collect::<openpgp::Result<Vec<TPK>>>().unwrap();
+    assert_eq!(tpks, vec![neal.clone(), testy.clone()]);
+
+    // Filter.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "keyring", "filter",
+              "--fingerprint", &testy.fingerprint().to_hex(),
+              "--output", &filtered.to_string_lossy(),
+              &merged.to_string_lossy()])
+        .unwrap();
+    let tpks = TPKParser::from_file(&filtered).unwrap()
+        .collect::<openpgp::Result<Vec<TPK>>>().unwrap();
+    assert_eq!(tpks, vec![testy]);
+}
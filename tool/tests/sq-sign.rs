@@ -384,6 +384,99 @@ fn sq_sign_detached() {
         .unwrap();
 }
 
+#[test]
+fn sq_sign_dry_run() {
+    let tmp_dir = TempDir::new().unwrap();
+    let sig = tmp_dir.path().join("sig0");
+
+    // Explain, but don't sign.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "sign",
+              "--dry-run",
+              "--explain",
+              "--secret-key-file",
+              &p("keys/dennis-simon-anton-private.pgp"),
+              "--output",
+              &sig.to_string_lossy(),
+              &p("messages/a-cypherpunks-manifesto.txt")])
+        .stderr().contains("Signing key:")
+        .stderr().contains("Armor: PGP MESSAGE")
+        .stderr().contains("Estimated output size:")
+        .unwrap();
+    assert!(! sig.exists());
+}
+
+#[test]
+fn sq_sign_detached_multiple_files() {
+    let tmp_dir = TempDir::new().unwrap();
+    let a = tmp_dir.path().join("a");
+    let b = tmp_dir.path().join("b");
+    fs::write(&a, b"Hello").unwrap();
+    fs::write(&b, b"world").unwrap();
+
+    // Signing multiple files requires --detached.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "sign",
+              "--secret-key-file",
+              &p("keys/dennis-simon-anton-private.pgp"),
+              &a.to_string_lossy(),
+              &b.to_string_lossy()])
+        .fails()
+        .unwrap();
+
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "sign",
+              "--detached",
+              "--secret-key-file",
+              &p("keys/dennis-simon-anton-private.pgp"),
+              &a.to_string_lossy(),
+              &b.to_string_lossy()])
+        .unwrap();
+
+    for f in &[&a, &b] {
+        let sig = f.with_extension("asc");
+        let content = fs::read(&sig).unwrap();
+        assert!(&content[..].starts_with(b"-----BEGIN PGP SIGNATURE-----\n\n"));
+
+        Assert::cargo_binary("sq")
+            .with_args(
+                &["--home",
+                  &tmp_dir.path().to_string_lossy(),
+                  "verify",
+                  "--public-key-file",
+                  &p("keys/dennis-simon-anton.pgp"),
+                  "--detached",
+                  &sig.to_string_lossy(),
+                  &f.to_string_lossy()])
+            .unwrap();
+    }
+
+    // A signature over the wrong file does not verify.  This is
+    // signaled using exit status 1.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "verify",
+              "--public-key-file",
+              &p("keys/dennis-simon-anton.pgp"),
+              "--signatures", "1",
+              "--detached",
+              &a.with_extension("asc").to_string_lossy(),
+              &b.to_string_lossy()])
+        .fails_with(1)
+        .unwrap();
+}
+
 #[test]
 fn sq_sign_detached_append() {
     let tmp_dir = TempDir::new().unwrap();
@@ -809,3 +902,59 @@ fn sq_sign_notarize_a_notarization() {
               &sig0.to_string_lossy()])
         .unwrap();
 }
+
+#[test]
+fn sq_verify_structure_policy() {
+    let tmp_dir = TempDir::new().unwrap();
+    let sig = tmp_dir.path().join("sig0");
+
+    // Sign message.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "sign",
+              "--secret-key-file",
+              &p("keys/dennis-simon-anton-private.pgp"),
+              "--output",
+              &sig.to_string_lossy(),
+              &p("messages/a-cypherpunks-manifesto.txt")])
+        .unwrap();
+
+    // The message is signed, and has one layer.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "verify",
+              "--require-signed",
+              "--max-nesting", "1",
+              "--public-key-file",
+              &p("keys/dennis-simon-anton.pgp"),
+              &sig.to_string_lossy()])
+        .unwrap();
+
+    // Too many layers.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "verify",
+              "--max-nesting", "0",
+              "--public-key-file",
+              &p("keys/dennis-simon-anton.pgp"),
+              &sig.to_string_lossy()])
+        .fails_with(1)
+        .unwrap();
+
+    // Without the key, there is no valid signature.
+    Assert::cargo_binary("sq")
+        .with_args(
+            &["--home",
+              &tmp_dir.path().to_string_lossy(),
+              "verify",
+              "--require-signed",
+              &sig.to_string_lossy()])
+        .fails_with(1)
+        .unwrap();
+}